@@ -1,12 +1,88 @@
-use crossbeam::channel::{unbounded, Receiver};
+use std::fmt::Debug;
 
+pub mod imbalance;
+pub mod indicator;
+pub mod positioning;
+pub mod roll;
+pub mod spread;
+pub mod volatility;
+
+use volatility::VolatilityRegime;
+
+use crate::{candle::CandleData, symbol::SymbolId};
 
 #[derive(Debug, Clone, Default)]
 pub struct SymbolPrice {
-    pub symbol: String,
+    pub symbol: SymbolId,
     pub mark_price: f64,
     pub price_index: f64,
     pub time: u64,
     pub funding_rate: f64,
 }
 
+/// 盘口快照，bids/asks为（价格，数量），按优先级排序
+#[derive(Debug, Clone, Default)]
+pub struct DepthData {
+    pub symbol: String,
+    pub time: u64,
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+}
+
+/// 已收盘的k线
+#[derive(Debug, Clone, Default)]
+pub struct KlineData {
+    pub symbol: SymbolId,
+    pub candle: CandleData,
+}
+
+pub trait Algorithm: Debug + Send + Sync {
+    fn update(&self, price: &SymbolPrice) -> Option<SignalData>;
+    #[allow(unused_variables)]
+    fn update_depth(&self, depth: &DepthData) -> Option<SignalData> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SignalData {
+    Volatility {
+        symbol: String,
+        time: u64,
+        regime: VolatilityRegime,
+        volatility: f64,
+    },
+    Imbalance {
+        symbol: String,
+        time: u64,
+        /// 窗口内平均买卖量失衡度，范围[-1, 1]，正数表示买盘更强
+        imbalance: f64,
+        /// 窗口内microprice的变化率
+        drift: f64,
+    },
+    Roll {
+        symbol: String,
+        time: u64,
+        /// 突破方向，true为向上突破
+        is_bull: bool,
+        price: f64,
+    },
+    Spread {
+        symbol: String,
+        time: u64,
+        /// (另一交易所标记价格 - 币安标记价格) / 币安标记价格
+        spread: f64,
+        /// 价差绝对值是否超出阈值
+        wide: bool,
+    },
+    /// 轮询到新的持仓量和大户多空比
+    Positioning {
+        symbol: String,
+        /// 数据的统计时间
+        time: u64,
+        open_interest: f64,
+        /// 持仓量在统计窗口内的变化比例，数据不足时为None
+        open_interest_change: Option<f64>,
+        long_short_ratio: Option<f64>,
+    },
+}
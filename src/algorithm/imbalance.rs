@@ -0,0 +1,126 @@
+use std::collections::VecDeque;
+
+use dashmap::DashMap;
+
+use super::{Algorithm, DepthData, SignalData, SymbolPrice};
+
+#[derive(Debug, Clone)]
+pub struct ImbalanceConfig {
+    /// 计算的盘口档数
+    pub levels: usize,
+    /// 滚动窗口时长（ms）
+    pub window: u64,
+    /// 确认开仓所需的最小失衡度
+    pub min_imbalance: f64,
+}
+
+impl Default for ImbalanceConfig {
+    fn default() -> Self {
+        Self {
+            levels: 10,
+            window: 5_000,
+            min_imbalance: 0.2,
+        }
+    }
+}
+
+/// 单个盘口快照的（时间，失衡度，microprice）
+#[derive(Debug, Clone, Copy)]
+struct DepthPoint {
+    time: u64,
+    imbalance: f64,
+    microprice: f64,
+}
+
+impl DepthPoint {
+    fn new(depth: &DepthData, levels: usize) -> Option<Self> {
+        let (best_bid, bid_qty) = *depth.bids.first()?;
+        let (best_ask, ask_qty) = *depth.asks.first()?;
+        if bid_qty + ask_qty <= 0. {
+            return None;
+        }
+        let bid_volume: f64 = depth.bids.iter().take(levels).map(|b| b.1).sum();
+        let ask_volume: f64 = depth.asks.iter().take(levels).map(|a| a.1).sum();
+        let imbalance = (bid_volume - ask_volume) / (bid_volume + ask_volume);
+        let microprice = (best_bid * ask_qty + best_ask * bid_qty) / (bid_qty + ask_qty);
+        Some(Self {
+            time: depth.time,
+            imbalance,
+            microprice,
+        })
+    }
+}
+
+/// 根据盘口买卖量失衡度和microprice漂移产生开仓确认信号
+#[derive(Debug, Default)]
+pub struct ImbalanceAlgo {
+    config: ImbalanceConfig,
+    windows: DashMap<String, VecDeque<DepthPoint>>,
+    /// 最新的（失衡度，漂移）
+    latest: DashMap<String, (f64, f64)>,
+}
+
+impl ImbalanceAlgo {
+    pub fn new(config: ImbalanceConfig) -> Self {
+        Self {
+            config,
+            windows: DashMap::new(),
+            latest: DashMap::new(),
+        }
+    }
+    /// 盘口是否支持该方向开仓
+    pub fn confirms(&self, symbol: &str, is_bull: bool) -> bool {
+        let Some((imbalance, drift)) = self.latest.get(symbol).map(|l| *l) else {
+            return false;
+        };
+        if is_bull {
+            imbalance >= self.config.min_imbalance && drift >= 0.
+        } else {
+            imbalance <= -self.config.min_imbalance && drift <= 0.
+        }
+    }
+}
+
+impl Algorithm for ImbalanceAlgo {
+    fn update(&self, _price: &SymbolPrice) -> Option<SignalData> {
+        None
+    }
+    fn update_depth(&self, depth: &DepthData) -> Option<SignalData> {
+        let point = DepthPoint::new(depth, self.config.levels)?;
+        let mut window = self.windows.entry(depth.symbol.clone()).or_default();
+        window.push_back(point);
+        while let Some(front) = window.front() {
+            if front.time + self.config.window >= point.time {
+                break;
+            }
+            window.pop_front();
+        }
+        let imbalance = window.iter().map(|p| p.imbalance).sum::<f64>() / window.len() as f64;
+        let drift = point.microprice / window.front().unwrap().microprice - 1.;
+        self.latest.insert(depth.symbol.clone(), (imbalance, drift));
+        Some(SignalData::Imbalance {
+            symbol: depth.symbol.clone(),
+            time: depth.time,
+            imbalance,
+            drift,
+        })
+    }
+}
+
+#[test]
+fn imbalance_test() {
+    let algo = ImbalanceAlgo::default();
+    let depth = |time: u64, bid_qty: f64, ask_price: f64| DepthData {
+        symbol: "BTCUSDT".to_string(),
+        time,
+        bids: vec![(100., bid_qty), (99.9, 5.)],
+        asks: vec![(ask_price, 1.), (ask_price + 0.1, 1.)],
+    };
+    algo.update_depth(&depth(0, 1., 100.1));
+    let signal = algo.update_depth(&depth(1000, 10., 100.2));
+    assert!(
+        matches!(signal, Some(SignalData::Imbalance { imbalance, drift, .. }) if imbalance > 0.5 && drift > 0.)
+    );
+    assert!(algo.confirms("BTCUSDT", true));
+    assert!(!algo.confirms("BTCUSDT", false));
+}
@@ -0,0 +1,153 @@
+//! 基于切片的技术指标，输出与输入等长，数据不足的位置为NaN
+
+use std::collections::VecDeque;
+
+/// 简单移动平均
+pub fn sma(values: &[f64], window: usize) -> Vec<f64> {
+    let window = window.max(1);
+    let mut out = vec![f64::NAN; values.len()];
+    let mut sum = 0.;
+    for (i, v) in values.iter().enumerate() {
+        sum += v;
+        if i >= window {
+            sum -= values[i - window];
+        }
+        if i + 1 >= window {
+            out[i] = sum / window as f64;
+        }
+    }
+    out
+}
+
+/// 指数移动平均，alpha = 2 / (period + 1)，以前period个值的均值为初值
+pub fn ema(values: &[f64], period: usize) -> Vec<f64> {
+    smooth(values, period, 2. / (period.max(1) as f64 + 1.))
+}
+
+/// Wilder平滑（RMA），alpha = 1 / period
+pub fn rma(values: &[f64], period: usize) -> Vec<f64> {
+    smooth(values, period, 1. / period.max(1) as f64)
+}
+
+fn smooth(values: &[f64], period: usize, alpha: f64) -> Vec<f64> {
+    let period = period.max(1);
+    let mut out = vec![f64::NAN; values.len()];
+    if values.len() < period {
+        return out;
+    }
+    let mut last = values[..period].iter().sum::<f64>() / period as f64;
+    out[period - 1] = last;
+    for i in period..values.len() {
+        last += alpha * (values[i] - last);
+        out[i] = last;
+    }
+    out
+}
+
+/// 滚动最大值，单调队列实现
+pub fn rolling_max(values: &[f64], window: usize) -> Vec<f64> {
+    rolling_extreme(values, window, |a, b| a >= b)
+}
+
+/// 滚动最小值
+pub fn rolling_min(values: &[f64], window: usize) -> Vec<f64> {
+    rolling_extreme(values, window, |a, b| a <= b)
+}
+
+fn rolling_extreme(values: &[f64], window: usize, keep: fn(f64, f64) -> bool) -> Vec<f64> {
+    let window = window.max(1);
+    let mut out = vec![f64::NAN; values.len()];
+    let mut deque: VecDeque<usize> = VecDeque::new();
+    for (i, &v) in values.iter().enumerate() {
+        while deque.back().is_some_and(|&j| !keep(values[j], v)) {
+            deque.pop_back();
+        }
+        deque.push_back(i);
+        if deque.front().is_some_and(|&j| j + window <= i) {
+            deque.pop_front();
+        }
+        if i + 1 >= window {
+            out[i] = values[deque[0]];
+        }
+    }
+    out
+}
+
+/// 滚动标准差（总体）
+pub fn rolling_std(values: &[f64], window: usize) -> Vec<f64> {
+    let window = window.max(1);
+    let mut out = vec![f64::NAN; values.len()];
+    let (mut sum, mut sum_sq) = (0., 0.);
+    for (i, v) in values.iter().enumerate() {
+        sum += v;
+        sum_sq += v * v;
+        if i >= window {
+            let old = values[i - window];
+            sum -= old;
+            sum_sq -= old * old;
+        }
+        if i + 1 >= window {
+            let mean = sum / window as f64;
+            out[i] = (sum_sq / window as f64 - mean * mean).max(0.).sqrt();
+        }
+    }
+    out
+}
+
+/// 对数收益率，首个位置为NaN
+pub fn log_returns(close: &[f64]) -> Vec<f64> {
+    let mut out = vec![f64::NAN; close.len()];
+    for i in 1..close.len() {
+        out[i] = (close[i] / close[i - 1]).ln();
+    }
+    out
+}
+
+/// 真实波幅，首根k线为最高价 - 最低价
+pub fn true_range(high: &[f64], low: &[f64], close: &[f64]) -> Vec<f64> {
+    (0..close.len())
+        .map(|i| {
+            let range = high[i] - low[i];
+            match i.checked_sub(1).map(|j| close[j]) {
+                Some(prev) => range.max((high[i] - prev).abs()).max((low[i] - prev).abs()),
+                None => range,
+            }
+        })
+        .collect()
+}
+
+/// 平均真实波幅（Wilder平滑）
+pub fn atr(high: &[f64], low: &[f64], close: &[f64], period: usize) -> Vec<f64> {
+    rma(&true_range(high, low, close), period)
+}
+
+#[test]
+fn indicator_test() {
+    let values = [1., 2., 3., 4., 5., 4., 3.];
+    let close_to = |a: &[f64], b: &[f64]| {
+        a.len() == b.len()
+            && a.iter()
+                .zip(b)
+                .all(|(x, y)| (x.is_nan() && y.is_nan()) || (x - y).abs() < 1e-9)
+    };
+    let nan = f64::NAN;
+    assert!(close_to(
+        &sma(&values, 3),
+        &[nan, nan, 2., 3., 4., 13. / 3., 4.]
+    ));
+    assert!(close_to(&ema(&values, 3), &[nan, nan, 2., 3., 4., 4., 3.5]));
+    assert!(close_to(
+        &rolling_max(&values, 3),
+        &[nan, nan, 3., 4., 5., 5., 5.]
+    ));
+    assert!(close_to(
+        &rolling_min(&values, 3),
+        &[nan, nan, 1., 2., 3., 4., 3.]
+    ));
+    let std = rolling_std(&values, 2);
+    assert!((std[1] - 0.5).abs() < 1e-9);
+    assert!((log_returns(&values)[1] - 2f64.ln()).abs() < 1e-9);
+    let tr = true_range(&[10., 12.], &[9., 11.], &[9.5, 11.5]);
+    assert_eq!(tr, vec![1., 2.5]);
+    assert!((atr(&[10., 12.], &[9., 11.], &[9.5, 11.5], 1)[1] - 2.5).abs() < 1e-9);
+}
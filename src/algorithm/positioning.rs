@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use time::OffsetDateTime;
+
+use super::{Algorithm, SignalData, SymbolPrice};
+use crate::{binance_futures::positioning::PositioningFeed, symbol::SymbolId};
+
+/// 由价格流驱动，轮询到新的持仓量数据时产生信号
+#[derive(Debug)]
+pub struct PositioningMonitor {
+    feed: Arc<PositioningFeed>,
+    /// 持仓量变化的统计窗口
+    window: time::Duration,
+    /// 各币种已产生信号的最新数据时间
+    emitted: DashMap<SymbolId, OffsetDateTime>,
+}
+
+impl PositioningMonitor {
+    pub fn new(feed: Arc<PositioningFeed>, window: time::Duration) -> Self {
+        Self {
+            feed,
+            window,
+            emitted: DashMap::new(),
+        }
+    }
+}
+
+impl Algorithm for PositioningMonitor {
+    fn update(&self, price: &SymbolPrice) -> Option<SignalData> {
+        let latest = self.feed.latest(&price.symbol)?;
+        if self
+            .emitted
+            .get(&price.symbol)
+            .is_some_and(|t| *t >= latest.time)
+        {
+            return None;
+        }
+        self.emitted.insert(price.symbol, latest.time);
+        Some(SignalData::Positioning {
+            symbol: price.symbol.to_string(),
+            time: (latest.time.unix_timestamp_nanos() / 1_000_000) as u64,
+            open_interest: latest.open_interest,
+            open_interest_change: self.feed.open_interest_change(
+                &price.symbol,
+                latest.time,
+                self.window,
+            ),
+            long_short_ratio: latest.long_short_ratio,
+        })
+    }
+}
+
+#[test]
+fn positioning_monitor_test() {
+    use crate::backtest::positioning::PositioningData;
+
+    let feed = Arc::new(PositioningFeed::new(10));
+    let t = |m: i64| OffsetDateTime::from_unix_timestamp(m * 300).unwrap();
+    let data = |m: i64, oi: f64| PositioningData {
+        time: t(m),
+        open_interest: oi,
+        open_interest_value: oi * 100.,
+        long_short_ratio: Some(1.1),
+    };
+    feed.push("BTCUSDT", [data(0, 1000.), data(1, 1100.)]);
+    let monitor = PositioningMonitor::new(feed.clone(), time::Duration::minutes(5));
+    let price = SymbolPrice {
+        symbol: "BTCUSDT".into(),
+        ..Default::default()
+    };
+    let Some(SignalData::Positioning {
+        open_interest_change: Some(change),
+        ..
+    }) = monitor.update(&price)
+    else {
+        panic!("expected positioning signal");
+    };
+    assert!((change - 0.1).abs() < 1e-9);
+    // 没有新数据时不重复产生信号
+    assert!(monitor.update(&price).is_none());
+    feed.push("BTCUSDT", [data(2, 1100.)]);
+    assert!(monitor.update(&price).is_some());
+}
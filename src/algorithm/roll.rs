@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+
+use dashmap::DashMap;
+
+use super::{Algorithm, SignalData, SymbolPrice};
+use crate::symbol::SymbolId;
+
+#[derive(Debug, Clone)]
+pub struct RollAlgoConfig {
+    /// 回看窗口长度（ms）
+    pub window: u64,
+    /// 突破时价格距窗口另一端极值的最小幅度
+    pub breakout: f64,
+    /// 同一币种两次信号的最小间隔（ms）
+    pub cooldown: u64,
+}
+
+impl Default for RollAlgoConfig {
+    fn default() -> Self {
+        Self {
+            window: 4 * 3_600_000,
+            breakout: 0.03,
+            cooldown: 3_600_000,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RollState {
+    /// 第一个tick的时间，窗口未填满前不产生信号
+    start: Option<u64>,
+    /// 窗口内价格单调递减的（时间，价格），队首为最大值
+    highs: VecDeque<(u64, f64)>,
+    /// 窗口内价格单调递增的（时间，价格），队首为最小值
+    lows: VecDeque<(u64, f64)>,
+    last_signal: Option<u64>,
+}
+
+impl RollState {
+    fn expire(&mut self, time: u64, window: u64) {
+        let start = time.saturating_sub(window);
+        while self.highs.front().is_some_and(|(t, _)| *t < start) {
+            self.highs.pop_front();
+        }
+        while self.lows.front().is_some_and(|(t, _)| *t < start) {
+            self.lows.pop_front();
+        }
+    }
+    fn push(&mut self, time: u64, price: f64) {
+        while self.highs.back().is_some_and(|(_, p)| *p <= price) {
+            self.highs.pop_back();
+        }
+        self.highs.push_back((time, price));
+        while self.lows.back().is_some_and(|(_, p)| *p >= price) {
+            self.lows.pop_back();
+        }
+        self.lows.push_back((time, price));
+    }
+}
+
+/// 滚仓入场检测：mark price突破回看窗口的最高（低）价，且距窗口最低（高）价的幅度足够大时产生信号
+#[derive(Debug, Default)]
+pub struct RollAlgo {
+    config: RollAlgoConfig,
+    states: DashMap<SymbolId, RollState>,
+}
+
+impl RollAlgo {
+    pub fn new(config: RollAlgoConfig) -> Self {
+        Self {
+            config,
+            states: DashMap::new(),
+        }
+    }
+}
+
+impl Algorithm for RollAlgo {
+    fn update(&self, price: &SymbolPrice) -> Option<SignalData> {
+        if price.mark_price <= 0. {
+            return None;
+        }
+        let mut state = self.states.entry(price.symbol).or_default();
+        let start = *state.start.get_or_insert(price.time);
+        state.expire(price.time, self.config.window);
+        let extremes = state
+            .highs
+            .front()
+            .zip(state.lows.front())
+            .map(|((_, high), (_, low))| (*high, *low));
+        state.push(price.time, price.mark_price);
+        let (high, low) = extremes?;
+        if price.time < start + self.config.window
+            || state
+                .last_signal
+                .is_some_and(|t| price.time < t + self.config.cooldown)
+        {
+            return None;
+        }
+        let is_bull = if price.mark_price > high
+            && price.mark_price / low - 1. >= self.config.breakout
+        {
+            true
+        } else if price.mark_price < low && 1. - price.mark_price / high >= self.config.breakout {
+            false
+        } else {
+            return None;
+        };
+        state.last_signal = Some(price.time);
+        Some(SignalData::Roll {
+            symbol: price.symbol.to_string(),
+            time: price.time,
+            is_bull,
+            price: price.mark_price,
+        })
+    }
+}
+
+#[test]
+fn roll_algo_test() {
+    let algo = RollAlgo::new(RollAlgoConfig {
+        window: 10_000,
+        breakout: 0.03,
+        cooldown: 5_000,
+    });
+    let tick = |time: u64, mark_price: f64| SymbolPrice {
+        symbol: "BTCUSDT".into(),
+        mark_price,
+        time,
+        ..Default::default()
+    };
+    // 窗口未填满
+    assert!(algo.update(&tick(0, 100.)).is_none());
+    assert!(algo.update(&tick(5_000, 110.)).is_none());
+    for i in 6..=15 {
+        assert!(algo.update(&tick(i * 1000, 101.)).is_none());
+    }
+    // 突破窗口高点但幅度不足
+    assert!(algo.update(&tick(16_000, 102.)).is_none());
+    assert!(matches!(
+        algo.update(&tick(17_000, 106.)),
+        Some(SignalData::Roll { is_bull: true, .. })
+    ));
+    // 冷却中
+    assert!(algo.update(&tick(18_000, 107.)).is_none());
+    assert!(matches!(
+        algo.update(&tick(23_000, 97.)),
+        Some(SignalData::Roll { is_bull: false, .. })
+    ));
+}
@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+
+use dashmap::DashMap;
+
+use super::{Algorithm, SignalData, SymbolPrice};
+use crate::{binance_futures::SymbolPrices, error::DataError, symbol::SymbolId};
+
+#[derive(Debug, Clone)]
+pub struct SpreadConfig {
+    /// 触发信号的价差比例（绝对值）
+    pub threshold: f64,
+    /// 另一交易所价格超过该时长（ms）未更新则忽略
+    pub max_age: u64,
+    /// 每个币种保留的历史记录数
+    pub history_len: usize,
+}
+
+impl Default for SpreadConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 0.002,
+            max_age: 5_000,
+            history_len: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpreadPoint {
+    pub time: u64,
+    /// 币安标记价格
+    pub base: f64,
+    /// 另一交易所标记价格
+    pub other: f64,
+}
+
+impl SpreadPoint {
+    /// (other - base) / base
+    pub fn spread(&self) -> f64 {
+        (self.other - self.base) / self.base
+    }
+}
+
+/// 跨交易所标记价格价差监控，以币安价格流驱动，价差超出或回到阈值内时产生信号
+#[derive(Debug)]
+pub struct SpreadMonitor {
+    config: SpreadConfig,
+    /// 另一交易所的最新价格（币安格式币种）
+    other: SymbolPrices,
+    history: DashMap<SymbolId, VecDeque<SpreadPoint>>,
+    /// 当前价差是否超出阈值
+    wide: DashMap<SymbolId, bool>,
+}
+
+impl SpreadMonitor {
+    pub fn new(config: SpreadConfig, other: SymbolPrices) -> Self {
+        Self {
+            config,
+            other,
+            history: DashMap::new(),
+            wide: DashMap::new(),
+        }
+    }
+    pub fn history(&self, symbol: &str) -> Vec<SpreadPoint> {
+        SymbolId::get(symbol)
+            .and_then(|id| self.history.get(&id))
+            .map(|h| h.iter().copied().collect())
+            .unwrap_or_default()
+    }
+    pub fn latest(&self, symbol: &str) -> Option<SpreadPoint> {
+        self.history.get(&SymbolId::get(symbol)?)?.back().copied()
+    }
+    /// time,symbol,base,other
+    pub fn write_to_csv(&self, path: &str) -> Result<(), DataError> {
+        let mut csv = csv::Writer::from_path(path)?;
+        csv.write_record(["time", "symbol", "base", "other"])?;
+        for h in self.history.iter() {
+            for p in h.value() {
+                csv.write_record([
+                    p.time.to_string(),
+                    h.key().to_string(),
+                    p.base.to_string(),
+                    p.other.to_string(),
+                ])?;
+            }
+        }
+        csv.flush()?;
+        Ok(())
+    }
+    /// 读取write_to_csv写出的历史，按时间排序
+    pub fn read_from_csv(path: &str) -> Result<Vec<(String, SpreadPoint)>, DataError> {
+        let mut csv = csv::Reader::from_path(path)?;
+        let mut points = vec![];
+        for d in csv.records() {
+            let d = d?;
+            let field = |i: usize, name: &str| {
+                d.get(i)
+                    .ok_or_else(|| DataError::parse(name, "missing field"))
+                    .map(|s| s.to_string())
+            };
+            points.push((
+                field(1, "symbol")?,
+                SpreadPoint {
+                    time: field(0, "time")?
+                        .parse()
+                        .map_err(|e| DataError::parse("time", e))?,
+                    base: field(2, "base")?
+                        .parse()
+                        .map_err(|e| DataError::parse("base", e))?,
+                    other: field(3, "other")?
+                        .parse()
+                        .map_err(|e| DataError::parse("other", e))?,
+                },
+            ));
+        }
+        points.sort_by_key(|(_, p)| p.time);
+        Ok(points)
+    }
+}
+
+impl Algorithm for SpreadMonitor {
+    fn update(&self, price: &SymbolPrice) -> Option<SignalData> {
+        let other = self.other.get(&price.symbol)?.clone();
+        if price.mark_price <= 0.
+            || other.mark_price <= 0.
+            || price.time.abs_diff(other.time) > self.config.max_age
+        {
+            return None;
+        }
+        let point = SpreadPoint {
+            time: price.time,
+            base: price.mark_price,
+            other: other.mark_price,
+        };
+        {
+            let mut history = self.history.entry(price.symbol).or_default();
+            if history.len() >= self.config.history_len {
+                history.pop_front();
+            }
+            history.push_back(point);
+        }
+        let spread = point.spread();
+        let wide = spread.abs() >= self.config.threshold;
+        let mut last = self.wide.entry(price.symbol).or_default();
+        if *last == wide {
+            return None;
+        }
+        *last = wide;
+        Some(SignalData::Spread {
+            symbol: price.symbol.to_string(),
+            time: price.time,
+            spread,
+            wide,
+        })
+    }
+}
+
+#[test]
+fn spread_monitor_test() {
+    use std::sync::Arc;
+
+    let other: SymbolPrices = Arc::new(DashMap::new());
+    let monitor = SpreadMonitor::new(SpreadConfig::default(), other.clone());
+    let price = |mark_price: f64, time: u64| SymbolPrice {
+        symbol: "BTCUSDT".into(),
+        mark_price,
+        time,
+        ..Default::default()
+    };
+    assert!(monitor.update(&price(100., 0)).is_none());
+    other.insert("BTCUSDT".into(), price(100.1, 0));
+    assert!(monitor.update(&price(100., 1000)).is_none());
+    other.insert("BTCUSDT".into(), price(100.5, 2000));
+    let Some(SignalData::Spread { spread, wide, .. }) = monitor.update(&price(100., 2000)) else {
+        panic!()
+    };
+    assert!(wide && (spread - 0.005).abs() < 1e-9);
+    assert!(monitor.update(&price(100., 2500)).is_none());
+    // 另一交易所价格过期
+    assert!(monitor.update(&price(100., 9000)).is_none());
+    assert_eq!(monitor.history("BTCUSDT").len(), 3);
+
+    let path = std::env::temp_dir().join("hurribot_spread_test.csv");
+    monitor.write_to_csv(path.to_str().unwrap()).unwrap();
+    let read = SpreadMonitor::read_from_csv(path.to_str().unwrap()).unwrap();
+    assert_eq!(read.len(), 3);
+    assert_eq!(read[2].1, monitor.latest("BTCUSDT").unwrap());
+}
@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+
+use dashmap::DashMap;
+
+use super::{Algorithm, SignalData, SymbolPrice};
+use crate::symbol::SymbolId;
+
+/// 波动率区间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VolatilityRegime {
+    /// 低波动（震荡），不宜开仓
+    Low,
+    #[default]
+    Normal,
+    /// 极端波动，应降低杠杆
+    Extreme,
+}
+
+impl VolatilityRegime {
+    pub fn allows_entry(&self) -> bool {
+        *self != VolatilityRegime::Low
+    }
+    /// 杠杆缩放系数
+    pub fn leverage_factor(&self) -> f64 {
+        match self {
+            VolatilityRegime::Extreme => 0.5,
+            _ => 1.,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VolatilityConfig {
+    /// 滚动窗口长度（tick数）
+    pub window: usize,
+    /// 小时波动率低于该值视为低波动
+    pub low: f64,
+    /// 小时波动率高于该值视为极端波动
+    pub extreme: f64,
+}
+
+impl Default for VolatilityConfig {
+    fn default() -> Self {
+        Self {
+            window: 600,
+            low: 0.003,
+            extreme: 0.02,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct VolatilityState {
+    /// 上一个tick的（时间，价格）
+    last: Option<(u64, f64)>,
+    /// 窗口内的（时间间隔ms，对数收益率平方）
+    returns: VecDeque<(u64, f64)>,
+    sum_sq: f64,
+    sum_dt: u64,
+    volatility: Option<f64>,
+    regime: VolatilityRegime,
+}
+
+impl VolatilityState {
+    fn push(&mut self, time: u64, price: f64, window: usize) {
+        if let Some((last_time, last_price)) = self.last {
+            if time <= last_time || last_price <= 0. {
+                return;
+            }
+            let r = (price / last_price).ln();
+            let dt = time - last_time;
+            self.returns.push_back((dt, r * r));
+            self.sum_sq += r * r;
+            self.sum_dt += dt;
+            while self.returns.len() > window {
+                let (dt, sq) = self.returns.pop_front().unwrap();
+                self.sum_sq -= sq;
+                self.sum_dt -= dt;
+            }
+        }
+        self.last = Some((time, price));
+    }
+    /// 按时间归一化后的小时波动率
+    fn hourly_volatility(&self) -> f64 {
+        (self.sum_sq.max(0.) / self.sum_dt as f64 * 3_600_000.).sqrt()
+    }
+}
+
+/// 根据mark price的滚动已实现波动率，将每个币种划分为低/正常/极端波动区间
+#[derive(Debug, Default)]
+pub struct VolatilityAlgo {
+    config: VolatilityConfig,
+    states: DashMap<SymbolId, VolatilityState>,
+}
+
+impl VolatilityAlgo {
+    pub fn new(config: VolatilityConfig) -> Self {
+        Self {
+            config,
+            states: DashMap::new(),
+        }
+    }
+    pub fn regime(&self, symbol: &str) -> VolatilityRegime {
+        SymbolId::get(symbol)
+            .and_then(|id| self.states.get(&id))
+            .map(|s| s.regime)
+            .unwrap_or_default()
+    }
+    pub fn volatility(&self, symbol: &str) -> Option<f64> {
+        self.states.get(&SymbolId::get(symbol)?)?.volatility
+    }
+}
+
+impl Algorithm for VolatilityAlgo {
+    fn update(&self, price: &SymbolPrice) -> Option<SignalData> {
+        let mut state = self.states.entry(price.symbol).or_default();
+        state.push(price.time, price.mark_price, self.config.window);
+        if state.returns.len() < self.config.window {
+            return None;
+        }
+        let volatility = state.hourly_volatility();
+        state.volatility = Some(volatility);
+        let regime = if volatility < self.config.low {
+            VolatilityRegime::Low
+        } else if volatility > self.config.extreme {
+            VolatilityRegime::Extreme
+        } else {
+            VolatilityRegime::Normal
+        };
+        if regime == state.regime {
+            return None;
+        }
+        state.regime = regime;
+        Some(SignalData::Volatility {
+            symbol: price.symbol.to_string(),
+            time: price.time,
+            regime,
+            volatility,
+        })
+    }
+}
+
+#[test]
+fn volatility_regime_test() {
+    let algo = VolatilityAlgo::new(VolatilityConfig {
+        window: 10,
+        ..Default::default()
+    });
+    let tick = |time: u64, mark_price: f64| SymbolPrice {
+        symbol: "BTCUSDT".into(),
+        mark_price,
+        price_index: mark_price,
+        time,
+        funding_rate: 0.,
+    };
+    for i in 0..=10 {
+        algo.update(&tick(i * 1000, 100.));
+    }
+    assert_eq!(algo.regime("BTCUSDT"), VolatilityRegime::Low);
+    let mut signal = None;
+    for i in 11..=21 {
+        let price = if i % 2 == 0 { 101. } else { 99. };
+        signal = algo.update(&tick(i * 1000, price)).or(signal);
+    }
+    assert_eq!(algo.regime("BTCUSDT"), VolatilityRegime::Extreme);
+    assert!(matches!(
+        signal,
+        Some(SignalData::Volatility {
+            regime: VolatilityRegime::Extreme,
+            ..
+        })
+    ));
+}
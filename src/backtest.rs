@@ -1,3 +1,28 @@
+pub mod basket;
+pub mod batch;
+pub mod benchmark;
+pub mod candle_cache;
 pub mod candle_chart;
+pub mod candle_download;
+pub mod candle_series;
+pub mod candle_store;
+pub mod carry;
+pub mod compare;
 pub mod contract;
+pub mod depth_chart;
+pub mod engine;
+pub mod execution;
+pub mod funding;
+pub mod journal;
+pub mod merged;
+pub mod optimizer;
+pub mod position_book;
+pub mod positioning;
+pub mod price_path;
+pub mod provenance;
+pub mod replay;
+pub mod report;
+pub mod rotation;
+pub mod scenario;
 pub mod strategy;
+pub mod tick_chart;
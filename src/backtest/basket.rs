@@ -0,0 +1,223 @@
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+
+use super::{
+    candle_chart::CandleData,
+    strategy::{geo_strategy::GeoStrategy, CapitalExhausted, Strategy, TradeRecord},
+};
+
+/// 单个币种的回测结果
+#[derive(Debug, Clone)]
+pub struct BasketLeg {
+    pub symbol: String,
+    /// 从资金池补充的资金
+    pub cost: f64,
+    /// 平仓后的资金
+    pub value: f64,
+    pub open_count: i64,
+    pub liquidations: usize,
+    /// 资金池不足，此后该币种暂停
+    pub exhausted: Option<CapitalExhausted>,
+    pub trades: Vec<TradeRecord>,
+}
+
+impl BasketLeg {
+    pub fn return_rate(&self) -> f64 {
+        if self.cost > 0. {
+            self.value / self.cost
+        } else {
+            f64::NAN
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BasketResult {
+    pub initial_pool: f64,
+    /// 资金池剩余
+    pub remaining_pool: f64,
+    pub legs: Vec<BasketLeg>,
+}
+
+impl BasketResult {
+    pub fn total_cost(&self) -> f64 {
+        self.legs.iter().map(|l| l.cost).sum()
+    }
+    pub fn total_value(&self) -> f64 {
+        self.legs.iter().map(|l| l.value).sum()
+    }
+    pub fn return_rate(&self) -> f64 {
+        let cost = self.total_cost();
+        if cost > 0. {
+            self.total_value() / cost
+        } else {
+            f64::NAN
+        }
+    }
+    /// 出现过资金池不足的币种
+    pub fn starved(&self) -> Vec<&str> {
+        self.legs
+            .iter()
+            .filter(|l| l.exhausted.is_some())
+            .map(|l| l.symbol.as_str())
+            .collect()
+    }
+}
+
+impl Display for BasketResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<12} {:>12} {:>12} {:>8} {:>6} {:>6}  exhausted",
+            "symbol", "cost", "value", "return", "opens", "liqs"
+        )?;
+        for l in self.legs.iter() {
+            writeln!(
+                f,
+                "{:<12} {:>12.2} {:>12.2} {:>8.4} {:>6} {:>6}  {}",
+                l.symbol,
+                l.cost,
+                l.value,
+                l.return_rate(),
+                l.open_count,
+                l.liquidations,
+                l.exhausted.map(|e| e.time.to_string()).unwrap_or_default()
+            )?;
+        }
+        write!(
+            f,
+            "total: cost {:.2}, value {:.2}, return {:.4}, pool {:.2}/{:.2}",
+            self.total_cost(),
+            self.total_value(),
+            self.return_rate(),
+            self.remaining_pool,
+            self.initial_pool
+        )
+    }
+}
+
+/// 多个币种的GeoStrategy共用一个资金池，按k线收盘时间交错回测，
+/// 各币种按时间先后从资金池补充资金
+#[derive(Debug)]
+pub struct GeoBasket {
+    pool: Arc<Mutex<f64>>,
+    initial_pool: f64,
+    legs: Vec<(String, GeoStrategy)>,
+}
+
+impl GeoBasket {
+    pub fn new(pool: f64) -> Self {
+        Self {
+            pool: Arc::new(Mutex::new(pool)),
+            initial_pool: pool,
+            legs: vec![],
+        }
+    }
+    /// 创建策略时传入的共享资金池
+    pub fn pool(&self) -> Arc<Mutex<f64>> {
+        self.pool.clone()
+    }
+    /// new_strategy以共享资金池创建该币种的策略
+    pub fn add(
+        &mut self,
+        symbol: &str,
+        new_strategy: impl FnOnce(Arc<Mutex<f64>>) -> GeoStrategy,
+    ) -> &mut Self {
+        self.legs
+            .push((symbol.to_string(), new_strategy(self.pool.clone())));
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.legs.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.legs.is_empty()
+    }
+    /// charts与add的顺序一一对应，各自按时间升序；结束时各币种按最后的收盘价平仓
+    pub fn run(mut self, charts: &[&[CandleData]]) -> BasketResult {
+        assert_eq!(charts.len(), self.legs.len(), "one chart per symbol");
+        let mut cursors = vec![0; charts.len()];
+        // 每次取收盘时间最早的一根，相同时间按添加顺序；资金池不足的币种暂停，其余继续
+        while let Some(i) = (0..charts.len())
+            .filter(|&i| {
+                cursors[i] < charts[i].len() && self.legs[i].1.capital_exhausted().is_none()
+            })
+            .min_by_key(|&i| charts[i][cursors[i]].close_time)
+        {
+            self.legs[i].1.update(&charts[i][cursors[i]]);
+            cursors[i] += 1;
+        }
+        let legs = self
+            .legs
+            .iter_mut()
+            .zip(charts)
+            .map(|((symbol, strategy), chart)| {
+                let value = match chart.last() {
+                    Some(c) => strategy.close(c.close),
+                    None => strategy.value(),
+                };
+                BasketLeg {
+                    symbol: symbol.clone(),
+                    cost: strategy.cost,
+                    value,
+                    open_count: strategy.open_count,
+                    liquidations: strategy.liquidations(),
+                    exhausted: strategy.capital_exhausted(),
+                    trades: strategy.trades().to_vec(),
+                }
+            })
+            .collect();
+        BasketResult {
+            initial_pool: self.initial_pool,
+            remaining_pool: *self.pool.lock().unwrap(),
+            legs,
+        }
+    }
+}
+
+#[test]
+fn geo_basket_test() {
+    use time::{Duration, OffsetDateTime};
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let chart = |closes: &[f64]| -> Vec<CandleData> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| CandleData {
+                open: close,
+                high: close,
+                low: close,
+                close,
+                open_time: start + Duration::minutes(i as i64),
+                close_time: start + Duration::minutes(i as i64 + 1),
+                ..Default::default()
+            })
+            .collect()
+    };
+    // 10倍杠杆下跌10%，止损后需要从资金池补充约一半的资金
+    let falling = chart(&[100., 100., 90., 90., 81., 81., 73., 73.]);
+    let flat = chart(&[10.; 8]);
+    let mut basket = GeoBasket::new(22.);
+    for symbol in ["AUSDT", "BUSDT"] {
+        basket.add(symbol, |pool| {
+            GeoStrategy::new(true, 10., 1., Duration::ZERO, 10., 0.05, 0.5, pool)
+        });
+    }
+    assert_eq!(basket.len(), 2);
+    let result = basket.run(&[&falling, &flat]);
+    // 资金池耗尽后暂停该币种，记录不足的时间和金额
+    assert!((result.remaining_pool - 2.).abs() < 1e-9);
+    assert_eq!(result.starved(), ["AUSDT"]);
+    let exhausted = result.legs[0].exhausted.unwrap();
+    assert_eq!(exhausted.available, 2.);
+    assert_eq!(exhausted.time, falling[2].close_time);
+    assert!((result.total_cost() - (22. - result.remaining_pool)).abs() < 1e-9);
+    let a = &result.legs[0];
+    assert!(a.return_rate() < 1.);
+    assert!(!a.trades.is_empty());
+    assert!((result.legs[1].cost - 10.).abs() < 1e-9);
+    assert!(result.to_string().contains("AUSDT"));
+}
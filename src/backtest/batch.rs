@@ -0,0 +1,568 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    str::FromStr,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use parking_lot::Mutex;
+use rayon::prelude::*;
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+use time::{Duration, OffsetDateTime};
+use tracing::{info, warn};
+
+use super::{
+    candle_chart::{parse_time, CandleData},
+    candle_series::unix_millis,
+    report::BacktestReport,
+    strategy::{breakout_strategy::BreakoutStrategy, geo_strategy::GeoStrategy, Strategy},
+};
+use crate::{error::DataError, strategy::breakout::BreakoutConfig};
+
+/// 批量回测中的一次运行
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchJob {
+    pub strategy: String,
+    pub symbol: String,
+    pub start: OffsetDateTime,
+    pub end: OffsetDateTime,
+    /// 策略参数（名称，取值）
+    pub params: Vec<(String, String)>,
+}
+
+impl BatchJob {
+    /// 策略、币种、区间和参数（与顺序无关）序列化后的SHA-256，用于断点续跑时识别已完成的运行，
+    /// 不随编译器版本变化
+    pub fn config_hash(&self) -> String {
+        let mut params = self.params.clone();
+        params.sort();
+        let config = serde_json::to_string(&(
+            &self.strategy,
+            &self.symbol,
+            unix_millis(self.start),
+            unix_millis(self.end),
+            params,
+        ))
+        .expect("serialize batch job");
+        Sha256::digest(config.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+    /// 参数值，不存在时为None
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+    /// 参数按类型解析，不存在时为default
+    fn parse_param<T>(&self, name: &str, default: T) -> Result<T, String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.param(name) {
+            Some(v) => v.parse().map_err(|e| format!("{}={}: {}", name, v, e)),
+            None => Ok(default),
+        }
+    }
+    /// "a=1;b=2"
+    pub fn params_str(&self) -> String {
+        self.params
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+    /// strategy    策略名称
+    /// symbol      币种
+    /// start       开始时间，unix时间戳、RFC3339或"年-月-日 时:分:秒"（UTC）
+    /// end         结束时间
+    /// params      参数，"a=1;b=2"
+    pub fn read_manifest(path: &str) -> Result<Vec<Self>, DataError> {
+        let mut csv = csv::Reader::from_path(path)?;
+        let mut jobs = vec![];
+        for d in csv.records() {
+            let d = d?;
+            let field = |i: usize, name: &str| {
+                d.get(i)
+                    .ok_or_else(|| DataError::parse(name, "missing field"))
+                    .map(|s| s.to_string())
+            };
+            let time = |i: usize, name: &str| {
+                let s = field(i, name)?;
+                parse_time(&s).ok_or_else(|| DataError::parse(name, s))
+            };
+            let params = field(4, "params")?
+                .split(';')
+                .filter(|p| !p.trim().is_empty())
+                .map(|p| {
+                    p.split_once('=')
+                        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                        .ok_or_else(|| DataError::parse("params", p))
+                })
+                .collect::<Result<_, _>>()?;
+            jobs.push(Self {
+                strategy: field(0, "strategy")?,
+                symbol: field(1, "symbol")?,
+                start: time(2, "start")?,
+                end: time(3, "end")?,
+                params,
+            });
+        }
+        Ok(jobs)
+    }
+}
+
+/// 各策略在清单中可用的参数，未列出的参数视为拼写错误
+const BREAKOUT_PARAMS: &[&str] = &[
+    "side",
+    "capital",
+    "leverage",
+    "period",
+    "volume_factor",
+    "atr_period",
+    "atr_multiplier",
+    "position",
+    "take_profit",
+];
+const GEO_PARAMS: &[&str] = &[
+    "side",
+    "capital",
+    "leverage",
+    "ratio",
+    "interval",
+    "supply",
+    "stop_loss",
+    "take_profit",
+];
+
+/// 按清单中的策略名称和参数构造策略，在job区间内的k线上回测
+///
+/// breakout    side(long|short) capital leverage period volume_factor atr_period atr_multiplier
+///             position take_profit
+/// geo         side(long|short) capital leverage ratio interval(分钟) supply stop_loss take_profit
+pub fn run_job(job: &BatchJob, candles: &[CandleData]) -> Result<BacktestReport, String> {
+    let allowed = match job.strategy.as_str() {
+        "breakout" => BREAKOUT_PARAMS,
+        "geo" => GEO_PARAMS,
+        s => return Err(format!("unknown strategy {}", s)),
+    };
+    if let Some((name, _)) = job
+        .params
+        .iter()
+        .find(|(k, _)| !allowed.contains(&k.as_str()))
+    {
+        return Err(format!("unknown param {} for {}", name, job.strategy));
+    }
+    let is_bull = match job.param("side").unwrap_or("long") {
+        "long" => true,
+        "short" => false,
+        s => return Err(format!("side={}: expected long or short", s)),
+    };
+    let capital: f64 = job.parse_param("capital", 1000.)?;
+    let leverage: f64 = job.parse_param("leverage", 1.)?;
+    let candles: Vec<CandleData> = candles
+        .iter()
+        .filter(|c| c.close_time >= job.start && c.close_time < job.end)
+        .cloned()
+        .collect();
+    if candles.is_empty() {
+        return Err(format!("no candles of {} in range", job.symbol));
+    }
+    let report = |strategy: &mut dyn Strategy| {
+        BacktestReport::run(
+            &job.strategy,
+            job.params.clone(),
+            strategy,
+            &candles,
+            (candles.len() / 1000).max(1),
+        )
+    };
+    Ok(match job.strategy.as_str() {
+        "breakout" => {
+            let default = BreakoutConfig::default();
+            let config = BreakoutConfig {
+                period: job.parse_param("period", default.period)?,
+                volume_factor: job.parse_param("volume_factor", default.volume_factor)?,
+                atr_period: job.parse_param("atr_period", default.atr_period)?,
+                atr_multiplier: job.parse_param("atr_multiplier", default.atr_multiplier)?,
+                position: job.parse_param("position", default.position)?,
+                take_profit: job.parse_param("take_profit", default.take_profit)?,
+                ..default
+            };
+            report(&mut BreakoutStrategy::new(
+                is_bull, config, capital, leverage,
+            ))
+        }
+        "geo" => {
+            let interval = Duration::minutes(job.parse_param("interval", 60)?);
+            report(&mut GeoStrategy::new(
+                is_bull,
+                leverage,
+                job.parse_param("ratio", 0.1)?,
+                interval,
+                job.parse_param("supply", capital / 10.)?,
+                job.parse_param("stop_loss", 0.05)?,
+                job.parse_param("take_profit", 0.01)?,
+                Arc::new(StdMutex::new(capital)),
+            ))
+        }
+        _ => unreachable!("strategy checked above"),
+    })
+}
+
+/// 一次运行的结果指标
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchMetrics {
+    pub final_value: f64,
+    pub return_rate: f64,
+    pub max_drawdown: f64,
+    pub trades: usize,
+    pub win_rate: f64,
+    pub profit_factor: f64,
+    pub liquidations: usize,
+    pub aborted: bool,
+}
+
+impl From<&BacktestReport> for BatchMetrics {
+    fn from(report: &BacktestReport) -> Self {
+        Self {
+            final_value: report.result.value,
+            return_rate: report.return_rate(),
+            max_drawdown: report.result.max_drawdown,
+            trades: report.trades.len(),
+            win_rate: report.win_rate(),
+            profit_factor: report.profit_factor(),
+            liquidations: report.liquidations,
+            aborted: report.result.aborted,
+        }
+    }
+}
+
+/// 结果库中的一行
+#[derive(Debug, Clone)]
+pub struct BatchRow {
+    pub config_hash: String,
+    pub strategy: String,
+    pub symbol: String,
+    pub params: String,
+    /// 失败时为错误信息
+    pub result: Result<BatchMetrics, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BatchSummary {
+    pub total: usize,
+    /// 已有成功结果而跳过的运行
+    pub skipped: usize,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+/// 批量回测结果库（SQLite），每次运行完成后立即写入，中断后可按配置哈希续跑
+#[derive(Debug)]
+pub struct ResultDb {
+    conn: Mutex<Connection>,
+}
+
+impl ResultDb {
+    pub fn open(path: &str) -> Result<Self, DataError> {
+        Self::init(Connection::open(path)?)
+    }
+    pub fn open_in_memory() -> Result<Self, DataError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+    fn init(conn: Connection) -> Result<Self, DataError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS batch_results (
+                batch TEXT NOT NULL,
+                config_hash TEXT NOT NULL,
+                strategy TEXT NOT NULL,
+                symbol TEXT NOT NULL,
+                start INTEGER NOT NULL,
+                end INTEGER NOT NULL,
+                params TEXT NOT NULL,
+                error TEXT,
+                final_value REAL,
+                return_rate REAL,
+                max_drawdown REAL,
+                trades INTEGER,
+                win_rate REAL,
+                profit_factor REAL,
+                liquidations INTEGER,
+                aborted INTEGER,
+                finished INTEGER NOT NULL,
+                PRIMARY KEY (batch, config_hash)
+            );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+    /// 写入一次运行的结果，同一配置只保留最新一次
+    pub fn insert(
+        &self,
+        batch: &str,
+        job: &BatchJob,
+        result: &Result<BatchMetrics, String>,
+    ) -> Result<(), DataError> {
+        let (error, m) = match result {
+            Ok(m) => (None, Some(m)),
+            Err(e) => (Some(e.as_str()), None),
+        };
+        self.conn.lock().execute(
+            "INSERT OR REPLACE INTO batch_results
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+            params![
+                batch,
+                job.config_hash(),
+                job.strategy,
+                job.symbol,
+                unix_millis(job.start),
+                unix_millis(job.end),
+                job.params_str(),
+                error,
+                m.map(|m| m.final_value),
+                m.map(|m| m.return_rate),
+                m.map(|m| m.max_drawdown),
+                m.map(|m| m.trades as i64),
+                m.map(|m| m.win_rate),
+                m.map(|m| m.profit_factor),
+                m.map(|m| m.liquidations as i64),
+                m.map(|m| m.aborted),
+                unix_millis(OffsetDateTime::now_utc()),
+            ],
+        )?;
+        Ok(())
+    }
+    /// 该批次已成功完成的配置哈希
+    pub fn completed(&self, batch: &str) -> Result<HashSet<String>, DataError> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare("SELECT config_hash FROM batch_results WHERE batch = ?1 AND error IS NULL")?;
+        let hashes = stmt
+            .query_map([batch], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(hashes)
+    }
+    /// 该批次的全部结果，成功的按收益率降序在前
+    pub fn results(&self, batch: &str) -> Result<Vec<BatchRow>, DataError> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT config_hash, strategy, symbol, params, error, final_value, return_rate,
+            max_drawdown, trades, win_rate, profit_factor, liquidations, aborted
+            FROM batch_results WHERE batch = ?1
+            ORDER BY error IS NOT NULL, return_rate DESC",
+        )?;
+        let mut rows = stmt.query([batch])?;
+        let mut results = vec![];
+        while let Some(row) = rows.next()? {
+            // NaN写入SQLite后为NULL
+            let real = |i| -> rusqlite::Result<f64> {
+                Ok(row.get::<_, Option<f64>>(i)?.unwrap_or(f64::NAN))
+            };
+            let error: Option<String> = row.get(4)?;
+            let result = match error {
+                Some(e) => Err(e),
+                None => Ok(BatchMetrics {
+                    final_value: real(5)?,
+                    return_rate: real(6)?,
+                    max_drawdown: real(7)?,
+                    trades: row.get::<_, i64>(8)? as usize,
+                    win_rate: real(9)?,
+                    profit_factor: real(10)?,
+                    liquidations: row.get::<_, i64>(11)? as usize,
+                    aborted: row.get(12)?,
+                }),
+            };
+            results.push(BatchRow {
+                config_hash: row.get(0)?,
+                strategy: row.get(1)?,
+                symbol: row.get(2)?,
+                params: row.get(3)?,
+                result,
+            });
+        }
+        Ok(results)
+    }
+}
+
+/// 批量回测：并行执行清单中的运行，跳过结果库中该批次已成功的配置，失败的重新运行
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub name: String,
+    pub jobs: Vec<BatchJob>,
+}
+
+impl Batch {
+    pub fn new(name: &str, jobs: Vec<BatchJob>) -> Self {
+        Self {
+            name: name.to_string(),
+            jobs,
+        }
+    }
+    /// run按任务回测并返回报告，Err为错误信息；写入结果库失败时返回错误，已写入的结果保留
+    pub fn run<F>(&self, db: &ResultDb, run: F) -> Result<BatchSummary, DataError>
+    where
+        F: Fn(&BatchJob) -> Result<BacktestReport, String> + Sync,
+    {
+        let completed = db.completed(&self.name)?;
+        let pending: Vec<&BatchJob> = self
+            .jobs
+            .iter()
+            .filter(|j| !completed.contains(&j.config_hash()))
+            .collect();
+        let summary = Mutex::new(BatchSummary {
+            total: self.jobs.len(),
+            skipped: self.jobs.len() - pending.len(),
+            ..Default::default()
+        });
+        info!(
+            "Batch {}: {} jobs, {} already completed",
+            self.name,
+            self.jobs.len(),
+            self.jobs.len() - pending.len()
+        );
+        pending.into_par_iter().try_for_each(|job| {
+            let result = run(job).map(|report| BatchMetrics::from(&report));
+            if let Err(e) = &result {
+                warn!(
+                    "Batch {} job {} {} [{}] failed: {}",
+                    self.name,
+                    job.strategy,
+                    job.symbol,
+                    job.params_str(),
+                    e
+                );
+            }
+            db.insert(&self.name, job, &result)?;
+            let mut summary = summary.lock();
+            match result {
+                Ok(_) => summary.completed += 1,
+                Err(_) => summary.failed += 1,
+            }
+            Ok::<_, DataError>(())
+        })?;
+        Ok(summary.into_inner())
+    }
+}
+
+#[test]
+fn batch_test() {
+    use super::{candle_chart::CandleData, strategy::breakout_strategy::BreakoutStrategy};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let path = std::env::temp_dir().join("hurribot_batch_manifest_test.csv");
+    std::fs::write(
+        &path,
+        "strategy,symbol,start,end,params\n\
+        geo,BTCUSDT,2024-01-01 00:00:00,2024-02-01 00:00:00,leverage=2;position=0.1\n\
+        geo,BTCUSDT,2024-01-01 00:00:00,2024-02-01 00:00:00,leverage=5;position=0.1\n\
+        geo,ETHUSDT,2024-01-01 00:00:00,2024-02-01 00:00:00,leverage=bad\n",
+    )
+    .unwrap();
+    let jobs = BatchJob::read_manifest(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(jobs.len(), 3);
+    assert_eq!(jobs[1].param("leverage"), Some("5"));
+    let mut reordered = jobs[0].clone();
+    reordered.params.reverse();
+    assert_eq!(reordered.config_hash(), jobs[0].config_hash());
+    assert_ne!(jobs[0].config_hash(), jobs[1].config_hash());
+    assert_eq!(jobs[0].config_hash().len(), 64);
+
+    let candles: Vec<CandleData> = (0..100)
+        .map(|i| {
+            let close = 100. + (i as f64 * 0.3).sin() * 5.;
+            CandleData {
+                open: close,
+                high: close + 1.,
+                low: close - 1.,
+                close,
+                ..Default::default()
+            }
+        })
+        .collect();
+    let runs = AtomicUsize::new(0);
+    let run = |job: &BatchJob| {
+        runs.fetch_add(1, Ordering::Relaxed);
+        let leverage: f64 = job
+            .param("leverage")
+            .unwrap_or("1")
+            .parse()
+            .map_err(|e| format!("leverage: {}", e))?;
+        let mut strategy = BreakoutStrategy::new(true, Default::default(), 1000., leverage);
+        Ok(BacktestReport::run(
+            &job.strategy,
+            job.params.clone(),
+            &mut strategy,
+            &candles,
+            10,
+        ))
+    };
+    let db = ResultDb::open_in_memory().unwrap();
+    let batch = Batch::new("sweep", jobs);
+    let summary = batch.run(&db, run).unwrap();
+    assert_eq!(
+        (summary.completed, summary.failed, summary.skipped),
+        (2, 1, 0)
+    );
+    let results = db.results("sweep").unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results[0].result.is_ok());
+    assert!(results[2].result.as_ref().unwrap_err().contains("leverage"));
+    // 续跑时只重新运行失败的任务
+    let summary = batch.run(&db, run).unwrap();
+    assert_eq!((summary.skipped, summary.failed), (2, 1));
+    assert_eq!(runs.load(Ordering::Relaxed), 4);
+    assert!(db.results("other").unwrap().is_empty());
+}
+
+#[test]
+fn batch_run_job_test() {
+    let start = parse_time("2024-01-01 00:00:00").unwrap();
+    let candles: Vec<CandleData> = (0..200)
+        .map(|i| {
+            let close = 100. + i as f64 * 0.5 + (i as f64 * 0.7).sin() * 3.;
+            CandleData {
+                open: close,
+                high: close + 1.,
+                low: close - 1.,
+                close,
+                volume: 10. + (i % 5) as f64 * 10.,
+                open_time: start + Duration::minutes(i),
+                close_time: start + Duration::minutes(i + 1),
+            }
+        })
+        .collect();
+    let job = |strategy: &str, params: &[(&str, &str)]| BatchJob {
+        strategy: strategy.to_string(),
+        symbol: "BTCUSDT".to_string(),
+        start,
+        end: start + Duration::minutes(100),
+        params: params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    };
+    let report = run_job(
+        &job("breakout", &[("period", "10"), ("leverage", "2")]),
+        &candles,
+    )
+    .unwrap();
+    // 只使用收盘时间在[start, end)内的k线
+    assert_eq!(report.result.processed, 99);
+    assert!(run_job(
+        &job("geo", &[("interval", "10"), ("side", "short")]),
+        &candles
+    )
+    .is_ok());
+    let err = |job: BatchJob| run_job(&job, &candles).unwrap_err();
+    assert!(err(job("unknown", &[])).contains("unknown strategy"));
+    assert!(err(job("breakout", &[("perod", "10")])).contains("unknown param perod"));
+    assert!(err(job("geo", &[("ratio", "x")])).contains("ratio=x"));
+    assert!(err(job("geo", &[("side", "up")])).contains("side=up"));
+}
@@ -0,0 +1,145 @@
+use time::OffsetDateTime;
+
+use super::candle_chart::CandleData;
+
+/// 基准曲线：同期持有不动的价值，与策略资金曲线按时间对齐
+#[derive(Debug, Clone)]
+pub struct Benchmark {
+    pub name: String,
+    /// （时间，价值），起点与策略资金曲线相同
+    pub curve: Vec<(OffsetDateTime, f64)>,
+}
+
+impl Benchmark {
+    /// 在times的每个时间点按最近一根已收盘k线计算买入持有的价值，
+    /// candles需按时间升序；早于第一根k线的时间点按第一根计算
+    pub fn buy_and_hold(
+        name: &str,
+        candles: &[CandleData],
+        initial_value: f64,
+        times: &[OffsetDateTime],
+    ) -> Option<Self> {
+        let price_at = |time: OffsetDateTime| {
+            let i = candles.partition_point(|c| c.close_time <= time);
+            candles[i.saturating_sub(1)].close
+        };
+        let first = times.first()?;
+        if candles.is_empty() {
+            return None;
+        }
+        let base = price_at(*first);
+        if base <= 0. {
+            return None;
+        }
+        Some(Self {
+            name: name.to_string(),
+            curve: times
+                .iter()
+                .map(|&t| (t, initial_value * price_at(t) / base))
+                .collect(),
+        })
+    }
+    pub fn return_rate(&self) -> f64 {
+        match (self.curve.first(), self.curve.last()) {
+            (Some(first), Some(last)) if first.1 > 0. => last.1 / first.1,
+            _ => f64::NAN,
+        }
+    }
+    /// 与策略资金曲线（与curve逐点对齐）比较
+    pub fn compare(&self, equity: &[f64]) -> BenchmarkStats {
+        let benchmark: Vec<f64> = self.curve.iter().map(|c| c.1).collect();
+        let strategy = period_returns(equity);
+        let base = period_returns(&benchmark);
+        let n = strategy.len().min(base.len());
+        let (strategy, base) = (&strategy[..n], &base[..n]);
+        let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+        let (mean_s, mean_b) = (mean(strategy), mean(base));
+        let covariance = strategy
+            .iter()
+            .zip(base)
+            .map(|(s, b)| (s - mean_s) * (b - mean_b))
+            .sum::<f64>();
+        let variance = base.iter().map(|b| (b - mean_b).powi(2)).sum::<f64>();
+        let beta = covariance / variance;
+        let relative: Vec<f64> = equity
+            .iter()
+            .zip(benchmark.iter())
+            .map(|(s, b)| s / b)
+            .collect();
+        BenchmarkStats {
+            return_rate: self.return_rate(),
+            beta,
+            alpha: (mean_s - beta * mean_b) * n as f64,
+            max_drawdown: max_drawdown(&benchmark),
+            relative_drawdown: max_drawdown(&relative),
+        }
+    }
+}
+
+/// 策略相对基准的表现
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkStats {
+    /// 基准的期末/期初
+    pub return_rate: f64,
+    /// 策略每期收益对基准每期收益的回归系数
+    pub beta: f64,
+    /// 扣除beta部分后每期超额收益的累计
+    pub alpha: f64,
+    /// 基准自身的最大回撤
+    pub max_drawdown: f64,
+    /// 策略/基准比值的最大回撤，即跑输基准的最大幅度
+    pub relative_drawdown: f64,
+}
+
+fn period_returns(values: &[f64]) -> Vec<f64> {
+    values.windows(2).map(|w| w[1] / w[0] - 1.).collect()
+}
+
+fn max_drawdown(values: &[f64]) -> f64 {
+    let mut peak = f64::NEG_INFINITY;
+    let mut drawdown: f64 = 0.;
+    for &v in values {
+        peak = peak.max(v);
+        if peak > 0. {
+            drawdown = drawdown.max(1. - v / peak);
+        }
+    }
+    drawdown
+}
+
+#[test]
+fn benchmark_test() {
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let closes = [100., 110., 99., 121.];
+    let candles: Vec<CandleData> = closes
+        .iter()
+        .enumerate()
+        .map(|(i, &close)| CandleData {
+            close,
+            close_time: start + Duration::minutes(i as i64),
+            ..Default::default()
+        })
+        .collect();
+    let times: Vec<_> = candles.iter().map(|c| c.close_time).collect();
+    let benchmark = Benchmark::buy_and_hold("hold", &candles, 1000., &times).unwrap();
+    assert_eq!(benchmark.curve[1].1, 1100.);
+    assert!((benchmark.return_rate() - 1.21).abs() < 1e-9);
+    // 两倍杠杆复制基准：beta为2，alpha为0
+    let equity: Vec<f64> = closes.windows(2).fold(vec![1000.], |mut e, w| {
+        e.push(e.last().unwrap() * (1. + 2. * (w[1] / w[0] - 1.)));
+        e
+    });
+    let stats = benchmark.compare(&equity);
+    assert!((stats.beta - 2.).abs() < 1e-9);
+    assert!(stats.alpha.abs() < 1e-9);
+    assert!((stats.max_drawdown - 0.1).abs() < 1e-9);
+    // 持平的资金曲线跑输上涨的基准
+    let stats = benchmark.compare(&[1000.; 4]);
+    assert_eq!(stats.beta, 0.);
+    assert!((stats.relative_drawdown - (1. - 0.99 / 1.21)).abs() < 1e-9);
+    // 时间点晚于最后一根k线时取最后的收盘价
+    let late = Benchmark::buy_and_hold("hold", &candles, 1., &[start, start + Duration::hours(1)]);
+    assert!((late.unwrap().return_rate() - 1.21).abs() < 1e-9);
+}
@@ -0,0 +1,251 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use dashmap::DashMap;
+use sha2::{Digest, Sha256};
+use time::Duration;
+use tracing::{info, warn};
+
+use super::{
+    candle_chart::{CandleChart, CandleColumns},
+    candle_series::CandleSeries,
+    provenance::DataManifest,
+};
+use crate::error::DataError;
+
+/// 缓存文件格式版本，CandleSeries结构变化时需递增
+const CACHE_VERSION: u32 = 3;
+
+/// 缓存文件名：v{版本}-{数据集键}-{源文件键}.bin，数据集键由路径、间隔和列映射决定，
+/// 源文件键由各文件的大小与修改时间决定
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    dataset: String,
+    source: String,
+}
+
+impl CacheKey {
+    fn file_name(&self) -> String {
+        format!("v{}-{}-{}.bin", CACHE_VERSION, self.dataset, self.source)
+    }
+}
+
+/// k线缓存：解析后的k线保存在内存中，并以postcard二进制格式写入磁盘，
+/// 以路径、间隔、列映射和源文件的大小与修改时间为键，源文件变化后自动失效；
+/// 写入新的缓存文件时删除同一数据集的旧文件和其他版本的文件
+#[derive(Debug)]
+pub struct CandleCache {
+    dir: PathBuf,
+    memory: DashMap<CacheKey, Arc<CandleChart>>,
+}
+
+impl CandleCache {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            memory: DashMap::new(),
+        }
+    }
+    /// 依次从内存、磁盘缓存、CSV加载，自动识别列映射
+    pub fn load(&self, path: &str, interval: Duration) -> Result<Arc<CandleChart>, DataError> {
+        self.load_with(path, interval, None)
+    }
+    /// 同load，按columns解析CSV，见CandleChart::read_from_csv_with
+    pub fn load_with(
+        &self,
+        path: &str,
+        interval: Duration,
+        columns: Option<&CandleColumns>,
+    ) -> Result<Arc<CandleChart>, DataError> {
+        let key = cache_key(Path::new(path), interval, columns);
+        if let Some(chart) = self.memory.get(&key) {
+            return Ok(chart.clone());
+        }
+        let file = self.dir.join(key.file_name());
+        let chart = match read_cache(&file) {
+            Ok(candles) => {
+                info!("read from cache: {} ({})", path, file.display());
+                let mut chart = CandleChart::new(interval);
+                chart.candles = candles;
+                // 缓存随源文件失效，元数据无需重新校验
+                chart.provenance = DataManifest::read(Path::new(path)).ok().flatten();
+                chart
+            }
+            Err(e) => {
+                if file.exists() {
+                    warn!("read cache {} failed: {}", file.display(), e);
+                }
+                let chart = CandleChart::read_from_csv_with(path, interval, columns)?;
+                match write_cache(&file, &chart.candles) {
+                    Ok(()) => self.prune(&key),
+                    Err(e) => warn!("write cache {} failed: {}", file.display(), e),
+                }
+                chart
+            }
+        };
+        let chart = Arc::new(chart);
+        self.memory.insert(key, chart.clone());
+        Ok(chart)
+    }
+    /// 清空内存缓存，磁盘缓存保留
+    pub fn clear_memory(&self) {
+        self.memory.clear();
+    }
+    /// 删除同一数据集的旧缓存文件（源文件已变化）和其他版本或旧命名的缓存文件
+    fn prune(&self, key: &CacheKey) {
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let current = key.file_name();
+        let version = format!("v{}-", CACHE_VERSION);
+        let dataset = format!("{}{}-", version, key.dataset);
+        for path in dir.filter_map(|e| e.ok().map(|e| e.path())) {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let stale = name.ends_with(".bin")
+                && name != current
+                && (name.starts_with(&dataset) || !name.starts_with(&version));
+            if stale {
+                match std::fs::remove_file(&path) {
+                    Ok(()) => info!("removed stale cache {}", path.display()),
+                    Err(e) => warn!("remove cache {} failed: {}", path.display(), e),
+                }
+            }
+        }
+    }
+}
+
+/// SHA-256的前16字节（十六进制），不随Rust版本或进程变化
+fn digest(hasher: Sha256) -> String {
+    hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn cache_key(path: &Path, interval: Duration, columns: Option<&CandleColumns>) -> CacheKey {
+    let mut dataset = Sha256::new();
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    dataset.update(canonical.to_string_lossy().as_bytes());
+    dataset.update(interval.whole_milliseconds().to_le_bytes());
+    match columns {
+        Some(c) => {
+            let optional = |c: Option<usize>| c.map_or(-1, |c| c as i64);
+            for column in [c.open_time, c.open, c.high, c.low, c.close] {
+                dataset.update((column as i64).to_le_bytes());
+            }
+            dataset.update(optional(c.volume).to_le_bytes());
+            dataset.update(optional(c.close_time).to_le_bytes());
+        }
+        None => dataset.update(b"auto"),
+    }
+    let mut source = Sha256::new();
+    let mut files: Vec<PathBuf> = if path.is_dir() {
+        std::fs::read_dir(path)
+            .map(|dir| dir.filter_map(|e| e.ok().map(|e| e.path())).collect())
+            .unwrap_or_default()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    files.sort();
+    for f in files {
+        let Ok(meta) = f.metadata() else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos());
+        source.update(f.file_name().unwrap_or_default().as_encoded_bytes());
+        source.update(meta.len().to_le_bytes());
+        source.update(modified.to_le_bytes());
+    }
+    CacheKey {
+        dataset: digest(dataset),
+        source: digest(source),
+    }
+}
+
+fn read_cache(file: &Path) -> Result<CandleSeries, DataError> {
+    let bytes = std::fs::read(file)?;
+    Ok(postcard::from_bytes(&bytes)?)
+}
+
+fn write_cache(file: &Path, candles: &CandleSeries) -> Result<(), DataError> {
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(file, postcard::to_stdvec(candles)?)?;
+    Ok(())
+}
+
+#[test]
+fn candle_cache_test() {
+    let dir = std::env::temp_dir().join("hurribot_candle_cache_test");
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let csv = dir.join("BTCUSDT.csv");
+    std::fs::write(
+        &csv,
+        "1704067200000,100,101,99,100.5,10,1704067259999,0,0,0,0,0\n",
+    )
+    .unwrap();
+    let cache_dir = dir.join("cache");
+    let interval = Duration::minutes(1);
+    let cache = CandleCache::new(cache_dir.to_str().unwrap());
+    let a = cache.load(csv.to_str().unwrap(), interval).unwrap();
+    assert!(Arc::ptr_eq(
+        &a,
+        &cache.load(csv.to_str().unwrap(), interval).unwrap()
+    ));
+    assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 1);
+    // 新的缓存实例从磁盘读取
+    let b = CandleCache::new(cache_dir.to_str().unwrap())
+        .load(csv.to_str().unwrap(), interval)
+        .unwrap();
+    assert_eq!(b.candles.len(), 1);
+    assert_eq!(b.candles, a.candles);
+    assert_eq!(b.candles.high[0], 101.);
+    // 源文件变化后缓存失效
+    std::fs::write(
+        &csv,
+        "1704067200000,100,101,99,100.5,10,1704067259999,0,0,0,0,0\n\
+         1704067260000,100.5,102,100,101,12,1704067319999,0,0,0,0,0\n",
+    )
+    .unwrap();
+    let c = cache.load(csv.to_str().unwrap(), interval).unwrap();
+    assert_eq!(c.candles.len(), 2);
+    // 旧的缓存文件被删除
+    assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 1);
+    // 列映射不同时分别缓存
+    let columns = CandleColumns {
+        volume: None,
+        ..Default::default()
+    };
+    let d = cache
+        .load_with(csv.to_str().unwrap(), interval, Some(&columns))
+        .unwrap();
+    assert_eq!(d.candles.volume, [0., 0.]);
+    assert_eq!(c.candles.volume, [10., 12.]);
+    assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 2);
+    // 其他版本的缓存文件被删除
+    let legacy = cache_dir.join("00000000deadbeef.bin");
+    std::fs::write(&legacy, b"").unwrap();
+    CandleCache::new(cache_dir.to_str().unwrap())
+        .load_with(
+            csv.to_str().unwrap(),
+            interval,
+            Some(&CandleColumns::default()),
+        )
+        .unwrap();
+    assert!(!legacy.exists());
+    assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 3);
+}
@@ -1,22 +1,43 @@
 use std::{fs::File, path::Path};
 
-use time::{Duration, OffsetDateTime};
-use tracing::info;
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Duration, OffsetDateTime,
+    PrimitiveDateTime,
+};
+use tracing::{info, warn};
+
+use super::{candle_series::CandleSeries, provenance::DataManifest};
+pub use crate::candle::CandleData;
+use crate::error::DataError;
 
 #[derive(Debug)]
 pub struct CandleChart {
     /// k线间隔（秒）
     interval: Duration,
-    pub candles: Vec<CandleData>,
+    /// 按时间升序列式存储
+    pub candles: CandleSeries,
+    /// 数据来源和校验和，数据旁没有元数据文件时为None
+    pub provenance: Option<DataManifest>,
 }
 
 impl CandleChart {
     pub fn new(interval: Duration) -> Self {
         Self {
             interval,
-            candles: vec![],
+            candles: CandleSeries::default(),
+            provenance: None,
         }
     }
+    /// 读取path旁的元数据并校验数据的SHA-256
+    pub fn verify_provenance(path: &str) -> Result<Option<DataManifest>, DataError> {
+        let path = Path::new(path);
+        let Some(manifest) = DataManifest::read(path)? else {
+            return Ok(None);
+        };
+        manifest.verify(path)?;
+        Ok(Some(manifest))
+    }
+    /// 默认为币安k线数据的列顺序（可带表头）：
     /// open_time               K线图开盘时间（unix格式）
     /// open                    开盘价
     /// high                    最高价
@@ -29,106 +50,263 @@ impl CandleChart {
     /// taker_buy_volume        在此期间吃单方买入的基础币数量
     /// taker_buy_quote_volume  在此期间吃单方买入的报价币数量
     /// ignore                  忽略
-    pub fn read_from_csv(path: &str, interval: Duration) -> Self {
-        fn read_from_csv_file(path: &Path) -> Vec<CandleData> {
-            let file = File::open(path).unwrap();
-            let mut csv = csv::Reader::from_reader(file);
-            let mut candles = vec![];
-
-            for d in csv.records() {
-                let d = d.unwrap();
-                let open_nano = d.get(0).unwrap().parse::<i64>().unwrap() as i128 * 1_000_000;
-                let close_nano = d.get(6).unwrap().parse::<i64>().unwrap() as i128 * 1_000_000;
-                let open_time = OffsetDateTime::from_unix_timestamp_nanos(open_nano).unwrap();
-                let close_time = OffsetDateTime::from_unix_timestamp_nanos(close_nano).unwrap();
-                let open = d.get(1).unwrap().parse::<f64>().unwrap();
-                let high = d.get(2).unwrap().parse::<f64>().unwrap();
-                let low = d.get(3).unwrap().parse::<f64>().unwrap();
-                let close = d.get(4).unwrap().parse::<f64>().unwrap();
-                let volume = d.get(5).unwrap().parse::<f64>().unwrap();
-                candles.push(CandleData {
-                    open,
-                    high,
-                    low,
-                    close,
-                    volume,
-                    open_time,
-                    close_time,
-                });
-            }
-            candles
-        }
+    /// 首行为表头时按列名识别（兼容TradingView、CryptoDataDownload等导出格式）
+    pub fn read_from_csv(path: &str, interval: Duration) -> Result<Self, DataError> {
+        Self::read_from_csv_with(path, interval, None)
+    }
+    /// columns为None时自动识别列映射，无法解析的行（表头、说明行）会被跳过，
+    /// 无法识别的表头返回错误；有元数据文件时先校验，数据与校验和不符时返回错误
+    pub fn read_from_csv_with(
+        path: &str,
+        interval: Duration,
+        columns: Option<&CandleColumns>,
+    ) -> Result<Self, DataError> {
         info!("read from csv: {}", path);
+        let provenance = Self::verify_provenance(path)?;
         let path = Path::new(path);
         let mut chart = Self::new(interval);
+        let mut candles = vec![];
         if path.is_dir() {
-            for entry in std::fs::read_dir(path).unwrap() {
-                let entry = entry.unwrap();
-                let path = entry.path();
-
-                if path.is_file() {
-                    chart.candles.append(&mut read_from_csv_file(&path));
+            for entry in std::fs::read_dir(path)? {
+                let path = entry?.path();
+                if path.is_file() && !DataManifest::is_manifest(&path) {
+                    read_csv_file(&path, interval, columns, |c| candles.push(c))?;
                 }
             }
-        } else if path.is_file() {
-            chart.candles = read_from_csv_file(path);
         } else {
-            panic!("invalid path: {}", path.display());
+            read_csv_file(path, interval, columns, |c| candles.push(c))?;
+        }
+
+        candles.sort();
+        chart.candles = CandleSeries::from(candles.as_slice());
+        if let Some(manifest) = &provenance {
+            if manifest.rows != chart.candles.len() {
+                warn!(
+                    "{}: {} candles read, manifest records {} rows",
+                    path.display(),
+                    chart.candles.len(),
+                    manifest.rows
+                );
+            }
         }
+        chart.provenance = provenance;
+        Ok(chart)
+    }
+}
 
-        chart.candles.sort();
-        chart
+/// 逐行解析单个csv文件，columns为None时自动识别列映射
+pub(super) fn read_csv_file(
+    path: &Path,
+    interval: Duration,
+    columns: Option<&CandleColumns>,
+    mut f: impl FnMut(CandleData),
+) -> Result<(), DataError> {
+    let file = File::open(path)?;
+    let mut csv = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(file);
+    let mut columns = columns.cloned();
+    let mut skipped = 0;
+    for d in csv.records() {
+        let d = d?;
+        let cols = match &columns {
+            Some(c) => c,
+            // 跳过列数不足的说明行（如CryptoDataDownload首行的网址）
+            None if d.len() < 5 => continue,
+            None => columns.insert(CandleColumns::detect(&d)?),
+        };
+        match cols.parse(&d, interval) {
+            Some(candle) => f(candle),
+            None => skipped += 1,
+        }
     }
+    if skipped > 1 {
+        warn!("{}: skipped {} unparsable rows", path.display(), skipped);
+    }
+    Ok(())
 }
 
-#[derive(Debug, Clone)]
-pub struct CandleData {
-    pub open: f64,
-    pub close: f64,
-    pub high: f64,
-    pub low: f64,
-    pub volume: f64,
-    pub open_time: OffsetDateTime,
-    pub close_time: OffsetDateTime,
+/// k线csv的列映射（列序号）
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandleColumns {
+    pub open_time: usize,
+    pub open: usize,
+    pub high: usize,
+    pub low: usize,
+    pub close: usize,
+    /// 缺失时成交量为0
+    pub volume: Option<usize>,
+    /// 缺失时为开盘时间 + 间隔 - 1ms
+    pub close_time: Option<usize>,
 }
 
-impl Default for CandleData {
+impl Default for CandleColumns {
+    /// 币安k线数据的列顺序
     fn default() -> Self {
         Self {
-            open: 0.,
-            close: 0.,
-            high: 0.,
-            low: 0.,
-            volume: 0.,
-            open_time: OffsetDateTime::from_unix_timestamp(0).unwrap(),
-            close_time: OffsetDateTime::from_unix_timestamp(0).unwrap(),
+            open_time: 0,
+            open: 1,
+            high: 2,
+            low: 3,
+            close: 4,
+            volume: Some(5),
+            close_time: Some(6),
         }
     }
 }
 
-impl Ord for CandleData {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.close_time.cmp(&other.close_time)
+impl CandleColumns {
+    /// 按表头列名识别，缺少开盘时间或OHLC列时返回None
+    pub fn from_headers(headers: &csv::StringRecord) -> Option<Self> {
+        let names: Vec<String> = headers
+            .iter()
+            .map(|h| h.trim().to_lowercase().replace(' ', "_"))
+            .collect();
+        let find = |candidates: &[&str]| {
+            candidates
+                .iter()
+                .find_map(|c| names.iter().position(|n| n == c))
+        };
+        Some(Self {
+            open_time: find(&["open_time", "unix", "timestamp", "time", "date", "datetime"])?,
+            open: find(&["open"])?,
+            high: find(&["high"])?,
+            low: find(&["low"])?,
+            close: find(&["close"])?,
+            volume: find(&["volume"])
+                .or_else(|| names.iter().position(|n| n.starts_with("volume"))),
+            close_time: find(&["close_time"]),
+        })
     }
-}
-
-impl PartialOrd for CandleData {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+    /// 首行能识别为表头时按列名映射，否则使用币安的列顺序；表头缺少必需的列时返回错误
+    pub fn detect(first: &csv::StringRecord) -> Result<Self, DataError> {
+        let is_header = first
+            .iter()
+            .all(|f| f.trim().parse::<f64>().is_err() && parse_time(f).is_none());
+        if !is_header {
+            return Ok(Self::default());
+        }
+        Self::from_headers(first).ok_or_else(|| {
+            DataError::parse("csv header", first.iter().collect::<Vec<_>>().join(","))
+        })
+    }
+    pub fn parse(&self, d: &csv::StringRecord, interval: Duration) -> Option<CandleData> {
+        let price = |i: usize| d.get(i)?.trim().parse::<f64>().ok();
+        let open_time = parse_time(d.get(self.open_time)?)?;
+        let close_time = match self.close_time {
+            Some(i) => parse_time(d.get(i)?)?,
+            None => open_time + interval - Duration::milliseconds(1),
+        };
+        Some(CandleData {
+            open: price(self.open)?,
+            high: price(self.high)?,
+            low: price(self.low)?,
+            close: price(self.close)?,
+            volume: self.volume.and_then(price).unwrap_or_default(),
+            open_time,
+            close_time,
+        })
     }
 }
 
-impl PartialEq for CandleData {
-    fn eq(&self, other: &Self) -> bool {
-        self.close_time == other.close_time
+/// 解析时间戳：数字按数量级识别秒/毫秒/微秒/纳秒，或RFC3339、"年-月-日 时:分:秒"（UTC）
+pub(crate) fn parse_time(s: &str) -> Option<OffsetDateTime> {
+    let s = s.trim();
+    let from_number = |t: i128| {
+        let nanos = match t.abs() {
+            t if t < 100_000_000_000 => t * 1_000_000_000,
+            t if t < 100_000_000_000_000 => t * 1_000_000,
+            t if t < 100_000_000_000_000_000 => t * 1_000,
+            _ => t,
+        } * t.signum();
+        OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
+    };
+    if let Ok(t) = s.parse::<i128>() {
+        return from_number(t);
     }
+    if let Ok(t) = s.parse::<f64>() {
+        // 带小数的秒级时间戳
+        return OffsetDateTime::from_unix_timestamp_nanos((t * 1e9) as i128).ok();
+    }
+    if let Ok(t) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Some(t);
+    }
+    PrimitiveDateTime::parse(
+        s,
+        format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"),
+    )
+    .ok()
+    .map(|t| t.assume_utc())
 }
 
-impl Eq for CandleData {}
-
 #[test]
 fn candle_test() {
     let close_time_nano = 1672531200000_i128 * 1_000_000;
     let close_time = OffsetDateTime::from_unix_timestamp_nanos(close_time_nano).unwrap();
     dbg!(close_time);
 }
+
+#[test]
+fn candle_csv_schema_test() {
+    let dir = std::env::temp_dir().join("hurribot_candle_schema_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    // 币安格式，无表头
+    let binance = dir.join("binance.csv");
+    std::fs::write(
+        &binance,
+        "1704067200000,100,101,99,100.5,10,1704067259999,0,0,0,0,0\n\
+         1704067260000,100.5,102,100,101,12,1704067319999,0,0,0,0,0\n",
+    )
+    .unwrap();
+    // TradingView导出，秒级时间戳
+    let tradingview = dir.join("tradingview.csv");
+    std::fs::write(
+        &tradingview,
+        "time,open,high,low,close,Volume\n1704067200,100,101,99,100.5,10\n",
+    )
+    .unwrap();
+    // CryptoDataDownload导出，首行为网址，倒序
+    let cdd = dir.join("cdd.csv");
+    std::fs::write(
+        &cdd,
+        "https://www.CryptoDataDownload.com\n\
+         unix,date,symbol,open,high,low,close,Volume BTC,Volume USDT,tradecount\n\
+         1704067260000,2024-01-01 00:01:00,BTCUSDT,100.5,102,100,101,12,1200,5\n\
+         1704067200000,2024-01-01 00:00:00,BTCUSDT,100,101,99,100.5,10,1000,4\n",
+    )
+    .unwrap();
+    let interval = Duration::minutes(1);
+    let read =
+        |p: &std::path::Path| CandleChart::read_from_csv(p.to_str().unwrap(), interval).unwrap();
+    let a = read(&binance);
+    let b = read(&tradingview);
+    let c = read(&cdd);
+    assert_eq!(a.candles.len(), 2);
+    assert_eq!(c.candles.len(), 2);
+    assert_eq!(a.candles.close_time[0], b.candles.close_time[0]);
+    assert_eq!(a.candles.close_time[1], c.candles.close_time[1]);
+    assert_eq!(c.candles.volume[0], 10.);
+    let columns = CandleColumns {
+        volume: None,
+        close_time: None,
+        ..Default::default()
+    };
+    let d = CandleChart::read_from_csv_with(binance.to_str().unwrap(), interval, Some(&columns))
+        .unwrap();
+    assert_eq!(d.candles.volume[1], 0.);
+    assert_eq!(d.candles.close_time[1], a.candles.close_time[1]);
+    // 无法识别的表头返回错误
+    let unknown = dir.join("unknown.csv");
+    std::fs::write(
+        &unknown,
+        "a,b,c,d,e,f
+1704067200,100,101,99,100.5,10
+",
+    )
+    .unwrap();
+    assert!(matches!(
+        CandleChart::read_from_csv(unknown.to_str().unwrap(), interval),
+        Err(DataError::Parse { .. })
+    ));
+}
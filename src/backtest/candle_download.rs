@@ -0,0 +1,158 @@
+use std::path::Path;
+
+use binance::futures::{market::FuturesMarket, model::KlineSummaries};
+use time::{Duration, OffsetDateTime};
+use tracing::info;
+
+use super::{
+    candle_chart::{CandleChart, CandleData},
+    funding::to_millis,
+    provenance::DataManifest,
+};
+use crate::{
+    error::{BinanceResultExt, DataError, MarketError},
+    utils::unix_millis,
+};
+
+/// 单次请求的最大条数
+const KLINE_LIMIT: u16 = 1500;
+
+/// 币安klines接口的间隔参数
+fn binance_interval(interval: Duration) -> Option<&'static str> {
+    Some(match interval.whole_minutes() {
+        1 => "1m",
+        3 => "3m",
+        5 => "5m",
+        15 => "15m",
+        30 => "30m",
+        60 => "1h",
+        120 => "2h",
+        240 => "4h",
+        360 => "6h",
+        480 => "8h",
+        720 => "12h",
+        1440 => "1d",
+        _ => return None,
+    })
+}
+
+impl CandleChart {
+    /// 从币安klines接口下载开盘时间在[start, end]内的k线
+    pub fn download(
+        market: &FuturesMarket,
+        symbol: &str,
+        interval: Duration,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> Result<Self, MarketError> {
+        let name = binance_interval(interval)
+            .ok_or_else(|| MarketError::Rejected(format!("kline interval {}", interval)))?;
+        let end_ms = to_millis(end);
+        let mut start_ms = to_millis(start);
+        let mut chart = Self::new(interval);
+        while start_ms <= end_ms {
+            let KlineSummaries::AllKlineSummaries(klines) = market
+                .get_klines(symbol, name, KLINE_LIMIT, start_ms, end_ms)
+                .market_context(&format!("get klines of {}", symbol))?;
+            let Some(last) = klines.last() else {
+                break;
+            };
+            start_ms = last.open_time as u64 + 1;
+            let full = klines.len() == KLINE_LIMIT as usize;
+            chart.candles.extend(klines.iter().map(CandleData::from));
+            if !full {
+                break;
+            }
+        }
+        info!(
+            "download {} {} klines of {}",
+            chart.candles.len(),
+            name,
+            symbol
+        );
+        Ok(chart)
+    }
+    /// 按币安的列顺序（开盘时间、OHLC、成交量、收盘时间，时间为unix毫秒）写入csv，
+    /// 同时在旁边写入元数据，返回元数据
+    pub fn write_to_csv(&self, path: &str, source: &str) -> Result<DataManifest, DataError> {
+        let mut csv = csv::Writer::from_path(path)?;
+        for c in self.candles.iter() {
+            csv.write_record([
+                to_millis(c.open_time).to_string(),
+                c.open.to_string(),
+                c.high.to_string(),
+                c.low.to_string(),
+                c.close.to_string(),
+                c.volume.to_string(),
+                to_millis(c.close_time).to_string(),
+            ])?;
+        }
+        csv.flush()?;
+        let path = Path::new(path);
+        let manifest = DataManifest::new(path, source, unix_millis(), self.candles.len())?;
+        manifest.write(path)?;
+        Ok(manifest)
+    }
+    /// 下载[start, end]内的k线写入path，元数据记录下载来源
+    pub fn download_to_csv(
+        market: &FuturesMarket,
+        symbol: &str,
+        interval: Duration,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+        path: &str,
+    ) -> crate::error::Result<Self> {
+        let mut chart = Self::download(market, symbol, interval, start, end)?;
+        let source = format!(
+            "binance fapi/v1/klines {} {} {} - {}",
+            symbol,
+            binance_interval(interval).unwrap_or_default(),
+            to_millis(start),
+            to_millis(end)
+        );
+        chart.provenance = Some(chart.write_to_csv(path, &source)?);
+        Ok(chart)
+    }
+}
+
+#[test]
+fn candle_download_test() {
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let mut chart = CandleChart::new(Duration::minutes(1));
+    chart.candles = (0..3)
+        .map(|i| CandleData {
+            open: 100. + i as f64,
+            high: 101. + i as f64,
+            low: 99. + i as f64,
+            close: 100.5 + i as f64,
+            volume: 10.,
+            open_time: start + Duration::minutes(i),
+            close_time: start + Duration::minutes(i + 1) - Duration::milliseconds(1),
+        })
+        .collect();
+    let dir = std::env::temp_dir().join("hurribot_candle_download_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("BTCUSDT.csv");
+    let path = path.to_str().unwrap();
+    let manifest = chart.write_to_csv(path, "test").unwrap();
+    assert_eq!(manifest.rows, 3);
+    let loaded = CandleChart::read_from_csv(path, Duration::minutes(1)).unwrap();
+    assert_eq!(loaded.provenance, Some(manifest));
+    assert_eq!(loaded.candles.len(), 3);
+    assert_eq!(loaded.candles.close[2], 102.5);
+    assert_eq!(loaded.candles.close_time[2], chart.candles.close_time[2]);
+    // 目录加载时跳过元数据文件，目录本身没有元数据
+    let loaded = CandleChart::read_from_csv(dir.to_str().unwrap(), Duration::minutes(1)).unwrap();
+    assert_eq!(loaded.candles.len(), 3);
+    assert_eq!(loaded.provenance, None);
+    // 数据被修改后校验失败
+    std::fs::write(path, "1700000000000,1,1,1,1,1,1700000059999\n").unwrap();
+    assert!(CandleChart::verify_provenance(path).is_err());
+    assert!(matches!(
+        CandleChart::read_from_csv(path, Duration::minutes(1)),
+        Err(DataError::Checksum { .. })
+    ));
+    assert_eq!(binance_interval(Duration::hours(4)), Some("4h"));
+    assert_eq!(binance_interval(Duration::minutes(7)), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
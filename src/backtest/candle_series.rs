@@ -0,0 +1,140 @@
+use time::OffsetDateTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::candle_chart::CandleData;
+
+/// 列式存储的k线，各字段分别连续存放，便于指标按切片批量计算，CandleChart以此存储
+/// 时间为unix毫秒
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CandleSeries {
+    pub open_time: Vec<i64>,
+    pub close_time: Vec<i64>,
+    pub open: Vec<f64>,
+    pub high: Vec<f64>,
+    pub low: Vec<f64>,
+    pub close: Vec<f64>,
+    pub volume: Vec<f64>,
+}
+
+pub(super) fn unix_millis(t: OffsetDateTime) -> i64 {
+    (t.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+pub(super) fn from_millis(ms: i64) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp_nanos(ms as i128 * 1_000_000)
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+}
+
+impl CandleSeries {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            open_time: Vec::with_capacity(capacity),
+            close_time: Vec::with_capacity(capacity),
+            open: Vec::with_capacity(capacity),
+            high: Vec::with_capacity(capacity),
+            low: Vec::with_capacity(capacity),
+            close: Vec::with_capacity(capacity),
+            volume: Vec::with_capacity(capacity),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.close.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.close.is_empty()
+    }
+    pub fn push(&mut self, candle: &CandleData) {
+        self.open_time.push(unix_millis(candle.open_time));
+        self.close_time.push(unix_millis(candle.close_time));
+        self.open.push(candle.open);
+        self.high.push(candle.high);
+        self.low.push(candle.low);
+        self.close.push(candle.close);
+        self.volume.push(candle.volume);
+    }
+    /// 第i根k线，越界时panic
+    pub fn get(&self, i: usize) -> CandleData {
+        CandleData {
+            open: self.open[i],
+            close: self.close[i],
+            high: self.high[i],
+            low: self.low[i],
+            volume: self.volume[i],
+            open_time: from_millis(self.open_time[i]),
+            close_time: from_millis(self.close_time[i]),
+        }
+    }
+    pub fn first(&self) -> Option<CandleData> {
+        (!self.is_empty()).then(|| self.get(0))
+    }
+    pub fn last(&self) -> Option<CandleData> {
+        self.len().checked_sub(1).map(|i| self.get(i))
+    }
+    /// 逐根还原为CandleData，供按k线更新的策略使用
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = CandleData> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+    /// 只保留f返回true的k线
+    pub fn retain(&mut self, mut f: impl FnMut(&CandleData) -> bool) {
+        *self = self.iter().filter(|c| f(c)).collect();
+    }
+    pub fn to_candles(&self) -> Vec<CandleData> {
+        self.iter().collect()
+    }
+}
+
+impl From<&[CandleData]> for CandleSeries {
+    fn from(candles: &[CandleData]) -> Self {
+        let mut series = Self::with_capacity(candles.len());
+        for c in candles {
+            series.push(c);
+        }
+        series
+    }
+}
+
+impl Extend<CandleData> for CandleSeries {
+    fn extend<I: IntoIterator<Item = CandleData>>(&mut self, iter: I) {
+        for c in iter {
+            self.push(&c);
+        }
+    }
+}
+
+impl FromIterator<CandleData> for CandleSeries {
+    fn from_iter<I: IntoIterator<Item = CandleData>>(iter: I) -> Self {
+        let mut series = Self::default();
+        series.extend(iter);
+        series
+    }
+}
+
+#[test]
+fn candle_series_test() {
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let candles: Vec<_> = (0..5)
+        .map(|i| CandleData {
+            open: i as f64,
+            high: i as f64 + 1.,
+            low: i as f64 - 1.,
+            close: i as f64 + 0.5,
+            volume: 10.,
+            open_time: start + Duration::minutes(i),
+            close_time: start + Duration::minutes(i + 1) - Duration::milliseconds(1),
+        })
+        .collect();
+    let series = CandleSeries::from(candles.as_slice());
+    assert_eq!(series.len(), 5);
+    assert_eq!(series.close, vec![0.5, 1.5, 2.5, 3.5, 4.5]);
+    let restored = series.to_candles();
+    assert_eq!(restored[3].close_time, candles[3].close_time);
+    assert_eq!(restored[3].high, candles[3].high);
+    assert_eq!(series.last(), candles.last().cloned());
+    let mut odd: CandleSeries = candles.iter().cloned().collect();
+    odd.retain(|c| c.open as i64 % 2 == 1);
+    assert_eq!(odd.open, vec![1., 3.]);
+    assert_eq!(odd.close_time[1], unix_millis(candles[3].close_time));
+}
@@ -0,0 +1,315 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use memmap2::Mmap;
+use time::{Duration, OffsetDateTime};
+use tracing::{info, warn};
+
+use super::{
+    candle_chart::{read_csv_file, CandleChart, CandleColumns, CandleData},
+    candle_series::{from_millis, unix_millis, CandleSeries},
+};
+use crate::error::DataError;
+
+const MAGIC: &[u8; 8] = b"HBCANDLE";
+/// 文件格式版本，记录布局变化时需递增
+const VERSION: u32 = 1;
+/// 文件头：magic(8) version(4) 保留(4) interval毫秒(8) k线数(8)
+const HEADER_LEN: usize = 32;
+/// 每根k线：open_time close_time（unix毫秒，i64） open high low close volume（f64），小端
+const RECORD_LEN: usize = 56;
+
+fn invalid(msg: impl Into<String>) -> DataError {
+    DataError::Parse {
+        field: "candle store".into(),
+        msg: msg.into(),
+    }
+}
+
+/// 内存映射的k线库：定长二进制记录按时间升序排列，按需由操作系统分页读入，
+/// 多年的1m数据也无需整体加载到内存
+#[derive(Debug)]
+pub struct CandleStore {
+    mmap: Mmap,
+    interval: Duration,
+    len: usize,
+}
+
+impl CandleStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DataError> {
+        let file = File::open(path.as_ref())?;
+        // 映射期间文件不应被其他进程截断或改写
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < HEADER_LEN || &mmap[..8] != MAGIC {
+            return Err(invalid("not a candle store"));
+        }
+        let version = u32::from_le_bytes(mmap[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(invalid(format!("unsupported version {}", version)));
+        }
+        let interval = Duration::milliseconds(i64::from_le_bytes(mmap[16..24].try_into().unwrap()));
+        let len = u64::from_le_bytes(mmap[24..32].try_into().unwrap()) as usize;
+        if mmap.len() < HEADER_LEN + len * RECORD_LEN {
+            return Err(invalid(format!("truncated, expect {} candles", len)));
+        }
+        #[cfg(unix)]
+        mmap.advise(memmap2::Advice::Sequential).ok();
+        Ok(Self {
+            mmap,
+            interval,
+            len,
+        })
+    }
+    /// 将csv文件或目录转换为k线库，逐个文件读取并按时间排序后写入，不整体加载；
+    /// 目录内文件按文件名顺序读取，时间不晚于上一根的k线（文件间重叠的部分）会被跳过
+    pub fn from_csv(
+        csv_path: &str,
+        store_path: impl AsRef<Path>,
+        interval: Duration,
+        columns: Option<&CandleColumns>,
+    ) -> Result<Self, DataError> {
+        info!("convert csv to candle store: {}", csv_path);
+        let csv_path = Path::new(csv_path);
+        let files: Vec<PathBuf> = if csv_path.is_dir() {
+            let mut files: Vec<PathBuf> = std::fs::read_dir(csv_path)?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.is_file())
+                .collect();
+            files.sort();
+            files
+        } else {
+            vec![csv_path.to_path_buf()]
+        };
+        let mut writer = CandleStoreWriter::create(store_path.as_ref(), interval)?;
+        let mut skipped = 0;
+        for file in files {
+            // 倒序导出（如CryptoDataDownload）的文件排序后再写入
+            let mut candles = vec![];
+            read_csv_file(&file, interval, columns, |c| candles.push(c))?;
+            candles.sort();
+            for c in candles {
+                if writer
+                    .last_open_time
+                    .is_some_and(|t| unix_millis(c.open_time) <= t)
+                {
+                    skipped += 1;
+                    continue;
+                }
+                writer.push(&c)?;
+            }
+        }
+        if skipped > 0 {
+            warn!(
+                "{}: skipped {} out-of-order candles",
+                csv_path.display(),
+                skipped
+            );
+        }
+        writer.finish()?;
+        Self::open(store_path)
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+    fn field(&self, i: usize, offset: usize) -> [u8; 8] {
+        let start = HEADER_LEN + i * RECORD_LEN + offset;
+        self.mmap[start..start + 8].try_into().unwrap()
+    }
+    fn open_millis(&self, i: usize) -> i64 {
+        i64::from_le_bytes(self.field(i, 0))
+    }
+    pub fn get(&self, i: usize) -> Option<CandleData> {
+        if i >= self.len {
+            return None;
+        }
+        let f = |offset| f64::from_le_bytes(self.field(i, offset));
+        Some(CandleData {
+            open_time: from_millis(self.open_millis(i)),
+            close_time: from_millis(i64::from_le_bytes(self.field(i, 8))),
+            open: f(16),
+            high: f(24),
+            low: f(32),
+            close: f(40),
+            volume: f(48),
+        })
+    }
+    /// 开盘时间落在[start, end)内的k线下标范围
+    pub fn range(&self, start: OffsetDateTime, end: OffsetDateTime) -> Range<usize> {
+        let lower = |t: i64| {
+            let (mut lo, mut hi) = (0, self.len);
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                if self.open_millis(mid) < t {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
+            lo
+        };
+        let start = lower(unix_millis(start));
+        start..lower(unix_millis(end)).max(start)
+    }
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = CandleData> + '_ {
+        self.iter_range(0..self.len)
+    }
+    pub fn iter_range(
+        &self,
+        range: Range<usize>,
+    ) -> impl ExactSizeIterator<Item = CandleData> + '_ {
+        let range = range.start.min(self.len)..range.end.min(self.len);
+        range.map(|i| self.get(i).unwrap())
+    }
+    /// 每次解码chunk_size根k线为列式数据，内存占用与总长度无关
+    pub fn chunks(&self, chunk_size: usize) -> impl Iterator<Item = CandleSeries> + '_ {
+        let chunk_size = chunk_size.max(1);
+        (0..self.len).step_by(chunk_size).map(move |start| {
+            let end = (start + chunk_size).min(self.len);
+            let mut series = CandleSeries::with_capacity(end - start);
+            for candle in self.iter_range(start..end) {
+                series.push(&candle);
+            }
+            series
+        })
+    }
+    /// 读取全部k线到内存
+    pub fn to_chart(&self) -> CandleChart {
+        let mut chart = CandleChart::new(self.interval);
+        chart.candles = self.iter().collect();
+        chart
+    }
+}
+
+/// 顺序写入k线库，k线需按开盘时间严格递增
+pub struct CandleStoreWriter {
+    writer: BufWriter<File>,
+    len: u64,
+    last_open_time: Option<i64>,
+}
+
+impl CandleStoreWriter {
+    pub fn create(path: &Path, interval: Duration) -> Result<Self, DataError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[0; 4])?;
+        writer.write_all(&(interval.whole_milliseconds() as i64).to_le_bytes())?;
+        writer.write_all(&0u64.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            len: 0,
+            last_open_time: None,
+        })
+    }
+    pub fn push(&mut self, candle: &CandleData) -> Result<(), DataError> {
+        let open_time = unix_millis(candle.open_time);
+        if self.last_open_time.is_some_and(|t| open_time <= t) {
+            return Err(invalid(format!(
+                "candle at {} out of order",
+                candle.open_time
+            )));
+        }
+        self.writer.write_all(&open_time.to_le_bytes())?;
+        self.writer
+            .write_all(&unix_millis(candle.close_time).to_le_bytes())?;
+        for v in [
+            candle.open,
+            candle.high,
+            candle.low,
+            candle.close,
+            candle.volume,
+        ] {
+            self.writer.write_all(&v.to_le_bytes())?;
+        }
+        self.len += 1;
+        self.last_open_time = Some(open_time);
+        Ok(())
+    }
+    /// 写入k线数并刷盘，未调用finish的文件k线数为0
+    pub fn finish(mut self) -> Result<(), DataError> {
+        self.writer.seek(SeekFrom::Start(24))?;
+        self.writer.write_all(&self.len.to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn candle_store_test() {
+    let dir = std::env::temp_dir().join("hurribot_candle_store_test");
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(dir.join("csv")).unwrap();
+    std::fs::write(
+        dir.join("csv/BTCUSDT-01.csv"),
+        "1704067200000,100,101,99,100.5,10,1704067259999,0,0,0,0,0\n\
+         1704067260000,100.5,102,100,101,12,1704067319999,0,0,0,0,0\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("csv/BTCUSDT-02.csv"),
+        "1704067260000,100.5,102,100,101,12,1704067319999,0,0,0,0,0\n\
+         1704067320000,101,103,100.5,102,8,1704067379999,0,0,0,0,0\n",
+    )
+    .unwrap();
+    let interval = Duration::minutes(1);
+    let store = CandleStore::from_csv(
+        dir.join("csv").to_str().unwrap(),
+        dir.join("BTCUSDT.bin"),
+        interval,
+        None,
+    )
+    .unwrap();
+    // 重复的k线被跳过
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.interval(), interval);
+    let chart =
+        CandleChart::read_from_csv(dir.join("csv/BTCUSDT-02.csv").to_str().unwrap(), interval)
+            .unwrap();
+    let last = store.get(2).unwrap();
+    assert_eq!(last.close_time, chart.candles.get(1).close_time);
+    assert_eq!(last.high, 103.);
+    assert!(store.get(3).is_none());
+    let t = |ms| from_millis(ms);
+    assert_eq!(store.range(t(1704067260000), t(1704067320001)), 1..3);
+    assert_eq!(store.range(t(0), t(1)), 0..0);
+    let chunks: Vec<_> = store.chunks(2).collect();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].close, vec![100.5, 101.]);
+    assert_eq!(chunks[1].volume, vec![8.]);
+    assert_eq!(store.iter().map(|c| c.close).sum::<f64>(), 303.5);
+    // 倒序的文件排序后写入，不丢弃k线
+    std::fs::write(
+        dir.join("desc.csv"),
+        "1704067320000,101,103,100.5,102,8,1704067379999,0,0,0,0,0\n\
+         1704067260000,100.5,102,100,101,12,1704067319999,0,0,0,0,0\n\
+         1704067200000,100,101,99,100.5,10,1704067259999,0,0,0,0,0\n",
+    )
+    .unwrap();
+    let desc = CandleStore::from_csv(
+        dir.join("desc.csv").to_str().unwrap(),
+        dir.join("desc.bin"),
+        interval,
+        None,
+    )
+    .unwrap();
+    assert_eq!(desc.len(), 3);
+    assert_eq!(
+        desc.iter().map(|c| c.close).collect::<Vec<_>>(),
+        [100.5, 101., 102.]
+    );
+    std::fs::write(dir.join("bad.bin"), b"not a store").unwrap();
+    assert!(CandleStore::open(dir.join("bad.bin")).is_err());
+}
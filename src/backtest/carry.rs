@@ -0,0 +1,311 @@
+use time::OffsetDateTime;
+
+use super::{candle_chart::CandleData, contract::Contract, funding::FundingSeries};
+use crate::fee::{FeeSchedule, FillType};
+
+const YEAR_SECONDS: f64 = 365. * 24. * 3600.;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarryConfig {
+    /// 永续空头的杠杆
+    pub leverage: f64,
+    /// 现货多头的杠杆，大于1时借入报价资产买入现货
+    pub spot_leverage: f64,
+    /// 借入资产的年化利率
+    pub borrow_rate: f64,
+    /// 现货手续费率
+    pub spot_fee: f64,
+    /// 最近结算的资金费率不低于该值时开仓
+    pub entry_rate: f64,
+    /// 最近结算的资金费率低于该值时平仓
+    pub exit_rate: f64,
+    /// 价格从开仓价向空头强平价走过该比例时，两腿按现价平仓后重新开仓，补足空头保证金
+    pub rebalance_at: f64,
+}
+
+impl Default for CarryConfig {
+    fn default() -> Self {
+        Self {
+            leverage: 3.,
+            spot_leverage: 1.,
+            borrow_rate: 0.1,
+            spot_fee: 0.001,
+            entry_rate: 0.0001,
+            exit_rate: 0.,
+            rebalance_at: 0.6,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CarryResult {
+    pub initial: f64,
+    /// 全部平仓后的资金
+    pub value: f64,
+    /// 空头收取的资金费用（支付为负）
+    pub funding: f64,
+    /// 借入现货资金的利息
+    pub borrow_cost: f64,
+    /// 两腿开平仓的手续费
+    pub fees: f64,
+    /// 开仓次数，不含再平衡
+    pub entries: usize,
+    pub rebalances: usize,
+    pub liquidations: usize,
+    /// 持仓的总时长（秒）
+    pub held: f64,
+    /// 回测区间的时长（秒）
+    pub span: f64,
+}
+
+impl CarryResult {
+    pub fn return_rate(&self) -> f64 {
+        self.value / self.initial
+    }
+    /// 两腿价格盈亏之和，对冲完全时为0，强平或滑点造成偏离
+    pub fn basis_pnl(&self) -> f64 {
+        self.value - self.initial - self.funding + self.borrow_cost + self.fees
+    }
+    /// 按回测区间年化的单利收益率
+    pub fn apr(&self) -> f64 {
+        if self.span <= 0. {
+            return 0.;
+        }
+        (self.return_rate() - 1.) * YEAR_SECONDS / self.span
+    }
+}
+
+/// 现货多头（以永续多头的标的价格近似）加等量永续空头的中性仓位
+#[derive(Debug, Clone)]
+struct CarryPosition {
+    short: Contract,
+    /// 借入的报价资产，含已计利息
+    debt: f64,
+}
+
+impl CarryPosition {
+    fn qty(&self) -> f64 {
+        self.short.amount
+    }
+}
+
+/// 资金费率套利回测：资金费率足够高时买入现货并做空等量永续，持有期间按历史资金费率收取资金费用，
+/// 现货借款按年化利率计息；空头接近强平时两腿再平衡，空头被强平时现货一并卖出
+#[derive(Debug, Clone)]
+pub struct CarryBacktest {
+    config: CarryConfig,
+    capital: f64,
+    fees: FeeSchedule,
+}
+
+impl CarryBacktest {
+    pub fn new(config: CarryConfig, capital: f64) -> Self {
+        Self {
+            config,
+            capital,
+            fees: FeeSchedule::default(),
+        }
+    }
+    pub fn set_fees(&mut self, fees: FeeSchedule) {
+        self.fees = fees;
+    }
+    /// 两腿都以taker成交，名义价值N满足 N/杠杆 + N·永续费率 + N/现货杠杆 + N·现货费率 = 资金
+    fn open(&self, capital: f64, price: f64, time: OffsetDateTime) -> (CarryPosition, f64) {
+        let c = &self.config;
+        let perp_fee = self.fees.rate(FillType::Taker);
+        let notional =
+            capital / (1. / c.leverage + perp_fee + 1. / c.spot_leverage.max(1.) + c.spot_fee);
+        let short = Contract::open_as(
+            false,
+            price,
+            notional / c.leverage * (1. + c.leverage * perp_fee),
+            c.leverage,
+            time,
+            None,
+            self.fees,
+            FillType::Taker,
+        );
+        let debt = notional - notional / c.spot_leverage.max(1.);
+        let fees = short.entry_fee() + notional * c.spot_fee;
+        (CarryPosition { short, debt }, fees)
+    }
+    /// 两腿按price市价平仓，返回（返还资金，手续费）
+    fn close(&self, position: &CarryPosition, price: f64) -> (f64, f64) {
+        let spot = position.qty() * price;
+        let fees = spot * self.config.spot_fee + self.fees.fee(spot, FillType::Taker);
+        let returned = spot * (1. - self.config.spot_fee) - position.debt
+            + position.short.cover_as(price, FillType::Taker);
+        (returned, fees)
+    }
+    /// candles按时间升序，funding为同一币种的历史资金费率，结束时按最后的收盘价平仓
+    pub fn run(&self, candles: &[CandleData], funding: &FundingSeries) -> CarryResult {
+        let mut result = CarryResult {
+            initial: self.capital,
+            value: 0.,
+            funding: 0.,
+            borrow_cost: 0.,
+            fees: 0.,
+            entries: 0,
+            rebalances: 0,
+            liquidations: 0,
+            held: 0.,
+            span: match (candles.first(), candles.last()) {
+                (Some(f), Some(l)) => (l.close_time - f.close_time).as_seconds_f64(),
+                _ => 0.,
+            },
+        };
+        let mut cash = self.capital;
+        let mut position: Option<CarryPosition> = None;
+        let mut prev: Option<OffsetDateTime> = None;
+        for candle in candles {
+            let now = candle.close_time;
+            if let (Some(p), Some(prev)) = (position.as_mut(), prev) {
+                let dt = (now - prev).as_seconds_f64();
+                result.held += dt;
+                for r in funding.settlements(prev, now) {
+                    let fee = p.short.funding(r.rate, candle.close);
+                    cash += fee;
+                    result.funding += fee;
+                }
+                let interest = p.debt * self.config.borrow_rate * dt / YEAR_SECONDS;
+                p.debt += interest;
+                result.borrow_cost += interest;
+            }
+            if let Some(p) = &position {
+                if let Some(r) = p.short.liquidate(candle.high) {
+                    // 空头被强平，现货按收盘价卖出还款
+                    let spot = p.qty() * candle.close;
+                    let perp_fee = self.fees.fee(p.qty() * p.short.liq_price, FillType::Taker);
+                    cash += r + spot * (1. - self.config.spot_fee) - p.debt;
+                    result.fees += spot * self.config.spot_fee + perp_fee;
+                    result.liquidations += 1;
+                    position = None;
+                }
+            }
+            let rate = funding.rate_at(now);
+            let carry = rate.is_some_and(|r| r >= self.config.entry_rate);
+            match &position {
+                Some(p) if !rate.is_some_and(|r| r >= self.config.exit_rate) => {
+                    let (returned, fees) = self.close(p, candle.close);
+                    cash += returned;
+                    result.fees += fees;
+                    position = None;
+                }
+                Some(p) => {
+                    let short = &p.short;
+                    let trigger = short.entry_price
+                        + (short.liq_price - short.entry_price) * self.config.rebalance_at;
+                    if candle.close >= trigger {
+                        let (returned, fees) = self.close(p, candle.close);
+                        let (reopened, open_fees) = self.open(cash + returned, candle.close, now);
+                        result.fees += fees + open_fees;
+                        result.rebalances += 1;
+                        cash = 0.;
+                        position = Some(reopened);
+                    }
+                }
+                None if carry && cash > 0. => {
+                    let (opened, fees) = self.open(cash, candle.close, now);
+                    result.fees += fees;
+                    result.entries += 1;
+                    cash = 0.;
+                    position = Some(opened);
+                }
+                None => {}
+            }
+            prev = Some(now);
+        }
+        if let (Some(p), Some(last)) = (&position, candles.last()) {
+            let (returned, fees) = self.close(p, last.close);
+            cash += returned;
+            result.fees += fees;
+        }
+        result.value = cash;
+        result
+    }
+}
+
+#[test]
+fn carry_backtest_test() {
+    use super::funding::FundingData;
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let chart = |closes: &[f64]| -> Vec<CandleData> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| CandleData {
+                open: close,
+                high: close,
+                low: close,
+                close,
+                open_time: start + Duration::hours(i as i64),
+                close_time: start + Duration::hours(i as i64 + 1) - Duration::milliseconds(1),
+                ..Default::default()
+            })
+            .collect()
+    };
+    let funding = |rates: &[f64]| FundingSeries {
+        symbol: "BTCUSDT".to_string(),
+        rates: rates
+            .iter()
+            .enumerate()
+            .map(|(i, &rate)| FundingData {
+                time: start + Duration::hours(8 * i as i64),
+                rate,
+            })
+            .collect(),
+    };
+    let no_fees = FeeSchedule {
+        maker: 0.,
+        taker: 0.,
+        discount: 1.,
+    };
+    let config = CarryConfig {
+        borrow_rate: 0.,
+        spot_fee: 0.,
+        ..Default::default()
+    };
+    let mut backtest = CarryBacktest::new(config.clone(), 1000.);
+    backtest.set_fees(no_fees);
+
+    // 价格不变，第一根k线开仓，之后收取8次资金费用：名义价值750，每次0.075
+    let result = backtest.run(&chart(&[100.; 72]), &funding(&[0.0001; 9]));
+    assert_eq!(result.entries, 1);
+    assert!((result.funding - 0.6).abs() < 1e-9);
+    assert!((result.value - 1000.6).abs() < 1e-9);
+    assert!(result.apr() > 0.);
+
+    // 价格上涨30%时多次再平衡，两腿盈亏对冲
+    let rising: Vec<f64> = (0..72).map(|i| 100. * 1.005f64.powi(i)).collect();
+    let result = backtest.run(&chart(&rising), &funding(&[0.0001; 9]));
+    assert!(result.rebalances >= 1);
+    assert_eq!(result.liquidations, 0);
+    assert!(result.basis_pnl().abs() < 1e-6);
+
+    // 资金费率转负后平仓且不再开仓
+    let result = backtest.run(
+        &chart(&[100.; 72]),
+        &funding(&[0.0001, 0.0001, 0.0001, 0.0001, -0.0001, 0.00005]),
+    );
+    assert_eq!(result.entries, 1);
+    // 32小时结算时支付一次后平仓
+    assert!((result.funding - 0.075 * 2.).abs() < 1e-9);
+    assert!((result.held - 32. * 3600.).abs() < 1e-6);
+
+    // 借入现货资金：名义价值1200，借款600计息
+    let mut backtest = CarryBacktest::new(
+        CarryConfig {
+            spot_leverage: 2.,
+            borrow_rate: 0.1,
+            ..config
+        },
+        1000.,
+    );
+    backtest.set_fees(no_fees);
+    let result = backtest.run(&chart(&[100.; 72]), &funding(&[0.0001; 9]));
+    assert!((result.funding - 0.12 * 8.).abs() < 1e-9);
+    assert!(result.borrow_cost > 0.45 && result.borrow_cost < 0.5);
+    assert!(result.basis_pnl().abs() < 1e-9);
+}
@@ -0,0 +1,178 @@
+use plotters::prelude::*;
+use time::OffsetDateTime;
+
+use super::{candle_chart::CandleChart, engine::Backtest, strategy::Strategy};
+
+#[derive(Debug, Clone)]
+pub struct RunMetrics {
+    pub name: String,
+    pub initial_value: f64,
+    pub final_value: f64,
+    pub max_drawdown: f64,
+    /// 资金归零提前结束
+    pub aborted: bool,
+}
+
+impl RunMetrics {
+    pub fn return_rate(&self) -> f64 {
+        if self.initial_value > 0. {
+            self.final_value / self.initial_value
+        } else {
+            f64::NAN
+        }
+    }
+}
+
+/// 多组策略在同一k线上的回测对比结果，资金曲线按采样点对齐
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub times: Vec<OffsetDateTime>,
+    /// 与metrics一一对应
+    pub curves: Vec<Vec<f64>>,
+    pub metrics: Vec<RunMetrics>,
+}
+
+/// 每隔sample_every根k线记录一次资金
+pub fn compare(
+    chart: &CandleChart,
+    runs: &mut [(&str, &mut dyn Strategy)],
+    sample_every: usize,
+) -> Comparison {
+    let backtest = Backtest {
+        report_every: sample_every.max(1),
+        ..Default::default()
+    };
+    let total = chart.candles.len();
+    let points: Vec<usize> = (1..=total)
+        .filter(|p| p % backtest.report_every == 0 || *p == total)
+        .collect();
+    let mut curves = vec![];
+    let mut metrics = vec![];
+    for (name, strategy) in runs.iter_mut() {
+        let initial_value = strategy.value();
+        let mut reports = vec![];
+        let result = backtest.run_stream(*strategy, chart.candles.iter(), total, |p| {
+            reports.push((p.index, p.equity));
+        });
+        // 提前结束的曲线按最后的资金补齐
+        let curve = points
+            .iter()
+            .map(|&p| {
+                let i = reports.partition_point(|(index, _)| *index <= p);
+                i.checked_sub(1).map_or(initial_value, |i| reports[i].1)
+            })
+            .collect();
+        curves.push(curve);
+        metrics.push(RunMetrics {
+            name: name.to_string(),
+            initial_value,
+            final_value: result.value,
+            max_drawdown: result.max_drawdown,
+            aborted: result.aborted,
+        });
+    }
+    Comparison {
+        times: points
+            .iter()
+            .map(|&p| chart.candles.get(p - 1).close_time)
+            .collect(),
+        curves,
+        metrics,
+    }
+}
+
+impl Comparison {
+    /// 对比表格
+    pub fn table(&self) -> String {
+        let mut table = format!(
+            "{:<20}{:>14}{:>14}{:>10}{:>10}{:>8}\n",
+            "name", "initial", "final", "return", "max dd", "abort"
+        );
+        for m in self.metrics.iter() {
+            table += &format!(
+                "{:<20}{:>14.2}{:>14.2}{:>10.4}{:>9.2}%{:>8}\n",
+                m.name,
+                m.initial_value,
+                m.final_value,
+                m.return_rate(),
+                m.max_drawdown * 100.,
+                m.aborted
+            );
+        }
+        table
+    }
+    /// 叠加绘制各组资金曲线，横轴为距开始的小时数
+    pub fn plot(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let root_area = BitMapBackend::new(path, (1280, 720)).into_drawing_area();
+        root_area.fill(&WHITE)?;
+        let Some(start) = self.times.first() else {
+            return Ok(());
+        };
+        let hours: Vec<f64> = self
+            .times
+            .iter()
+            .map(|t| (*t - *start).as_seconds_f64() / 3600.)
+            .collect();
+        let max_x = hours.last().copied().unwrap_or(1.).max(1.);
+        let values = self.curves.iter().flatten();
+        let min_y = values.clone().copied().fold(f64::INFINITY, f64::min);
+        let max_y = values.copied().fold(f64::NEG_INFINITY, f64::max);
+        let mut chart = ChartBuilder::on(&root_area)
+            .caption("Equity", ("sans-serif", 40).into_font())
+            .x_label_area_size(35)
+            .y_label_area_size(60)
+            .build_cartesian_2d(0f64..max_x, min_y.min(0.)..max_y.max(1.))?;
+        chart.configure_mesh().x_desc("hours").draw()?;
+        for (i, (curve, metrics)) in self.curves.iter().zip(self.metrics.iter()).enumerate() {
+            let color = Palette99::pick(i).to_rgba();
+            chart
+                .draw_series(LineSeries::new(
+                    hours.iter().copied().zip(curve.iter().copied()),
+                    color,
+                ))?
+                .label(metrics.name.clone())
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        }
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+        root_area.present()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn compare_test() {
+    use super::candle_chart::CandleData;
+    use time::Duration;
+
+    #[derive(Debug)]
+    struct Hold(f64, f64);
+    impl Strategy for Hold {
+        fn update(&mut self, candle: &CandleData) {
+            self.0 = candle.close * self.1;
+        }
+        fn value(&self) -> f64 {
+            self.0
+        }
+    }
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let mut chart = CandleChart::new(Duration::minutes(1));
+    chart.candles = [100., 110., 0., 120., 130.]
+        .iter()
+        .enumerate()
+        .map(|(i, &close)| CandleData {
+            close,
+            close_time: start + Duration::minutes(i as i64),
+            ..Default::default()
+        })
+        .collect();
+    let (mut a, mut b) = (Hold(100., 1.), Hold(100., 2.));
+    let result = compare(&chart, &mut [("a", &mut a), ("b", &mut b)], 2);
+    assert_eq!(result.times.len(), 3);
+    assert!(result.curves.iter().all(|c| c.len() == 3));
+    assert!(result.metrics[0].aborted);
+    assert!(result.table().contains('b'));
+}
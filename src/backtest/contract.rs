@@ -1,10 +1,13 @@
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use tracing::error;
 
-/// 手续费率（maker为0.02%，taker为0.05%，随VIP等级变化）
-pub const HANDLING_FEE_RATE_MAKER: f64 = 0.0002;
-pub const HANDLING_FEE_RATE_TAKER: f64 = 0.0005;
-#[derive(Debug, Clone)]
+use crate::{
+    fee::{FeeSchedule, FillType},
+    liquidation::MarginBrackets,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contract {
     pub is_bull: bool,
     /// 保证金
@@ -13,7 +16,7 @@ pub struct Contract {
     pub entry_price: f64,
     /// 开仓时间
     pub open_time: OffsetDateTime,
-    /// 强平价格（维持保证金 = 0.4% * 初始名义价值）
+    /// 逐仓强平价格（按默认维持保证金阶梯计算）
     pub liq_price: f64,
     /// 合约数量（合约数量 * 现价 = 名义价值）
     pub amount: f64,
@@ -21,25 +24,77 @@ pub struct Contract {
     pub leverage: f64,
     /// 止损价格（需大于强平价格）
     pub stop_loss: Option<f64>,
+    /// 止损限价，触发后挂限价单以maker成交；None为市价止损
+    #[serde(default)]
+    pub stop_limit: Option<f64>,
+    /// 手续费率
+    pub fees: FeeSchedule,
+    /// 开仓成交类型
+    #[serde(default)]
+    pub entry_fill: FillType,
 }
 impl Contract {
+    /// 按默认费率（VIP0）挂单开仓
     pub fn open(
+        is_bull: bool,
+        entry_price: f64,
+        offered_balance: f64,
+        leverage: f64,
+        open_time: OffsetDateTime,
+        stop_loss: Option<f64>,
+    ) -> Self {
+        Self::open_with_fees(
+            is_bull,
+            entry_price,
+            offered_balance,
+            leverage,
+            open_time,
+            stop_loss,
+            FeeSchedule::default(),
+        )
+    }
+    /// 挂单开仓
+    pub fn open_with_fees(
+        is_bull: bool,
+        entry_price: f64,
+        offered_balance: f64,
+        leverage: f64,
+        open_time: OffsetDateTime,
+        stop_loss: Option<f64>,
+        fees: FeeSchedule,
+    ) -> Self {
+        Self::open_as(
+            is_bull,
+            entry_price,
+            offered_balance,
+            leverage,
+            open_time,
+            stop_loss,
+            fees,
+            FillType::Maker,
+        )
+    }
+    /// 按成交类型收取开仓手续费，市价开仓为Taker
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_as(
         is_bull: bool,
         entry_price: f64,
         offered_balance: f64,
         leverage: f64,
         open_time: OffsetDateTime,
         mut stop_loss: Option<f64>,
+        fees: FeeSchedule,
+        entry_fill: FillType,
     ) -> Self {
         // 初始保证金 + 手续费消耗 = 提供资金；手续费消耗 = 初始保证金 * 杠杆 * 手续费率
         // 由上面两个公式可得：初始保证金 = 提供资金 / (1 + 杠杆 * 手续费率)
-        let margin = offered_balance / (1. + leverage * HANDLING_FEE_RATE_MAKER);
-        let liq_price = if is_bull {
-            entry_price * (1. - 1. / leverage) + entry_price * 0.004
-        } else {
-            entry_price * (1. + 1. / leverage) - entry_price * 0.004
-        };
+        let margin = offered_balance / (1. + leverage * fees.rate(entry_fill));
         let amount = margin * leverage / entry_price;
+        let side = if is_bull { 1. } else { -1. };
+        // 默认阶梯非空，不会出错
+        let liq_price = MarginBrackets::default()
+            .liq_price(side * amount, entry_price, margin, 0.)
+            .expect("default margin brackets");
         if let Some(sl) = stop_loss {
             if (is_bull && sl < liq_price) || (!is_bull && sl > liq_price) {
                 error!("stop loss price exceeds liquidation price");
@@ -55,36 +110,220 @@ impl Contract {
             amount,
             leverage,
             stop_loss,
+            stop_limit: None,
+            fees,
+            entry_fill,
+        }
+    }
+    /// 止损改为限价单，限价为止损价向亏损方向偏移offset比例，越过强平价时仍为市价止损
+    pub fn with_stop_limit(mut self, offset: f64) -> Self {
+        let Some(sl) = self.stop_loss else {
+            return self;
+        };
+        let limit = if self.is_bull {
+            sl * (1. - offset)
+        } else {
+            sl * (1. + offset)
+        };
+        if (self.is_bull && limit < self.liq_price) || (!self.is_bull && limit > self.liq_price) {
+            error!("stop limit price exceeds liquidation price");
+            return self;
         }
+        self.stop_limit = Some(limit);
+        self
+    }
+    /// 开仓手续费
+    pub fn entry_fee(&self) -> f64 {
+        self.fees
+            .fee(self.amount * self.entry_price, self.entry_fill)
     }
     /// 止损平仓或强制平仓，强制平仓有15%的强平费用，所以尽量确保不要强平
     pub fn liquidate(&self, price: f64) -> Option<f64> {
-        if let Some(stop_loss) = self.stop_loss {
-            if (self.is_bull && price < stop_loss) || (!self.is_bull && price > stop_loss) {
-                return Some(self.cover(stop_loss));
-            }
+        if let Some(r) = self.stop_out(price) {
+            return Some(r);
         }
         if (self.is_bull && price < self.liq_price) || (!self.is_bull && price > self.liq_price) {
-            return Some(self.cover(self.liq_price) * 0.85);
+            return Some(self.cover_as(self.liq_price, FillType::Taker) * 0.85);
         }
         None
     }
+    /// 仅判断止损；止损限价单触发时价格已越过止损价，限价单立即与盘口成交，按taker费率和限价计算，
+    /// 价格跳空越过限价时不成交，仓位继续持有直到回到限价内或被强平
+    /// （不记录触发状态，价格回到止损价另一侧后视为未触发）
+    pub fn stop_out(&self, price: f64) -> Option<f64> {
+        self.stop_fill(price)
+            .map(|fill| self.cover_as(fill, FillType::Taker))
+    }
+    /// 价格到达price时止损单的成交价，未触发或跳空越过限价未成交时为None
+    fn stop_fill(&self, price: f64) -> Option<f64> {
+        let stop_loss = self.stop_loss?;
+        if (self.is_bull && price >= stop_loss) || (!self.is_bull && price <= stop_loss) {
+            return None;
+        }
+        match self.stop_limit {
+            None => Some(stop_loss),
+            Some(limit)
+                if (self.is_bull && price >= limit) || (!self.is_bull && price <= limit) =>
+            {
+                Some(limit)
+            }
+            Some(_) => None,
+        }
+    }
+    /// 在price平仓时的实际成交价：触发止损为止损价或限价，强平为强平价，否则为price
+    pub fn exit_price(&self, price: f64) -> f64 {
+        if let Some(fill) = self.stop_fill(price) {
+            return fill;
+        }
+        if (self.is_bull && price < self.liq_price) || (!self.is_bull && price > self.liq_price) {
+            return self.liq_price;
+        }
+        price
+    }
     pub fn close(&self, price: f64) -> f64 {
+        self.close_as(price, FillType::Maker)
+    }
+    /// 未触发止损或强平时按成交类型平仓，如市价平仓为Taker
+    pub fn close_as(&self, price: f64, fill: FillType) -> f64 {
         if let Some(r) = self.liquidate(price) {
             return r;
         }
-        self.cover(price)
+        self.cover_as(price, fill)
+    }
+    /// 按比例平仓，返回释放的保证金加已实现盈亏（扣除手续费），剩余仓位的保证金与数量同比例减少
+    /// 若该价格触发止损或强平，则全部平仓
+    pub fn close_partial(&mut self, price: f64, fraction: f64) -> f64 {
+        let fraction = fraction.clamp(0., 1.);
+        let r = match self.liquidate(price) {
+            Some(r) => r,
+            None if fraction == 1. => self.cover(price),
+            None => {
+                let closed = Self {
+                    margin: self.margin * fraction,
+                    amount: self.amount * fraction,
+                    ..self.clone()
+                };
+                self.margin -= closed.margin;
+                self.amount -= closed.amount;
+                self.liq_price = self.liquidation_price();
+                return closed.cover(price);
+            }
+        };
+        self.margin = 0.;
+        self.amount = 0.;
+        r
+    }
+    /// 逐仓强平价格
+    fn liquidation_price(&self) -> f64 {
+        let side = if self.is_bull { 1. } else { -1. };
+        MarginBrackets::default()
+            .liq_price(side * self.amount, self.entry_price, self.margin, 0.)
+            .expect("default margin brackets")
+    }
+    /// 按资金费率和标记价格结算一次资金费用，收入为正（费率为正时多头支付、空头收取）
+    pub fn funding(&self, rate: f64, price: f64) -> f64 {
+        let fee = self.amount * price * rate;
+        if self.is_bull {
+            -fee
+        } else {
+            fee
+        }
     }
     /// 理想状态是只做挂单且不会被穿透，但实盘会有这两种风险
-    fn cover(&self, price: f64) -> f64 {
+    pub fn cover(&self, price: f64) -> f64 {
+        self.cover_as(price, FillType::Maker)
+    }
+    /// 按成交类型计算平仓手续费，返回保证金加盈亏
+    pub fn cover_as(&self, price: f64, fill: FillType) -> f64 {
+        let fee = self.fees.fee(self.amount * price, fill);
         if self.is_bull {
-            self.amount * (price - self.entry_price) + self.margin
-                - self.amount * price * HANDLING_FEE_RATE_MAKER
+            self.amount * (price - self.entry_price) + self.margin - fee
         } else {
-            self.amount * (self.entry_price - price) + self.margin
-                - self.amount * price * HANDLING_FEE_RATE_MAKER
+            self.amount * (self.entry_price - price) + self.margin - fee
+        }
+    }
+}
+
+/// 检查合约的资金计算不变量，prices为模拟的价格路径，返回首个违反的不变量：
+/// 开仓价平仓返还保证金减手续费、强平价在开仓价亏损一侧、止损价和止损限价不越过强平价、
+/// 平仓返还随价格单调（多头不减、空头不增）、亏损不超过保证金
+pub fn verify(contract: &Contract, prices: &[f64]) -> Result<(), String> {
+    const EPS: f64 = 1e-9;
+    let tolerance = EPS * contract.margin.abs().max(1.);
+    let side = if contract.is_bull { 1. } else { -1. };
+    let notional = contract.amount * contract.entry_price;
+    if (notional - contract.margin * contract.leverage).abs() > tolerance * contract.leverage {
+        return Err(format!(
+            "notional {} != margin {} * leverage {}",
+            notional, contract.margin, contract.leverage
+        ));
+    }
+    let expected = contract.margin - contract.fees.fee(notional, FillType::Maker);
+    let at_entry = contract.cover(contract.entry_price);
+    if (at_entry - expected).abs() > tolerance {
+        return Err(format!(
+            "cover at entry {} != margin minus fees {}",
+            at_entry, expected
+        ));
+    }
+    if contract.liquidate(contract.entry_price).is_none()
+        && (contract.close(contract.entry_price) - at_entry).abs() > tolerance
+    {
+        return Err("close at entry differs from cover".into());
+    }
+    if side * (contract.entry_price - contract.liq_price) < 0. {
+        return Err(format!(
+            "liquidation price {} on the profit side of entry {}",
+            contract.liq_price, contract.entry_price
+        ));
+    }
+    if let Some(sl) = contract.stop_loss {
+        if side * (sl - contract.liq_price) < 0. {
+            return Err(format!(
+                "stop loss {} beyond liquidation price {}",
+                sl, contract.liq_price
+            ));
+        }
+    }
+    if let Some(limit) = contract.stop_limit {
+        if side * (limit - contract.liq_price) < 0. {
+            return Err(format!(
+                "stop limit {} beyond liquidation price {}",
+                limit, contract.liq_price
+            ));
+        }
+    }
+    let mut sorted: Vec<f64> = prices.iter().copied().filter(|p| p.is_finite()).collect();
+    sorted.sort_by(f64::total_cmp);
+    let mut last: Option<(f64, f64)> = None;
+    for price in sorted {
+        let r = contract.close(price);
+        if r < -tolerance {
+            return Err(format!(
+                "close at {} returns {}, loss exceeds margin {}",
+                price, r, contract.margin
+            ));
         }
+        if let Some((last_price, last_r)) = last {
+            // 跳空越过止损限价后按maker平仓，可能比在限价按taker止损多出手续费之差
+            let gap = match contract.stop_limit {
+                Some(_) => {
+                    let notional = contract.amount * price;
+                    contract.fees.fee(notional, FillType::Taker)
+                        - contract.fees.fee(notional, FillType::Maker)
+                }
+                None => 0.,
+            };
+            if side * (r - last_r) < -tolerance - gap {
+                return Err(format!(
+                    "close not monotonic: {} at {} then {} at {}",
+                    last_r, last_price, r, price
+                ));
+            }
+        }
+        last = Some((price, r));
     }
+    Ok(())
 }
 
 #[test]
@@ -100,3 +339,120 @@ fn contract_test() {
     println!("{:?}", offer);
     println!("{:?}", offer.liquidate(9.));
 }
+
+#[test]
+fn close_partial_test() {
+    let open_time = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let mut contract = Contract::open(true, 100., 100., 10., open_time, None);
+    let whole = contract.clone().close(110.);
+    let liq_price = contract.liq_price;
+    let first = contract.close_partial(110., 0.25);
+    assert!((contract.liq_price - liq_price).abs() < 1e-9);
+    assert!((contract.amount - contract.margin * 10. / 100.).abs() < 1e-9);
+    let second = contract.close_partial(110., 1.);
+    assert!((first + second - whole).abs() < 1e-9);
+    assert_eq!(contract.amount, 0.);
+}
+
+#[test]
+fn fee_schedule_contract_test() {
+    let open_time = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let vip0 = Contract::open(true, 100., 100., 10., open_time, Some(95.));
+    let vip9 = Contract::open_with_fees(
+        true,
+        100.,
+        100.,
+        10.,
+        open_time,
+        Some(95.),
+        FeeSchedule::vip(9, true),
+    );
+    assert_eq!(vip9.margin, 100.);
+    assert!(vip9.close(110.) > vip0.close(110.));
+    // 止损按taker费率成交
+    let stop = vip0.stop_out(94.).unwrap();
+    let expected = vip0.amount * (95. - 100.) + vip0.margin - vip0.amount * 95. * 0.0005;
+    assert!((stop - expected).abs() < 1e-9);
+}
+
+#[test]
+fn stop_limit_test() {
+    let open_time = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let contract =
+        Contract::open(true, 100., 100., 10., open_time, Some(95.)).with_stop_limit(0.01);
+    assert_eq!(contract.stop_limit, Some(95. * 0.99));
+    // 触发后在限价内立即成交，按taker费率
+    let stop = contract.stop_out(94.5).unwrap();
+    assert!((stop - contract.cover_as(95. * 0.99, FillType::Taker)).abs() < 1e-9);
+    // 跳空越过限价不成交，仓位继续暴露直到强平
+    assert_eq!(contract.stop_out(94.), None);
+    assert_eq!(contract.liquidate(94.), None);
+    assert!(contract.liquidate(contract.liq_price - 1.).is_some());
+    let short = Contract::open(false, 100., 100., 10., open_time, Some(105.)).with_stop_limit(0.01);
+    assert!(short.stop_out(105.5).is_some());
+    assert_eq!(short.stop_out(106.5), None);
+    // 平仓记录的成交价
+    assert_eq!(contract.exit_price(94.5), 95. * 0.99);
+    assert_eq!(contract.exit_price(94.), 94.);
+    assert_eq!(contract.exit_price(1.), contract.liq_price);
+    assert_eq!(contract.exit_price(110.), 110.);
+    let market_stop = Contract::open(true, 100., 100., 10., open_time, Some(95.));
+    assert_eq!(market_stop.exit_price(93.), 95.);
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn contract_invariants_test(
+        is_bull: bool,
+        entry_price in 0.01f64..100_000.,
+        offered_balance in 1f64..100_000.,
+        leverage in 1f64..20.,
+        stop_distance in proptest::option::of(0f64..0.2),
+        stop_limit in proptest::option::of(0f64..0.05),
+        vip in 0u8..10,
+        prices in proptest::collection::vec(0.5f64..1.5, 1..64),
+    ) {
+        let side = if is_bull { 1. } else { -1. };
+        let stop_loss = stop_distance.map(|d| entry_price * (1. - side * d));
+        let contract = Contract::open_with_fees(
+            is_bull,
+            entry_price,
+            offered_balance,
+            leverage,
+            OffsetDateTime::from_unix_timestamp(0).unwrap(),
+            stop_loss,
+            FeeSchedule::vip(vip, false),
+        );
+        let contract = match stop_limit {
+            Some(offset) => contract.with_stop_limit(offset),
+            None => contract,
+        };
+        let prices: Vec<f64> = prices.iter().map(|r| r * entry_price).collect();
+        if let Err(e) = verify(&contract, &prices) {
+            panic!("{}: {:?}", e, contract);
+        }
+    }
+
+    #[test]
+    fn liquidation_monotonic_test(
+        is_bull: bool,
+        entry_price in 1f64..100_000.,
+        offered_balance in 1f64..10_000.,
+        leverage in 1f64..20.,
+    ) {
+        let open = |leverage| {
+            Contract::open(
+                is_bull,
+                entry_price,
+                offered_balance,
+                leverage,
+                OffsetDateTime::from_unix_timestamp(0).unwrap(),
+                None,
+            )
+        };
+        // 杠杆越高，强平价越接近开仓价
+        let distance = |c: &Contract| (c.entry_price - c.liq_price).abs();
+        proptest::prop_assert!(distance(&open(leverage)) >= distance(&open(leverage * 1.5)));
+    }
+}
@@ -0,0 +1,120 @@
+use std::{fs::File, path::Path};
+
+use tracing::info;
+
+use crate::{
+    algorithm::{Algorithm, DepthData, SignalData},
+    error::DataError,
+};
+
+/// 录制的盘口快照，用于回测中重放盘口类算法
+#[derive(Debug, Default)]
+pub struct DepthChart {
+    pub depths: Vec<DepthData>,
+}
+
+impl DepthChart {
+    /// time        事件时间（unix毫秒）
+    /// symbol      币种
+    /// bids        买盘，格式为 价格:数量|价格:数量...
+    /// asks        卖盘，格式同上
+    pub fn read_from_csv(path: &str) -> Self {
+        fn parse_levels(s: &str) -> Vec<(f64, f64)> {
+            s.split('|')
+                .filter(|l| !l.is_empty())
+                .map(|l| {
+                    let (price, qty) = l.split_once(':').unwrap();
+                    (price.parse().unwrap(), qty.parse().unwrap())
+                })
+                .collect()
+        }
+        fn read_from_csv_file(path: &Path) -> Vec<DepthData> {
+            let file = File::open(path).unwrap();
+            let mut csv = csv::Reader::from_reader(file);
+            let mut depths = vec![];
+            for d in csv.records() {
+                let d = d.unwrap();
+                depths.push(DepthData {
+                    time: d.get(0).unwrap().parse().unwrap(),
+                    symbol: d.get(1).unwrap().to_string(),
+                    bids: parse_levels(d.get(2).unwrap()),
+                    asks: parse_levels(d.get(3).unwrap()),
+                });
+            }
+            depths
+        }
+        info!("read depth from csv: {}", path);
+        let path = Path::new(path);
+        let mut chart = Self::default();
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_file() {
+                    chart.depths.append(&mut read_from_csv_file(&path));
+                }
+            }
+        } else if path.is_file() {
+            chart.depths = read_from_csv_file(path);
+        } else {
+            panic!("invalid path: {}", path.display());
+        }
+        chart.depths.sort_by_key(|d| d.time);
+        chart
+    }
+    pub fn write_to_csv(&self, path: &str) -> Result<(), DataError> {
+        fn format_levels(levels: &[(f64, f64)]) -> String {
+            levels
+                .iter()
+                .map(|(price, qty)| format!("{}:{}", price, qty))
+                .collect::<Vec<_>>()
+                .join("|")
+        }
+        let mut csv = csv::Writer::from_path(path)?;
+        csv.write_record(["time", "symbol", "bids", "asks"])?;
+        for d in self.depths.iter() {
+            csv.write_record([
+                d.time.to_string(),
+                d.symbol.clone(),
+                format_levels(&d.bids),
+                format_levels(&d.asks),
+            ])?;
+        }
+        csv.flush()?;
+        Ok(())
+    }
+    /// 按时间顺序将盘口快照喂给算法，返回产生的信号
+    pub fn replay(&self, algorithm: &dyn Algorithm) -> Vec<SignalData> {
+        self.depths
+            .iter()
+            .filter_map(|d| algorithm.update_depth(d))
+            .collect()
+    }
+}
+
+#[test]
+fn depth_chart_test() {
+    use crate::algorithm::imbalance::ImbalanceAlgo;
+
+    let chart = DepthChart {
+        depths: vec![
+            DepthData {
+                symbol: "BTCUSDT".to_string(),
+                time: 0,
+                bids: vec![(100., 1.)],
+                asks: vec![(100.1, 2.)],
+            },
+            DepthData {
+                symbol: "BTCUSDT".to_string(),
+                time: 100,
+                bids: vec![(100., 3.)],
+                asks: vec![(100.1, 1.), (100.2, 1.)],
+            },
+        ],
+    };
+    let path = std::env::temp_dir().join("hurribot_depth_chart_test.csv");
+    chart.write_to_csv(path.to_str().unwrap()).unwrap();
+    let read = DepthChart::read_from_csv(path.to_str().unwrap());
+    assert_eq!(read.depths.len(), 2);
+    assert_eq!(read.depths[1].asks, vec![(100.1, 1.), (100.2, 1.)]);
+    assert_eq!(read.replay(&ImbalanceAlgo::default()).len(), 2);
+}
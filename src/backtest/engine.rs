@@ -0,0 +1,971 @@
+use crossbeam::channel::Sender;
+use rayon::prelude::*;
+use time::OffsetDateTime;
+
+use tracing::{info, warn};
+
+use crate::{
+    capital::{Capital, CapitalPolicy},
+    deleverage::{Deleverage, DeleverageConfig},
+};
+
+use super::{
+    candle_chart::CandleData,
+    merged::MergedCandles,
+    strategy::{CapitalExhausted, Strategy},
+};
+
+#[derive(Debug, Clone)]
+pub struct BacktestProgress {
+    /// 已处理k线数
+    pub index: usize,
+    pub total: usize,
+    /// 完成百分比（0~100）
+    pub percent: f64,
+    pub time: OffsetDateTime,
+    pub price: f64,
+    /// 当前资金
+    pub equity: f64,
+    /// 当前回撤（相对资金最高点）
+    pub drawdown: f64,
+    /// 至今最大回撤
+    pub max_drawdown: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestResult {
+    pub value: f64,
+    pub max_drawdown: f64,
+    /// 资金归零提前结束
+    pub aborted: bool,
+    /// 实际处理的k线数，含预热
+    pub processed: usize,
+    /// 共享资金池不足，之后的k线不再交给策略
+    pub exhausted: Option<CapitalExhausted>,
+    /// 资金策略从策略中取出的储备，已计入value
+    pub reserve: f64,
+    /// 闲置资金和储备累计的理财利息，已计入value
+    pub interest: f64,
+    /// 回撤降杠杆的级别变化次数
+    pub deleverages: usize,
+}
+
+/// 多币种流式回测结果，回撤按各策略资金之和计算
+#[derive(Debug, Clone)]
+pub struct MergedResult {
+    pub result: BacktestResult,
+    /// 与策略一一对应，按各自最后的收盘价平仓后的资金（含储备）
+    pub values: Vec<f64>,
+}
+
+/// 回测中按资金策略在策略闲置资金与储备之间调拨
+#[derive(Debug, Clone)]
+pub(super) struct Reserve {
+    capital: Capital,
+    amount: f64,
+    /// 上次计息的k线收盘时间
+    accrued_at: Option<OffsetDateTime>,
+    interest: f64,
+}
+
+impl Reserve {
+    /// value为策略的初始资金
+    pub(super) fn new(policy: CapitalPolicy, value: f64) -> Self {
+        Self {
+            capital: Capital::new(policy, value),
+            amount: 0.,
+            accrued_at: None,
+            interest: 0.,
+        }
+    }
+    /// 策略不支持调拨时资金策略不生效，按复利计算并给出警告
+    fn of<S: Strategy + ?Sized>(policy: CapitalPolicy, strategy: &S) -> Self {
+        let policy = match strategy.idle_capital() {
+            None if policy != CapitalPolicy::Compound => {
+                warn!(
+                    "Strategy does not support capital transfer, capital policy {:?} falls back to compound",
+                    policy
+                );
+                CapitalPolicy::Compound
+            }
+            _ => policy,
+        };
+        Self::new(policy, strategy.value())
+    }
+    pub(super) fn amount(&self) -> f64 {
+        self.amount
+    }
+    fn equity<S: Strategy + ?Sized>(&self, strategy: &S) -> f64 {
+        strategy.value() + self.amount
+    }
+    /// 使策略资金value接近资金策略允许的可交易资金，只能取出闲置资金idle，
+    /// 返回应注入策略的资金（负数为取出）
+    pub(super) fn transfer(&mut self, value: f64, idle: f64) -> f64 {
+        // 复利不产生储备
+        if self.capital.policy() == CapitalPolicy::Compound {
+            return 0.;
+        }
+        let equity = value + self.amount;
+        self.capital.update(equity);
+        let amount = (self.capital.trading(equity) - value)
+            .min(self.amount)
+            .max(-idle);
+        self.amount -= amount;
+        amount
+    }
+    fn rebalance<S: Strategy + ?Sized>(&mut self, strategy: &mut S) {
+        let Some(idle) = strategy.idle_capital() else {
+            return;
+        };
+        let amount = self.transfer(strategy.value(), idle);
+        if amount != 0. {
+            strategy.transfer_capital(amount);
+        }
+    }
+    /// 按年化收益率为上根k线以来的闲置资金和储备计息，利息计入各自的资金
+    fn accrue<S: Strategy + ?Sized>(&mut self, strategy: &mut S, apr: f64, time: OffsetDateTime) {
+        let Some(last) = self.accrued_at.replace(time) else {
+            return;
+        };
+        let years = (time - last).as_seconds_f64() / YEAR_SECONDS;
+        if apr <= 0. || years <= 0. {
+            return;
+        }
+        let idle = strategy.idle_capital().unwrap_or(0.).max(0.) * apr * years;
+        if idle > 0. {
+            strategy.transfer_capital(idle);
+        }
+        let reserve = self.amount.max(0.) * apr * years;
+        self.amount += reserve;
+        self.interest += idle + reserve;
+    }
+}
+
+/// 多币种回测中的单个策略，同一时刻的k线可与其他策略并行处理
+struct Leg<'a> {
+    strategy: &'a mut (dyn Strategy + Send),
+    reserve: Reserve,
+    /// 已收到的k线数，用于预热
+    seen: usize,
+    last_close: Option<f64>,
+    /// 本批交给该策略的k线
+    pending: Vec<CandleData>,
+    /// 本批中有预热之外的k线
+    traded: bool,
+    /// 本批中因资金池不足暂停
+    paused: Option<CapitalExhausted>,
+}
+
+impl<'a> Leg<'a> {
+    fn new(reserve: Reserve, strategy: &'a mut (dyn Strategy + Send)) -> Self {
+        Self {
+            strategy,
+            reserve,
+            seen: 0,
+            last_close: None,
+            pending: vec![],
+            traded: false,
+            paused: None,
+        }
+    }
+    /// 处理本批的k线，只改动自身的状态
+    fn step(&mut self, warm_up: usize, apr: f64) {
+        for candle in std::mem::take(&mut self.pending) {
+            self.seen += 1;
+            if self.seen <= warm_up {
+                feed_warm_up(&mut *self.strategy, &candle);
+                continue;
+            }
+            self.traded = true;
+            self.reserve
+                .accrue(&mut *self.strategy, apr, candle.close_time);
+            if self.strategy.capital_exhausted().is_none() {
+                self.strategy.update(&candle);
+                self.reserve.rebalance(&mut *self.strategy);
+                self.paused = self.strategy.capital_exhausted();
+            }
+            self.last_close = Some(candle.close);
+        }
+    }
+}
+
+/// 预热中策略资金不应变化（开仓、手续费等），变化时说明策略的warm_up实现有误
+fn feed_warm_up<S: Strategy + ?Sized>(strategy: &mut S, candle: &CandleData) {
+    let value = strategy.value();
+    strategy.warm_up(candle);
+    if strategy.value() != value {
+        warn!(
+            "Strategy capital changed during warm-up at {}: {} -> {}",
+            candle.close_time,
+            value,
+            strategy.value()
+        );
+    }
+}
+
+/// 计息按365天一年
+const YEAR_SECONDS: f64 = 365. * 24. * 3600.;
+
+#[derive(Debug, Clone)]
+pub struct Backtest {
+    /// 每隔多少根k线汇报一次进度
+    pub report_every: usize,
+    /// 资金归零时提前结束
+    pub abort_on_zero: bool,
+    /// 盈利复利或留作储备，同一策略可在不同资金管理规则下比较
+    pub capital_policy: CapitalPolicy,
+    /// 闲置资金（策略的idle_capital及储备）的年化收益率，模拟存入活期理财（如LDUSDT），0为不计息
+    pub idle_apr: f64,
+    /// 多币种回测并行处理同一时刻k线的线程数，1为单线程；策略之间不能共享可变状态
+    /// （如GeoBasket的资金池），否则结果取决于调度
+    pub threads: usize,
+    /// 开头的k线数只用于预热指标，不开仓、不计息，资金和回撤从预热结束后开始统计；
+    /// 多币种回测按每个来源分别计数
+    pub warm_up: usize,
+    /// 按组合回撤分级缩小开仓金额，降到0时全部平仓，None为不限制
+    pub deleverage: Option<DeleverageConfig>,
+    /// 命令行进度条
+    #[cfg(feature = "progress-bar")]
+    pub progress_bar: bool,
+}
+
+impl Default for Backtest {
+    fn default() -> Self {
+        Self {
+            report_every: 10000,
+            abort_on_zero: true,
+            capital_policy: CapitalPolicy::default(),
+            idle_apr: 0.,
+            threads: 1,
+            warm_up: 0,
+            deleverage: None,
+            #[cfg(feature = "progress-bar")]
+            progress_bar: false,
+        }
+    }
+}
+
+impl Backtest {
+    pub fn run<S, F>(
+        &self,
+        strategy: &mut S,
+        candles: &[CandleData],
+        on_progress: F,
+    ) -> BacktestResult
+    where
+        S: Strategy + ?Sized,
+        F: FnMut(&BacktestProgress),
+    {
+        self.run_stream(
+            strategy,
+            candles.iter().cloned(),
+            candles.len(),
+            on_progress,
+        )
+    }
+    /// 逐根读取k线回测，不要求全部k线驻留内存（如内存映射的k线库），total仅用于进度
+    pub fn run_stream<S, I, F>(
+        &self,
+        strategy: &mut S,
+        candles: I,
+        total: usize,
+        mut on_progress: F,
+    ) -> BacktestResult
+    where
+        S: Strategy + ?Sized,
+        I: IntoIterator<Item = CandleData>,
+        F: FnMut(&BacktestProgress),
+    {
+        #[cfg(feature = "progress-bar")]
+        let bar = self.progress_bar.then(|| {
+            let bar = indicatif::ProgressBar::new(total as u64);
+            bar.set_style(
+                indicatif::ProgressStyle::with_template(
+                    "[{elapsed_precise}] {bar:40} {pos}/{len} {msg}",
+                )
+                .unwrap(),
+            );
+            bar
+        });
+        let mut max_equity: f64 = 0.;
+        let mut max_drawdown: f64 = 0.;
+        let mut aborted = false;
+        let mut processed = 0;
+        let mut last_close = None;
+        let mut exhausted = None;
+        let mut reserve = Reserve::of(self.capital_policy, strategy);
+        let mut deleverage = self.deleverage.clone().map(Deleverage::new);
+        let mut deleverages = 0;
+        for (i, candle) in candles.into_iter().enumerate() {
+            if i < self.warm_up {
+                feed_warm_up(strategy, &candle);
+                processed = i + 1;
+                #[cfg(feature = "progress-bar")]
+                if let Some(bar) = &bar {
+                    bar.set_position(processed as u64);
+                }
+                continue;
+            }
+            reserve.accrue(strategy, self.idle_apr, candle.close_time);
+            if exhausted.is_none() {
+                strategy.update(&candle);
+                reserve.rebalance(strategy);
+                exhausted = strategy.capital_exhausted();
+                if let Some(e) = &exhausted {
+                    warn!("Strategy paused: {}", e);
+                }
+            }
+            last_close = Some(candle.close);
+            processed = i + 1;
+            let equity = reserve.equity(strategy);
+            max_equity = max_equity.max(equity);
+            let drawdown = if max_equity > 0. {
+                1. - equity / max_equity
+            } else {
+                0.
+            };
+            max_drawdown = max_drawdown.max(drawdown);
+            let time = (candle.close_time.unix_timestamp_nanos() / 1_000_000) as u64;
+            if let Some(scale) = deleverage.as_mut().and_then(|d| d.update(equity, time)) {
+                info!("Drawdown {:.2}%, entry scale {}", drawdown * 100., scale);
+                strategy.scale_entry(scale);
+                if scale <= 0. {
+                    strategy.close(candle.close);
+                }
+                deleverages += 1;
+            }
+            // 暂停的策略不算资金归零
+            aborted = self.abort_on_zero && exhausted.is_none() && equity <= 0.;
+            if processed % self.report_every.max(1) == 0 || processed == total || aborted {
+                let progress = BacktestProgress {
+                    index: processed,
+                    total,
+                    percent: processed as f64 / total.max(processed) as f64 * 100.,
+                    time: candle.close_time,
+                    price: candle.close,
+                    equity,
+                    drawdown,
+                    max_drawdown,
+                };
+                #[cfg(feature = "progress-bar")]
+                if let Some(bar) = &bar {
+                    bar.set_position(processed as u64);
+                    bar.set_message(format!(
+                        "equity: {:.2}, drawdown: {:.2}%",
+                        equity,
+                        drawdown * 100.
+                    ));
+                }
+                on_progress(&progress);
+            }
+            if aborted {
+                break;
+            }
+        }
+        #[cfg(feature = "progress-bar")]
+        if let Some(bar) = bar {
+            bar.finish();
+        }
+        let value = match last_close {
+            Some(close) => strategy.close(close),
+            None => strategy.value(),
+        };
+        BacktestResult {
+            value: value + reserve.amount,
+            max_drawdown,
+            aborted,
+            processed,
+            exhausted,
+            reserve: reserve.amount,
+            interest: reserve.interest,
+            deleverages,
+        }
+    }
+    /// 多币种流式回测：strategies与candles的来源一一对应，k线按收盘时间交错交给对应的策略，
+    /// 不保留历史k线；资金池不足的策略暂停，其余继续；进度中的资金为各策略之和。
+    /// 收盘时间相同的一批k线按threads并行交给各自的策略，之后按批内次序汇总，
+    /// 资金、回撤和进度在每批之后统计，结果与线程数无关
+    pub fn run_merged<I, F>(
+        &self,
+        strategies: &mut [&mut (dyn Strategy + Send)],
+        mut candles: MergedCandles<I>,
+        total: usize,
+        mut on_progress: F,
+    ) -> MergedResult
+    where
+        I: Iterator<Item = CandleData>,
+        F: FnMut(&BacktestProgress),
+    {
+        assert_eq!(candles.width(), strategies.len(), "one source per strategy");
+        let pool = (self.threads > 1)
+            .then(|| {
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(self.threads)
+                    .build()
+            })
+            .and_then(|pool| {
+                pool.map_err(|e| warn!("Build backtest thread pool failed: {}", e))
+                    .ok()
+            });
+        let mut legs: Vec<Leg> = strategies
+            .iter_mut()
+            .map(|s| Leg::new(Reserve::of(self.capital_policy, &**s), &mut **s))
+            .collect();
+        let mut max_equity: f64 = 0.;
+        let mut max_drawdown: f64 = 0.;
+        let mut aborted = false;
+        let mut processed = 0;
+        let mut exhausted = None;
+        let mut deleverage = self.deleverage.clone().map(Deleverage::new);
+        let mut deleverages = 0;
+        loop {
+            let batch = candles.next_batch();
+            let Some((_, last)) = batch.last() else {
+                break;
+            };
+            let (time, price) = (last.close_time, last.close);
+            let order: Vec<usize> = batch.iter().map(|(i, _)| *i).collect();
+            for (i, candle) in batch {
+                legs[i].pending.push(candle);
+            }
+            let step = |leg: &mut Leg| leg.step(self.warm_up, self.idle_apr);
+            match &pool {
+                Some(pool) => pool.install(|| legs.par_iter_mut().for_each(step)),
+                None => legs.iter_mut().for_each(step),
+            }
+            let before = processed;
+            let mut traded = false;
+            for &i in order.iter() {
+                processed += 1;
+                let leg = &mut legs[i];
+                traded |= std::mem::take(&mut leg.traded);
+                if let Some(e) = leg.paused.take() {
+                    warn!("Strategy {} paused: {}", i, e);
+                    exhausted.get_or_insert(e);
+                }
+            }
+            // 整批都在预热时不统计
+            if !traded {
+                continue;
+            }
+            let equity: f64 = legs.iter().map(|l| l.reserve.equity(&*l.strategy)).sum();
+            max_equity = max_equity.max(equity);
+            let drawdown = if max_equity > 0. {
+                1. - equity / max_equity
+            } else {
+                0.
+            };
+            max_drawdown = max_drawdown.max(drawdown);
+            let millis = (time.unix_timestamp_nanos() / 1_000_000) as u64;
+            if let Some(scale) = deleverage.as_mut().and_then(|d| d.update(equity, millis)) {
+                info!("Drawdown {:.2}%, entry scale {}", drawdown * 100., scale);
+                for leg in legs.iter_mut() {
+                    leg.strategy.scale_entry(scale);
+                    if let (true, Some(close)) = (scale <= 0., leg.last_close) {
+                        leg.strategy.close(close);
+                    }
+                }
+                deleverages += 1;
+            }
+            aborted = self.abort_on_zero && equity <= 0.;
+            let every = self.report_every.max(1);
+            if processed / every != before / every || processed == total || aborted {
+                on_progress(&BacktestProgress {
+                    index: processed,
+                    total,
+                    percent: processed as f64 / total.max(processed) as f64 * 100.,
+                    time,
+                    price,
+                    equity,
+                    drawdown,
+                    max_drawdown,
+                });
+            }
+            if aborted {
+                break;
+            }
+        }
+        let values: Vec<f64> = legs
+            .iter_mut()
+            .map(|leg| {
+                let value = match leg.last_close {
+                    Some(close) => leg.strategy.close(close),
+                    None => leg.strategy.value(),
+                };
+                value + leg.reserve.amount
+            })
+            .collect();
+        MergedResult {
+            result: BacktestResult {
+                value: values.iter().sum(),
+                max_drawdown,
+                aborted,
+                processed,
+                exhausted,
+                reserve: legs.iter().map(|l| l.reserve.amount).sum(),
+                interest: legs.iter().map(|l| l.reserve.interest).sum(),
+                deleverages,
+            },
+            values,
+        }
+    }
+    /// 通过通道推送进度，接收端断开不影响回测
+    pub fn run_with_channel<S>(
+        &self,
+        strategy: &mut S,
+        candles: &[CandleData],
+        progress_tx: Sender<BacktestProgress>,
+    ) -> BacktestResult
+    where
+        S: Strategy + ?Sized,
+    {
+        self.run(strategy, candles, |p| {
+            progress_tx.send(p.clone()).ok();
+        })
+    }
+}
+
+#[test]
+fn backtest_progress_test() {
+    use time::Duration;
+
+    #[derive(Debug)]
+    struct Hold(f64);
+    impl Strategy for Hold {
+        fn update(&mut self, candle: &CandleData) {
+            self.0 = candle.close - 50.;
+        }
+        fn value(&self) -> f64 {
+            self.0
+        }
+    }
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let candles: Vec<_> = [100., 120., 90., 60., 40., 80.]
+        .iter()
+        .enumerate()
+        .map(|(i, &close)| CandleData {
+            close,
+            close_time: start + Duration::minutes(i as i64),
+            ..Default::default()
+        })
+        .collect();
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let backtest = Backtest {
+        report_every: 2,
+        ..Default::default()
+    };
+    let result = backtest.run_with_channel(&mut Hold(0.), &candles, tx);
+    assert!(result.aborted);
+    assert_eq!(result.processed, 5);
+    let progress: Vec<_> = rx.try_iter().collect();
+    assert_eq!(progress.len(), 3);
+    assert_eq!(progress[1].index, 4);
+    assert!((progress[1].drawdown - (1. - 10. / 70.)).abs() < 1e-9);
+}
+
+#[test]
+fn backtest_capital_exhausted_test() {
+    use std::sync::{Arc, Mutex};
+
+    use super::strategy::geo_strategy::GeoStrategy;
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let candles: Vec<_> = (0..10)
+        .map(|i| CandleData {
+            open: 100.,
+            high: 100.,
+            low: 100.,
+            close: 100.,
+            close_time: start + Duration::minutes(i),
+            ..Default::default()
+        })
+        .collect();
+    let pool = Arc::new(Mutex::new(5.));
+    let mut strategy = GeoStrategy::new(true, 10., 1., Duration::ZERO, 10., 0.05, 0.5, pool);
+    let result = Backtest::default().run(&mut strategy, &candles, |_| {});
+    // 资金池不足时暂停而不是panic或提前结束
+    assert!(!result.aborted);
+    assert_eq!(result.processed, 10);
+    let exhausted = result.exhausted.unwrap();
+    assert_eq!((exhausted.need, exhausted.available), (10., 5.));
+    assert_eq!(exhausted.time, candles[0].close_time);
+}
+
+#[test]
+fn backtest_merged_test() {
+    use super::strategy::breakout_strategy::BreakoutStrategy;
+    use crate::strategy::breakout::BreakoutConfig;
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    // A横盘后放量突破，B一直横盘
+    let chart = |offset: i64, breakout: bool| {
+        (0..40).map(move |i| {
+            let (close, volume) = match i {
+                25.. if breakout => (100. + (i - 24) as f64 * 5., 40.),
+                _ => (100., 10.),
+            };
+            CandleData {
+                open: close,
+                high: close + 1.,
+                low: close - 1.,
+                close,
+                volume,
+                open_time: start + Duration::minutes(i),
+                close_time: start + Duration::minutes(i + 1) + Duration::seconds(offset),
+            }
+        })
+    };
+    let config = BreakoutConfig {
+        period: 10,
+        atr_period: 5,
+        ..Default::default()
+    };
+    let mut a = BreakoutStrategy::new(true, config, 1000., 2.);
+    let mut b = BreakoutStrategy::new(true, config, 500., 2.);
+    let candles = MergedCandles::new(vec![chart(0, true), chart(1, false)]);
+    let mut times = vec![];
+    let backtest = Backtest {
+        report_every: 1,
+        ..Default::default()
+    };
+    let merged = backtest.run_merged(&mut [&mut a, &mut b], candles, 80, |p| {
+        times.push(p.time);
+    });
+    assert_eq!(merged.result.processed, 80);
+    assert!(times.windows(2).all(|w| w[0] <= w[1]));
+    assert_eq!(a.open_count, 1);
+    assert_eq!(b.open_count, 0);
+    assert!(merged.values[0] > 1000.);
+    assert_eq!(merged.values[1], 500.);
+    assert!((merged.result.value - merged.values.iter().sum::<f64>()).abs() < 1e-9);
+}
+
+#[test]
+fn backtest_capital_policy_test() {
+    /// 每根k线闲置资金增长10%
+    struct Grow(f64);
+    impl Strategy for Grow {
+        fn update(&mut self, _: &CandleData) {
+            self.0 *= 1.1;
+        }
+        fn value(&self) -> f64 {
+            self.0
+        }
+        fn idle_capital(&self) -> Option<f64> {
+            Some(self.0)
+        }
+        fn transfer_capital(&mut self, amount: f64) {
+            self.0 += amount;
+        }
+    }
+    let candles = vec![CandleData::default(); 3];
+    let run = |policy| {
+        let backtest = Backtest {
+            capital_policy: policy,
+            ..Default::default()
+        };
+        backtest.run(&mut Grow(100.), &candles, |_| {})
+    };
+    let compound = run(CapitalPolicy::Compound);
+    assert!((compound.value - 133.1).abs() < 1e-9);
+    assert_eq!(compound.reserve, 0.);
+    let fixed = run(CapitalPolicy::Fixed);
+    assert!((fixed.value - 130.).abs() < 1e-9);
+    assert!((fixed.reserve - 30.).abs() < 1e-9);
+    // 第二根k线权益达到115以上时提升资金并取回储备
+    let ratchet = run(CapitalPolicy::Ratchet { step: 0.15 });
+    assert!((ratchet.value - 132.).abs() < 1e-9);
+    assert!((ratchet.reserve - 12.).abs() < 1e-9);
+    // 不支持调拨的策略按复利计算
+    struct Locked(Grow);
+    impl Strategy for Locked {
+        fn update(&mut self, candle: &CandleData) {
+            self.0.update(candle);
+        }
+        fn value(&self) -> f64 {
+            self.0.value()
+        }
+    }
+    let locked = Backtest {
+        capital_policy: CapitalPolicy::Fixed,
+        ..Default::default()
+    }
+    .run(&mut Locked(Grow(100.)), &candles, |_| {});
+    assert!((locked.value - 133.1).abs() < 1e-9);
+    assert_eq!(locked.reserve, 0.);
+}
+
+#[test]
+fn backtest_idle_interest_test() {
+    use time::Duration;
+
+    /// 全部资金闲置，不交易
+    struct Idle(f64);
+    impl Strategy for Idle {
+        fn update(&mut self, _: &CandleData) {}
+        fn value(&self) -> f64 {
+            self.0
+        }
+        fn idle_capital(&self) -> Option<f64> {
+            Some(self.0)
+        }
+        fn transfer_capital(&mut self, amount: f64) {
+            self.0 += amount;
+        }
+    }
+    let start = OffsetDateTime::UNIX_EPOCH;
+    let candles: Vec<CandleData> = (0..=365)
+        .map(|d| CandleData {
+            close_time: start + Duration::days(d),
+            ..Default::default()
+        })
+        .collect();
+    let backtest = Backtest {
+        idle_apr: 0.1,
+        ..Default::default()
+    };
+    let result = backtest.run(&mut Idle(1000.), &candles, |_| {});
+    // 按日复利
+    let expected = 1000. * (1f64 + 0.1 / 365.).powi(365);
+    assert!((result.value - expected).abs() < 1e-6);
+    assert!((result.interest - (expected - 1000.)).abs() < 1e-6);
+    // 储备同样计息
+    let fixed = Backtest {
+        capital_policy: CapitalPolicy::Fixed,
+        ..backtest.clone()
+    }
+    .run(&mut Idle(1000.), &candles, |_| {});
+    assert!((fixed.value - expected).abs() < 1e-6);
+    assert!(fixed.reserve > 0.);
+    // 默认不计息
+    let none = Backtest::default().run(&mut Idle(1000.), &candles, |_| {});
+    assert_eq!((none.value, none.interest), (1000., 0.));
+}
+
+#[test]
+fn backtest_deleverage_test() {
+    use crate::deleverage::DrawdownStage;
+
+    /// 价值跟随k线收盘价，记录开仓比例和平仓
+    #[derive(Default)]
+    struct Follow {
+        value: f64,
+        scale: Vec<f64>,
+        closed: bool,
+    }
+    impl Strategy for Follow {
+        fn update(&mut self, candle: &CandleData) {
+            if !self.closed {
+                self.value = candle.close;
+            }
+        }
+        fn close(&mut self, _: f64) -> f64 {
+            self.closed = true;
+            self.value
+        }
+        fn value(&self) -> f64 {
+            self.value
+        }
+        fn scale_entry(&mut self, scale: f64) {
+            self.scale.push(scale);
+        }
+    }
+    let candles: Vec<CandleData> = [100., 95., 89., 93., 79., 120.]
+        .into_iter()
+        .map(|close| CandleData {
+            close,
+            ..Default::default()
+        })
+        .collect();
+    let backtest = Backtest {
+        deleverage: Some(DeleverageConfig {
+            stages: vec![
+                DrawdownStage {
+                    drawdown: 0.1,
+                    scale: 0.5,
+                },
+                DrawdownStage {
+                    drawdown: 0.2,
+                    scale: 0.,
+                },
+            ],
+            recovery: 0.02,
+            resume_after: None,
+        }),
+        ..Default::default()
+    };
+    let mut strategy = Follow::default();
+    let result = backtest.run(&mut strategy, &candles, |_| {});
+    assert_eq!(strategy.scale, [0.5, 1., 0.]);
+    assert_eq!(result.deleverages, 3);
+    // 降到0后平仓，之后的上涨不再计入
+    assert!(strategy.closed);
+    assert_eq!(result.value, 79.);
+}
+
+#[test]
+fn backtest_warm_up_test() {
+    /// 允许开仓时资金跟随收盘价
+    struct Trader {
+        allowed: bool,
+        value: f64,
+        seen: usize,
+        entries: usize,
+    }
+    impl Strategy for Trader {
+        fn update(&mut self, candle: &CandleData) {
+            self.seen += 1;
+            if self.allowed {
+                self.entries += 1;
+                self.value = candle.close;
+            }
+        }
+        fn value(&self) -> f64 {
+            self.value
+        }
+        fn warm_up(&mut self, candle: &CandleData) {
+            self.allowed = false;
+            self.update(candle);
+            self.allowed = true;
+        }
+    }
+    let trader = || Trader {
+        allowed: true,
+        value: 100.,
+        seen: 0,
+        entries: 0,
+    };
+    let candles: Vec<CandleData> = [100., 10., 100., 110.]
+        .into_iter()
+        .map(|close| CandleData {
+            close,
+            ..Default::default()
+        })
+        .collect();
+    let mut strategy = trader();
+    let result = Backtest::default().run(&mut strategy, &candles, |_| {});
+    assert!((result.max_drawdown - 0.9).abs() < 1e-9);
+    let mut strategy = trader();
+    let mut reports = 0;
+    let result = Backtest {
+        warm_up: 2,
+        report_every: 1,
+        ..Default::default()
+    }
+    .run(&mut strategy, &candles, |_| reports += 1);
+    // 预热的k线交给策略但不开仓，也不计入回撤和进度
+    assert_eq!((strategy.seen, strategy.entries), (4, 2));
+    assert_eq!(result.max_drawdown, 0.);
+    assert_eq!((result.processed, reports), (4, 2));
+    assert_eq!(result.value, 110.);
+    // 未实现预热的策略跳过预热的k线
+    struct Plain(Trader);
+    impl Strategy for Plain {
+        fn update(&mut self, candle: &CandleData) {
+            self.0.update(candle);
+        }
+        fn value(&self) -> f64 {
+            self.0.value()
+        }
+    }
+    let mut strategy = Plain(trader());
+    let result = Backtest {
+        warm_up: 2,
+        ..Default::default()
+    }
+    .run(&mut strategy, &candles, |_| {});
+    assert_eq!((strategy.0.seen, strategy.0.entries), (2, 2));
+    assert_eq!(result.max_drawdown, 0.);
+}
+
+#[test]
+fn backtest_merged_determinism_test() {
+    use super::strategy::breakout_strategy::BreakoutStrategy;
+    use crate::strategy::breakout::BreakoutConfig;
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    // 各币种k线时间相同，突破的时点和幅度不同
+    let chart = |seed: i64| -> Vec<CandleData> {
+        (0..60)
+            .map(|i| {
+                let step = ((i * 7 + seed * 13) % 11 - 5) as f64;
+                let close = 100.
+                    + step
+                    + if i > 20 + seed {
+                        (i - 20) as f64 * 3.
+                    } else {
+                        0.
+                    };
+                CandleData {
+                    open: close,
+                    high: close + 2.,
+                    low: close - 2.,
+                    close,
+                    volume: 10. + (i % 7) as f64 * 5.,
+                    open_time: start + Duration::minutes(i),
+                    close_time: start + Duration::minutes(i + 1),
+                }
+            })
+            .collect()
+    };
+    let symbols = ["EUSDT", "AUSDT", "DUSDT", "BUSDT", "FUSDT", "CUSDT"];
+    let charts: Vec<Vec<CandleData>> = (0..symbols.len() as i64).map(chart).collect();
+    let config = BreakoutConfig {
+        period: 10,
+        atr_period: 5,
+        ..Default::default()
+    };
+    let run = |threads: usize, order: &[usize]| {
+        let mut strategies: Vec<BreakoutStrategy> = order
+            .iter()
+            .map(|&i| BreakoutStrategy::new(i % 2 == 0, config, 100. * (i + 1) as f64, 3.))
+            .collect();
+        let mut refs: Vec<&mut (dyn Strategy + Send)> = strategies
+            .iter_mut()
+            .map(|s| s as &mut (dyn Strategy + Send))
+            .collect();
+        let candles = MergedCandles::with_symbols(
+            order
+                .iter()
+                .map(|&i| charts[i].clone().into_iter())
+                .collect(),
+            &order.iter().map(|&i| symbols[i]).collect::<Vec<_>>(),
+        );
+        let mut progress = vec![];
+        let backtest = Backtest {
+            threads,
+            warm_up: 5,
+            report_every: 1,
+            ..Default::default()
+        };
+        let merged = backtest.run_merged(&mut refs, candles, 360, |p| {
+            progress.push((p.index, p.equity, p.max_drawdown));
+        });
+        let mut by_symbol: Vec<(&str, f64)> = order
+            .iter()
+            .zip(merged.values.iter())
+            .map(|(&i, v)| (symbols[i], *v))
+            .collect();
+        by_symbol.sort_by(|a, b| a.0.cmp(b.0));
+        (
+            merged.result.processed,
+            merged.result.max_drawdown,
+            progress,
+            by_symbol,
+        )
+    };
+    let order: Vec<usize> = (0..symbols.len()).collect();
+    let single = run(1, &order);
+    assert_eq!(single.0, 360);
+    // 同一时刻的k线作为一批处理，预热之后每批汇报一次
+    assert_eq!(single.2.len(), 55);
+    assert!(single.1 > 0.);
+    assert_eq!(run(4, &order), single);
+    assert_eq!(run(6, &order), single);
+    // 来源顺序不影响各币种的结果
+    let reversed: Vec<usize> = order.iter().rev().copied().collect();
+    assert_eq!(run(4, &reversed).3, single.3);
+}
@@ -0,0 +1,119 @@
+use super::candle_chart::CandleData;
+use crate::{fee::FillType, market::TimeInForce};
+
+/// 限价单提交或k线内触价的结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitFill {
+    Filled {
+        price: f64,
+        fill: FillType,
+    },
+    /// 挂在盘口等待触价
+    Resting,
+    /// IOC/FOK未能立即成交
+    Cancelled,
+    /// GTX会立即成交
+    Rejected,
+}
+
+/// 回测中的限价单，盘口深度未知，IOC与FOK均视为全部成交或全部撤销
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimitOrder {
+    pub is_buy: bool,
+    pub price: f64,
+    pub time_in_force: TimeInForce,
+}
+
+impl LimitOrder {
+    pub fn new(is_buy: bool, price: f64, time_in_force: TimeInForce) -> Self {
+        Self {
+            is_buy,
+            price,
+            time_in_force,
+        }
+    }
+    fn crosses(&self, market_price: f64) -> bool {
+        if self.is_buy {
+            self.price >= market_price
+        } else {
+            self.price <= market_price
+        }
+    }
+    /// 按当前价格提交，会交叉时以当前价格taker成交（GTX拒绝）
+    pub fn submit(&self, market_price: f64) -> LimitFill {
+        if self.crosses(market_price) {
+            if self.time_in_force.takes_on_cross() {
+                LimitFill::Filled {
+                    price: market_price,
+                    fill: FillType::Taker,
+                }
+            } else {
+                LimitFill::Rejected
+            }
+        } else if self.time_in_force.rests() {
+            LimitFill::Resting
+        } else {
+            LimitFill::Cancelled
+        }
+    }
+    /// 挂单在k线内触价时以maker成交，跳空越过挂单价时按开盘价成交
+    pub fn touch(&self, candle: &CandleData) -> Option<LimitFill> {
+        let touched = if self.is_buy {
+            candle.low <= self.price
+        } else {
+            candle.high >= self.price
+        };
+        if !touched {
+            return None;
+        }
+        let price = if self.crosses(candle.open) {
+            candle.open
+        } else {
+            self.price
+        };
+        Some(LimitFill::Filled {
+            price,
+            fill: FillType::Maker,
+        })
+    }
+}
+
+#[test]
+fn limit_order_test() {
+    let filled = |price, fill| LimitFill::Filled { price, fill };
+    // 卖出止盈价高于当前价，不交叉
+    for (tif, expect) in [
+        (TimeInForce::Gtc, LimitFill::Resting),
+        (TimeInForce::Gtx, LimitFill::Resting),
+        (TimeInForce::Ioc, LimitFill::Cancelled),
+        (TimeInForce::Fok, LimitFill::Cancelled),
+    ] {
+        assert_eq!(LimitOrder::new(false, 110., tif).submit(100.), expect);
+    }
+    // 买入价高于当前价会立即成交，只做maker时拒绝
+    for (tif, expect) in [
+        (TimeInForce::Gtc, filled(100., FillType::Taker)),
+        (TimeInForce::Ioc, filled(100., FillType::Taker)),
+        (TimeInForce::Fok, filled(100., FillType::Taker)),
+        (TimeInForce::Gtx, LimitFill::Rejected),
+    ] {
+        assert_eq!(LimitOrder::new(true, 101., tif).submit(100.), expect);
+    }
+    let candle = |open, high, low| CandleData {
+        open,
+        high,
+        low,
+        close: open,
+        ..Default::default()
+    };
+    let order = LimitOrder::new(false, 110., TimeInForce::Gtx);
+    assert_eq!(order.touch(&candle(100., 105., 99.)), None);
+    assert_eq!(
+        order.touch(&candle(100., 111., 99.)),
+        Some(filled(110., FillType::Maker))
+    );
+    assert_eq!(
+        order.touch(&candle(112., 115., 111.)),
+        Some(filled(112., FillType::Maker))
+    );
+}
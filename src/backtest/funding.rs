@@ -0,0 +1,217 @@
+use std::path::Path;
+
+use binance::futures::market::FuturesMarket;
+use time::OffsetDateTime;
+use tracing::info;
+
+use super::contract::Contract;
+use crate::error::{BinanceResultExt, DataError, MarketError};
+
+/// 单次请求的最大条数
+const FUNDING_LIMIT: u16 = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingData {
+    /// 结算时间
+    pub time: OffsetDateTime,
+    /// 资金费率（正值多头付给空头）
+    pub rate: f64,
+}
+
+/// 单个币种的历史资金费率，按结算时间排序
+#[derive(Debug, Clone, Default)]
+pub struct FundingSeries {
+    pub symbol: String,
+    pub rates: Vec<FundingData>,
+}
+
+impl FundingSeries {
+    /// 从币安fundingRate接口下载[start, end]内的资金费率
+    pub fn download(
+        market: &FuturesMarket,
+        symbol: &str,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> Result<Self, MarketError> {
+        let end_ms = to_millis(end);
+        let mut start_ms = to_millis(start);
+        let mut series = Self {
+            symbol: symbol.to_string(),
+            rates: vec![],
+        };
+        while start_ms <= end_ms {
+            let rates = market
+                .get_funding_rate(symbol, start_ms, end_ms, FUNDING_LIMIT)
+                .market_context(&format!("get funding rate of {}", symbol))?;
+            let Some(last) = rates.last() else {
+                break;
+            };
+            start_ms = last.funding_time + 1;
+            let full = rates.len() == FUNDING_LIMIT as usize;
+            series.rates.extend(rates.iter().map(|r| FundingData {
+                time: from_millis(r.funding_time),
+                rate: r.funding_rate,
+            }));
+            if !full {
+                break;
+            }
+        }
+        info!(
+            "download {} funding rates of {}",
+            series.rates.len(),
+            symbol
+        );
+        Ok(series)
+    }
+    /// 增量更新本地文件：读取已有数据，从最后一条之后下载到end，写回文件
+    pub fn sync(
+        market: &FuturesMarket,
+        symbol: &str,
+        dir: &str,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> crate::error::Result<Self> {
+        let path = Path::new(dir).join(format!("{}.csv", symbol));
+        let path = path.to_str().unwrap();
+        let mut series = if Path::new(path).is_file() {
+            Self::read_from_csv(path)?
+        } else {
+            std::fs::create_dir_all(dir).map_err(DataError::from)?;
+            Self {
+                symbol: symbol.to_string(),
+                rates: vec![],
+            }
+        };
+        let from = match series.rates.last() {
+            Some(last) => last.time + time::Duration::milliseconds(1),
+            None => start,
+        };
+        series.merge(Self::download(market, symbol, from, end)?);
+        series.write_to_csv(path)?;
+        Ok(series)
+    }
+    /// symbol      币种
+    /// time        结算时间（unix毫秒）
+    /// rate        资金费率
+    pub fn read_from_csv(path: &str) -> Result<Self, DataError> {
+        let mut csv = csv::Reader::from_path(path)?;
+        let mut series = Self::default();
+        for d in csv.records() {
+            let d = d?;
+            let field = |i: usize, name: &str| {
+                d.get(i)
+                    .ok_or_else(|| DataError::parse(name, "missing field"))
+                    .map(|s| s.to_string())
+            };
+            series.symbol = field(0, "symbol")?;
+            let time = field(1, "time")?
+                .parse::<u64>()
+                .map_err(|e| DataError::parse("time", e))?;
+            let rate = field(2, "rate")?
+                .parse::<f64>()
+                .map_err(|e| DataError::parse("rate", e))?;
+            series.rates.push(FundingData {
+                time: from_millis(time),
+                rate,
+            });
+        }
+        series.rates.sort_by_key(|r| r.time);
+        Ok(series)
+    }
+    pub fn write_to_csv(&self, path: &str) -> Result<(), DataError> {
+        let mut csv = csv::Writer::from_path(path)?;
+        csv.write_record(["symbol", "time", "rate"])?;
+        for r in self.rates.iter() {
+            csv.write_record([
+                self.symbol.clone(),
+                to_millis(r.time).to_string(),
+                r.rate.to_string(),
+            ])?;
+        }
+        csv.flush()?;
+        Ok(())
+    }
+    /// 合并另一段数据，按时间去重
+    pub fn merge(&mut self, other: Self) {
+        self.rates.extend(other.rates);
+        self.rates.sort_by_key(|r| r.time);
+        self.rates.dedup_by_key(|r| r.time);
+    }
+    /// time时刻生效的（最近一次已结算的）资金费率
+    pub fn rate_at(&self, time: OffsetDateTime) -> Option<f64> {
+        let i = self.rates.partition_point(|r| r.time <= time);
+        i.checked_sub(1).map(|i| self.rates[i].rate)
+    }
+    /// (from, to]内的结算记录
+    pub fn settlements(&self, from: OffsetDateTime, to: OffsetDateTime) -> &[FundingData] {
+        let start = self.rates.partition_point(|r| r.time <= from);
+        let end = self.rates.partition_point(|r| r.time <= to);
+        &self.rates[start..end.max(start)]
+    }
+    /// 持仓在(from, to]内按price结算的资金费用总和，收入为正
+    pub fn funding_fee(
+        &self,
+        contract: &Contract,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        price: f64,
+    ) -> f64 {
+        self.settlements(from, to)
+            .iter()
+            .map(|r| contract.funding(r.rate, price))
+            .sum()
+    }
+}
+
+pub(super) fn to_millis(time: OffsetDateTime) -> u64 {
+    (time.unix_timestamp_nanos() / 1_000_000) as u64
+}
+
+pub(super) fn from_millis(millis: u64) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp_nanos(millis as i128 * 1_000_000).unwrap()
+}
+
+#[test]
+fn funding_series_test() {
+    let t = |h: i64| OffsetDateTime::from_unix_timestamp(h * 3600).unwrap();
+    let mut series = FundingSeries {
+        symbol: "BTCUSDT".to_string(),
+        rates: vec![
+            FundingData {
+                time: t(0),
+                rate: 0.0001,
+            },
+            FundingData {
+                time: t(8),
+                rate: 0.0003,
+            },
+        ],
+    };
+    series.merge(FundingSeries {
+        symbol: "BTCUSDT".to_string(),
+        rates: vec![
+            FundingData {
+                time: t(8),
+                rate: 0.0003,
+            },
+            FundingData {
+                time: t(16),
+                rate: -0.0002,
+            },
+        ],
+    });
+    assert_eq!(series.rates.len(), 3);
+    assert_eq!(series.rate_at(t(7)), Some(0.0001));
+    assert_eq!(series.rate_at(t(16)), Some(-0.0002));
+    assert_eq!(series.settlements(t(0), t(16)).len(), 2);
+
+    let path = std::env::temp_dir().join("hurribot_funding_test.csv");
+    series.write_to_csv(path.to_str().unwrap()).unwrap();
+    let read = FundingSeries::read_from_csv(path.to_str().unwrap()).unwrap();
+    assert_eq!(read.symbol, "BTCUSDT");
+    assert_eq!(read.rates, series.rates);
+
+    let long = Contract::open(true, 100., 100., 10., t(0), None);
+    let fee = series.funding_fee(&long, t(0), t(16), 100.);
+    assert!((fee + long.amount * 100. * 0.0001).abs() < 1e-9);
+}
@@ -0,0 +1,172 @@
+use std::path::Path;
+
+use super::{
+    funding::{from_millis, to_millis},
+    strategy::{ExitReason, TradeRecord},
+};
+use crate::{error::DataError, fee::FillType};
+
+const HEADERS: [&str; 13] = [
+    "side",
+    "open_time",
+    "close_time",
+    "entry_price",
+    "exit_price",
+    "leverage",
+    "margin",
+    "pnl",
+    "reason",
+    "entry_fill",
+    "exit_fill",
+    "entry_fee",
+    "exit_fee",
+];
+
+fn parse_reason(s: &str) -> Option<ExitReason> {
+    Some(match s {
+        "TakeProfit" => ExitReason::TakeProfit,
+        "Trailing" => ExitReason::Trailing,
+        "StopLoss" => ExitReason::StopLoss,
+        "Liquidation" => ExitReason::Liquidation,
+        "Close" => ExitReason::Close,
+        "Signal" => ExitReason::Signal,
+        _ => return None,
+    })
+}
+
+fn parse_fill(s: &str) -> Option<FillType> {
+    Some(match s {
+        "Maker" => FillType::Maker,
+        "Taker" => FillType::Taker,
+        _ => return None,
+    })
+}
+
+/// 写入交易记录，时间为unix毫秒，列名见HEADERS
+pub fn write_journal(path: &Path, trades: &[TradeRecord]) -> Result<(), DataError> {
+    let mut csv = csv::Writer::from_path(path)?;
+    csv.write_record(HEADERS)?;
+    for t in trades {
+        csv.write_record([
+            if t.is_bull { "long" } else { "short" }.to_string(),
+            to_millis(t.open_time).to_string(),
+            to_millis(t.close_time).to_string(),
+            t.entry_price.to_string(),
+            t.exit_price.to_string(),
+            t.leverage.to_string(),
+            t.margin.to_string(),
+            t.pnl.to_string(),
+            format!("{:?}", t.reason),
+            format!("{:?}", t.entry_fill),
+            format!("{:?}", t.exit_fill),
+            t.entry_fee.to_string(),
+            t.exit_fee.to_string(),
+        ])?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// 读取write_journal写入的交易记录
+pub fn read_journal(path: &Path) -> Result<Vec<TradeRecord>, DataError> {
+    let mut csv = csv::Reader::from_path(path)?;
+    let mut trades = vec![];
+    for d in csv.records() {
+        let d = d?;
+        let field = |i: usize| {
+            d.get(i)
+                .ok_or_else(|| DataError::parse(HEADERS[i], "missing field"))
+        };
+        let number = |i: usize| {
+            field(i)?
+                .parse::<f64>()
+                .map_err(|e| DataError::parse(HEADERS[i], e))
+        };
+        let time = |i: usize| {
+            let s = field(i)?;
+            s.parse::<u64>()
+                .map(from_millis)
+                .map_err(|e| DataError::parse(HEADERS[i], e))
+        };
+        let fill = |i: usize| {
+            let s = field(i)?;
+            parse_fill(s).ok_or_else(|| DataError::parse(HEADERS[i], s))
+        };
+        let reason = field(8)?;
+        trades.push(TradeRecord {
+            is_bull: match field(0)? {
+                "long" => true,
+                "short" => false,
+                s => return Err(DataError::parse("side", s)),
+            },
+            open_time: time(1)?,
+            close_time: time(2)?,
+            entry_price: number(3)?,
+            exit_price: number(4)?,
+            leverage: number(5)?,
+            margin: number(6)?,
+            pnl: number(7)?,
+            reason: parse_reason(reason).ok_or_else(|| DataError::parse("reason", reason))?,
+            entry_fill: fill(9)?,
+            exit_fill: fill(10)?,
+            entry_fee: number(11)?,
+            exit_fee: number(12)?,
+        });
+    }
+    Ok(trades)
+}
+
+#[test]
+fn journal_test() {
+    use time::{Duration, OffsetDateTime};
+
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let trades = vec![
+        TradeRecord {
+            is_bull: true,
+            open_time: start,
+            close_time: start + Duration::minutes(5),
+            entry_price: 100.,
+            exit_price: 110.5,
+            leverage: 10.,
+            margin: 50.,
+            pnl: 52.1,
+            reason: ExitReason::TakeProfit,
+            entry_fill: FillType::Taker,
+            exit_fill: FillType::Maker,
+            entry_fee: 0.25,
+            exit_fee: 0.11,
+        },
+        TradeRecord {
+            is_bull: false,
+            open_time: start + Duration::minutes(6),
+            close_time: start + Duration::minutes(9),
+            entry_price: 110.,
+            exit_price: 120.,
+            leverage: 20.,
+            margin: 100.,
+            pnl: -100.,
+            reason: ExitReason::Liquidation,
+            entry_fill: FillType::Taker,
+            exit_fill: FillType::Taker,
+            entry_fee: 1.,
+            exit_fee: 0.,
+        },
+    ];
+    let path = std::env::temp_dir().join("hurribot_journal_test.csv");
+    write_journal(&path, &trades).unwrap();
+    let read = read_journal(&path).unwrap();
+    assert_eq!(read.len(), 2);
+    assert_eq!(read[0].close_time, trades[0].close_time);
+    assert_eq!(read[0].exit_price, 110.5);
+    assert_eq!(read[0].exit_fill, FillType::Maker);
+    assert!(!read[1].is_bull);
+    assert_eq!(read[1].reason, ExitReason::Liquidation);
+    std::fs::write(
+        &path,
+        HEADERS.join(",") + "\nlong,0,1,1,1,1,1,1,Unknown,Maker,Maker,0,0\n",
+    )
+    .unwrap();
+    assert!(matches!(read_journal(&path), Err(DataError::Parse { .. })));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,160 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use time::OffsetDateTime;
+
+use super::{candle_chart::CandleData, candle_store::CandleStore};
+
+/// 多个按收盘时间升序的k线迭代器的归并，按收盘时间先后输出（来源序号，k线），
+/// 相同时间按来源序号或币种名；每个来源只预读一根，内存占用与历史长度无关
+#[derive(Debug)]
+pub struct MergedCandles<I> {
+    sources: Vec<I>,
+    heads: Vec<Option<CandleData>>,
+    /// 相同收盘时间的先后次序
+    ranks: Vec<usize>,
+    heap: BinaryHeap<Reverse<(OffsetDateTime, usize, usize)>>,
+}
+
+impl<I: Iterator<Item = CandleData>> MergedCandles<I> {
+    pub fn new(sources: Vec<I>) -> Self {
+        let ranks = (0..sources.len()).collect();
+        Self::with_ranks(sources, ranks)
+    }
+    /// 相同收盘时间按币种名排序，结果与来源的添加顺序无关
+    pub fn with_symbols(sources: Vec<I>, symbols: &[&str]) -> Self {
+        assert_eq!(sources.len(), symbols.len(), "one symbol per source");
+        let mut order: Vec<usize> = (0..symbols.len()).collect();
+        order.sort_by_key(|&i| (symbols[i], i));
+        let mut ranks = vec![0; order.len()];
+        for (rank, i) in order.into_iter().enumerate() {
+            ranks[i] = rank;
+        }
+        Self::with_ranks(sources, ranks)
+    }
+    fn with_ranks(mut sources: Vec<I>, ranks: Vec<usize>) -> Self {
+        let mut heap = BinaryHeap::with_capacity(sources.len());
+        let heads = sources
+            .iter_mut()
+            .enumerate()
+            .map(|(i, source)| {
+                let head = source.next();
+                if let Some(c) = &head {
+                    heap.push(Reverse((c.close_time, ranks[i], i)));
+                }
+                head
+            })
+            .collect();
+        Self {
+            sources,
+            heads,
+            ranks,
+            heap,
+        }
+    }
+    /// 来源数
+    pub fn width(&self) -> usize {
+        self.sources.len()
+    }
+    /// 取出收盘时间相同的下一批k线，批内按相同时间的先后次序排列；没有k线时为空
+    pub fn next_batch(&mut self) -> Vec<(usize, CandleData)> {
+        let Some(first) = self.next() else {
+            return vec![];
+        };
+        let time = first.1.close_time;
+        let mut batch = vec![first];
+        while self.heap.peek().is_some_and(|Reverse((t, ..))| *t == time) {
+            batch.extend(self.next());
+        }
+        batch
+    }
+}
+
+impl<I: Iterator<Item = CandleData>> Iterator for MergedCandles<I> {
+    type Item = (usize, CandleData);
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, _, i)) = self.heap.pop()?;
+        let candle = self.heads[i].take()?;
+        self.heads[i] = self.sources[i].next();
+        if let Some(c) = &self.heads[i] {
+            self.heap.push(Reverse((c.close_time, self.ranks[i], i)));
+        }
+        Some((i, candle))
+    }
+}
+
+/// 按开盘时间[start, end)惰性读取各k线库并归并，同时返回总k线数用于进度
+pub fn merge_stores(
+    stores: &[CandleStore],
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> (MergedCandles<impl Iterator<Item = CandleData> + '_>, usize) {
+    let ranges: Vec<_> = stores.iter().map(|s| s.range(start, end)).collect();
+    let total = ranges.iter().map(|r| r.len()).sum();
+    let sources = stores
+        .iter()
+        .zip(ranges)
+        .map(|(store, range)| store.iter_range(range))
+        .collect();
+    (MergedCandles::new(sources), total)
+}
+
+#[test]
+fn merged_candles_test() {
+    use super::candle_store::CandleStoreWriter;
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let candle = |minute: i64, close: f64| CandleData {
+        close,
+        open_time: start + Duration::minutes(minute),
+        close_time: start + Duration::minutes(minute + 1),
+        ..Default::default()
+    };
+    let a = vec![candle(0, 1.), candle(2, 2.), candle(4, 3.)];
+    let b = vec![candle(1, 10.), candle(2, 20.), candle(3, 30.)];
+    let merged: Vec<(usize, f64)> =
+        MergedCandles::new(vec![a.clone().into_iter(), b.clone().into_iter()])
+            .map(|(i, c)| (i, c.close))
+            .collect();
+    assert_eq!(
+        merged,
+        [(0, 1.), (1, 10.), (0, 2.), (1, 20.), (1, 30.), (0, 3.)]
+    );
+    // 相同时间按币种名，来源1（A）在前
+    let mut by_symbol =
+        MergedCandles::with_symbols(vec![a.clone().into_iter(), b.into_iter()], &["B", "A"]);
+    by_symbol.next();
+    by_symbol.next();
+    let batch: Vec<(usize, f64)> = by_symbol
+        .next_batch()
+        .into_iter()
+        .map(|(i, c)| (i, c.close))
+        .collect();
+    assert_eq!(batch, [(1, 20.), (0, 2.)]);
+
+    let dir = std::env::temp_dir().join("hurribot_merged_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let stores: Vec<CandleStore> = [&a, &vec![candle(1, 10.), candle(5, 50.)]]
+        .iter()
+        .enumerate()
+        .map(|(i, candles)| {
+            let path = dir.join(format!("{}.bin", i));
+            let mut writer = CandleStoreWriter::create(&path, Duration::minutes(1)).unwrap();
+            for c in candles.iter() {
+                writer.push(c).unwrap();
+            }
+            writer.finish().unwrap();
+            CandleStore::open(&path).unwrap()
+        })
+        .collect();
+    let (merged, total) = merge_stores(
+        &stores,
+        start + Duration::minutes(1),
+        start + Duration::minutes(5),
+    );
+    assert_eq!(total, 3);
+    let closes: Vec<f64> = merged.map(|(_, c)| c.close).collect();
+    assert_eq!(closes, [10., 2., 3.]);
+    drop(stores);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
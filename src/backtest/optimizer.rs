@@ -0,0 +1,275 @@
+use plotters::prelude::*;
+use rayon::prelude::*;
+
+/// 遗传算法参数优化，基因为各参数在给定区间内的取值
+#[derive(Debug, Clone)]
+pub struct Evolution {
+    /// 种群大小
+    pub population: usize,
+    /// 迭代代数
+    pub generations: usize,
+    /// 交叉概率
+    pub crossover_rate: f64,
+    /// 每个基因的变异概率
+    pub mutation_rate: f64,
+    /// 直接保留到下一代的最优个体数
+    pub elitism: usize,
+    /// 锦标赛选择的参赛个体数
+    pub tournament: usize,
+    pub seed: u64,
+}
+
+impl Default for Evolution {
+    fn default() -> Self {
+        Self {
+            population: 64,
+            generations: 50,
+            crossover_rate: 0.8,
+            mutation_rate: 0.1,
+            elitism: 2,
+            tournament: 3,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Individual {
+    pub genes: Vec<f64>,
+    pub fitness: f64,
+}
+
+impl Evolution {
+    /// bounds为各参数的取值区间，fitness越大越好（NaN视为最差），并行计算
+    pub fn run<F>(&self, bounds: &[(f64, f64)], fitness: F) -> Individual
+    where
+        F: Fn(&[f64]) -> f64 + Sync,
+    {
+        let mut rng = fastrand::Rng::with_seed(self.seed);
+        let size = self.population.max(2);
+        let evaluate = |genes: Vec<Vec<f64>>| -> Vec<Individual> {
+            let mut population: Vec<Individual> = genes
+                .into_par_iter()
+                .map(|genes| {
+                    let f = fitness(&genes);
+                    Individual {
+                        genes,
+                        fitness: if f.is_nan() { f64::NEG_INFINITY } else { f },
+                    }
+                })
+                .collect();
+            population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+            population
+        };
+        let mut population = evaluate(
+            (0..size)
+                .map(|_| {
+                    bounds
+                        .iter()
+                        .map(|&(low, high)| low + rng.f64() * (high - low))
+                        .collect()
+                })
+                .collect(),
+        );
+        for _ in 0..self.generations {
+            let mut next: Vec<Vec<f64>> = population
+                .iter()
+                .take(self.elitism.min(size))
+                .map(|i| i.genes.clone())
+                .collect();
+            while next.len() < size {
+                let a = self.select(&population, &mut rng);
+                let b = self.select(&population, &mut rng);
+                let mut child = if rng.f64() < self.crossover_rate {
+                    a.iter()
+                        .zip(b.iter())
+                        .map(|(x, y)| if rng.bool() { *x } else { *y })
+                        .collect()
+                } else {
+                    a.to_vec()
+                };
+                self.mutate(&mut child, bounds, &mut rng);
+                next.push(child);
+            }
+            population = evaluate(next);
+        }
+        population.swap_remove(0)
+    }
+    fn select<'a>(&self, population: &'a [Individual], rng: &mut fastrand::Rng) -> &'a [f64] {
+        // 种群已按适应度降序排列，取参赛者中序号最小的
+        let best = (0..self.tournament.max(1))
+            .map(|_| rng.usize(..population.len()))
+            .min()
+            .unwrap();
+        &population[best].genes
+    }
+    /// 高斯变异，标准差为区间宽度的10%
+    fn mutate(&self, genes: &mut [f64], bounds: &[(f64, f64)], rng: &mut fastrand::Rng) {
+        for (gene, &(low, high)) in genes.iter_mut().zip(bounds.iter()) {
+            if rng.f64() < self.mutation_rate {
+                let u1 = rng.f64().max(f64::MIN_POSITIVE);
+                let normal = (-2. * u1.ln()).sqrt() * (2. * std::f64::consts::PI * rng.f64()).cos();
+                *gene = (*gene + normal * (high - low) * 0.1).clamp(low, high);
+            }
+        }
+    }
+}
+
+/// 二维参数网格搜索结果，values[i][j]对应(xs[i], ys[j])
+#[derive(Debug, Clone)]
+pub struct Grid {
+    pub xs: Vec<f64>,
+    pub ys: Vec<f64>,
+    pub values: Vec<Vec<f64>>,
+}
+
+/// low到high之间（含两端）等距取steps个点
+pub fn linspace(low: f64, high: f64, steps: usize) -> Vec<f64> {
+    match steps {
+        0 => vec![],
+        1 => vec![low],
+        _ => (0..steps)
+            .map(|i| low + (high - low) * i as f64 / (steps - 1) as f64)
+            .collect(),
+    }
+}
+
+impl Grid {
+    /// 并行计算每个网格点的目标值
+    pub fn search<F>(xs: Vec<f64>, ys: Vec<f64>, fitness: F) -> Self
+    where
+        F: Fn(f64, f64) -> f64 + Sync,
+    {
+        let values = xs
+            .par_iter()
+            .map(|&x| ys.par_iter().map(|&y| fitness(x, y)).collect())
+            .collect();
+        Self { xs, ys, values }
+    }
+    /// 目标值最大的点（x, y, value），忽略NaN
+    pub fn best(&self) -> Option<(f64, f64, f64)> {
+        self.xs
+            .iter()
+            .zip(self.values.iter())
+            .flat_map(|(&x, row)| {
+                self.ys
+                    .iter()
+                    .zip(row.iter())
+                    .map(move |(&y, &v)| (x, y, v))
+            })
+            .filter(|p| !p.2.is_nan())
+            .max_by(|a, b| a.2.total_cmp(&b.2))
+    }
+    /// 绘制热力图，蓝色为低、红色为高，NaN为灰色
+    pub fn plot_heatmap(
+        &self,
+        path: &str,
+        caption: &str,
+        x_desc: &str,
+        y_desc: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let root_area = BitMapBackend::new(path, (1024, 768)).into_drawing_area();
+        root_area.fill(&WHITE)?;
+        let (nx, ny) = (self.xs.len(), self.ys.len());
+        if nx == 0 || ny == 0 {
+            return Ok(());
+        }
+        let finite = self.values.iter().flatten().filter(|v| v.is_finite());
+        let min = finite.clone().copied().fold(f64::INFINITY, f64::min);
+        let max = finite.copied().fold(f64::NEG_INFINITY, f64::max);
+        let (xs, ys) = (&self.xs, &self.ys);
+        let mut chart = ChartBuilder::on(&root_area)
+            .caption(caption, ("sans-serif", 30).into_font())
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(60)
+            .build_cartesian_2d(0..nx, 0..ny)?;
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_desc(x_desc)
+            .y_desc(y_desc)
+            .x_labels(nx.min(20))
+            .y_labels(ny.min(20))
+            .x_label_formatter(&|i| xs.get(*i).map_or(String::new(), |x| format!("{:.4}", x)))
+            .y_label_formatter(&|j| ys.get(*j).map_or(String::new(), |y| format!("{:.4}", y)))
+            .draw()?;
+        chart.draw_series(self.values.iter().enumerate().flat_map(|(i, row)| {
+            row.iter().enumerate().map(move |(j, &v)| {
+                let color = if v.is_finite() {
+                    let t = if max > min {
+                        (v - min) / (max - min)
+                    } else {
+                        0.5
+                    };
+                    HSLColor((1. - t) * 240. / 360., 0.8, 0.5).to_rgba()
+                } else {
+                    RGBAColor(160, 160, 160, 1.)
+                };
+                Rectangle::new([(i, j), (i + 1, j + 1)], color.filled())
+            })
+        }))?;
+        root_area.present()?;
+        Ok(())
+    }
+}
+
+/// 资金曲线的夏普比率（按采样点收益计算，未年化）
+pub fn sharpe(curve: &[f64]) -> f64 {
+    let returns: Vec<f64> = curve
+        .windows(2)
+        .filter(|w| w[0] > 0.)
+        .map(|w| w[1] / w[0] - 1.)
+        .collect();
+    if returns.len() < 2 {
+        return 0.;
+    }
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let var = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / returns.len() as f64;
+    if var == 0. {
+        return 0.;
+    }
+    mean / var.sqrt()
+}
+
+/// 夏普比率减去回撤惩罚
+pub fn penalized_sharpe(curve: &[f64], max_drawdown: f64, penalty: f64) -> f64 {
+    sharpe(curve) - penalty * max_drawdown
+}
+
+#[test]
+fn evolution_test() {
+    let target = [3., -2., 0.5];
+    let bounds = [(-5., 5.); 3];
+    let best = Evolution {
+        generations: 80,
+        ..Default::default()
+    }
+    .run(&bounds, |genes| {
+        -genes
+            .iter()
+            .zip(target.iter())
+            .map(|(g, t)| (g - t).powi(2))
+            .sum::<f64>()
+    });
+    assert!(best.fitness > -0.05, "{:?}", best);
+    assert!(sharpe(&[1., 1.1, 1.21, 1.3]) > 0.);
+}
+
+#[test]
+fn grid_search_test() {
+    let grid = Grid::search(linspace(1., 5., 5), linspace(0., 1., 3), |x, y| {
+        if x == 5. {
+            f64::NAN
+        } else {
+            -(x - 3.).powi(2) - (y - 0.5).powi(2)
+        }
+    });
+    assert_eq!(grid.values.len(), 5);
+    assert!(grid.values.iter().all(|row| row.len() == 3));
+    assert_eq!(grid.best(), Some((3., 0.5, 0.)));
+    let path = std::env::temp_dir().join("hurribot_heatmap_test.png");
+    grid.plot_heatmap(path.to_str().unwrap(), "test", "x", "y")
+        .unwrap();
+    assert!(path.exists());
+}
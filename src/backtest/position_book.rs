@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use tracing::error;
+
+use super::contract::Contract;
+use crate::liquidation::MarginBrackets;
+
+/// 加仓持仓簿：同一币种可持有多笔同方向合约，按合并仓位计算均价和强平
+#[derive(Debug, Clone, Default)]
+pub struct PositionBook {
+    lots: HashMap<String, Vec<Contract>>,
+}
+
+impl PositionBook {
+    /// 加仓，方向与已有仓位相反时拒绝并返回false
+    pub fn add(&mut self, symbol: &str, contract: Contract) -> bool {
+        let lots = self.lots.entry(symbol.to_string()).or_default();
+        if lots.first().is_some_and(|c| c.is_bull != contract.is_bull) {
+            error!("{} add lot in opposite direction", symbol);
+            return false;
+        }
+        lots.push(contract);
+        true
+    }
+    pub fn lots(&self, symbol: &str) -> &[Contract] {
+        self.lots
+            .get(symbol)
+            .map(|l| l.as_slice())
+            .unwrap_or_default()
+    }
+    pub fn symbols(&self) -> impl Iterator<Item = &String> {
+        self.lots.keys()
+    }
+    pub fn amount(&self, symbol: &str) -> f64 {
+        self.lots(symbol).iter().map(|c| c.amount).sum()
+    }
+    pub fn margin(&self, symbol: &str) -> f64 {
+        self.lots(symbol).iter().map(|c| c.margin).sum()
+    }
+    /// 按数量加权的开仓均价
+    pub fn entry_price(&self, symbol: &str) -> Option<f64> {
+        let amount = self.amount(symbol);
+        if amount == 0. {
+            return None;
+        }
+        let cost: f64 = self
+            .lots(symbol)
+            .iter()
+            .map(|c| c.entry_price * c.amount)
+            .sum();
+        Some(cost / amount)
+    }
+    /// 合并仓位的逐仓强平价格
+    pub fn liq_price(&self, symbol: &str) -> Option<f64> {
+        let entry_price = self.entry_price(symbol)?;
+        let side = if self.lots(symbol)[0].is_bull {
+            1.
+        } else {
+            -1.
+        };
+        MarginBrackets::default()
+            .liq_price(side * self.amount(symbol), entry_price, self.margin(symbol), 0.)
+            .ok()
+    }
+    /// 先按各笔止损平仓，再按合并仓位判断强平，返回回收的资金
+    pub fn liquidate(&mut self, symbol: &str, price: f64) -> Option<f64> {
+        let lots = self.lots.get_mut(symbol)?;
+        let mut r = None;
+        lots.retain(|c| match c.stop_out(price) {
+            Some(v) => {
+                *r.get_or_insert(0.) += v;
+                false
+            }
+            None => true,
+        });
+        let liq_price = self.liq_price(symbol);
+        if let Some(liq_price) = liq_price {
+            let is_bull = self.lots(symbol)[0].is_bull;
+            if (is_bull && price < liq_price) || (!is_bull && price > liq_price) {
+                let lots = self.lots.remove(symbol).unwrap_or_default();
+                let v: f64 = lots.iter().map(|c| c.cover(liq_price)).sum();
+                *r.get_or_insert(0.) += v * 0.85;
+            }
+        }
+        if self.lots(symbol).is_empty() {
+            self.lots.remove(symbol);
+        }
+        r
+    }
+    /// 全部平仓
+    pub fn close(&mut self, symbol: &str, price: f64) -> f64 {
+        let r = self.liquidate(symbol, price).unwrap_or_default();
+        r + self
+            .lots
+            .remove(symbol)
+            .unwrap_or_default()
+            .iter()
+            .map(|c| c.cover(price))
+            .sum::<f64>()
+    }
+    /// 各笔按相同比例减仓（强平按合并仓位判断，不再按单笔判断）
+    pub fn close_partial(&mut self, symbol: &str, price: f64, fraction: f64) -> f64 {
+        if fraction >= 1. {
+            return self.close(symbol, price);
+        }
+        let mut r = self.liquidate(symbol, price).unwrap_or_default();
+        for c in self.lots.get_mut(symbol).into_iter().flatten() {
+            let mut closed = c.clone();
+            closed.margin *= fraction;
+            closed.amount *= fraction;
+            c.margin -= closed.margin;
+            c.amount -= closed.amount;
+            r += closed.cover(price);
+        }
+        r
+    }
+    /// 按价格估算所有仓位平仓后的资金
+    pub fn value(&self, prices: &HashMap<String, f64>) -> f64 {
+        self.lots
+            .iter()
+            .flat_map(|(symbol, lots)| {
+                let price = prices.get(symbol).copied();
+                lots.iter()
+                    .map(move |c| price.map(|p| c.cover(p)).unwrap_or(c.margin))
+            })
+            .sum()
+    }
+}
+
+#[test]
+fn position_book_test() {
+    use time::OffsetDateTime;
+
+    let t = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let mut book = PositionBook::default();
+    assert!(book.add("BTCUSDT", Contract::open(true, 100., 100., 10., t, None)));
+    assert!(book.add("BTCUSDT", Contract::open(true, 110., 110., 10., t, None)));
+    assert!(!book.add("BTCUSDT", Contract::open(false, 110., 110., 10., t, None)));
+    assert_eq!(book.lots("BTCUSDT").len(), 2);
+    // 两笔数量相同，均价为105
+    assert!((book.entry_price("BTCUSDT").unwrap() - 105.).abs() < 1e-9);
+    let liq_price = book.liq_price("BTCUSDT").unwrap();
+    assert!(liq_price > 90. && liq_price < 105.);
+    assert_eq!(book.liquidate("BTCUSDT", liq_price + 0.1), None);
+    let half = book.close_partial("BTCUSDT", 120., 0.5);
+    let rest = book.close("BTCUSDT", 120.);
+    assert!((half - rest).abs() < 1e-9);
+    assert!(book.lots("BTCUSDT").is_empty());
+
+    book.add("ETHUSDT", Contract::open(true, 100., 100., 10., t, None));
+    assert!(book.liquidate("ETHUSDT", 80.).is_some());
+    assert_eq!(book.symbols().count(), 0);
+}
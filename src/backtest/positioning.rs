@@ -0,0 +1,145 @@
+use time::OffsetDateTime;
+
+use super::funding::{from_millis, to_millis};
+use crate::error::DataError;
+
+/// 某一时刻的持仓量和大户多空比
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositioningData {
+    pub time: OffsetDateTime,
+    /// 持仓量（币）
+    pub open_interest: f64,
+    /// 持仓价值（结算资产）
+    pub open_interest_value: f64,
+    /// 大户持仓多空比，未获取到时为None
+    pub long_short_ratio: Option<f64>,
+}
+
+/// 单个币种的持仓数据，按时间排序，可由实盘轮询记录后用于回测
+#[derive(Debug, Clone, Default)]
+pub struct PositioningSeries {
+    pub symbol: String,
+    pub data: Vec<PositioningData>,
+}
+
+impl PositioningSeries {
+    /// symbol              币种
+    /// time                统计时间（unix毫秒）
+    /// open_interest       持仓量
+    /// open_interest_value 持仓价值
+    /// long_short_ratio    大户持仓多空比，为空表示缺失
+    pub fn read_from_csv(path: &str) -> Result<Self, DataError> {
+        let mut csv = csv::Reader::from_path(path)?;
+        let mut series = Self::default();
+        for d in csv.records() {
+            let d = d?;
+            let field = |i: usize, name: &str| {
+                d.get(i)
+                    .ok_or_else(|| DataError::parse(name, "missing field"))
+                    .map(|s| s.to_string())
+            };
+            let number = |i: usize, name: &str| {
+                field(i, name)?
+                    .parse::<f64>()
+                    .map_err(|e| DataError::parse(name, e))
+            };
+            series.symbol = field(0, "symbol")?;
+            let time = field(1, "time")?
+                .parse::<u64>()
+                .map_err(|e| DataError::parse("time", e))?;
+            let ratio = field(4, "long_short_ratio")?;
+            series.data.push(PositioningData {
+                time: from_millis(time),
+                open_interest: number(2, "open_interest")?,
+                open_interest_value: number(3, "open_interest_value")?,
+                long_short_ratio: match ratio.as_str() {
+                    "" => None,
+                    r => Some(
+                        r.parse()
+                            .map_err(|e| DataError::parse("long_short_ratio", e))?,
+                    ),
+                },
+            });
+        }
+        series.data.sort_by_key(|d| d.time);
+        Ok(series)
+    }
+    pub fn write_to_csv(&self, path: &str) -> Result<(), DataError> {
+        let mut csv = csv::Writer::from_path(path)?;
+        csv.write_record([
+            "symbol",
+            "time",
+            "open_interest",
+            "open_interest_value",
+            "long_short_ratio",
+        ])?;
+        for d in self.data.iter() {
+            csv.write_record([
+                self.symbol.clone(),
+                to_millis(d.time).to_string(),
+                d.open_interest.to_string(),
+                d.open_interest_value.to_string(),
+                d.long_short_ratio
+                    .map(|r| r.to_string())
+                    .unwrap_or_default(),
+            ])?;
+        }
+        csv.flush()?;
+        Ok(())
+    }
+    /// 合并另一段数据，按时间去重，时间相同时保留other的
+    pub fn merge(&mut self, other: Self) {
+        let mut data = other.data;
+        data.append(&mut self.data);
+        data.sort_by_key(|d| d.time);
+        data.dedup_by_key(|d| d.time);
+        self.data = data;
+    }
+    /// time时刻已知的最近一条数据
+    pub fn at(&self, time: OffsetDateTime) -> Option<&PositioningData> {
+        let i = self.data.partition_point(|d| d.time <= time);
+        i.checked_sub(1).map(|i| &self.data[i])
+    }
+    /// time时的持仓量相对time - window时的变化比例
+    pub fn open_interest_change(
+        &self,
+        time: OffsetDateTime,
+        window: time::Duration,
+    ) -> Option<f64> {
+        let now = self.at(time)?;
+        let before = self.at(time - window)?;
+        (before.open_interest > 0.).then(|| now.open_interest / before.open_interest - 1.)
+    }
+}
+
+#[test]
+fn positioning_series_test() {
+    let t = |m: i64| OffsetDateTime::from_unix_timestamp(m * 300).unwrap();
+    let data = |m: i64, oi: f64, ratio: Option<f64>| PositioningData {
+        time: t(m),
+        open_interest: oi,
+        open_interest_value: oi * 100.,
+        long_short_ratio: ratio,
+    };
+    let mut series = PositioningSeries {
+        symbol: "BTCUSDT".to_string(),
+        data: vec![data(0, 1000., Some(1.2)), data(1, 1100., None)],
+    };
+    series.merge(PositioningSeries {
+        symbol: "BTCUSDT".to_string(),
+        data: vec![data(1, 1100., None), data(2, 1210., Some(0.9))],
+    });
+    assert_eq!(series.data.len(), 3);
+    assert_eq!(series.at(t(1)).unwrap().open_interest, 1100.);
+    assert!(series.at(t(-1)).is_none());
+    let change = series
+        .open_interest_change(t(2), time::Duration::minutes(10))
+        .unwrap();
+    assert!((change - 0.21).abs() < 1e-9);
+
+    let path = std::env::temp_dir().join("hurribot_positioning_test.csv");
+    series.write_to_csv(path.to_str().unwrap()).unwrap();
+    let read = PositioningSeries::read_from_csv(path.to_str().unwrap()).unwrap();
+    assert_eq!(read.symbol, "BTCUSDT");
+    assert_eq!(read.data, series.data);
+}
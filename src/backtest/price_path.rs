@@ -0,0 +1,116 @@
+use super::candle_chart::CandleData;
+
+/// k线内价格路径模型，用于确定止损/强平与止盈的先后顺序
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathModel {
+    /// O→H→L→C
+    HighFirst,
+    /// O→L→H→C
+    LowFirst,
+    /// 以该概率先走到持仓的不利极值（1为最悲观，0为最乐观）
+    Pessimism(f64),
+    /// 极值顺序同Pessimism，各段之间用steps步布朗桥连接
+    BrownianBridge { pessimism: f64, steps: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct IntrabarPath {
+    model: PathModel,
+    rng: fastrand::Rng,
+}
+
+impl Default for IntrabarPath {
+    /// 默认最悲观：先触及不利极值
+    fn default() -> Self {
+        Self::new(PathModel::Pessimism(1.), 0)
+    }
+}
+
+impl IntrabarPath {
+    pub fn new(model: PathModel, seed: u64) -> Self {
+        Self {
+            model,
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+    /// 返回从开盘价到收盘价、经过最高价和最低价的价格序列
+    pub fn path(&mut self, candle: &CandleData, is_bull: bool) -> Vec<f64> {
+        let high_first = match self.model {
+            PathModel::HighFirst => true,
+            PathModel::LowFirst => false,
+            PathModel::Pessimism(p) | PathModel::BrownianBridge { pessimism: p, .. } => {
+                // 做多的不利极值为最低价
+                let adverse_first = self.rng.f64() < p;
+                adverse_first != is_bull
+            }
+        };
+        let (first, second) = if high_first {
+            (candle.high, candle.low)
+        } else {
+            (candle.low, candle.high)
+        };
+        let points = [candle.open, first, second, candle.close];
+        let PathModel::BrownianBridge { steps, .. } = self.model else {
+            return points.to_vec();
+        };
+        let mut path = vec![candle.open];
+        for w in points.windows(2) {
+            path.extend(self.bridge(w[0], w[1], steps.max(1), candle.low, candle.high));
+        }
+        path
+    }
+    /// 从a到b的布朗桥（不含起点），限制在[low, high]内
+    fn bridge(&mut self, a: f64, b: f64, steps: usize, low: f64, high: f64) -> Vec<f64> {
+        let sigma = (high - low) / (steps as f64).sqrt() * 0.5;
+        let mut walk = Vec::with_capacity(steps);
+        let mut sum = 0.;
+        for _ in 0..steps {
+            sum += self.normal() * sigma;
+            walk.push(sum);
+        }
+        let total = sum;
+        walk.iter()
+            .enumerate()
+            .map(|(i, w)| {
+                let t = (i + 1) as f64 / steps as f64;
+                (a + (b - a) * t + w - t * total).clamp(low, high)
+            })
+            .collect()
+    }
+    /// Box-Muller
+    fn normal(&mut self) -> f64 {
+        let u1 = self.rng.f64().max(f64::MIN_POSITIVE);
+        let u2 = self.rng.f64();
+        (-2. * u1.ln()).sqrt() * (2. * std::f64::consts::PI * u2).cos()
+    }
+}
+
+#[test]
+fn price_path_test() {
+    let candle = CandleData {
+        open: 100.,
+        high: 110.,
+        low: 90.,
+        close: 105.,
+        ..Default::default()
+    };
+    let mut path = IntrabarPath::default();
+    assert_eq!(path.path(&candle, true), vec![100., 90., 110., 105.]);
+    assert_eq!(path.path(&candle, false), vec![100., 110., 90., 105.]);
+    let mut path = IntrabarPath::new(PathModel::Pessimism(0.), 0);
+    assert_eq!(path.path(&candle, true), vec![100., 110., 90., 105.]);
+    let mut path = IntrabarPath::new(
+        PathModel::BrownianBridge {
+            pessimism: 1.,
+            steps: 20,
+        },
+        42,
+    );
+    let p = path.path(&candle, true);
+    assert_eq!(p.len(), 61);
+    assert_eq!(p[0], 100.);
+    assert_eq!(p[20], 90.);
+    assert_eq!(p[40], 110.);
+    assert_eq!(*p.last().unwrap(), 105.);
+    assert!(p.iter().all(|&x| (90. ..=110.).contains(&x)));
+}
@@ -0,0 +1,149 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::DataError;
+
+/// 元数据文件的后缀，目录的元数据文件与目录同级
+const MANIFEST_SUFFIX: &str = ".meta.json";
+
+/// 数据集的来源和校验信息，以<数据路径>.meta.json保存在数据旁，回测报告据此记录数据版本
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataManifest {
+    /// 数据来源，如"binance fapi/v1/klines BTCUSDT 1m"
+    pub source: String,
+    /// 下载时间（unix毫秒）
+    pub downloaded_at: u64,
+    pub rows: usize,
+    /// 文件内容的SHA-256（十六进制），目录按文件名顺序依次计入各文件
+    pub sha256: String,
+}
+
+impl DataManifest {
+    /// 计算path的校验和并生成元数据
+    pub fn new(
+        path: &Path,
+        source: &str,
+        downloaded_at: u64,
+        rows: usize,
+    ) -> Result<Self, DataError> {
+        Ok(Self {
+            source: source.to_string(),
+            downloaded_at,
+            rows,
+            sha256: sha256(path)?,
+        })
+    }
+    pub fn path(data: &Path) -> PathBuf {
+        let mut name = data.as_os_str().to_owned();
+        name.push(MANIFEST_SUFFIX);
+        PathBuf::from(name)
+    }
+    pub fn is_manifest(path: &Path) -> bool {
+        path.to_str().is_some_and(|p| p.ends_with(MANIFEST_SUFFIX))
+    }
+    pub fn write(&self, data: &Path) -> Result<(), DataError> {
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| DataError::parse("manifest", e))?;
+        std::fs::write(Self::path(data), json)?;
+        Ok(())
+    }
+    /// 没有元数据文件时返回None
+    pub fn read(data: &Path) -> Result<Option<Self>, DataError> {
+        let path = Self::path(data);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| DataError::parse("manifest", e))
+    }
+    /// 重新计算校验和，与记录不一致时返回错误
+    pub fn verify(&self, data: &Path) -> Result<(), DataError> {
+        let actual = sha256(data)?;
+        if actual != self.sha256 {
+            return Err(DataError::Checksum {
+                path: data.display().to_string(),
+                expected: self.sha256.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Display for DataManifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {} rows, downloaded at {}, sha256 {}",
+            self.source, self.rows, self.downloaded_at, self.sha256
+        )
+    }
+}
+
+/// 文件或目录（按文件名顺序，跳过元数据文件）的SHA-256
+fn sha256(path: &Path) -> Result<String, DataError> {
+    let mut files = vec![];
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.is_file() && !DataManifest::is_manifest(&path) {
+                files.push(path);
+            }
+        }
+        files.sort();
+    } else {
+        files.push(path.to_path_buf());
+    }
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 1 << 16];
+    for file in files {
+        let mut file = File::open(file)?;
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+#[test]
+fn data_manifest_test() {
+    let dir = std::env::temp_dir().join("hurribot_provenance_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let data = dir.join("a.csv");
+    std::fs::write(&data, "abc").unwrap();
+    let manifest = DataManifest::new(&data, "test", 1, 1).unwrap();
+    assert_eq!(
+        manifest.sha256,
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    manifest.write(&data).unwrap();
+    assert_eq!(DataManifest::read(&data).unwrap(), Some(manifest.clone()));
+    manifest.verify(&data).unwrap();
+    // 目录的校验和不受目录中元数据文件影响
+    let dir_manifest = DataManifest::new(&dir, "test", 1, 1).unwrap();
+    assert_eq!(dir_manifest.sha256, manifest.sha256);
+    std::fs::write(&data, "abd").unwrap();
+    assert!(matches!(
+        manifest.verify(&data),
+        Err(DataError::Checksum { .. })
+    ));
+    assert_eq!(DataManifest::read(&dir.join("b.csv")).unwrap(), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,240 @@
+use std::path::{Path, PathBuf};
+
+use plotters::prelude::*;
+use tracing::info;
+
+use super::{
+    candle_chart::CandleData,
+    report::{format_time, key_value_table},
+    strategy::TradeRecord,
+};
+
+/// 逐笔回放交易：每笔交易一页HTML，内嵌交易前后k线的蜡烛图并标出开平仓，
+/// 页面之间可前后翻页，便于检查阶梯杠杆等状态切换
+#[derive(Debug)]
+pub struct Replay<'a> {
+    /// 按时间升序
+    candles: &'a [CandleData],
+    trades: &'a [TradeRecord],
+    /// 开仓前和平仓后各显示的k线数
+    context: usize,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(candles: &'a [CandleData], trades: &'a [TradeRecord]) -> Self {
+        Self {
+            candles,
+            trades,
+            context: 30,
+        }
+    }
+    pub fn with_context(mut self, context: usize) -> Self {
+        self.context = context;
+        self
+    }
+    /// 交易前后的k线窗口
+    fn window(&self, trade: &TradeRecord) -> &'a [CandleData] {
+        let start = self
+            .candles
+            .partition_point(|c| c.close_time < trade.open_time)
+            .saturating_sub(self.context);
+        let end = (self
+            .candles
+            .partition_point(|c| c.open_time <= trade.close_time)
+            + self.context)
+            .min(self.candles.len());
+        &self.candles[start..end.max(start)]
+    }
+    fn page_name(i: usize) -> String {
+        format!("trade-{:04}.html", i + 1)
+    }
+    /// 第i笔交易的页面
+    pub fn page(&self, i: usize) -> Result<String, Box<dyn std::error::Error>> {
+        let trade = &self.trades[i];
+        let title = format!("Trade {}/{}", i + 1, self.trades.len());
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+             <style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse}}\
+             td,th{{border:1px solid #ccc;padding:2px 8px;text-align:right}}</style>\n\
+             </head>\n<body>\n<h1>{0}</h1>\n<p>",
+            title
+        );
+        if i > 0 {
+            html += &format!("<a href=\"{}\">previous</a> | ", Self::page_name(i - 1));
+        }
+        html += "<a href=\"index.html\">index</a>";
+        if i + 1 < self.trades.len() {
+            html += &format!(" | <a href=\"{}\">next</a>", Self::page_name(i + 1));
+        }
+        html += "</p>\n";
+        let previous = i.checked_sub(1).map(|p| &self.trades[p]);
+        let leverage = match previous {
+            Some(p) if p.leverage != trade.leverage => {
+                format!("{} (from {})", trade.leverage, p.leverage)
+            }
+            _ => trade.leverage.to_string(),
+        };
+        let rows = [
+            (
+                "side",
+                if trade.is_bull { "long" } else { "short" }.to_string(),
+            ),
+            ("open", format_time(trade.open_time)),
+            ("close", format_time(trade.close_time)),
+            ("entry", trade.entry_price.to_string()),
+            ("exit", trade.exit_price.to_string()),
+            ("leverage", leverage),
+            ("margin", format!("{:.2}", trade.margin)),
+            ("pnl", format!("{:.2}", trade.pnl)),
+            ("reason", format!("{:?}", trade.reason)),
+        ];
+        html += &key_value_table(rows.iter().map(|(k, v)| (*k, v.as_str())));
+        html += &self.svg(trade)?;
+        html += "</body>\n</html>\n";
+        Ok(html)
+    }
+    /// 全部交易的目录页
+    pub fn index(&self) -> String {
+        let mut html = "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                        <title>Replay</title>\n</head>\n<body>\n<h1>Replay</h1>\n<ol>\n"
+            .to_string();
+        for (i, t) in self.trades.iter().enumerate() {
+            html += &format!(
+                "<li><a href=\"{}\">{} {} x{} {:?} pnl {:.2}</a></li>\n",
+                Self::page_name(i),
+                format_time(t.open_time),
+                if t.is_bull { "long" } else { "short" },
+                t.leverage,
+                t.reason,
+                t.pnl
+            );
+        }
+        html + "</ol>\n</body>\n</html>\n"
+    }
+    /// 写入dir/index.html和各交易页面，返回目录页路径
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(dir)?;
+        for i in 0..self.trades.len() {
+            std::fs::write(dir.join(Self::page_name(i)), self.page(i)?)?;
+        }
+        let index = dir.join("index.html");
+        std::fs::write(&index, self.index())?;
+        info!("replay {} trades: {}", self.trades.len(), index.display());
+        Ok(index)
+    }
+    /// 以分钟为横轴的蜡烛图，开仓为圆点，平仓为叉，虚线为开平仓价
+    fn svg(&self, trade: &TradeRecord) -> Result<String, Box<dyn std::error::Error>> {
+        let candles = self.window(trade);
+        let mut svg = String::new();
+        let Some(start) = candles.first().map(|c| c.open_time) else {
+            return Ok(svg);
+        };
+        {
+            let x = |t: time::OffsetDateTime| (t - start).as_seconds_f64() / 60.;
+            let root_area = SVGBackend::with_string(&mut svg, (1280, 600)).into_drawing_area();
+            root_area.fill(&WHITE)?;
+            let min_y = candles
+                .iter()
+                .map(|c| c.low)
+                .chain([trade.entry_price, trade.exit_price])
+                .fold(f64::INFINITY, f64::min);
+            let max_y = candles
+                .iter()
+                .map(|c| c.high)
+                .chain([trade.entry_price, trade.exit_price])
+                .fold(f64::NEG_INFINITY, f64::max);
+            let margin = ((max_y - min_y) * 0.05).max(1e-6);
+            let max_x = candles.last().map_or(1., |c| x(c.close_time)).max(1e-3);
+            let mut chart = ChartBuilder::on(&root_area)
+                .x_label_area_size(35)
+                .y_label_area_size(60)
+                .build_cartesian_2d(0f64..max_x, (min_y - margin)..(max_y + margin))?;
+            chart.configure_mesh().x_desc("minutes").draw()?;
+            let width = (1200. / candles.len() as f64 * 0.6).clamp(1., 15.) as u32;
+            chart.draw_series(candles.iter().map(|c| {
+                CandleStick::new(
+                    (x(c.open_time) + x(c.close_time)) / 2.,
+                    c.open,
+                    c.high,
+                    c.low,
+                    c.close,
+                    GREEN.filled(),
+                    RED.filled(),
+                    width,
+                )
+            }))?;
+            let (open_x, close_x) = (x(trade.open_time), x(trade.close_time));
+            for (price, color) in [(trade.entry_price, BLUE), (trade.exit_price, MAGENTA)] {
+                chart.draw_series(DashedLineSeries::new(
+                    [(open_x, price), (close_x, price)],
+                    5,
+                    5,
+                    color.stroke_width(1),
+                ))?;
+            }
+            chart.draw_series([Circle::new((open_x, trade.entry_price), 6, BLUE.filled())])?;
+            chart.draw_series([Cross::new(
+                (close_x, trade.exit_price),
+                6,
+                MAGENTA.stroke_width(2),
+            )])?;
+            root_area.present()?;
+        }
+        Ok(svg + "\n")
+    }
+}
+
+#[test]
+fn replay_test() {
+    use super::strategy::ExitReason;
+    use crate::fee::FillType;
+    use time::{Duration, OffsetDateTime};
+
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let candles: Vec<CandleData> = (0..100)
+        .map(|i| {
+            let close = 100. + i as f64 * 0.1;
+            CandleData {
+                open: close - 0.05,
+                high: close + 0.2,
+                low: close - 0.2,
+                close,
+                volume: 1.,
+                open_time: start + Duration::minutes(i),
+                close_time: start + Duration::minutes(i + 1) - Duration::milliseconds(1),
+            }
+        })
+        .collect();
+    let trade = |open: i64, close: i64, leverage: f64| TradeRecord {
+        is_bull: true,
+        open_time: candles[open as usize].close_time,
+        close_time: candles[close as usize].close_time,
+        entry_price: candles[open as usize].close,
+        exit_price: candles[close as usize].close,
+        leverage,
+        margin: 10.,
+        pnl: 1.,
+        reason: ExitReason::TakeProfit,
+        entry_fill: FillType::Taker,
+        exit_fill: FillType::Maker,
+        entry_fee: 0.,
+        exit_fee: 0.,
+    };
+    let trades = vec![trade(5, 10, 10.), trade(50, 60, 20.)];
+    let replay = Replay::new(&candles, &trades).with_context(10);
+    // 开仓k线前10根到平仓k线后10根，受数据边界限制
+    assert_eq!(replay.window(&trades[0]).len(), 5 + 6 + 10);
+    assert_eq!(replay.window(&trades[1]).len(), 10 + 11 + 10);
+    let page = replay.page(1).unwrap();
+    assert!(page.contains("<svg"));
+    assert!(page.contains("20 (from 10)"));
+    assert!(page.contains("href=\"trade-0001.html\">previous"));
+    assert!(!page.contains(">next<"));
+    let dir = std::env::temp_dir().join("hurribot_replay_test");
+    let index = replay.write(&dir).unwrap();
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+    assert!(std::fs::read_to_string(index)
+        .unwrap()
+        .contains("trade-0002.html"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
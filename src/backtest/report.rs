@@ -0,0 +1,385 @@
+use std::path::{Path, PathBuf};
+
+use plotters::prelude::*;
+use time::{macros::format_description, OffsetDateTime};
+use tracing::info;
+
+use super::{
+    benchmark::{Benchmark, BenchmarkStats},
+    candle_chart::CandleData,
+    engine::{Backtest, BacktestResult},
+    journal::write_journal,
+    provenance::DataManifest,
+    strategy::{Strategy, TradeRecord},
+};
+use crate::utils::local_now;
+
+/// 默认的报告目录
+pub const REPORT_DIR: &str = "./reports";
+
+/// 单次回测的完整报告：指标、参数、资金/回撤曲线和交易记录
+#[derive(Debug, Clone)]
+pub struct BacktestReport {
+    pub name: String,
+    /// 策略参数（名称，取值）
+    pub params: Vec<(String, String)>,
+    pub initial_value: f64,
+    pub result: BacktestResult,
+    pub liquidations: usize,
+    /// （时间，资金，回撤）
+    pub curve: Vec<(OffsetDateTime, f64, f64)>,
+    pub trades: Vec<TradeRecord>,
+    /// 与资金曲线对齐的基准，如交易币种或BTC的买入持有
+    pub benchmarks: Vec<Benchmark>,
+    /// 回测所用数据的来源和校验和
+    pub data: Option<DataManifest>,
+}
+
+impl BacktestReport {
+    /// 回测并收集报告，每隔sample_every根k线记录一次资金
+    pub fn run<S: Strategy + ?Sized>(
+        name: &str,
+        params: Vec<(String, String)>,
+        strategy: &mut S,
+        candles: &[CandleData],
+        sample_every: usize,
+    ) -> Self {
+        let backtest = Backtest {
+            report_every: sample_every.max(1),
+            ..Default::default()
+        };
+        let initial_value = strategy.value();
+        let mut curve = vec![];
+        let result = backtest.run(strategy, candles, |p| {
+            curve.push((p.time, p.equity, p.drawdown));
+        });
+        Self {
+            name: name.to_string(),
+            params,
+            initial_value,
+            result,
+            liquidations: strategy.liquidations(),
+            curve,
+            trades: strategy.trades().to_vec(),
+            benchmarks: vec![],
+            data: None,
+        }
+    }
+    /// 记录数据版本，如CandleChart::provenance
+    pub fn with_data(mut self, data: Option<DataManifest>) -> Self {
+        self.data = data;
+        self
+    }
+    /// 加入name的买入持有基准，candles需按时间升序
+    pub fn with_benchmark(mut self, name: &str, candles: &[CandleData]) -> Self {
+        let times: Vec<OffsetDateTime> = self.curve.iter().map(|c| c.0).collect();
+        // 起点与资金曲线的第一个采样相同
+        let initial = self.curve.first().map_or(self.initial_value, |c| c.1);
+        if let Some(b) = Benchmark::buy_and_hold(name, candles, initial, &times) {
+            self.benchmarks.push(b);
+        }
+        self
+    }
+    /// 各基准的（名称，比较结果）
+    pub fn benchmark_stats(&self) -> Vec<(&str, BenchmarkStats)> {
+        let equity: Vec<f64> = self.curve.iter().map(|c| c.1).collect();
+        self.benchmarks
+            .iter()
+            .map(|b| (b.name.as_str(), b.compare(&equity)))
+            .collect()
+    }
+    pub fn return_rate(&self) -> f64 {
+        if self.initial_value > 0. {
+            self.result.value / self.initial_value
+        } else {
+            f64::NAN
+        }
+    }
+    /// 盈利交易占比
+    pub fn win_rate(&self) -> f64 {
+        if self.trades.is_empty() {
+            return f64::NAN;
+        }
+        self.trades.iter().filter(|t| t.pnl > 0.).count() as f64 / self.trades.len() as f64
+    }
+    /// 已支付的手续费（maker，taker）
+    pub fn fees(&self) -> (f64, f64) {
+        self.trades.iter().fold((0., 0.), |(maker, taker), t| {
+            let (m, k) = t.fees_by_fill();
+            (maker + m, taker + k)
+        })
+    }
+    /// 总盈利 / 总亏损
+    pub fn profit_factor(&self) -> f64 {
+        let profit: f64 = self.trades.iter().map(|t| t.pnl.max(0.)).sum();
+        let loss: f64 = self.trades.iter().map(|t| (-t.pnl).max(0.)).sum();
+        profit / loss
+    }
+    pub fn metrics(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("initial", format!("{:.2}", self.initial_value)),
+            ("final", format!("{:.2}", self.result.value)),
+            ("return", format!("{:.4}", self.return_rate())),
+            (
+                "max drawdown",
+                format!("{:.2}%", self.result.max_drawdown * 100.),
+            ),
+            ("trades", self.trades.len().to_string()),
+            ("win rate", format!("{:.2}%", self.win_rate() * 100.)),
+            ("profit factor", format!("{:.2}", self.profit_factor())),
+            ("maker fees", format!("{:.2}", self.fees().0)),
+            ("taker fees", format!("{:.2}", self.fees().1)),
+            ("liquidations", self.liquidations.to_string()),
+            ("candles", self.result.processed.to_string()),
+            ("aborted", self.result.aborted.to_string()),
+            (
+                "capital exhausted",
+                self.result
+                    .exhausted
+                    .map(|e| e.time.to_string())
+                    .unwrap_or("-".to_string()),
+            ),
+            (
+                "data",
+                self.data
+                    .as_ref()
+                    .map(|d| d.to_string())
+                    .unwrap_or("-".to_string()),
+            ),
+        ]
+    }
+    /// 生成自包含的HTML，图表以SVG内嵌
+    pub fn html(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+             <style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse}}\
+             td,th{{border:1px solid #ccc;padding:2px 8px;text-align:right}}</style>\n\
+             </head>\n<body>\n<h1>{0}</h1>\n",
+            escape(&self.name)
+        );
+        html += "<h2>Metrics</h2>\n";
+        html += &key_value_table(self.metrics().iter().map(|(k, v)| (*k, v.as_str())));
+        html += "<h2>Parameters</h2>\n";
+        html += &key_value_table(self.params.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        let stats = self.benchmark_stats();
+        if !stats.is_empty() {
+            html += "<h2>Benchmarks</h2>\n<table>\n<tr><th>benchmark</th><th>return</th>\
+                     <th>alpha</th><th>beta</th><th>max drawdown</th>\
+                     <th>relative drawdown</th></tr>\n";
+            for (name, s) in stats {
+                html += &format!(
+                    "<tr><td>{}</td><td>{:.4}</td><td>{:.4}</td><td>{:.4}</td>\
+                     <td>{:.2}%</td><td>{:.2}%</td></tr>\n",
+                    escape(name),
+                    s.return_rate,
+                    s.alpha,
+                    s.beta,
+                    s.max_drawdown * 100.,
+                    s.relative_drawdown * 100.
+                );
+            }
+            html += "</table>\n";
+        }
+        html += "<h2>Equity</h2>\n";
+        html += &self.svg("Equity", |c| c.1, &self.benchmarks)?;
+        html += "<h2>Drawdown</h2>\n";
+        html += &self.svg("Drawdown (%)", |c| -c.2 * 100., &[])?;
+        html += "<h2>Trades</h2>\n<table>\n<tr><th>side</th><th>open</th><th>close</th>\
+                 <th>entry</th><th>exit</th><th>leverage</th><th>margin</th><th>pnl</th>\
+                 <th>reason</th></tr>\n";
+        for t in self.trades.iter() {
+            html += &format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                 <td>{:.2}</td><td>{:.2}</td><td>{:?}</td></tr>\n",
+                if t.is_bull { "long" } else { "short" },
+                format_time(t.open_time),
+                format_time(t.close_time),
+                t.entry_price,
+                t.exit_price,
+                t.leverage,
+                t.margin,
+                t.pnl,
+                t.reason
+            );
+        }
+        html += "</table>\n</body>\n</html>\n";
+        Ok(html)
+    }
+    /// 写入 dir/<时间>/<name>.html 和交易记录 <name>.trades.csv，返回报告路径
+    pub fn write(&self, dir: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let dir = Path::new(dir).join(local_now().format(format_description!(
+            "[year][month][day]-[hour][minute][second]"
+        ))?);
+        std::fs::create_dir_all(&dir)?;
+        let name: String = self
+            .name
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{}.html", name));
+        std::fs::write(&path, self.html()?)?;
+        write_journal(&dir.join(format!("{}.trades.csv", name)), &self.trades)?;
+        info!("backtest report: {}", path.display());
+        Ok(path)
+    }
+    /// 以小时为横轴绘制曲线，overlays为叠加的基准曲线
+    fn svg<F>(
+        &self,
+        caption: &str,
+        value: F,
+        overlays: &[Benchmark],
+    ) -> Result<String, Box<dyn std::error::Error>>
+    where
+        F: Fn(&(OffsetDateTime, f64, f64)) -> f64,
+    {
+        let mut svg = String::new();
+        let Some(start) = self.curve.first().map(|c| c.0) else {
+            return Ok(svg);
+        };
+        {
+            let root_area = SVGBackend::with_string(&mut svg, (1280, 480)).into_drawing_area();
+            root_area.fill(&WHITE)?;
+            let points: Vec<(f64, f64)> = self
+                .curve
+                .iter()
+                .map(|c| ((c.0 - start).as_seconds_f64() / 3600., value(c)))
+                .collect();
+            let overlays: Vec<(&str, Vec<(f64, f64)>)> = overlays
+                .iter()
+                .map(|b| {
+                    let points = b
+                        .curve
+                        .iter()
+                        .map(|c| ((c.0 - start).as_seconds_f64() / 3600., c.1))
+                        .collect();
+                    (b.name.as_str(), points)
+                })
+                .collect();
+            let max_x = points.last().map_or(1., |p| p.0).max(1e-3);
+            let ys = points
+                .iter()
+                .chain(overlays.iter().flat_map(|o| o.1.iter()))
+                .map(|p| p.1);
+            let min_y = ys.clone().fold(f64::INFINITY, f64::min);
+            let max_y = ys.fold(f64::NEG_INFINITY, f64::max);
+            let margin = ((max_y - min_y) * 0.05).max(1e-6);
+            let mut chart = ChartBuilder::on(&root_area)
+                .caption(caption, ("sans-serif", 30).into_font())
+                .x_label_area_size(35)
+                .y_label_area_size(60)
+                .build_cartesian_2d(0f64..max_x, (min_y - margin)..(max_y + margin))?;
+            chart.configure_mesh().x_desc("hours").draw()?;
+            chart
+                .draw_series(LineSeries::new(points, BLUE))?
+                .label(caption)
+                .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE));
+            let legend = !overlays.is_empty();
+            for (i, (name, points)) in overlays.into_iter().enumerate() {
+                let color = Palette99::pick(i + 1).to_rgba();
+                chart
+                    .draw_series(LineSeries::new(points, color))?
+                    .label(name)
+                    .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+            }
+            if legend {
+                chart
+                    .configure_series_labels()
+                    .background_style(WHITE.mix(0.8))
+                    .border_style(BLACK)
+                    .draw()?;
+            }
+            root_area.present()?;
+        }
+        Ok(svg + "\n")
+    }
+}
+
+pub(super) fn key_value_table<'a>(rows: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    let mut table = "<table>\n".to_string();
+    for (k, v) in rows {
+        table += &format!("<tr><th>{}</th><td>{}</td></tr>\n", escape(k), escape(v));
+    }
+    table + "</table>\n"
+}
+
+pub(super) fn format_time(time: OffsetDateTime) -> String {
+    time.format(format_description!(
+        "[year]-[month]-[day] [hour]:[minute]:[second]"
+    ))
+    .unwrap_or_default()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn backtest_report_test() {
+    use super::strategy::geo_strategy::GeoStrategy;
+    use crate::fee::FillType;
+    use std::sync::{Arc, Mutex};
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let candles: Vec<_> = (0..200)
+        .map(|i| {
+            let close = 100. + (i as f64 / 10.).sin() * 5.;
+            CandleData {
+                open: close,
+                high: close + 1.,
+                low: close - 1.,
+                close,
+                close_time: start + Duration::minutes(i),
+                ..Default::default()
+            }
+        })
+        .collect();
+    let mut strategy = GeoStrategy::new(
+        true,
+        10.,
+        0.5,
+        Duration::minutes(10),
+        100.,
+        0.05,
+        0.01,
+        Arc::new(Mutex::new(1000.)),
+    );
+    let report = BacktestReport::run(
+        "geo <test>",
+        vec![("leverage".to_string(), "10".to_string())],
+        &mut strategy,
+        &candles,
+        10,
+    );
+    assert_eq!(report.curve.len(), 20);
+    assert!(!report.trades.is_empty());
+    // 市价开仓按taker收费，止盈按maker
+    let (maker, taker) = report.fees();
+    assert!(maker > 0. && taker > 0.);
+    let entry_fees: f64 = report.trades.iter().map(|t| t.entry_fee).sum();
+    assert!(taker >= entry_fees - 1e-9);
+    assert!(report
+        .trades
+        .iter()
+        .all(|t| t.entry_fill == FillType::Taker));
+    // 交易币种买入持有作为基准
+    let report = report.with_benchmark("hold", &candles);
+    let stats = report.benchmark_stats();
+    assert_eq!(stats.len(), 1);
+    let close_at = |time| candles.iter().find(|c| c.close_time == time).unwrap().close;
+    let hold = close_at(report.curve.last().unwrap().0) / close_at(report.curve[0].0);
+    assert!((report.benchmarks[0].curve[0].1 - report.curve[0].1).abs() < 1e-9);
+    assert!((stats[0].1.return_rate - hold).abs() < 1e-9);
+    assert!(stats[0].1.beta > 0.);
+    let html = report.html().unwrap();
+    assert!(html.contains("<h2>Benchmarks</h2>"));
+    assert!(html.contains("geo &lt;test&gt;"));
+    assert_eq!(html.matches("<svg").count(), 2);
+    let dir = std::env::temp_dir().join("hurribot_report_test");
+    std::fs::remove_dir_all(&dir).ok();
+    let path = report.write(dir.to_str().unwrap()).unwrap();
+    assert!(path.ends_with("geo__test_.html"));
+}
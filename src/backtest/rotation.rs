@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use time::OffsetDateTime;
+
+use super::{
+    candle_chart::CandleData,
+    contract::Contract,
+    strategy::{ExitReason, TradeRecord},
+};
+use crate::{
+    fee::{FeeSchedule, FillType},
+    strategy::rotation::{MomentumTracker, Rebalance, RotationConfig},
+};
+
+#[derive(Debug, Clone)]
+pub struct RotationResult {
+    pub initial: f64,
+    /// 全部平仓后的资金
+    pub value: f64,
+    /// 持仓发生变化的调仓次数
+    pub rebalances: usize,
+    /// 开仓和平仓的成交额之和
+    pub turnover: f64,
+    /// 开仓和平仓手续费之和
+    pub fees: f64,
+    pub liquidations: usize,
+    /// （币种，交易记录），按平仓时间排序
+    pub trades: Vec<(String, TradeRecord)>,
+}
+
+impl RotationResult {
+    pub fn return_rate(&self) -> f64 {
+        self.value / self.initial
+    }
+    /// 成交额相对初始资金的倍数
+    pub fn turnover_ratio(&self) -> f64 {
+        self.turnover / self.initial
+    }
+}
+
+/// 动量轮动回测：各币种按收盘时间对齐，每个调仓间隔按动量排名持有前top_k个币种，
+/// 平掉跌出的币种后以总资金的1/top_k市价买入新进的币种
+#[derive(Debug, Clone)]
+pub struct RotationBacktest {
+    config: RotationConfig,
+    capital: f64,
+    leverage: f64,
+    fees: FeeSchedule,
+}
+
+impl RotationBacktest {
+    pub fn new(config: RotationConfig, capital: f64, leverage: f64) -> Self {
+        Self {
+            config,
+            capital,
+            leverage,
+            fees: FeeSchedule::default(),
+        }
+    }
+    pub fn set_fees(&mut self, fees: FeeSchedule) {
+        self.fees = fees;
+    }
+    /// charts为（币种，按时间升序的k线），结束时按各自最后的收盘价平仓
+    pub fn run(&self, charts: &[(&str, &[CandleData])]) -> RotationResult {
+        let universe: Vec<String> = charts.iter().map(|(s, _)| s.to_string()).collect();
+        let mut tracker = MomentumTracker::new(self.config.lookback);
+        let mut capital = self.capital;
+        let mut held: Vec<(String, Contract)> = vec![];
+        let mut closes: HashMap<String, f64> = HashMap::new();
+        let mut result = RotationResult {
+            initial: self.capital,
+            value: 0.,
+            rebalances: 0,
+            turnover: 0.,
+            fees: 0.,
+            liquidations: 0,
+            trades: vec![],
+        };
+        let mut settle = |symbol: &str, contract: &Contract, now, price, returned, reason| {
+            let record = TradeRecord::new(contract, now, price, returned, reason);
+            result.turnover +=
+                contract.amount * contract.entry_price + contract.amount * record.exit_price;
+            result.fees += record.entry_fee + record.exit_fee;
+            if reason == ExitReason::Liquidation {
+                result.liquidations += 1;
+            }
+            result.trades.push((symbol.to_string(), record));
+            returned
+        };
+        let mut rebalances = 0;
+        let mut cursors = vec![0; charts.len()];
+        let mut next: Option<OffsetDateTime> = None;
+        while let Some(now) = (0..charts.len())
+            .filter(|&i| cursors[i] < charts[i].1.len())
+            .map(|i| charts[i].1[cursors[i]].close_time)
+            .min()
+        {
+            for (i, (symbol, chart)) in charts.iter().enumerate() {
+                let Some(candle) = chart.get(cursors[i]).filter(|c| c.close_time == now) else {
+                    continue;
+                };
+                cursors[i] += 1;
+                tracker.update(symbol, candle.close);
+                closes.insert(symbol.to_string(), candle.close);
+                if let Some(j) = held.iter().position(|(s, _)| s == symbol) {
+                    let contract = &held[j].1;
+                    if let Some(r) = contract.liquidate(candle.low) {
+                        let (_, contract) = held.remove(j);
+                        capital += settle(
+                            symbol,
+                            &contract,
+                            now,
+                            contract.liq_price,
+                            r,
+                            ExitReason::Liquidation,
+                        );
+                    }
+                }
+            }
+            if next.is_some_and(|next| now < next) {
+                continue;
+            }
+            next = Some(now + self.config.rebalance);
+            let target = tracker.select(&universe, self.config.top_k, self.config.min_momentum);
+            let symbols: Vec<String> = held.iter().map(|(s, _)| s.clone()).collect();
+            let rebalance = Rebalance::new(&symbols, &target);
+            if rebalance.is_empty() {
+                continue;
+            }
+            rebalances += 1;
+            for symbol in rebalance.close.iter() {
+                let j = held.iter().position(|(s, _)| s == symbol).unwrap();
+                let (_, contract) = held.remove(j);
+                let price = closes[symbol];
+                let r = contract.close_as(price, ExitReason::Signal.fill());
+                capital += settle(symbol, &contract, now, price, r, ExitReason::Signal);
+            }
+            let equity = capital
+                + held
+                    .iter()
+                    .map(|(s, c)| c.cover_as(closes[s], FillType::Taker))
+                    .sum::<f64>();
+            let slot = equity / self.config.top_k as f64;
+            for symbol in rebalance.open {
+                let offered = slot.min(capital);
+                if offered <= 0. {
+                    break;
+                }
+                let contract = Contract::open_as(
+                    true,
+                    closes[&symbol],
+                    offered,
+                    self.leverage,
+                    now,
+                    None,
+                    self.fees,
+                    FillType::Taker,
+                );
+                capital -= offered;
+                held.push((symbol, contract));
+            }
+        }
+        for (symbol, contract) in held {
+            let price = closes[&symbol];
+            let r = contract.close_as(price, ExitReason::Close.fill());
+            let now = charts
+                .iter()
+                .find(|(s, _)| *s == symbol)
+                .and_then(|(_, c)| c.last())
+                .map_or(contract.open_time, |c| c.close_time);
+            capital += settle(&symbol, &contract, now, price, r, ExitReason::Close);
+        }
+        result.value = capital;
+        result.rebalances = rebalances;
+        result
+    }
+}
+
+#[test]
+fn rotation_backtest_test() {
+    use std::time::Duration;
+    use time::Duration as TimeDuration;
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let chart = |closes: &[f64]| -> Vec<CandleData> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| CandleData {
+                open: close,
+                high: close,
+                low: close,
+                close,
+                close_time: start + TimeDuration::minutes(i as i64),
+                ..Default::default()
+            })
+            .collect()
+    };
+    // A先涨后跌，B后涨，C一直横盘
+    let a = chart(&[10., 11., 12., 12., 11., 10., 10.]);
+    let b = chart(&[10., 10., 10., 11., 12., 13., 14.]);
+    let c = chart(&[10.; 7]);
+    let config = RotationConfig {
+        lookback: 1,
+        top_k: 1,
+        rebalance: Duration::from_secs(60),
+        min_momentum: 0.01,
+        ..Default::default()
+    };
+    let result = RotationBacktest::new(config, 1000., 1.).run(&[
+        ("AUSDT", &a),
+        ("BUSDT", &b),
+        ("CUSDT", &c),
+    ]);
+    let symbols: Vec<&str> = result.trades.iter().map(|(s, _)| s.as_str()).collect();
+    assert_eq!(symbols, ["AUSDT", "BUSDT"]);
+    assert_eq!(result.trades[0].1.reason, ExitReason::Signal);
+    assert_eq!(result.trades[1].1.reason, ExitReason::Close);
+    // 买入A、换到B两次调仓，结束时平仓不计入
+    assert_eq!(result.rebalances, 2);
+    let fees: f64 = result
+        .trades
+        .iter()
+        .map(|(_, t)| t.entry_fee + t.exit_fee)
+        .sum();
+    assert!((result.fees - fees).abs() < 1e-9);
+    assert!(result.turnover_ratio() > 3.);
+    assert!(result.return_rate() > 1.);
+    assert_eq!(result.liquidations, 0);
+}
@@ -0,0 +1,231 @@
+use rayon::prelude::*;
+
+use super::{candle_chart::CandleData, engine::Backtest, strategy::Strategy};
+
+/// 注入历史k线的合成冲击
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shock {
+    /// 从第at根k线起整体跳空，价格乘以(1 + change)
+    Gap { at: usize, change: f64 },
+    /// 第at根k线出现影线，change为负时下影线最低到open * (1 + change)，为正时为上影线
+    Wick { at: usize, change: f64 },
+    /// 从第at根k线起持续length根无成交，价格停在前一根收盘价，恢复时直接跳回原价格
+    Freeze { at: usize, length: usize },
+}
+
+impl Shock {
+    pub fn apply(&self, candles: &mut [CandleData]) {
+        match *self {
+            Shock::Gap { at, change } => {
+                for c in candles.iter_mut().skip(at) {
+                    c.open *= 1. + change;
+                    c.high *= 1. + change;
+                    c.low *= 1. + change;
+                    c.close *= 1. + change;
+                }
+            }
+            Shock::Wick { at, change } => {
+                if let Some(c) = candles.get_mut(at) {
+                    let price = c.open * (1. + change);
+                    c.low = c.low.min(price);
+                    c.high = c.high.max(price);
+                }
+            }
+            Shock::Freeze { at, length } => {
+                let Some(price) = at
+                    .checked_sub(1)
+                    .and_then(|i| candles.get(i))
+                    .or(candles.get(at))
+                    .map(|c| c.close)
+                else {
+                    return;
+                };
+                for c in candles.iter_mut().skip(at).take(length) {
+                    c.open = price;
+                    c.high = price;
+                    c.low = price;
+                    c.close = price;
+                    c.volume = 0.;
+                }
+            }
+        }
+    }
+}
+
+/// 一组冲击构成的压力测试场景
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub name: String,
+    pub shocks: Vec<Shock>,
+}
+
+impl Scenario {
+    pub fn new(name: &str, shocks: Vec<Shock>) -> Self {
+        Self {
+            name: name.to_string(),
+            shocks,
+        }
+    }
+    /// 常用场景：在第at根k线处跳空±10%、±20%影线、240根k线无成交
+    pub fn presets(at: usize) -> Vec<Self> {
+        vec![
+            Self::new("gap down 10%", vec![Shock::Gap { at, change: -0.1 }]),
+            Self::new("gap up 10%", vec![Shock::Gap { at, change: 0.1 }]),
+            Self::new("wick down 20%", vec![Shock::Wick { at, change: -0.2 }]),
+            Self::new("wick up 20%", vec![Shock::Wick { at, change: 0.2 }]),
+            Self::new("freeze 240", vec![Shock::Freeze { at, length: 240 }]),
+        ]
+    }
+    pub fn apply(&self, candles: &[CandleData]) -> Vec<CandleData> {
+        let mut candles = candles.to_vec();
+        for shock in self.shocks.iter() {
+            shock.apply(&mut candles);
+        }
+        candles
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScenarioResult {
+    pub name: String,
+    pub final_value: f64,
+    /// 回测过程中的最低资金
+    pub min_equity: f64,
+    pub max_drawdown: f64,
+    pub liquidations: usize,
+    pub aborted: bool,
+}
+
+/// 对原始k线（名为base）和每个场景分别用新建的策略回测，并行运行
+pub fn stress_test<S, F>(
+    new_strategy: F,
+    candles: &[CandleData],
+    scenarios: &[Scenario],
+) -> Vec<ScenarioResult>
+where
+    S: Strategy,
+    F: Fn() -> S + Sync,
+{
+    let backtest = Backtest {
+        report_every: 1,
+        ..Default::default()
+    };
+    let base = Scenario::new("base", vec![]);
+    std::iter::once(&base)
+        .chain(scenarios.iter())
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|scenario| {
+            let candles = scenario.apply(candles);
+            let mut strategy = new_strategy();
+            let mut min_equity = strategy.value();
+            let result = backtest.run(&mut strategy, &candles, |p| {
+                min_equity = min_equity.min(p.equity);
+            });
+            ScenarioResult {
+                name: scenario.name.clone(),
+                final_value: result.value,
+                min_equity,
+                max_drawdown: result.max_drawdown,
+                liquidations: strategy.liquidations(),
+                aborted: result.aborted,
+            }
+        })
+        .collect()
+}
+
+/// 压力测试结果表格
+pub fn table(results: &[ScenarioResult]) -> String {
+    let mut table = format!(
+        "{:<20}{:>14}{:>14}{:>10}{:>8}{:>8}\n",
+        "scenario", "final", "min equity", "max dd", "liq", "abort"
+    );
+    for r in results {
+        table += &format!(
+            "{:<20}{:>14.2}{:>14.2}{:>9.2}%{:>8}{:>8}\n",
+            r.name,
+            r.final_value,
+            r.min_equity,
+            r.max_drawdown * 100.,
+            r.liquidations,
+            r.aborted
+        );
+    }
+    table
+}
+
+#[test]
+fn stress_test_test() {
+    use super::contract::Contract;
+    use time::{Duration, OffsetDateTime};
+
+    /// 第一根k线10倍开多并一直持有
+    #[derive(Debug, Default)]
+    struct Long {
+        capital: f64,
+        contract: Option<Contract>,
+        liquidations: usize,
+    }
+    impl Strategy for Long {
+        fn update(&mut self, candle: &CandleData) {
+            match self.contract.take() {
+                None if self.capital > 0. => {
+                    let c = Contract::open(
+                        true,
+                        candle.close,
+                        self.capital,
+                        10.,
+                        candle.close_time,
+                        None,
+                    );
+                    self.capital = 0.;
+                    self.contract = Some(c);
+                }
+                Some(c) => match c.liquidate(candle.low) {
+                    Some(r) => {
+                        self.capital += r;
+                        self.liquidations += 1;
+                    }
+                    None => self.contract = Some(c),
+                },
+                None => {}
+            }
+        }
+        fn value(&self) -> f64 {
+            self.capital + self.contract.as_ref().map_or(0., |c| c.margin)
+        }
+        fn liquidations(&self) -> usize {
+            self.liquidations
+        }
+    }
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let candles: Vec<_> = (0..10)
+        .map(|i| CandleData {
+            open: 100.,
+            high: 101.,
+            low: 99.,
+            close: 100.,
+            close_time: start + Duration::minutes(i),
+            ..Default::default()
+        })
+        .collect();
+    let frozen = Scenario::new("freeze", vec![Shock::Freeze { at: 2, length: 3 }]).apply(&candles);
+    assert!(frozen[2..5]
+        .iter()
+        .all(|c| c.volume == 0. && c.high == c.low));
+    let results = stress_test(
+        || Long {
+            capital: 100.,
+            ..Default::default()
+        },
+        &candles,
+        &Scenario::presets(5),
+    );
+    assert_eq!(results.len(), 6);
+    assert_eq!(results[0].name, "base");
+    assert_eq!(results[0].liquidations, 0);
+    assert_eq!(results[1].liquidations, 1);
+    assert_eq!(results[3].liquidations, 1);
+    assert_eq!(results[2].liquidations, 0);
+    assert!(table(&results).contains("wick down 20%"));
+}
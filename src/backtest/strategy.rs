@@ -1,13 +1,216 @@
-use super::candle_chart::CandleData;
+use std::{fmt::Display, sync::Mutex};
+
+use time::OffsetDateTime;
+
+use super::{candle_chart::CandleData, contract::Contract};
+use crate::{fee::FillType, strategy::session::Sessioned};
 
 pub trait Strategy {
     fn update(&mut self, candle: &CandleData);
+    /// 预热阶段：只更新指标，不能开仓或产生费用；默认跳过预热的k线，
+    /// 需要预热指标的策略自行实现
+    #[allow(unused_variables)]
+    fn warm_up(&mut self, candle: &CandleData) {}
     #[allow(unused_variables)]
     fn close(&mut self, price: f64) -> f64 {
         self.value()
     }
     fn value(&self) -> f64;
+    /// 累计被强制平仓的次数，用于压力测试统计
+    fn liquidations(&self) -> usize {
+        0
+    }
+    /// 已平仓的交易记录，用于回测报告
+    fn trades(&self) -> &[TradeRecord] {
+        &[]
+    }
+    /// 共享资金池不足时返回Some，回测引擎记录后暂停该策略
+    fn capital_exhausted(&self) -> Option<CapitalExhausted> {
+        None
+    }
+    /// 是否允许开新仓（如交易时段过滤），不影响已有仓位的止盈止损；默认忽略
+    #[allow(unused_variables)]
+    fn allow_entry(&mut self, allowed: bool) {}
+    /// 之后的开仓金额乘以该比例（如回撤降杠杆），0为不开新仓；默认忽略
+    #[allow(unused_variables)]
+    fn scale_entry(&mut self, scale: f64) {}
+    /// 未用作保证金的闲置资金，资金策略只能从中取出；默认None，不支持调拨
+    fn idle_capital(&self) -> Option<f64> {
+        None
+    }
+    /// 注入（正数）或取出（负数）闲置资金，由回测引擎按资金策略调用
+    #[allow(unused_variables)]
+    fn transfer_capital(&mut self, amount: f64) {}
+}
+
+/// 按k线收盘时间判断时段，与实盘使用同一个SessionFilter
+impl<S: Strategy> Strategy for Sessioned<S> {
+    fn update(&mut self, candle: &CandleData) {
+        let allowed = self.filter.allows(candle.close_time);
+        self.inner.allow_entry(allowed);
+        self.inner.update(candle);
+    }
+    fn warm_up(&mut self, candle: &CandleData) {
+        self.inner.warm_up(candle);
+    }
+    fn close(&mut self, price: f64) -> f64 {
+        self.inner.close(price)
+    }
+    fn value(&self) -> f64 {
+        self.inner.value()
+    }
+    fn liquidations(&self) -> usize {
+        self.inner.liquidations()
+    }
+    fn trades(&self) -> &[TradeRecord] {
+        self.inner.trades()
+    }
+    fn capital_exhausted(&self) -> Option<CapitalExhausted> {
+        self.inner.capital_exhausted()
+    }
+    fn allow_entry(&mut self, allowed: bool) {
+        self.inner.allow_entry(allowed);
+    }
+    fn scale_entry(&mut self, scale: f64) {
+        self.inner.scale_entry(scale);
+    }
+    fn idle_capital(&self) -> Option<f64> {
+        self.inner.idle_capital()
+    }
+    fn transfer_capital(&mut self, amount: f64) {
+        self.inner.transfer_capital(amount);
+    }
+}
+
+/// 共享资金池不足以补充策略资金
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CapitalExhausted {
+    pub time: OffsetDateTime,
+    /// 需要补充的资金
+    pub need: f64,
+    /// 资金池剩余
+    pub available: f64,
+}
+
+impl Display for CapitalExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "capital exhausted at {}: need {:.2}, available {:.2}",
+            self.time, self.need, self.available
+        )
+    }
+}
+
+/// 从共享资金池提取资金，不足时不扣除
+pub fn draw_capital(
+    pool: &Mutex<f64>,
+    amount: f64,
+    time: OffsetDateTime,
+) -> Result<(), CapitalExhausted> {
+    let mut available = pool.lock().unwrap();
+    if *available < amount {
+        return Err(CapitalExhausted {
+            time,
+            need: amount,
+            available: *available,
+        });
+    }
+    *available -= amount;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitReason {
+    TakeProfit,
+    /// 移动止盈
+    Trailing,
+    StopLoss,
+    Liquidation,
+    /// 回测结束时平仓
+    Close,
+    /// 策略信号市价平仓
+    Signal,
+}
+
+impl ExitReason {
+    /// 止盈和回测结束按挂单成交，其余为市价、止损单（含止损限价单）或强平
+    pub fn fill(&self) -> FillType {
+        match self {
+            ExitReason::TakeProfit | ExitReason::Close => FillType::Maker,
+            ExitReason::Trailing
+            | ExitReason::StopLoss
+            | ExitReason::Liquidation
+            | ExitReason::Signal => FillType::Taker,
+        }
+    }
+}
+
+/// 一笔已平仓的交易
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeRecord {
+    pub is_bull: bool,
+    pub open_time: OffsetDateTime,
+    pub close_time: OffsetDateTime,
+    pub entry_price: f64,
+    pub exit_price: f64,
+    pub leverage: f64,
+    pub margin: f64,
+    /// 扣除手续费后的盈亏
+    pub pnl: f64,
+    pub reason: ExitReason,
+    pub entry_fill: FillType,
+    pub exit_fill: FillType,
+    /// 开仓和平仓手续费
+    pub entry_fee: f64,
+    pub exit_fee: f64,
+}
+
+impl TradeRecord {
+    /// 按成交类型分别统计的手续费（maker，taker）
+    pub fn fees_by_fill(&self) -> (f64, f64) {
+        let mut fees = (0., 0.);
+        for (fill, fee) in [
+            (self.entry_fill, self.entry_fee),
+            (self.exit_fill, self.exit_fee),
+        ] {
+            match fill {
+                FillType::Maker => fees.0 += fee,
+                FillType::Taker => fees.1 += fee,
+            }
+        }
+        fees
+    }
+    /// price为平仓时的行情价，触发止损或强平时记录实际成交价，见Contract::exit_price；
+    /// returned为平仓后返还的资金
+    pub fn new(
+        contract: &Contract,
+        close_time: OffsetDateTime,
+        price: f64,
+        returned: f64,
+        reason: ExitReason,
+    ) -> Self {
+        let exit_fill = reason.fill();
+        let exit_price = contract.exit_price(price);
+        Self {
+            is_bull: contract.is_bull,
+            open_time: contract.open_time,
+            close_time,
+            entry_price: contract.entry_price,
+            exit_price,
+            leverage: contract.leverage,
+            margin: contract.margin,
+            pnl: returned - contract.margin,
+            reason,
+            entry_fill: contract.entry_fill,
+            exit_fill,
+            entry_fee: contract.entry_fee(),
+            exit_fee: contract.fees.fee(contract.amount * exit_price, exit_fill),
+        }
+    }
 }
 
+pub mod breakout_strategy;
 pub mod geo_strategy;
+pub mod pairs_strategy;
 pub mod roll_strategy;
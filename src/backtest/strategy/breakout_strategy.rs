@@ -0,0 +1,274 @@
+use time::OffsetDateTime;
+
+use crate::{
+    backtest::{candle_chart::CandleData, contract::Contract, positioning::PositioningSeries},
+    fee::{FeeSchedule, FillType},
+    strategy::{
+        breakout::{BreakoutConfig, TrailingStop},
+        roll_judge::RollJudge,
+    },
+};
+
+use super::{ExitReason, Strategy, TradeRecord};
+
+/// 突破策略回测：收盘突破时市价开仓，按ATR移动止损离场
+#[derive(Debug, Clone)]
+pub struct BreakoutStrategy {
+    is_bull: bool,
+    config: BreakoutConfig,
+    leverage: f64,
+    capital: f64,
+    judge: RollJudge,
+    position: Option<(Contract, TrailingStop)>,
+    /// 为false时不开新仓
+    entry_allowed: bool,
+    /// 开仓金额比例
+    entry_scale: f64,
+    fees: FeeSchedule,
+    /// 持仓量数据，配置了min_open_interest_change时使用
+    positioning: Option<PositioningSeries>,
+    now: OffsetDateTime,
+    /// 开仓次数
+    pub open_count: i64,
+    liquidations: usize,
+    journal: Vec<TradeRecord>,
+}
+
+impl BreakoutStrategy {
+    pub fn new(is_bull: bool, config: BreakoutConfig, capital: f64, leverage: f64) -> Self {
+        Self {
+            is_bull,
+            config,
+            leverage,
+            capital,
+            judge: config.judge(),
+            position: None,
+            entry_allowed: true,
+            entry_scale: 1.,
+            fees: FeeSchedule::default(),
+            positioning: None,
+            now: OffsetDateTime::from_unix_timestamp(0).unwrap(),
+            open_count: 0,
+            liquidations: 0,
+            journal: vec![],
+        }
+    }
+    pub fn set_fees(&mut self, fees: FeeSchedule) {
+        self.fees = fees;
+    }
+    /// 实盘轮询记录的持仓量数据，见PositioningFeed::record
+    pub fn set_positioning(&mut self, series: PositioningSeries) {
+        self.positioning = Some(series);
+    }
+    /// 当前的移动止损价
+    pub fn stop(&self) -> Option<f64> {
+        self.position.as_ref().map(|(_, stop)| stop.price)
+    }
+    fn settle(&mut self, contract: &Contract, price: f64, returned: f64, reason: ExitReason) {
+        self.capital += returned;
+        self.journal.push(TradeRecord::new(
+            contract, self.now, price, returned, reason,
+        ));
+    }
+}
+
+impl Strategy for BreakoutStrategy {
+    fn update(&mut self, candle: &CandleData) {
+        self.now = candle.close_time;
+        // 先按上一根k线的止损判断离场，再移动止损
+        if let Some((contract, stop)) = self.position.take() {
+            let beyond_liq = |price: f64| {
+                (self.is_bull && price < contract.liq_price)
+                    || (!self.is_bull && price > contract.liq_price)
+            };
+            let exit = match stop.hit(candle) {
+                Some(price) if !beyond_liq(price) => Some((
+                    contract.cover_as(price, FillType::Taker),
+                    price,
+                    ExitReason::Trailing,
+                )),
+                _ => {
+                    let worst = if self.is_bull {
+                        candle.low
+                    } else {
+                        candle.high
+                    };
+                    contract
+                        .liquidate(worst)
+                        .map(|r| (r, contract.liq_price, ExitReason::Liquidation))
+                }
+            };
+            match exit {
+                Some((r, price, reason)) => {
+                    if reason == ExitReason::Liquidation {
+                        self.liquidations += 1;
+                    }
+                    self.settle(&contract, price, r, reason);
+                }
+                None => self.position = Some((contract, stop)),
+            }
+        }
+        self.judge.update(candle);
+        let distance = self
+            .config
+            .stop_distance(&self.judge, candle.close, self.leverage);
+        let open_interest_change = self
+            .positioning
+            .as_ref()
+            .and_then(|p| p.open_interest_change(self.now, self.config.open_interest_window));
+        match &mut self.position {
+            Some((_, stop)) => stop.trail(candle, distance),
+            None if self.entry_allowed
+                && self.entry_scale > 0.
+                && distance > 0.
+                && self.config.entry(&self.judge, self.is_bull)
+                && self.config.confirms(open_interest_change) =>
+            {
+                let offered = self.capital * self.config.position * self.entry_scale;
+                let contract = Contract::open_as(
+                    self.is_bull,
+                    candle.close,
+                    offered,
+                    self.leverage,
+                    self.now,
+                    None,
+                    self.fees,
+                    FillType::Taker,
+                );
+                self.capital -= offered;
+                self.open_count += 1;
+                self.position = Some((
+                    contract,
+                    TrailingStop::new(self.is_bull, candle.close, distance),
+                ));
+            }
+            None => {}
+        }
+    }
+    fn close(&mut self, price: f64) -> f64 {
+        if let Some((contract, _)) = self.position.take() {
+            let r = contract.close_as(price, ExitReason::Close.fill());
+            self.settle(&contract, price, r, ExitReason::Close);
+        }
+        self.value()
+    }
+    fn value(&self) -> f64 {
+        match &self.position {
+            Some((contract, _)) => self.capital + contract.margin,
+            None => self.capital,
+        }
+    }
+    fn liquidations(&self) -> usize {
+        self.liquidations
+    }
+    fn trades(&self) -> &[TradeRecord] {
+        &self.journal
+    }
+    fn allow_entry(&mut self, allowed: bool) {
+        self.entry_allowed = allowed;
+    }
+    /// 预热时禁止开仓，只更新指标
+    fn warm_up(&mut self, candle: &CandleData) {
+        let allowed = std::mem::replace(&mut self.entry_allowed, false);
+        self.update(candle);
+        self.entry_allowed = allowed;
+    }
+    fn scale_entry(&mut self, scale: f64) {
+        self.entry_scale = scale;
+    }
+    fn idle_capital(&self) -> Option<f64> {
+        Some(self.capital)
+    }
+    fn transfer_capital(&mut self, amount: f64) {
+        self.capital += amount;
+    }
+}
+
+#[test]
+fn breakout_strategy_test() {
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    // 横盘后放量突破，上涨后回落
+    let mut closes = vec![(100., 10.); 25];
+    closes.extend([(105., 40.), (110., 10.), (115., 10.), (120., 10.)]);
+    closes.extend([(112., 10.), (105., 10.), (100., 10.)]);
+    let candles: Vec<CandleData> = closes
+        .iter()
+        .enumerate()
+        .map(|(i, &(close, volume))| CandleData {
+            open: closes[i.saturating_sub(1)].0,
+            close,
+            high: close + 1.,
+            low: close - 1.,
+            volume,
+            open_time: start + Duration::minutes(i as i64),
+            close_time: start + Duration::minutes(i as i64 + 1),
+        })
+        .collect();
+    let config = BreakoutConfig {
+        period: 10,
+        atr_period: 5,
+        atr_multiplier: 2.,
+        ..Default::default()
+    };
+    let mut strategy = BreakoutStrategy::new(true, config, 1000., 3.);
+    for c in candles.iter() {
+        strategy.update(c);
+    }
+    let value = strategy.close(candles.last().unwrap().close);
+    assert_eq!(strategy.open_count, 1);
+    let trades = strategy.trades();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].reason, ExitReason::Trailing);
+    assert_eq!(trades[0].entry_price, 105.);
+    assert!(trades[0].exit_price > 105.);
+    assert!((value - 1000. - trades[0].pnl + trades[0].entry_fee).abs() < 1e-9);
+    assert!(value > 1000.);
+
+    // 同样的行情做空不会入场
+    let mut short = BreakoutStrategy::new(false, config, 1000., 3.);
+    for c in candles.iter() {
+        short.update(c);
+    }
+    assert_eq!(short.open_count, 0);
+
+    // 要求持仓量增长时，持仓量下降不入场
+    let filtered = BreakoutConfig {
+        min_open_interest_change: Some(0.05),
+        open_interest_window: Duration::minutes(5),
+        ..config
+    };
+    let positioning = |change: f64| crate::backtest::positioning::PositioningSeries {
+        symbol: "BTCUSDT".into(),
+        data: candles
+            .iter()
+            .enumerate()
+            .map(|(i, c)| crate::backtest::positioning::PositioningData {
+                time: c.close_time,
+                open_interest: 1000. * (1. + change).powf(i as f64 / 5.),
+                open_interest_value: 0.,
+                long_short_ratio: None,
+            })
+            .collect(),
+    };
+    let mut falling = BreakoutStrategy::new(true, filtered, 1000., 3.);
+    falling.set_positioning(positioning(-0.1));
+    let mut rising = BreakoutStrategy::new(true, filtered, 1000., 3.);
+    rising.set_positioning(positioning(0.1));
+    for c in candles.iter() {
+        falling.update(c);
+        rising.update(c);
+    }
+    assert_eq!((falling.open_count, rising.open_count), (0, 1));
+
+    // 时段外不开仓（1970-01-01为周四）
+    let mut sessioned = crate::strategy::session::SessionFilter::new()
+        .with_weekdays(&[time::Weekday::Monday])
+        .wrap(BreakoutStrategy::new(true, config, 1000., 3.));
+    for c in candles.iter() {
+        sessioned.update(c);
+    }
+    assert_eq!(sessioned.inner.open_count, 0);
+    assert_eq!(sessioned.value(), 1000.);
+}
@@ -3,12 +3,13 @@ use std::sync::{Arc, Mutex};
 use time::{Duration, OffsetDateTime};
 use tracing::warn;
 
-use crate::backtest::{
-    candle_chart::CandleData,
-    contract::{Contract, HANDLING_FEE_RATE_MAKER},
+use crate::{
+    backtest::{candle_chart::CandleData, contract::Contract, price_path::IntrabarPath},
+    fee::{FeeSchedule, FillType},
+    strategy::{roll_judge::RollJudge, stop::StopPlacer},
 };
 
-use super::Strategy;
+use super::{draw_capital, CapitalExhausted, ExitReason, Strategy, TradeRecord};
 
 // 等比定时开仓策略
 #[derive(Debug, Clone)]
@@ -23,8 +24,9 @@ pub struct GeoStrategy {
     ratio: f64,
     /// 若总资金不足则补充到此值
     supply: f64,
-    /// 止损比例
-    stop_loss_ratio: f64,
+    /// 止损放置方式
+    stop: StopPlacer,
+    judge: RollJudge,
     /// 超过间隔后按该比例止盈
     take_profit_ratio: f64,
     /// 当前持仓
@@ -37,10 +39,27 @@ pub struct GeoStrategy {
     pub cost: f64,
     /// 开单次数
     pub open_count: i64,
+    /// 被强平次数
+    pub liquidations: usize,
+    /// 总资金不足，此后不再开仓
+    exhausted: Option<CapitalExhausted>,
+    /// 交易记录
+    journal: Vec<TradeRecord>,
     /// 上次开单时间
     last_time: OffsetDateTime,
+    /// 最近一根k线的时间
+    now: OffsetDateTime,
     /// 总资金
     total_capital: Arc<Mutex<f64>>,
+    /// k线内价格路径
+    path: IntrabarPath,
+    fees: FeeSchedule,
+    /// 止损限价单的偏移比例，None为市价止损
+    stop_limit: Option<f64>,
+    /// 为false时不开新仓
+    entry_allowed: bool,
+    /// 开仓金额比例
+    entry_scale: f64,
 }
 
 impl GeoStrategy {
@@ -55,10 +74,11 @@ impl GeoStrategy {
         take_profit_ratio: f64,
         total_capital: Arc<Mutex<f64>>,
     ) -> Self {
-        if take_profit_ratio < HANDLING_FEE_RATE_MAKER * 2. {
+        let fees = FeeSchedule::default();
+        if take_profit_ratio < fees.rate(FillType::Maker) * 2. {
             warn!(
                 "take profit ratio is too low, can't take profit unless it is greater than {}",
-                1. + HANDLING_FEE_RATE_MAKER * 2.
+                1. + fees.rate(FillType::Maker) * 2.
             );
         }
         Self {
@@ -67,83 +87,158 @@ impl GeoStrategy {
             interval,
             ratio,
             supply,
-            stop_loss_ratio,
+            stop: StopPlacer::Fixed {
+                ratio: stop_loss_ratio,
+            },
+            judge: RollJudge::new(1),
             take_profit_ratio,
             position: None,
             capital: 0.,
             stake: 0.,
             cost: 0.,
             open_count: 0,
+            liquidations: 0,
+            exhausted: None,
+            journal: vec![],
             last_time: OffsetDateTime::from_unix_timestamp(0).unwrap(),
+            now: OffsetDateTime::from_unix_timestamp(0).unwrap(),
             total_capital,
+            path: IntrabarPath::default(),
+            fees,
+            stop_limit: None,
+            entry_allowed: true,
+            entry_scale: 1.,
         }
     }
+    pub fn set_path(&mut self, path: IntrabarPath) {
+        self.path = path;
+    }
+    pub fn set_fees(&mut self, fees: FeeSchedule) {
+        self.fees = fees;
+    }
+    /// 替换构造时的固定止损比例
+    pub fn set_stop_placer(&mut self, stop: StopPlacer) {
+        self.stop = stop;
+        self.judge = stop.judge();
+    }
+    pub fn set_stop_limit(&mut self, offset: Option<f64>) {
+        self.stop_limit = offset;
+    }
 }
 
 impl Strategy for GeoStrategy {
     fn update(&mut self, candle: &CandleData) {
+        self.now = candle.close_time;
+        self.judge.update(candle);
         if let Some(contract) = self.position.take() {
-            if let Some(r) = contract.liquidate(if self.is_bull {
-                candle.low
-            } else {
-                candle.high
-            }) {
-                // 止损或强制平仓
-                self.capital += r;
-            } else if contract.open_time + self.interval <= candle.close_time
-                && ((self.is_bull
-                    && candle.close > contract.entry_price * (1. + self.take_profit_ratio))
-                    || (!self.is_bull
-                        && candle.close < contract.entry_price * (1. - self.take_profit_ratio)))
-            {
-                // 超过间隔后按比例止盈，否则继续持有该仓位
-                self.capital += contract.close(candle.close);
+            let take_profit_price = if self.is_bull {
+                contract.entry_price * (1. + self.take_profit_ratio)
             } else {
-                self.position = Some(contract);
+                contract.entry_price * (1. - self.take_profit_ratio)
+            };
+            let can_take_profit = contract.open_time + self.interval <= candle.close_time;
+            // 按k线内价格路径依次判断止损/强平与止盈，先触发者生效
+            let mut exit = None;
+            for price in self.path.path(candle, self.is_bull) {
+                if let Some(r) = contract.liquidate(price) {
+                    // 止损或强制平仓
+                    let reason = match contract.stop_out(price) {
+                        Some(_) => ExitReason::StopLoss,
+                        None => {
+                            self.liquidations += 1;
+                            ExitReason::Liquidation
+                        }
+                    };
+                    exit = Some((r, price, reason));
+                    break;
+                }
+                if can_take_profit
+                    && ((self.is_bull && price > take_profit_price)
+                        || (!self.is_bull && price < take_profit_price))
+                {
+                    // 超过间隔后按比例止盈，否则继续持有该仓位
+                    exit = Some((
+                        contract.close(take_profit_price),
+                        take_profit_price,
+                        ExitReason::TakeProfit,
+                    ));
+                    break;
+                }
+            }
+            match exit {
+                Some((r, price, reason)) => {
+                    self.capital += r;
+                    self.journal.push(TradeRecord::new(
+                        &contract,
+                        candle.close_time,
+                        price,
+                        r,
+                        reason,
+                    ));
+                }
+                None => self.position = Some(contract),
             }
         }
-        if self.position.is_some() || self.last_time + self.interval > candle.close_time {
-            // 只有空仓且超过间隔后才开仓
+        if self.position.is_some()
+            || !self.entry_allowed
+            || self.entry_scale <= 0.
+            || self.exhausted.is_some()
+            || self.last_time + self.interval > candle.close_time
+        {
+            // 只有空仓、时段允许且超过间隔后才开仓
             return;
         }
         if self.capital < self.supply {
             let cost = self.supply - self.capital;
             if self.stake < cost {
                 let supplement = cost - self.stake;
-                let mut total_capital = self.total_capital.lock().unwrap();
-                if *total_capital < supplement {
-                    panic!(
-                        "total capital = {} is not enough, need {}",
-                        *total_capital, cost
-                    );
+                // 总资金不足时保持空仓，由回测引擎记录并暂停
+                if let Err(e) = draw_capital(&self.total_capital, supplement, candle.close_time) {
+                    warn!("{}", e);
+                    self.exhausted = Some(e);
+                    return;
                 }
-                *total_capital -= supplement;
                 self.stake = cost;
                 self.cost += supplement;
             }
             self.stake -= cost;
             self.capital = self.supply;
         }
-        let stop_loss = if self.is_bull {
-            Some((1. - self.stop_loss_ratio) * candle.close)
-        } else {
-            Some((1. + self.stop_loss_ratio) * candle.close)
-        };
-        self.position = Some(Contract::open(
+        let stop_loss =
+            Some(
+                self.stop
+                    .stop_price(&self.judge, self.is_bull, candle.close, self.leverage),
+            );
+        let contract = Contract::open_as(
             self.is_bull,
             candle.close,
-            self.capital * self.ratio,
+            self.capital * self.ratio * self.entry_scale,
             self.leverage,
             candle.close_time,
             stop_loss,
-        ));
-        self.capital -= self.capital * self.ratio;
+            self.fees,
+            // 按收盘价市价开仓
+            FillType::Taker,
+        );
+        self.position = Some(match self.stop_limit {
+            Some(offset) => contract.with_stop_limit(offset),
+            None => contract,
+        });
+        self.capital -= self.capital * self.ratio * self.entry_scale;
         self.last_time = candle.close_time;
         self.open_count += 1;
     }
     fn close(&mut self, price: f64) -> f64 {
         if let Some(offer) = self.position.take() {
-            self.capital += offer.close(price);
+            let r = offer.close(price);
+            self.capital += r;
+            self.journal.push(TradeRecord::new(
+                &offer,
+                self.now,
+                price,
+                r,
+                ExitReason::Close,
+            ));
         }
         self.value()
     }
@@ -154,4 +249,36 @@ impl Strategy for GeoStrategy {
             self.capital + self.stake
         }
     }
+    fn liquidations(&self) -> usize {
+        self.liquidations
+    }
+    fn trades(&self) -> &[TradeRecord] {
+        &self.journal
+    }
+    fn capital_exhausted(&self) -> Option<CapitalExhausted> {
+        self.exhausted
+    }
+    fn allow_entry(&mut self, allowed: bool) {
+        self.entry_allowed = allowed;
+    }
+    /// 预热时禁止开仓，只更新指标
+    fn warm_up(&mut self, candle: &CandleData) {
+        let allowed = std::mem::replace(&mut self.entry_allowed, false);
+        self.update(candle);
+        self.entry_allowed = allowed;
+    }
+    fn scale_entry(&mut self, scale: f64) {
+        self.entry_scale = scale;
+    }
+    fn idle_capital(&self) -> Option<f64> {
+        Some(self.capital + self.stake)
+    }
+    /// 注入计入后备资金，取出时先用后备资金
+    fn transfer_capital(&mut self, amount: f64) {
+        self.stake += amount;
+        if self.stake < 0. {
+            self.capital += self.stake;
+            self.stake = 0.;
+        }
+    }
 }
@@ -0,0 +1,260 @@
+use time::OffsetDateTime;
+
+use crate::{
+    backtest::{candle_chart::CandleData, contract::Contract, engine::Reserve},
+    capital::CapitalPolicy,
+    fee::{FeeSchedule, FillType},
+    strategy::pairs::{Cointegration, PairsModel, PairsSignal},
+};
+
+use super::{ExitReason, TradeRecord};
+
+/// 按收盘时间对齐两个币种的k线，只保留两边都有的时间点
+pub fn align<'a>(
+    a: &'a [CandleData],
+    b: &'a [CandleData],
+) -> Vec<(&'a CandleData, &'a CandleData)> {
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = vec![];
+    while i < a.len() && j < b.len() {
+        match a[i].close_time.cmp(&b[j].close_time) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                pairs.push((&a[i], &b[j]));
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    pairs
+}
+
+/// 配对交易回测：按价差z-score市价开反向的两条腿，价差回归或一条腿强平时全部平仓
+#[derive(Debug, Clone)]
+pub struct PairsStrategy {
+    model: PairsModel,
+    leverage: f64,
+    capital: f64,
+    /// （a腿，b腿）
+    legs: Option<(Contract, Contract)>,
+    fees: FeeSchedule,
+    /// 按资金策略从闲置资金中取出的储备，默认复利
+    reserve: Reserve,
+    now: OffsetDateTime,
+    /// 开仓次数
+    pub open_count: i64,
+    liquidations: usize,
+    journal: Vec<TradeRecord>,
+}
+
+impl PairsStrategy {
+    pub fn new(model: PairsModel, capital: f64, leverage: f64) -> Self {
+        Self {
+            model,
+            leverage,
+            capital,
+            legs: None,
+            fees: FeeSchedule::default(),
+            reserve: Reserve::new(CapitalPolicy::Compound, capital),
+            now: OffsetDateTime::from_unix_timestamp(0).unwrap(),
+            open_count: 0,
+            liquidations: 0,
+            journal: vec![],
+        }
+    }
+    pub fn set_fees(&mut self, fees: FeeSchedule) {
+        self.fees = fees;
+    }
+    /// 与单币种回测相同的资金策略，在run中每根k线后调拨
+    pub fn set_capital_policy(&mut self, policy: CapitalPolicy) {
+        self.reserve = Reserve::new(policy, self.value());
+    }
+    /// 未用作保证金的闲置资金
+    pub fn idle_capital(&self) -> f64 {
+        self.capital
+    }
+    /// 注入（正数）或取出（负数）闲置资金
+    pub fn transfer_capital(&mut self, amount: f64) {
+        self.capital += amount;
+    }
+    /// 资金策略取出的储备，不计入value
+    pub fn reserve(&self) -> f64 {
+        self.reserve.amount()
+    }
+    fn rebalance(&mut self) {
+        let amount = self.reserve.transfer(self.value(), self.idle_capital());
+        if amount != 0. {
+            self.transfer_capital(amount);
+        }
+    }
+    /// 用历史k线估计对冲比例和z-score窗口
+    pub fn calibrate(&mut self, a: &[CandleData], b: &[CandleData]) -> Option<Cointegration> {
+        let (a, b): (Vec<f64>, Vec<f64>) = align(a, b)
+            .into_iter()
+            .map(|(a, b)| (a.close, b.close))
+            .unzip();
+        self.model.calibrate(&a, &b)
+    }
+    /// a、b为同一时间收盘的k线
+    pub fn update(&mut self, a: &CandleData, b: &CandleData) {
+        self.now = a.close_time;
+        if let Some((leg_a, leg_b)) = self.legs.take() {
+            // 按k线内对该腿最不利的价格判断强平，强平时另一条腿按收盘价平仓
+            let worst = |leg: &Contract, candle: &CandleData| {
+                if leg.is_bull {
+                    candle.low
+                } else {
+                    candle.high
+                }
+            };
+            let liquidated_a = leg_a.liquidate(worst(&leg_a, a));
+            let liquidated_b = leg_b.liquidate(worst(&leg_b, b));
+            if liquidated_a.is_some() || liquidated_b.is_some() {
+                for (leg, liquidated, candle) in
+                    [(leg_a, liquidated_a, a), (leg_b, liquidated_b, b)]
+                {
+                    match liquidated {
+                        Some(r) => {
+                            self.liquidations += 1;
+                            self.settle(&leg, leg.liq_price, r, ExitReason::Liquidation);
+                        }
+                        None => {
+                            let r = leg.close_as(candle.close, FillType::Taker);
+                            self.settle(&leg, candle.close, r, ExitReason::Signal);
+                        }
+                    }
+                }
+                self.model.reset();
+                self.model.update(a.close, b.close);
+                return;
+            }
+            self.legs = Some((leg_a, leg_b));
+        }
+        match self.model.update(a.close, b.close) {
+            Some(PairsSignal::Exit) => self.exit(a.close, b.close, ExitReason::Signal),
+            Some(signal) if self.legs.is_none() => {
+                let total = self.capital * self.model.config().position;
+                let ratio = self.model.leg_ratio();
+                let value_a = total * ratio / (1. + ratio);
+                let is_bull = signal == PairsSignal::Long;
+                let open = |is_bull, price, value| {
+                    Contract::open_as(
+                        is_bull,
+                        price,
+                        value,
+                        self.leverage,
+                        self.now,
+                        None,
+                        self.fees,
+                        FillType::Taker,
+                    )
+                };
+                self.legs = Some((
+                    open(is_bull, a.close, value_a),
+                    open(!is_bull, b.close, total - value_a),
+                ));
+                self.capital -= total;
+                self.open_count += 1;
+            }
+            _ => {}
+        }
+    }
+    fn exit(&mut self, price_a: f64, price_b: f64, reason: ExitReason) {
+        if let Some((leg_a, leg_b)) = self.legs.take() {
+            for (leg, price) in [(leg_a, price_a), (leg_b, price_b)] {
+                let r = leg.close_as(price, reason.fill());
+                self.settle(&leg, price, r, reason);
+            }
+        }
+    }
+    fn settle(&mut self, leg: &Contract, price: f64, returned: f64, reason: ExitReason) {
+        self.capital += returned;
+        self.journal
+            .push(TradeRecord::new(leg, self.now, price, returned, reason));
+    }
+    /// 按时间对齐后逐根回测，结束时按最后的收盘价平仓，返回最终资金（含储备）
+    pub fn run(&mut self, a: &[CandleData], b: &[CandleData]) -> f64 {
+        let pairs = align(a, b);
+        for (a, b) in pairs.iter() {
+            self.update(a, b);
+            self.rebalance();
+        }
+        if let Some((a, b)) = pairs.last() {
+            self.exit(a.close, b.close, ExitReason::Close);
+        }
+        self.value() + self.reserve()
+    }
+    pub fn value(&self) -> f64 {
+        match &self.legs {
+            Some((a, b)) => self.capital + a.margin + b.margin,
+            None => self.capital,
+        }
+    }
+    pub fn liquidations(&self) -> usize {
+        self.liquidations
+    }
+    /// 每次开仓产生两条记录，分别对应两条腿
+    pub fn trades(&self) -> &[TradeRecord] {
+        &self.journal
+    }
+}
+
+#[test]
+fn pairs_strategy_test() {
+    use crate::strategy::pairs::PairsConfig;
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let chart = |closes: &[f64]| -> Vec<CandleData> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| CandleData {
+                open: close,
+                high: close,
+                low: close,
+                close,
+                close_time: start + Duration::minutes(i as i64),
+                ..Default::default()
+            })
+            .collect()
+    };
+    // a围绕b小幅波动，中间出现一次大的偏离后回归
+    let mut a: Vec<f64> = (0..40)
+        .map(|i| if i % 2 == 0 { 100.1 } else { 99.9 })
+        .collect();
+    a.extend([103., 102., 100., 100.1, 99.9]);
+    let b = chart(&vec![100.; a.len()]);
+    let a = chart(&a);
+    // b缺少一根k线时按时间对齐
+    let mut b_missing = b.clone();
+    b_missing.remove(3);
+    assert_eq!(align(&a, &b_missing).len(), a.len() - 1);
+
+    let model = PairsModel::new(PairsConfig {
+        window: 20,
+        // 窗口为20时单个偏离点的z-score最大约为4.36
+        stop_z: 10.,
+        ..Default::default()
+    });
+    let mut strategy = PairsStrategy::new(model.clone(), 1000., 5.);
+    let value = strategy.run(&a, &b);
+    assert_eq!(strategy.open_count, 1);
+    let trades = strategy.trades();
+    assert_eq!(trades.len(), 2);
+    // 卖a买b，a回落后平仓获利
+    assert!(!trades[0].is_bull && trades[1].is_bull);
+    assert_eq!(trades[0].reason, ExitReason::Signal);
+    assert!(trades[0].pnl > 0.);
+    // pnl不含开仓手续费，开仓手续费在开仓时从资金中扣除
+    let pnl: f64 = trades.iter().map(|t| t.pnl - t.entry_fee).sum();
+    assert!((value - 1000. - pnl).abs() < 1e-9);
+    assert!(value > 1000.);
+    // 固定本金时盈利留作储备，总资金不变
+    let mut fixed = PairsStrategy::new(model, 1000., 5.);
+    fixed.set_capital_policy(CapitalPolicy::Fixed);
+    assert!((fixed.run(&a, &b) - value).abs() < 1e-9);
+    assert!(fixed.reserve() > 0.);
+    assert!((fixed.value() - 1000.).abs() < 1e-9);
+}
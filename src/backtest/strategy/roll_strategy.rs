@@ -1,9 +1,17 @@
-use std::collections::VecDeque;
+use crate::{
+    backtest::{
+        candle_chart::CandleData, contract::Contract, funding::FundingSeries,
+        price_path::IntrabarPath,
+    },
+    error::DataError,
+    fee::{FeeSchedule, FillType},
+    strategy::{roll_judge::RollJudge, state::Stateful, stop::StopPlacer},
+};
 
-use crate::backtest::{candle_chart::CandleData, contract::Contract};
-
-use super::Strategy;
+use super::{ExitReason, Strategy, TradeRecord};
 
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use tracing::info;
 
 #[derive(Debug, Clone)]
@@ -16,18 +24,68 @@ pub struct RollOnceStrategy {
     pub max_value: f64,
     pub best_price: f64,
     pub status: RollOnceStatus,
+    /// 被强平次数
+    pub liquidations: usize,
+    /// 交易记录
+    journal: Vec<TradeRecord>,
+    /// 最近一根k线的时间
+    now: OffsetDateTime,
+    path: IntrabarPath,
+    fees: FeeSchedule,
+    /// 每级开仓时的止损放置方式
+    stop: StopPlacer,
+    judge: RollJudge,
+    /// 止损后再次入场的规则
+    reentry: ReentryPolicy,
+    /// 判断再次入场的突破
+    reentry_judge: RollJudge,
+    /// 已再次入场的次数
+    pub retries: usize,
+    /// 止损后已等待的k线数
+    waited: usize,
+    /// 每轮滚仓的结果，用于报告
+    pub history: Vec<RollAttempt>,
+    /// 持仓期间结算的资金费率
+    funding: Option<FundingSeries>,
+    /// 累计资金费用，收入为正
+    pub funding_fee: f64,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RollOnceStatus {
     Processing,
     Successed,
     Failed,
     Aborted,
+    /// 止损后等待再次入场
+    Waiting,
+}
+
+/// 止损后再次入场的规则，默认不再入场
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReentryPolicy {
+    /// 止损后至少等待的k线数
+    pub wait: usize,
+    /// 需收盘创出之前breakout根k线的新低（做空为新高），0为不要求
+    pub breakout: usize,
+    /// 最多再次入场的次数
+    pub max_retries: usize,
+}
+
+/// 一轮滚仓的结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RollAttempt {
+    /// 结束时间
+    pub time: OffsetDateTime,
+    pub status: RollOnceStatus,
+    /// 结束时的阶梯级数
+    pub level: usize,
+    /// 结束时的资金
+    pub value: f64,
 }
 
 impl RollOnceStrategy {
-    fn new(is_bull: bool, capital: f64, config: RollConfig) -> Self {
+    pub fn new(is_bull: bool, capital: f64, config: RollConfig) -> Self {
         Self {
             is_bull,
             capital,
@@ -37,37 +95,155 @@ impl RollOnceStrategy {
             max_value: 0.,
             best_price: 0.,
             status: RollOnceStatus::Processing,
+            liquidations: 0,
+            journal: vec![],
+            now: OffsetDateTime::from_unix_timestamp(0).unwrap(),
+            path: IntrabarPath::default(),
+            fees: FeeSchedule::default(),
+            stop: StopPlacer::default(),
+            judge: StopPlacer::default().judge(),
+            reentry: ReentryPolicy::default(),
+            reentry_judge: RollJudge::new(1),
+            retries: 0,
+            waited: 0,
+            history: vec![],
+            funding: None,
+            funding_fee: 0.,
+        }
+    }
+    pub fn set_path(&mut self, path: IntrabarPath) {
+        self.path = path;
+    }
+    pub fn set_fees(&mut self, fees: FeeSchedule) {
+        self.fees = fees;
+    }
+    pub fn set_stop_placer(&mut self, stop: StopPlacer) {
+        self.stop = stop;
+        self.judge = stop.judge();
+    }
+    pub fn set_reentry(&mut self, reentry: ReentryPolicy) {
+        self.reentry = reentry;
+        self.reentry_judge = RollJudge::new(reentry.breakout + 1);
+    }
+    pub fn set_funding(&mut self, funding: FundingSeries) {
+        self.funding = Some(funding);
+    }
+    /// 记录本轮结果；止损且还有再次入场次数时从第一级重新开始等待
+    fn finish(&mut self, status: RollOnceStatus) {
+        self.history.push(RollAttempt {
+            time: self.now,
+            status: status.clone(),
+            level: self.level,
+            value: self.value(),
+        });
+        if status == RollOnceStatus::Failed && self.retries < self.reentry.max_retries {
+            self.status = RollOnceStatus::Waiting;
+            self.waited = 0;
+            self.level = 0;
+            self.max_value = 0.;
+            self.best_price = 0.;
+        } else {
+            self.status = status;
         }
     }
+    /// 等待足够的k线且满足突破条件
+    fn reentry_ready(&self) -> bool {
+        self.waited >= self.reentry.wait
+            && (self.reentry.breakout == 0
+                || self
+                    .reentry_judge
+                    .breaks_out(self.reentry.breakout, !self.is_bull))
+    }
 }
 
 impl Strategy for RollOnceStrategy {
+    /// 不支持禁止开仓，预热时只更新止损所用的指标
+    fn warm_up(&mut self, candle: &CandleData) {
+        self.now = candle.close_time;
+        self.judge.update(candle);
+    }
     fn update(&mut self, candle: &CandleData) {
-        if self.status != RollOnceStatus::Processing {
+        if !matches!(
+            self.status,
+            RollOnceStatus::Processing | RollOnceStatus::Waiting
+        ) {
             return;
         }
+        let prev = self.now;
+        self.now = candle.close_time;
+        self.judge.update(candle);
+        self.reentry_judge.update(candle);
+        // 费率为正时多头支付、空头收取，方向由合约处理
+        if let (Some(contract), Some(funding)) = (&self.contract, &self.funding) {
+            let fee = funding.funding_fee(contract, prev, self.now, candle.close);
+            self.capital += fee;
+            self.funding_fee += fee;
+        }
+        if self.status == RollOnceStatus::Waiting {
+            self.waited += 1;
+            if !self.reentry_ready() {
+                return;
+            }
+            self.retries += 1;
+            self.status = RollOnceStatus::Processing;
+            info!(
+                "roll once reenter: time: {}, price: {}, retry: {}",
+                candle.close_time, candle.close, self.retries
+            );
+        }
         if let Some(contract) = self.contract.take() {
             let (_leverage, take_profit, max_draw) = self.config.0[self.level - 1];
-            if let Some(r) = contract.liquidate(if self.is_bull {
-                candle.low
+            let take_profit_price = if self.is_bull {
+                contract.entry_price * (1. + take_profit)
             } else {
-                candle.high
-            }) {
+                contract.entry_price * (1. - take_profit)
+            };
+            // 按k线内价格路径依次判断强平/止损与止盈，先触发者生效
+            let mut take_profit_hit = false;
+            for price in self.path.path(candle, self.is_bull) {
+                if let Some(r) = contract.liquidate(price) {
+                    let reason = match contract.stop_out(price) {
+                        Some(_) => ExitReason::StopLoss,
+                        None => {
+                            self.liquidations += 1;
+                            ExitReason::Liquidation
+                        }
+                    };
+                    self.capital += r;
+                    self.journal.push(TradeRecord::new(
+                        &contract,
+                        candle.close_time,
+                        price,
+                        r,
+                        reason,
+                    ));
+                    info!(
+                        "roll once failed: time: {}, price: {}, level: {}, value: {}",
+                        candle.close_time,
+                        price,
+                        self.level,
+                        self.value()
+                    );
+                    self.finish(RollOnceStatus::Failed);
+                    return;
+                }
+                if (self.is_bull && price > take_profit_price)
+                    || (!self.is_bull && price < take_profit_price)
+                {
+                    take_profit_hit = true;
+                    break;
+                }
+            }
+            if take_profit_hit {
+                let r = contract.close(take_profit_price);
                 self.capital += r;
-                self.status = RollOnceStatus::Failed;
-                info!(
-                    "roll once failed: time: {}, price: {}, level: {}, value: {}",
+                self.journal.push(TradeRecord::new(
+                    &contract,
                     candle.close_time,
-                    candle.close,
-                    self.level,
-                    self.value()
-                );
-                return;
-            }
-            if (self.is_bull && candle.close > contract.entry_price * (1. + take_profit))
-                || (!self.is_bull && candle.close < contract.entry_price * (1. - take_profit))
-            {
-                self.capital += contract.close(candle.close);
+                    take_profit_price,
+                    r,
+                    ExitReason::TakeProfit,
+                ));
             } else {
                 let value_high = contract.close(candle.high);
                 let value_low = contract.close(candle.low);
@@ -81,8 +257,16 @@ impl Strategy for RollOnceStrategy {
                 }
                 if let Some(max_draw) = max_draw {
                     if contract.close(candle.close) < self.max_value * (1. - max_draw) {
-                        self.capital += contract.close(candle.close);
-                        self.status = RollOnceStatus::Successed;
+                        // 回撤离场为市价单
+                        let r = contract.close_as(candle.close, FillType::Taker);
+                        self.capital += r;
+                        self.journal.push(TradeRecord::new(
+                            &contract,
+                            candle.close_time,
+                            candle.close,
+                            r,
+                            ExitReason::Trailing,
+                        ));
                         info!(
                             "roll once successed: time: {}, price: {}, level:, {}, value: {}",
                             candle.close_time,
@@ -90,6 +274,7 @@ impl Strategy for RollOnceStrategy {
                             self.level,
                             self.value()
                         );
+                        self.finish(RollOnceStatus::Successed);
                         return;
                     }
                 }
@@ -103,18 +288,19 @@ impl Strategy for RollOnceStrategy {
             return;
         }
         let leverage = self.config.0[self.level].0;
-        let stop_loss = if self.is_bull {
-            candle.close * (1. - 0.99 / leverage) + candle.close * 0.004
-        } else {
-            candle.close * (1. + 0.99 / leverage) - candle.close * 0.004
-        };
-        let contract = Contract::open(
+        let stop_loss = self
+            .stop
+            .stop_price(&self.judge, self.is_bull, candle.close, leverage);
+        let contract = Contract::open_as(
             self.is_bull,
             candle.close,
             self.capital,
             leverage,
             candle.close_time,
             Some(stop_loss),
+            self.fees,
+            // 按收盘价市价开仓
+            FillType::Taker,
         );
         self.capital = 0.;
         self.contract = Some(contract);
@@ -130,7 +316,21 @@ impl Strategy for RollOnceStrategy {
     }
     fn close(&mut self, price: f64) -> f64 {
         if let Some(contract) = &self.contract.take() {
-            self.capital += contract.close(price);
+            let r = contract.close(price);
+            self.capital += r;
+            self.journal.push(TradeRecord::new(
+                contract,
+                self.now,
+                price,
+                r,
+                ExitReason::Close,
+            ));
+        }
+        if matches!(
+            self.status,
+            RollOnceStatus::Processing | RollOnceStatus::Waiting
+        ) {
+            self.finish(RollOnceStatus::Aborted);
         }
         self.status = RollOnceStatus::Aborted;
         self.capital
@@ -143,19 +343,135 @@ impl Strategy for RollOnceStrategy {
                 0.
             }
     }
+    fn liquidations(&self) -> usize {
+        self.liquidations
+    }
+    fn trades(&self) -> &[TradeRecord] {
+        &self.journal
+    }
+    fn idle_capital(&self) -> Option<f64> {
+        Some(self.capital)
+    }
+    fn transfer_capital(&mut self, amount: f64) {
+        self.capital += amount;
+    }
+}
+
+/// 阶梯进度，交易记录不保存
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollOnceState {
+    pub is_bull: bool,
+    pub capital: f64,
+    pub contract: Option<Contract>,
+    pub level: usize,
+    pub max_value: f64,
+    pub best_price: f64,
+    pub status: RollOnceStatus,
+    #[serde(default)]
+    pub liquidations: usize,
+    pub now: OffsetDateTime,
+    #[serde(default)]
+    pub retries: usize,
+    #[serde(default)]
+    pub waited: usize,
+    #[serde(default)]
+    pub history: Vec<RollAttempt>,
+    #[serde(default)]
+    pub funding_fee: f64,
+}
+
+impl Stateful for RollOnceStrategy {
+    const STATE_VERSION: u32 = 1;
+    type State = RollOnceState;
+    fn save_state(&self) -> RollOnceState {
+        RollOnceState {
+            is_bull: self.is_bull,
+            capital: self.capital,
+            contract: self.contract.clone(),
+            level: self.level,
+            max_value: self.max_value,
+            best_price: self.best_price,
+            status: self.status.clone(),
+            liquidations: self.liquidations,
+            now: self.now,
+            retries: self.retries,
+            waited: self.waited,
+            history: self.history.clone(),
+            funding_fee: self.funding_fee,
+        }
+    }
+    /// 方向不同或阶梯级数超出当前配置时拒绝恢复
+    fn load_state(&mut self, state: RollOnceState) -> Result<(), DataError> {
+        if state.is_bull != self.is_bull {
+            return Err(DataError::parse("roll once state", "direction mismatch"));
+        }
+        if state.level > self.config.0.len() {
+            return Err(DataError::parse(
+                "roll once state",
+                format!("level {} exceeds config", state.level),
+            ));
+        }
+        self.capital = state.capital;
+        self.contract = state.contract;
+        self.level = state.level;
+        self.max_value = state.max_value;
+        self.best_price = state.best_price;
+        self.status = state.status;
+        self.liquidations = state.liquidations;
+        self.now = state.now;
+        self.retries = state.retries;
+        self.waited = state.waited;
+        self.history = state.history;
+        self.funding_fee = state.funding_fee;
+        Ok(())
+    }
 }
 
 type Leverage = f64;
 type TakeProfit = f64;
 type MaxDraw = Option<f64>;
 #[derive(Debug, Clone)]
-struct RollConfig(Vec<(Leverage, TakeProfit, MaxDraw)>);
+pub struct RollConfig(Vec<(Leverage, TakeProfit, MaxDraw)>);
 
 impl RollConfig {
     pub fn new(config: Vec<(Leverage, TakeProfit, MaxDraw)>) -> Self {
         Self(config)
     }
-    fn linear(k: f64, b: f64, max: f64, _step: f64) -> Self {
+    /// 做空的默认阶梯：价格最多跌到0且逼空行情更急，杠杆和止盈都小于做多，
+    /// 最后一级1倍杠杆无法止盈100%，只靠移动止盈离场
+    pub fn short() -> Self {
+        let config = vec![
+            (20., 0.04, None),     // 4%     104%
+            (15., 0.05, None),     // 5%     109.2%
+            (10., 0.07, None),     // 7%     116.8%
+            (6., 0.1, None),       // 10%    128.5%
+            (4., 0.15, None),      // 15%    147.8%
+            (2., 0.25, Some(0.4)), // 25%    184.8%
+            (1., 1., Some(0.15)),
+        ];
+        Self(config)
+    }
+    /// 按方向选择默认阶梯
+    pub fn for_side(is_bull: bool) -> Self {
+        if is_bull {
+            Self::default()
+        } else {
+            Self::short()
+        }
+    }
+    /// 由优化器基因生成，每两个基因为一级（杠杆，止盈），杠杆取整且不小于1，最后一级不设止盈而以移动止盈代替
+    pub fn from_genes(genes: &[f64]) -> Self {
+        let mut config: Vec<_> = genes
+            .chunks_exact(2)
+            .map(|g| (g[0].max(1.).floor(), g[1], None))
+            .collect();
+        if let Some(last) = config.last_mut() {
+            *last = (last.0, 1., Some(last.1));
+        }
+        Self(config)
+    }
+    /// 杠杆从1开始按x * k + b递增到max（不含），每级止盈100%
+    fn linear(k: f64, b: f64, max: f64) -> Self {
         let mut config = Vec::new();
         let mut x = 1.;
         while x < max {
@@ -164,6 +480,77 @@ impl RollConfig {
         }
         Self(config)
     }
+    /// 根据历史统计生成杠杆阶梯：
+    /// 首级杠杆保证可承受两倍的历史最大逆向回撤，之后每级杠杆约为上一级的0.7倍，止盈为1/杠杆（每级资金约翻倍），
+    /// 最后一级为1倍杠杆并以历史波动率推算的回撤作为移动止盈
+    fn adaptive(judge: &RollJudge, is_bull: bool) -> Self {
+        let size = judge.len();
+        let max_draw = judge.max_draw(size, is_bull);
+        let volatility = judge.volatility(size);
+        let mut leverage = (0.5 / max_draw).clamp(1., 25.).floor();
+        let mut config = Vec::new();
+        while leverage > 1. {
+            config.push((leverage, 1. / leverage, None));
+            leverage = (leverage * 0.7).floor();
+        }
+        let trailing = (volatility * (size as f64).sqrt() * 3.).clamp(0.1, 0.6);
+        config.push((1., 1., Some(trailing)));
+        Self(config)
+    }
+    /// 用全部candles（时间升序）的统计生成阶梯，见adaptive
+    fn from_candles(candles: &[CandleData], is_bull: bool) -> Self {
+        let mut judge = RollJudge::new(candles.len().max(1));
+        candles.iter().for_each(|c| judge.update(c));
+        Self::adaptive(&judge, is_bull)
+    }
+}
+
+/// 滚仓阶梯的生成方式，用于配置文件
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RollLadder {
+    /// 按方向的默认阶梯
+    #[default]
+    Default,
+    /// 见RollConfig::linear
+    Linear { k: f64, b: f64, max: f64 },
+    /// 由开仓前lookback根k线的回撤和波动率生成，见RollConfig::adaptive
+    Adaptive { lookback: usize },
+}
+
+impl RollLadder {
+    /// 生成阶梯所需的k线数
+    pub fn lookback(&self) -> usize {
+        match *self {
+            Self::Adaptive { lookback } => lookback,
+            _ => 0,
+        }
+    }
+    /// 检查参数，错误信息用于ConfigError::Invalid
+    pub fn validate(&self) -> Result<(), String> {
+        let valid = match *self {
+            // 杠杆需严格递增，且至少有一级
+            Self::Linear { k, b, max } => k >= 1. && b >= 0. && k + b > 1. && max > 1.,
+            Self::Adaptive { lookback } => lookback > 1,
+            Self::Default => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("roll ladder {:?}", self))
+        }
+    }
+    /// candles为开仓前的k线（时间升序），只取最后lookback根；k线不足两根时使用默认阶梯
+    pub fn build(&self, is_bull: bool, candles: &[CandleData]) -> RollConfig {
+        match *self {
+            Self::Linear { k, b, max } => RollConfig::linear(k, b, max),
+            Self::Adaptive { lookback } if candles.len() >= 2 => {
+                let start = candles.len().saturating_sub(lookback);
+                RollConfig::from_candles(&candles[start..], is_bull)
+            }
+            _ => RollConfig::for_side(is_bull),
+        }
+    }
 }
 
 impl Default for RollConfig {
@@ -183,46 +570,6 @@ impl Default for RollConfig {
     }
 }
 
-struct RollJudge {
-    cache: VecDeque<CandleData>,
-    max_length: usize,
-}
-
-impl RollJudge {
-    fn new(max_length: usize) -> Self {
-        let cache = VecDeque::with_capacity(max_length);
-        Self { cache, max_length }
-    }
-    fn update(&mut self, candle: &CandleData) {
-        if self.cache.len() >= self.max_length {
-            self.cache.pop_back();
-        }
-        self.cache.push_front(candle.clone());
-    }
-    fn max(&self, size: usize) -> CandleData {
-        self.cache
-            .iter()
-            .take(size)
-            .max_by(|x, y| x.high.partial_cmp(&y.high).unwrap())
-            .unwrap()
-            .clone()
-    }
-    fn min(&self, size: usize) -> CandleData {
-        self.cache
-            .iter()
-            .take(size)
-            .min_by(|x, y| x.low.partial_cmp(&y.low).unwrap())
-            .unwrap()
-            .clone()
-    }
-    fn is_max(&self, size: usize) -> bool {
-        self.cache[0] == self.max(size)
-    }
-    fn is_min(&self, size: usize) -> bool {
-        self.cache[0] == self.min(size)
-    }
-}
-
 #[test]
 fn roll_once_test() {
     use crate::utils::init_log;
@@ -243,7 +590,8 @@ fn roll_once_test() {
     let mut chart = crate::backtest::candle_chart::CandleChart::read_from_csv(
         "./data/ETHUSDT",
         Duration::minutes(1),
-    );
+    )
+    .unwrap();
     chart.candles.retain(|c| {
         c.close_time
             > OffsetDateTime::new_utc(
@@ -253,7 +601,7 @@ fn roll_once_test() {
     });
     let mut strategy = RollOnceStrategy::new(true, 100., RollConfig::default());
     for c in chart.candles.iter() {
-        strategy.update(c);
+        strategy.update(&c);
         if strategy.status != RollOnceStatus::Processing {
             break;
         }
@@ -271,6 +619,66 @@ fn roll_once_test() {
     );
 }
 
+#[test]
+fn adaptive_config_test() {
+    let mut judge = RollJudge::new(100);
+    for i in 0..100 {
+        let close = 100. + i as f64 * 0.1 + (i % 5) as f64 * 0.3;
+        judge.update(&CandleData {
+            open: close,
+            close,
+            high: close * 1.01,
+            low: close * 0.99,
+            ..Default::default()
+        });
+    }
+    let config = RollConfig::adaptive(&judge, true);
+    assert!(config.0.windows(2).all(|w| w[0].0 > w[1].0));
+    assert!(config.0[0].0 <= 0.5 / judge.max_draw(100, true));
+    let last = config.0.last().unwrap();
+    assert_eq!((last.0, last.1), (1., 1.));
+    assert!(last.2.is_some());
+}
+
+#[test]
+fn roll_ladder_test() {
+    #[derive(Deserialize)]
+    struct Config {
+        ladder: RollLadder,
+    }
+    let parse = |s: &str| toml::from_str::<Config>(s).unwrap().ladder;
+    assert_eq!(parse(r#"ladder = "default""#), RollLadder::Default);
+    let linear = parse("ladder = { linear = { k = 2.0, b = 0.0, max = 20.0 } }");
+    assert!(linear.validate().is_ok());
+    let levels: Vec<_> = linear.build(true, &[]).0.iter().map(|l| l.0).collect();
+    assert_eq!(levels, [1., 2., 4., 8., 16.]);
+    // 杠杆不增长时会死循环
+    let linear = parse("ladder = { linear = { k = 1.0, b = 0.0, max = 20.0 } }");
+    assert!(linear.validate().is_err());
+
+    let adaptive = parse("ladder = { adaptive = { lookback = 50 } }");
+    assert_eq!(adaptive.lookback(), 50);
+    let candles: Vec<_> = (0..100)
+        .map(|i| {
+            let close = 100. + (i % 7) as f64;
+            CandleData {
+                open: close,
+                close,
+                high: close * 1.01,
+                low: close * 0.99,
+                ..Default::default()
+            }
+        })
+        .collect();
+    let config = adaptive.build(false, &candles);
+    assert_eq!(config.0, RollConfig::from_candles(&candles[50..], false).0);
+    // k线不足时使用默认阶梯
+    assert_eq!(
+        adaptive.build(false, &candles[..1]).0,
+        RollConfig::short().0
+    );
+}
+
 #[test]
 fn roll_bull_finder() {
     use crate::utils::init_log;
@@ -289,7 +697,8 @@ fn roll_bull_finder() {
     let chart = crate::backtest::candle_chart::CandleChart::read_from_csv(
         "./data/PEOPLEUSDT",
         Duration::minutes(1),
-    );
+    )
+    .unwrap();
     let mut max = CandleData::default();
     let mut entry = CandleData::default();
     let mut start_new = true;
@@ -297,8 +706,8 @@ fn roll_bull_finder() {
     let mut max_draw: f64 = 0.;
     let mut max_draw_before_max: f64 = 0.;
     let mut max_draw_static = Vec::new();
-    let last = chart.candles.last().unwrap().clone();
-    for c in chart.candles {
+    let last = chart.candles.last().unwrap();
+    for c in chart.candles.iter() {
         if start_new {
             entry = c.clone();
             max = c.clone();
@@ -382,3 +791,184 @@ fn scratch() {
     let x = 2.0f64.powf(1. / steps as f64);
     println!("x: {}", x.powf(1000.));
 }
+
+#[test]
+fn roll_once_state_test() {
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let candles: Vec<CandleData> = (0..200)
+        .map(|i| {
+            let close = 100. * (1. + 0.002 * i as f64);
+            CandleData {
+                open: close,
+                close,
+                high: close * 1.001,
+                low: close * 0.999,
+                open_time: start + Duration::minutes(i),
+                close_time: start + Duration::minutes(i + 1),
+                ..Default::default()
+            }
+        })
+        .collect();
+    let config = RollConfig::new(vec![
+        (5., 0.05, None),
+        (3., 0.1, None),
+        (1., 1., Some(0.05)),
+    ]);
+    let mut strategy = RollOnceStrategy::new(true, 100., config.clone());
+    for c in candles[..100].iter() {
+        strategy.update(c);
+    }
+    assert!(strategy.level > 0);
+    let state = strategy.snapshot(0).unwrap();
+    let mut restored = RollOnceStrategy::new(true, 0., config.clone());
+    restored.restore(state.clone()).unwrap();
+    for c in candles[100..].iter() {
+        strategy.update(c);
+        restored.update(c);
+    }
+    assert_eq!(restored.level, strategy.level);
+    assert_eq!(restored.value(), strategy.value());
+    assert!(RollOnceStrategy::new(false, 0., config)
+        .restore(state)
+        .is_err());
+}
+
+#[test]
+fn roll_reentry_test() {
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let closes = [
+        100., 100., 70., 70., 70., 70., 69., 69., 40., 40., 39., 38., 37.,
+    ];
+    let candles: Vec<CandleData> = closes
+        .iter()
+        .enumerate()
+        .map(|(i, &close)| CandleData {
+            open: close,
+            close,
+            high: close * 1.001,
+            low: close * 0.999,
+            open_time: start + Duration::minutes(i as i64),
+            close_time: start + Duration::minutes(i as i64 + 1),
+            ..Default::default()
+        })
+        .collect();
+    let mut strategy = RollOnceStrategy::new(true, 100., RollConfig::new(vec![(5., 0.5, None)]));
+    strategy.set_reentry(ReentryPolicy {
+        wait: 2,
+        breakout: 3,
+        max_retries: 1,
+    });
+    for c in candles[..3].iter() {
+        strategy.update(c);
+    }
+    // 止损后等待，未创新低不入场
+    assert_eq!(strategy.status, RollOnceStatus::Waiting);
+    assert_eq!(strategy.history.len(), 1);
+    assert_eq!(strategy.history[0].status, RollOnceStatus::Failed);
+    for c in candles[3..6].iter() {
+        strategy.update(c);
+    }
+    assert_eq!(strategy.status, RollOnceStatus::Waiting);
+    strategy.update(&candles[6]);
+    assert_eq!(strategy.status, RollOnceStatus::Processing);
+    assert_eq!(strategy.retries, 1);
+    assert_eq!(strategy.level, 1);
+    // 再次止损后次数用尽
+    for c in candles[7..].iter() {
+        strategy.update(c);
+    }
+    assert_eq!(strategy.status, RollOnceStatus::Failed);
+    assert_eq!(strategy.history.len(), 2);
+    let state = strategy.snapshot(0).unwrap();
+    let mut restored = RollOnceStrategy::new(true, 0., RollConfig::new(vec![(5., 0.5, None)]));
+    restored.restore(state).unwrap();
+    assert_eq!(restored.history, strategy.history);
+}
+
+#[test]
+fn roll_short_symmetry_test() {
+    use time::Duration;
+
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let candles = |closes: &[f64]| -> Vec<CandleData> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| CandleData {
+                open: close,
+                close,
+                high: close,
+                low: close,
+                open_time: start + Duration::hours(i as i64),
+                close_time: start + Duration::hours(i as i64 + 1),
+                ..Default::default()
+            })
+            .collect()
+    };
+    let no_fees = FeeSchedule {
+        maker: 0.,
+        taker: 0.,
+        discount: 1.,
+    };
+    let run = |is_bull: bool, config: &RollConfig, closes: &[f64]| {
+        let mut strategy = RollOnceStrategy::new(is_bull, 100., config.clone());
+        strategy.set_fees(no_fees);
+        for c in candles(closes).iter() {
+            strategy.update(c);
+        }
+        strategy
+    };
+    // 以开仓价为中心镜像的价格，单级阶梯下多空结果应一致
+    let cases = [
+        (
+            RollConfig::new(vec![(5., 0.1, None)]),
+            vec![100., 105., 111.],
+        ),
+        (RollConfig::new(vec![(5., 0.1, None)]), vec![100., 90., 80.]),
+        (
+            RollConfig::new(vec![(2., 1., Some(0.05))]),
+            vec![100., 110., 120., 112.],
+        ),
+    ];
+    for (config, closes) in cases.iter() {
+        let mirrored: Vec<f64> = closes.iter().map(|c| 200. - c).collect();
+        let long = run(true, config, closes);
+        let short = run(false, config, &mirrored);
+        assert_eq!(long.status, short.status);
+        assert_eq!(long.trades().len(), 1);
+        assert_eq!(long.trades()[0].reason, short.trades()[0].reason);
+        assert!((long.value() - short.value()).abs() < 1e-9);
+        assert!((long.trades()[0].pnl - short.trades()[0].pnl).abs() < 1e-9);
+    }
+
+    // 正费率时多头支付、空头收取
+    let funding = FundingSeries {
+        symbol: "ETHUSDT".to_string(),
+        rates: (1..4)
+            .map(|i| crate::backtest::funding::FundingData {
+                time: start + Duration::hours(8 * i),
+                rate: 0.001,
+            })
+            .collect(),
+    };
+    let flat = candles(&[100.; 30]);
+    let mut sides = [true, false].map(|is_bull| {
+        let mut strategy =
+            RollOnceStrategy::new(is_bull, 100., RollConfig::new(vec![(5., 1., None)]));
+        strategy.set_fees(no_fees);
+        strategy.set_funding(funding.clone());
+        strategy
+    });
+    for c in flat.iter() {
+        sides.iter_mut().for_each(|s| s.update(c));
+    }
+    let [long, short] = sides;
+    assert!(long.funding_fee < 0.);
+    assert!((long.funding_fee + short.funding_fee).abs() < 1e-9);
+    assert!((long.value() + short.value() - 200.).abs() < 1e-9);
+    assert_eq!(RollConfig::for_side(false).0, RollConfig::short().0);
+}
@@ -0,0 +1,121 @@
+use std::{fs::File, path::Path};
+
+use tracing::info;
+
+use crate::{
+    algorithm::{Algorithm, SignalData, SymbolPrice},
+    error::DataError,
+};
+
+/// 录制的mark price推送，用于回测中按tick重放价格类算法
+#[derive(Debug, Default)]
+pub struct TickChart {
+    pub prices: Vec<SymbolPrice>,
+}
+
+impl TickChart {
+    /// time            事件时间（unix毫秒）
+    /// symbol          币种
+    /// mark_price      标记价格
+    /// price_index     指数价格
+    /// funding_rate    资金费率
+    pub fn read_from_csv(path: &str) -> Self {
+        fn read_from_csv_file(path: &Path) -> Vec<SymbolPrice> {
+            let file = File::open(path).unwrap();
+            let mut csv = csv::Reader::from_reader(file);
+            let mut prices = vec![];
+            for d in csv.records() {
+                let d = d.unwrap();
+                prices.push(SymbolPrice {
+                    time: d.get(0).unwrap().parse().unwrap(),
+                    symbol: d.get(1).unwrap().into(),
+                    mark_price: d.get(2).unwrap().parse().unwrap(),
+                    price_index: d.get(3).unwrap().parse().unwrap(),
+                    funding_rate: d.get(4).unwrap().parse().unwrap(),
+                });
+            }
+            prices
+        }
+        info!("read ticks from csv: {}", path);
+        let path = Path::new(path);
+        let mut chart = Self::default();
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_file() {
+                    chart.prices.append(&mut read_from_csv_file(&path));
+                }
+            }
+        } else if path.is_file() {
+            chart.prices = read_from_csv_file(path);
+        } else {
+            panic!("invalid path: {}", path.display());
+        }
+        chart.prices.sort_by_key(|p| p.time);
+        chart
+    }
+    pub fn write_to_csv(&self, path: &str) -> Result<(), DataError> {
+        let mut csv = csv::Writer::from_path(path)?;
+        csv.write_record([
+            "time",
+            "symbol",
+            "mark_price",
+            "price_index",
+            "funding_rate",
+        ])?;
+        for p in self.prices.iter() {
+            csv.write_record([
+                p.time.to_string(),
+                p.symbol.to_string(),
+                p.mark_price.to_string(),
+                p.price_index.to_string(),
+                p.funding_rate.to_string(),
+            ])?;
+        }
+        csv.flush()?;
+        Ok(())
+    }
+    /// 按时间顺序将价格依次喂给各算法，返回产生的信号
+    pub fn replay(&self, algorithms: &[&dyn Algorithm]) -> Vec<SignalData> {
+        self.prices
+            .iter()
+            .flat_map(|p| algorithms.iter().filter_map(|a| a.update(p)))
+            .collect()
+    }
+}
+
+#[test]
+fn tick_chart_test() {
+    use crate::algorithm::{
+        roll::{RollAlgo, RollAlgoConfig},
+        volatility::VolatilityAlgo,
+    };
+
+    let chart = TickChart {
+        prices: [100., 100.5, 100.2, 104.]
+            .iter()
+            .enumerate()
+            .map(|(i, &mark_price)| SymbolPrice {
+                symbol: "BTCUSDT".into(),
+                mark_price,
+                price_index: mark_price,
+                time: i as u64 * 1000,
+                funding_rate: 0.0001,
+            })
+            .collect(),
+    };
+    let path = std::env::temp_dir().join("hurribot_tick_chart_test.csv");
+    chart.write_to_csv(path.to_str().unwrap()).unwrap();
+    let read = TickChart::read_from_csv(path.to_str().unwrap());
+    assert_eq!(read.prices.len(), 4);
+    assert_eq!(read.prices[3].mark_price, 104.);
+    let roll = RollAlgo::new(RollAlgoConfig {
+        window: 2000,
+        ..Default::default()
+    });
+    let signals = read.replay(&[&roll, &VolatilityAlgo::default()]);
+    assert!(matches!(
+        signals.as_slice(),
+        [SignalData::Roll { is_bull: true, .. }]
+    ));
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use hurribot::{
+    backtest::{
+        batch::{run_job, Batch, BatchJob, ResultDb},
+        candle_chart::CandleChart,
+    },
+    utils::stdout_logger,
+};
+use time::Duration;
+
+const USAGE: &str = "usage: batch-backtest <manifest.csv> <results.db> <candles dir> <interval minutes> [batch name]
+       candles are read from <candles dir>/<symbol>.csv, batch name defaults to the manifest file name";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    stdout_logger();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 4 {
+        return Err(USAGE.into());
+    }
+    let jobs = BatchJob::read_manifest(&args[0])?;
+    let db = ResultDb::open(&args[1])?;
+    let interval = Duration::minutes(args[3].parse()?);
+    let name = match args.get(4) {
+        Some(name) => name.clone(),
+        None => std::path::Path::new(&args[0])
+            .file_stem()
+            .map_or("batch".to_string(), |s| s.to_string_lossy().into_owned()),
+    };
+    // 每个币种只读取一次k线，读取失败的币种其任务记为失败
+    let mut charts = HashMap::new();
+    for job in jobs.iter() {
+        charts.entry(job.symbol.clone()).or_insert_with(|| {
+            let path = format!("{}/{}.csv", args[2], job.symbol);
+            CandleChart::read_from_csv(&path, interval)
+                .map(|chart| chart.candles.to_candles())
+                .map_err(|e| format!("{}: {}", path, e))
+        });
+    }
+    let summary = Batch::new(&name, jobs).run(&db, |job| {
+        let candles = charts[&job.symbol].as_ref().map_err(Clone::clone)?;
+        run_job(job, candles)
+    })?;
+    println!(
+        "batch {}: {} jobs, {} skipped, {} completed, {} failed",
+        name, summary.total, summary.skipped, summary.completed, summary.failed
+    );
+    for row in db.results(&name)?.iter().take(10) {
+        if let Ok(m) = &row.result {
+            println!(
+                "{} {} [{}] return x{:.3} drawdown {:.2}%",
+                row.strategy,
+                row.symbol,
+                row.params,
+                m.return_rate,
+                m.max_drawdown * 100.
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use std::path::Path;
+
+use hurribot::{
+    backtest::{candle_chart::CandleChart, journal::read_journal, replay::Replay},
+    utils::stdout_logger,
+};
+use time::Duration;
+
+const USAGE: &str =
+    "usage: replay-view <trades.csv> <candles.csv|dir> <interval minutes> [out dir] [context candles]";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    stdout_logger();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 3 {
+        return Err(USAGE.into());
+    }
+    let trades = read_journal(Path::new(&args[0]))?;
+    let interval = Duration::minutes(args[2].parse()?);
+    let chart = CandleChart::read_from_csv(&args[1], interval)?;
+    let out = args.get(3).map_or("./reports/replay", String::as_str);
+    let candles = chart.candles.to_candles();
+    let mut replay = Replay::new(&candles, &trades);
+    if let Some(context) = args.get(4) {
+        replay = replay.with_context(context.parse()?);
+    }
+    let index = replay.write(Path::new(out))?;
+    println!("{}", index.display());
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use hurribot::{
+    backtest::journal::read_journal,
+    store::Store,
+    tax::{fills_from_trades, realize, write_tax_lots, CostMethod, FundingPayment},
+    utils::stdout_logger,
+};
+
+const USAGE: &str = "usage: tax-export <fifo|average> <out.csv> journal <trades.csv> <symbol>
+       tax-export <fifo|average> <out.csv> store <store.db> [asset]";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    stdout_logger();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 4 {
+        return Err(USAGE.into());
+    }
+    let method: CostMethod = args[0].parse()?;
+    let (fills, funding) = match (args[2].as_str(), args.get(4)) {
+        ("journal", Some(symbol)) => {
+            let trades = read_journal(Path::new(&args[3]))?;
+            (fills_from_trades(symbol, &trades), vec![])
+        }
+        ("store", asset) => {
+            let store = Store::open(&args[3])?;
+            let asset = asset.map_or("USDT", String::as_str);
+            let incomes = store.incomes(asset, 0, u64::MAX >> 1)?;
+            (
+                store.fills(0, u64::MAX >> 1)?,
+                FundingPayment::from_incomes(&incomes),
+            )
+        }
+        _ => return Err(USAGE.into()),
+    };
+    let lots = realize(method, &fills, &funding);
+    write_tax_lots(Path::new(&args[1]), &lots)?;
+    let gain: f64 = lots.iter().map(|l| l.gain()).sum();
+    println!("{} lots, total gain {:.2}", lots.len(), gain);
+    Ok(())
+}
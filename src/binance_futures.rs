@@ -1,6 +1,6 @@
 use std::{
     sync::{
-        atomic::{AtomicBool, Ordering::Relaxed},
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering::Relaxed},
         Arc,
     },
     thread::JoinHandle,
@@ -11,45 +11,65 @@ use binance::{
     api::Binance,
     config::Config,
     futures::{
+        account::FuturesAccount,
         userstream::FuturesUserStream,
         websockets::{FuturesMarket, FuturesWebSockets, FuturesWebsocketEvent},
     },
 };
-use crossbeam::channel::Receiver;
+use crossbeam::channel::{Receiver, Sender};
 use dashmap::DashMap;
+use hmac::{Hmac, Mac};
 use serde::Deserialize;
+use sha2::Sha256;
+use time::OffsetDateTime;
 use tracing::{error, info, warn};
 
-use crate::{algorithm::SymbolPrice, controller::AccountInfo};
+use crate::{
+    algorithm::{DepthData, KlineData, SymbolPrice},
+    candle::CandleData,
+    controller::AccountInfo,
+    error::{BinanceResultExt, ConfigError, MarketError, WsError},
+    market::account_cache::AccountCache,
+    symbol::{
+        filter::{SymbolFilter, DEFAULT_QUOTE_ASSET},
+        SymbolId,
+    },
+    utils::{
+        coalesce::{coalescing_channel, ChannelStats, IDLE_FLUSH_INTERVAL},
+        unix_millis,
+    },
+};
+
+/// 价格通道容量，约为全部币种三轮推送
+pub const PRICE_CHANNEL_CAPACITY: usize = 1024;
+
+#[cfg(feature = "async")]
+pub mod async_ws;
+pub mod combined;
+pub mod countdown;
+pub mod income;
+pub mod listen_key;
+pub mod positioning;
+pub mod reconnect;
+pub mod subscriptions;
+pub mod transfer;
+pub mod user_stream;
+
+use listen_key::ListenKeyManager;
+use reconnect::{BackoffPolicy, Reconnect, ReconnectStats};
+use subscriptions::{depth_stream, kline_stream, Subscriptions, MARK_PRICE_ALL_STREAM};
+use user_stream::{UserDataEvent, UserDataSocket};
 
 trait FuturesWebSocketsExt {
-    fn event_loop_reconnect(&mut self, running: &AtomicBool) -> bool;
+    fn event_loop_disconnect(&mut self, running: &AtomicBool) -> Result<(), WsError>;
 }
 
 impl<'a> FuturesWebSocketsExt for FuturesWebSockets<'a> {
-    fn event_loop_reconnect(&mut self, running: &AtomicBool) -> bool {
-        if let Err(e) = self.event_loop(running) {
-            match e.0 {
-                binance::errors::ErrorKind::Msg(e) => {
-                    if e.contains("Disconnected") || e.contains("UserDataStreamExpiredEvent") {
-                        warn!("Disconnected from binance, reconnecting...: {}", e);
-                        self.disconnect().ok();
-                        return true;
-                    }
-                    error!("Event loop error, exiting...: {:?}", e);
-                }
-                binance::errors::ErrorKind::Tungstenite(e) => {
-                    warn!("Connection error, reconnecting...: {}", e);
-                    self.disconnect().ok();
-                    return true;
-                }
-                e => {
-                    error!("Unexpected error, exiting...: {:?}", e);
-                }
-            }
-        }
+    /// 事件循环退出后断开连接，因running置为false退出时返回Ok
+    fn event_loop_disconnect(&mut self, running: &AtomicBool) -> Result<(), WsError> {
+        let result = self.event_loop(running).map_err(WsError::from);
         self.disconnect().ok();
-        false
+        result
     }
 }
 
@@ -59,153 +79,597 @@ pub struct BinanceKeys {
     pub secret_key: String,
 }
 impl BinanceKeys {
-    pub fn value_parse(path: &str) -> anyhow::Result<Self> {
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
         let c = std::fs::read_to_string(path)?;
         let val: Self = toml::from_str(&c)?;
         Ok(val)
     }
+    /// 自行构造的REST请求的HMAC-SHA256签名
+    pub fn sign(&self, query: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret_key.as_bytes()).unwrap();
+        mac.update(query.as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
 }
 opaque_debug::implement!(BinanceKeys);
 
+pub type SymbolPrices = Arc<DashMap<SymbolId, SymbolPrice>>;
+
+/// 行情流健康状态
+#[derive(Debug)]
+pub struct StreamHealth {
+    /// 最近一次收到事件的本地时间（unix毫秒）
+    last_event: AtomicU64,
+    stale: AtomicBool,
+    timeout: Duration,
+    reconnects: Arc<ReconnectStats>,
+}
+
+impl StreamHealth {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            last_event: AtomicU64::new(unix_millis()),
+            stale: AtomicBool::new(false),
+            timeout,
+            reconnects: Arc::default(),
+        }
+    }
+    pub fn is_stale(&self) -> bool {
+        self.stale.load(Relaxed)
+    }
+    pub fn last_event(&self) -> u64 {
+        self.last_event.load(Relaxed)
+    }
+    pub fn reconnects(&self) -> &ReconnectStats {
+        &self.reconnects
+    }
+    fn elapsed(&self) -> Duration {
+        Duration::from_millis(unix_millis().saturating_sub(self.last_event()))
+    }
+    fn touch(&self) {
+        self.last_event.store(unix_millis(), Relaxed);
+        if self.stale.swap(false, Relaxed) {
+            info!("Market data recovered");
+        }
+    }
+    /// 超时未收到事件时标记数据过期，返回是否过期
+    fn check(&self) -> bool {
+        if self.elapsed() <= self.timeout {
+            return false;
+        }
+        if !self.stale.swap(true, Relaxed) {
+            warn!("Market data stale: no event for {:?}", self.elapsed());
+        }
+        true
+    }
+    /// 阻塞读取时需等到下一帧（包括服务器ping）事件循环才会退出，但过期标记立即生效
+    fn watch(&self, running: &AtomicBool, alive: &AtomicBool) {
+        while running.load(Relaxed) {
+            std::thread::sleep(Duration::from_secs(1));
+            if self.check() {
+                alive.store(false, Relaxed);
+            }
+        }
+        alive.store(false, Relaxed);
+    }
+    /// 事件循环是否因watchdog超时退出，是则重置计时并允许重连
+    fn stalled(
+        running: &AtomicBool,
+        alive: &AtomicBool,
+        health: &Option<Arc<StreamHealth>>,
+    ) -> bool {
+        let Some(health) = health else {
+            return false;
+        };
+        if !running.load(Relaxed) || !health.is_stale() {
+            return false;
+        }
+        warn!("Stream stalled, reconnecting...");
+        health.last_event.store(unix_millis(), Relaxed);
+        alive.store(true, Relaxed);
+        true
+    }
+}
+
+/// 全市场标记价格的处理函数，价格通道有界，消费过慢时每个币种只保留最新的价格
+fn price_handler(
+    filter: Arc<SymbolFilter>,
+) -> (
+    impl Fn(FuturesWebsocketEvent) + Send + Sync + 'static,
+    Receiver<SymbolPrice>,
+    SymbolPrices,
+    Arc<ChannelStats>,
+) {
+    let prices = Arc::new(DashMap::new());
+    let (price_tx, price_rx) =
+        coalescing_channel(PRICE_CHANNEL_CAPACITY, |p: &SymbolPrice| p.symbol);
+    let price_tx = price_tx.with_idle_flush(IDLE_FLUSH_INTERVAL);
+    let stats = price_tx.stats();
+    let handler = price_events(filter, prices.clone(), move |p| {
+        price_tx.send(p);
+    });
+    (handler, price_rx, prices, stats)
+}
+
+/// 标记价格写入prices后交给send
+fn price_events(
+    filter: Arc<SymbolFilter>,
+    prices: SymbolPrices,
+    send: impl Fn(SymbolPrice) + Send + Sync + 'static,
+) -> impl Fn(FuturesWebsocketEvent) + Send + Sync + 'static {
+    move |event: FuturesWebsocketEvent| {
+        if let FuturesWebsocketEvent::MarkPriceAll(v) = event {
+            v.into_iter().for_each(|p| {
+                if !filter.allows(&p.symbol) {
+                    return;
+                }
+                let mark_price: f64 = p.mark_price.parse().unwrap_or_default();
+                let price_index: f64 = p
+                    .index_price
+                    .map(|s| s.parse().unwrap_or_default())
+                    .unwrap_or(mark_price);
+                let symbol = SymbolId::intern_cached(&p.symbol);
+                let s = SymbolPrice {
+                    symbol,
+                    mark_price,
+                    price_index,
+                    time: p.event_time,
+                    funding_rate: p.funding_rate.parse().unwrap_or_default(),
+                };
+                prices.insert(symbol, s.clone());
+                send(s);
+            });
+        }
+    }
+}
+
+/// 深度的处理函数，接收端断开后丢弃事件
+fn depth_handler() -> (
+    impl Fn(FuturesWebsocketEvent) + Send + Sync + 'static,
+    Receiver<DepthData>,
+) {
+    let (depth_tx, depth_rx) = crossbeam::channel::unbounded();
+    let handler = depth_events(move |d| {
+        depth_tx.send(d).ok();
+    });
+    (handler, depth_rx)
+}
+
+fn depth_events(
+    send: impl Fn(DepthData) + Send + Sync + 'static,
+) -> impl Fn(FuturesWebsocketEvent) + Send + Sync + 'static {
+    move |event: FuturesWebsocketEvent| {
+        if let FuturesWebsocketEvent::DepthOrderBook(d) = event {
+            send(DepthData {
+                symbol: d.symbol,
+                time: d.event_time,
+                bids: d.bids.into_iter().map(|b| (b.price, b.qty)).collect(),
+                asks: d.asks.into_iter().map(|a| (a.price, a.qty)).collect(),
+            });
+        }
+    }
+}
+
+/// k线的处理函数，只推送已收盘的k线，接收端断开后丢弃事件
+fn kline_handler() -> (
+    impl Fn(FuturesWebsocketEvent) + Send + Sync + 'static,
+    Receiver<KlineData>,
+) {
+    let (kline_tx, kline_rx) = crossbeam::channel::unbounded();
+    let handler = kline_events(move |k| {
+        kline_tx.send(k).ok();
+    });
+    (handler, kline_rx)
+}
+
+fn kline_events(
+    send: impl Fn(KlineData) + Send + Sync + 'static,
+) -> impl Fn(FuturesWebsocketEvent) + Send + Sync + 'static {
+    move |event: FuturesWebsocketEvent| {
+        if let FuturesWebsocketEvent::Kline(e) = event {
+            if !e.kline.is_final_bar {
+                return;
+            }
+            let number = |v: &str| v.parse::<f64>().unwrap_or_default();
+            let time = |ms: i64| {
+                OffsetDateTime::from_unix_timestamp_nanos(ms as i128 * 1_000_000).unwrap()
+            };
+            send(KlineData {
+                symbol: SymbolId::intern_cached(&e.symbol),
+                candle: CandleData {
+                    open: number(&e.kline.open),
+                    close: number(&e.kline.close),
+                    high: number(&e.kline.high),
+                    low: number(&e.kline.low),
+                    volume: number(&e.kline.volume),
+                    open_time: time(e.kline.open_time),
+                    close_time: time(e.kline.close_time),
+                },
+            });
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum FuturesWsConnection {
-    MarketData(Vec<String>),
-    UserData(BinanceKeys),
+    /// 订阅变化时以新的列表重连
+    MarketData(Subscriptions),
+    /// MARGIN_CALL事件转为AccountInfo::MarginCall发送到通道，不设置时忽略
+    UserData(BinanceKeys, Option<Sender<AccountInfo>>),
 }
 impl FuturesWsConnection {
+    /// 价格通道有界，消费过慢时每个币种只保留最新的价格，合并和丢弃的数量见ChannelStats
     pub fn run_price_info() -> (
         Receiver<SymbolPrice>,
-        Arc<DashMap<String, SymbolPrice>>,
+        SymbolPrices,
+        Arc<StreamHealth>,
+        Arc<ChannelStats>,
+        JoinHandle<()>,
+    ) {
+        Self::run_price_info_filtered(Arc::default(), BackoffPolicy::default())
+    }
+    /// 只推送通过过滤的币种，成交额规则在screener或BinanceMarket更新成交额后生效
+    pub fn run_price_info_filtered(
+        filter: Arc<SymbolFilter>,
+        policy: BackoffPolicy,
+    ) -> (
+        Receiver<SymbolPrice>,
+        SymbolPrices,
+        Arc<StreamHealth>,
+        Arc<ChannelStats>,
         JoinHandle<()>,
     ) {
-        let prices = Arc::new(DashMap::new());
-        let prices_c = prices.clone();
-        let (price_tx, price_rx) = crossbeam::channel::unbounded();
+        let (handler, price_rx, prices, stats) = price_handler(filter);
         let running = Arc::new(AtomicBool::new(true));
-        let handler = move |event: FuturesWebsocketEvent| {
-            if let FuturesWebsocketEvent::MarkPriceAll(v) = event {
-                v.into_iter().for_each(|p| {
-                    if p.symbol.contains('_') || p.symbol.contains("USDC") {
-                        return;
-                    }
-                    let mark_price: f64 = p.mark_price.parse().unwrap_or_default();
-                    let price_index: f64 = p
-                        .index_price
-                        .map(|s| s.parse().unwrap_or_default())
-                        .unwrap_or(mark_price);
-                    let s = SymbolPrice {
-                        symbol: p.symbol.clone(),
-                        mark_price,
-                        price_index,
-                        time: p.event_time,
-                        funding_rate: p.funding_rate.parse().unwrap_or_default(),
-                    };
-                    prices_c.insert(p.symbol.clone(), s.clone());
-                    price_tx.send(s).unwrap();
-                });
-            }
-            Ok(())
-        };
-        let subscribes = vec!["!markPrice@arr@1s".to_string()];
-        let conn = FuturesWsConnection::MarketData(subscribes);
-        let h = conn.run(handler, running.clone());
-        (price_rx, prices, h)
+        let subscribes = vec![MARK_PRICE_ALL_STREAM.to_string()];
+        let conn = FuturesWsConnection::MarketData(subscribes.into());
+        let health = Arc::new(StreamHealth::new(Duration::from_secs(10)));
+        let h = conn.run_with_health(
+            move |e| {
+                handler(e);
+                Ok(())
+            },
+            running.clone(),
+            Some(health.clone()),
+            policy,
+            |_| {},
+        );
+        (price_rx, prices, health, stats, h)
+    }
+    pub fn run_depth_info(symbols: &[String]) -> (Receiver<DepthData>, JoinHandle<()>) {
+        let subscriptions = symbols.iter().map(|s| depth_stream(s)).collect();
+        Self::run_depth_subscriptions(Subscriptions::new(subscriptions))
+    }
+    /// 订阅列表可在运行时修改，如跟随screener的观察列表
+    pub fn run_depth_subscriptions(
+        subscriptions: Subscriptions,
+    ) -> (Receiver<DepthData>, JoinHandle<()>) {
+        let (handler, depth_rx) = depth_handler();
+        let running = Arc::new(AtomicBool::new(true));
+        let conn = FuturesWsConnection::MarketData(subscriptions);
+        let h = conn.run(
+            move |e| {
+                handler(e);
+                Ok(())
+            },
+            running.clone(),
+        );
+        (depth_rx, h)
+    }
+    /// 只推送已收盘的k线
+    pub fn run_kline_info(
+        symbols: &[String],
+        interval: &str,
+    ) -> (Receiver<KlineData>, JoinHandle<()>) {
+        let subscriptions = symbols.iter().map(|s| kline_stream(s, interval)).collect();
+        Self::run_kline_subscriptions(Subscriptions::new(subscriptions))
+    }
+    /// 订阅列表可在运行时修改，如跟随screener的观察列表
+    pub fn run_kline_subscriptions(
+        subscriptions: Subscriptions,
+    ) -> (Receiver<KlineData>, JoinHandle<()>) {
+        let (handler, kline_rx) = kline_handler();
+        let running = Arc::new(AtomicBool::new(true));
+        let conn = FuturesWsConnection::MarketData(subscriptions);
+        let h = conn.run(
+            move |e| {
+                handler(e);
+                Ok(())
+            },
+            running.clone(),
+        );
+        (kline_rx, h)
     }
     pub fn run_account_info(binance_keys: BinanceKeys) -> (Receiver<AccountInfo>, JoinHandle<()>) {
+        Self::run_account_info_with_cache(binance_keys, None, BackoffPolicy::default())
+    }
+    /// 同时用事件维护账户缓存，重连后对账失败时缓存失效；对账的结算资产与缓存一致
+    pub fn run_account_info_with_cache(
+        binance_keys: BinanceKeys,
+        cache: Option<Arc<AccountCache>>,
+        policy: BackoffPolicy,
+    ) -> (Receiver<AccountInfo>, JoinHandle<()>) {
         let (account_tx, account_rx) = crossbeam::channel::unbounded();
+        let reconcile_tx = account_tx.clone();
+        let margin_call_tx = account_tx.clone();
+        let on_reconnect = Self::reconciler(&binance_keys, cache.clone(), move |info| {
+            reconcile_tx.send(info).ok();
+        });
         let running = Arc::new(AtomicBool::new(true));
         let handler = move |event: FuturesWebsocketEvent| {
-            info!("Account Stream Received: {:?}", event);
-            match event {
-                FuturesWebsocketEvent::OrderTrade(e) => {
-                    account_tx
-                        .send(AccountInfo::OrderTrade {
-                            time: e.event_time,
-                            order: Box::new(e.order),
-                        })
-                        .unwrap();
-                }
-                FuturesWebsocketEvent::AccountUpdate(e) => {
-                    account_tx
-                        .send(AccountInfo::AccountUpdate {
-                            time: e.event_time,
-                            data: e.data,
-                        })
-                        .unwrap();
-                }
-                _ => {}
+            let Some(info) = Self::account_info(event) else {
+                return Ok(());
+            };
+            if let Some(cache) = &cache {
+                cache.apply(&info);
             }
+            account_tx.send(info).unwrap();
             Ok(())
         };
-        let conn = FuturesWsConnection::UserData(binance_keys);
-        let h = conn.run(handler, running.clone());
+        let conn = FuturesWsConnection::UserData(binance_keys, Some(margin_call_tx));
+        let h = conn.run_with_health(handler, running.clone(), None, policy, on_reconnect);
         (account_rx, h)
     }
-    pub fn run<F>(self, mut handler: F, running: Arc<AtomicBool>) -> JoinHandle<()>
+    /// 重连期间可能丢失事件，重连后立即通过REST对账，对账结果交给send；
+    /// 对账的结算资产与缓存一致，失败时缓存失效
+    fn reconciler(
+        binance_keys: &BinanceKeys,
+        cache: Option<Arc<AccountCache>>,
+        send: impl Fn(AccountInfo) + Send + 'static,
+    ) -> impl FnMut(u64) + Send + 'static {
+        let quote_asset = cache
+            .as_ref()
+            .map_or(DEFAULT_QUOTE_ASSET, |c| c.quote_asset())
+            .to_string();
+        let account = FuturesAccount::new(
+            Some(binance_keys.api_key.clone()),
+            Some(binance_keys.secret_key.clone()),
+        );
+        move |gap: u64| match Self::reconcile(&account, &quote_asset) {
+            Ok(info) => {
+                info!("Account reconciled after {}ms event gap", gap);
+                if let Some(cache) = &cache {
+                    cache.apply(&info);
+                }
+                send(info);
+            }
+            Err(e) => {
+                error!("Account reconcile failed: {}", e);
+                if let Some(cache) = &cache {
+                    cache.invalidate();
+                }
+            }
+        }
+    }
+    /// 账户流中的订单和账户更新，其他事件为None
+    fn account_info(event: FuturesWebsocketEvent) -> Option<AccountInfo> {
+        info!("Account Stream Received: {:?}", event);
+        match event {
+            FuturesWebsocketEvent::OrderTrade(e) => Some(AccountInfo::OrderTrade {
+                time: e.event_time,
+                order: Box::new(e.order),
+            }),
+            FuturesWebsocketEvent::AccountUpdate(e) => Some(AccountInfo::AccountUpdate {
+                time: e.event_time,
+                data: e.data,
+            }),
+            _ => None,
+        }
+    }
+    /// 通过REST获取结算资产余额、持仓和挂单
+    fn reconcile(account: &FuturesAccount, quote_asset: &str) -> Result<AccountInfo, MarketError> {
+        let info = account
+            .account_information()
+            .market_context("get account info")?;
+        let balance = info
+            .assets
+            .iter()
+            .find(|a| a.asset == quote_asset)
+            .map(|a| a.wallet_balance);
+        let mut positions = vec![];
+        let mut open_orders = vec![];
+        for p in info.positions.iter().filter(|p| p.position_amount != 0.) {
+            positions.extend(
+                account
+                    .position_information(p.symbol.clone())
+                    .market_context(&format!("get position of {}", p.symbol))?,
+            );
+            open_orders.extend(
+                account
+                    .get_all_open_orders(p.symbol.clone())
+                    .market_context(&format!("get open orders of {}", p.symbol))?,
+            );
+        }
+        Ok(AccountInfo::Reconcile {
+            time: unix_millis(),
+            balance,
+            positions,
+            open_orders,
+        })
+    }
+    pub fn run<F>(self, handler: F, running: Arc<AtomicBool>) -> JoinHandle<()>
     where
         F: FnMut(FuturesWebsocketEvent) -> binance::errors::Result<()> + Send + 'static,
+    {
+        self.run_with_health(handler, running, None, BackoffPolicy::default(), |_| {})
+    }
+    /// health不为空时启动watchdog：超时未收到事件则标记数据过期并强制重连，重连次数计入health
+    /// 断线或连接失败后按policy退避重连，24小时定期断开立即重连
+    /// 每次重连成功后以距上次事件的间隔（ms）调用on_reconnect
+    pub fn run_with_health<F, R>(
+        self,
+        mut handler: F,
+        running: Arc<AtomicBool>,
+        health: Option<Arc<StreamHealth>>,
+        policy: BackoffPolicy,
+        mut on_reconnect: R,
+    ) -> JoinHandle<()>
+    where
+        F: FnMut(FuturesWebsocketEvent) -> binance::errors::Result<()> + Send + 'static,
+        R: FnMut(u64) + Send + 'static,
     {
         std::thread::spawn(move || {
+            // 事件循环以alive为准，watchdog超时、订阅变化或listen key被删除后将其置为false
+            let alive = Arc::new(AtomicBool::new(true));
+            if let Some(h) = &health {
+                let (h, r, a) = (h.clone(), running.clone(), alive.clone());
+                std::thread::spawn(move || h.watch(&r, &a));
+            }
+            let stats = health
+                .as_ref()
+                .map_or_else(Arc::default, |h| h.reconnects.clone());
+            let mut reconnect = Reconnect::new(policy, stats.clone());
+            let health_c = health.clone();
+            let last_event = Arc::new(AtomicU64::new(unix_millis()));
+            let last_event_c = last_event.clone();
+            let mut handler = move |e: FuturesWebsocketEvent| {
+                if let Some(h) = &health_c {
+                    h.touch();
+                }
+                stats.clear_failures();
+                last_event_c.store(unix_millis(), Relaxed);
+                handler(e)
+            };
+            let mut connected = false;
+            let mut connected_at = 0;
+            let mut reconnected = |connected_at: &mut u64| {
+                *connected_at = unix_millis();
+                if connected {
+                    let gap = unix_millis().saturating_sub(last_event.load(Relaxed));
+                    warn!("Reconnected, {}ms since last event", gap);
+                    on_reconnect(gap);
+                }
+                connected = true;
+            };
+            // 断线后按退避等待，返回是否继续重连
+            let mut backoff = |e: Option<WsError>, connected_at: u64| {
+                let delay = match e {
+                    Some(e) => {
+                        let uptime =
+                            Duration::from_millis(unix_millis().saturating_sub(connected_at));
+                        let received = last_event.load(Relaxed) >= connected_at;
+                        reconnect.disconnected(&e, uptime, received)
+                    }
+                    None => reconnect.retry(),
+                };
+                delay.is_some_and(|d| reconnect::wait(&running, d))
+            };
             match self {
-                Self::MarketData(sub) => {
+                Self::MarketData(subscriptions) => {
+                    let (s, r, a) = (subscriptions.clone(), running.clone(), alive.clone());
+                    std::thread::spawn(move || s.watch(&r, &a));
                     let mut futures_ws = FuturesWebSockets::new(handler);
                     loop {
+                        subscriptions.take_changed();
+                        let Some(sub) = subscriptions.wait_nonempty(&running) else {
+                            break;
+                        };
                         if let Err(e) =
                             futures_ws.connect_multiple_streams(&FuturesMarket::USDM, &sub)
                         {
-                            error!("Init connection error, exiting...: {:?}", e);
+                            error!("Init connection error: {:?}", e);
+                            if backoff(None, connected_at) {
+                                continue;
+                            }
                             break;
                         }
-                        if !futures_ws.event_loop_reconnect(&running) {
+                        reconnected(&mut connected_at);
+                        match futures_ws.event_loop_disconnect(&alive) {
+                            Ok(()) => {}
+                            Err(e) if !e.is_retryable() => {
+                                error!("Event loop error, exiting...: {}", e);
+                                break;
+                            }
+                            Err(e) => {
+                                if !backoff(Some(e), connected_at) {
+                                    break;
+                                }
+                            }
+                        }
+                        if !running.load(Relaxed) {
                             break;
                         }
+                        if subscriptions.take_changed() {
+                            info!("Resubscribing {} streams", subscriptions.streams().len());
+                        } else {
+                            StreamHealth::stalled(&running, &alive, &health);
+                        }
+                        alive.store(true, Relaxed);
                     }
                 }
-                Self::UserData(config) => {
-                    let user_stream = FuturesUserStream::new(
+                Self::UserData(config, margin_calls) => {
+                    // listen key被删除时退出事件循环，用新的key重连
+                    let mut listen_keys = ListenKeyManager::new(FuturesUserStream::new(
                         Some(config.api_key.clone()),
                         Some(config.secret_key.clone()),
-                    );
-                    let handler = |e: FuturesWebsocketEvent| {
-                        if let FuturesWebsocketEvent::UserDataStreamExpiredEvent(_) = e {
-                            error_chain::bail!("UserDataStreamExpiredEvent");
+                    ))
+                    .with_disconnect(alive.clone());
+                    let mut handler = |e: UserDataEvent| match e {
+                        UserDataEvent::Futures(e) => {
+                            if let FuturesWebsocketEvent::UserDataStreamExpiredEvent(_) = *e {
+                                error_chain::bail!("UserDataStreamExpiredEvent");
+                            }
+                            handler(*e)
+                        }
+                        UserDataEvent::MarginCall { time, positions } => {
+                            if let Some(tx) = &margin_calls {
+                                tx.send(AccountInfo::MarginCall { time, positions }).ok();
+                            }
+                            Ok(())
                         }
-                        handler(e)
                     };
-                    let mut futures_ws = FuturesWebSockets::new(handler);
-                    let mut listen_key_last = String::new();
                     loop {
-                        let listen_key = match user_stream.start() {
-                            Ok(u) => u.listen_key,
+                        let listen_key = match listen_keys.listen_key() {
+                            Ok(k) => k,
                             Err(e) => {
-                                error!("Request for listen key failed, exiting...: {:?}", e);
+                                error!("Request for listen key failed: {}", e);
+                                if backoff(None, connected_at) {
+                                    continue;
+                                }
                                 break;
                             }
                         };
-                        if listen_key != listen_key_last {
-                            let (u_c, l_c) = (user_stream.clone(), listen_key.clone());
-                            std::thread::spawn(move || loop {
-                                match u_c.keep_alive(&l_c) {
-                                    Ok(_) => {
-                                        info!("Listen key {} extended.", l_c);
-                                        std::thread::sleep(Duration::from_secs(50 * 60))
-                                    }
-                                    Err(e) => {
-                                        warn!("Listen key {} dropped: {:?}", l_c, e.0);
-                                        break;
-                                    }
+                        let mut socket = match UserDataSocket::connect(&listen_key) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                error!("Init connection error: {}", e);
+                                if backoff(None, connected_at) {
+                                    continue;
                                 }
-                            });
-                            listen_key_last = listen_key.clone();
-                        }
-                        if let Err(e) = futures_ws.connect(&FuturesMarket::USDM, &listen_key) {
-                            error!("Init connection error, exiting...: {:?}", e);
-                            break;
-                        }
-                        if !futures_ws.event_loop_reconnect(&running) {
-                            break;
+                                break;
+                            }
+                        };
+                        reconnected(&mut connected_at);
+                        match socket.event_loop(&mut handler, &running, &alive) {
+                            Ok(()) => {
+                                if running.load(Relaxed) && listen_keys.take_lost() {
+                                    warn!("Listen key lost, reconnecting...");
+                                    alive.store(true, Relaxed);
+                                    continue;
+                                }
+                                if !StreamHealth::stalled(&running, &alive, &health) {
+                                    break;
+                                }
+                            }
+                            Err(e) if !e.is_retryable() => {
+                                error!("Event loop error, exiting...: {}", e);
+                                break;
+                            }
+                            Err(e) => {
+                                if let WsError::ListenKeyExpired = e {
+                                    listen_keys.expire();
+                                }
+                                if !backoff(Some(e), connected_at) {
+                                    break;
+                                }
+                            }
                         }
                     }
+                    listen_keys.close();
                 }
             };
             running.store(false, Relaxed);
@@ -213,15 +677,66 @@ impl FuturesWsConnection {
     }
 }
 
+pub const RECV_WINDOW: u64 = 10000;
+
+/// 本地时钟与币安服务器时间的偏差（服务器时间 - 本地时间，ms）
+#[derive(Debug, Default)]
+pub struct ClockDrift {
+    offset: AtomicI64,
+}
+
+impl ClockDrift {
+    pub fn offset(&self) -> i64 {
+        self.offset.load(Relaxed)
+    }
+    /// 校正后的服务器时间（unix毫秒），用于自行签名的请求时间戳
+    pub fn timestamp(&self) -> u64 {
+        (unix_millis() as i64 + self.offset()) as u64
+    }
+    /// 以请求往返的中点估计本地时间
+    fn measure(general: &binance::futures::general::FuturesGeneral) -> Result<i64, MarketError> {
+        let start = unix_millis();
+        let server_time = general
+            .get_server_time()
+            .market_context("get server time")?
+            .server_time;
+        let end = unix_millis();
+        Ok(server_time as i64 - ((start + end) / 2) as i64)
+    }
+    fn update(&self, offset: i64) {
+        self.offset.store(offset, Relaxed);
+        if offset.unsigned_abs() >= RECV_WINDOW {
+            error!(
+                "Clock drift {}ms exceeds recv window {}ms",
+                offset, RECV_WINDOW
+            );
+        } else if offset.unsigned_abs() >= RECV_WINDOW / 2 {
+            warn!(
+                "Clock drift {}ms is close to recv window {}ms",
+                offset, RECV_WINDOW
+            );
+        }
+    }
+    /// 偏差超过recv_window时签名请求必然被拒（-1021）
+    pub fn check(&self) -> Result<(), MarketError> {
+        let offset = self.offset();
+        if offset.unsigned_abs() >= RECV_WINDOW {
+            return Err(MarketError::ClockDrift(offset));
+        }
+        Ok(())
+    }
+}
+
 pub struct Clients {
     pub general: binance::futures::general::FuturesGeneral,
     pub market: binance::futures::market::FuturesMarket,
     pub account: binance::futures::account::FuturesAccount,
+    pub clock: Arc<ClockDrift>,
 }
 impl Clients {
     pub fn new(keys: BinanceKeys) -> Self {
         let config = Config {
-            recv_window: 10000,
+            recv_window: RECV_WINDOW,
             ..Default::default()
         };
         let general = binance::futures::general::FuturesGeneral::new_with_config(
@@ -243,11 +758,63 @@ impl Clients {
             general,
             market,
             account,
+            clock: Arc::new(ClockDrift::default()),
+        }
+    }
+    pub fn sync_time(&self) -> Result<i64, MarketError> {
+        let offset = ClockDrift::measure(&self.general)?;
+        self.clock.update(offset);
+        Ok(offset)
+    }
+    /// 校正后的服务器时间（unix毫秒），用于自行构造的请求时间戳
+    pub fn timestamp(&self) -> u64 {
+        self.clock.timestamp()
+    }
+    /// 定期校时，返回的TimeSync停止或drop时线程退出
+    pub fn run_time_sync(&self, interval: Duration) -> TimeSync {
+        let (general, clock) = (self.general.clone(), self.clock.clone());
+        let running = Arc::new(AtomicBool::new(true));
+        let r = running.clone();
+        let handle = std::thread::spawn(move || {
+            while r.load(Relaxed) {
+                match ClockDrift::measure(&general) {
+                    Ok(offset) => clock.update(offset),
+                    Err(e) => warn!("Time sync failed: {:?}", e),
+                }
+                reconnect::wait(&r, interval);
+            }
+        });
+        TimeSync {
+            running,
+            handle: Some(handle),
         }
     }
 }
 opaque_debug::implement!(Clients);
 
+/// 校时线程的停止句柄
+#[derive(Debug)]
+pub struct TimeSync {
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TimeSync {
+    /// 停止校时并等待线程退出
+    pub fn stop(&mut self) {
+        self.running.store(false, Relaxed);
+        if let Some(h) = self.handle.take() {
+            h.join().ok();
+        }
+    }
+}
+
+impl Drop for TimeSync {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -264,10 +831,37 @@ mod tests {
             Ok(())
         };
         let subscribes = vec!["!markPrice@arr".to_string()];
-        let conn = FuturesWsConnection::MarketData(subscribes);
+        let conn = FuturesWsConnection::MarketData(subscribes.into());
         conn.run(handler, running.clone()).join().unwrap();
     }
     #[test]
+    fn stream_health() {
+        let health = Arc::new(StreamHealth::new(Duration::from_millis(100)));
+        let running = Arc::new(AtomicBool::new(true));
+        let alive = Arc::new(AtomicBool::new(true));
+        let (h, r, a) = (health.clone(), running.clone(), alive.clone());
+        let watchdog = std::thread::spawn(move || h.watch(&r, &a));
+        std::thread::sleep(Duration::from_millis(1500));
+        assert!(health.is_stale());
+        assert!(!alive.load(Relaxed));
+        let health_opt = Some(health.clone());
+        assert!(StreamHealth::stalled(&running, &alive, &health_opt));
+        assert!(alive.load(Relaxed));
+        health.touch();
+        assert!(!health.is_stale());
+        running.store(false, Relaxed);
+        watchdog.join().unwrap();
+        assert!(!StreamHealth::stalled(&running, &alive, &health_opt));
+    }
+    #[test]
+    fn clock_drift() {
+        let clock = ClockDrift::default();
+        clock.update(-(RECV_WINDOW as i64) / 2);
+        assert!(clock.check().is_ok());
+        clock.update(RECV_WINDOW as i64);
+        assert!(clock.check().is_err());
+    }
+    #[test]
     fn rest() {
         stdout_logger();
         let binance_keys = BinanceKeys::value_parse("./config/binance_keys.toml").unwrap();
@@ -323,7 +917,7 @@ mod tests {
             println!("Received: {:?}", event);
             Ok(())
         };
-        let conn = FuturesWsConnection::UserData(config);
+        let conn = FuturesWsConnection::UserData(config, None);
         conn.run(handler, running).join().unwrap();
         // Received: OrderTrade(OrderTradeEvent { event_type: "ORDER_TRADE_UPDATE", event_time: 1711310062035, transaction_time: 1711310062035, order: OrderUpdate { symbol: "SOLUSDT", new_client_order_id: "ios_mO5PYJzaUuK8SVCt4eQL", side: "BUY", order_type: "MARKET", time_in_force: "GTC", qty: "1", price: "0", average_price: "0", stop_price: "0", execution_type: "NEW", order_status: "NEW", order_id: 45348952648, qty_last_filled_trade: "0", accumulated_qty_filled_trades: "0", price_last_filled_trade: "0", asset_commisioned: None, commission: Some("0"), trade_order_time: 1711310062035, trade_id: 0, bids_notional: "0", ask_notional: "0", is_buyer_maker: false, is_reduce_only: false, stop_price_working_type: "CONTRACT_PRICE", original_order_type: "MARKET", position_side: "BOTH", close_all: Some(false), activation_price: None, callback_rate: None, pp_ignore: false, si_ignore: 0, ss_ignore: 0, realized_profit: "0" } })
         // Received: AccountUpdate(AccountUpdateEvent { event_type: "ACCOUNT_UPDATE", event_time: 1711310062035, data: AccountUpdateDataEvent { reason: "ORDER", balances: [EventBalance { asset: "USDT", wallet_balance: "1091.96321610", cross_wallet_balance: "1047.81330743", balance_change: "0" }], positions: [EventPosition { symbol: "SOLUSDT", position_amount: "1", entry_price: "176.614", accumulated_realized: "-1698.49199986", unrealized_pnl: "0.00359133", margin_type: "isolated", isolated_wallet: "44.14990867", position_side: "BOTH" }] } })
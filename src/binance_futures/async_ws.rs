@@ -0,0 +1,499 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
+    time::Duration,
+};
+
+use binance::futures::{userstream::FuturesUserStream, websockets::FuturesWebsocketEvent};
+use dashmap::DashMap;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio::{
+    net::TcpStream,
+    sync::{mpsc, watch},
+    task::{block_in_place, JoinHandle},
+    time::{interval, sleep, timeout, Instant, MissedTickBehavior},
+};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tracing::{error, info, warn};
+
+use super::{
+    depth_events, kline_events, price_events,
+    reconnect::{BackoffPolicy, Reconnect},
+    subscriptions::{Subscriptions, MARK_PRICE_ALL_STREAM},
+    user_stream::{from_value, parse_user_event, UserDataEvent, USER_STREAM_URL},
+    BinanceKeys, FuturesWsConnection, ListenKeyManager, StreamHealth, SymbolPrices,
+    PRICE_CHANNEL_CAPACITY,
+};
+use crate::{
+    algorithm::{DepthData, KlineData, SymbolPrice},
+    controller::AccountInfo,
+    error::WsError,
+    market::account_cache::AccountCache,
+    symbol::filter::SymbolFilter,
+    utils::{
+        coalesce::{coalescing_channel_async, ChannelStats, IDLE_FLUSH_INTERVAL},
+        unix_millis,
+    },
+};
+
+const MARKET_STREAM_URL: &str = "wss://fstream.binance.com/stream?streams=";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// 主动ping的间隔，服务器的pong也算作收到帧
+const PING_INTERVAL: Duration = Duration::from_secs(60);
+/// 没有StreamHealth时，超过该时间未收到任何帧视为断线
+const IDLE_TIMEOUT: Duration = Duration::from_secs(150);
+/// 检查shutdown以外的订阅变化、数据过期、listen key和ping的间隔
+const TICK: Duration = Duration::from_millis(200);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// 等待shutdown置为true，发送端drop也视为退出
+pub async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    shutdown.wait_for(|s| *s).await.ok();
+}
+
+/// 解析组合流的一条消息，只解析价格、深度和k线，不关心的事件为None
+pub fn parse_market_event(text: &str) -> Result<Option<FuturesWebsocketEvent>, String> {
+    let mut value: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    if let Some(data) = value.get_mut("data") {
+        value = data.take();
+    }
+    if value.is_array() {
+        return Ok(Some(FuturesWebsocketEvent::MarkPriceAll(from_value(
+            value,
+        )?)));
+    }
+    let Some(event_type) = value.get("e").and_then(|e| e.as_str()) else {
+        return Ok(None);
+    };
+    let event = match event_type {
+        "markPriceUpdate" => FuturesWebsocketEvent::MarkPrice(from_value(value)?),
+        "depthUpdate" => FuturesWebsocketEvent::DepthOrderBook(from_value(value)?),
+        "kline" => FuturesWebsocketEvent::Kline(from_value(value)?),
+        _ => return Ok(None),
+    };
+    Ok(Some(event))
+}
+
+/// 事件循环主动断开的原因
+enum Exit {
+    Shutdown,
+    /// 订阅变化、数据过期或listen key被删除
+    Reconnect,
+}
+
+/// 订阅为空时等待，直到有新的订阅或shutdown
+async fn wait_nonempty(
+    subscriptions: &Subscriptions,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Option<Vec<String>> {
+    loop {
+        let streams = subscriptions.streams();
+        if !streams.is_empty() {
+            return Some(streams);
+        }
+        tokio::select! {
+            _ = sleep(TICK) => {}
+            _ = stopped(shutdown) => return None,
+        }
+    }
+}
+
+/// 按退避等待，返回是否继续重连
+async fn backoff(delay: Option<Duration>, shutdown: &mut watch::Receiver<bool>) -> bool {
+    let Some(delay) = delay else {
+        return false;
+    };
+    tokio::select! {
+        _ = sleep(delay) => true,
+        _ = stopped(shutdown) => false,
+    }
+}
+
+/// 一次连接的事件循环，读取、ping和各项检查在同一个任务中完成
+async fn event_loop<F>(
+    socket: &mut Socket,
+    handler: &mut F,
+    conn: &FuturesWsConnection,
+    health: &Option<Arc<StreamHealth>>,
+    alive: &AtomicBool,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Result<Exit, WsError>
+where
+    F: FnMut(UserDataEvent) -> binance::errors::Result<()>,
+{
+    let disconnected =
+        |e: tokio_tungstenite::tungstenite::Error| WsError::Disconnected(e.to_string());
+    let mut ticker = interval(TICK);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut last_frame = Instant::now();
+    let mut last_ping = Instant::now();
+    let exit = loop {
+        tokio::select! {
+            _ = stopped(shutdown) => break Exit::Shutdown,
+            _ = ticker.tick() => {
+                if let FuturesWsConnection::MarketData(subscriptions) = conn {
+                    if subscriptions.take_changed() {
+                        info!("Resubscribing {} streams", subscriptions.streams().len());
+                        break Exit::Reconnect;
+                    }
+                }
+                if !alive.load(Relaxed) {
+                    warn!("Listen key lost, reconnecting...");
+                    break Exit::Reconnect;
+                }
+                match health {
+                    Some(h) if h.check() => {
+                        warn!("Stream stalled, reconnecting...");
+                        h.last_event.store(unix_millis(), Relaxed);
+                        break Exit::Reconnect;
+                    }
+                    None if last_frame.elapsed() > IDLE_TIMEOUT => {
+                        return Err(WsError::Disconnected(format!(
+                            "no frame for {:?}",
+                            last_frame.elapsed()
+                        )));
+                    }
+                    _ => {}
+                }
+                if last_ping.elapsed() >= PING_INTERVAL {
+                    socket
+                        .send(Message::Ping(Default::default()))
+                        .await
+                        .map_err(disconnected)?;
+                    last_ping = Instant::now();
+                }
+            }
+            msg = socket.next() => {
+                last_frame = Instant::now();
+                match msg {
+                    Some(Ok(Message::Text(text))) => match conn.parse(text.as_ref()) {
+                        Ok(Some(event)) => handler(event).map_err(WsError::from)?,
+                        Ok(None) => {}
+                        Err(e) => warn!("Message parse failed: {}, {}", e, text),
+                    },
+                    Some(Ok(Message::Ping(p))) => {
+                        socket.send(Message::Pong(p)).await.map_err(disconnected)?;
+                    }
+                    Some(Ok(Message::Close(_))) | None => {
+                        return Err(WsError::Disconnected("closed by server".to_string()));
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(disconnected(e)),
+                }
+            }
+        }
+    };
+    socket.close(None).await.ok();
+    Ok(exit)
+}
+
+impl FuturesWsConnection {
+    fn parse(&self, text: &str) -> Result<Option<UserDataEvent>, String> {
+        match self {
+            Self::MarketData(_) => {
+                Ok(parse_market_event(text)?.map(|e| UserDataEvent::Futures(Box::new(e))))
+            }
+            Self::UserData(..) => parse_user_event(text),
+        }
+    }
+    /// run_with_health的tokio版本：读取、keepalive ping、数据过期和订阅变化的检查在同一个任务中完成，
+    /// 不需要watchdog线程；shutdown置为true或发送端drop后断开并返回。
+    /// listen key和重连对账的REST请求为阻塞调用，需在多线程运行时中运行
+    pub async fn run_async<F, R>(
+        self,
+        mut handler: F,
+        shutdown: watch::Receiver<bool>,
+        health: Option<Arc<StreamHealth>>,
+        policy: BackoffPolicy,
+        on_reconnect: R,
+    ) where
+        F: FnMut(FuturesWebsocketEvent) -> binance::errors::Result<()> + Send,
+        R: FnMut(u64) + Send,
+    {
+        let margin_calls = match &self {
+            Self::UserData(_, margin_calls) => margin_calls.clone(),
+            Self::MarketData(_) => None,
+        };
+        let handler = move |e: UserDataEvent| match e {
+            UserDataEvent::Futures(e) => handler(*e),
+            UserDataEvent::MarginCall { time, positions } => {
+                if let Some(tx) = &margin_calls {
+                    tx.send(AccountInfo::MarginCall { time, positions }).ok();
+                }
+                Ok(())
+            }
+        };
+        self.run_events(handler, shutdown, health, policy, on_reconnect)
+            .await
+    }
+    async fn run_events<F, R>(
+        self,
+        mut handler: F,
+        mut shutdown: watch::Receiver<bool>,
+        health: Option<Arc<StreamHealth>>,
+        policy: BackoffPolicy,
+        mut on_reconnect: R,
+    ) where
+        F: FnMut(UserDataEvent) -> binance::errors::Result<()> + Send,
+        R: FnMut(u64) + Send,
+    {
+        let stats = health
+            .as_ref()
+            .map_or_else(Arc::default, |h| h.reconnects.clone());
+        let mut reconnect = Reconnect::new(policy, stats.clone());
+        // listen key被删除时续期线程将alive置为false
+        let alive = Arc::new(AtomicBool::new(true));
+        let mut listen_keys = None;
+        let last_event = AtomicU64::new(unix_millis());
+        let mut handler = |e: UserDataEvent| {
+            if let UserDataEvent::Futures(e) = &e {
+                if let FuturesWebsocketEvent::UserDataStreamExpiredEvent(_) = **e {
+                    error_chain::bail!("UserDataStreamExpiredEvent");
+                }
+            }
+            if let Some(h) = &health {
+                h.touch();
+            }
+            stats.clear_failures();
+            last_event.store(unix_millis(), Relaxed);
+            handler(e)
+        };
+        let mut connected = false;
+        loop {
+            let url = match &self {
+                Self::MarketData(subscriptions) => {
+                    subscriptions.take_changed();
+                    let Some(streams) = wait_nonempty(subscriptions, &mut shutdown).await else {
+                        break;
+                    };
+                    format!("{}{}", MARKET_STREAM_URL, streams.join("/"))
+                }
+                Self::UserData(keys, _) => {
+                    let listen_keys = listen_keys.get_or_insert_with(|| {
+                        ListenKeyManager::new(FuturesUserStream::new(
+                            Some(keys.api_key.clone()),
+                            Some(keys.secret_key.clone()),
+                        ))
+                        .with_disconnect(alive.clone())
+                    });
+                    match block_in_place(|| listen_keys.listen_key()) {
+                        Ok(k) => format!("{}{}", USER_STREAM_URL, k),
+                        Err(e) => {
+                            error!("Request for listen key failed: {}", e);
+                            if backoff(reconnect.retry(), &mut shutdown).await {
+                                continue;
+                            }
+                            break;
+                        }
+                    }
+                }
+            };
+            let socket = match timeout(CONNECT_TIMEOUT, connect_async(url)).await {
+                Ok(result) => result.map_err(|e| e.to_string()),
+                Err(_) => Err("timed out".to_string()),
+            };
+            let mut socket = match socket {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    error!("Init connection error: {}", e);
+                    if backoff(reconnect.retry(), &mut shutdown).await {
+                        continue;
+                    }
+                    break;
+                }
+            };
+            let connected_at = unix_millis();
+            if connected {
+                let gap = connected_at.saturating_sub(last_event.load(Relaxed));
+                warn!("Reconnected, {}ms since last event", gap);
+                block_in_place(|| on_reconnect(gap));
+            }
+            connected = true;
+            let result = event_loop(
+                &mut socket,
+                &mut handler,
+                &self,
+                &health,
+                &alive,
+                &mut shutdown,
+            )
+            .await;
+            match result {
+                Ok(Exit::Shutdown) => break,
+                Ok(Exit::Reconnect) => {
+                    if let Some(listen_keys) = &listen_keys {
+                        listen_keys.take_lost();
+                    }
+                    alive.store(true, Relaxed);
+                }
+                Err(e) if !e.is_retryable() => {
+                    error!("Event loop error, exiting...: {}", e);
+                    break;
+                }
+                Err(e) => {
+                    if let (WsError::ListenKeyExpired, Some(listen_keys)) = (&e, &listen_keys) {
+                        listen_keys.expire();
+                    }
+                    let uptime = Duration::from_millis(unix_millis().saturating_sub(connected_at));
+                    let received = last_event.load(Relaxed) >= connected_at;
+                    let delay = reconnect.disconnected(&e, uptime, received);
+                    if !backoff(delay, &mut shutdown).await {
+                        break;
+                    }
+                }
+            }
+        }
+        if let Some(mut listen_keys) = listen_keys {
+            block_in_place(|| listen_keys.close());
+        }
+    }
+    /// run_price_info_filtered的tokio版本，价格通道同样有界并按币种合并，需在运行时内调用
+    pub fn run_price_info_async(
+        filter: Arc<SymbolFilter>,
+        policy: BackoffPolicy,
+        shutdown: watch::Receiver<bool>,
+    ) -> (
+        mpsc::Receiver<SymbolPrice>,
+        SymbolPrices,
+        Arc<StreamHealth>,
+        Arc<ChannelStats>,
+        JoinHandle<()>,
+    ) {
+        let prices = Arc::new(DashMap::new());
+        let (price_tx, price_rx) =
+            coalescing_channel_async(PRICE_CHANNEL_CAPACITY, |p: &SymbolPrice| p.symbol);
+        let price_tx = price_tx.with_idle_flush_async(IDLE_FLUSH_INTERVAL);
+        let stats = price_tx.stats();
+        let handler = price_events(filter, prices.clone(), move |p| {
+            price_tx.send(p);
+        });
+        let conn = FuturesWsConnection::MarketData(vec![MARK_PRICE_ALL_STREAM.to_string()].into());
+        let health = Arc::new(StreamHealth::new(Duration::from_secs(10)));
+        let h = tokio::spawn(conn.run_async(
+            move |e| {
+                handler(e);
+                Ok(())
+            },
+            shutdown,
+            Some(health.clone()),
+            policy,
+            |_| {},
+        ));
+        (price_rx, prices, health, stats, h)
+    }
+    /// run_depth_subscriptions的tokio版本，需在运行时内调用
+    pub fn run_depth_subscriptions_async(
+        subscriptions: Subscriptions,
+        shutdown: watch::Receiver<bool>,
+    ) -> (mpsc::UnboundedReceiver<DepthData>, JoinHandle<()>) {
+        let (depth_tx, depth_rx) = mpsc::unbounded_channel();
+        let handler = depth_events(move |d| {
+            depth_tx.send(d).ok();
+        });
+        let conn = FuturesWsConnection::MarketData(subscriptions);
+        let h = tokio::spawn(conn.run_async(
+            move |e| {
+                handler(e);
+                Ok(())
+            },
+            shutdown,
+            None,
+            BackoffPolicy::default(),
+            |_| {},
+        ));
+        (depth_rx, h)
+    }
+    /// run_kline_subscriptions的tokio版本，只推送已收盘的k线，需在运行时内调用
+    pub fn run_kline_subscriptions_async(
+        subscriptions: Subscriptions,
+        shutdown: watch::Receiver<bool>,
+    ) -> (mpsc::UnboundedReceiver<KlineData>, JoinHandle<()>) {
+        let (kline_tx, kline_rx) = mpsc::unbounded_channel();
+        let handler = kline_events(move |k| {
+            kline_tx.send(k).ok();
+        });
+        let conn = FuturesWsConnection::MarketData(subscriptions);
+        let h = tokio::spawn(conn.run_async(
+            move |e| {
+                handler(e);
+                Ok(())
+            },
+            shutdown,
+            None,
+            BackoffPolicy::default(),
+            |_| {},
+        ));
+        (kline_rx, h)
+    }
+    /// run_account_info_with_cache的tokio版本，MARGIN_CALL同样转为AccountInfo::MarginCall，需在运行时内调用
+    pub fn run_account_info_async(
+        binance_keys: BinanceKeys,
+        cache: Option<Arc<AccountCache>>,
+        policy: BackoffPolicy,
+        shutdown: watch::Receiver<bool>,
+    ) -> (mpsc::UnboundedReceiver<AccountInfo>, JoinHandle<()>) {
+        let (account_tx, account_rx) = mpsc::unbounded_channel();
+        let reconcile_tx = account_tx.clone();
+        let on_reconnect = Self::reconciler(&binance_keys, cache.clone(), move |info| {
+            reconcile_tx.send(info).ok();
+        });
+        let handler = move |e: UserDataEvent| {
+            let info = match e {
+                UserDataEvent::Futures(e) => {
+                    let Some(info) = Self::account_info(*e) else {
+                        return Ok(());
+                    };
+                    if let Some(cache) = &cache {
+                        cache.apply(&info);
+                    }
+                    info
+                }
+                UserDataEvent::MarginCall { time, positions } => {
+                    AccountInfo::MarginCall { time, positions }
+                }
+            };
+            account_tx.send(info).ok();
+            Ok(())
+        };
+        let conn = FuturesWsConnection::UserData(binance_keys, None);
+        let h = tokio::spawn(conn.run_events(handler, shutdown, None, policy, on_reconnect));
+        (account_rx, h)
+    }
+}
+
+#[test]
+fn parse_market_event_test() {
+    let prices = r#"{"stream":"!markPrice@arr@1s","data":[{"e":"markPriceUpdate","E":1562305380000,
+        "s":"BTCUSDT","p":"11794.15000000","i":"11784.62659091","P":"11784.25641265",
+        "r":"0.00038167","T":1562306400000}]}"#;
+    let Some(FuturesWebsocketEvent::MarkPriceAll(v)) = parse_market_event(prices).unwrap() else {
+        panic!("mark prices not parsed");
+    };
+    assert_eq!(
+        (v[0].symbol.as_str(), v[0].mark_price.as_str()),
+        ("BTCUSDT", "11794.15000000")
+    );
+    let kline = r#"{"stream":"ethusdt@kline_1m","data":{"e":"kline","E":1638747660000,
+        "s":"ETHUSDT","k":{"t":1638747660000,"T":1638747719999,"s":"ETHUSDT","i":"1m",
+        "f":100,"L":200,"o":"4000.1","c":"4001.2","h":"4002.0","l":"3999.5","v":"10.5",
+        "n":100,"x":true,"q":"42000.0","V":"5.0","Q":"20000.0","B":"0"}}}"#;
+    let Some(FuturesWebsocketEvent::Kline(k)) = parse_market_event(kline).unwrap() else {
+        panic!("kline not parsed");
+    };
+    assert!(k.kline.is_final_bar);
+    assert_eq!(k.kline.close, "4001.2");
+    let depth = r#"{"stream":"ethusdt@depth5","data":{"e":"depthUpdate","E":1,"T":1,
+        "s":"ETHUSDT","U":1,"u":2,"pu":0,"b":[["4000.1","2.5"]],"a":[["4000.2","1.0"]]}}"#;
+    let Some(FuturesWebsocketEvent::DepthOrderBook(d)) = parse_market_event(depth).unwrap() else {
+        panic!("depth not parsed");
+    };
+    assert_eq!((d.bids[0].price, d.asks[0].qty), (4000.1, 1.));
+    assert!(parse_market_event(r#"{"result":null,"id":1}"#)
+        .unwrap()
+        .is_none());
+    assert!(parse_market_event(r#"{"data":{"e":"kline","E":1}}"#).is_err());
+}
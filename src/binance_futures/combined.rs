@@ -0,0 +1,321 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use binance::futures::websockets::FuturesWebsocketEvent;
+use crossbeam::channel::Receiver;
+use tracing::info;
+
+use super::{
+    depth_handler, kline_handler, price_handler,
+    reconnect::BackoffPolicy,
+    subscriptions::{Subscriptions, MARK_PRICE_ALL_STREAM},
+    FuturesWsConnection, StreamHealth, SymbolPrices,
+};
+use crate::{
+    algorithm::{DepthData, KlineData, SymbolPrice},
+    symbol::filter::SymbolFilter,
+    utils::coalesce::ChannelStats,
+};
+
+/// 币安单个连接最多订阅的流数
+pub const MAX_STREAMS_PER_CONNECTION: usize = 200;
+/// 连接超过该时间未收到事件时强制重连，同run_price_info
+pub const STREAM_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Handler = Arc<dyn Fn(FuturesWebsocketEvent) + Send + Sync>;
+
+/// 一路订阅及其事件的处理函数
+struct Route {
+    subscriptions: Subscriptions,
+    handler: Handler,
+    /// 该路订阅的数据是否过期，与所在的连接无关
+    health: Option<Arc<StreamHealth>>,
+}
+
+/// 一个连接及其分到的流，退订后为空时关闭
+struct Shard {
+    subscriptions: Subscriptions,
+    running: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Shard {
+    fn open(
+        streams: Vec<String>,
+        routes: &Arc<[Route]>,
+        policy: BackoffPolicy,
+        timeout: Duration,
+    ) -> Self {
+        let subscriptions = Subscriptions::new(streams);
+        let running = Arc::new(AtomicBool::new(true));
+        let routes = routes.clone();
+        let handle = FuturesWsConnection::MarketData(subscriptions.clone()).run_with_health(
+            move |e| {
+                dispatch(&routes, e);
+                Ok(())
+            },
+            running.clone(),
+            Some(Arc::new(StreamHealth::new(timeout))),
+            policy,
+            |_| {},
+        );
+        Self {
+            subscriptions,
+            running,
+            handle,
+        }
+    }
+    /// 通知连接退出，阻塞读取要等到下一帧才返回，由调用方稍后join
+    fn close(self) -> JoinHandle<()> {
+        self.running.store(false, Relaxed);
+        self.handle
+    }
+}
+
+/// 将多路行情订阅（标记价格、深度、k线等）合并到尽量少的连接上，每个连接不超过capacity个流，
+/// 收到的事件按所属的流分发给对应的订阅；每个连接有各自的watchdog，超时未收到事件时重连
+pub struct CombinedStreams {
+    routes: Vec<Route>,
+    capacity: usize,
+    policy: BackoffPolicy,
+    timeout: Duration,
+}
+
+impl Default for CombinedStreams {
+    fn default() -> Self {
+        Self::new(MAX_STREAMS_PER_CONNECTION)
+    }
+}
+
+impl CombinedStreams {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            routes: vec![],
+            capacity: capacity.max(1),
+            policy: BackoffPolicy::default(),
+            timeout: STREAM_TIMEOUT,
+        }
+    }
+    pub fn with_policy(mut self, policy: BackoffPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+    /// 连接的watchdog超时
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    /// 添加一路订阅，订阅列表可在运行时修改
+    pub fn route<F>(&mut self, subscriptions: Subscriptions, handler: F)
+    where
+        F: Fn(FuturesWebsocketEvent) + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            subscriptions,
+            handler: Arc::new(handler),
+            health: None,
+        });
+    }
+    /// 全市场标记价格，同FuturesWsConnection::run_price_info_filtered；
+    /// health按标记价格事件判断过期，供下单前检查
+    pub fn prices(
+        &mut self,
+        filter: Arc<SymbolFilter>,
+    ) -> (
+        Receiver<SymbolPrice>,
+        SymbolPrices,
+        Arc<StreamHealth>,
+        Arc<ChannelStats>,
+    ) {
+        let (handler, price_rx, prices, stats) = price_handler(filter);
+        let health = Arc::new(StreamHealth::new(self.timeout));
+        self.route(vec![MARK_PRICE_ALL_STREAM.to_string()].into(), handler);
+        if let Some(route) = self.routes.last_mut() {
+            route.health = Some(health.clone());
+        }
+        (price_rx, prices, health, stats)
+    }
+    pub fn depth(&mut self, subscriptions: Subscriptions) -> Receiver<DepthData> {
+        let (handler, depth_rx) = depth_handler();
+        self.route(subscriptions, handler);
+        depth_rx
+    }
+    /// 只推送已收盘的k线
+    pub fn klines(&mut self, subscriptions: Subscriptions) -> Receiver<KlineData> {
+        let (handler, kline_rx) = kline_handler();
+        self.route(subscriptions, handler);
+        kline_rx
+    }
+    /// 启动连接，订阅变化时重新分配到各连接，只有分配变化的连接会重连，退订后为空的连接关闭；
+    /// running置为false后退出
+    pub fn run(self, running: Arc<AtomicBool>) -> JoinHandle<()> {
+        let Self {
+            routes,
+            capacity,
+            policy,
+            timeout,
+        } = self;
+        let routes: Arc<[Route]> = routes.into();
+        for health in routes.iter().filter_map(|r| r.health.clone()) {
+            // 只标记过期，重连由所在连接的watchdog负责
+            let (running, alive) = (running.clone(), AtomicBool::new(true));
+            std::thread::spawn(move || health.watch(&running, &alive));
+        }
+        std::thread::spawn(move || {
+            let mut shards: Vec<Shard> = vec![];
+            let mut closed = vec![];
+            let mut last = vec![];
+            while running.load(Relaxed) {
+                let streams = union(&routes);
+                if streams != last {
+                    let current: Vec<Vec<String>> = shards
+                        .iter()
+                        .map(|s| s.subscriptions.streams())
+                        .collect();
+                    let mut old = std::mem::take(&mut shards).into_iter();
+                    for streams in assign(&current, &streams, capacity) {
+                        match old.next() {
+                            Some(shard) if streams.is_empty() => closed.push(shard.close()),
+                            Some(shard) => {
+                                shard.subscriptions.set(streams);
+                                shards.push(shard);
+                            }
+                            None => shards.push(Shard::open(streams, &routes, policy, timeout)),
+                        }
+                    }
+                    info!(
+                        "Combined streams: {} streams on {} connections",
+                        streams.len(),
+                        shards.len()
+                    );
+                    last = streams;
+                }
+                std::thread::sleep(Duration::from_millis(200));
+            }
+            for h in shards.into_iter().map(Shard::close).chain(closed) {
+                h.join().ok();
+            }
+        })
+    }
+}
+
+/// 所有订阅的并集，已排序
+fn union(routes: &[Route]) -> Vec<String> {
+    let mut streams: Vec<String> = routes
+        .iter()
+        .flat_map(|r| r.subscriptions.streams())
+        .collect();
+    streams.sort();
+    streams.dedup();
+    streams
+}
+
+/// 将streams分配到各连接：已有的分配保持不变以免无关连接重连，移除已退订的流，
+/// 新的流依次填入有空位的连接，都已满时新增连接；结果与shards一一对应，为空的连接由run关闭
+fn assign(shards: &[Vec<String>], streams: &[String], capacity: usize) -> Vec<Vec<String>> {
+    let mut shards: Vec<Vec<String>> = shards
+        .iter()
+        .map(|s| {
+            s.iter()
+                .filter(|x| streams.binary_search(x).is_ok())
+                .cloned()
+                .collect()
+        })
+        .collect();
+    for s in streams {
+        if shards.iter().any(|shard| shard.contains(s)) {
+            continue;
+        }
+        match shards.iter_mut().find(|shard| shard.len() < capacity) {
+            Some(shard) => shard.push(s.clone()),
+            None => shards.push(vec![s.clone()]),
+        }
+    }
+    shards
+}
+
+/// 事件所属流名称的前缀，与订阅的流名称比较
+fn stream_prefix(event: &FuturesWebsocketEvent) -> Option<String> {
+    Some(match event {
+        FuturesWebsocketEvent::MarkPriceAll(_) => "!markPrice@arr".to_string(),
+        FuturesWebsocketEvent::MarkPrice(e) => format!("{}@markPrice", e.symbol.to_lowercase()),
+        FuturesWebsocketEvent::DepthOrderBook(e) => format!("{}@depth", e.symbol.to_lowercase()),
+        FuturesWebsocketEvent::Kline(e) => {
+            format!("{}@kline_{}", e.symbol.to_lowercase(), e.kline.interval)
+        }
+        _ => return None,
+    })
+}
+
+/// 将事件交给订阅了其所属流的处理函数
+fn dispatch(routes: &[Route], event: FuturesWebsocketEvent) {
+    let Some(prefix) = stream_prefix(&event) else {
+        return;
+    };
+    let mut targets = routes
+        .iter()
+        .filter(|r| r.subscriptions.contains_prefix(&prefix))
+        .peekable();
+    while let Some(route) = targets.next() {
+        if let Some(health) = &route.health {
+            health.touch();
+        }
+        if targets.peek().is_some() {
+            (route.handler)(event.clone());
+        } else {
+            (route.handler)(event);
+            break;
+        }
+    }
+}
+
+#[test]
+fn combined_streams_test() {
+    use super::subscriptions::depth_stream;
+    use std::sync::atomic::AtomicUsize;
+
+    let s = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    // 新的流填入有空位的连接，已有分配不变
+    let shards = assign(&[], &s(&["a", "b", "c"]), 2);
+    assert_eq!(shards, [s(&["a", "b"]), s(&["c"])]);
+    let shards = assign(&shards, &s(&["b", "c", "d", "e", "f"]), 2);
+    assert_eq!(shards, [s(&["b", "d"]), s(&["c", "e"]), s(&["f"])]);
+    // 退订后为空的连接在结果中为空，由run关闭，之后只剩一个连接
+    let shards = assign(&shards, &s(&["f"]), 2);
+    assert_eq!(shards, [s(&[]), s(&[]), s(&["f"])]);
+    let shards: Vec<Vec<String>> = shards.into_iter().filter(|s| !s.is_empty()).collect();
+    assert_eq!(assign(&shards, &s(&["f", "g"]), 2), [s(&["f", "g"])]);
+
+    let mut streams = CombinedStreams::default();
+    let (prices, depths) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let p = prices.clone();
+    streams.route(vec![MARK_PRICE_ALL_STREAM.to_string()].into(), move |_| {
+        p.fetch_add(1, Relaxed);
+    });
+    let d = depths.clone();
+    streams.route(vec![depth_stream("BTCUSDT")].into(), move |_| {
+        d.fetch_add(1, Relaxed);
+    });
+    let _depth_rx = streams.depth(vec![depth_stream("BTCUSDT"), depth_stream("ETHUSDT")].into());
+    assert_eq!(
+        union(&streams.routes),
+        [
+            MARK_PRICE_ALL_STREAM,
+            "btcusdt@depth10@100ms",
+            "ethusdt@depth10@100ms"
+        ]
+    );
+    let (_price_rx, _, health, _) = streams.prices(Arc::default());
+    health.stale.store(true, Relaxed);
+    dispatch(&streams.routes, FuturesWebsocketEvent::MarkPriceAll(vec![]));
+    assert_eq!(prices.load(Relaxed), 1);
+    assert_eq!(depths.load(Relaxed), 0);
+    // 标记价格事件刷新该路订阅的健康状态
+    assert!(!health.is_stale());
+}
@@ -0,0 +1,101 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::Deserialize;
+
+use crate::error::{BinanceErrorCode, MarketError};
+
+use super::{income::ErrorResponse, BinanceKeys, ClockDrift};
+
+const COUNTDOWN_URL: &str = "https://fapi.binance.com/fapi/v1/countdownCancelAll";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CountdownResponse {
+    symbol: String,
+    countdown_time: String,
+}
+
+/// 解析/fapi/v1/countdownCancelAll的响应，返回生效的倒计时（毫秒）
+pub fn parse_countdown(body: &str) -> Result<(String, u64), MarketError> {
+    if let Ok(e) = serde_json::from_str::<ErrorResponse>(body) {
+        return Err(MarketError::Binance {
+            context: "countdown cancel all".to_string(),
+            code: BinanceErrorCode::from(e.code),
+            msg: e.msg,
+        });
+    }
+    let r: CountdownResponse = serde_json::from_str(body).map_err(|e| MarketError::Request {
+        context: "parse countdown".to_string(),
+        msg: e.to_string(),
+    })?;
+    let countdown = r.countdown_time.parse().map_err(|e| MarketError::Request {
+        context: "parse countdown".to_string(),
+        msg: format!("{}", e),
+    })?;
+    Ok((r.symbol, countdown))
+}
+
+/// 交易所端的倒计时撤单：倒计时结束前未再次刷新时，交易所撤销该币种的全部挂单
+#[derive(Clone)]
+pub struct CountdownClient {
+    keys: BinanceKeys,
+    http: reqwest::blocking::Client,
+    /// 请求时间戳按其校正
+    clock: Arc<ClockDrift>,
+}
+opaque_debug::implement!(CountdownClient);
+
+impl CountdownClient {
+    pub fn new(keys: BinanceKeys) -> Self {
+        Self {
+            keys,
+            http: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+            clock: Arc::default(),
+        }
+    }
+    /// 与下单共用校时结果，见Clients::clock
+    pub fn with_clock(mut self, clock: Arc<ClockDrift>) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// 设置symbol的倒计时，每次调用重新计时；countdown为0时取消倒计时
+    pub fn countdown(&self, symbol: &str, countdown: Duration) -> Result<(), MarketError> {
+        let query = format!(
+            "symbol={}&countdownTime={}&timestamp={}",
+            symbol,
+            countdown.as_millis(),
+            self.clock.timestamp()
+        );
+        let body = self
+            .http
+            .post(format!(
+                "{}?{}&signature={}",
+                COUNTDOWN_URL,
+                query,
+                self.keys.sign(&query)
+            ))
+            .header("X-MBX-APIKEY", &self.keys.api_key)
+            .send()
+            .and_then(|r| r.text())
+            .map_err(|e| MarketError::Request {
+                context: "countdown cancel all".to_string(),
+                msg: e.to_string(),
+            })?;
+        parse_countdown(&body).map(|_| ())
+    }
+}
+
+#[test]
+fn parse_countdown_test() {
+    assert_eq!(
+        parse_countdown(r#"{"symbol":"BTCUSDT","countdownTime":"100000"}"#).unwrap(),
+        ("BTCUSDT".to_string(), 100_000)
+    );
+    let e = parse_countdown(r#"{"code":-1102,"msg":"Mandatory parameter 'symbol' was not sent"}"#)
+        .unwrap_err();
+    assert!(matches!(e, MarketError::Binance { .. }));
+    assert!(parse_countdown("<html>").is_err());
+}
@@ -0,0 +1,252 @@
+use std::{fmt::Display, sync::Arc, time::Duration};
+
+use serde::Deserialize;
+
+use crate::error::{BinanceErrorCode, MarketError};
+
+use super::{BinanceKeys, ClockDrift};
+
+const INCOME_URL: &str = "https://fapi.binance.com/fapi/v1/income";
+/// 单次请求的最大条数
+const PAGE_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncomeType {
+    RealizedPnl,
+    Commission,
+    FundingFee,
+    /// 转账、返佣等其他类型
+    Other,
+}
+
+impl IncomeType {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "REALIZED_PNL" => Self::RealizedPnl,
+            "COMMISSION" => Self::Commission,
+            "FUNDING_FEE" => Self::FundingFee,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// /fapi/v1/income返回的一条资金流水
+#[derive(Debug, Clone, PartialEq)]
+pub struct Income {
+    pub symbol: String,
+    /// 原始类型名，如REALIZED_PNL
+    pub income_type: String,
+    /// 收入为正
+    pub income: f64,
+    pub asset: String,
+    pub time: u64,
+    pub tran_id: u64,
+}
+
+impl Income {
+    pub fn kind(&self) -> IncomeType {
+        IncomeType::parse(&self.income_type)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawIncome {
+    symbol: String,
+    income_type: String,
+    income: String,
+    asset: String,
+    time: u64,
+    tran_id: u64,
+}
+
+#[derive(Deserialize)]
+pub(super) struct ErrorResponse {
+    pub code: i16,
+    pub msg: String,
+}
+
+/// 解析/fapi/v1/income的响应
+pub fn parse_incomes(body: &str) -> Result<Vec<Income>, MarketError> {
+    if let Ok(e) = serde_json::from_str::<ErrorResponse>(body) {
+        return Err(MarketError::Binance {
+            context: "get income".to_string(),
+            code: BinanceErrorCode::from(e.code),
+            msg: e.msg,
+        });
+    }
+    let raw: Vec<RawIncome> = serde_json::from_str(body).map_err(|e| MarketError::Request {
+        context: "parse income".to_string(),
+        msg: e.to_string(),
+    })?;
+    raw.into_iter()
+        .map(|r| {
+            Ok(Income {
+                income: r.income.parse().map_err(|e| MarketError::Request {
+                    context: format!("parse income {}", r.tran_id),
+                    msg: format!("{}", e),
+                })?,
+                symbol: r.symbol,
+                income_type: r.income_type,
+                asset: r.asset,
+                time: r.time,
+                tran_id: r.tran_id,
+            })
+        })
+        .collect()
+}
+
+/// 按类型汇总的USDT流水
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IncomeTotals {
+    pub realized_pnl: f64,
+    pub commission: f64,
+    pub funding_fee: f64,
+}
+
+impl IncomeTotals {
+    pub fn add(&mut self, kind: IncomeType, income: f64) {
+        match kind {
+            IncomeType::RealizedPnl => self.realized_pnl += income,
+            IncomeType::Commission => self.commission += income,
+            IncomeType::FundingFee => self.funding_fee += income,
+            IncomeType::Other => {}
+        }
+    }
+}
+
+/// 交易所记录的已实现盈亏与程序统计的偏差
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PnlDivergence {
+    pub start: u64,
+    pub end: u64,
+    pub exchange: f64,
+    pub internal: f64,
+}
+
+impl PnlDivergence {
+    /// 偏差超过threshold时返回Some
+    pub fn check(
+        start: u64,
+        end: u64,
+        exchange: f64,
+        internal: f64,
+        threshold: f64,
+    ) -> Option<Self> {
+        ((exchange - internal).abs() > threshold).then_some(Self {
+            start,
+            end,
+            exchange,
+            internal,
+        })
+    }
+}
+
+impl Display for PnlDivergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "realized pnl {} - {}: exchange {:.4}, internal {:.4}, diff {:.4}",
+            self.start,
+            self.end,
+            self.exchange,
+            self.internal,
+            self.exchange - self.internal
+        )
+    }
+}
+
+/// 通过REST导入资金流水
+#[derive(Clone)]
+pub struct IncomeClient {
+    keys: BinanceKeys,
+    http: reqwest::blocking::Client,
+    /// 请求时间戳按其校正
+    clock: Arc<ClockDrift>,
+}
+opaque_debug::implement!(IncomeClient);
+
+impl IncomeClient {
+    pub fn new(keys: BinanceKeys) -> Self {
+        Self {
+            keys,
+            http: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+            clock: Arc::default(),
+        }
+    }
+    /// 与下单共用校时结果，见Clients::clock
+    pub fn with_clock(mut self, clock: Arc<ClockDrift>) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// 获取[start, end]内的全部流水，按时间分页；同一毫秒的记录可能重复返回，由存储按tran_id去重
+    pub fn fetch(&self, start: u64, end: u64) -> Result<Vec<Income>, MarketError> {
+        let mut incomes = vec![];
+        let mut from = start;
+        loop {
+            let page = self.fetch_page(from, end)?;
+            let full = page.len() >= PAGE_LIMIT;
+            let last = page.last().map(|i| i.time);
+            incomes.extend(page);
+            match last {
+                Some(last) if full && last > from => from = last,
+                _ => break,
+            }
+        }
+        Ok(incomes)
+    }
+    fn fetch_page(&self, start: u64, end: u64) -> Result<Vec<Income>, MarketError> {
+        let query = format!(
+            "startTime={}&endTime={}&limit={}&timestamp={}",
+            start,
+            end,
+            PAGE_LIMIT,
+            self.clock.timestamp()
+        );
+        let url = format!(
+            "{}?{}&signature={}",
+            INCOME_URL,
+            query,
+            self.keys.sign(&query)
+        );
+        let body = self
+            .http
+            .get(url)
+            .header("X-MBX-APIKEY", &self.keys.api_key)
+            .send()
+            .and_then(|r| r.text())
+            .map_err(|e| MarketError::Request {
+                context: "get income".to_string(),
+                msg: e.to_string(),
+            })?;
+        parse_incomes(&body)
+    }
+}
+
+#[test]
+fn parse_incomes_test() {
+    let body = r#"[
+        {"symbol":"BTCUSDT","incomeType":"REALIZED_PNL","income":"10.5","asset":"USDT","info":"","time":1000,"tranId":1,"tradeId":"7"},
+        {"symbol":"BTCUSDT","incomeType":"COMMISSION","income":"-0.2","asset":"USDT","info":"","time":1000,"tranId":2,"tradeId":"7"},
+        {"symbol":"","incomeType":"TRANSFER","income":"100","asset":"USDT","info":"","time":2000,"tranId":3,"tradeId":""}
+    ]"#;
+    let incomes = parse_incomes(body).unwrap();
+    assert_eq!(incomes.len(), 3);
+    assert_eq!(incomes[0].kind(), IncomeType::RealizedPnl);
+    assert_eq!(incomes[1].income, -0.2);
+    assert_eq!(incomes[2].kind(), IncomeType::Other);
+    let mut totals = IncomeTotals::default();
+    for i in incomes.iter() {
+        totals.add(i.kind(), i.income);
+    }
+    assert_eq!(totals.realized_pnl, 10.5);
+    assert_eq!(totals.commission, -0.2);
+    let e = parse_incomes(r#"{"code":-1021,"msg":"Timestamp outside recvWindow"}"#).unwrap_err();
+    assert!(matches!(e, MarketError::Binance { .. }));
+    assert!(PnlDivergence::check(0, 1, 10.5, 10.4, 0.5).is_none());
+    let d = PnlDivergence::check(0, 1, 10.5, 9., 0.5).unwrap();
+    assert!(d.to_string().contains("diff 1.5"));
+}
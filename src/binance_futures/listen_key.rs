@@ -0,0 +1,260 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use binance::futures::userstream::FuturesUserStream;
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+use super::reconnect;
+use crate::error::{BinanceErrorCode, BinanceResultExt, MarketError};
+
+/// listen key的续期间隔，币安60分钟未续期即过期
+pub const LISTEN_KEY_RENEW_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// user data stream的listen key接口
+pub trait UserStreamApi: Send + Sync + 'static {
+    fn start(&self) -> Result<String, MarketError>;
+    fn keep_alive(&self, listen_key: &str) -> Result<(), MarketError>;
+    fn close(&self, listen_key: &str) -> Result<(), MarketError>;
+}
+
+impl UserStreamApi for FuturesUserStream {
+    fn start(&self) -> Result<String, MarketError> {
+        FuturesUserStream::start(self)
+            .map(|u| u.listen_key)
+            .market_context("start user stream")
+    }
+    fn keep_alive(&self, listen_key: &str) -> Result<(), MarketError> {
+        FuturesUserStream::keep_alive(self, listen_key)
+            .map(|_| ())
+            .market_context("keep alive listen key")
+    }
+    fn close(&self, listen_key: &str) -> Result<(), MarketError> {
+        FuturesUserStream::close(self, listen_key)
+            .map(|_| ())
+            .market_context("close listen key")
+    }
+}
+
+/// 续期遇到临时错误时的重试次数和间隔，key不存在（-1125）时不重试
+const KEEP_ALIVE_RETRIES: u32 = 3;
+const KEEP_ALIVE_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// 续期线程与使用方共享的状态
+struct Shared<U> {
+    api: U,
+    key: Mutex<Option<String>>,
+    /// key被服务端删除后置为true，连接需用新的key重连
+    lost: AtomicBool,
+}
+
+impl<U: UserStreamApi> Shared<U> {
+    /// 续期当前的key，请求期间不持有锁；临时错误最多重试KEEP_ALIVE_RETRIES次，仍失败时保留key
+    /// 等下次续期，key不存在时丢弃并将disconnect置为false通知连接重连
+    fn renew(&self, running: &AtomicBool, retry_delay: Duration, disconnect: Option<&AtomicBool>) {
+        let Some(k) = self.key.lock().clone() else {
+            return;
+        };
+        for attempt in 1..=KEEP_ALIVE_RETRIES {
+            match self.api.keep_alive(&k) {
+                Ok(()) => {
+                    info!("Listen key {} extended", k);
+                    return;
+                }
+                Err(e) if e.code() == Some(BinanceErrorCode::ListenKeyNotExist) => {
+                    let mut key = self.key.lock();
+                    // 续期期间key可能已被替换
+                    if key.as_deref() == Some(k.as_str()) {
+                        warn!("Listen key {} dropped: {}", k, e);
+                        *key = None;
+                        self.lost.store(true, Relaxed);
+                        if let Some(d) = disconnect {
+                            d.store(false, Relaxed);
+                        }
+                    }
+                    return;
+                }
+                Err(e) => {
+                    warn!(
+                        "Keep alive listen key {} failed ({}/{}): {}",
+                        k, attempt, KEEP_ALIVE_RETRIES, e
+                    );
+                    if attempt == KEEP_ALIVE_RETRIES || !reconnect::wait(running, retry_delay) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 管理listen key的生命周期：按需申请，由唯一的续期线程定期续期，
+/// key不存在或过期后下次使用时重新申请，关闭或drop时停止续期并关闭key
+pub struct ListenKeyManager<U: UserStreamApi> {
+    shared: Arc<Shared<U>>,
+    renew_interval: Duration,
+    retry_delay: Duration,
+    /// 连接事件循环的运行标志，key被删除时置为false
+    disconnect: Option<Arc<AtomicBool>>,
+    running: Arc<AtomicBool>,
+    renewer: Option<JoinHandle<()>>,
+}
+
+impl<U: UserStreamApi> ListenKeyManager<U> {
+    pub fn new(api: U) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                api,
+                key: Mutex::new(None),
+                lost: AtomicBool::new(false),
+            }),
+            renew_interval: LISTEN_KEY_RENEW_INTERVAL,
+            retry_delay: KEEP_ALIVE_RETRY_DELAY,
+            disconnect: None,
+            running: Arc::new(AtomicBool::new(true)),
+            renewer: None,
+        }
+    }
+    pub fn with_renew_interval(mut self, interval: Duration) -> Self {
+        self.renew_interval = interval;
+        self
+    }
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+    /// key被删除时将alive置为false，使使用该key的事件循环退出
+    pub fn with_disconnect(mut self, alive: Arc<AtomicBool>) -> Self {
+        self.disconnect = Some(alive);
+        self
+    }
+    /// 当前的listen key，没有时申请新的；首次调用时启动续期线程
+    pub fn listen_key(&mut self) -> Result<String, MarketError> {
+        if let Some(k) = self.shared.key.lock().as_ref() {
+            return Ok(k.clone());
+        }
+        let k = self.shared.api.start()?;
+        info!("Listen key {} created", k);
+        *self.shared.key.lock() = Some(k.clone());
+        if self.renewer.is_none() {
+            let (shared, running) = (self.shared.clone(), self.running.clone());
+            let (interval, retry_delay) = (self.renew_interval, self.retry_delay);
+            let disconnect = self.disconnect.clone();
+            self.renewer = Some(std::thread::spawn(move || {
+                while reconnect::wait(&running, interval) {
+                    shared.renew(&running, retry_delay, disconnect.as_deref());
+                }
+            }));
+        }
+        Ok(k)
+    }
+    /// 续期时发现key已被删除，连接需用新的key重连；读取后清除标记
+    pub fn take_lost(&self) -> bool {
+        self.shared.lost.swap(false, Relaxed)
+    }
+    /// 收到过期事件后调用，下次使用时重新申请
+    pub fn expire(&self) {
+        if let Some(k) = self.shared.key.lock().take() {
+            warn!("Listen key {} expired", k);
+        }
+    }
+    /// 停止续期线程并关闭当前的key
+    pub fn close(&mut self) {
+        self.running.store(false, Relaxed);
+        if let Some(h) = self.renewer.take() {
+            h.join().ok();
+        }
+        let key = self.shared.key.lock().take();
+        if let Some(k) = key {
+            match self.shared.api.close(&k) {
+                Ok(()) => info!("Listen key {} closed", k),
+                Err(e) => warn!("Close listen key {} failed: {}", k, e),
+            }
+        }
+    }
+}
+
+impl<U: UserStreamApi> Drop for ListenKeyManager<U> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[test]
+fn listen_key_manager_test() {
+    use std::{collections::VecDeque, sync::atomic::AtomicUsize};
+
+    /// keep_alive按顺序返回预设的结果，用完后成功
+    #[derive(Default)]
+    struct MockUserStream {
+        created: AtomicUsize,
+        extended: AtomicUsize,
+        keep_alive: Mutex<VecDeque<Option<i16>>>,
+        closed: Mutex<Vec<String>>,
+    }
+    impl UserStreamApi for Arc<MockUserStream> {
+        fn start(&self) -> Result<String, MarketError> {
+            Ok(format!("key{}", self.created.fetch_add(1, Relaxed) + 1))
+        }
+        fn keep_alive(&self, _: &str) -> Result<(), MarketError> {
+            if let Some(Some(code)) = self.keep_alive.lock().pop_front() {
+                return Err(MarketError::Binance {
+                    context: "keep alive listen key".to_string(),
+                    code: code.into(),
+                    msg: String::new(),
+                });
+            }
+            self.extended.fetch_add(1, Relaxed);
+            Ok(())
+        }
+        fn close(&self, listen_key: &str) -> Result<(), MarketError> {
+            self.closed.lock().push(listen_key.to_string());
+            Ok(())
+        }
+    }
+
+    let mock = Arc::new(MockUserStream::default());
+    let alive = Arc::new(AtomicBool::new(true));
+    // 续期由测试直接调用，线程的续期间隔足够长不会触发
+    let mut manager = ListenKeyManager::new(mock.clone())
+        .with_renew_interval(Duration::from_secs(3600))
+        .with_retry_delay(Duration::ZERO)
+        .with_disconnect(alive.clone());
+    let running = AtomicBool::new(true);
+    let renew = |m: &ListenKeyManager<Arc<MockUserStream>>| {
+        m.shared
+            .renew(&running, m.retry_delay, m.disconnect.as_deref())
+    };
+    assert_eq!(manager.listen_key().unwrap(), "key1");
+    assert_eq!(manager.listen_key().unwrap(), "key1");
+    renew(&manager);
+    assert_eq!(mock.extended.load(Relaxed), 1);
+    // 临时错误重试后成功，保留key
+    mock.keep_alive.lock().extend([Some(-1001), Some(-1000)]);
+    renew(&manager);
+    assert_eq!(mock.extended.load(Relaxed), 2);
+    // 重试用完仍失败也保留key，等下次续期
+    mock.keep_alive.lock().extend([Some(-1001); 3]);
+    renew(&manager);
+    assert_eq!(manager.listen_key().unwrap(), "key1");
+    assert!(!manager.take_lost() && alive.load(Relaxed));
+    // key不存在时丢弃并通知重连
+    mock.keep_alive.lock().push_back(Some(-1125));
+    renew(&manager);
+    assert!(!alive.load(Relaxed));
+    assert!(manager.take_lost());
+    assert!(!manager.take_lost());
+    assert_eq!(manager.listen_key().unwrap(), "key2");
+    manager.expire();
+    assert_eq!(manager.listen_key().unwrap(), "key3");
+    drop(manager);
+    assert_eq!(*mock.closed.lock(), ["key3"]);
+    assert_eq!(mock.created.load(Relaxed), 3);
+    assert_eq!(mock.extended.load(Relaxed), 2);
+}
@@ -0,0 +1,316 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tracing::warn;
+
+use super::income::ErrorResponse;
+use crate::{
+    backtest::positioning::{PositioningData, PositioningSeries},
+    error::{BinanceErrorCode, DataError, MarketError},
+};
+
+const OPEN_INTEREST_URL: &str = "https://fapi.binance.com/futures/data/openInterestHist";
+const LONG_SHORT_URL: &str = "https://fapi.binance.com/futures/data/topLongShortPositionRatio";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawOpenInterest {
+    sum_open_interest: String,
+    sum_open_interest_value: String,
+    timestamp: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawLongShort {
+    long_short_ratio: String,
+    timestamp: u64,
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(body: &str, context: &str) -> Result<T, MarketError> {
+    if let Ok(e) = serde_json::from_str::<ErrorResponse>(body) {
+        return Err(MarketError::Binance {
+            context: context.to_string(),
+            code: BinanceErrorCode::from(e.code),
+            msg: e.msg,
+        });
+    }
+    serde_json::from_str(body).map_err(|e| MarketError::Request {
+        context: format!("parse {}", context),
+        msg: e.to_string(),
+    })
+}
+
+fn parse_number(s: &str, context: &str) -> Result<f64, MarketError> {
+    s.parse().map_err(|e| MarketError::Request {
+        context: format!("parse {}", context),
+        msg: format!("{}", e),
+    })
+}
+
+/// 解析持仓量和大户多空比的响应，按时间合并；多空比缺失的时间点为None
+pub fn parse_positioning(
+    open_interest: &str,
+    long_short: &str,
+) -> Result<Vec<PositioningData>, MarketError> {
+    let ratios: HashMap<u64, f64> =
+        parse_body::<Vec<RawLongShort>>(long_short, "long short ratio")?
+            .into_iter()
+            .map(|r| {
+                Ok((
+                    r.timestamp,
+                    parse_number(&r.long_short_ratio, "long short ratio")?,
+                ))
+            })
+            .collect::<Result<_, MarketError>>()?;
+    let mut data = parse_body::<Vec<RawOpenInterest>>(open_interest, "open interest")?
+        .into_iter()
+        .map(|r| {
+            Ok(PositioningData {
+                time: OffsetDateTime::from_unix_timestamp_nanos(r.timestamp as i128 * 1_000_000)
+                    .map_err(|e| MarketError::Request {
+                        context: "parse open interest time".to_string(),
+                        msg: e.to_string(),
+                    })?,
+                open_interest: parse_number(&r.sum_open_interest, "open interest")?,
+                open_interest_value: parse_number(&r.sum_open_interest_value, "open interest")?,
+                long_short_ratio: ratios.get(&r.timestamp).copied(),
+            })
+        })
+        .collect::<Result<Vec<_>, MarketError>>()?;
+    data.sort_by_key(|d| d.time);
+    Ok(data)
+}
+
+/// 通过公开REST接口获取持仓量和大户持仓多空比
+#[derive(Clone)]
+pub struct PositioningClient {
+    http: reqwest::blocking::Client,
+    /// 统计周期，如"5m"、"1h"
+    period: String,
+}
+opaque_debug::implement!(PositioningClient);
+
+impl PositioningClient {
+    pub fn new(period: &str) -> Self {
+        Self {
+            http: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+            period: period.to_string(),
+        }
+    }
+    fn get(
+        &self,
+        url: &str,
+        symbol: &str,
+        start: Option<OffsetDateTime>,
+        limit: usize,
+        context: &str,
+    ) -> Result<String, MarketError> {
+        let mut url = format!(
+            "{}?symbol={}&period={}&limit={}",
+            url, symbol, self.period, limit
+        );
+        if let Some(start) = start {
+            url += &format!("&startTime={}", start.unix_timestamp_nanos() / 1_000_000);
+        }
+        self.http
+            .get(url)
+            .send()
+            .and_then(|r| r.text())
+            .map_err(|e| MarketError::Request {
+                context: context.to_string(),
+                msg: e.to_string(),
+            })
+    }
+    /// 最近limit个周期的数据（交易所最多保留30天）
+    pub fn fetch(&self, symbol: &str, limit: usize) -> Result<Vec<PositioningData>, MarketError> {
+        self.fetch_since(symbol, None, limit)
+    }
+    /// 从start（含）开始的最多limit个周期，start为None时为最近的limit个
+    pub fn fetch_since(
+        &self,
+        symbol: &str,
+        start: Option<OffsetDateTime>,
+        limit: usize,
+    ) -> Result<Vec<PositioningData>, MarketError> {
+        let open_interest =
+            self.get(OPEN_INTEREST_URL, symbol, start, limit, "get open interest")?;
+        let long_short = self.get(LONG_SHORT_URL, symbol, start, limit, "get long short ratio")?;
+        parse_positioning(&open_interest, &long_short)
+    }
+}
+
+/// 各币种最近的持仓数据，供算法和策略共享读取
+#[derive(Debug, Default)]
+pub struct PositioningFeed {
+    /// 每个币种保留的条数
+    capacity: usize,
+    series: DashMap<String, VecDeque<PositioningData>>,
+}
+
+impl PositioningFeed {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            series: DashMap::new(),
+        }
+    }
+    /// 按时间追加；时间相同时更新已有的一条（如补上之后才发布的多空比），
+    /// 早于已保留的最早一条的数据忽略
+    pub fn push(&self, symbol: &str, data: impl IntoIterator<Item = PositioningData>) {
+        let mut series = self.series.entry(symbol.to_string()).or_default();
+        for d in data {
+            let i = series.partition_point(|s| s.time < d.time);
+            match series.get_mut(i) {
+                Some(existing) if existing.time == d.time => {
+                    if d.long_short_ratio.is_some() || existing.long_short_ratio.is_none() {
+                        *existing = d;
+                    } else {
+                        existing.open_interest = d.open_interest;
+                        existing.open_interest_value = d.open_interest_value;
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    series.push_back(d);
+                    if series.len() > self.capacity {
+                        series.pop_front();
+                    }
+                }
+            }
+        }
+    }
+    pub fn latest(&self, symbol: &str) -> Option<PositioningData> {
+        self.series.get(symbol)?.back().cloned()
+    }
+    /// time时已知的持仓量相对time - window时的变化比例，见PositioningSeries::open_interest_change
+    pub fn open_interest_change(
+        &self,
+        symbol: &str,
+        time: OffsetDateTime,
+        window: time::Duration,
+    ) -> Option<f64> {
+        let series = self.series.get(symbol)?;
+        let at = |t: OffsetDateTime| {
+            let i = series.partition_point(|d| d.time <= t);
+            i.checked_sub(1).map(|i| series[i].open_interest)
+        };
+        let (now, before) = (at(time)?, at(time - window)?);
+        (before > 0.).then(|| now / before - 1.)
+    }
+    /// 与path中已记录的数据合并后写回，供回测使用
+    pub fn record(&self, symbol: &str, path: &str) -> Result<(), DataError> {
+        let mut series = match std::path::Path::new(path).exists() {
+            true => PositioningSeries::read_from_csv(path)?,
+            false => PositioningSeries::default(),
+        };
+        series.symbol = symbol.to_string();
+        series.merge(self.series(symbol));
+        series.write_to_csv(path)
+    }
+    /// 转为回测使用的序列，可写入csv
+    pub fn series(&self, symbol: &str) -> PositioningSeries {
+        PositioningSeries {
+            symbol: symbol.to_string(),
+            data: self
+                .series
+                .get(symbol)
+                .map(|s| s.iter().cloned().collect())
+                .unwrap_or_default(),
+        }
+    }
+    /// 每隔interval轮询symbols：首次拉取capacity条历史，之后从已有的最新一条开始补齐，
+    /// 断线期间缺失的周期和之后才发布的多空比都会补上；record_dir不为空时每轮写入{symbol}.csv
+    pub fn run_poller(
+        self: &Arc<Self>,
+        client: PositioningClient,
+        symbols: Vec<String>,
+        interval: Duration,
+        record_dir: Option<String>,
+        running: Arc<AtomicBool>,
+    ) -> JoinHandle<()> {
+        let feed = self.clone();
+        std::thread::spawn(move || {
+            while running.load(Relaxed) {
+                for symbol in symbols.iter() {
+                    let start = feed.latest(symbol).map(|d| d.time);
+                    match client.fetch_since(symbol, start, feed.capacity) {
+                        Ok(data) => feed.push(symbol, data),
+                        Err(e) => warn!("Poll positioning of {} failed: {}", symbol, e),
+                    }
+                    if let Some(dir) = &record_dir {
+                        let path = format!("{}/{}.csv", dir, symbol);
+                        if let Err(e) = feed.record(symbol, &path) {
+                            warn!("Record positioning of {} failed: {}", symbol, e);
+                        }
+                    }
+                }
+                std::thread::sleep(interval);
+            }
+        })
+    }
+}
+
+#[test]
+fn positioning_feed_test() {
+    let open_interest = r#"[
+        {"symbol":"BTCUSDT","sumOpenInterest":"1000.5","sumOpenInterestValue":"60000000","timestamp":1700000000000},
+        {"symbol":"BTCUSDT","sumOpenInterest":"1010","sumOpenInterestValue":"60600000","timestamp":1700000300000}
+    ]"#;
+    let long_short = r#"[
+        {"symbol":"BTCUSDT","longShortRatio":"1.25","longAccount":"0.5556","shortAccount":"0.4444","timestamp":1700000300000}
+    ]"#;
+    let data = parse_positioning(open_interest, long_short).unwrap();
+    assert_eq!(data.len(), 2);
+    assert_eq!(data[0].open_interest, 1000.5);
+    assert_eq!(data[0].long_short_ratio, None);
+    assert_eq!(data[1].long_short_ratio, Some(1.25));
+    assert!(matches!(
+        parse_positioning(r#"{"code":-1121,"msg":"Invalid symbol."}"#, "[]"),
+        Err(MarketError::Binance { .. })
+    ));
+
+    let feed = PositioningFeed::new(2);
+    feed.push("BTCUSDT", data.clone());
+    feed.push("BTCUSDT", data[1..].to_vec());
+    assert_eq!(feed.series("BTCUSDT").data, data);
+    // 之后才发布的多空比补到已有的一条
+    let mut late = data[0].clone();
+    late.long_short_ratio = Some(0.8);
+    feed.push("BTCUSDT", [late.clone()]);
+    assert_eq!(feed.series("BTCUSDT").data[0], late);
+    feed.push("BTCUSDT", data[..1].to_vec());
+    assert_eq!(feed.series("BTCUSDT").data[0].long_short_ratio, Some(0.8));
+    let change = feed
+        .open_interest_change("BTCUSDT", data[1].time, time::Duration::minutes(5))
+        .unwrap();
+    assert!((change - (1010. / 1000.5 - 1.)).abs() < 1e-9);
+    // 记录与已写入的数据合并，容量之外的旧数据保留在文件中
+    let path = std::env::temp_dir().join("hurribot_positioning_record_test.csv");
+    let path = path.to_str().unwrap();
+    std::fs::remove_file(path).ok();
+    feed.record("BTCUSDT", path).unwrap();
+    let mut next = data[1].clone();
+    next.time += time::Duration::minutes(5);
+    feed.push("BTCUSDT", [next.clone()]);
+    assert_eq!(feed.latest("BTCUSDT"), Some(next));
+    assert_eq!(feed.series("BTCUSDT").data.len(), 2);
+    assert!(feed.latest("ETHUSDT").is_none());
+    feed.record("BTCUSDT", path).unwrap();
+    let recorded = PositioningSeries::read_from_csv(path).unwrap();
+    assert_eq!(recorded.data.len(), 3);
+    assert_eq!(recorded.data[0], late);
+}
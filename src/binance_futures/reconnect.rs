@@ -0,0 +1,211 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Deserializer};
+use tracing::{error, info, warn};
+
+use crate::error::WsError;
+
+/// 币安每个连接最多保持24小时，连接时长超过此值后的断线视为服务器定期断开
+const FORCED_DISCONNECT_UPTIME: Duration = Duration::from_secs(23 * 3600);
+
+/// 重连的指数退避：第n次连续重连前等待initial·2^n（不超过max）乘以[0.5, 1)的随机抖动，
+/// 自上次连接成功起连续失败max_retries次后放弃，0为一直重试；
+/// 放弃后连接线程退出，调用方需在join返回后告警并停止交易
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BackoffPolicy {
+    /// 配置中以秒为单位
+    #[serde(deserialize_with = "secs")]
+    pub initial: Duration,
+    #[serde(deserialize_with = "secs")]
+    pub max: Duration,
+    pub max_retries: u32,
+}
+
+fn secs<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+    let secs = f64::deserialize(d)?;
+    Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            max_retries: 0,
+        }
+    }
+}
+
+impl BackoffPolicy {
+    /// 第attempt次连续重连前的等待时间，jitter取[0, 1)
+    fn delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let base = self
+            .initial
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max);
+        base.mul_f64(0.5 + jitter * 0.5)
+    }
+}
+
+/// 连接的重连计数
+#[derive(Debug, Default)]
+pub struct ReconnectStats {
+    reconnects: AtomicU64,
+    forced_disconnects: AtomicU64,
+    consecutive_failures: AtomicU64,
+}
+
+impl ReconnectStats {
+    /// 全部重连次数，包括定期断开后的重连
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects.load(Relaxed)
+    }
+    /// 服务器定期（24小时）断开的次数
+    pub fn forced_disconnects(&self) -> u64 {
+        self.forced_disconnects.load(Relaxed)
+    }
+    /// 当前连续失败的次数，收到事件后清零
+    pub fn consecutive_failures(&self) -> u64 {
+        self.consecutive_failures.load(Relaxed)
+    }
+    pub(super) fn clear_failures(&self) {
+        self.consecutive_failures.store(0, Relaxed);
+    }
+}
+
+/// 单个连接的重连状态
+pub(super) struct Reconnect {
+    policy: BackoffPolicy,
+    stats: Arc<ReconnectStats>,
+    /// 连续重连的次数，决定退避时间，收到事件后清零
+    attempt: u32,
+    /// 上次连接成功以来失败的次数
+    failures: u32,
+}
+
+impl Reconnect {
+    pub(super) fn new(policy: BackoffPolicy, stats: Arc<ReconnectStats>) -> Self {
+        Self {
+            policy,
+            stats,
+            attempt: 0,
+            failures: 0,
+        }
+    }
+    /// 连接断开后调用，uptime为本次连接的时长，received为本次连接是否收到过事件；
+    /// 断开前连接成功过，重试次数重新计算；返回重连前的等待时间
+    pub(super) fn disconnected(
+        &mut self,
+        e: &WsError,
+        uptime: Duration,
+        received: bool,
+    ) -> Option<Duration> {
+        self.failures = 0;
+        if received {
+            self.attempt = 0;
+        }
+        if matches!(e, WsError::Disconnected(_)) && uptime >= FORCED_DISCONNECT_UPTIME {
+            info!("Scheduled disconnect after {:?}, reconnecting...", uptime);
+            self.stats.forced_disconnects.fetch_add(1, Relaxed);
+            self.stats.reconnects.fetch_add(1, Relaxed);
+            return Some(Duration::ZERO);
+        }
+        if let WsError::ListenKeyExpired = e {
+            info!("Listen key expired, reconnecting with a new key...");
+            self.stats.reconnects.fetch_add(1, Relaxed);
+            return Some(Duration::ZERO);
+        }
+        warn!("Connection lost: {}", e);
+        self.retry()
+    }
+    /// 连接或请求失败后调用，返回重连前的等待时间，重试次数用尽时返回None
+    pub(super) fn retry(&mut self) -> Option<Duration> {
+        if self.policy.max_retries > 0 && self.failures >= self.policy.max_retries {
+            error!(
+                "Reconnect failed {} times in a row, exiting...",
+                self.failures
+            );
+            return None;
+        }
+        let delay = self.policy.delay(self.attempt, fastrand::f64());
+        self.attempt = self.attempt.saturating_add(1);
+        self.failures += 1;
+        self.stats.reconnects.fetch_add(1, Relaxed);
+        self.stats
+            .consecutive_failures
+            .store(self.attempt as u64, Relaxed);
+        warn!("Reconnecting in {:?} (attempt {})", delay, self.attempt);
+        Some(delay)
+    }
+}
+
+/// 等待delay，running置为false时提前返回false
+pub(super) fn wait(running: &AtomicBool, delay: Duration) -> bool {
+    let step = Duration::from_millis(200);
+    let mut left = delay;
+    while running.load(Relaxed) && !left.is_zero() {
+        let d = left.min(step);
+        std::thread::sleep(d);
+        left -= d;
+    }
+    running.load(Relaxed)
+}
+
+#[test]
+fn reconnect_test() {
+    let policy = BackoffPolicy {
+        initial: Duration::from_millis(100),
+        max: Duration::from_secs(1),
+        max_retries: 3,
+    };
+    assert_eq!(policy.delay(0, 0.), Duration::from_millis(50));
+    assert_eq!(policy.delay(2, 0.5), Duration::from_millis(300));
+    assert_eq!(policy.delay(10, 0.), Duration::from_millis(500));
+    assert_eq!(policy.delay(40, 0.), Duration::from_millis(500));
+
+    let stats = Arc::new(ReconnectStats::default());
+    let mut reconnect = Reconnect::new(policy, stats.clone());
+    let lost = WsError::Connection("reset".to_string());
+    for i in 0..3 {
+        let delay = reconnect.disconnected(&lost, Duration::from_secs(1), false);
+        assert!(delay.unwrap() < policy.initial * 2u32.pow(i));
+    }
+    assert_eq!(stats.consecutive_failures(), 3);
+    // 每次断开前都连接成功过，重试次数重新计算；连续连接失败时用尽
+    assert!(reconnect.retry().is_some());
+    assert!(reconnect.retry().is_some());
+    assert_eq!(reconnect.retry(), None);
+    // 收到过事件后重新计数
+    assert!(reconnect
+        .disconnected(&lost, Duration::from_secs(1), true)
+        .is_some());
+    assert_eq!(stats.consecutive_failures(), 1);
+    // 24小时定期断开立即重连，不计入失败
+    let closed = WsError::Disconnected("close frame".to_string());
+    let delay = reconnect.disconnected(&closed, Duration::from_secs(24 * 3600), false);
+    assert_eq!(delay, Some(Duration::ZERO));
+    assert_eq!(stats.forced_disconnects(), 1);
+    assert_eq!(stats.reconnects(), 7);
+    let delay = reconnect.disconnected(&WsError::ListenKeyExpired, Duration::ZERO, false);
+    assert_eq!(delay, Some(Duration::ZERO));
+    stats.clear_failures();
+    assert_eq!(stats.consecutive_failures(), 0);
+
+    let running = AtomicBool::new(false);
+    assert!(!wait(&running, Duration::from_secs(10)));
+
+    // 默认一直重试
+    let mut reconnect = Reconnect::new(Default::default(), Arc::default());
+    assert!((0..100).all(|_| reconnect.retry().is_some()));
+    let policy: BackoffPolicy = toml::from_str("initial = 0.5\nmax_retries = 30").unwrap();
+    assert_eq!(policy.initial, Duration::from_millis(500));
+    assert_eq!(policy.max, Duration::from_secs(60));
+    assert!(toml::from_str::<BackoffPolicy>("max = -1").is_err());
+}
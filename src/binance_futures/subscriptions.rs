@@ -0,0 +1,137 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    time::Duration,
+};
+
+use parking_lot::RwLock;
+use tracing::info;
+
+/// 运行时可修改的行情订阅列表，修改后连接以新的列表重连
+#[derive(Debug, Clone, Default)]
+pub struct Subscriptions {
+    streams: Arc<RwLock<Vec<String>>>,
+    changed: Arc<AtomicBool>,
+}
+
+impl From<Vec<String>> for Subscriptions {
+    fn from(streams: Vec<String>) -> Self {
+        Self::new(streams)
+    }
+}
+
+impl Subscriptions {
+    pub fn new(mut streams: Vec<String>) -> Self {
+        streams.sort();
+        streams.dedup();
+        Self {
+            streams: Arc::new(RwLock::new(streams)),
+            changed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+    pub fn streams(&self) -> Vec<String> {
+        self.streams.read().clone()
+    }
+    pub fn contains(&self, stream: &str) -> bool {
+        self.streams.read().iter().any(|s| s == stream)
+    }
+    /// 是否有以prefix开头的流，用于按事件所属的流分发
+    pub fn contains_prefix(&self, prefix: &str) -> bool {
+        self.streams.read().iter().any(|s| s.starts_with(prefix))
+    }
+    /// 返回是否有变化
+    pub fn subscribe(&self, streams: &[String]) -> bool {
+        let mut all = self.streams();
+        all.extend_from_slice(streams);
+        self.set(all)
+    }
+    pub fn unsubscribe(&self, streams: &[String]) -> bool {
+        let mut all = self.streams();
+        all.retain(|s| !streams.contains(s));
+        self.set(all)
+    }
+    /// 替换整个订阅列表
+    pub fn set(&self, mut streams: Vec<String>) -> bool {
+        streams.sort();
+        streams.dedup();
+        let mut current = self.streams.write();
+        if *current == streams {
+            return false;
+        }
+        let added: Vec<&String> = streams.iter().filter(|s| !current.contains(s)).collect();
+        let removed: Vec<&String> = current.iter().filter(|s| !streams.contains(s)).collect();
+        info!("Subscriptions changed: +{:?} -{:?}", added, removed);
+        *current = streams;
+        self.changed.store(true, Relaxed);
+        true
+    }
+    /// 取出并清除变化标记
+    pub(super) fn take_changed(&self) -> bool {
+        self.changed.swap(false, Relaxed)
+    }
+    /// 订阅变化或running置为false时将alive置为false，中断事件循环
+    pub(super) fn watch(&self, running: &AtomicBool, alive: &AtomicBool) {
+        while running.load(Relaxed) {
+            std::thread::sleep(Duration::from_millis(200));
+            if self.changed.load(Relaxed) {
+                alive.store(false, Relaxed);
+            }
+        }
+        alive.store(false, Relaxed);
+    }
+    /// 订阅为空时等待，直到有新的订阅或running置为false
+    pub(super) fn wait_nonempty(&self, running: &AtomicBool) -> Option<Vec<String>> {
+        loop {
+            let streams = self.streams();
+            if !streams.is_empty() {
+                return Some(streams);
+            }
+            if !running.load(Relaxed) {
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
+    }
+}
+
+/// 全市场标记价格流，每秒推送
+pub const MARK_PRICE_ALL_STREAM: &str = "!markPrice@arr@1s";
+
+/// 单币种10档深度流
+pub fn depth_stream(symbol: &str) -> String {
+    format!("{}@depth10@100ms", symbol.to_lowercase())
+}
+
+/// 单币种k线流，interval如1m、1h
+pub fn kline_stream(symbol: &str, interval: &str) -> String {
+    format!("{}@kline_{}", symbol.to_lowercase(), interval)
+}
+
+#[test]
+fn subscriptions_test() {
+    let subs = Subscriptions::new(vec![depth_stream("BTCUSDT"), depth_stream("BTCUSDT")]);
+    assert_eq!(subs.streams(), ["btcusdt@depth10@100ms"]);
+    assert!(!subs.take_changed());
+    assert!(subs.subscribe(&[depth_stream("ETHUSDT")]));
+    assert!(!subs.subscribe(&[depth_stream("ETHUSDT")]));
+    assert!(subs.contains("ethusdt@depth10@100ms"));
+    assert!(subs.contains_prefix("ethusdt@depth"));
+    assert!(subs.take_changed());
+    assert!(subs.unsubscribe(&[depth_stream("BTCUSDT")]));
+    assert_eq!(subs.streams(), ["ethusdt@depth10@100ms"]);
+    assert!(!subs.contains_prefix("btcusdt@depth"));
+    assert_eq!(kline_stream("ETHUSDT", "1h"), "ethusdt@kline_1h");
+    // 订阅变化时中断事件循环
+    let (running, alive) = (
+        Arc::new(AtomicBool::new(true)),
+        Arc::new(AtomicBool::new(true)),
+    );
+    let (s, r, a) = (subs.clone(), running.clone(), alive.clone());
+    let h = std::thread::spawn(move || s.watch(&r, &a));
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(!alive.load(Relaxed));
+    running.store(false, Relaxed);
+    h.join().unwrap();
+}
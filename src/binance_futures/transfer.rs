@@ -0,0 +1,210 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{
+    error::{BinanceErrorCode, MarketError},
+    utils::truncate_step,
+};
+
+use super::{income::ErrorResponse, BinanceKeys, ClockDrift};
+
+const SAPI_URL: &str = "https://api.binance.com";
+/// 主账户内不同钱包之间划转
+const UNIVERSAL_TRANSFER_PATH: &str = "/sapi/v1/asset/transfer";
+/// 主账户与子账户、子账户之间划转，需主账户的API key
+const SUB_ACCOUNT_TRANSFER_PATH: &str = "/sapi/v1/sub-account/universalTransfer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Wallet {
+    Spot,
+    /// U本位合约
+    UsdMFutures,
+}
+
+impl Wallet {
+    fn account_type(&self) -> &'static str {
+        match self {
+            Wallet::Spot => "SPOT",
+            Wallet::UsdMFutures => "USDT_FUTURE",
+        }
+    }
+    fn universal_name(&self) -> &'static str {
+        match self {
+            Wallet::Spot => "MAIN",
+            Wallet::UsdMFutures => "UMFUTURE",
+        }
+    }
+}
+
+/// 划转的一方，email为None时是主账户
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TransferAccount {
+    #[serde(default)]
+    pub email: Option<String>,
+    pub wallet: Wallet,
+}
+
+impl TransferAccount {
+    pub fn main(wallet: Wallet) -> Self {
+        Self {
+            email: None,
+            wallet,
+        }
+    }
+}
+
+/// 划转请求的路径和参数（不含timestamp和签名），数量保留8位小数
+pub fn transfer_query(
+    from: &TransferAccount,
+    to: &TransferAccount,
+    asset: &str,
+    amount: f64,
+) -> Result<(&'static str, String), MarketError> {
+    if from == to {
+        return Err(MarketError::Rejected(
+            "transfer to the same wallet".to_string(),
+        ));
+    }
+    let amount = truncate_step(amount, 1e-8);
+    if amount <= 0. {
+        return Err(MarketError::Rejected(format!("transfer amount {}", amount)));
+    }
+    if from.email.is_none() && to.email.is_none() {
+        let query = format!(
+            "type={}_{}&asset={}&amount={:.8}",
+            from.wallet.universal_name(),
+            to.wallet.universal_name(),
+            asset,
+            amount
+        );
+        return Ok((UNIVERSAL_TRANSFER_PATH, query));
+    }
+    let mut query = String::new();
+    if let Some(email) = &from.email {
+        query += &format!("fromEmail={}&", email);
+    }
+    if let Some(email) = &to.email {
+        query += &format!("toEmail={}&", email);
+    }
+    query += &format!(
+        "fromAccountType={}&toAccountType={}&asset={}&amount={:.8}",
+        from.wallet.account_type(),
+        to.wallet.account_type(),
+        asset,
+        amount
+    );
+    Ok((SUB_ACCOUNT_TRANSFER_PATH, query))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransferResponse {
+    tran_id: u64,
+}
+
+/// 解析划转响应，返回交易所的划转编号
+pub fn parse_transfer(body: &str) -> Result<u64, MarketError> {
+    if let Ok(e) = serde_json::from_str::<ErrorResponse>(body) {
+        return Err(MarketError::Binance {
+            context: "transfer".to_string(),
+            code: BinanceErrorCode::from(e.code),
+            msg: e.msg,
+        });
+    }
+    serde_json::from_str::<TransferResponse>(body)
+        .map(|r| r.tran_id)
+        .map_err(|e| MarketError::Request {
+            context: "parse transfer".to_string(),
+            msg: e.to_string(),
+        })
+}
+
+/// 现货、合约钱包及子账户之间的资金划转
+#[derive(Clone)]
+pub struct TransferClient {
+    keys: BinanceKeys,
+    http: reqwest::blocking::Client,
+    /// 请求时间戳按其校正
+    clock: Arc<ClockDrift>,
+}
+opaque_debug::implement!(TransferClient);
+
+impl TransferClient {
+    pub fn new(keys: BinanceKeys) -> Self {
+        Self {
+            keys,
+            http: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+            clock: Arc::default(),
+        }
+    }
+    /// 与下单共用校时结果，见Clients::clock
+    pub fn with_clock(mut self, clock: Arc<ClockDrift>) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// 从from划转amount个asset到to，返回划转编号
+    pub fn transfer(
+        &self,
+        from: &TransferAccount,
+        to: &TransferAccount,
+        asset: &str,
+        amount: f64,
+    ) -> Result<u64, MarketError> {
+        let (path, query) = transfer_query(from, to, asset, amount)?;
+        let query = format!("{}&timestamp={}", query, self.clock.timestamp());
+        let body = self
+            .http
+            .post(format!(
+                "{}{}?{}&signature={}",
+                SAPI_URL,
+                path,
+                query,
+                self.keys.sign(&query)
+            ))
+            .header("X-MBX-APIKEY", &self.keys.api_key)
+            .send()
+            .and_then(|r| r.text())
+            .map_err(|e| MarketError::Request {
+                context: "transfer".to_string(),
+                msg: e.to_string(),
+            })?;
+        parse_transfer(&body)
+    }
+}
+
+#[test]
+fn transfer_test() {
+    let spot = TransferAccount::main(Wallet::Spot);
+    let futures = TransferAccount::main(Wallet::UsdMFutures);
+    assert_eq!(
+        transfer_query(&futures, &spot, "USDT", 12.345678919).unwrap(),
+        (
+            UNIVERSAL_TRANSFER_PATH,
+            "type=UMFUTURE_MAIN&asset=USDT&amount=12.34567891".to_string()
+        )
+    );
+    let sub = TransferAccount {
+        email: Some("sub@example.com".into()),
+        wallet: Wallet::Spot,
+    };
+    assert_eq!(
+        transfer_query(&futures, &sub, "USDT", 5.).unwrap(),
+        (
+            SUB_ACCOUNT_TRANSFER_PATH,
+            "toEmail=sub@example.com&fromAccountType=USDT_FUTURE&toAccountType=SPOT&asset=USDT&amount=5.00000000"
+                .to_string()
+        )
+    );
+    assert!(transfer_query(&spot, &spot, "USDT", 1.).is_err());
+    assert!(transfer_query(&futures, &spot, "USDT", 0.).is_err());
+    assert_eq!(
+        parse_transfer(r#"{"tranId":13526853623}"#).unwrap(),
+        13526853623
+    );
+    let e = parse_transfer(r#"{"code":-5002,"msg":"Insufficient balance"}"#).unwrap_err();
+    assert!(matches!(e, MarketError::Binance { .. }));
+}
@@ -0,0 +1,162 @@
+use std::{
+    net::TcpStream,
+    sync::atomic::{AtomicBool, Ordering::Relaxed},
+};
+
+use binance::futures::websockets::FuturesWebsocketEvent;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use crate::{controller::forced_close::MarginCallPosition, error::WsError};
+
+pub(super) const USER_STREAM_URL: &str = "wss://fstream.binance.com/ws/";
+
+#[derive(Deserialize)]
+struct RawMarginCallPosition {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "pa")]
+    amount: String,
+    #[serde(rename = "mp")]
+    mark_price: String,
+    #[serde(rename = "mm")]
+    maint_margin: String,
+}
+
+#[derive(Deserialize)]
+struct RawMarginCall {
+    #[serde(rename = "E")]
+    event_time: u64,
+    #[serde(rename = "p")]
+    positions: Vec<RawMarginCallPosition>,
+}
+
+/// user data stream的事件，币安库不解析MARGIN_CALL，由这里自行解析
+#[derive(Debug)]
+pub enum UserDataEvent {
+    Futures(Box<FuturesWebsocketEvent>),
+    MarginCall {
+        time: u64,
+        positions: Vec<MarginCallPosition>,
+    },
+}
+
+fn parse_number(s: &str) -> Result<f64, String> {
+    s.parse().map_err(|e| format!("parse {}: {}", s, e))
+}
+
+fn parse_margin_call(value: Value) -> Result<UserDataEvent, String> {
+    let raw: RawMarginCall = from_value(value)?;
+    let positions = raw
+        .positions
+        .into_iter()
+        .map(|p| {
+            Ok(MarginCallPosition {
+                amount: parse_number(&p.amount)?,
+                mark_price: parse_number(&p.mark_price)?,
+                maint_margin: parse_number(&p.maint_margin)?,
+                symbol: p.symbol,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(UserDataEvent::MarginCall {
+        time: raw.event_time,
+        positions,
+    })
+}
+
+/// 按事件类型解析一条消息，不关心的事件为None
+pub fn parse_user_event(text: &str) -> Result<Option<UserDataEvent>, String> {
+    let value: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let Some(event_type) = value.get("e").and_then(|e| e.as_str()) else {
+        return Ok(None);
+    };
+    let event = match event_type {
+        "ORDER_TRADE_UPDATE" => FuturesWebsocketEvent::OrderTrade(from_value(value)?),
+        "ACCOUNT_UPDATE" => FuturesWebsocketEvent::AccountUpdate(from_value(value)?),
+        "listenKeyExpired" => FuturesWebsocketEvent::UserDataStreamExpiredEvent(from_value(value)?),
+        "MARGIN_CALL" => return parse_margin_call(value).map(Some),
+        _ => return Ok(None),
+    };
+    Ok(Some(UserDataEvent::Futures(Box::new(event))))
+}
+
+pub(super) fn from_value<T: for<'de> Deserialize<'de>>(value: Value) -> Result<T, String> {
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+/// 直接读取user data stream的连接，替代币安库的FuturesWebSockets以收到MARGIN_CALL
+pub struct UserDataSocket {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+}
+
+impl UserDataSocket {
+    pub fn connect(listen_key: &str) -> Result<Self, WsError> {
+        let (socket, _) = tungstenite::connect(format!("{}{}", USER_STREAM_URL, listen_key))
+            .map_err(|e| WsError::Connection(e.to_string()))?;
+        Ok(Self { socket })
+    }
+    /// 事件循环，running或alive置为false时断开并返回Ok，handler的错误原样返回
+    pub fn event_loop<F>(
+        &mut self,
+        handler: &mut F,
+        running: &AtomicBool,
+        alive: &AtomicBool,
+    ) -> Result<(), WsError>
+    where
+        F: FnMut(UserDataEvent) -> binance::errors::Result<()>,
+    {
+        while running.load(Relaxed) && alive.load(Relaxed) {
+            let msg = self
+                .socket
+                .read()
+                .map_err(|e| WsError::Disconnected(e.to_string()))?;
+            match msg {
+                Message::Text(text) => match parse_user_event(text.as_ref()) {
+                    Ok(Some(event)) => handler(event).map_err(WsError::from)?,
+                    Ok(None) => {}
+                    Err(e) => warn!("User data message parse failed: {}, {}", e, text),
+                },
+                Message::Ping(p) => {
+                    self.socket
+                        .send(Message::Pong(p))
+                        .map_err(|e| WsError::Disconnected(e.to_string()))?;
+                }
+                Message::Close(_) => {
+                    return Err(WsError::Disconnected("closed by server".to_string()));
+                }
+                _ => {}
+            }
+        }
+        self.socket.close(None).ok();
+        Ok(())
+    }
+}
+
+#[test]
+fn user_event_test() {
+    let margin_call = r#"{"e":"MARGIN_CALL","E":1587727187525,"cw":"3.16812045","p":[
+        {"s":"ETHUSDT","ps":"BOTH","pa":"-1.327","mt":"CROSSED","iw":"0","mp":"187.17127","up":"-1.166074","mm":"1.614445"}
+    ]}"#;
+    let Some(UserDataEvent::MarginCall { time, positions }) =
+        parse_user_event(margin_call).unwrap()
+    else {
+        panic!("margin call not parsed");
+    };
+    assert_eq!(time, 1587727187525);
+    assert_eq!(
+        positions,
+        [MarginCallPosition {
+            symbol: "ETHUSDT".into(),
+            amount: -1.327,
+            mark_price: 187.17127,
+            maint_margin: 1.614445,
+        }]
+    );
+    assert!(parse_user_event(r#"{"e":"TRADE_LITE","E":1}"#)
+        .unwrap()
+        .is_none());
+    assert!(parse_user_event(r#"{"e":"MARGIN_CALL","E":1,"p":[{"s":"ETHUSDT"}]}"#).is_err());
+}
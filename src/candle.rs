@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// k线，回测与实盘共用，按收盘时间排序和比较
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CandleData {
+    pub open: f64,
+    pub close: f64,
+    pub high: f64,
+    pub low: f64,
+    pub volume: f64,
+    pub open_time: OffsetDateTime,
+    pub close_time: OffsetDateTime,
+}
+
+impl Default for CandleData {
+    fn default() -> Self {
+        Self {
+            open: 0.,
+            close: 0.,
+            high: 0.,
+            low: 0.,
+            volume: 0.,
+            open_time: OffsetDateTime::from_unix_timestamp(0).unwrap(),
+            close_time: OffsetDateTime::from_unix_timestamp(0).unwrap(),
+        }
+    }
+}
+
+impl Ord for CandleData {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.close_time.cmp(&other.close_time)
+    }
+}
+
+impl PartialOrd for CandleData {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for CandleData {
+    fn eq(&self, other: &Self) -> bool {
+        self.close_time == other.close_time
+    }
+}
+
+impl Eq for CandleData {}
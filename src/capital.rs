@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+/// 已实现盈利如何计入策略资金，实盘分账和回测共用；亏损总是直接减少可交易资金
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CapitalPolicy {
+    /// 固定本金，超出初始资金的盈利全部留作储备
+    Fixed,
+    /// 盈利全部复利
+    #[default]
+    Compound,
+    /// 权益比上次提升后的资金高出step（比例）时才将资金提升到当前权益，其间的盈利留作储备
+    Ratchet { step: f64 },
+}
+
+/// 单个策略的资金水位
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capital {
+    policy: CapitalPolicy,
+    /// 当前允许交易的资金上限，复利时不使用
+    level: f64,
+}
+
+impl Capital {
+    pub fn new(policy: CapitalPolicy, base: f64) -> Self {
+        Self {
+            policy,
+            level: base,
+        }
+    }
+    pub fn policy(&self) -> CapitalPolicy {
+        self.policy
+    }
+    /// 权益变化后更新资金水位
+    pub fn update(&mut self, equity: f64) {
+        match self.policy {
+            CapitalPolicy::Fixed | CapitalPolicy::Compound => {}
+            CapitalPolicy::Ratchet { step } => {
+                if equity >= self.level * (1. + step) {
+                    self.level = equity;
+                }
+            }
+        }
+    }
+    /// 可交易资金
+    pub fn trading(&self, equity: f64) -> f64 {
+        match self.policy {
+            CapitalPolicy::Compound => equity,
+            _ => self.level.min(equity),
+        }
+    }
+    /// 不参与交易的储备
+    pub fn reserve(&self, equity: f64) -> f64 {
+        (equity - self.trading(equity)).max(0.)
+    }
+}
+
+#[test]
+fn capital_test() {
+    let mut fixed = Capital::new(CapitalPolicy::Fixed, 100.);
+    fixed.update(130.);
+    assert_eq!(fixed.trading(130.), 100.);
+    assert_eq!(fixed.reserve(130.), 30.);
+    assert_eq!(fixed.trading(80.), 80.);
+
+    let mut compound = Capital::new(CapitalPolicy::Compound, 100.);
+    compound.update(130.);
+    assert_eq!(compound.trading(130.), 130.);
+    assert_eq!(compound.reserve(130.), 0.);
+
+    let mut ratchet = Capital::new(CapitalPolicy::Ratchet { step: 0.1 }, 100.);
+    ratchet.update(105.);
+    assert_eq!(ratchet.trading(105.), 100.);
+    ratchet.update(112.);
+    assert_eq!(ratchet.trading(112.), 112.);
+    // 回撤时不降低水位，但不超过权益
+    ratchet.update(90.);
+    assert_eq!(ratchet.trading(90.), 90.);
+    ratchet.update(120.);
+    assert_eq!(ratchet.trading(120.), 112.);
+    assert_eq!(ratchet.reserve(120.), 8.);
+
+    let policy: CapitalPolicy = toml::from_str::<toml::Value>("p = { ratchet = { step = 0.2 } }")
+        .unwrap()["p"]
+        .clone()
+        .try_into()
+        .unwrap();
+    assert_eq!(policy, CapitalPolicy::Ratchet { step: 0.2 });
+}
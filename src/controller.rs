@@ -10,92 +10,1675 @@ use std::{
     time::Duration,
 };
 
-use anyhow::{anyhow, Ok};
-use binance::{futures::model::OrderUpdate, model::AccountUpdateDataEvent};
+use binance::{
+    futures::model::{OrderUpdate, PositionRisk},
+    model::AccountUpdateDataEvent,
+};
 use crossbeam::channel::{Receiver, Select, Sender};
 use dashmap::DashMap;
 use parking_lot::Mutex;
 use rayon::{prelude::*, Scope};
-use tracing::error;
+use tracing::{error, info, warn};
 
 use crate::{
-    algorithm::{ SymbolPrice},
-    market::{Market, MarketOrderRequest},
-    strategy::{Strategy, StrategyOrderReturn},
+    algorithm::{Algorithm, DepthData, KlineData, SignalData, SymbolPrice},
+    binance_futures::{
+        countdown::CountdownClient,
+        income::{IncomeClient, PnlDivergence},
+        transfer::TransferClient,
+        BinanceKeys, StreamHealth, SymbolPrices,
+    },
+    deleverage::Deleverage,
+    error::{ConfigError, MarketError},
+    fee::{FeeSchedule, FillType},
+    market::{
+        spread::SpreadOrderRequest, ClientOrderId, Market, MarketOrderRequest, MarketOrderReturn,
+    },
+    notifier::{NotifierRouter, Severity},
+    store::Store,
+    strategy::{HedgeLeg, Strategy, StrategyOrderRequest, StrategyOrderReturn, StrategyStatus},
+    symbol::{filter::DEFAULT_QUOTE_ASSET, SymbolId},
+    tax::Fill,
+    treasury::Treasury,
+    utils::{
+        coalesce::{coalescing_channel, drain_latest, IDLE_FLUSH_INTERVAL},
+        local_now, unix_millis, AUDIT_TARGET,
+    },
 };
 
+#[cfg(feature = "dashboard")]
+use crate::dashboard::{Dashboard, DashboardEvent, FillUpdate, StreamStatus};
+
+pub mod approval;
+#[cfg(feature = "async")]
+mod async_run;
+pub mod base_currency;
+pub mod circuit_breaker;
+pub mod config;
+pub mod correlation;
+pub mod dead_man;
+pub mod equity;
+pub mod event_guard;
+pub mod forced_close;
+pub mod funding_schedule;
+pub mod ledger;
+pub mod margin_guard;
+pub mod report;
+pub mod router;
+pub mod schedule;
+pub mod spread;
+pub mod tca;
+pub mod throttle;
+
+use approval::{ApprovalQueue, TradeIdea, EXPIRE_INTERVAL};
+use base_currency::BaseCurrency;
+use circuit_breaker::CircuitBreaker;
+use config::ControllerConfig;
+use correlation::CorrelationGuard;
+use dead_man::DeadManSwitch;
+use equity::EquitySample;
+use event_guard::{EventAction, EventGuard};
+use forced_close::{ForcedClose, MarginCallPosition};
+use funding_schedule::FundingSchedule;
+use ledger::Ledger;
+use margin_guard::{MarginGuard, TopUp};
+use report::{AccountSnapshot, PositionSnapshot};
+use router::{shard, SymbolRouter, SHARD_QUEUE, SIGNAL_SHARDS};
+use schedule::Scheduler;
+use spread::{LegClosed, SpreadBook};
+use tca::{ExecutionPrices, TradeCostAnalysis};
+use throttle::Throttle;
+
 #[derive(Debug)]
 struct Controller<M> {
     market: M,
+    algorithms: Vec<Box<dyn Algorithm>>,
     strategies: Vec<Box<dyn Strategy>>,
-    // prices: Arc<DashMap<String, SymbolPrice>>,
+    /// 按币种将行情分发给关注的策略
+    router: SymbolRouter,
+    /// 与strategies一一对应
+    breakers: Vec<Mutex<CircuitBreaker>>,
+    /// 持仓币种 -> 策略序号
+    owners: DashMap<SymbolId, usize>,
+    /// 各策略的虚拟子账户
+    ledger: Ledger,
+    notifiers: NotifierRouter,
+    price_health: Arc<StreamHealth>,
+    prices: SymbolPrices,
+    margin_guard: MarginGuard,
+    /// 高度相关币种的合计敞口限制
+    correlation: CorrelationGuard,
+    /// 按币种的再入场冷却和下单频率限制
+    throttle: Throttle,
+    /// 资金费率结算前后的开平仓时机，None为不处理
+    funding: Option<FundingSchedule>,
+    /// 重大经济事件前后暂停开仓或缩小仓位，None为不处理
+    events: Option<EventGuard>,
+    /// 账户回撤分级缩小所有策略的开仓金额，降到0时全部平仓，None为不处理
+    deleverage: Option<Mutex<Deleverage>>,
+    /// 人工确认模式，开仓请求经控制命令批准后才下单，None为直接下单
+    approval: Option<ApprovalQueue>,
+    /// 按计划启用和停用策略，None为始终启用
+    schedule: Option<Scheduler>,
+    /// 用于估算未实现盈亏的手续费率
+    fees: FeeSchedule,
+    /// 每日报告时间（东八区）
+    report_time: time::Time,
+    /// 上次报告以来的已实现盈亏
+    realized_pnl: Mutex<f64>,
+    store: Option<Store>,
+    /// 每日报告时导入交易所资金流水并对账，需配置store
+    income: Option<IncomeClient>,
+    /// 交易所倒计时撤单，进程退出或断网后撤销未成交的开仓单，None为不启用
+    dead_man: Option<DeadManSwitch>,
+    /// 每周报告时按策略将合约钱包的盈余划出，None为不划转
+    treasury: Option<Treasury>,
+    /// 已实现盈亏对账允许的偏差（结算资产）
+    pnl_tolerance: f64,
+    /// 信号价、下单价与成交价的滑点统计
+    tca: TradeCostAnalysis,
+    /// 组合单各腿的合并盈亏
+    spreads: SpreadBook,
+    /// 权益采样推送（如实时绘图）
+    equity_tx: Option<Sender<EquitySample>>,
+    /// 网页监控面板，与权益采样同频推送状态
+    #[cfg(feature = "dashboard")]
+    dashboard: Option<Dashboard>,
+    equity_interval: Duration,
+    /// 策略状态快照间隔，需配置store
+    state_interval: Duration,
+    /// 结算资产，余额、盈亏和下单金额均以其计价，需与行情和市场的过滤规则一致
+    quote_asset: String,
+    /// 报告和面板额外使用的计价货币，None为只用结算资产
+    base_currency: Option<BaseCurrency>,
     total_balance: Mutex<f64>,
     cross_balance: Mutex<f64>,
     open_orders: DashMap<u64, Order>,
-    positions: DashMap<String, Position>,
+    positions: DashMap<SymbolId, Position>,
     update_time: AtomicU64,
 }
 
 impl<M: Market> Controller<M> {
+    /// allocations为各策略分配的资金，其余组件取默认配置
+    fn new(
+        market: M,
+        strategies: Vec<Box<dyn Strategy>>,
+        allocations: &[f64],
+        prices: SymbolPrices,
+    ) -> Self {
+        Self {
+            fees: market.fees(),
+            market,
+            algorithms: vec![],
+            router: SymbolRouter::new(&strategies),
+            breakers: strategies
+                .iter()
+                .map(|_| Mutex::new(CircuitBreaker::new(Default::default())))
+                .collect(),
+            strategies,
+            owners: DashMap::new(),
+            ledger: Ledger::new(allocations),
+            notifiers: NotifierRouter::default(),
+            price_health: Arc::new(StreamHealth::new(Duration::from_secs(10))),
+            prices,
+            margin_guard: MarginGuard::new(Default::default()),
+            correlation: CorrelationGuard::new(Default::default()),
+            throttle: Throttle::new(Default::default()),
+            funding: None,
+            events: None,
+            deleverage: None,
+            approval: None,
+            schedule: None,
+            report_time: time::Time::MIDNIGHT,
+            realized_pnl: Mutex::new(0.),
+            store: None,
+            income: None,
+            dead_man: None,
+            treasury: None,
+            pnl_tolerance: 1.,
+            tca: TradeCostAnalysis::default(),
+            spreads: SpreadBook::default(),
+            equity_tx: None,
+            #[cfg(feature = "dashboard")]
+            dashboard: None,
+            equity_interval: Duration::from_secs(60),
+            state_interval: Duration::from_secs(60),
+            quote_asset: DEFAULT_QUOTE_ASSET.to_string(),
+            base_currency: None,
+            total_balance: Mutex::new(0.),
+            cross_balance: Mutex::new(0.),
+            open_orders: DashMap::new(),
+            positions: DashMap::new(),
+            update_time: AtomicU64::new(0),
+        }
+    }
+    /// 按配置替换各风控组件的默认配置，读取事件日历失败时返回错误；
+    /// keys用于死人开关和资金划转等直接调用交易所接口的组件，为None时不能启用这些组件
+    fn from_config(
+        market: M,
+        strategies: Vec<Box<dyn Strategy>>,
+        allocations: &[f64],
+        prices: SymbolPrices,
+        config: &ControllerConfig,
+        keys: Option<&BinanceKeys>,
+    ) -> crate::error::Result<Self> {
+        let mut controller = Self::new(market, strategies, allocations, prices);
+        controller.throttle = Throttle::new(config.throttle.clone());
+        for index in 0..controller.strategies.len() {
+            controller
+                .ledger
+                .set_policy(index, config.capital.policy(index));
+        }
+        controller.events = config.events.as_ref().map(|e| e.guard()).transpose()?;
+        controller.deleverage = config
+            .deleverage
+            .clone()
+            .map(|d| Mutex::new(Deleverage::new(d)));
+        controller.funding = config.funding.clone().map(FundingSchedule::new);
+        controller.report_time = config.report_time;
+        controller.store = config.store.as_deref().map(Store::open).transpose()?;
+        controller.notifiers = config.notification.build()?;
+        if let Some(index) = config
+            .schedule
+            .iter()
+            .filter_map(|r| r.strategy)
+            .find(|&i| i >= controller.strategies.len())
+        {
+            return Err(ConfigError::Invalid(format!("schedule strategy {}", index)).into());
+        }
+        controller.schedule =
+            (!config.schedule.is_empty()).then(|| Scheduler::new(config.schedule.clone()));
+        controller.approval = config.approval_timeout.map(ApprovalQueue::new);
+        if let Some(countdown) = config.dead_man_countdown {
+            let keys = keys.ok_or(ConfigError::Invalid("dead man switch without keys".into()))?;
+            controller.dead_man = Some(DeadManSwitch::new(
+                CountdownClient::new(keys.clone()),
+                Duration::from_millis(countdown),
+            ));
+        }
+        if let Some(policy) = &config.treasury {
+            let keys = keys.ok_or(ConfigError::Invalid("treasury without keys".into()))?;
+            controller.treasury = Some(Treasury::new(
+                TransferClient::new(keys.clone()),
+                policy.clone(),
+            ));
+        }
+        controller.base_currency = config.base_currency.clone();
+        controller.margin_guard = MarginGuard::new(config.margin_guard.clone());
+        controller.correlation = CorrelationGuard::new(config.correlation.clone());
+        controller.breakers = (0..controller.strategies.len())
+            .map(|_| Mutex::new(CircuitBreaker::new(config.circuit_breaker.clone())))
+            .collect();
+        if let Some(plot) = &config.equity_plot {
+            let (tx, rx) = crossbeam::channel::unbounded();
+            // 绘图线程在控制器释放、通道关闭后画完最后一次退出
+            equity::serve_plot(rx, plot.path.clone(), plot.redraw_every);
+            controller.equity_tx = Some(tx);
+        }
+        Ok(controller)
+    }
     fn run(
         self,
         signal_rx: Receiver<SymbolPrice>,
+        depth_rx: Receiver<DepthData>,
+        kline_rx: Receiver<KlineData>,
         account_rx: Receiver<AccountInfo>,
+        control_rx: Receiver<ControlCommand>,
     ) -> JoinHandle<()> {
         std::thread::spawn(move || {
-            rayon::ThreadPoolBuilder::new()
-                .num_threads(4)
-                .use_current_thread()
-                .build()
-                .unwrap()
-                .scope(|s| loop {
-                    crossbeam::channel::select! {
-                        recv(signal_rx) -> signal => {
-                            s.spawn(|_| self.input_signal(signal.unwrap()));
-                        }
-                        recv(account_rx) -> account_info => {
-                            s.spawn(|_| self.update_account(account_info.unwrap()));
-                        }
-                    }
+            self.restore_states();
+            // 行情按币种分片，每个分片一个线程按顺序处理，积压时每个币种只处理最新的价格
+            let (shard_txs, shard_rxs): (Vec<_>, Vec<_>) = (0..SIGNAL_SHARDS)
+                .map(|_| {
+                    let (tx, rx) = coalescing_channel(SHARD_QUEUE, |p: &SymbolPrice| p.symbol);
+                    (tx.with_idle_flush(IDLE_FLUSH_INTERVAL), rx)
                 })
+                .unzip();
+            std::thread::scope(|ts| {
+                for (i, rx) in shard_rxs.into_iter().enumerate() {
+                    let this = &self;
+                    std::thread::Builder::new()
+                        .name(format!("signal-shard-{}", i))
+                        .spawn_scoped(ts, move || {
+                            for first in rx.iter() {
+                                for signal in drain_latest(first, &rx, |p| p.symbol) {
+                                    this.input_signal(signal);
+                                }
+                            }
+                        })
+                        .unwrap();
+                }
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(4)
+                    .use_current_thread()
+                    .build()
+                    .unwrap()
+                    .scope(|s| {
+                        let mut report_timer = crossbeam::channel::after(
+                            report::until_next(local_now(), self.report_time).unsigned_abs(),
+                        );
+                        let equity_timer = if self.samples_equity() {
+                            crossbeam::channel::tick(self.equity_interval)
+                        } else {
+                            crossbeam::channel::never()
+                        };
+                        let state_timer = match self.store {
+                            Some(_) => crossbeam::channel::tick(self.state_interval),
+                            None => crossbeam::channel::never(),
+                        };
+                        // 行情中断时也要让待确认的请求按时作废
+                        let approval_timer = match self.approval {
+                            Some(_) => crossbeam::channel::tick(EXPIRE_INTERVAL),
+                            None => crossbeam::channel::never(),
+                        };
+                        let dead_man_timer = match &self.dead_man {
+                            Some(d) => crossbeam::channel::tick(d.interval()),
+                            None => crossbeam::channel::never(),
+                        };
+                        loop {
+                            crossbeam::channel::select! {
+                                recv(signal_rx) -> signal => {
+                                    let signal = signal.unwrap();
+                                    shard_txs[shard(signal.symbol, SIGNAL_SHARDS)].send(signal);
+                                }
+                                recv(depth_rx) -> depth => {
+                                    s.spawn(|_| self.input_depth(depth.unwrap()));
+                                }
+                                recv(kline_rx) -> kline => {
+                                    s.spawn(|_| self.input_kline(kline.unwrap()));
+                                }
+                                recv(account_rx) -> account_info => {
+                                    s.spawn(|_| self.update_account(account_info.unwrap()));
+                                }
+                                recv(control_rx) -> command => {
+                                    self.control(command.unwrap());
+                                }
+                                recv(report_timer) -> _ => {
+                                    self.report();
+                                    report_timer = crossbeam::channel::after(
+                                        report::until_next(local_now(), self.report_time).unsigned_abs(),
+                                    );
+                                }
+                                recv(equity_timer) -> _ => {
+                                    self.sample_equity();
+                                }
+                                recv(state_timer) -> _ => {
+                                    self.save_states();
+                                }
+                                recv(approval_timer) -> _ => {
+                                    self.expire_ideas(unix_millis());
+                                }
+                                recv(dead_man_timer) -> _ => {
+                                    s.spawn(|_| self.refresh_dead_man());
+                                }
+                            }
+                        }
+                    })
+            })
         })
     }
 
     fn input_signal(&self, signal: SymbolPrice) {
+        self.expire_ideas(signal.time);
+        self.apply_schedule(signal.time);
+        self.guard_margin(&signal);
+        self.guard_funding(&signal);
+        if self
+            .positions
+            .get(&signal.symbol)
+            .is_some_and(|p| p.position_amount != 0.)
+        {
+            self.guard_drawdown(signal.time);
+        }
+        self.correlation.update(&signal);
+        for algorithm in self.algorithms.iter() {
+            if let Some(data) = algorithm.update(&signal) {
+                self.broadcast_signal(&data);
+            }
+        }
+        for &index in self.router.route(signal.symbol) {
+            if !self.is_running(index) {
+                continue;
+            }
+            if let Some(order_request) = self.strategies[index].update(&signal) {
+                self.place(index, order_request, &signal);
+            }
+        }
+    }
+    /// k线只交给关注该币种的策略，信号价取最近的标记价格，没有时为收盘价
+    fn input_kline(&self, kline: KlineData) {
+        let symbol = kline.symbol;
+        let signal = self
+            .prices
+            .get(&symbol)
+            .map(|p| p.clone())
+            .unwrap_or_else(|| SymbolPrice {
+                symbol,
+                mark_price: kline.candle.close,
+                price_index: kline.candle.close,
+                time: (kline.candle.close_time.unix_timestamp_nanos() / 1_000_000) as u64,
+                funding_rate: 0.,
+            });
+        for &index in self.router.route(symbol) {
+            if !self.is_running(index) {
+                continue;
+            }
+            if let Some(order_request) = self.strategies[index].update_kline(&kline) {
+                self.place(index, order_request, &signal);
+            }
+        }
+    }
+    /// 处理策略的下单请求，signal为产生请求时的行情
+    fn place(&self, index: usize, order_request: StrategyOrderRequest, signal: &SymbolPrice) {
+        if self.price_health.is_stale() {
+            warn!(
+                "Market data stale, order {} of strategy {} skipped",
+                order_request.symbol, index
+            );
+            return;
+        }
+        if order_request.position == 0. {
+            if self.exit(index, &order_request.symbol) {
+                self.throttle
+                    .record_order(&order_request.symbol, signal.time);
+            }
+            return;
+        }
+        if let Some(approval) = &self.approval {
+            let symbol = order_request.symbol.clone();
+            let id = approval.submit(index, order_request, signal.clone());
+            self.alert(
+                Severity::Warning,
+                "Trade idea",
+                &format!(
+                    "Strategy {} {} awaiting approval: #{} (POST /control/approve/{} or /control/deny/{})",
+                    index, symbol, id, id, id
+                ),
+            );
+            return;
+        }
+        self.enter(index, order_request, signal);
+    }
+    /// 开仓，人工确认模式下在批准后调用
+    fn enter(&self, index: usize, order_request: StrategyOrderRequest, signal: &SymbolPrice) {
+        let symbol = order_request.symbol.clone();
+        if !self.allow_entry(&symbol, signal) {
+            info!(
+                "Strategy {} order {} postponed until funding settled",
+                index, symbol
+            );
+            return;
+        }
+        let mut position = order_request.position;
+        if let Some((action, event)) = self
+            .events
+            .as_ref()
+            .and_then(|e| e.check(index, signal.time))
+        {
+            match action {
+                EventAction::Blackout => {
+                    info!(
+                        "Strategy {} order {} skipped: blackout around {}",
+                        index, symbol, event.name
+                    );
+                    return;
+                }
+                EventAction::Reduce(ratio) => {
+                    info!(
+                        "Strategy {} order {} reduced to {} around {}",
+                        index, symbol, ratio, event.name
+                    );
+                    position *= ratio;
+                }
+            }
+        }
+        let scale = self.guard_drawdown(signal.time);
+        if scale <= 0. {
+            info!(
+                "Strategy {} order {} skipped: drawdown deleveraged",
+                index, symbol
+            );
+            return;
+        }
+        position *= scale;
+        let value = position * self.ledger.trading_capital(index);
+        if let Err(reason) = self.correlation.check(
+            SymbolId::intern(&symbol),
+            value,
+            &self.exposures(),
+            *self.total_balance.lock(),
+        ) {
+            warn!("Strategy {} order {} skipped: {}", index, symbol, reason);
+            return;
+        }
+        if let Err(violation) = self.throttle.check_entry(index, &symbol, signal.time) {
+            info!(
+                "Strategy {} order {} throttled: {}",
+                index, symbol, violation
+            );
+            return;
+        }
+        if let Some(hedge) = &order_request.hedge {
+            self.enter_spread(index, &order_request, hedge, value, signal.time);
+            return;
+        }
+        if !self.ledger.reserve(index, &symbol, value) {
+            warn!(
+                "Strategy {} budget exceeded, order {} skipped",
+                index, symbol
+            );
+            return;
+        }
+        let result = MarketOrderRequest::new(
+            order_request.symbol,
+            true,
+            value,
+            order_request.stop_loss,
+            order_request.take_profit,
+        )
+        .map(|r| {
+            let r = r.with_tag(index, order_request.request_id);
+            let r = match order_request.time_in_force {
+                Some(time_in_force) => r.with_time_in_force(time_in_force),
+                None => r,
+            };
+            let r = match order_request.leverage {
+                Some(leverage) => r.with_leverage(leverage),
+                None => r,
+            };
+            match order_request.stop_limit_offset {
+                Some(offset) => r.with_stop_limit(offset),
+                None => r,
+            }
+        })
+        .and_then(|r| self.market.order(r));
+        if result.is_err() {
+            self.ledger.release(index, &symbol);
+        }
+        let tripped = match &result {
+            Ok(r) => {
+                info!(
+                    target: AUDIT_TARGET,
+                    "Strategy {} order {} filled: id {}, qty {}", index, symbol, r.order_id, r.qty
+                );
+                self.alert(
+                    Severity::Info,
+                    "Order filled",
+                    &format!(
+                        "Strategy {} {} qty {}, value {:.2}",
+                        index, symbol, r.qty, r.value
+                    ),
+                );
+                if let Some(e) = &r.margin_error {
+                    self.alert(
+                        Severity::Warning,
+                        "Margin buffer",
+                        &format!("Strategy {} {} add margin failed: {}", index, symbol, e),
+                    );
+                }
+                self.owners.insert(SymbolId::intern(&symbol), index);
+                self.track_children(&symbol, r);
+                self.throttle.record_order(&symbol, signal.time);
+                self.breakers[index].lock().record_success();
+                let unprotected = (!r.failed_legs.is_empty())
+                    .then(|| self.close_unprotected(index, std::slice::from_ref(&symbol)))
+                    .flatten();
+                // 策略可能由其他币种的行情触发下单，参考价取下单币种的标记价格
+                let mark_price = match SymbolId::get(&symbol) {
+                    Some(id) if id == signal.symbol => Some(signal.mark_price),
+                    Some(id) => self.prices.get(&id).map(|p| p.mark_price),
+                    None => None,
+                };
+                if let Some(prices) = mark_price.and_then(|mark_price| {
+                    self.tca.submit(
+                        index,
+                        order_request.request_id,
+                        true,
+                        mark_price,
+                        r.price,
+                        signal.time,
+                    )
+                }) {
+                    self.log_execution(index, &symbol, &prices);
+                }
+                unprotected
+            }
+            // 本地校验不通过不计入连续失败
+            Err(e @ MarketError::Rejected(_)) => {
+                warn!("Strategy {} order {} rejected: {}", index, symbol, e);
+                None
+            }
+            Err(e) => {
+                error!("Strategy {} order {} failed: {}", index, symbol, e);
+                self.breakers[index].lock().record_failure()
+            }
+        };
+        self.strategies[index].notify(StrategyOrderReturn {
+            request_id: order_request.request_id,
+            result: result.map(|r| Order {
+                order_id: r.order_id,
+                symbol,
+                filled_qty: r.qty,
+                // 下单响应没有成交价时为0，以之后的成交推送为准
+                average_price: r.fill_price.unwrap_or_default(),
+                ..Default::default()
+            }),
+        });
+        if let Some(reason) = tripped {
+            self.trip(index, &reason);
+        }
+    }
+    /// 记录开仓附带的止盈止损单，持仓归零或对账时移除
+    fn track_children(&self, symbol: &str, r: &MarketOrderReturn) {
+        let children = [(1, r.take_profit_id), (2, r.stop_loss_id)];
+        for (leg, order_id) in children {
+            let Some(order_id) = order_id else {
+                continue;
+            };
+            self.open_orders.insert(
+                order_id,
+                Order {
+                    order_id,
+                    symbol: symbol.to_string(),
+                    leg,
+                    status: "NEW".to_string(),
+                    reduce_only: true,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    /// 开仓已成交但止盈或止损单下单失败，仓位没有保护：立即平仓，平仓失败时返回熔断原因
+    fn close_unprotected(&self, index: usize, symbols: &[String]) -> Option<String> {
+        for symbol in symbols {
+            if let Err(e) = self
+                .market
+                .clear_orders(symbol)
+                .and_then(|_| self.market.close_position(symbol))
+            {
+                error!("Close unprotected position {} failed: {}", symbol, e);
+                return Some(format!("unprotected position {} not closed: {}", symbol, e));
+            }
+            info!(target: AUDIT_TARGET, "Strategy {} closed unprotected {}", index, symbol);
+            self.remove_owner(symbol);
+        }
+        self.alert(
+            Severity::Critical,
+            "Unprotected position closed",
+            &format!(
+                "Strategy {} {}: take profit or stop loss rejected",
+                index,
+                symbols.join("/")
+            ),
+        );
+        None
+    }
+    /// 组合单：买入主腿、卖出对冲腿，任一腿失败时回滚，回滚失败则熔断
+    fn enter_spread(
+        &self,
+        index: usize,
+        order_request: &StrategyOrderRequest,
+        hedge: &HedgeLeg,
+        value: f64,
+        time: u64,
+    ) {
+        let request = match SpreadOrderRequest::pair(
+            order_request.symbol.clone(),
+            hedge.symbol.clone(),
+            value,
+            hedge.ratio,
+            order_request.stop_loss,
+            order_request.take_profit,
+        ) {
+            Ok(r) => r.with_tag(index, order_request.request_id),
+            Err(e) => {
+                return warn!(
+                    "Strategy {} spread {}/{} rejected: {}",
+                    index, order_request.symbol, hedge.symbol, e
+                );
+            }
+        };
+        let legs = request.legs();
+        for (i, (symbol, leg_value)) in legs.iter().enumerate() {
+            if !self.ledger.reserve(index, symbol, *leg_value) {
+                for (symbol, _) in &legs[..i] {
+                    self.ledger.release(index, symbol);
+                }
+                return warn!(
+                    "Strategy {} budget exceeded, spread {}/{} skipped",
+                    index, order_request.symbol, hedge.symbol
+                );
+            }
+        }
+        let result = self.market.order_spread(request);
+        let symbols: Vec<String> = legs.into_iter().map(|(symbol, _)| symbol).collect();
+        if result.is_err() {
+            for symbol in symbols.iter() {
+                self.ledger.release(index, symbol);
+            }
+        }
+        let tripped = match &result {
+            Ok(r) => {
+                info!(
+                    target: AUDIT_TARGET,
+                    "Strategy {} spread {} filled: value {:.2}", index, symbols.join("/"), r.value()
+                );
+                self.alert(
+                    Severity::Info,
+                    "Spread filled",
+                    &format!(
+                        "Strategy {} {}, value {:.2}",
+                        index,
+                        symbols.join("/"),
+                        r.value()
+                    ),
+                );
+                for (symbol, leg) in symbols.iter().zip(r.legs.iter()) {
+                    self.owners.insert(SymbolId::intern(symbol), index);
+                    self.track_children(symbol, leg);
+                    self.throttle.record_order(symbol, time);
+                }
+                self.breakers[index].lock().record_success();
+                // 任一腿缺少保护时整组平仓，保持对冲
+                if r.legs.iter().any(|l| !l.failed_legs.is_empty()) {
+                    self.close_unprotected(index, &symbols)
+                } else {
+                    self.spreads
+                        .open(index, order_request.request_id, symbols.clone());
+                    None
+                }
+            }
+            // 已成交的腿未能回滚，交给熔断平仓
+            Err(e @ MarketError::Spread { unwind_errors, .. }) if !unwind_errors.is_empty() => {
+                for (symbol, _) in unwind_errors.iter() {
+                    self.owners.insert(SymbolId::intern(symbol), index);
+                }
+                Some(e.to_string())
+            }
+            Err(MarketError::Rejected(e)) => {
+                warn!("Strategy {} spread rejected: {}", index, e);
+                None
+            }
+            Err(e @ MarketError::Spread { error, .. })
+                if matches!(**error, MarketError::Rejected(_)) =>
+            {
+                warn!("Strategy {} spread rejected: {}", index, e);
+                None
+            }
+            Err(e) => {
+                error!("Strategy {} spread failed: {}", index, e);
+                self.breakers[index].lock().record_failure()
+            }
+        };
+        self.strategies[index].notify(StrategyOrderReturn {
+            request_id: order_request.request_id,
+            result: result.map(|r| Order {
+                order_id: r.legs[0].order_id,
+                symbol: symbols[0].clone(),
+                filled_qty: r.legs[0].qty,
+                ..Default::default()
+            }),
+        });
+        if let Some(reason) = tripped {
+            self.trip(index, &reason);
+        }
+    }
+    /// 策略主动平仓，属于组合单时平掉全部腿；只处理归属于该策略的币种，
+    /// 返回是否有仓位平仓成功
+    fn exit(&self, index: usize, symbol: &str) -> bool {
+        let symbols = self
+            .spreads
+            .legs_of(symbol)
+            .unwrap_or_else(|| vec![symbol.to_string()]);
+        let mut closed = false;
+        for symbol in symbols {
+            if self.owner(&symbol) != Some(index) {
+                continue;
+            }
+            match self.market.close_position(&symbol) {
+                Ok(_) => {
+                    info!(target: AUDIT_TARGET, "Strategy {} exit {}", index, symbol);
+                    closed = true;
+                }
+                Err(e) => error!("Strategy {} exit {} failed: {}", index, symbol, e),
+            }
+        }
+        closed
+    }
+    /// 组合单某条腿已平仓（止盈止损或强平），平掉其余的腿
+    fn leg_closed(&self, symbol: &str) {
+        match self.spreads.leg_closed(symbol) {
+            Some(LegClosed::Unhedged(symbols)) => {
+                for other in symbols {
+                    match self
+                        .market
+                        .clear_orders(&other)
+                        .and_then(|_| self.market.close_position(&other))
+                    {
+                        Ok(_) => info!(
+                            target: AUDIT_TARGET,
+                            "Spread leg {} closed, unwind {}", symbol, other
+                        ),
+                        Err(e) => error!("Unwind spread leg {} failed: {}", other, e),
+                    }
+                }
+            }
+            Some(LegClosed::Closed(spread)) => {
+                info!(target: AUDIT_TARGET, "{} closed", spread);
+                self.alert(Severity::Info, "Spread closed", &spread.to_string());
+            }
+            None => {}
+        }
+    }
+    /// 需支付资金费率的仓位在结算前平仓
+    fn guard_funding(&self, price: &SymbolPrice) {
+        let Some(funding) = &self.funding else {
+            return;
+        };
+        let Some(amount) = self.positions.get(&price.symbol).map(|p| p.position_amount) else {
+            return;
+        };
+        if !funding.should_close(&price.symbol, amount, price.funding_rate, price.time) {
+            return;
+        }
+        info!(
+            target: AUDIT_TARGET,
+            "Close {} before funding settlement, rate {}", price.symbol, price.funding_rate
+        );
+        if let Err(e) = self.market.close_position(&price.symbol) {
+            error!("Close {} before funding failed: {}", price.symbol, e);
+        }
+    }
+    /// 开多仓是否需推迟到资金费率结算之后
+    fn allow_entry(&self, symbol: &str, signal: &SymbolPrice) -> bool {
+        let Some(funding) = &self.funding else {
+            return true;
+        };
+        let funding_rate = if symbol == signal.symbol.as_str() {
+            signal.funding_rate
+        } else {
+            SymbolId::get(symbol)
+                .and_then(|id| self.prices.get(&id))
+                .map(|p| p.funding_rate)
+                .unwrap_or_default()
+        };
+        funding.allow_entry(true, funding_rate, signal.time)
+    }
+    /// 持有symbol仓位的策略序号
+    fn owner(&self, symbol: &str) -> Option<usize> {
+        SymbolId::get(symbol).and_then(|s| self.owners.get(&s).map(|o| *o))
+    }
+    fn remove_owner(&self, symbol: &str) -> Option<(SymbolId, usize)> {
+        SymbolId::get(symbol).and_then(|s| self.owners.remove(&s))
+    }
+    /// 各持仓的带方向名义价值，无标记价格时按开仓价估算
+    fn exposures(&self) -> Vec<(SymbolId, f64)> {
+        self.positions
+            .iter()
+            .filter(|p| p.position_amount != 0.)
+            .map(|p| {
+                let symbol = *p.key();
+                let price = self
+                    .prices
+                    .get(&symbol)
+                    .map(|s| s.mark_price)
+                    .unwrap_or(p.entry_price);
+                (symbol, p.position_amount * price)
+            })
+            .collect()
+    }
+    /// 逐仓仓位距强平过近时追加保证金，达到上限时报警
+    fn guard_margin(&self, price: &SymbolPrice) {
+        let Some(mut position) = self.positions.get_mut(&price.symbol) else {
+            return;
+        };
+        if !self.margin_guard.has_brackets(&price.symbol) {
+            if let Some(brackets) = self.market.margin_brackets(&price.symbol) {
+                self.margin_guard.set_brackets(&price.symbol, brackets);
+            }
+        }
+        let top_up = self.margin_guard.check(
+            &price.symbol,
+            position.position_amount,
+            position.entry_price,
+            position.isolated_wallet,
+            price.mark_price,
+        );
+        let (amount, capped) = match top_up {
+            Ok(None) => return,
+            Ok(Some(TopUp::Add(amount))) => (amount, false),
+            Ok(Some(TopUp::Capped(amount))) => (amount, true),
+            Err(e) => {
+                warn!("Margin guard check of {} failed: {}", price.symbol, e);
+                return;
+            }
+        };
+        if amount > 0. {
+            match self.market.add_margin(&price.symbol, amount) {
+                Ok(_) => {
+                    // 等待账户推送前先在本地记账，避免重复追加
+                    position.isolated_wallet += amount;
+                    info!(target: AUDIT_TARGET, "Add margin {} to {}", amount, price.symbol);
+                }
+                Err(e) => error!("Add margin to {} failed: {}", price.symbol, e),
+            }
+        }
+        drop(position);
+        if capped {
+            self.alert(
+                Severity::Warning,
+                "Margin cap reached",
+                &format!(
+                    "{} added margin reached cap {}, mark price {}",
+                    price.symbol,
+                    self.margin_guard.added(&price.symbol),
+                    price.mark_price
+                ),
+            );
+        }
+    }
+    /// 按time（unix毫秒）的账户权益更新回撤降杠杆级别，返回当前的开仓比例；
+    /// 降到0时平掉所有策略的仓位；首次收到账户余额前不更新
+    fn guard_drawdown(&self, time: u64) -> f64 {
+        let Some(deleverage) = &self.deleverage else {
+            return 1.;
+        };
+        let balance_known = *self.total_balance.lock() > 0.;
+        let mut deleverage = deleverage.lock();
+        if !balance_known {
+            return deleverage.scale();
+        }
+        let equity = self.equity();
+        let Some(scale) = deleverage.update(equity, time) else {
+            return deleverage.scale();
+        };
+        let drawdown = deleverage.drawdown(equity);
+        drop(deleverage);
+        if scale <= 0. {
+            let owners: Vec<(SymbolId, usize)> =
+                self.owners.iter().map(|o| (*o.key(), *o.value())).collect();
+            for (symbol, index) in owners {
+                self.exit(index, &symbol);
+            }
+        }
+        self.alert(
+            Severity::Critical,
+            "Drawdown deleverage",
+            &format!(
+                "Account drawdown {:.2}%, entry scale {}",
+                drawdown * 100.,
+                scale
+            ),
+        );
+        scale
+    }
+    /// 账户权益：钱包余额加未实现盈亏
+    fn equity(&self) -> f64 {
+        *self.total_balance.lock()
+            + self
+                .position_snapshots()
+                .iter()
+                .map(|p| p.unrealized_pnl())
+                .sum::<f64>()
+    }
+    /// 未被熔断或手动暂停，且在计划的启用时段内
+    fn is_running(&self, index: usize) -> bool {
+        !self.breakers[index].lock().is_paused()
+            && self.schedule.as_ref().is_none_or(|s| s.is_active(index))
+    }
+    /// 按行情时间更新计划，策略停用时按配置平仓
+    fn apply_schedule(&self, time: u64) {
+        let Some(schedule) = &self.schedule else {
+            return;
+        };
+        for (index, active) in schedule.update(self.strategies.len(), time) {
+            let state = if active { "enabled" } else { "disabled" };
+            info!(target: AUDIT_TARGET, "Strategy {} {} by schedule", index, state);
+            self.alert(
+                Severity::Info,
+                "Schedule",
+                &format!("Strategy {} {} by schedule", index, state),
+            );
+            if !active && schedule.closes(index) {
+                self.close_strategy(index);
+            }
+        }
+    }
+    /// 熔断：暂停策略、平掉其所有仓位并发送警报
+    fn trip(&self, index: usize, reason: &str) {
+        self.breakers[index].lock().pause();
+        self.close_strategy(index);
+        self.alert(
+            Severity::Critical,
+            "Kill switch",
+            &format!("Strategy {} paused: {}", index, reason),
+        );
+    }
+    /// 平掉策略的所有仓位
+    fn close_strategy(&self, index: usize) {
+        let symbols: Vec<SymbolId> = self
+            .owners
+            .iter()
+            .filter(|o| *o.value() == index)
+            .map(|o| *o.key())
+            .collect();
+        for symbol in symbols {
+            match self.market.close_position(&symbol) {
+                Ok(_) => {
+                    info!(target: AUDIT_TARGET, "Strategy {} closed {}", index, symbol);
+                    self.owners.remove(&symbol);
+                }
+                Err(e) => error!("Close position {} failed: {}", symbol, e),
+            }
+        }
+    }
+    fn alert(&self, severity: Severity, title: &str, message: &str) {
+        self.notifiers.notify(severity, title, message);
+    }
+    fn control(&self, command: ControlCommand) {
+        match command {
+            ControlCommand::Enable(index) => {
+                if let Some(breaker) = self.breakers.get(index) {
+                    breaker.lock().reset();
+                    info!("Strategy {} enabled", index);
+                }
+            }
+            ControlCommand::Pause(index) => {
+                if index < self.breakers.len() {
+                    self.trip(index, "paused manually");
+                }
+            }
+            ControlCommand::Delist(symbol) => self.delist(&symbol),
+            ControlCommand::ResetDrawdown => {
+                if let Some(deleverage) = &self.deleverage {
+                    let equity = self.equity();
+                    deleverage.lock().reset(equity);
+                    info!("Drawdown deleverage reset at equity {:.2}", equity);
+                }
+            }
+            ControlCommand::Approve(id) => self.approve(id),
+            ControlCommand::Deny(id) => match self.approval.as_ref().and_then(|a| a.take(id)) {
+                Some(idea) => self.reject_idea(idea, "denied"),
+                None => warn!("Trade idea #{} not found or expired", id),
+            },
+        }
+    }
+    /// 批准后按最新行情开仓，行情过期时放弃
+    fn approve(&self, id: u64) {
+        let Some(idea) = self.approval.as_ref().and_then(|a| a.take(id)) else {
+            warn!("Trade idea #{} not found or expired", id);
+            return;
+        };
+        if self.price_health.is_stale() {
+            self.reject_idea(idea, "market data stale");
+            return;
+        }
+        info!(target: AUDIT_TARGET, "Trade idea {} approved", idea);
+        let signal = self
+            .prices
+            .get(&SymbolId::intern(&idea.request.symbol))
+            .map(|p| p.clone())
+            .unwrap_or(idea.signal);
+        self.enter(idea.index, idea.request, &signal);
+    }
+    /// 拒绝或超时的请求以Rejected通知策略
+    fn reject_idea(&self, idea: TradeIdea, reason: &str) {
+        info!(target: AUDIT_TARGET, "Trade idea {} {}", idea, reason);
+        self.strategies[idea.index].notify(StrategyOrderReturn {
+            request_id: idea.request.request_id,
+            result: Err(MarketError::Rejected(format!("trade idea {}", reason))),
+        });
+    }
+    fn expire_ideas(&self, now: u64) {
+        let Some(approval) = &self.approval else {
+            return;
+        };
+        for idea in approval.expire(now) {
+            self.reject_idea(idea, "expired");
+        }
+    }
+    /// 币种进入下架流程，平掉其仓位（属于组合单时连同其余的腿）
+    fn delist(&self, symbol: &str) {
+        match self.owner(symbol) {
+            Some(index) => {
+                self.exit(index, symbol);
+            }
+            None => {
+                let holding = SymbolId::get(symbol)
+                    .and_then(|s| self.positions.get(&s))
+                    .is_some_and(|p| p.position_amount != 0.);
+                if !holding {
+                    return;
+                }
+                if let Err(e) = self.market.close_position(symbol) {
+                    error!("Close delisting {} failed: {}", symbol, e);
+                }
+            }
+        }
+        self.alert(
+            Severity::Warning,
+            "Symbol delisting",
+            &format!("{} is delisting, position closed", symbol),
+        );
+    }
+    fn position_snapshots(&self) -> Vec<PositionSnapshot> {
+        self.positions
+            .iter()
+            .filter(|p| p.position_amount != 0.)
+            .map(|p| {
+                let mark_price = self
+                    .prices
+                    .get(p.key())
+                    .map(|price| price.mark_price)
+                    .unwrap_or(p.entry_price);
+                PositionSnapshot {
+                    symbol: p.key().to_string(),
+                    amount: p.position_amount,
+                    entry_price: p.entry_price,
+                    mark_price,
+                    close_fee: self
+                        .fees
+                        .fee(p.position_amount * mark_price, FillType::Taker),
+                }
+            })
+            .collect()
+    }
+    /// 各策略的运行状态，持仓按归属划分
+    fn strategy_status(&self) -> Vec<StrategyStatus> {
+        let positions = self.position_snapshots();
+        self.strategies
+            .iter()
+            .enumerate()
+            .map(|(index, strategy)| StrategyStatus {
+                index,
+                name: strategy.name(),
+                symbols: strategy.symbols(),
+                paused: !self.is_running(index),
+                positions: positions
+                    .iter()
+                    .filter(|p| self.owner(&p.symbol) == Some(index))
+                    .cloned()
+                    .collect(),
+                realized_pnl: self
+                    .ledger
+                    .account(index)
+                    .map(|a| a.realized_pnl - a.commission)
+                    .unwrap_or_default(),
+                params: strategy.params(),
+                throttled: self.throttle.stats(index),
+            })
+            .collect()
+    }
+    /// 是否需要定时采样权益
+    fn samples_equity(&self) -> bool {
+        #[cfg(feature = "dashboard")]
+        if self.dashboard.is_some() {
+            return true;
+        }
+        self.equity_tx.is_some()
+    }
+    /// 推送一次权益采样，接收端断开后不再推送；同时向监控面板推送状态
+    fn sample_equity(&self) {
+        let positions = self.position_snapshots();
+        let sample = EquitySample {
+            time: unix_millis(),
+            total_balance: *self.total_balance.lock(),
+            unrealized_pnl: positions.iter().map(|p| p.unrealized_pnl()).sum(),
+            base_rate: self
+                .base_currency
+                .as_ref()
+                .and_then(|b| b.rate(&self.quote_asset, &self.prices)),
+        };
+        if let Some(tx) = &self.equity_tx {
+            tx.try_send(sample).ok();
+        }
+        #[cfg(feature = "dashboard")]
+        if let Some(dashboard) = &self.dashboard {
+            dashboard.publish(DashboardEvent::Equity(sample));
+            dashboard.publish(DashboardEvent::Status {
+                time: sample.time,
+                positions,
+                strategies: self.strategy_status(),
+                streams: vec![StreamStatus::new("price", &self.price_health)],
+                base_currency: sample
+                    .base_rate
+                    .and(self.base_currency.as_ref())
+                    .map(|b| b.currency.clone()),
+            });
+        }
+    }
+    fn state_key(index: usize, strategy: &dyn Strategy) -> String {
+        format!("{}:{}", index, strategy.name())
+    }
+    /// 将各策略的状态快照写入数据库
+    fn save_states(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let time = unix_millis();
+        for (index, strategy) in self.strategies.iter().enumerate() {
+            let key = Self::state_key(index, strategy.as_ref());
+            let result = match strategy.snapshot(time) {
+                None => continue,
+                Some(state) => state.and_then(|s| store.save_strategy_state(&key, &s)),
+            };
+            if let Err(e) = result {
+                error!("Save state of strategy {} failed: {}", key, e);
+            }
+        }
+    }
+    /// 启动时从数据库恢复各策略的状态
+    fn restore_states(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        for (index, strategy) in self.strategies.iter().enumerate() {
+            let key = Self::state_key(index, strategy.as_ref());
+            if strategy.snapshot(unix_millis()).is_none() {
+                warn!(
+                    "Strategy {} has no snapshot, its state is lost on restart",
+                    key
+                );
+            }
+            let result = store
+                .load_strategy_state(&key)
+                .and_then(|state| match state {
+                    Some(state) => {
+                        info!("Restore strategy {} from snapshot @ {}", key, state.time);
+                        strategy.restore(state)
+                    }
+                    None => Ok(()),
+                });
+            if let Err(e) = result {
+                error!("Restore state of strategy {} failed: {}", key, e);
+                self.alert(
+                    Severity::Critical,
+                    "Strategy state not restored",
+                    &format!("{}: {}", key, e),
+                );
+            }
+        }
+    }
+    /// 生成账户快照和每日盈亏报告，写入日志、通知和数据库
+    fn report(&self) {
+        let positions = self.position_snapshots();
+        let snapshot = AccountSnapshot {
+            time: unix_millis(),
+            total_balance: *self.total_balance.lock(),
+            cross_balance: *self.cross_balance.lock(),
+            positions,
+            realized_pnl: std::mem::take(&mut *self.realized_pnl.lock()),
+        };
+        let mut report = snapshot.to_string();
+        if let Some(base) = &self.base_currency {
+            match base.rate(&self.quote_asset, &self.prices) {
+                Some(rate) => report += &base.convert(&snapshot, rate).to_string(),
+                None => {
+                    report += &format!("no index price of {}", base.index_symbol(&self.quote_asset))
+                }
+            }
+        }
+        for status in self.strategy_status() {
+            report += &format!("\n{}", status);
+        }
+        for spread in self.spreads.positions() {
+            report += &format!(
+                "\n{}, unrealized pnl: {:.2}",
+                spread,
+                spread.unrealized_pnl(&snapshot.positions)
+            );
+        }
+        for (index, stats) in self.tca.stats() {
+            report += &format!("\nStrategy {}: {}", index, stats);
+        }
+        for (index, account) in (0..self.strategies.len())
+            .filter_map(|i| Some((i, self.ledger.account(i)?)))
+            .filter(|(_, a)| a.profit_reserve() > 0.)
+        {
+            report += &format!(
+                "\nStrategy {}: trading capital {:.2}, reserve {:.2} ({:?})",
+                index,
+                account.trading_capital(),
+                account.profit_reserve(),
+                account.capital.policy()
+            );
+        }
+        for index in 0..self.strategies.len() {
+            let stats = self.throttle.stats(index);
+            if stats.total() > 0 {
+                report += &format!("\nStrategy {}: {}", index, stats);
+            }
+        }
+        if let Some(shadow) = self.market.shadow_report().filter(|s| s.trades > 0) {
+            report += &format!("\n{}", shadow);
+        }
+        info!("{}", report);
+        self.alert(Severity::Report, "Daily report", &report);
+        self.sweep(snapshot.total_balance);
+        if let Some(store) = &self.store {
+            let last = store.last_snapshot_time();
+            if let Err(e) = store.insert_snapshot(&snapshot) {
+                error!("Save snapshot failed: {}", e);
+            }
+            match last {
+                Ok(Some(last)) => self.reconcile_income(store, last, &snapshot),
+                Ok(None) => {}
+                Err(e) => error!("Load last snapshot time failed: {}", e),
+            }
+        }
+    }
+    /// 到达划转日时将钱包余额中超出保留资金的部分划出
+    fn sweep(&self, balance: f64) {
+        let Some(treasury) = &self.treasury else {
+            return;
+        };
+        if !treasury.policy().is_due(local_now()) {
+            return;
+        }
+        match treasury.sweep(balance) {
+            Ok(Some((amount, id))) => {
+                let msg = format!(
+                    "Swept {:.2} {} to {:?}, transfer {}",
+                    amount,
+                    treasury.policy().asset,
+                    treasury.policy().to,
+                    id
+                );
+                info!("{}", msg);
+                self.alert(Severity::Info, "Treasury sweep", &msg);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("Treasury sweep failed: {}", e);
+                self.alert(
+                    Severity::Warning,
+                    "Treasury sweep",
+                    &format!("Sweep failed: {}", e),
+                );
+            }
+        }
+    }
+    /// 有未成交开仓单的币种，不含只减仓的止盈止损单
+    fn pending_entries(&self) -> Vec<String> {
+        self.open_orders
+            .iter()
+            .filter(|o| !o.reduce_only)
+            .map(|o| o.symbol.clone())
+            .collect()
+    }
+    /// 为有未成交开仓单的币种刷新交易所的倒计时撤单，失败时告警；
+    /// 持仓币种只挂着止盈止损单，不设倒计时，避免程序退出后仓位失去保护
+    fn refresh_dead_man(&self) {
+        let Some(dead_man) = &self.dead_man else {
+            return;
+        };
+        for (symbol, e) in dead_man.refresh(&self.pending_entries()) {
+            error!("Dead man switch refresh {} failed: {}", symbol, e);
+            self.alert(
+                Severity::Warning,
+                "Dead man switch",
+                &format!("Refresh countdown of {} failed: {}", symbol, e),
+            );
+        }
+    }
+    fn log_execution(&self, index: usize, symbol: &str, prices: &ExecutionPrices) {
+        info!(
+            target: AUDIT_TARGET,
+            "Strategy {} {} execution: signal {}, sizing {}, fill {}, slippage {:.2}bp",
+            index,
+            symbol,
+            prices.signal,
+            prices.sizing,
+            prices.fill,
+            prices.signal_slippage()
+        );
+    }
+    /// 导入交易所资金流水，与上次报告以来统计的已实现盈亏对账，偏差过大时告警
+    fn reconcile_income(&self, store: &Store, start: u64, snapshot: &AccountSnapshot) {
+        let Some(client) = &self.income else {
+            return;
+        };
+        let from = match store.last_income_time() {
+            Ok(time) => time.unwrap_or(start),
+            Err(e) => return error!("Load income failed: {}", e),
+        };
+        let incomes = match client.fetch(from, snapshot.time) {
+            Ok(incomes) => incomes,
+            Err(e) => return error!("Import income failed: {}", e),
+        };
+        if let Err(e) = store.insert_incomes(&incomes) {
+            return error!("Save income failed: {}", e);
+        }
+        let totals = match store.income_totals(&self.quote_asset, start, snapshot.time) {
+            Ok(totals) => totals,
+            Err(e) => return error!("Load income failed: {}", e),
+        };
+        info!(
+            "Income {} - {}: realized pnl {:.4}, commission {:.4}, funding fee {:.4}",
+            start, snapshot.time, totals.realized_pnl, totals.commission, totals.funding_fee
+        );
+        if let Some(divergence) = PnlDivergence::check(
+            start,
+            snapshot.time,
+            totals.realized_pnl,
+            snapshot.realized_pnl,
+            self.pnl_tolerance,
+        ) {
+            warn!("PnL divergence: {}", divergence);
+            self.alert(Severity::Warning, "PnL divergence", &divergence.to_string());
+        }
+    }
+    fn input_depth(&self, depth: DepthData) {
+        for algorithm in self.algorithms.iter() {
+            if let Some(data) = algorithm.update_depth(&depth) {
+                self.broadcast_signal(&data);
+            }
+        }
+    }
+    fn broadcast_signal(&self, data: &SignalData) {
         for strategy in self.strategies.iter() {
-            if let Some(order_request) = strategy.update(&signal) {
-                let market_order_request = MarketOrderRequest::new(
-                    order_request.symbol,
-                    true,
-                    order_request.position * *self.cross_balance.lock(),
-                    order_request.stop_loss,
-                    order_request.take_profit,
-                )
-                .unwrap();
-
-                self.market.order(market_order_request);
-                // send order request to exchange
+            strategy.update_signal(data);
+        }
+    }
+    /// 成交写入数据库，用于导出税务批次
+    fn save_fill(&self, time: u64, order: &OrderUpdate, commission: f64) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let qty: f64 = order.qty_last_filled_trade.parse().unwrap_or_default();
+        let fill = Fill {
+            time,
+            symbol: order.symbol.clone(),
+            qty: if order.side == "BUY" { qty } else { -qty },
+            price: order.price_last_filled_trade.parse().unwrap_or_default(),
+            fee: commission,
+        };
+        if let Err(e) = store.insert_fill(order.trade_id, &fill) {
+            error!("Save fill of {} failed: {}", order.symbol, e);
+        }
+    }
+    /// 已实现盈亏计入统计和持仓策略的熔断器
+    fn record_pnl(&self, symbol: &str, time: u64, pnl: f64) {
+        *self.realized_pnl.lock() += pnl;
+        let Some(index) = self.owner(symbol) else {
+            return;
+        };
+        let tripped = self.breakers[index].lock().record_pnl(time, pnl);
+        if let Some(reason) = tripped {
+            self.trip(index, &reason);
+        }
+    }
+    /// 交易所强平或自动减仓：亏损计入持仓策略的子账户并暂停该策略，
+    /// 订单结束后撤销剩余的止盈止损单并平掉残余仓位
+    fn forced_close(&self, kind: ForcedClose, order: &OrderUpdate, pnl: f64, commission: f64) {
+        let symbol = &order.symbol;
+        let owner = self.owner(symbol);
+        if let Some(index) = owner {
+            self.ledger.record_fill(index, pnl, commission);
+            self.breakers[index].lock().pause();
+        }
+        if !forced_close::is_final(order) {
+            return;
+        }
+        let residual = self
+            .market
+            .clear_orders(symbol)
+            .and_then(|_| self.market.close_position(symbol));
+        match &residual {
+            Ok(_) => {
+                info!(target: AUDIT_TARGET, "{} of {}: residual closed", kind.title(), symbol);
+                if let Some((symbol, index)) = self.remove_owner(symbol) {
+                    self.ledger.release(index, &symbol);
+                }
+            }
+            Err(e) => error!("Close residual of {} failed: {}", symbol, e),
+        }
+        let owner = match owner {
+            Some(index) => format!("strategy {} paused", index),
+            None => "no owning strategy".to_string(),
+        };
+        self.alert(
+            Severity::Critical,
+            kind.title(),
+            &format!(
+                "{} {} {} @ {}, realized pnl {:.2}, {}, residual {}",
+                symbol,
+                order.side,
+                order.accumulated_qty_filled_trades,
+                order.average_price,
+                pnl,
+                owner,
+                match residual {
+                    Ok(_) => "closed".to_string(),
+                    Err(e) => format!("close failed: {}", e),
+                }
+            ),
+        );
+    }
+    /// 追加保证金通知：暂停持有这些仓位的策略，不再开新仓
+    fn margin_call(&self, positions: &[MarginCallPosition]) {
+        let mut message = vec![];
+        for p in positions {
+            let owner = self.owner(&p.symbol);
+            if let Some(index) = owner {
+                self.breakers[index].lock().pause();
             }
+            message.push(format!(
+                "{} {} @ {}, maint margin {:.2}, strategy {}",
+                p.symbol,
+                p.amount,
+                p.mark_price,
+                p.maint_margin,
+                owner.map(|i| i.to_string()).unwrap_or("-".into())
+            ));
         }
+        self.alert(Severity::Critical, "Margin call", &message.join("\n"));
+    }
+    /// 按订单推送维护未成交的订单，结束的订单移除
+    fn track_order(&self, order: &OrderUpdate) {
+        if !matches!(order.order_status.as_str(), "NEW" | "PARTIALLY_FILLED") {
+            self.open_orders.remove(&order.order_id);
+            return;
+        }
+        let leg = ClientOrderId::parse(&order.new_client_order_id)
+            .map(|id| id.leg)
+            .unwrap_or_default();
+        let mut tracked = Order::from_update(order, leg);
+        tracked.reduce_only = order.is_reduce_only || order.close_all == Some(true);
+        self.open_orders.insert(order.order_id, tracked);
     }
     fn update_account(&self, account_info: AccountInfo) {
         match account_info {
             AccountInfo::OrderTrade { time, order } => {
-                // update open orders and positions
+                self.track_order(&order);
+                let pnl: f64 = order.realized_profit.parse().unwrap_or_default();
+                let commission: f64 = order
+                    .commission
+                    .as_deref()
+                    .and_then(|c| c.parse().ok())
+                    .unwrap_or_default();
+                if let Some(id) = ClientOrderId::parse(&order.new_client_order_id) {
+                    self.ledger.record_fill(id.strategy, pnl, commission);
+                    // 只统计开仓单
+                    if id.leg == 0 && order.order_status == "FILLED" {
+                        let average_price = order.average_price.parse().unwrap_or_default();
+                        if let Some(prices) =
+                            self.tca
+                                .fill(id.strategy, id.request_id, average_price, time)
+                        {
+                            self.log_execution(id.strategy, &order.symbol, &prices);
+                        }
+                    }
+                    if let Some(strategy) = self.strategies.get(id.strategy) {
+                        strategy.notify(StrategyOrderReturn {
+                            request_id: id.request_id,
+                            result: Ok(Order::from_update(&order, id.leg)),
+                        });
+                    }
+                }
+                #[cfg(feature = "dashboard")]
+                if let Some(dashboard) = &self.dashboard {
+                    if order.execution_type == "TRADE" {
+                        let strategy = Ledger::strategy_of(&order.new_client_order_id)
+                            .or_else(|| self.owner(&order.symbol));
+                        dashboard.publish(DashboardEvent::Fill(FillUpdate::new(
+                            time, &order, strategy,
+                        )));
+                    }
+                }
+                if order.execution_type == "TRADE" {
+                    self.save_fill(time, &order, commission);
+                }
+                self.spreads.record_fill(&order.symbol, pnl - commission);
+                if pnl != 0. {
+                    self.record_pnl(&order.symbol, time, pnl);
+                }
+                if let Some(kind) = ForcedClose::parse(&order) {
+                    self.forced_close(kind, &order, pnl, commission);
+                }
             }
             AccountInfo::AccountUpdate { time, data } => {
                 self.update_time.store(time, Ordering::Relaxed);
                 for b in data.balances {
-                    if b.asset == "USDT" {
+                    if b.asset == self.quote_asset {
                         *self.total_balance.lock() = b.wallet_balance.parse().unwrap();
                         *self.cross_balance.lock() = b.cross_wallet_balance.parse().unwrap();
                     }
                 }
                 for p in data.positions {
-                    let mut position = self.positions.entry(p.symbol.clone()).or_default();
+                    let mut position = self
+                        .positions
+                        .entry(SymbolId::intern(&p.symbol))
+                        .or_default();
                     position.entry_price = p.entry_price.parse().unwrap();
                     position.position_amount = p.position_amount.parse().unwrap();
                     position.isolated_wallet = p.isolated_wallet.parse().unwrap();
+                    if position.position_amount == 0. {
+                        drop(position);
+                        // 止盈止损随仓位失效，未成交的开仓单保留
+                        self.open_orders
+                            .retain(|_, o| o.symbol != p.symbol || !o.reduce_only);
+                        if let Some((symbol, index)) = self.remove_owner(&p.symbol) {
+                            self.ledger.release(index, &symbol);
+                            self.throttle.record_exit(&symbol, time);
+                        }
+                        self.leg_closed(&p.symbol);
+                    }
+                }
+            }
+            AccountInfo::MarginCall { time, positions } => {
+                self.update_time.store(time, Ordering::Relaxed);
+                self.margin_call(&positions);
+            }
+            AccountInfo::Reconcile {
+                time,
+                balance,
+                positions,
+                open_orders,
+            } => {
+                self.update_time.store(time, Ordering::Relaxed);
+                if let Some(balance) = balance {
+                    *self.total_balance.lock() = balance;
+                }
+                // 未返回的币种均为空仓
+                self.positions.retain(|symbol, position| {
+                    if positions.iter().all(|p| p.symbol != symbol.as_str()) {
+                        if position.position_amount != 0. {
+                            warn!("Reconcile: position {} closed while disconnected", symbol);
+                        }
+                        return false;
+                    }
+                    true
+                });
+                for p in positions {
+                    let mut position = self
+                        .positions
+                        .entry(SymbolId::intern(&p.symbol))
+                        .or_default();
+                    if position.position_amount != p.position_amount {
+                        warn!(
+                            "Reconcile: position {} amount {} -> {}",
+                            p.symbol, position.position_amount, p.position_amount
+                        );
+                    }
+                    position.entry_price = p.entry_price;
+                    position.position_amount = p.position_amount;
+                    // isolated_margin包含未实现盈亏
+                    position.isolated_wallet = p.isolated_margin - p.unrealized_profit;
+                }
+                self.open_orders.clear();
+                for o in open_orders {
+                    self.open_orders.insert(
+                        o.order_id,
+                        Order {
+                            order_id: o.order_id,
+                            symbol: o.symbol,
+                            status: o.status,
+                            filled_qty: o.executed_qty,
+                            reduce_only: o.reduce_only,
+                            ..Default::default()
+                        },
+                    );
                 }
             }
         }
@@ -108,8 +1691,52 @@ struct Position {
     position_amount: f64,
     isolated_wallet: f64,
 }
-#[derive(Debug)]
-pub struct Order {}
+#[derive(Debug, Default)]
+pub struct Order {
+    pub order_id: u64,
+    pub symbol: String,
+    /// 0为开仓单，之后为附属单
+    pub leg: usize,
+    pub status: String,
+    pub filled_qty: f64,
+    pub average_price: f64,
+    /// 只减仓，止盈止损等平仓单
+    pub reduce_only: bool,
+}
+
+impl Order {
+    fn from_update(order: &OrderUpdate, leg: usize) -> Self {
+        Self {
+            order_id: order.order_id,
+            symbol: order.symbol.clone(),
+            leg,
+            status: order.order_status.clone(),
+            filled_qty: order
+                .accumulated_qty_filled_trades
+                .parse()
+                .unwrap_or_default(),
+            average_price: order.average_price.parse().unwrap_or_default(),
+            reduce_only: order.is_reduce_only,
+        }
+    }
+}
+
+/// 控制接口命令
+#[derive(Debug, Clone)]
+pub enum ControlCommand {
+    /// 恢复被熔断或暂停的策略，参数为策略序号
+    Enable(usize),
+    /// 暂停策略并平仓，参数为策略序号
+    Pause(usize),
+    /// 币种下架，平掉其持仓，见BinanceMarket::run_exchange_info_refresh
+    Delist(String),
+    /// 以当前权益为新高点恢复回撤降杠杆，全部平仓后权益不再变化，需手动恢复
+    ResetDrawdown,
+    /// 批准人工确认模式下排队的开仓请求，参数为请求编号
+    Approve(u64),
+    /// 拒绝排队的开仓请求
+    Deny(u64),
+}
 
 pub enum AccountInfo {
     OrderTrade {
@@ -120,4 +1747,760 @@ pub enum AccountInfo {
         time: u64,
         data: AccountUpdateDataEvent,
     },
+    /// 保证金不足，持仓接近强平
+    MarginCall {
+        time: u64,
+        positions: Vec<MarginCallPosition>,
+    },
+    /// 重连后通过REST获取的账户状态，用于修正丢失事件造成的偏差
+    Reconcile {
+        time: u64,
+        balance: Option<f64>,
+        positions: Vec<PositionRisk>,
+        open_orders: Vec<binance::futures::model::Order>,
+    },
+}
+
+#[cfg(test)]
+use crate::market::mock_market::{MockCall, MockMarket, MockResponse};
+
+/// 每个行情都按10%仓位买入，记录下单结果
+#[cfg(test)]
+#[derive(Debug, Default)]
+struct AlwaysBuy {
+    results: Arc<Mutex<Vec<bool>>>,
+    /// 设置时以组合单开仓
+    hedge: Option<HedgeLeg>,
+    /// 设置时按该杠杆开仓
+    leverage: Option<u8>,
+}
+
+#[cfg(test)]
+impl Strategy for AlwaysBuy {
+    fn notify(&self, order_return: StrategyOrderReturn) {
+        self.results.lock().push(order_return.result.is_ok());
+    }
+    fn update(&self, price: &SymbolPrice) -> Option<StrategyOrderRequest> {
+        Some(StrategyOrderRequest {
+            request_id: price.time,
+            symbol: price.symbol.to_string(),
+            position: 0.1,
+            stop_loss: 0.9,
+            take_profit: 1.1,
+            time_in_force: Default::default(),
+            stop_limit_offset: None,
+            hedge: self.hedge.clone(),
+            leverage: self.leverage,
+        })
+    }
+    fn params(&self) -> Vec<(String, String)> {
+        vec![("position".into(), "0.1".into())]
+    }
+}
+
+#[cfg(test)]
+fn controller(market: MockMarket) -> (Controller<MockMarket>, Arc<Mutex<Vec<bool>>>) {
+    let strategy = AlwaysBuy::default();
+    let results = strategy.results.clone();
+    let controller = Controller::new(
+        market,
+        vec![Box::new(strategy)],
+        &[1000.],
+        Default::default(),
+    );
+    (controller, results)
+}
+
+#[cfg(test)]
+fn price(time: u64) -> SymbolPrice {
+    SymbolPrice {
+        symbol: "BTCUSDT".into(),
+        mark_price: 100.,
+        time,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn controller_circuit_breaker_test() {
+    let market = MockMarket::new(1000.);
+    market.set_price("BTCUSDT", 100.);
+    market.push(MockResponse::Fill);
+    market.push(MockResponse::Reject("min notional".into()));
+    for _ in 0..5 {
+        market.push(MockResponse::Request("timeout".into()));
+    }
+    let (controller, results) = controller(market);
+    for time in 0..7 {
+        controller.input_signal(price(time));
+    }
+    assert_eq!(
+        *results.lock(),
+        [true, false, false, false, false, false, false]
+    );
+    // 本地拒单不计入连续失败，第5次网络错误触发熔断并平掉已开的仓位
+    assert!(controller.breakers[0].lock().is_paused());
+    assert_eq!(
+        controller.market.calls().last(),
+        Some(&MockCall::ClosePosition("BTCUSDT".into()))
+    );
+    assert_eq!(controller.market.position("BTCUSDT"), 0.);
+    controller.input_signal(price(7));
+    assert_eq!(results.lock().len(), 7);
+}
+
+#[test]
+fn controller_circuit_breaker_alert_test() {
+    use crate::notifier::Notifier;
+
+    #[derive(Debug)]
+    struct Channel(Sender<String>);
+    impl Notifier for Channel {
+        fn notify(&self, title: &str, _message: &str) -> anyhow::Result<()> {
+            Ok(self.0.send(title.to_string())?)
+        }
+    }
+
+    let market = MockMarket::new(1000.);
+    market.set_price("BTCUSDT", 100.);
+    for _ in 0..2 {
+        market.push(MockResponse::Request("timeout".into()));
+    }
+    let config: ControllerConfig = toml::from_str(
+        r#"[circuit_breaker]
+           max_failures = 2"#,
+    )
+    .unwrap();
+    let strategies: Vec<Box<dyn Strategy>> = vec![Box::new(AlwaysBuy::default())];
+    let mut controller = Controller::from_config(
+        market,
+        strategies,
+        &[1000.],
+        Default::default(),
+        &config,
+        None,
+    )
+    .unwrap();
+    let (tx, rx) = crossbeam::channel::unbounded();
+    controller
+        .notifiers
+        .add(Box::new(Channel(tx)), &[Severity::Critical]);
+    controller.input_signal(price(0));
+    assert!(rx.try_recv().is_err());
+    controller.input_signal(price(1));
+    // 第2次失败触发熔断，告警经通知路由送达渠道
+    assert!(controller.breakers[0].lock().is_paused());
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["Kill switch"]);
+}
+
+#[test]
+fn controller_account_events_test() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    let (controller, _) = controller(market);
+    controller.input_signal(price(0));
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    let position = controller.positions.get(&"BTCUSDT".into()).unwrap();
+    assert!((position.position_amount - 1.).abs() < 1e-9);
+    assert!((position.isolated_wallet - 10.).abs() < 1e-9);
+    drop(position);
+    assert_eq!(controller.owner("BTCUSDT"), Some(0));
+    // 平仓的已实现盈亏计入统计，仓位归零后释放预算
+    let status = &controller.strategy_status()[0];
+    assert_eq!(status.name, "AlwaysBuy");
+    assert_eq!(status.positions.len(), 1);
+    assert_eq!(status.params, [("position".to_string(), "0.1".to_string())]);
+    assert!(controller.strategies[0]
+        .downcast_ref::<AlwaysBuy>()
+        .is_some());
+    controller.market.set_price("BTCUSDT", 110.);
+    controller.market.close_position("BTCUSDT").unwrap();
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    let position = controller.positions.get(&"BTCUSDT".into()).unwrap();
+    assert_eq!(position.position_amount, 0.);
+    assert!(controller.owner("BTCUSDT").is_none());
+    assert!(controller.ledger.account(0).unwrap().exposure.is_empty());
+    assert!((*controller.realized_pnl.lock() - 10.).abs() < 1e-9);
+    assert!((*controller.total_balance.lock() - controller.market.balance()).abs() < 1e-9);
+}
+
+#[test]
+fn controller_delist_test() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    let (controller, _) = controller(market);
+    controller.input_signal(price(0));
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    assert_eq!(controller.market.position("BTCUSDT"), 1.);
+    controller.control(ControlCommand::Delist("ETHUSDT".to_string()));
+    assert_eq!(controller.market.position("BTCUSDT"), 1.);
+    controller.control(ControlCommand::Delist("BTCUSDT".to_string()));
+    assert_eq!(controller.market.position("BTCUSDT"), 0.);
+}
+
+#[test]
+fn controller_approval_test() {
+    let market = MockMarket::new(1000.);
+    market.set_price("BTCUSDT", 100.);
+    let (mut controller, results) = controller(market);
+    controller.approval = Some(ApprovalQueue::new(1000));
+    let orders = |c: &Controller<MockMarket>| {
+        c.market
+            .calls()
+            .iter()
+            .filter(|c| matches!(c, MockCall::Order { .. }))
+            .count()
+    };
+    controller.input_signal(price(0));
+    assert_eq!(orders(&controller), 0);
+    controller.control(ControlCommand::Approve(1));
+    assert_eq!(orders(&controller), 1);
+    controller.input_signal(price(10));
+    controller.control(ControlCommand::Deny(2));
+    // 已处理的请求不能再次批准
+    controller.control(ControlCommand::Approve(2));
+    controller.input_signal(price(20));
+    controller.input_signal(price(1020));
+    assert_eq!(*results.lock(), vec![true, false, false]);
+    assert_eq!(orders(&controller), 1);
+    assert_eq!(controller.approval.as_ref().unwrap().pending(), vec![4]);
+}
+
+#[test]
+fn controller_schedule_test() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    let (mut controller, results) = controller(market);
+    let rule: schedule::ScheduleRule = toml::from_str(
+        r#"mode = "active"
+           close = true
+           windows = [{ cron = "0 8 * * *", minutes = 30 }]"#,
+    )
+    .unwrap();
+    controller.schedule = Some(Scheduler::new(vec![rule]));
+    let hour = 3_600_000;
+    controller.input_signal(price(7 * hour));
+    assert!(results.lock().is_empty());
+    assert!(controller.strategy_status()[0].paused);
+    controller.input_signal(price(8 * hour));
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    assert_eq!(results.lock().first(), Some(&true));
+    assert_eq!(controller.market.position("BTCUSDT"), 1.);
+    let notified = results.lock().len();
+    // 窗口结束时平仓
+    controller.input_signal(price(8 * hour + 30 * 60_000));
+    assert_eq!(controller.market.position("BTCUSDT"), 0.);
+    assert_eq!(results.lock().len(), notified);
+
+    let config: ControllerConfig = toml::from_str(
+        r#"[[schedule]]
+           strategy = 1
+           mode = "pause"
+           windows = [{ cron = "0 8 * * *", minutes = 30 }]"#,
+    )
+    .unwrap();
+    let strategies: Vec<Box<dyn Strategy>> = vec![Box::new(AlwaysBuy::default())];
+    let market = MockMarket::new(1000.);
+    let result = Controller::from_config(
+        market,
+        strategies,
+        &[1000.],
+        Default::default(),
+        &config,
+        None,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn controller_deleverage_test() {
+    let market = MockMarket::new(1000.);
+    market.set_price("BTCUSDT", 100.);
+    let (mut controller, _) = controller(market);
+    controller.deleverage = Some(Mutex::new(Deleverage::new(
+        crate::deleverage::DeleverageConfig {
+        resume_after: Some(3_600_000),
+        ..Default::default()
+    })));
+    let order_values = |controller: &Controller<MockMarket>| -> Vec<f64> {
+        controller
+            .market
+            .calls()
+            .iter()
+            .filter_map(|c| match c {
+                MockCall::Order { value, .. } => Some(*value),
+                _ => None,
+            })
+            .collect()
+    };
+    *controller.total_balance.lock() = 1000.;
+    controller.input_signal(price(0));
+    // 回撤15%，开仓金额减半
+    *controller.total_balance.lock() = 850.;
+    controller.input_signal(price(1));
+    assert_eq!(order_values(&controller), [100., 50.]);
+    // 回撤超过20%，平仓且不再开仓
+    *controller.total_balance.lock() = 790.;
+    controller.input_signal(price(2));
+    assert_eq!(order_values(&controller).len(), 2);
+    assert_eq!(
+        controller.market.calls().last(),
+        Some(&MockCall::ClosePosition("BTCUSDT".into()))
+    );
+    controller.control(ControlCommand::ResetDrawdown);
+    controller.input_signal(price(3));
+    assert_eq!(order_values(&controller).len(), 3);
+    // 平仓一小时后以当时的权益为新高点恢复全部仓位
+    *controller.total_balance.lock() = 600.;
+    controller.input_signal(price(4));
+    assert_eq!(order_values(&controller).len(), 3);
+    controller.input_signal(price(3_600_004));
+    assert_eq!(order_values(&controller)[3], 100.);
+}
+
+#[test]
+fn controller_leverage_test() {
+    let market = MockMarket::new(1000.);
+    market.set_price("BTCUSDT", 100.);
+    let strategy = AlwaysBuy {
+        leverage: Some(5),
+        ..Default::default()
+    };
+    let controller = Controller::new(
+        market,
+        vec![Box::new(strategy)],
+        &[1000.],
+        Default::default(),
+    );
+    controller.input_signal(price(0));
+    assert!(matches!(
+        controller.market.calls().last(),
+        Some(MockCall::Order {
+            leverage: Some(5),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn controller_child_orders_test() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    let (controller, _) = controller(market);
+    controller.input_signal(price(0));
+    let mut legs: Vec<(usize, String)> = controller
+        .open_orders
+        .iter()
+        .map(|o| (o.leg, o.symbol.clone()))
+        .collect();
+    legs.sort();
+    assert_eq!(legs, [(1, "BTCUSDT".into()), (2, "BTCUSDT".into())]);
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    // 只有止盈止损单的持仓币种不设倒计时撤单
+    assert!(controller.pending_entries().is_empty());
+    let entry = |status: &str| {
+        let AccountInfo::OrderTrade { time, mut order } = crate::market::mock_market::FakeFill {
+            symbol: "ETHUSDT".into(),
+            client_order_id: "s0_9_0".into(),
+            order_id: 100,
+            qty: 1.,
+            price: 10.,
+            ..Default::default()
+        }
+        .event(0) else {
+            unreachable!()
+        };
+        order.order_status = status.to_string();
+        AccountInfo::OrderTrade { time, order }
+    };
+    controller.update_account(entry("NEW"));
+    assert_eq!(controller.pending_entries(), ["ETHUSDT"]);
+    controller.update_account(entry("FILLED"));
+    assert!(controller.pending_entries().is_empty());
+    controller.control(ControlCommand::Delist("BTCUSDT".to_string()));
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    assert!(controller.open_orders.is_empty());
+}
+
+#[test]
+fn controller_unprotected_position_test() {
+    let market = MockMarket::new(1000.);
+    market.set_price("BTCUSDT", 100.);
+    market.push(MockResponse::StopRejected);
+    let (controller, results) = controller(market);
+    controller.input_signal(price(0));
+    // 止损单被拒，开仓后立即平仓，不熔断
+    assert_eq!(*results.lock(), [true]);
+    assert_eq!(controller.market.position("BTCUSDT"), 0.);
+    assert!(controller
+        .market
+        .calls()
+        .contains(&MockCall::ClosePosition("BTCUSDT".into())));
+    assert!(controller.owner("BTCUSDT").is_none());
+    assert!(!controller.breakers[0].lock().is_paused());
+}
+
+#[test]
+fn controller_funding_schedule_test() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    let (mut controller, results) = controller(market);
+    controller.funding = Some(FundingSchedule::new(Default::default()));
+    let settle = 8 * 3600 * 1000;
+    let signal = |time: u64, funding_rate: f64| SymbolPrice {
+        funding_rate,
+        ..price(time)
+    };
+    // 结算前一分钟内多头需支付资金费率，推迟开仓
+    controller.input_signal(signal(settle - 30_000, 0.0001));
+    assert!(results.lock().is_empty());
+    // 收取资金费率时照常开仓
+    controller.input_signal(signal(settle - 20_000, -0.0001));
+    assert_eq!(*results.lock(), [true]);
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    // 下次结算前费率转正，平仓
+    controller.input_signal(signal(2 * settle - 30_000, 0.0001));
+    assert_eq!(
+        controller.market.calls().last(),
+        Some(&MockCall::ClosePosition("BTCUSDT".into()))
+    );
+    assert_eq!(controller.market.position("BTCUSDT"), 0.);
+}
+
+#[test]
+fn controller_throttle_test() {
+    use throttle::ThrottleConfig;
+
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    let strategy = AlwaysBuy::default();
+    let results = strategy.results.clone();
+    let config = ControllerConfig {
+        throttle: ThrottleConfig {
+            reentry_cooldown: 60_000,
+            max_orders_per_minute: 0,
+        },
+        capital: toml::from_str(r#"default = "fixed""#).unwrap(),
+        ..Default::default()
+    };
+    let controller = Controller::from_config(
+        market,
+        vec![Box::new(strategy)],
+        &[1000.],
+        Default::default(),
+        &config,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        controller.ledger.account(0).unwrap().capital.policy(),
+        crate::capital::CapitalPolicy::Fixed
+    );
+    let now = unix_millis();
+    controller.input_signal(price(now));
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    controller.market.close_position("BTCUSDT").unwrap();
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    // 平仓后冷却期内不再开仓
+    let orders = results.lock().len();
+    controller.input_signal(price(now + 1_000));
+    assert_eq!(results.lock().len(), orders);
+    assert_eq!(controller.strategy_status()[0].throttled.cooldown, 1);
+    controller.input_signal(price(now + 120_000));
+    assert_eq!(results.lock().len(), orders + 1);
+}
+
+#[test]
+fn controller_equity_plot_test() {
+    let path = std::env::temp_dir().join("hurribot_controller_equity_test.png");
+    let path = path.to_str().unwrap().to_string();
+    std::fs::remove_file(&path).ok();
+    let config = ControllerConfig {
+        equity_plot: Some(equity::EquityPlotConfig {
+            path: path.clone(),
+            redraw_every: 1,
+        }),
+        ..Default::default()
+    };
+    let controller = Controller::from_config(
+        MockMarket::new(1000.),
+        vec![Box::new(AlwaysBuy::default())],
+        &[1000.],
+        Default::default(),
+        &config,
+        None,
+    )
+    .unwrap();
+    assert!(controller.samples_equity());
+    controller.sample_equity();
+    drop(controller);
+    for _ in 0..100 {
+        if std::path::Path::new(&path).exists() {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    panic!("equity plot not drawn");
+}
+
+#[test]
+fn controller_event_test() {
+    use crate::events::{EconomicEvent, EventCalendar, Impact};
+    use event_guard::EventPolicy;
+
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    let (mut controller, results) = controller(market);
+    let hour = 3_600_000;
+    let calendar = EventCalendar::new(vec![EconomicEvent {
+        time: 10 * hour,
+        name: "CPI".into(),
+        impact: Impact::High,
+    }]);
+    controller.events = Some(EventGuard::new(calendar.clone(), Some(Default::default())));
+    // 事件前后暂停开仓
+    controller.input_signal(price(10 * hour - 60_000));
+    assert!(results.lock().is_empty());
+    controller.events = Some(EventGuard::new(calendar, None).with_policy(
+        0,
+        Some(EventPolicy {
+            action: EventAction::Reduce(0.5),
+            ..Default::default()
+        }),
+    ));
+    controller.input_signal(price(10 * hour));
+    assert_eq!(*results.lock(), [true]);
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    // 10%仓位减半
+    assert!((controller.market.position("BTCUSDT") * 100. - 50.).abs() < 1e-9);
+}
+
+#[test]
+fn controller_forced_close_test() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    let (controller, _) = controller(market);
+    controller.input_signal(price(0));
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    // ADL减掉一半仓位，暂停策略并平掉残余仓位
+    controller.market.set_price("BTCUSDT", 90.);
+    controller
+        .market
+        .force_close("BTCUSDT", -0.5, "adl_autoclose")
+        .unwrap();
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    assert!(controller.breakers[0].lock().is_paused());
+    assert_eq!(controller.market.position("BTCUSDT"), 0.);
+    assert!(controller.owner("BTCUSDT").is_none());
+    assert!(controller.ledger.account(0).unwrap().exposure.is_empty());
+    let calls = controller.market.calls();
+    assert_eq!(
+        calls[calls.len() - 2..],
+        [
+            MockCall::ClearOrders("BTCUSDT".into()),
+            MockCall::ClosePosition("BTCUSDT".into())
+        ]
+    );
+    // 没有归属策略的仓位只发出通知
+    controller.update_account(AccountInfo::MarginCall {
+        time: 1,
+        positions: vec![MarginCallPosition {
+            symbol: "ETHUSDT".into(),
+            amount: 1.,
+            mark_price: 100.,
+            maint_margin: 1.,
+        }],
+    });
+}
+
+#[test]
+fn controller_spread_test() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    market.set_price("ETHUSDT", 10.);
+    let strategy = AlwaysBuy {
+        hedge: Some(HedgeLeg {
+            symbol: "ETHUSDT".into(),
+            ratio: 1.,
+        }),
+        ..Default::default()
+    };
+    let controller = Controller::new(
+        market,
+        vec![Box::new(strategy)],
+        &[1000.],
+        Default::default(),
+    );
+    controller.input_signal(price(0));
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    assert!((controller.market.position("BTCUSDT") - 0.5).abs() < 1e-9);
+    assert!((controller.market.position("ETHUSDT") + 5.).abs() < 1e-9);
+    assert_eq!(controller.owner("ETHUSDT"), Some(0));
+    assert_eq!(controller.spreads.positions().len(), 1);
+    // 一条腿止盈后平掉另一条腿，合并盈亏计入组合单
+    controller.market.set_price("BTCUSDT", 110.);
+    controller.market.close_position("BTCUSDT").unwrap();
+    for _ in 0..2 {
+        for event in rx.try_iter() {
+            controller.update_account(event);
+        }
+    }
+    assert_eq!(controller.market.position("ETHUSDT"), 0.);
+    assert!(controller.spreads.positions().is_empty());
+    assert!(controller.ledger.account(0).unwrap().exposure.is_empty());
+}
+
+#[test]
+fn controller_kline_test() {
+    use crate::{
+        candle::CandleData,
+        strategy::breakout::{BreakoutConfig, BreakoutStrategy},
+    };
+
+    let market = MockMarket::new(1000.);
+    let strategy = BreakoutStrategy::new(
+        vec!["BTCUSDT".into()],
+        BreakoutConfig {
+            period: 5,
+            atr_period: 5,
+            ..Default::default()
+        },
+    );
+    let controller = Controller::new(
+        market,
+        vec![Box::new(strategy)],
+        &[1000.],
+        Default::default(),
+    );
+    let kline = |close: f64, volume: f64| KlineData {
+        symbol: "BTCUSDT".into(),
+        candle: CandleData {
+            open: close,
+            close,
+            high: close + 1.,
+            low: close - 1.,
+            volume,
+            ..Default::default()
+        },
+    };
+    controller.market.set_price("BTCUSDT", 100.);
+    for _ in 0..6 {
+        controller.input_kline(kline(100., 10.));
+    }
+    assert_eq!(controller.market.position("BTCUSDT"), 0.);
+    controller.market.set_price("BTCUSDT", 105.);
+    controller.input_kline(kline(105., 50.));
+    assert!(controller.market.position("BTCUSDT") > 0.);
+    assert_eq!(controller.owner("BTCUSDT"), Some(0));
+    // 跌破移动止损后平仓
+    controller.market.set_price("BTCUSDT", 90.);
+    controller.input_kline(kline(90., 10.));
+    assert_eq!(controller.market.position("BTCUSDT"), 0.);
+}
+
+#[test]
+fn controller_quote_asset_test() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    let (mut controller, _) = controller(market);
+    controller.quote_asset = "USDC".to_string();
+    controller.input_signal(price(0));
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    // 模拟市场只推送USDT余额，持仓照常更新
+    assert_eq!(*controller.total_balance.lock(), 0.);
+    assert!(controller.positions.contains_key(&"BTCUSDT".into()));
+}
+
+#[test]
+fn controller_fees_test() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.)
+        .with_events(tx)
+        .with_fees(FeeSchedule::vip(9, false));
+    market.set_price("BTCUSDT", 100.);
+    let (controller, _) = controller(market);
+    controller.input_signal(price(0));
+    for event in rx.try_iter() {
+        controller.update_account(event);
+    }
+    // 平仓手续费按市场的费率估算
+    let snapshot = &controller.position_snapshots()[0];
+    let notional = snapshot.amount.abs() * snapshot.mark_price;
+    assert!((snapshot.close_fee - notional * 0.00017).abs() < 1e-9);
+}
+
+#[test]
+fn controller_keys_test() {
+    let build = |config: &ControllerConfig, keys| {
+        let strategies: Vec<Box<dyn Strategy>> = vec![Box::new(AlwaysBuy::default())];
+        let market = MockMarket::new(1000.);
+        Controller::from_config(
+            market,
+            strategies,
+            &[1000.],
+            Default::default(),
+            config,
+            keys,
+        )
+    };
+    let mut config: ControllerConfig = toml::from_str("dead_man_countdown = 60000").unwrap();
+    // 死人开关和资金划转需要直接调用交易所接口
+    assert!(build(&config, None).is_err());
+    let keys = BinanceKeys {
+        api_key: String::new(),
+        secret_key: String::new(),
+    };
+    let controller = build(&config, Some(&keys)).unwrap();
+    let dead_man = controller.dead_man.as_ref().unwrap();
+    assert_eq!(dead_man.interval(), Duration::from_secs(20));
+    assert!(controller.treasury.is_none());
+
+    config = toml::from_str(
+        r#"[treasury]
+           keep = 1000
+           weekday = 1
+           to = { wallet = "Spot" }"#,
+    )
+    .unwrap();
+    assert!(build(&config, None).is_err());
+    let controller = build(&config, Some(&keys)).unwrap();
+    assert_eq!(controller.treasury.unwrap().policy().keep, 1000.);
 }
@@ -0,0 +1,120 @@
+use std::{fmt::Display, time::Duration};
+
+use parking_lot::Mutex;
+
+use crate::{algorithm::SymbolPrice, strategy::StrategyOrderRequest};
+
+/// 等待人工确认的开仓请求
+#[derive(Debug)]
+pub struct TradeIdea {
+    pub id: u64,
+    /// 策略序号
+    pub index: usize,
+    pub request: StrategyOrderRequest,
+    /// 产生请求时的行情
+    pub signal: SymbolPrice,
+}
+
+impl Display for TradeIdea {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "#{} strategy {} {} position {}, stop loss {}, take profit {}",
+            self.id,
+            self.index,
+            self.request.symbol,
+            self.request.position,
+            self.request.stop_loss,
+            self.request.take_profit
+        )
+    }
+}
+
+/// 没有行情时按本地时间检查超时的间隔
+pub const EXPIRE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default)]
+struct Queue {
+    next_id: u64,
+    ideas: Vec<TradeIdea>,
+}
+
+/// 人工确认模式：策略的开仓请求先排队，通过控制命令批准后才下单，
+/// 拒绝或超过timeout（毫秒，按行情时间，行情中断时按本地时间）未处理的请求作废
+#[derive(Debug, Default)]
+pub struct ApprovalQueue {
+    timeout: u64,
+    queue: Mutex<Queue>,
+}
+
+impl ApprovalQueue {
+    pub fn new(timeout: u64) -> Self {
+        Self {
+            timeout,
+            ..Default::default()
+        }
+    }
+    /// 加入队列，返回确认用的编号
+    pub fn submit(&self, index: usize, request: StrategyOrderRequest, signal: SymbolPrice) -> u64 {
+        let mut queue = self.queue.lock();
+        queue.next_id += 1;
+        let id = queue.next_id;
+        queue.ideas.push(TradeIdea {
+            id,
+            index,
+            request,
+            signal,
+        });
+        id
+    }
+    /// 取出待批准或拒绝的请求，不存在（已处理或已超时）时为None
+    pub fn take(&self, id: u64) -> Option<TradeIdea> {
+        let mut queue = self.queue.lock();
+        let i = queue.ideas.iter().position(|idea| idea.id == id)?;
+        Some(queue.ideas.remove(i))
+    }
+    /// 取出now时已超时的请求
+    pub fn expire(&self, now: u64) -> Vec<TradeIdea> {
+        let mut queue = self.queue.lock();
+        let (expired, pending) = std::mem::take(&mut queue.ideas)
+            .into_iter()
+            .partition(|idea| now.saturating_sub(idea.signal.time) >= self.timeout);
+        queue.ideas = pending;
+        expired
+    }
+    /// 待确认的请求编号
+    pub fn pending(&self) -> Vec<u64> {
+        self.queue.lock().ideas.iter().map(|idea| idea.id).collect()
+    }
+}
+
+#[test]
+fn approval_queue_test() {
+    let request = |symbol: &str| StrategyOrderRequest {
+        request_id: 0,
+        symbol: symbol.to_string(),
+        position: 0.1,
+        stop_loss: 0.9,
+        take_profit: 1.1,
+        time_in_force: Default::default(),
+        stop_limit_offset: None,
+        hedge: None,
+        leverage: None,
+    };
+    let signal = |time: u64| SymbolPrice {
+        time,
+        ..Default::default()
+    };
+    let queue = ApprovalQueue::new(1000);
+    let a = queue.submit(0, request("BTCUSDT"), signal(0));
+    let b = queue.submit(1, request("ETHUSDT"), signal(500));
+    assert_eq!(queue.pending(), vec![a, b]);
+    assert!(queue.expire(999).is_empty());
+    let expired = queue.expire(1000);
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].id, a);
+    assert!(queue.take(a).is_none());
+    let idea = queue.take(b).unwrap();
+    assert_eq!((idea.index, idea.request.symbol.as_str()), (1, "ETHUSDT"));
+    assert!(queue.pending().is_empty());
+}
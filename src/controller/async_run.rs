@@ -0,0 +1,190 @@
+use std::{iter, sync::Arc, time::Duration};
+
+use tokio::{
+    sync::{mpsc, watch, Semaphore},
+    task::{block_in_place, JoinHandle},
+    time::{interval_at, sleep_until, Instant, Interval, MissedTickBehavior},
+};
+use tracing::info;
+
+use super::{
+    report,
+    router::{shard, SHARD_QUEUE, SIGNAL_SHARDS},
+    AccountInfo, ControlCommand, Controller, EXPIRE_INTERVAL,
+};
+use crate::{
+    algorithm::{DepthData, KlineData, SymbolPrice},
+    binance_futures::async_ws::stopped,
+    market::Market,
+    utils::{
+        coalesce::{coalescing_channel_async, latest, IDLE_FLUSH_INTERVAL},
+        local_now, unix_millis,
+    },
+};
+
+/// 同时处理深度、k线、账户事件和死人开关续期的数量，同run的线程池大小
+const WORKERS: usize = 4;
+
+/// 首次在period之后触发，错过的触发不补
+fn ticker(period: Duration) -> Interval {
+    let mut ticker = interval_at(Instant::now() + period, period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    ticker
+}
+
+/// 未配置的定时器永不触发
+async fn tick(timer: &mut Option<Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+impl<M: Market> Controller<M> {
+    /// run的tokio版本：行情、深度、k线、账户、控制命令、各定时器和shutdown在同一个select中处理，
+    /// 定时器不再占用通道和线程；shutdown置为true或发送端drop后等待处理中的事件完成，保存策略状态后退出。
+    /// 下单等REST请求为阻塞调用，需在多线程运行时中调用
+    fn run_async(
+        self,
+        mut signal_rx: mpsc::Receiver<SymbolPrice>,
+        mut depth_rx: mpsc::UnboundedReceiver<DepthData>,
+        mut kline_rx: mpsc::UnboundedReceiver<KlineData>,
+        mut account_rx: mpsc::UnboundedReceiver<AccountInfo>,
+        mut control_rx: mpsc::UnboundedReceiver<ControlCommand>,
+        mut shutdown: watch::Receiver<bool>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let this = Arc::new(self);
+            block_in_place(|| this.restore_states());
+            // 行情按币种分片，每个分片一个任务按顺序处理，积压时每个币种只处理最新的价格
+            let (shard_txs, shards): (Vec<_>, Vec<_>) = (0..SIGNAL_SHARDS)
+                .map(|_| {
+                    let (tx, mut rx) =
+                        coalescing_channel_async(SHARD_QUEUE, |p: &SymbolPrice| p.symbol);
+                    let this = this.clone();
+                    let shard = tokio::spawn(async move {
+                        while let Some(first) = rx.recv().await {
+                            let pending = iter::from_fn(|| rx.try_recv().ok());
+                            let signals = latest(iter::once(first).chain(pending), |p| p.symbol);
+                            block_in_place(|| {
+                                for signal in signals {
+                                    this.input_signal(signal);
+                                }
+                            });
+                        }
+                    });
+                    (tx.with_idle_flush_async(IDLE_FLUSH_INTERVAL), shard)
+                })
+                .unzip();
+            let workers = Arc::new(Semaphore::new(WORKERS));
+            // 在阻塞线程池中处理，同时处理的数量不超过WORKERS
+            let spawn = |f: Box<dyn FnOnce(&Self) + Send>| {
+                let (this, workers) = (this.clone(), workers.clone());
+                async move {
+                    let permit = workers.acquire_owned().await.unwrap();
+                    tokio::task::spawn_blocking(move || {
+                        f(&this);
+                        drop(permit);
+                    });
+                }
+            };
+            let report_at = || {
+                Instant::now() + report::until_next(local_now(), this.report_time).unsigned_abs()
+            };
+            let report_timer = sleep_until(report_at());
+            tokio::pin!(report_timer);
+            let mut equity_timer = this.samples_equity().then(|| ticker(this.equity_interval));
+            let mut state_timer = this.store.as_ref().map(|_| ticker(this.state_interval));
+            // 行情中断时也要让待确认的请求按时作废
+            let mut approval_timer = this.approval.as_ref().map(|_| ticker(EXPIRE_INTERVAL));
+            let mut dead_man_timer = this.dead_man.as_ref().map(|d| ticker(d.interval()));
+            loop {
+                tokio::select! {
+                    _ = stopped(&mut shutdown) => break,
+                    Some(signal) = signal_rx.recv() => {
+                        shard_txs[shard(signal.symbol, SIGNAL_SHARDS)].send(signal);
+                    }
+                    Some(depth) = depth_rx.recv() => {
+                        spawn(Box::new(move |c| c.input_depth(depth))).await;
+                    }
+                    Some(kline) = kline_rx.recv() => {
+                        spawn(Box::new(move |c| c.input_kline(kline))).await;
+                    }
+                    Some(account_info) = account_rx.recv() => {
+                        spawn(Box::new(move |c| c.update_account(account_info))).await;
+                    }
+                    Some(command) = control_rx.recv() => {
+                        block_in_place(|| this.control(command));
+                    }
+                    _ = &mut report_timer => {
+                        block_in_place(|| this.report());
+                        report_timer.as_mut().reset(report_at());
+                    }
+                    _ = tick(&mut equity_timer) => {
+                        block_in_place(|| this.sample_equity());
+                    }
+                    _ = tick(&mut state_timer) => {
+                        block_in_place(|| this.save_states());
+                    }
+                    _ = tick(&mut approval_timer) => {
+                        this.expire_ideas(unix_millis());
+                    }
+                    _ = tick(&mut dead_man_timer) => {
+                        spawn(Box::new(|c| c.refresh_dead_man())).await;
+                    }
+                }
+            }
+            // 分片处理完已收到的行情后退出
+            drop(shard_txs);
+            for shard in shards {
+                shard.await.ok();
+            }
+            workers.acquire_many(WORKERS as u32).await.ok();
+            block_in_place(|| this.save_states());
+            info!("Controller stopped");
+        })
+    }
+}
+
+#[test]
+fn controller_run_async_test() {
+    use tokio::time::{sleep, timeout};
+
+    use super::{controller, price, MockMarket, MockResponse};
+
+    let market = MockMarket::new(1000.);
+    market.set_price("BTCUSDT", 100.);
+    market.push(MockResponse::Fill);
+    let (controller, results) = controller(market);
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let (signal_tx, signal_rx) = mpsc::channel(16);
+        let (_depth_tx, depth_rx) = mpsc::unbounded_channel();
+        let (_kline_tx, kline_rx) = mpsc::unbounded_channel();
+        let (_account_tx, account_rx) = mpsc::unbounded_channel();
+        let (_control_tx, control_rx) = mpsc::unbounded_channel();
+        let (shutdown_tx, shutdown) = watch::channel(false);
+        let handle = controller.run_async(
+            signal_rx, depth_rx, kline_rx, account_rx, control_rx, shutdown,
+        );
+        signal_tx.send(price(0)).await.unwrap();
+        let filled = async {
+            while results.lock().is_empty() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        };
+        timeout(Duration::from_secs(5), filled).await.unwrap();
+        // shutdown发送端drop同样退出
+        drop(shutdown_tx);
+        timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+    });
+    assert_eq!(*results.lock(), vec![true]);
+}
@@ -0,0 +1,135 @@
+use std::fmt::Display;
+
+use serde::Deserialize;
+
+use super::report::AccountSnapshot;
+use crate::{binance_futures::SymbolPrices, error::ConfigError, symbol::SymbolId};
+
+/// 报告和面板额外以该货币（如EUR、BTC）计价，汇率取指数价格
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BaseCurrency {
+    pub currency: String,
+    /// 提供汇率的币种，为空时为currency加结算资产，如EURUSDT、BTCUSDT
+    #[serde(default)]
+    pub symbol: Option<String>,
+    /// 固定汇率（1单位currency折合的结算资产），设置后不使用指数价格
+    #[serde(default)]
+    pub rate: Option<f64>,
+}
+
+impl BaseCurrency {
+    pub fn new(currency: &str) -> Self {
+        Self {
+            currency: currency.to_string(),
+            symbol: None,
+            rate: None,
+        }
+    }
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.currency.is_empty() || self.rate.is_some_and(|r| r <= 0.) {
+            return Err(ConfigError::Invalid(format!(
+                "base currency {} rate {:?}",
+                self.currency, self.rate
+            )));
+        }
+        Ok(())
+    }
+    pub fn index_symbol(&self, quote_asset: &str) -> String {
+        self.symbol
+            .clone()
+            .unwrap_or_else(|| format!("{}{}", self.currency, quote_asset))
+    }
+    /// 1单位currency折合的结算资产，没有指数价格时为None
+    pub fn rate(&self, quote_asset: &str, prices: &SymbolPrices) -> Option<f64> {
+        if let Some(rate) = self.rate {
+            return Some(rate);
+        }
+        if self.currency == quote_asset {
+            return Some(1.);
+        }
+        SymbolId::get(&self.index_symbol(quote_asset))
+            .and_then(|id| prices.get(&id))
+            .map(|p| p.price_index)
+            .filter(|p| *p > 0.)
+    }
+    /// 将账户快照折算为currency计价
+    pub fn convert(&self, snapshot: &AccountSnapshot, rate: f64) -> ConvertedSnapshot {
+        ConvertedSnapshot {
+            currency: self.currency.clone(),
+            rate,
+            total_balance: snapshot.total_balance / rate,
+            realized_pnl: snapshot.realized_pnl / rate,
+            unrealized_pnl: snapshot.unrealized_pnl() / rate,
+        }
+    }
+}
+
+/// 以报告货币计价的账户快照
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertedSnapshot {
+    pub currency: String,
+    pub rate: f64,
+    pub total_balance: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+}
+
+impl Display for ConvertedSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // BTC等高价资产需要更多小数位
+        let d = if self.rate > 1000. { 6 } else { 2 };
+        write!(
+            f,
+            "in {} @ {}: balance {:.d$}, realized pnl {:.d$}, unrealized pnl {:.d$}",
+            self.currency, self.rate, self.total_balance, self.realized_pnl, self.unrealized_pnl
+        )
+    }
+}
+
+#[test]
+fn base_currency_test() {
+    use super::report::PositionSnapshot;
+    use crate::algorithm::SymbolPrice;
+
+    let prices = SymbolPrices::default();
+    let btc = BaseCurrency::new("BTC");
+    assert_eq!(btc.index_symbol("USDT"), "BTCUSDT");
+    assert_eq!(btc.rate("USDT", &prices), None);
+    let symbol = SymbolId::intern("BTCUSDT");
+    prices.insert(
+        symbol,
+        SymbolPrice {
+            symbol,
+            price_index: 50000.,
+            ..Default::default()
+        },
+    );
+    let rate = btc.rate("USDT", &prices).unwrap();
+    assert_eq!(rate, 50000.);
+    let snapshot = AccountSnapshot {
+        total_balance: 10000.,
+        realized_pnl: 500.,
+        positions: vec![PositionSnapshot {
+            symbol: "ETHUSDT".to_string(),
+            amount: 1.,
+            entry_price: 2000.,
+            mark_price: 2100.,
+            close_fee: 0.,
+        }],
+        ..Default::default()
+    };
+    let converted = btc.convert(&snapshot, rate);
+    assert_eq!(converted.total_balance, 0.2);
+    assert_eq!(converted.realized_pnl, 0.01);
+    assert_eq!(converted.unrealized_pnl, 0.002);
+    assert_eq!(
+        converted.to_string(),
+        "in BTC @ 50000: balance 0.200000, realized pnl 0.010000, unrealized pnl 0.002000"
+    );
+
+    let eur: BaseCurrency = toml::from_str("currency = \"EUR\"\nrate = 1.08").unwrap();
+    assert_eq!(eur.rate("USDT", &prices), Some(1.08));
+    assert_eq!(BaseCurrency::new("USDT").rate("USDT", &prices), Some(1.));
+    let zero: BaseCurrency = toml::from_str("currency = \"EUR\"\nrate = 0").unwrap();
+    assert!(zero.validate().is_err());
+}
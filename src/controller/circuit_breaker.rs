@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+
+use serde::Deserialize;
+
+use crate::error::ConfigError;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// 统计已实现盈亏的滚动窗口（ms）
+    pub window: u64,
+    /// 窗口内允许的最大亏损（USDT）
+    pub max_loss: f64,
+    /// 允许的最大连续下单失败次数
+    pub max_failures: usize,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            window: 24 * 60 * 60 * 1000,
+            max_loss: 100.,
+            max_failures: 5,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.window == 0 || self.max_loss <= 0. || self.max_failures == 0 {
+            return Err(ConfigError::Invalid(format!(
+                "circuit breaker window {}, max loss {}, max failures {}",
+                self.window, self.max_loss, self.max_failures
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// 策略熔断器：窗口内亏损过大或连续下单失败时暂停策略，需手动恢复
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    paused: bool,
+    failures: usize,
+    /// 窗口内的（时间，已实现盈亏）
+    pnl: VecDeque<(u64, f64)>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    pub fn record_success(&mut self) {
+        self.failures = 0;
+    }
+    /// 返回触发熔断的原因
+    pub fn record_failure(&mut self) -> Option<String> {
+        self.failures += 1;
+        if self.failures >= self.config.max_failures {
+            return self.trip(format!("{} consecutive order failures", self.failures));
+        }
+        None
+    }
+    /// 返回触发熔断的原因
+    pub fn record_pnl(&mut self, time: u64, pnl: f64) -> Option<String> {
+        self.pnl.push_back((time, pnl));
+        while let Some((t, _)) = self.pnl.front() {
+            if t + self.config.window >= time {
+                break;
+            }
+            self.pnl.pop_front();
+        }
+        let total = self.window_pnl();
+        if total < -self.config.max_loss {
+            return self.trip(format!("realized pnl {:.2} exceeds loss limit", total));
+        }
+        None
+    }
+    pub fn window_pnl(&self) -> f64 {
+        self.pnl.iter().map(|(_, p)| p).sum()
+    }
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+    /// 手动恢复，清空失败计数和盈亏窗口
+    pub fn reset(&mut self) {
+        self.paused = false;
+        self.failures = 0;
+        self.pnl.clear();
+    }
+    fn trip(&mut self, reason: String) -> Option<String> {
+        if self.paused {
+            return None;
+        }
+        self.paused = true;
+        Some(reason)
+    }
+}
+
+#[test]
+fn circuit_breaker_test() {
+    let mut breaker = CircuitBreaker::new(CircuitBreakerConfig {
+        window: 1000,
+        max_loss: 10.,
+        max_failures: 2,
+    });
+    assert!(breaker.record_failure().is_none());
+    breaker.record_success();
+    assert!(breaker.record_failure().is_none());
+    assert!(breaker.record_failure().is_some());
+    assert!(breaker.is_paused());
+    assert!(breaker.record_failure().is_none());
+    breaker.reset();
+    assert!(breaker.record_pnl(0, -6.).is_none());
+    assert!(breaker.record_pnl(2000, -6.).is_none());
+    assert!(breaker.record_pnl(2500, -6.).is_some());
+    assert!(breaker.is_paused());
+
+    let config: CircuitBreakerConfig = toml::from_str("max_loss = 0").unwrap();
+    assert_eq!(config.max_failures, 5);
+    assert!(config.validate().is_err());
+}
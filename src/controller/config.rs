@@ -0,0 +1,227 @@
+use serde::{Deserialize, Deserializer};
+use time::macros::format_description;
+
+use super::{
+    base_currency::BaseCurrency, circuit_breaker::CircuitBreakerConfig,
+    correlation::CorrelationConfig, equity::EquityPlotConfig, event_guard::EventConfig,
+    funding_schedule::FundingScheduleConfig, ledger::CapitalConfig,
+    margin_guard::MarginGuardConfig, schedule::ScheduleRule, throttle::ThrottleConfig,
+};
+use crate::{
+    deleverage::DeleverageConfig, error::ConfigError, notifier::NotificationConfig,
+    treasury::SweepPolicy,
+};
+
+/// Controller风控组件的配置，见Controller::from_config，未配置的部分取默认值
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ControllerConfig {
+    /// 按币种的再入场冷却和下单频率限制
+    pub throttle: ThrottleConfig,
+    /// 重大经济事件前后按策略暂停开仓或缩小仓位，不设置为不处理
+    pub events: Option<EventConfig>,
+    /// 组合回撤分级降杠杆，不设置为不处理
+    pub deleverage: Option<DeleverageConfig>,
+    /// 各策略已实现盈利是否复利，默认全部复利
+    pub capital: CapitalConfig,
+    /// 按权益采样定期重绘权益曲线PNG，不设置为不绘制
+    pub equity_plot: Option<EquityPlotConfig>,
+    /// 资金费率结算前平掉需支付的仓位并推迟开仓，不设置为不处理
+    pub funding: Option<FundingScheduleConfig>,
+    /// 每日报告时间（东八区），格式为"HH:MM"，默认为0点
+    #[serde(deserialize_with = "deserialize_time")]
+    pub report_time: time::Time,
+    /// SQLite数据库路径，保存账户快照、成交和策略状态，不设置为不保存
+    pub store: Option<String>,
+    /// 告警、成交和报告的通知渠道，默认只写日志
+    pub notification: NotificationConfig,
+    /// 按计划启用和停用策略，为空时始终启用
+    pub schedule: Vec<ScheduleRule>,
+    /// 人工确认模式下开仓请求的超时（毫秒），不设置为直接下单
+    pub approval_timeout: Option<u64>,
+    /// 死人开关的倒计时（毫秒），交易所约每10秒检查一次，不设置为不启用
+    pub dead_man_countdown: Option<u64>,
+    /// 每周报告时将合约钱包的盈余划出，不设置为不划转
+    pub treasury: Option<SweepPolicy>,
+    /// 报告和面板额外使用的计价货币，不设置为只用结算资产
+    pub base_currency: Option<BaseCurrency>,
+    /// 逐仓仓位距强平的阈值和追加保证金上限
+    pub margin_guard: MarginGuardConfig,
+    /// 高度相关币种的合计敞口限制
+    pub correlation: CorrelationConfig,
+    /// 各策略的熔断条件，触发时暂停策略并发送Critical通知
+    pub circuit_breaker: CircuitBreakerConfig,
+}
+
+impl Default for ControllerConfig {
+    fn default() -> Self {
+        Self {
+            throttle: Default::default(),
+            events: None,
+            deleverage: None,
+            capital: Default::default(),
+            equity_plot: None,
+            funding: None,
+            report_time: time::Time::MIDNIGHT,
+            store: None,
+            notification: Default::default(),
+            schedule: vec![],
+            approval_timeout: None,
+            dead_man_countdown: None,
+            treasury: None,
+            base_currency: None,
+            margin_guard: Default::default(),
+            correlation: Default::default(),
+            circuit_breaker: Default::default(),
+        }
+    }
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<time::Time, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    time::Time::parse(&s, format_description!("[hour]:[minute]")).map_err(serde::de::Error::custom)
+}
+
+impl ControllerConfig {
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
+        let c = std::fs::read_to_string(path)?;
+        let val: Self = toml::from_str(&c)?;
+        val.capital.validate()?;
+        if let Some(events) = &val.events {
+            events.validate()?;
+        }
+        if let Some(funding) = &val.funding {
+            funding.validate()?;
+        }
+        val.notification.validate()?;
+        for rule in &val.schedule {
+            rule.validate()?;
+        }
+        if val.approval_timeout == Some(0) {
+            return Err(ConfigError::Invalid("approval timeout 0".to_string()));
+        }
+        if let Some(countdown) = val.dead_man_countdown.filter(|&c| c < 10_000) {
+            return Err(ConfigError::Invalid(format!(
+                "dead man countdown {}",
+                countdown
+            )));
+        }
+        if let Some(treasury) = &val.treasury {
+            treasury.validate()?;
+        }
+        if let Some(base) = &val.base_currency {
+            base.validate()?;
+        }
+        val.margin_guard.validate()?;
+        val.correlation.validate()?;
+        val.circuit_breaker.validate()?;
+        Ok(val)
+    }
+}
+
+#[test]
+fn controller_config_test() {
+    use crate::notifier::Severity;
+
+    let config: ControllerConfig = toml::from_str(
+        r#"
+        report_time = "08:30"
+        approval_timeout = 300000
+        dead_man_countdown = 120000
+        store = "./hurribot.db"
+        [throttle]
+        reentry_cooldown = 60000
+        [events]
+        calendar = "./config/events.ics"
+        default = { action = "blackout" }
+        [deleverage]
+        stages = [{ drawdown = 0.2, scale = 0.0 }]
+        resume_after = 86400000
+        [capital]
+        default = "fixed"
+        [equity_plot]
+        path = "./equity.png"
+        [funding]
+        close_before = 120000
+        [notification]
+        log = ["warning", "critical"]
+        discord = { webhook_url = "https://discord.com/api/webhooks/1/x" }
+        [base_currency]
+        currency = "EUR"
+        [margin_guard]
+        max_added = 200
+        [correlation]
+        threshold = 0.9
+        [circuit_breaker]
+        max_loss = 300
+        [treasury]
+        keep = 1000
+        weekday = 1
+        to = { wallet = "Spot" }
+        [[schedule]]
+        strategy = 1
+        mode = "pause"
+        windows = [{ cron = "0 2 * * 3", minutes = 60 }]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.throttle,
+        ThrottleConfig {
+            reentry_cooldown: 60_000,
+            max_orders_per_minute: 0,
+        }
+    );
+    let events = config.events.unwrap();
+    assert_eq!(events.calendar, "./config/events.ics");
+    assert_eq!(events.default.unwrap(), Default::default());
+    assert!(events.strategies.is_empty());
+    let deleverage = config.deleverage.unwrap();
+    assert_eq!(deleverage.resume_after, Some(86_400_000));
+    assert_eq!(deleverage.recovery, 0.);
+    assert_eq!(
+        config.equity_plot,
+        Some(EquityPlotConfig {
+            path: "./equity.png".into(),
+            redraw_every: 10,
+        })
+    );
+    let funding = config.funding.unwrap();
+    assert_eq!(funding.close_before, 120_000);
+    assert_eq!(funding.interval, 8 * 3600 * 1000);
+    assert_eq!(
+        config.capital.policy(0),
+        crate::capital::CapitalPolicy::Fixed
+    );
+    let notification = config.notification;
+    assert_eq!(
+        notification.log,
+        Some(vec![Severity::Warning, Severity::Critical])
+    );
+    assert!(notification.discord.unwrap().severities.is_none());
+    assert!(notification.email.is_none());
+    assert_eq!(config.schedule.len(), 1);
+    assert_eq!(config.schedule[0].strategy, Some(1));
+    assert_eq!(config.approval_timeout, Some(300_000));
+    assert_eq!(config.dead_man_countdown, Some(120_000));
+    assert_eq!(config.treasury.unwrap().keep, 1000.);
+    assert_eq!(config.base_currency, Some(BaseCurrency::new("EUR")));
+    assert_eq!(config.circuit_breaker.max_loss, 300.);
+    assert_eq!(config.circuit_breaker.max_failures, 5);
+    assert_eq!(config.correlation.threshold, 0.9);
+    assert_eq!(config.correlation.window, 240);
+    assert_eq!(
+        config.margin_guard,
+        MarginGuardConfig {
+            max_added: 200.,
+            ..Default::default()
+        }
+    );
+    assert_eq!(config.report_time, time::macros::time!(8:30));
+    assert_eq!(config.store.as_deref(), Some("./hurribot.db"));
+    assert!(toml::from_str::<ControllerConfig>("report_time = \"25:00\"").is_err());
+    assert_eq!(
+        toml::from_str::<ControllerConfig>("").unwrap(),
+        ControllerConfig::default()
+    );
+}
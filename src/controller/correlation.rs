@@ -0,0 +1,254 @@
+use std::collections::VecDeque;
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::Deserialize;
+
+use crate::{algorithm::SymbolPrice, error::ConfigError, symbol::SymbolId};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CorrelationConfig {
+    /// 收益率采样间隔（毫秒），按行情时间对齐到同一时间格
+    pub sample_interval: u64,
+    /// 保留的收益率样本数
+    pub window: usize,
+    /// 计算相关系数所需的最少共同样本数，不足时视为不相关
+    pub min_samples: usize,
+    /// 相关系数不低于该值的币种归为同一簇
+    pub threshold: f64,
+    /// 同一簇的名义敞口上限，为总权益的倍数
+    pub max_cluster_exposure: f64,
+}
+
+impl Default for CorrelationConfig {
+    fn default() -> Self {
+        Self {
+            sample_interval: 60_000,
+            window: 240,
+            min_samples: 60,
+            threshold: 0.8,
+            max_cluster_exposure: 3.,
+        }
+    }
+}
+
+impl CorrelationConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.sample_interval == 0 || self.min_samples > self.window {
+            return Err(ConfigError::Invalid(format!(
+                "correlation sample interval {}, min samples {} of window {}",
+                self.sample_interval, self.min_samples, self.window
+            )));
+        }
+        if self.threshold.abs() > 1. || self.max_cluster_exposure <= 0. {
+            return Err(ConfigError::Invalid(format!(
+                "correlation threshold {}, max cluster exposure {}",
+                self.threshold, self.max_cluster_exposure
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Returns {
+    /// 当前时间格及其最新价格
+    bucket: u64,
+    price: f64,
+    /// (时间格, 对数收益率)，按时间格升序
+    returns: VecDeque<(u64, f64)>,
+}
+
+/// 相关系数矩阵，symbols与行列一一对应
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorrelationMatrix {
+    pub symbols: Vec<SymbolId>,
+    pub values: Vec<Vec<f64>>,
+}
+
+impl CorrelationMatrix {
+    pub fn get(&self, a: SymbolId, b: SymbolId) -> Option<f64> {
+        let i = self.symbols.iter().position(|&s| s == a)?;
+        let j = self.symbols.iter().position(|&s| s == b)?;
+        Some(self.values[i][j])
+    }
+}
+
+/// 由近期收益率估计币种间的相关性，限制高度相关币种的合计敞口
+#[derive(Debug, Default)]
+pub struct CorrelationGuard {
+    config: CorrelationConfig,
+    returns: DashMap<SymbolId, Mutex<Returns>>,
+}
+
+impl CorrelationGuard {
+    pub fn new(config: CorrelationConfig) -> Self {
+        Self {
+            config,
+            returns: DashMap::new(),
+        }
+    }
+    pub fn update(&self, price: &SymbolPrice) {
+        if price.mark_price <= 0. {
+            return;
+        }
+        let bucket = price.time / self.config.sample_interval.max(1);
+        let entry = self.returns.entry(price.symbol).or_default();
+        let mut r = entry.lock();
+        if r.price == 0. {
+            (r.bucket, r.price) = (bucket, price.mark_price);
+            return;
+        }
+        if bucket > r.bucket {
+            // 上一格的收盘价到本格的第一个价格
+            let ret = (price.mark_price / r.price).ln();
+            let prev = r.bucket;
+            r.returns.push_back((prev, ret));
+            while r.returns.len() > self.config.window {
+                r.returns.pop_front();
+            }
+            r.bucket = bucket;
+        }
+        if bucket >= r.bucket {
+            r.price = price.mark_price;
+        }
+    }
+    /// 按共同时间格计算皮尔逊相关系数，样本不足时返回None
+    pub fn correlation(&self, a: SymbolId, b: SymbolId) -> Option<f64> {
+        if a == b {
+            return Some(1.);
+        }
+        let ra = self.returns.get(&a)?.lock().returns.clone();
+        let rb = self.returns.get(&b)?.lock().returns.clone();
+        let (mut i, mut j, mut pairs) = (0, 0, vec![]);
+        while i < ra.len() && j < rb.len() {
+            match ra[i].0.cmp(&rb[j].0) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    pairs.push((ra[i].1, rb[j].1));
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        if pairs.len() < self.config.min_samples.max(2) {
+            return None;
+        }
+        let n = pairs.len() as f64;
+        let (ma, mb) = pairs
+            .iter()
+            .fold((0., 0.), |(sa, sb), (x, y)| (sa + x / n, sb + y / n));
+        let (mut cov, mut va, mut vb) = (0., 0., 0.);
+        for (x, y) in pairs {
+            cov += (x - ma) * (y - mb);
+            va += (x - ma) * (x - ma);
+            vb += (y - mb) * (y - mb);
+        }
+        if va == 0. || vb == 0. {
+            return None;
+        }
+        Some(cov / (va * vb).sqrt())
+    }
+    /// 样本不足的币种对记为0
+    pub fn matrix(&self, symbols: &[SymbolId]) -> CorrelationMatrix {
+        let values = symbols
+            .iter()
+            .map(|&a| {
+                symbols
+                    .iter()
+                    .map(|&b| self.correlation(a, b).unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        CorrelationMatrix {
+            symbols: symbols.to_vec(),
+            values,
+        }
+    }
+    /// 检查新开仓后该币种所在簇的合计名义敞口，exposures为现有仓位的带方向名义价值，
+    /// 返回超限原因；equity未知（不大于0）时不限制
+    pub fn check(
+        &self,
+        symbol: SymbolId,
+        value: f64,
+        exposures: &[(SymbolId, f64)],
+        equity: f64,
+    ) -> Result<(), String> {
+        if equity <= 0. {
+            return Ok(());
+        }
+        let mut cluster = vec![];
+        let mut total = value;
+        for &(s, exposure) in exposures {
+            if s != symbol
+                && self
+                    .correlation(symbol, s)
+                    .is_some_and(|c| c >= self.config.threshold)
+            {
+                cluster.push(s);
+                total += exposure;
+            } else if s == symbol {
+                total += exposure;
+            }
+        }
+        let limit = self.config.max_cluster_exposure * equity;
+        if total.abs() > limit {
+            return Err(format!(
+                "cluster {} + {:?} exposure {:.2} exceeds {:.2}",
+                symbol, cluster, total, limit
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn correlation_guard_test() {
+    let guard = CorrelationGuard::new(CorrelationConfig {
+        sample_interval: 1,
+        min_samples: 10,
+        max_cluster_exposure: 2.,
+        ..Default::default()
+    });
+    let (btc, eth, xrp) = (
+        SymbolId::intern("BTCUSDT"),
+        SymbolId::intern("ETHUSDT"),
+        SymbolId::intern("XRPUSDT"),
+    );
+    let mut rng = fastrand::Rng::with_seed(7);
+    let (mut pb, mut pe, mut px) = (100., 10., 1.);
+    for time in 0..100 {
+        let shock = rng.f64() - 0.5;
+        pb *= 1. + shock * 0.02;
+        pe *= 1. + shock * 0.03 + (rng.f64() - 0.5) * 0.002;
+        px *= 1. + (rng.f64() - 0.5) * 0.02;
+        for (symbol, mark_price) in [(btc, pb), (eth, pe), (xrp, px)] {
+            guard.update(&SymbolPrice {
+                symbol,
+                mark_price,
+                time,
+                ..Default::default()
+            });
+        }
+    }
+    assert!(guard.correlation(btc, eth).unwrap() > 0.9);
+    assert!(guard.correlation(btc, xrp).unwrap().abs() < 0.5);
+    let matrix = guard.matrix(&[btc, eth, xrp]);
+    assert_eq!(matrix.values[0][0], 1.);
+    assert_eq!(matrix.get(eth, btc), matrix.get(btc, eth));
+    // 权益100，簇上限200：已有BTC 150，再开ETH 100超限，开XRP不受影响
+    let exposures = [(btc, 150.), (xrp, 150.)];
+    assert!(guard.check(eth, 100., &exposures, 100.).is_err());
+    assert!(guard.check(eth, 40., &exposures, 100.).is_ok());
+    assert!(guard.check(xrp, 40., &exposures, 100.).is_ok());
+    assert!(guard.check(eth, 100., &exposures, 0.).is_ok());
+    // 样本不足视为不相关
+    let ada = SymbolId::intern("ADAUSDT");
+    assert_eq!(guard.correlation(btc, ada), None);
+
+    let config: CorrelationConfig = toml::from_str("window = 30").unwrap();
+    assert_eq!(config.min_samples, 60);
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,79 @@
+use std::{collections::HashSet, time::Duration};
+
+use parking_lot::Mutex;
+
+use crate::{binance_futures::countdown::CountdownClient, error::MarketError};
+
+/// 死人开关：为有未成交开仓单的币种定期刷新交易所的倒计时撤单，程序退出或断网后，
+/// 交易所在倒计时结束时撤销这些币种的全部挂单；倒计时会连同止盈止损一起撤销，不能用于持仓币种
+#[derive(Debug)]
+pub struct DeadManSwitch {
+    client: CountdownClient,
+    countdown: Duration,
+    /// 已设置倒计时的币种
+    armed: Mutex<HashSet<String>>,
+}
+
+impl DeadManSwitch {
+    pub fn new(client: CountdownClient, countdown: Duration) -> Self {
+        Self {
+            client,
+            countdown,
+            armed: Mutex::new(HashSet::new()),
+        }
+    }
+    /// 刷新间隔，倒计时内至少刷新三次，单次请求失败不会触发撤单
+    pub fn interval(&self) -> Duration {
+        self.countdown / 3
+    }
+    /// 为symbols刷新倒计时，之前设置过但已不在symbols中的币种取消倒计时；返回失败的币种及错误
+    pub fn refresh(&self, symbols: &[String]) -> Vec<(String, MarketError)> {
+        let mut armed = self.armed.lock();
+        let (refresh, disarm) = plan(&armed, symbols);
+        let mut errors = vec![];
+        for symbol in refresh {
+            match self.client.countdown(&symbol, self.countdown) {
+                Ok(_) => {
+                    armed.insert(symbol);
+                }
+                Err(e) => errors.push((symbol, e)),
+            }
+        }
+        for symbol in disarm {
+            match self.client.countdown(&symbol, Duration::ZERO) {
+                Ok(_) => {
+                    armed.remove(&symbol);
+                }
+                Err(e) => errors.push((symbol, e)),
+            }
+        }
+        errors
+    }
+}
+
+/// （需刷新的币种，需取消的币种）
+fn plan(armed: &HashSet<String>, symbols: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut refresh = symbols.to_vec();
+    refresh.sort();
+    refresh.dedup();
+    let mut disarm: Vec<String> = armed
+        .iter()
+        .filter(|s| !refresh.contains(s))
+        .cloned()
+        .collect();
+    disarm.sort();
+    (refresh, disarm)
+}
+
+#[test]
+fn dead_man_test() {
+    let armed: HashSet<String> = ["BTCUSDT", "ETHUSDT"].map(String::from).into();
+    let (refresh, disarm) = plan(
+        &armed,
+        &["SOLUSDT".into(), "BTCUSDT".into(), "SOLUSDT".into()],
+    );
+    assert_eq!(refresh, ["BTCUSDT", "SOLUSDT"]);
+    assert_eq!(disarm, ["ETHUSDT"]);
+    let (refresh, disarm) = plan(&HashSet::new(), &[]);
+    assert!(refresh.is_empty() && disarm.is_empty());
+}
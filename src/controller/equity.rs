@@ -0,0 +1,131 @@
+use std::thread::JoinHandle;
+
+use crossbeam::channel::Receiver;
+use plotters::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+/// 实盘权益采样
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct EquitySample {
+    /// unix毫秒
+    pub time: u64,
+    pub total_balance: f64,
+    pub unrealized_pnl: f64,
+    /// 1单位报告货币折合的结算资产，未配置报告货币或没有汇率时为None
+    pub base_rate: Option<f64>,
+}
+
+impl EquitySample {
+    pub fn equity(&self) -> f64 {
+        self.total_balance + self.unrealized_pnl
+    }
+    /// 以报告货币计价的权益
+    pub fn base_equity(&self) -> Option<f64> {
+        self.base_rate.map(|r| self.equity() / r)
+    }
+}
+
+/// 实盘权益曲线图，见ControllerConfig::equity_plot
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EquityPlotConfig {
+    /// PNG文件路径
+    pub path: String,
+    /// 每收到多少个权益采样重绘一次
+    #[serde(default = "default_redraw_every")]
+    pub redraw_every: usize,
+}
+
+fn default_redraw_every() -> usize {
+    10
+}
+
+/// 接收权益采样，每收到redraw_every个采样重绘一次PNG，通道关闭时绘制最后一次并退出
+pub fn serve_plot(rx: Receiver<EquitySample>, path: String, redraw_every: usize) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut samples = vec![];
+        let mut pending = 0;
+        for sample in rx.iter() {
+            samples.push(sample);
+            pending += 1;
+            if pending >= redraw_every.max(1) {
+                pending = 0;
+                if let Err(e) = plot(&samples, &path) {
+                    error!("Plot equity failed: {}", e);
+                }
+            }
+        }
+        if pending > 0 {
+            plot(&samples, &path).ok();
+        }
+        info!("Equity plot stopped");
+    })
+}
+
+/// 绘制余额和权益（余额 + 未实现盈亏）曲线，横轴为距第一个采样的小时数
+/// 先写入临时文件再替换，避免读取到未写完的图片
+pub fn plot(samples: &[EquitySample], path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(first) = samples.first() else {
+        return Ok(());
+    };
+    let tmp = format!("{}.tmp.png", path);
+    {
+        let root_area = BitMapBackend::new(&tmp, (1280, 720)).into_drawing_area();
+        root_area.fill(&WHITE)?;
+        let hour = |s: &EquitySample| s.time.saturating_sub(first.time) as f64 / 3_600_000.;
+        let max_x = samples.last().map(hour).unwrap_or(1.).max(1e-3);
+        let values = samples.iter().flat_map(|s| [s.total_balance, s.equity()]);
+        let min_y = values.clone().fold(f64::INFINITY, f64::min);
+        let max_y = values.fold(f64::NEG_INFINITY, f64::max);
+        let margin = ((max_y - min_y) * 0.05).max(1.);
+        let mut chart = ChartBuilder::on(&root_area)
+            .caption("Live equity", ("sans-serif", 40).into_font())
+            .x_label_area_size(35)
+            .y_label_area_size(60)
+            .build_cartesian_2d(0f64..max_x, (min_y - margin)..(max_y + margin))?;
+        chart.configure_mesh().x_desc("hours").draw()?;
+        chart
+            .draw_series(LineSeries::new(
+                samples.iter().map(|s| (hour(s), s.total_balance)),
+                BLUE,
+            ))?
+            .label("balance")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE));
+        chart
+            .draw_series(LineSeries::new(
+                samples.iter().map(|s| (hour(s), s.equity())),
+                RED,
+            ))?
+            .label("equity")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], RED));
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+        root_area.present()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[test]
+fn equity_plot_test() {
+    let path = std::env::temp_dir().join("hurribot_equity_test.png");
+    let path = path.to_str().unwrap().to_string();
+    std::fs::remove_file(&path).ok();
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let h = serve_plot(rx, path.clone(), 10);
+    for i in 0..5 {
+        tx.send(EquitySample {
+            time: i * 60_000,
+            total_balance: 100. + i as f64,
+            unrealized_pnl: -(i as f64),
+            base_rate: None,
+        })
+        .unwrap();
+    }
+    drop(tx);
+    h.join().unwrap();
+    assert!(std::path::Path::new(&path).exists());
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::{
+    error::{ConfigError, DataError},
+    events::{EconomicEvent, EventCalendar, Impact},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventAction {
+    /// 暂停开仓
+    Blackout,
+    /// 开仓金额乘以该比例，等效于降低杠杆
+    Reduce(f64),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct EventPolicy {
+    /// 事件前多久开始生效（毫秒）
+    pub before: u64,
+    /// 事件后多久恢复（毫秒）
+    pub after: u64,
+    /// 只处理影响不低于该值的事件
+    pub min_impact: Impact,
+    pub action: EventAction,
+}
+
+impl Default for EventPolicy {
+    fn default() -> Self {
+        Self {
+            before: 30 * 60_000,
+            after: 30 * 60_000,
+            min_impact: Impact::High,
+            action: EventAction::Blackout,
+        }
+    }
+}
+
+impl EventPolicy {
+    fn validate(&self) -> Result<(), ConfigError> {
+        match self.action {
+            EventAction::Reduce(scale) if !(0. ..=1.).contains(&scale) => Err(
+                ConfigError::Invalid(format!("event reduce scale {}", scale)),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 单个策略的事件规则
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StrategyEventPolicy {
+    /// 策略序号
+    pub index: usize,
+    /// 不设置为该策略不受限
+    pub policy: Option<EventPolicy>,
+}
+
+/// 事件日历和各策略的规则，见ControllerConfig::events
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EventConfig {
+    /// .ics或.csv日历文件
+    pub calendar: String,
+    /// 未单独配置的策略使用的规则，不设置为不受限
+    pub default: Option<EventPolicy>,
+    #[serde(default)]
+    pub strategies: Vec<StrategyEventPolicy>,
+}
+
+impl EventConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let policies = self.strategies.iter().filter_map(|s| s.policy.as_ref());
+        for policy in self.default.iter().chain(policies) {
+            policy.validate()?;
+        }
+        Ok(())
+    }
+    /// 读取日历，生成EventGuard
+    pub fn guard(&self) -> Result<EventGuard, DataError> {
+        let calendar = EventCalendar::load(&self.calendar)?;
+        Ok(self.strategies.iter().fold(
+            EventGuard::new(calendar, self.default.clone()),
+            |guard, s| guard.with_policy(s.index, s.policy.clone()),
+        ))
+    }
+}
+
+/// 重大经济事件前后限制开仓，只影响新开仓，不影响平仓和已有仓位的止盈止损
+#[derive(Debug, Clone, Default)]
+pub struct EventGuard {
+    calendar: EventCalendar,
+    /// 未单独配置的策略使用的规则，None为不受限
+    default: Option<EventPolicy>,
+    /// 策略序号 -> 规则，None为该策略不受限
+    policies: HashMap<usize, Option<EventPolicy>>,
+}
+
+impl EventGuard {
+    pub fn new(calendar: EventCalendar, default: Option<EventPolicy>) -> Self {
+        Self {
+            calendar,
+            default,
+            policies: HashMap::new(),
+        }
+    }
+    pub fn with_policy(mut self, index: usize, policy: Option<EventPolicy>) -> Self {
+        self.policies.insert(index, policy);
+        self
+    }
+    /// 策略index在time（unix毫秒）开仓时需采取的处理及对应事件
+    pub fn check(&self, index: usize, time: u64) -> Option<(EventAction, &EconomicEvent)> {
+        let policy = self
+            .policies
+            .get(&index)
+            .unwrap_or(&self.default)
+            .as_ref()?;
+        let event = self
+            .calendar
+            .active(time, policy.before, policy.after, policy.min_impact)?;
+        Some((policy.action, event))
+    }
+}
+
+#[test]
+fn event_guard_test() {
+    let hour = 3_600_000;
+    let calendar = EventCalendar::new(vec![
+        EconomicEvent {
+            time: 10 * hour,
+            name: "FOMC".into(),
+            impact: Impact::High,
+        },
+        EconomicEvent {
+            time: 20 * hour,
+            name: "Jobless Claims".into(),
+            impact: Impact::Medium,
+        },
+    ]);
+    let guard = EventGuard::new(calendar, Some(EventPolicy::default()))
+        .with_policy(
+            1,
+            Some(EventPolicy {
+                before: hour,
+                after: 2 * hour,
+                min_impact: Impact::Medium,
+                action: EventAction::Reduce(0.5),
+            }),
+        )
+        .with_policy(2, None);
+    let check = |index, time| guard.check(index, time).map(|(a, e)| (a, e.name.as_str()));
+    assert_eq!(
+        check(0, 10 * hour - 60_000),
+        Some((EventAction::Blackout, "FOMC"))
+    );
+    assert_eq!(check(0, 11 * hour), None);
+    assert_eq!(check(0, 20 * hour), None);
+    assert_eq!(
+        check(1, 11 * hour),
+        Some((EventAction::Reduce(0.5), "FOMC"))
+    );
+    assert_eq!(
+        check(1, 19 * hour),
+        Some((EventAction::Reduce(0.5), "Jobless Claims"))
+    );
+    assert_eq!(check(2, 10 * hour), None);
+
+    let path = std::env::temp_dir().join("hurribot_event_guard_test.csv");
+    std::fs::write(&path, "time,name,impact\n36000,FOMC,high\n").unwrap();
+    let config: EventConfig = toml::from_str(&format!(
+        r#"
+        calendar = "{}"
+        default = {{ before = 60000 }}
+        [[strategies]]
+        index = 1
+        policy = {{ min_impact = "medium", action = {{ reduce = 0.5 }} }}
+        [[strategies]]
+        index = 2
+        "#,
+        path.display()
+    ))
+    .unwrap();
+    config.validate().unwrap();
+    let guard = config.guard().unwrap();
+    assert_eq!(
+        guard.check(0, 36_000_000).map(|(a, _)| a),
+        Some(EventAction::Blackout)
+    );
+    assert!(guard.check(0, 36_000_000 - 120_000).is_none());
+    assert_eq!(
+        guard.check(1, 36_000_000).map(|(a, _)| a),
+        Some(EventAction::Reduce(0.5))
+    );
+    assert!(guard.check(2, 36_000_000).is_none());
+    let invalid = EventConfig {
+        default: Some(EventPolicy {
+            action: EventAction::Reduce(2.),
+            ..Default::default()
+        }),
+        ..config
+    };
+    assert!(invalid.validate().is_err());
+}
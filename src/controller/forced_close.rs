@@ -0,0 +1,81 @@
+use binance::futures::model::OrderUpdate;
+
+/// 交易所发起的强制平仓
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForcedClose {
+    /// 强平
+    Liquidation,
+    /// 自动减仓
+    Adl,
+}
+
+impl ForcedClose {
+    /// 按client order id和原始订单类型识别：强平为autoclose-前缀或LIQUIDATION类型，ADL为adl_autoclose
+    pub fn parse(order: &OrderUpdate) -> Option<Self> {
+        let id = order.new_client_order_id.as_str();
+        if id.starts_with("adl_autoclose") {
+            Some(Self::Adl)
+        } else if id.starts_with("autoclose-") || order.original_order_type == "LIQUIDATION" {
+            Some(Self::Liquidation)
+        } else {
+            None
+        }
+    }
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Liquidation => "Liquidation",
+            Self::Adl => "Auto-deleverage",
+        }
+    }
+}
+
+/// 强平或ADL订单已结束（可能只部分成交），此时处理剩余仓位
+pub fn is_final(order: &OrderUpdate) -> bool {
+    matches!(order.order_status.as_str(), "FILLED" | "EXPIRED")
+}
+
+/// MARGIN_CALL事件中的持仓，由user_stream解析后转换为AccountInfo::MarginCall
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarginCallPosition {
+    pub symbol: String,
+    pub amount: f64,
+    pub mark_price: f64,
+    pub maint_margin: f64,
+}
+
+#[test]
+fn forced_close_test() {
+    use crate::{controller::AccountInfo, market::mock_market::FakeFill};
+
+    let order = |client_order_id: &str| {
+        let fill = FakeFill {
+            symbol: "BTCUSDT".into(),
+            client_order_id: client_order_id.into(),
+            qty: -1.,
+            price: 90.,
+            ..Default::default()
+        };
+        match fill.event(0) {
+            AccountInfo::OrderTrade { order, .. } => order,
+            _ => unreachable!(),
+        }
+    };
+    assert_eq!(
+        ForcedClose::parse(&order("autoclose-1711310062035")),
+        Some(ForcedClose::Liquidation)
+    );
+    assert_eq!(
+        ForcedClose::parse(&order("adl_autoclose")),
+        Some(ForcedClose::Adl)
+    );
+    assert_eq!(ForcedClose::parse(&order("s0_1_0")), None);
+    let mut liquidation = order("web_123");
+    liquidation.original_order_type = "LIQUIDATION".into();
+    assert_eq!(
+        ForcedClose::parse(&liquidation),
+        Some(ForcedClose::Liquidation)
+    );
+    assert!(is_final(&liquidation));
+    liquidation.order_status = "PARTIALLY_FILLED".into();
+    assert!(!is_final(&liquidation));
+}
@@ -0,0 +1,131 @@
+use dashmap::DashMap;
+use serde::Deserialize;
+
+use crate::error::ConfigError;
+
+/// 见ControllerConfig::funding
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FundingScheduleConfig {
+    /// 结算间隔（毫秒），币安为UTC 0:00 8:00 16:00
+    pub interval: u64,
+    /// 需支付资金费率时，结算前多久平仓并停止开仓（毫秒）
+    pub close_before: u64,
+    /// 结算后多久恢复开仓（毫秒）
+    pub entry_after: u64,
+    /// 资金费率绝对值不超过该值时不处理
+    pub min_rate: f64,
+}
+
+impl Default for FundingScheduleConfig {
+    fn default() -> Self {
+        Self {
+            interval: 8 * 3600 * 1000,
+            close_before: 60_000,
+            entry_after: 10_000,
+            min_rate: 0.,
+        }
+    }
+}
+
+impl FundingScheduleConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.interval == 0 {
+            return Err(ConfigError::Invalid(
+                "funding interval must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 资金费率结算前后的开平仓时机：需支付资金费率的仓位在结算前平仓，
+/// 结算前后的窗口内推迟同方向开仓；收取资金费率的仓位不受影响
+#[derive(Debug, Default)]
+pub struct FundingSchedule {
+    config: FundingScheduleConfig,
+    /// 币种 -> 已为之平仓的结算时间，每次结算只平仓一次
+    closed: DashMap<String, u64>,
+}
+
+impl FundingSchedule {
+    pub fn new(config: FundingScheduleConfig) -> Self {
+        Self {
+            config,
+            closed: DashMap::new(),
+        }
+    }
+    /// 不早于time的下一次结算时间（unix毫秒）
+    pub fn next_settlement(&self, time: u64) -> u64 {
+        time.div_ceil(self.config.interval) * self.config.interval
+    }
+    /// 该方向的仓位是否需支付资金费率（正费率多头支付）
+    pub fn pays(&self, is_long: bool, funding_rate: f64) -> bool {
+        if funding_rate.abs() <= self.config.min_rate {
+            return false;
+        }
+        (funding_rate > 0.) == is_long
+    }
+    /// 是否处于结算前close_before到结算后entry_after的窗口内
+    fn in_window(&self, time: u64) -> bool {
+        let last = time / self.config.interval * self.config.interval;
+        time + self.config.close_before >= self.next_settlement(time)
+            || time < last + self.config.entry_after
+    }
+    /// 需支付资金费率时，窗口内推迟开仓
+    pub fn allow_entry(&self, is_long: bool, funding_rate: f64, time: u64) -> bool {
+        !(self.pays(is_long, funding_rate) && self.in_window(time))
+    }
+    /// 需支付资金费率的仓位进入结算前窗口时返回true，每次结算只返回一次
+    pub fn should_close(&self, symbol: &str, amount: f64, funding_rate: f64, time: u64) -> bool {
+        if amount == 0. || !self.pays(amount > 0., funding_rate) {
+            return false;
+        }
+        let next = self.next_settlement(time);
+        if time + self.config.close_before < next || time == next {
+            return false;
+        }
+        let mut closed = self.closed.entry(symbol.to_string()).or_default();
+        if *closed == next {
+            return false;
+        }
+        *closed = next;
+        true
+    }
+}
+
+#[test]
+fn funding_schedule_test() {
+    let schedule = FundingSchedule::default();
+    let hour = 3600 * 1000;
+    let settle = 16 * hour;
+    assert_eq!(schedule.next_settlement(settle - 1), settle);
+    assert_eq!(schedule.next_settlement(settle), settle);
+    assert_eq!(schedule.next_settlement(settle + 1), 24 * hour);
+    // 多头支付正费率，空头收取
+    assert!(schedule.pays(true, 0.0001));
+    assert!(!schedule.pays(false, 0.0001));
+    assert!(schedule.pays(false, -0.0001));
+    // 结算前一分钟平仓，只平一次
+    assert!(!schedule.should_close("BTCUSDT", 1., 0.0001, settle - 61_000));
+    assert!(schedule.should_close("BTCUSDT", 1., 0.0001, settle - 30_000));
+    assert!(!schedule.should_close("BTCUSDT", 1., 0.0001, settle - 20_000));
+    assert!(!schedule.should_close("ETHUSDT", -1., 0.0001, settle - 30_000));
+    // 结算前一分钟到结算后10秒内推迟需支付资金费率的开仓
+    assert!(schedule.allow_entry(true, 0.0001, settle - 61_000));
+    assert!(!schedule.allow_entry(true, 0.0001, settle - 30_000));
+    assert!(!schedule.allow_entry(true, 0.0001, settle));
+    assert!(!schedule.allow_entry(true, 0.0001, settle + 5_000));
+    assert!(schedule.allow_entry(true, 0.0001, settle + 10_000));
+    assert!(schedule.allow_entry(false, 0.0001, settle - 30_000));
+    let schedule = FundingSchedule::new(FundingScheduleConfig {
+        min_rate: 0.0005,
+        ..Default::default()
+    });
+    assert!(schedule.allow_entry(true, 0.0001, settle));
+    let config = FundingScheduleConfig {
+        interval: 0,
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+use serde::Deserialize;
+
+use crate::{
+    capital::{Capital, CapitalPolicy},
+    error::ConfigError,
+};
+
+/// 策略虚拟子账户
+#[derive(Debug, Clone, Default)]
+pub struct SubAccount {
+    /// 分配的资金
+    pub allocated: f64,
+    /// 已实现盈亏
+    pub realized_pnl: f64,
+    /// 手续费
+    pub commission: f64,
+    /// 币种 -> 持仓名义价值
+    pub exposure: HashMap<String, f64>,
+    /// 盈利是否复利
+    pub capital: Capital,
+}
+
+impl SubAccount {
+    /// 权益，含储备
+    pub fn balance(&self) -> f64 {
+        self.allocated + self.realized_pnl - self.commission
+    }
+    /// 按资金策略可用于交易的资金
+    pub fn trading_capital(&self) -> f64 {
+        self.capital.trading(self.balance())
+    }
+    /// 按资金策略留作储备、不参与交易的盈利，与Ledger::reserve的预占无关
+    pub fn profit_reserve(&self) -> f64 {
+        self.capital.reserve(self.balance())
+    }
+    /// 可用于新开仓的名义价值
+    pub fn available(&self) -> f64 {
+        self.trading_capital() - self.exposure.values().sum::<f64>()
+    }
+}
+
+/// 单个策略的资金策略
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct StrategyCapitalPolicy {
+    /// 策略序号
+    pub index: usize,
+    pub policy: CapitalPolicy,
+}
+
+/// 各策略盈利计入资金的方式，见ControllerConfig::capital
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CapitalConfig {
+    /// 未单独配置的策略使用的资金策略
+    pub default: CapitalPolicy,
+    pub strategies: Vec<StrategyCapitalPolicy>,
+}
+
+impl CapitalConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let policies = self.strategies.iter().map(|s| &s.policy);
+        for policy in std::iter::once(&self.default).chain(policies) {
+            if let CapitalPolicy::Ratchet { step } = policy {
+                if step.is_nan() || *step <= 0. {
+                    return Err(ConfigError::Invalid(format!(
+                        "ratchet step must be positive: {}",
+                        step
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+    /// 策略index使用的资金策略
+    pub fn policy(&self, index: usize) -> CapitalPolicy {
+        self.strategies
+            .iter()
+            .find(|s| s.index == index)
+            .map_or(self.default, |s| s.policy)
+    }
+}
+
+/// 多策略共用账户时的资金分账，成交按client order id归属到策略
+#[derive(Debug, Default)]
+pub struct Ledger {
+    accounts: Vec<Mutex<SubAccount>>,
+}
+
+impl Ledger {
+    /// allocations与策略一一对应
+    pub fn new(allocations: &[f64]) -> Self {
+        Self {
+            accounts: allocations
+                .iter()
+                .map(|&allocated| {
+                    Mutex::new(SubAccount {
+                        allocated,
+                        capital: Capital::new(CapitalPolicy::default(), allocated),
+                        ..Default::default()
+                    })
+                })
+                .collect(),
+        }
+    }
+    /// 为策略index设置资金策略，从当前权益重新起算
+    pub fn set_policy(&self, index: usize, policy: CapitalPolicy) {
+        if let Some(account) = self.accounts.get(index) {
+            let mut account = account.lock();
+            account.capital = Capital::new(policy, account.balance());
+        }
+    }
+    pub fn account(&self, index: usize) -> Option<SubAccount> {
+        self.accounts.get(index).map(|a| a.lock().clone())
+    }
+    pub fn balance(&self, index: usize) -> f64 {
+        self.accounts
+            .get(index)
+            .map(|a| a.lock().balance())
+            .unwrap_or_default()
+    }
+    /// 下单定量使用的资金
+    pub fn trading_capital(&self, index: usize) -> f64 {
+        self.accounts
+            .get(index)
+            .map(|a| a.lock().trading_capital())
+            .unwrap_or_default()
+    }
+    /// 预占名义价值，超出预算时返回false
+    pub fn reserve(&self, index: usize, symbol: &str, value: f64) -> bool {
+        let Some(account) = self.accounts.get(index) else {
+            return false;
+        };
+        let mut account = account.lock();
+        if value > account.available() {
+            return false;
+        }
+        *account.exposure.entry(symbol.to_string()).or_default() += value;
+        true
+    }
+    /// 仓位关闭或下单失败后释放预占
+    pub fn release(&self, index: usize, symbol: &str) {
+        if let Some(account) = self.accounts.get(index) {
+            account.lock().exposure.remove(symbol);
+        }
+    }
+    pub fn record_fill(&self, index: usize, pnl: f64, commission: f64) {
+        if let Some(account) = self.accounts.get(index) {
+            let mut account = account.lock();
+            account.realized_pnl += pnl;
+            account.commission += commission;
+            let balance = account.balance();
+            account.capital.update(balance);
+        }
+    }
+    /// 策略下单使用的client order id前缀，与ClientOrderId的格式一致
+    pub fn prefix(index: usize) -> String {
+        format!("s{}_", index)
+    }
+    /// 从client order id解析策略序号，只要求有策略前缀，不要求是完整的ClientOrderId
+    pub fn strategy_of(client_order_id: &str) -> Option<usize> {
+        let (index, _) = client_order_id.strip_prefix('s')?.split_once('_')?;
+        index.parse().ok()
+    }
+}
+
+#[test]
+fn ledger_test() {
+    let ledger = Ledger::new(&[100., 50.]);
+    assert_eq!(Ledger::strategy_of(&(Ledger::prefix(1) + "abc")), Some(1));
+    assert_eq!(Ledger::strategy_of("s2_1700000000123_1"), Some(2));
+    assert_eq!(Ledger::strategy_of("web_123"), None);
+    assert!(ledger.reserve(0, "BTCUSDT", 80.));
+    assert!(!ledger.reserve(0, "ETHUSDT", 30.));
+    ledger.record_fill(0, 20., 1.);
+    assert_eq!(ledger.balance(0), 119.);
+    assert!(ledger.reserve(0, "ETHUSDT", 30.));
+    ledger.release(0, "BTCUSDT");
+    assert_eq!(ledger.account(0).unwrap().available(), 89.);
+    assert!(!ledger.reserve(2, "BTCUSDT", 1.));
+    // 固定本金时盈利不可用于开仓
+    ledger.set_policy(1, CapitalPolicy::Fixed);
+    ledger.record_fill(1, 10., 0.);
+    assert_eq!(ledger.balance(1), 60.);
+    assert_eq!(ledger.trading_capital(1), 50.);
+    assert_eq!(ledger.account(1).unwrap().profit_reserve(), 10.);
+    assert!(!ledger.reserve(1, "BTCUSDT", 55.));
+}
+
+#[test]
+fn capital_config_test() {
+    let config: CapitalConfig = toml::from_str(
+        r#"
+        default = "fixed"
+        strategies = [{ index = 1, policy = { ratchet = { step = 0.1 } } }]
+        "#,
+    )
+    .unwrap();
+    config.validate().unwrap();
+    assert_eq!(config.policy(0), CapitalPolicy::Fixed);
+    assert_eq!(config.policy(1), CapitalPolicy::Ratchet { step: 0.1 });
+    assert_eq!(CapitalConfig::default().policy(0), CapitalPolicy::Compound);
+    let invalid: CapitalConfig = toml::from_str("default = { ratchet = { step = 0.0 } }").unwrap();
+    assert!(invalid.validate().is_err());
+}
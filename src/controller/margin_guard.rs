@@ -0,0 +1,179 @@
+use dashmap::DashMap;
+use serde::Deserialize;
+
+use crate::{
+    error::{ConfigError, MarketError},
+    liquidation::MarginBrackets,
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MarginGuardConfig {
+    /// 距强平的最小价格比例，低于该值时追加保证金
+    pub min_buffer: f64,
+    /// 追加保证金后恢复到的价格比例
+    pub target_buffer: f64,
+    /// 每个仓位累计追加保证金上限（USDT）
+    pub max_added: f64,
+}
+
+impl Default for MarginGuardConfig {
+    fn default() -> Self {
+        Self {
+            min_buffer: 0.03,
+            target_buffer: 0.05,
+            max_added: 50.,
+        }
+    }
+}
+
+impl MarginGuardConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let buffers = 0. < self.min_buffer && self.min_buffer < self.target_buffer;
+        if !buffers || self.target_buffer >= 1. || self.max_added < 0. {
+            return Err(ConfigError::Invalid(format!(
+                "margin guard buffer {} - {}, max added {}",
+                self.min_buffer, self.target_buffer, self.max_added
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TopUp {
+    /// 追加保证金
+    Add(f64),
+    /// 达到上限，只能追加剩余额度（可能为0）
+    Capped(f64),
+}
+
+/// 逐仓保证金守护：按标记价格监控距强平的距离，自动追加保证金
+#[derive(Debug, Default)]
+pub struct MarginGuard {
+    config: MarginGuardConfig,
+    /// 币种 -> 已追加保证金
+    added: DashMap<String, f64>,
+    /// 币种 -> 维持保证金阶梯，未设置时使用默认阶梯
+    brackets: DashMap<String, MarginBrackets>,
+    default_brackets: MarginBrackets,
+}
+
+impl MarginGuard {
+    pub fn new(config: MarginGuardConfig) -> Self {
+        Self {
+            config,
+            added: DashMap::new(),
+            brackets: DashMap::new(),
+            default_brackets: MarginBrackets::default(),
+        }
+    }
+    pub fn set_brackets(&self, symbol: &str, brackets: MarginBrackets) {
+        self.brackets.insert(symbol.to_string(), brackets);
+    }
+    pub fn has_brackets(&self, symbol: &str) -> bool {
+        self.brackets.contains_key(symbol)
+    }
+    /// 用币种的阶梯计算，未设置时使用默认阶梯
+    fn with_brackets<T>(&self, symbol: &str, f: impl FnOnce(&MarginBrackets) -> T) -> T {
+        match self.brackets.get(symbol) {
+            Some(b) => f(&b),
+            None => f(&self.default_brackets),
+        }
+    }
+    /// 价格向不利方向变动多少比例后触发强平
+    pub fn liquidation_distance(
+        &self,
+        symbol: &str,
+        amount: f64,
+        entry: f64,
+        wallet: f64,
+        mark: f64,
+    ) -> Result<f64, MarketError> {
+        self.with_brackets(symbol, |b| {
+            b.liquidation_distance(amount, entry, wallet, mark)
+        })
+    }
+    /// 按标记价格对应的档位计算的强平价格
+    pub fn liq_price(
+        &self,
+        symbol: &str,
+        amount: f64,
+        entry: f64,
+        wallet: f64,
+    ) -> Result<f64, MarketError> {
+        self.with_brackets(symbol, |b| b.liq_price(amount, entry, wallet, 0.))
+    }
+    /// 计算需要追加的保证金，None表示不需要；超过上限时返回Capped且之后不再追加
+    pub fn check(
+        &self,
+        symbol: &str,
+        amount: f64,
+        entry: f64,
+        wallet: f64,
+        mark: f64,
+    ) -> Result<Option<TopUp>, MarketError> {
+        if amount == 0. {
+            self.added.remove(symbol);
+            return Ok(None);
+        }
+        if self.liquidation_distance(symbol, amount, entry, wallet, mark)? >= self.config.min_buffer
+        {
+            return Ok(None);
+        }
+        let notional = amount.abs() * mark;
+        let maint_margin = self.with_brackets(symbol, |b| b.maint_margin(notional))?;
+        let mut added = self.added.entry(symbol.to_string()).or_default();
+        if *added >= self.config.max_added {
+            return Ok(None);
+        }
+        let equity = wallet + (mark - entry) * amount;
+        let need = maint_margin + notional * self.config.target_buffer - equity;
+        let remaining = self.config.max_added - *added;
+        if need > remaining {
+            *added = self.config.max_added;
+            Ok(Some(TopUp::Capped(remaining)))
+        } else {
+            *added += need;
+            Ok(Some(TopUp::Add(need)))
+        }
+    }
+    pub fn added(&self, symbol: &str) -> f64 {
+        self.added.get(symbol).map(|a| *a).unwrap_or_default()
+    }
+}
+
+#[test]
+fn margin_guard_test() {
+    let guard = MarginGuard::new(MarginGuardConfig {
+        max_added: 3.,
+        ..Default::default()
+    });
+    // 多头1个，开仓100，保证金5
+    let distance = guard.liquidation_distance("BTCUSDT", 1., 100., 5., 100.);
+    assert!((distance.unwrap() - 0.046).abs() < 1e-9);
+    assert_eq!(guard.check("BTCUSDT", 1., 100., 5., 100.).unwrap(), None);
+    // 跌到98，距强平约2.7%
+    let Some(TopUp::Add(m)) = guard.check("BTCUSDT", 1., 100., 5., 98.).unwrap() else {
+        panic!()
+    };
+    let distance = guard.liquidation_distance("BTCUSDT", 1., 100., 5. + m, 98.);
+    assert!((distance.unwrap() - 0.05).abs() < 1e-9);
+    assert_eq!(
+        guard.check("BTCUSDT", 1., 100., 5. + m, 95.).unwrap(),
+        Some(TopUp::Capped(3. - m))
+    );
+    assert_eq!(guard.check("BTCUSDT", 1., 100., 8., 90.).unwrap(), None);
+    // 平仓后重置
+    guard.check("BTCUSDT", 0., 0., 0., 90.).unwrap();
+    assert_eq!(guard.added("BTCUSDT"), 0.);
+    // 交易所返回空阶梯时报错，不记入已追加额度
+    guard.set_brackets("ETHUSDT", MarginBrackets::from(&[][..]));
+    assert!(guard.has_brackets("ETHUSDT"));
+    assert!(guard.check("ETHUSDT", 1., 100., 5., 98.).is_err());
+    assert_eq!(guard.added("ETHUSDT"), 0.);
+
+    let config: MarginGuardConfig = toml::from_str("min_buffer = 0.06").unwrap();
+    assert_eq!(config.max_added, 50.);
+    assert!(config.validate().is_err());
+}
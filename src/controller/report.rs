@@ -0,0 +1,117 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+use time::{Duration, OffsetDateTime, Time};
+
+/// 账户快照
+#[derive(Debug, Clone, Default)]
+pub struct AccountSnapshot {
+    /// unix毫秒
+    pub time: u64,
+    pub total_balance: f64,
+    pub cross_balance: f64,
+    pub positions: Vec<PositionSnapshot>,
+    /// 距上次快照的已实现盈亏（来自成交回报）
+    pub realized_pnl: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PositionSnapshot {
+    pub symbol: String,
+    /// 持仓数量，空头为负
+    pub amount: f64,
+    pub entry_price: f64,
+    pub mark_price: f64,
+    /// 按标记价格市价平仓的预估手续费
+    pub close_fee: f64,
+}
+
+impl PositionSnapshot {
+    pub fn unrealized_pnl(&self) -> f64 {
+        (self.mark_price - self.entry_price) * self.amount
+    }
+}
+
+impl AccountSnapshot {
+    pub fn unrealized_pnl(&self) -> f64 {
+        self.positions.iter().map(|p| p.unrealized_pnl()).sum()
+    }
+    /// 扣除预估平仓手续费后的未实现盈亏
+    pub fn net_unrealized_pnl(&self) -> f64 {
+        self.positions
+            .iter()
+            .map(|p| p.unrealized_pnl() - p.close_fee)
+            .sum()
+    }
+}
+
+impl Display for AccountSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Daily report @ {}", self.time)?;
+        writeln!(
+            f,
+            "balance: {:.2}, cross: {:.2}",
+            self.total_balance, self.cross_balance
+        )?;
+        writeln!(
+            f,
+            "realized pnl: {:.2}, unrealized pnl: {:.2}, net of close fees: {:.2}",
+            self.realized_pnl,
+            self.unrealized_pnl(),
+            self.net_unrealized_pnl()
+        )?;
+        for p in self.positions.iter() {
+            writeln!(
+                f,
+                "  {}: amount {}, entry {}, mark {}, pnl {:.2}, close fee {:.2}",
+                p.symbol,
+                p.amount,
+                p.entry_price,
+                p.mark_price,
+                p.unrealized_pnl(),
+                p.close_fee
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// 距离下一个at时刻（与now同一时区）的时长
+pub fn until_next(now: OffsetDateTime, at: Time) -> Duration {
+    let mut next = now.replace_time(at);
+    if next <= now {
+        next += Duration::days(1);
+    }
+    next - now
+}
+
+#[test]
+fn report_test() {
+    use time::macros::{datetime, time};
+
+    let now = datetime!(2024-01-01 12:00 +8);
+    assert_eq!(until_next(now, time!(13:30)), Duration::minutes(90));
+    assert_eq!(until_next(now, time!(12:00)), Duration::days(1));
+    let snapshot = AccountSnapshot {
+        positions: vec![
+            PositionSnapshot {
+                symbol: "BTCUSDT".to_string(),
+                amount: 0.5,
+                entry_price: 100.,
+                mark_price: 110.,
+                close_fee: 0.5,
+            },
+            PositionSnapshot {
+                symbol: "ETHUSDT".to_string(),
+                amount: -2.,
+                entry_price: 10.,
+                mark_price: 11.,
+                close_fee: 0.,
+            },
+        ],
+        ..Default::default()
+    };
+    assert_eq!(snapshot.unrealized_pnl(), 3.);
+    assert_eq!(snapshot.net_unrealized_pnl(), 2.5);
+    assert!(snapshot.to_string().contains("BTCUSDT"));
+}
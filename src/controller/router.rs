@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use crate::{strategy::Strategy, symbol::SymbolId};
+
+/// 行情分片数，同一币种总在同一分片内按顺序处理
+pub const SIGNAL_SHARDS: usize = 4;
+/// 每个分片的行情队列容量，满时按币种只保留最新的价格
+pub const SHARD_QUEUE: usize = 1024;
+
+/// 币种 -> 关注该币种的策略序号，构建时合并好，分发行情时不分配内存
+#[derive(Debug, Clone, Default)]
+pub struct SymbolRouter {
+    /// 含关注所有币种的策略，升序
+    routes: HashMap<SymbolId, Vec<usize>>,
+    /// 关注所有币种的策略
+    all: Vec<usize>,
+}
+
+impl SymbolRouter {
+    pub fn new(strategies: &[Box<dyn Strategy>]) -> Self {
+        let mut router = Self::default();
+        for (index, strategy) in strategies.iter().enumerate() {
+            match strategy.symbols() {
+                Some(symbols) => {
+                    for symbol in symbols {
+                        router
+                            .routes
+                            .entry(SymbolId::intern(&symbol))
+                            .or_default()
+                            .push(index);
+                    }
+                }
+                None => router.all.push(index),
+            }
+        }
+        for indices in router.routes.values_mut() {
+            indices.extend(router.all.iter().copied());
+            indices.sort_unstable();
+            indices.dedup();
+        }
+        router
+    }
+    /// 应收到该币种行情的策略序号，升序
+    pub fn route(&self, symbol: SymbolId) -> &[usize] {
+        self.routes.get(&symbol).unwrap_or(&self.all)
+    }
+}
+
+/// 币种所在的分片，按驻留序号取模，不查币种表
+pub fn shard(symbol: SymbolId, shards: usize) -> usize {
+    symbol.id() as usize % shards.max(1)
+}
+
+#[test]
+fn symbol_router_test() {
+    use crate::{
+        algorithm::SymbolPrice,
+        strategy::{StrategyOrderRequest, StrategyOrderReturn},
+    };
+
+    #[derive(Debug)]
+    struct Watch(Option<Vec<String>>);
+    impl Strategy for Watch {
+        fn notify(&self, _: StrategyOrderReturn) {}
+        fn update(&self, _: &SymbolPrice) -> Option<StrategyOrderRequest> {
+            None
+        }
+        fn symbols(&self) -> Option<Vec<String>> {
+            self.0.clone()
+        }
+    }
+    let strategies: Vec<Box<dyn Strategy>> = vec![
+        Box::new(Watch(Some(vec!["BTCUSDT".to_string()]))),
+        Box::new(Watch(None)),
+        Box::new(Watch(Some(vec![
+            "ETHUSDT".to_string(),
+            "BTCUSDT".to_string(),
+        ]))),
+    ];
+    let router = SymbolRouter::new(&strategies);
+    assert_eq!(router.route("BTCUSDT".into()), [0, 1, 2]);
+    assert_eq!(router.route("ETHUSDT".into()), [1, 2]);
+    assert_eq!(router.route("SOLUSDT".into()), [1]);
+    assert!(shard("BTCUSDT".into(), SIGNAL_SHARDS) < SIGNAL_SHARDS);
+    assert_eq!(shard("BTCUSDT".into(), 4), shard("BTCUSDT".into(), 4));
+}
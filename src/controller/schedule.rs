@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::error::ConfigError;
+
+/// cron表达式：分 时 日 月 周（UTC，周日为0或7），支持*、列表、范围和步长，如"45 7,15,23 * * *"
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cron {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    /// 日和周均有限制时满足其一即可，与标准cron一致
+    day_any: bool,
+    weekday_any: bool,
+}
+
+/// 解析一个字段为位掩码
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, ConfigError> {
+    let invalid = || ConfigError::Invalid(format!("cron field {}", field));
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (
+                    a.parse().map_err(|_| invalid())?,
+                    b.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let v = range.parse().map_err(|_| invalid())?;
+                    // 单个值带步长时到最大值为止
+                    (v, if part.contains('/') { max } else { v })
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for v in (start..=end).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+impl TryFrom<String> for Cron {
+    type Error = ConfigError;
+    fn try_from(expr: String) -> Result<Self, Self::Error> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(ConfigError::Invalid(format!("cron {}", expr)));
+        };
+        let weekdays = parse_field(weekday, 0, 7)?;
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)? as u32,
+            days: parse_field(day, 1, 31)? as u32,
+            months: parse_field(month, 1, 12)? as u16,
+            // 7与0同为周日
+            weekdays: (weekdays | weekdays >> 7) as u8 & 0x7f,
+            day_any: day == "*",
+            weekday_any: weekday == "*",
+        })
+    }
+}
+
+impl Cron {
+    pub fn matches(&self, time: OffsetDateTime) -> bool {
+        let time = time.to_offset(time::UtcOffset::UTC);
+        let day = self.days & 1 << time.day() != 0;
+        let weekday = self.weekdays & 1 << time.weekday().number_days_from_sunday() != 0;
+        let date = match (self.day_any, self.weekday_any) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        date && self.minutes & 1 << time.minute() != 0
+            && self.hours & 1 << time.hour() != 0
+            && self.months & 1 << time.month() as u8 != 0
+    }
+}
+
+/// 从cron匹配的时刻开始持续minutes分钟
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScheduleWindow {
+    pub cron: Cron,
+    pub minutes: u64,
+}
+
+impl ScheduleWindow {
+    /// time为毫秒时间戳
+    pub fn contains(&self, time: u64) -> bool {
+        let minute = time / 60_000;
+        (0..self.minutes.min(minute + 1)).any(|i| {
+            OffsetDateTime::from_unix_timestamp(((minute - i) * 60) as i64)
+                .is_ok_and(|t| self.cron.matches(t))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleMode {
+    /// 只在窗口内运行
+    Active,
+    /// 窗口内暂停
+    Pause,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScheduleRule {
+    /// 策略序号，None为所有策略
+    #[serde(default)]
+    pub strategy: Option<usize>,
+    pub mode: ScheduleMode,
+    pub windows: Vec<ScheduleWindow>,
+    /// 停用时平掉策略的仓位，否则只停止向策略推送行情，已有仓位的止盈止损不变
+    #[serde(default)]
+    pub close: bool,
+}
+
+impl ScheduleRule {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // 没有窗口或窗口长度为0的规则不会生效
+        if self.windows.is_empty() || self.windows.iter().any(|w| w.minutes == 0) {
+            return Err(ConfigError::Invalid("schedule windows".to_string()));
+        }
+        Ok(())
+    }
+    fn applies(&self, index: usize) -> bool {
+        self.strategy.is_none_or(|s| s == index)
+    }
+}
+
+/// 按计划启用和停用策略，与熔断和手动暂停相互独立
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    rules: Vec<ScheduleRule>,
+    /// 上次计算的分钟，策略序号 -> 是否启用
+    state: Mutex<(u64, HashMap<usize, bool>)>,
+}
+
+impl Scheduler {
+    pub fn new(rules: Vec<ScheduleRule>) -> Self {
+        Self {
+            rules,
+            ..Default::default()
+        }
+    }
+    /// 读取配置文件中的[[schedule]]部分
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
+        #[derive(Deserialize)]
+        struct File {
+            #[serde(default)]
+            schedule: Vec<ScheduleRule>,
+        }
+        let c = std::fs::read_to_string(path)?;
+        let val: File = toml::from_str(&c)?;
+        for rule in &val.schedule {
+            rule.validate()?;
+        }
+        Ok(Self::new(val.schedule))
+    }
+    /// 有Active规则时须处于其中一个窗口内，且不在任何Pause规则的窗口内
+    pub fn is_active_at(&self, index: usize, time: u64) -> bool {
+        let rules = || self.rules.iter().filter(|r| r.applies(index));
+        let inside = |r: &ScheduleRule| r.windows.iter().any(|w| w.contains(time));
+        let mut active = rules()
+            .filter(|r| r.mode == ScheduleMode::Active)
+            .peekable();
+        (active.peek().is_none() || active.any(inside))
+            && !rules()
+                .filter(|r| r.mode == ScheduleMode::Pause)
+                .any(inside)
+    }
+    /// 按time更新strategies个策略的状态（每分钟计算一次），返回状态改变的策略及新状态
+    pub fn update(&self, strategies: usize, time: u64) -> Vec<(usize, bool)> {
+        let minute = time / 60_000;
+        let mut state = self.state.lock();
+        if state.0 == minute && !state.1.is_empty() {
+            return vec![];
+        }
+        state.0 = minute;
+        let mut changes = vec![];
+        for index in 0..strategies {
+            let active = self.is_active_at(index, time);
+            // 首次计算时只记录停用的策略
+            match state.1.insert(index, active) {
+                Some(previous) if previous != active => changes.push((index, active)),
+                None if !active => changes.push((index, active)),
+                _ => {}
+            }
+        }
+        changes
+    }
+    /// 最近一次update时的状态，未计算过时为启用
+    pub fn is_active(&self, index: usize) -> bool {
+        self.state.lock().1.get(&index).copied().unwrap_or(true)
+    }
+    /// 停用时是否需要平仓
+    pub fn closes(&self, index: usize) -> bool {
+        self.rules.iter().any(|r| r.applies(index) && r.close)
+    }
+}
+
+#[test]
+fn scheduler_test() {
+    let config = r#"
+        [[schedule]]
+        strategy = 1
+        mode = "active"
+        close = true
+        windows = [{ cron = "45 7,15,23 * * *", minutes = 30 }]
+
+        [[schedule]]
+        mode = "pause"
+        windows = [{ cron = "0 2 * * 3", minutes = 60 }]
+    "#;
+    let path = std::env::temp_dir().join("hurribot_schedule_test.toml");
+    std::fs::write(&path, config).unwrap();
+    let scheduler = Scheduler::value_parse(path.to_str().unwrap()).unwrap();
+    // 2024-01-03为周三
+    let at = |h: u64, m: u64| 1_704_240_000_000 + (h * 60 + m) * 60_000;
+    assert!(scheduler.is_active_at(0, at(1, 59)));
+    assert!(!scheduler.is_active_at(0, at(2, 30)));
+    assert!(scheduler.is_active_at(0, at(3, 0)));
+    assert!(!scheduler.is_active_at(1, at(7, 44)));
+    assert!(scheduler.is_active_at(1, at(8, 14)));
+    assert!(!scheduler.is_active_at(1, at(8, 15)));
+    assert_eq!(scheduler.update(2, at(7, 0)), vec![(1, false)]);
+    assert!(scheduler.update(2, at(7, 0) + 30_000).is_empty());
+    assert_eq!(scheduler.update(2, at(7, 45)), vec![(1, true)]);
+    assert_eq!(scheduler.update(2, at(8, 15)), vec![(1, false)]);
+    assert!(scheduler.closes(1) && !scheduler.closes(0));
+
+    let cron = Cron::try_from("*/15 9-17 * * 1-5".to_string()).unwrap();
+    let t = |ms: u64| OffsetDateTime::from_unix_timestamp((ms / 1000) as i64).unwrap();
+    assert!(cron.matches(t(at(9, 30))));
+    assert!(!cron.matches(t(at(9, 31))));
+    assert!(!cron.matches(t(at(18, 0))));
+    assert!(Cron::try_from("60 * * * *".to_string()).is_err());
+    let rule: ScheduleRule = toml::from_str(
+        r#"mode = "pause"
+           windows = [{ cron = "0 2 * * *", minutes = 0 }]"#,
+    )
+    .unwrap();
+    assert!(rule.validate().is_err());
+    assert!(Cron::try_from("* * *".to_string()).is_err());
+}
@@ -0,0 +1,143 @@
+use dashmap::DashMap;
+
+use super::report::PositionSnapshot;
+
+/// 组合单持仓，各腿的盈亏合并统计
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpreadPosition {
+    pub strategy: usize,
+    pub request_id: u64,
+    pub symbols: Vec<String>,
+    /// 各腿是否仍有持仓
+    pub open: Vec<bool>,
+    /// 各腿扣除手续费后的已实现盈亏之和
+    pub realized_pnl: f64,
+}
+
+impl SpreadPosition {
+    /// 按当前持仓计算各腿未实现盈亏之和
+    pub fn unrealized_pnl(&self, positions: &[PositionSnapshot]) -> f64 {
+        positions
+            .iter()
+            .filter(|p| self.symbols.contains(&p.symbol))
+            .map(|p| p.unrealized_pnl())
+            .sum()
+    }
+}
+
+impl std::fmt::Display for SpreadPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Spread {} of strategy {} [{}], realized pnl: {:.2}",
+            self.request_id,
+            self.strategy,
+            self.symbols.join("/"),
+            self.realized_pnl
+        )
+    }
+}
+
+/// 某条腿平仓后组合单的状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum LegClosed {
+    /// 其余腿仍有持仓，对冲已失效
+    Unhedged(Vec<String>),
+    /// 全部腿都已平仓
+    Closed(SpreadPosition),
+}
+
+/// 按币种索引的组合单，币种同一时间只归属一个组合单
+#[derive(Debug, Default)]
+pub struct SpreadBook {
+    spreads: DashMap<(usize, u64), SpreadPosition>,
+}
+
+impl SpreadBook {
+    pub fn open(&self, strategy: usize, request_id: u64, symbols: Vec<String>) {
+        let open = vec![true; symbols.len()];
+        self.spreads.insert(
+            (strategy, request_id),
+            SpreadPosition {
+                strategy,
+                request_id,
+                symbols,
+                open,
+                realized_pnl: 0.,
+            },
+        );
+    }
+    fn find(&self, symbol: &str) -> Option<(usize, u64)> {
+        self.spreads
+            .iter()
+            .find(|s| s.symbols.iter().any(|s| s == symbol))
+            .map(|s| *s.key())
+    }
+    /// 计入成交的盈亏，不属于组合单时忽略
+    pub fn record_fill(&self, symbol: &str, pnl: f64) {
+        if let Some(key) = self.find(symbol) {
+            if let Some(mut spread) = self.spreads.get_mut(&key) {
+                spread.realized_pnl += pnl;
+            }
+        }
+    }
+    /// 某条腿仓位归零，不属于组合单时返回None
+    pub fn leg_closed(&self, symbol: &str) -> Option<LegClosed> {
+        let key = self.find(symbol)?;
+        let mut spread = self.spreads.get_mut(&key)?;
+        let i = spread.symbols.iter().position(|s| s == symbol)?;
+        spread.open[i] = false;
+        let open: Vec<String> = spread
+            .symbols
+            .iter()
+            .zip(spread.open.iter())
+            .filter(|(_, &open)| open)
+            .map(|(s, _)| s.clone())
+            .collect();
+        drop(spread);
+        if open.is_empty() {
+            self.spreads
+                .remove(&key)
+                .map(|(_, spread)| LegClosed::Closed(spread))
+        } else {
+            Some(LegClosed::Unhedged(open))
+        }
+    }
+    /// symbol所属组合单的全部腿
+    pub fn legs_of(&self, symbol: &str) -> Option<Vec<String>> {
+        let key = self.find(symbol)?;
+        self.spreads.get(&key).map(|s| s.symbols.clone())
+    }
+    pub fn positions(&self) -> Vec<SpreadPosition> {
+        self.spreads.iter().map(|s| s.clone()).collect()
+    }
+}
+
+#[test]
+fn spread_book_test() {
+    let book = SpreadBook::default();
+    book.open(0, 1, vec!["ETHUSDT".into(), "BTCUSDT".into()]);
+    book.record_fill("ETHUSDT", -0.1);
+    book.record_fill("BTCUSDT", 5.);
+    book.record_fill("SOLUSDT", 1.);
+    assert_eq!(book.legs_of("BTCUSDT").unwrap().len(), 2);
+    assert!(book.leg_closed("SOLUSDT").is_none());
+    assert_eq!(
+        book.leg_closed("BTCUSDT"),
+        Some(LegClosed::Unhedged(vec!["ETHUSDT".into()]))
+    );
+    book.record_fill("ETHUSDT", -2.);
+    let Some(LegClosed::Closed(spread)) = book.leg_closed("ETHUSDT") else {
+        panic!("spread not closed");
+    };
+    assert!((spread.realized_pnl - 2.9).abs() < 1e-9);
+    assert!(book.positions().is_empty());
+    let positions = [PositionSnapshot {
+        symbol: "ETHUSDT".into(),
+        amount: 2.,
+        entry_price: 10.,
+        mark_price: 11.,
+        close_fee: 0.,
+    }];
+    assert_eq!(spread.unrealized_pnl(&positions), 2.);
+}
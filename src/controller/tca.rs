@@ -0,0 +1,184 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+
+/// 只有下单或只有成交的记录保留的时长（ms），如成交回报丢失或下单失败
+pub const PENDING_TTL: u64 = 10 * 60 * 1000;
+
+/// 一笔开仓从信号到成交的三个价格
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExecutionPrices {
+    pub is_buy: bool,
+    /// 信号时的标记价格
+    pub signal: f64,
+    /// 下单时通过REST获取、用于计算数量的价格
+    pub sizing: f64,
+    /// 实际成交均价
+    pub fill: f64,
+}
+
+impl ExecutionPrices {
+    /// 成交价相对信号价的不利滑点（基点），买入成交高于参考价为正
+    pub fn signal_slippage(&self) -> f64 {
+        self.slippage(self.signal)
+    }
+    /// 成交价相对下单价的不利滑点（基点）
+    pub fn sizing_slippage(&self) -> f64 {
+        self.slippage(self.sizing)
+    }
+    fn slippage(&self, reference: f64) -> f64 {
+        let side = if self.is_buy { 1. } else { -1. };
+        side * (self.fill - reference) / reference * 10000.
+    }
+}
+
+/// 单个策略的滑点统计，单位为基点
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SlippageStats {
+    pub count: usize,
+    pub signal_sum: f64,
+    pub sizing_sum: f64,
+    /// 相对信号价最差的一笔
+    pub worst: f64,
+}
+
+impl SlippageStats {
+    pub fn add(&mut self, prices: &ExecutionPrices) {
+        let signal = prices.signal_slippage();
+        self.worst = if self.count == 0 {
+            signal
+        } else {
+            self.worst.max(signal)
+        };
+        self.count += 1;
+        self.signal_sum += signal;
+        self.sizing_sum += prices.sizing_slippage();
+    }
+    pub fn mean_signal(&self) -> f64 {
+        self.signal_sum / self.count as f64
+    }
+    pub fn mean_sizing(&self) -> f64 {
+        self.sizing_sum / self.count as f64
+    }
+}
+
+impl Display for SlippageStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} fills, slippage vs signal {:.2}bp (worst {:.2}bp), vs sizing {:.2}bp",
+            self.count,
+            self.mean_signal(),
+            self.worst,
+            self.mean_sizing()
+        )
+    }
+}
+
+/// 下单和成交回报分别到达，任一先到都先暂存
+#[derive(Debug, Default)]
+struct Pending {
+    /// 先到的一方的时间（unix毫秒）
+    time: u64,
+    /// （方向，信号价，下单价）
+    order: Option<(bool, f64, f64)>,
+    fill: Option<f64>,
+}
+
+/// 实盘成交成本分析：按（策略，request_id）匹配下单与成交，累计各策略的滑点
+#[derive(Debug, Default)]
+pub struct TradeCostAnalysis {
+    pending: DashMap<(usize, u64), Pending>,
+    stats: Mutex<BTreeMap<usize, SlippageStats>>,
+}
+
+impl TradeCostAnalysis {
+    /// 记录下单时下单币种的标记价格和下单价，time为信号时间
+    pub fn submit(
+        &self,
+        strategy: usize,
+        request_id: u64,
+        is_buy: bool,
+        signal: f64,
+        sizing: f64,
+        time: u64,
+    ) -> Option<ExecutionPrices> {
+        self.expire(time);
+        let mut pending = self
+            .pending
+            .entry((strategy, request_id))
+            .or_insert_with(|| Pending {
+                time,
+                ..Default::default()
+            });
+        pending.order = Some((is_buy, signal, sizing));
+        drop(pending);
+        self.complete(strategy, request_id)
+    }
+    /// 记录开仓单的成交均价，time为成交回报时间
+    pub fn fill(
+        &self,
+        strategy: usize,
+        request_id: u64,
+        price: f64,
+        time: u64,
+    ) -> Option<ExecutionPrices> {
+        self.expire(time);
+        let mut pending = self
+            .pending
+            .entry((strategy, request_id))
+            .or_insert_with(|| Pending {
+                time,
+                ..Default::default()
+            });
+        pending.fill = Some(price);
+        drop(pending);
+        self.complete(strategy, request_id)
+    }
+    /// 丢弃超过PENDING_TTL仍未匹配的记录
+    fn expire(&self, now: u64) {
+        self.pending.retain(|_, p| p.time + PENDING_TTL > now);
+    }
+    /// 下单和成交都到达后计入统计
+    fn complete(&self, strategy: usize, request_id: u64) -> Option<ExecutionPrices> {
+        let (_, pending) = self.pending.remove_if(&(strategy, request_id), |_, p| {
+            p.order.is_some() && p.fill.is_some()
+        })?;
+        let (is_buy, signal, sizing) = pending.order?;
+        let prices = ExecutionPrices {
+            is_buy,
+            signal,
+            sizing,
+            fill: pending.fill?,
+        };
+        self.stats.lock().entry(strategy).or_default().add(&prices);
+        Some(prices)
+    }
+    pub fn stats(&self) -> Vec<(usize, SlippageStats)> {
+        self.stats.lock().iter().map(|(&i, &s)| (i, s)).collect()
+    }
+}
+
+#[test]
+fn tca_test() {
+    let tca = TradeCostAnalysis::default();
+    assert!(tca.submit(0, 1, true, 100., 100.5, 0).is_none());
+    let prices = tca.fill(0, 1, 101., 0).unwrap();
+    assert!((prices.signal_slippage() - 100.).abs() < 1e-9);
+    assert!((prices.sizing_slippage() - 10000. * 0.5 / 100.5).abs() < 1e-9);
+    // 成交回报先于下单返回
+    assert!(tca.fill(0, 2, 99., 0).is_none());
+    let prices = tca.submit(0, 2, false, 100., 100., 0).unwrap();
+    assert!((prices.signal_slippage() - 100.).abs() < 1e-9);
+    assert!(tca.submit(1, 1, true, 100., 100., 0).is_none());
+    // 未匹配的记录过期后丢弃，迟到的成交不再计入
+    assert!(tca.fill(0, 3, 100., PENDING_TTL).is_none());
+    assert!(tca.pending.get(&(1, 1)).is_none());
+    assert!(tca.fill(1, 1, 100., PENDING_TTL).is_none());
+    let stats = tca.stats();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].1.count, 2);
+    assert!((stats[0].1.mean_signal() - 100.).abs() < 1e-9);
+    assert!(stats[0].1.to_string().starts_with("2 fills"));
+}
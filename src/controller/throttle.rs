@@ -0,0 +1,167 @@
+use std::{collections::VecDeque, fmt::Display};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    /// 同一币种平仓后多久才能再次开仓（毫秒），0为不限
+    pub reentry_cooldown: u64,
+    /// 同一币种每分钟最多下单数（开仓和平仓合计），0为不限；平仓只计数不拦截
+    pub max_orders_per_minute: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleViolation {
+    Cooldown,
+    RateLimit,
+}
+
+impl Display for ThrottleViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThrottleViolation::Cooldown => write!(f, "re-entry cooldown"),
+            ThrottleViolation::RateLimit => write!(f, "orders per minute limit"),
+        }
+    }
+}
+
+/// 被拦截的开仓次数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ThrottleStats {
+    pub cooldown: usize,
+    pub rate_limit: usize,
+}
+
+impl ThrottleStats {
+    pub fn total(&self) -> usize {
+        self.cooldown + self.rate_limit
+    }
+}
+
+impl Display for ThrottleStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "throttled {} (cooldown {}, rate limit {})",
+            self.total(),
+            self.cooldown,
+            self.rate_limit
+        )
+    }
+}
+
+/// 按币种限制开仓频率，避免噪声信号反复开平仓消耗手续费
+#[derive(Debug, Default)]
+pub struct Throttle {
+    config: ThrottleConfig,
+    /// 币种 -> 最近一分钟内的下单时间
+    orders: DashMap<String, VecDeque<u64>>,
+    /// 币种 -> 最近一次平仓时间
+    exits: DashMap<String, u64>,
+    /// 策略序号 -> 被拦截次数
+    violations: DashMap<usize, ThrottleStats>,
+}
+
+impl Throttle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+    /// 开仓前检查，拦截时计入策略index的违规次数；
+    /// 检查不计入下单次数，下单成交后由record_order计入
+    pub fn check_entry(
+        &self,
+        index: usize,
+        symbol: &str,
+        time: u64,
+    ) -> Result<(), ThrottleViolation> {
+        let cooling = self.config.reentry_cooldown > 0
+            && self
+                .exits
+                .get(symbol)
+                .is_some_and(|t| time < *t + self.config.reentry_cooldown);
+        let result = if cooling {
+            Err(ThrottleViolation::Cooldown)
+        } else if self.config.max_orders_per_minute > 0
+            && self.recent(symbol, time) >= self.config.max_orders_per_minute
+        {
+            Err(ThrottleViolation::RateLimit)
+        } else {
+            Ok(())
+        };
+        if let Err(violation) = result {
+            let mut stats = self.violations.entry(index).or_default();
+            match violation {
+                ThrottleViolation::Cooldown => stats.cooldown += 1,
+                ThrottleViolation::RateLimit => stats.rate_limit += 1,
+            }
+        }
+        result
+    }
+    /// 计入已成交的下单（开仓和平仓）
+    pub fn record_order(&self, symbol: &str, time: u64) {
+        if self.config.max_orders_per_minute > 0 {
+            self.orders
+                .entry(symbol.to_string())
+                .or_default()
+                .push_back(time);
+        }
+    }
+    /// 仓位已平，开始冷却
+    pub fn record_exit(&self, symbol: &str, time: u64) {
+        if self.config.reentry_cooldown > 0 {
+            self.exits.insert(symbol.to_string(), time);
+        }
+    }
+    /// 最近一分钟内的下单数，同时清理过期记录
+    fn recent(&self, symbol: &str, time: u64) -> usize {
+        let Some(mut orders) = self.orders.get_mut(symbol) else {
+            return 0;
+        };
+        while orders.front().is_some_and(|t| t + 60_000 <= time) {
+            orders.pop_front();
+        }
+        orders.len()
+    }
+    pub fn stats(&self, index: usize) -> ThrottleStats {
+        self.violations.get(&index).map(|s| *s).unwrap_or_default()
+    }
+}
+
+#[test]
+fn throttle_test() {
+    let throttle = Throttle::new(ThrottleConfig {
+        reentry_cooldown: 30_000,
+        max_orders_per_minute: 2,
+    });
+    assert!(throttle.check_entry(0, "BTCUSDT", 0).is_ok());
+    // 检查本身不计数，未成交的开仓不占用频率
+    assert!(throttle.check_entry(0, "BTCUSDT", 0).is_ok());
+    throttle.record_order("BTCUSDT", 0);
+    throttle.record_order("BTCUSDT", 1_000);
+    throttle.record_exit("BTCUSDT", 1_000);
+    assert_eq!(
+        throttle.check_entry(0, "BTCUSDT", 20_000),
+        Err(ThrottleViolation::Cooldown)
+    );
+    // 冷却结束但一分钟内已下单两次
+    assert_eq!(
+        throttle.check_entry(1, "BTCUSDT", 40_000),
+        Err(ThrottleViolation::RateLimit)
+    );
+    assert!(throttle.check_entry(1, "ETHUSDT", 40_000).is_ok());
+    assert!(throttle.check_entry(1, "BTCUSDT", 61_000).is_ok());
+    assert_eq!(
+        throttle.stats(0),
+        ThrottleStats {
+            cooldown: 1,
+            rate_limit: 0
+        }
+    );
+    assert_eq!(throttle.stats(1).total(), 1);
+    assert_eq!(throttle.stats(2), ThrottleStats::default());
+}
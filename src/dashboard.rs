@@ -0,0 +1,369 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::Arc,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use binance::futures::model::OrderUpdate;
+use crossbeam::channel::{Receiver, Sender, TrySendError};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::{error, info, warn};
+use tungstenite::{Message, WebSocket};
+
+use crate::{
+    binance_futures::StreamHealth,
+    controller::{equity::EquitySample, report::PositionSnapshot, ControlCommand},
+    strategy::StrategyStatus,
+};
+
+const INDEX_HTML: &str = include_str!("dashboard/index.html");
+/// 新连接时补发的历史权益采样数
+const EQUITY_HISTORY: usize = 1440;
+/// 新连接时补发的最近成交数
+const FILL_HISTORY: usize = 100;
+/// 待推送事件的队列容量，满时丢弃最早的事件
+const EVENT_QUEUE: usize = 256;
+/// 读取请求头的超时，不发送数据的连接不会占住监听线程
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+/// 推送时不等待慢速客户端
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamStatus {
+    pub name: String,
+    pub stale: bool,
+    /// 最近一次收到事件的时间（unix毫秒）
+    pub last_event: u64,
+    pub reconnects: u64,
+    /// 服务器24小时定期断开的次数
+    pub forced_disconnects: u64,
+}
+
+impl StreamStatus {
+    pub fn new(name: &str, health: &StreamHealth) -> Self {
+        Self {
+            name: name.to_string(),
+            stale: health.is_stale(),
+            last_event: health.last_event(),
+            reconnects: health.reconnects().reconnects(),
+            forced_disconnects: health.reconnects().forced_disconnects(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FillUpdate {
+    pub time: u64,
+    pub symbol: String,
+    pub side: String,
+    pub qty: f64,
+    pub price: f64,
+    pub realized_pnl: f64,
+    /// 归属的策略序号，非本程序下单为None
+    pub strategy: Option<usize>,
+}
+
+impl FillUpdate {
+    pub fn new(time: u64, order: &OrderUpdate, strategy: Option<usize>) -> Self {
+        Self {
+            time,
+            symbol: order.symbol.clone(),
+            side: order.side.clone(),
+            qty: order.qty_last_filled_trade.parse().unwrap_or_default(),
+            price: order.price_last_filled_trade.parse().unwrap_or_default(),
+            realized_pnl: order.realized_profit.parse().unwrap_or_default(),
+            strategy,
+        }
+    }
+}
+
+/// 推送给网页的事件，JSON的type字段区分类型
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DashboardEvent {
+    Status {
+        time: u64,
+        positions: Vec<PositionSnapshot>,
+        strategies: Vec<StrategyStatus>,
+        streams: Vec<StreamStatus>,
+        /// 权益曲线的报告货币，None为结算资产
+        base_currency: Option<String>,
+    },
+    Equity(EquitySample),
+    Fill(FillUpdate),
+}
+
+/// 新连接时补发的状态
+#[derive(Debug, Default)]
+struct History {
+    status: Option<String>,
+    equity: VecDeque<String>,
+    fills: VecDeque<String>,
+}
+
+impl History {
+    fn record(&mut self, event: &DashboardEvent, json: &str) {
+        let (queue, cap) = match event {
+            DashboardEvent::Status { .. } => {
+                self.status = Some(json.to_string());
+                return;
+            }
+            DashboardEvent::Equity(_) => (&mut self.equity, EQUITY_HISTORY),
+            DashboardEvent::Fill(_) => (&mut self.fills, FILL_HISTORY),
+        };
+        if queue.len() >= cap {
+            queue.pop_front();
+        }
+        queue.push_back(json.to_string());
+    }
+    fn replay(&self) -> impl Iterator<Item = &String> {
+        self.equity
+            .iter()
+            .chain(self.fills.iter())
+            .chain(self.status.iter())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    clients: Mutex<Vec<WebSocket<TcpStream>>>,
+    history: Mutex<History>,
+}
+
+impl Shared {
+    /// 推送给全部已连接的页面，发送失败的连接被移除
+    fn push(&self, event: &DashboardEvent) {
+        let json = match serde_json::to_string(event) {
+            Ok(json) => json,
+            Err(e) => return error!("Serialize dashboard event failed: {}", e),
+        };
+        self.history.lock().record(event, &json);
+        self.clients
+            .lock()
+            .retain_mut(|ws| ws.send(Message::text(json.as_str())).is_ok());
+    }
+}
+
+/// 网页监控面板和控制接口：/ 返回静态页面，/ws 为websocket推送通道，
+/// POST /control/... 转为ControlCommand；控制接口没有鉴权，只应监听本地地址
+#[derive(Debug, Clone)]
+pub struct Dashboard {
+    shared: Arc<Shared>,
+    events: Sender<DashboardEvent>,
+    /// 队列满时从中取出最早的事件丢弃
+    oldest: Receiver<DashboardEvent>,
+    /// None时不提供控制接口
+    control: Option<Sender<ControlCommand>>,
+}
+
+impl Dashboard {
+    /// 在addr上监听HTTP和websocket连接，事件由后台线程推送，每个连接单独处理
+    pub fn serve(
+        addr: impl ToSocketAddrs,
+        control: Option<Sender<ControlCommand>>,
+    ) -> std::io::Result<(Self, JoinHandle<()>)> {
+        let listener = TcpListener::bind(addr)?;
+        info!("Dashboard listening on {}", listener.local_addr()?);
+        let (events, rx) = crossbeam::channel::bounded(EVENT_QUEUE);
+        let dashboard = Self {
+            shared: Arc::default(),
+            events,
+            oldest: rx.clone(),
+            control,
+        };
+        let shared = dashboard.shared.clone();
+        std::thread::spawn(move || {
+            for event in rx.iter() {
+                shared.push(&event);
+            }
+        });
+        let this = dashboard.clone();
+        let h = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let this = this.clone();
+                        std::thread::spawn(move || {
+                            if let Err(e) = this.accept(stream) {
+                                warn!("Dashboard connection failed: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => error!("Dashboard accept failed: {}", e),
+                }
+            }
+        });
+        Ok((dashboard, h))
+    }
+    /// 按请求路径返回页面、执行控制命令或升级为websocket
+    fn accept(&self, stream: TcpStream) -> Result<(), String> {
+        stream
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        stream
+            .set_write_timeout(Some(WRITE_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        let mut head = [0; 512];
+        let n = stream.peek(&mut head).map_err(|e| e.to_string())?;
+        let head = String::from_utf8_lossy(&head[..n]);
+        let mut request = head.split_whitespace();
+        let method = request.next().unwrap_or("GET");
+        let path = request.next().unwrap_or("/");
+        match (method, path) {
+            ("GET", "/ws") => {
+                let mut ws = tungstenite::accept(stream).map_err(|e| e.to_string())?;
+                // 持有history直到加入clients，期间的推送不会遗漏
+                let history = self.shared.history.lock();
+                for json in history.replay() {
+                    ws.send(Message::text(json.as_str()))
+                        .map_err(|e| e.to_string())?;
+                }
+                self.shared.clients.lock().push(ws);
+                Ok(())
+            }
+            ("GET", "/" | "/index.html") => respond(stream, "200 OK", "text/html", INDEX_HTML),
+            ("POST", path) if path.starts_with("/control/") => {
+                let Some(control) = &self.control else {
+                    return respond(stream, "404 Not Found", "text/plain", "control disabled");
+                };
+                match parse_control(path) {
+                    Some(command) => {
+                        info!("Dashboard control: {:?}", command);
+                        control.send(command).map_err(|e| e.to_string())?;
+                        respond(stream, "200 OK", "text/plain", "ok")
+                    }
+                    None => respond(stream, "400 Bad Request", "text/plain", "bad command"),
+                }
+            }
+            _ => respond(stream, "404 Not Found", "text/plain", "not found"),
+        }
+    }
+    /// 放入推送队列后立即返回，队列满时丢弃最早的事件，不阻塞调用方
+    pub fn publish(&self, mut event: DashboardEvent) {
+        loop {
+            match self.events.try_send(event) {
+                Ok(()) => return,
+                Err(TrySendError::Full(e)) => {
+                    self.oldest.try_recv().ok();
+                    event = e;
+                }
+                Err(TrySendError::Disconnected(_)) => return,
+            }
+        }
+    }
+    pub fn clients(&self) -> usize {
+        self.shared.clients.lock().len()
+    }
+}
+
+/// /control/enable/{策略序号}、/control/pause/{策略序号}、/control/reset_drawdown、
+/// /control/approve/{请求编号}、/control/deny/{请求编号}
+fn parse_control(path: &str) -> Option<ControlCommand> {
+    let mut parts = path.trim_start_matches("/control/").split('/');
+    let command = match (parts.next()?, parts.next()) {
+        ("enable", Some(index)) => ControlCommand::Enable(index.parse().ok()?),
+        ("pause", Some(index)) => ControlCommand::Pause(index.parse().ok()?),
+        ("reset_drawdown", None) => ControlCommand::ResetDrawdown,
+        ("approve", Some(id)) => ControlCommand::Approve(id.parse().ok()?),
+        ("deny", Some(id)) => ControlCommand::Deny(id.parse().ok()?),
+        _ => return None,
+    };
+    parts.next().is_none().then_some(command)
+}
+
+fn respond(
+    mut stream: TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<(), String> {
+    // 读掉请求，避免未读数据导致连接被重置
+    let mut buf = [0; 4096];
+    stream
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .ok();
+    while let Ok(1..) = stream.read(&mut buf) {}
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[test]
+fn dashboard_test() {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let (control_tx, control_rx) = crossbeam::channel::unbounded();
+    let (dashboard, _) = Dashboard::serve(addr, Some(control_tx)).unwrap();
+    dashboard.publish(DashboardEvent::Equity(EquitySample {
+        time: 1,
+        total_balance: 100.,
+        unrealized_pnl: 1.,
+        base_rate: Some(2.),
+    }));
+    while dashboard.shared.history.lock().equity.is_empty() {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    // 页面
+    let mut http = TcpStream::connect(addr).unwrap();
+    write!(http, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut page = String::new();
+    http.read_to_string(&mut page).unwrap();
+    assert!(page.starts_with("HTTP/1.1 200 OK"));
+    assert!(page.contains("<html"));
+    // 不发送请求的连接不影响其他连接
+    let _idle = TcpStream::connect(addr).unwrap();
+    // 控制命令
+    let post = |path: &str| {
+        let mut http = TcpStream::connect(addr).unwrap();
+        write!(http, "POST {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        http.read_to_string(&mut response).unwrap();
+        response
+    };
+    assert!(post("/control/pause/1").starts_with("HTTP/1.1 200 OK"));
+    assert!(matches!(
+        control_rx.try_recv(),
+        Ok(ControlCommand::Pause(1))
+    ));
+    assert!(post("/control/approve/7").starts_with("HTTP/1.1 200 OK"));
+    assert!(matches!(
+        control_rx.try_recv(),
+        Ok(ControlCommand::Approve(7))
+    ));
+    assert!(post("/control/pause/x").starts_with("HTTP/1.1 400"));
+    assert!(control_rx.try_recv().is_err());
+    // 新连接先收到历史采样，之后收到实时推送
+    let (mut ws, _) = tungstenite::connect(format!("ws://{}/ws", addr)).unwrap();
+    let text = ws.read().unwrap().into_text().unwrap();
+    assert!(text.contains(r#""type":"equity""#));
+    while dashboard.clients() == 0 {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    dashboard.publish(DashboardEvent::Status {
+        time: 2,
+        positions: vec![],
+        strategies: vec![StrategyStatus {
+            name: "Roll".into(),
+            ..Default::default()
+        }],
+        streams: vec![],
+        base_currency: Some("EUR".to_string()),
+    });
+    let json: serde_json::Value =
+        serde_json::from_str(&ws.read().unwrap().into_text().unwrap()).unwrap();
+    assert_eq!(json["type"], "status");
+    assert_eq!(json["strategies"][0]["name"], "Roll");
+    assert_eq!(json["base_currency"], "EUR");
+}
@@ -0,0 +1,157 @@
+use serde::Deserialize;
+
+/// 账户回撤达到drawdown（比例，如0.1为-10%）后，开仓金额乘以scale，0为全部平仓
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct DrawdownStage {
+    pub drawdown: f64,
+    pub scale: f64,
+}
+
+/// 组合回撤分级降杠杆规则，实盘风控和回测共用
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DeleverageConfig {
+    pub stages: Vec<DrawdownStage>,
+    /// 回撤需回落到阈值以下该比例才恢复上一级，避免在阈值附近反复切换
+    #[serde(default)]
+    pub recovery: f64,
+    /// 降到0全部平仓后权益不再变化，经过该时长（毫秒）以当时的权益为新高点恢复全部仓位；
+    /// None为只能手动reset
+    #[serde(default)]
+    pub resume_after: Option<u64>,
+}
+
+impl Default for DeleverageConfig {
+    fn default() -> Self {
+        Self {
+            stages: vec![
+                DrawdownStage {
+                    drawdown: 0.1,
+                    scale: 0.5,
+                },
+                DrawdownStage {
+                    drawdown: 0.2,
+                    scale: 0.,
+                },
+            ],
+            recovery: 0.02,
+            resume_after: None,
+        }
+    }
+}
+
+/// 按权益高点计算回撤并给出当前允许的仓位比例；
+/// 降到0平仓后权益不再变化，需reset或经过resume_after以当前权益为新高点恢复
+#[derive(Debug, Clone, Default)]
+pub struct Deleverage {
+    config: DeleverageConfig,
+    peak: f64,
+    /// 已触发的级数，0为未触发
+    level: usize,
+    /// 降到0的时间（unix毫秒）
+    halted: Option<u64>,
+}
+
+impl Deleverage {
+    pub fn new(mut config: DeleverageConfig) -> Self {
+        config
+            .stages
+            .sort_by(|a, b| a.drawdown.total_cmp(&b.drawdown));
+        Self {
+            config,
+            peak: 0.,
+            level: 0,
+            halted: None,
+        }
+    }
+    /// 用time（unix毫秒）时的账户权益更新，级别变化时返回新的仓位比例
+    pub fn update(&mut self, equity: f64, time: u64) -> Option<f64> {
+        if let (Some(halted), Some(resume_after)) = (self.halted, self.config.resume_after) {
+            if time >= halted + resume_after {
+                self.reset(equity);
+                return Some(1.);
+            }
+        }
+        self.peak = self.peak.max(equity);
+        let drawdown = self.drawdown(equity);
+        let reached = |d: f64| {
+            self.config
+                .stages
+                .iter()
+                .take_while(|s| d >= s.drawdown)
+                .count()
+        };
+        let mut level = reached(drawdown);
+        if level < self.level {
+            level = reached(drawdown + self.config.recovery).min(self.level);
+        }
+        if level == self.level {
+            return None;
+        }
+        self.level = level;
+        let scale = self.scale();
+        self.halted = match self.halted {
+            Some(halted) if scale <= 0. => Some(halted),
+            _ => (scale <= 0.).then_some(time),
+        };
+        Some(scale)
+    }
+    pub fn scale(&self) -> f64 {
+        match self.level {
+            0 => 1.,
+            level => self.config.stages[level - 1].scale,
+        }
+    }
+    /// 相对权益高点的回撤
+    pub fn drawdown(&self, equity: f64) -> f64 {
+        if self.peak > 0. {
+            (1. - equity / self.peak).max(0.)
+        } else {
+            0.
+        }
+    }
+    /// 手动恢复，以equity为新的高点
+    pub fn reset(&mut self, equity: f64) {
+        self.peak = equity;
+        self.level = 0;
+        self.halted = None;
+    }
+}
+
+#[test]
+fn deleverage_test() {
+    let config: DeleverageConfig = toml::from_str(
+        "recovery = 0.02
+         stages = [{ drawdown = 0.2, scale = 0.0 }, { drawdown = 0.1, scale = 0.5 }]",
+    )
+    .unwrap();
+    let mut deleverage = Deleverage::new(config.clone());
+    assert_eq!(deleverage.update(1000., 0), None);
+    assert_eq!(deleverage.update(950., 0), None);
+    assert_eq!(deleverage.update(890., 0), Some(0.5));
+    // 回撤回落但未超过恢复余量
+    assert_eq!(deleverage.update(910., 0), None);
+    assert_eq!(deleverage.scale(), 0.5);
+    assert_eq!(deleverage.update(930., 0), Some(1.));
+    assert_eq!(deleverage.update(790., 0), Some(0.));
+    assert_eq!(deleverage.update(810., 0), None);
+    assert_eq!(deleverage.update(900., 0), Some(0.5));
+    deleverage.update(700., 0);
+    deleverage.reset(700.);
+    assert_eq!(deleverage.scale(), 1.);
+    assert!(deleverage.drawdown(700.) == 0.);
+    // 未配置resume_after时平仓后一直为0
+    assert_eq!(deleverage.update(500., 0), Some(0.));
+    assert_eq!(deleverage.update(500., u64::MAX / 2), None);
+
+    // 平仓后经过resume_after以当时的权益恢复
+    let mut deleverage = Deleverage::new(DeleverageConfig {
+        resume_after: Some(60_000),
+        ..config
+    });
+    deleverage.update(1000., 0);
+    assert_eq!(deleverage.update(790., 1_000), Some(0.));
+    assert_eq!(deleverage.update(780., 30_000), None);
+    assert_eq!(deleverage.update(780., 61_000), Some(1.));
+    assert_eq!(deleverage.drawdown(780.), 0.);
+    assert_eq!(deleverage.update(700., 62_000), Some(0.5));
+}
@@ -1,6 +1,277 @@
 use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
 #[derive(Error, Debug)]
 pub enum Error {
+    #[error("Market: {0}")]
+    Market(#[from] MarketError),
+    #[error("Data: {0}")]
+    Data(#[from] DataError),
+    #[error("Ws: {0}")]
+    Ws(#[from] WsError),
+    #[error("Config: {0}")]
+    Config(#[from] ConfigError),
     #[error("Any: {0}")]
     Any(#[from] anyhow::Error),
 }
+
+impl Error {
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Market(e) => e.is_retryable(),
+            Error::Ws(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+/// 币安错误码，见 https://binance-docs.github.io/apidocs/futures/en/#error-codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinanceErrorCode {
+    /// -1000
+    Unknown,
+    /// -1001
+    Disconnected,
+    /// -1003
+    TooManyRequests,
+    /// -1007
+    Timeout,
+    /// -1021 本地时钟偏差超过recv_window
+    InvalidTimestamp,
+    /// -1022
+    InvalidSignature,
+    /// -1111
+    BadPrecision,
+    /// -1125 listen key不存在（已过期或被删除）
+    ListenKeyNotExist,
+    /// -2019
+    MarginInsufficient,
+    /// -2021
+    OrderWouldImmediatelyTrigger,
+    /// -2022
+    ReduceOnlyRejected,
+    /// -4046
+    NoNeedToChangeMarginType,
+    /// -4059
+    NoNeedToChangePositionSide,
+    /// -4164
+    MinNotional,
+    Other(i16),
+}
+
+impl From<i16> for BinanceErrorCode {
+    fn from(code: i16) -> Self {
+        match code {
+            -1000 => Self::Unknown,
+            -1001 => Self::Disconnected,
+            -1003 => Self::TooManyRequests,
+            -1007 => Self::Timeout,
+            -1021 => Self::InvalidTimestamp,
+            -1022 => Self::InvalidSignature,
+            -1111 => Self::BadPrecision,
+            -1125 => Self::ListenKeyNotExist,
+            -2019 => Self::MarginInsufficient,
+            -2021 => Self::OrderWouldImmediatelyTrigger,
+            -2022 => Self::ReduceOnlyRejected,
+            -4046 => Self::NoNeedToChangeMarginType,
+            -4059 => Self::NoNeedToChangePositionSide,
+            -4164 => Self::MinNotional,
+            c => Self::Other(c),
+        }
+    }
+}
+
+impl BinanceErrorCode {
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Unknown
+                | Self::Disconnected
+                | Self::TooManyRequests
+                | Self::Timeout
+                | Self::InvalidTimestamp
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MarketError {
+    #[error("{context} failed: binance error {code:?}: {msg}")]
+    Binance {
+        context: String,
+        code: BinanceErrorCode,
+        msg: String,
+    },
+    #[error("{context} failed: okx error {code}: {msg}")]
+    Okx {
+        context: String,
+        code: String,
+        msg: String,
+    },
+    /// 网络、解析等请求层面的错误
+    #[error("{context} failed: {msg}")]
+    Request { context: String, msg: String },
+    /// 交易所返回的字段无法解析，重试也不会改变
+    #[error("parse {context} failed: {msg}")]
+    Parse { context: String, msg: String },
+    /// 本地校验不通过，未发送到交易所
+    #[error("order rejected: {0}")]
+    Rejected(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("clock drift {0}ms exceeds recv window")]
+    ClockDrift(i64),
+    /// 组合单某条腿失败，unwind_errors为未能回滚的腿（包括可能部分成交的失败腿）
+    #[error(
+        "spread leg {leg} failed: {error}, {} legs not rolled back",
+        unwind_errors.len()
+    )]
+    Spread {
+        leg: usize,
+        error: Box<MarketError>,
+        unwind_errors: Vec<(String, MarketError)>,
+    },
+}
+
+impl MarketError {
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            MarketError::Binance { code, .. } => code.is_retryable(),
+            MarketError::Request { .. } => true,
+            _ => false,
+        }
+    }
+    pub fn code(&self) -> Option<BinanceErrorCode> {
+        match self {
+            MarketError::Binance { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+    pub fn binance(context: &str, e: binance::errors::Error) -> Self {
+        match e.0 {
+            binance::errors::ErrorKind::BinanceError(response) => MarketError::Binance {
+                context: context.to_string(),
+                code: response.code.into(),
+                msg: response.msg,
+            },
+            e => MarketError::Request {
+                context: context.to_string(),
+                msg: format!("{:?}", e),
+            },
+        }
+    }
+}
+
+pub trait BinanceResultExt<T> {
+    fn market_context(self, context: &str) -> std::result::Result<T, MarketError>;
+}
+
+impl<T> BinanceResultExt<T> for binance::errors::Result<T> {
+    fn market_context(self, context: &str) -> std::result::Result<T, MarketError> {
+        self.map_err(|e| MarketError::binance(context, e))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DataError {
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("csv: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("sqlite: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("postcard: {0}")]
+    Postcard(#[from] postcard::Error),
+    #[error("parse {field} failed: {msg}")]
+    Parse { field: String, msg: String },
+    #[error("checksum mismatch {path}: expected {expected}, got {actual}")]
+    Checksum {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl DataError {
+    pub fn parse(field: &str, e: impl std::fmt::Display) -> Self {
+        DataError::Parse {
+            field: field.to_string(),
+            msg: e.to_string(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum WsError {
+    /// 断线，可重连
+    #[error("disconnected: {0}")]
+    Disconnected(String),
+    /// user data stream的listen key过期，重新申请后可重连
+    #[error("listen key expired")]
+    ListenKeyExpired,
+    #[error("connection: {0}")]
+    Connection(String),
+    #[error("listen key: {0}")]
+    ListenKey(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl WsError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            WsError::Disconnected(_) | WsError::ListenKeyExpired | WsError::Connection(_)
+        )
+    }
+}
+
+impl From<binance::errors::Error> for WsError {
+    fn from(e: binance::errors::Error) -> Self {
+        match e.0 {
+            binance::errors::ErrorKind::Msg(msg) => {
+                if msg.contains("UserDataStreamExpiredEvent") {
+                    WsError::ListenKeyExpired
+                } else if msg.contains("Disconnected") {
+                    WsError::Disconnected(msg)
+                } else {
+                    WsError::Other(msg)
+                }
+            }
+            binance::errors::ErrorKind::Tungstenite(e) => WsError::Connection(e.to_string()),
+            e => WsError::Other(format!("{:?}", e)),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("toml: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("invalid {0}")]
+    Invalid(String),
+}
+
+#[test]
+fn error_code_test() {
+    let e = MarketError::Binance {
+        context: "order".to_string(),
+        code: (-1021).into(),
+        msg: "Timestamp for this request is outside of the recvWindow.".to_string(),
+    };
+    assert_eq!(e.code(), Some(BinanceErrorCode::InvalidTimestamp));
+    assert!(Error::from(e).is_retryable());
+    assert!(!MarketError::Binance {
+        context: "order".to_string(),
+        code: (-2019).into(),
+        msg: "Margin is insufficient.".to_string(),
+    }
+    .is_retryable());
+    assert_eq!(
+        BinanceErrorCode::from(-9999),
+        BinanceErrorCode::Other(-9999)
+    );
+}
@@ -0,0 +1,209 @@
+use std::{path::Path, str::FromStr};
+
+use serde::Deserialize;
+use time::{macros::format_description, OffsetDateTime, PrimitiveDateTime};
+
+use crate::{backtest::candle_chart::parse_time, error::DataError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Impact {
+    Low,
+    Medium,
+    High,
+}
+
+impl FromStr for Impact {
+    type Err = DataError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "low" => Ok(Impact::Low),
+            "medium" => Ok(Impact::Medium),
+            "high" => Ok(Impact::High),
+            _ => Err(DataError::parse("impact", s)),
+        }
+    }
+}
+
+/// 宏观经济事件，如FOMC议息、CPI公布
+#[derive(Debug, Clone, PartialEq)]
+pub struct EconomicEvent {
+    /// unix毫秒
+    pub time: u64,
+    pub name: String,
+    pub impact: Impact,
+}
+
+/// 按时间升序的事件日历
+#[derive(Debug, Clone, Default)]
+pub struct EventCalendar {
+    events: Vec<EconomicEvent>,
+}
+
+impl EventCalendar {
+    pub fn new(mut events: Vec<EconomicEvent>) -> Self {
+        events.sort_by_key(|e| e.time);
+        Self { events }
+    }
+    /// 按扩展名读取.ics或.csv日历
+    pub fn load(path: &str) -> Result<Self, DataError> {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("ics") => Self::parse_ics(&std::fs::read_to_string(path)?),
+            _ => Self::read_from_csv(path),
+        }
+    }
+    /// time        事件时间，unix时间戳、RFC3339或"年-月-日 时:分:秒"（UTC）
+    /// name        事件名称
+    /// impact      影响程度 low/medium/high
+    pub fn read_from_csv(path: &str) -> Result<Self, DataError> {
+        let mut csv = csv::Reader::from_path(path)?;
+        let mut events = vec![];
+        for d in csv.records() {
+            let d = d?;
+            let field = |i: usize, name: &str| {
+                d.get(i)
+                    .ok_or_else(|| DataError::parse(name, "missing field"))
+                    .map(|s| s.to_string())
+            };
+            let time = field(0, "time")?;
+            let time = parse_time(&time).ok_or_else(|| DataError::parse("time", &time))?;
+            events.push(EconomicEvent {
+                time: to_millis(time),
+                name: field(1, "name")?,
+                impact: field(2, "impact")?.parse()?,
+            });
+        }
+        Ok(Self::new(events))
+    }
+    /// 解析iCalendar中的VEVENT，DTSTART需为UTC时间或日期；
+    /// PRIORITY 1-4为high、5为medium、6-9为low，未设置或为0时视为high
+    pub fn parse_ics(text: &str) -> Result<Self, DataError> {
+        // 以空白开头的行是上一行的续行
+        let mut lines: Vec<String> = vec![];
+        for line in text.lines() {
+            match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+                (Some(rest), Some(last)) => last.push_str(rest),
+                _ => lines.push(line.trim_end().to_string()),
+            }
+        }
+        let mut events = vec![];
+        let mut current: Option<(Option<u64>, String, Impact)> = None;
+        for line in lines {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (name, params) = key.split_once(';').unwrap_or((key, ""));
+            match (name, current.as_mut()) {
+                ("BEGIN", _) if value == "VEVENT" => {
+                    current = Some((None, String::new(), Impact::High));
+                }
+                ("END", Some(_)) if value == "VEVENT" => {
+                    let (time, name, impact) = current.take().unwrap();
+                    let time = time.ok_or_else(|| DataError::parse("DTSTART", "missing"))?;
+                    events.push(EconomicEvent { time, name, impact });
+                }
+                ("DTSTART", Some(event)) => event.0 = Some(parse_ics_time(value, params)?),
+                ("SUMMARY", Some(event)) => event.1 = value.replace("\\,", ","),
+                ("PRIORITY", Some(event)) => {
+                    event.2 = match value.parse::<u8>() {
+                        Ok(0..=4) => Impact::High,
+                        Ok(5) => Impact::Medium,
+                        Ok(_) => Impact::Low,
+                        Err(e) => return Err(DataError::parse("PRIORITY", e)),
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Self::new(events))
+    }
+    pub fn events(&self) -> &[EconomicEvent] {
+        &self.events
+    }
+    /// time处于事件前before到事件后after（毫秒）窗口内、影响不低于min_impact的事件，
+    /// 多个时取最早的一个
+    pub fn active(
+        &self,
+        time: u64,
+        before: u64,
+        after: u64,
+        min_impact: Impact,
+    ) -> Option<&EconomicEvent> {
+        let start = self.events.partition_point(|e| e.time + after < time);
+        self.events[start..]
+            .iter()
+            .take_while(|e| e.time <= time + before)
+            .find(|e| e.impact >= min_impact)
+    }
+}
+
+fn to_millis(time: OffsetDateTime) -> u64 {
+    (time.unix_timestamp_nanos() / 1_000_000) as u64
+}
+
+fn parse_ics_time(value: &str, params: &str) -> Result<u64, DataError> {
+    if params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME") {
+        let date = time::Date::parse(value, format_description!("[year][month][day]"))
+            .map_err(|e| DataError::parse("DTSTART", e))?;
+        return Ok(to_millis(date.midnight().assume_utc()));
+    }
+    let utc = value
+        .strip_suffix('Z')
+        .ok_or_else(|| DataError::parse("DTSTART", format!("{} is not UTC", value)))?;
+    let time = PrimitiveDateTime::parse(
+        utc,
+        format_description!("[year][month][day]T[hour][minute][second]"),
+    )
+    .map_err(|e| DataError::parse("DTSTART", e))?;
+    Ok(to_millis(time.assume_utc()))
+}
+
+#[test]
+fn events_test() {
+    let ics = "BEGIN:VCALENDAR\r\n\
+        BEGIN:VEVENT\r\n\
+        DTSTART:20240131T190000Z\r\n\
+        SUMMARY:FOMC Rate\r\n  Decision\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        DTSTART;VALUE=DATE:20240113\r\n\
+        SUMMARY:PPI\r\n\
+        PRIORITY:7\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+    let calendar = EventCalendar::parse_ics(ics).unwrap();
+    let events = calendar.events();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].name, "PPI");
+    assert_eq!(events[0].impact, Impact::Low);
+    assert_eq!(events[1].name, "FOMC Rate Decision");
+    assert_eq!(events[1].time, 1706727600000);
+    assert!(EventCalendar::parse_ics(
+        "BEGIN:VEVENT\nDTSTART;TZID=America/New_York:20240131T140000\nEND:VEVENT"
+    )
+    .is_err());
+
+    let path = std::env::temp_dir().join("hurribot_events_test.csv");
+    std::fs::write(
+        &path,
+        "time,name,impact\n2024-01-11 13:30:00,CPI,high\n1706727600000,FOMC,High\n",
+    )
+    .unwrap();
+    let calendar = EventCalendar::load(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let cpi = 1704979800000;
+    assert_eq!(calendar.events()[0].time, cpi);
+    let minute = 60_000;
+    assert_eq!(
+        calendar
+            .active(cpi - 10 * minute, 30 * minute, 30 * minute, Impact::High)
+            .map(|e| e.name.as_str()),
+        Some("CPI")
+    );
+    assert!(calendar
+        .active(cpi + 31 * minute, 30 * minute, 30 * minute, Impact::High)
+        .is_none());
+    assert!(calendar
+        .active(cpi - 31 * minute, 30 * minute, 30 * minute, Impact::High)
+        .is_none());
+}
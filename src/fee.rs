@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConfigError;
+
+/// 成交类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FillType {
+    /// 挂单成交
+    #[default]
+    Maker,
+    /// 吃单成交（市价单、止损单、强平）
+    Taker,
+}
+
+/// 币安U本位合约各VIP等级的（maker，taker）费率
+const VIP_RATES: [(f64, f64); 10] = [
+    (0.0002, 0.0005),
+    (0.00016, 0.0004),
+    (0.00014, 0.00035),
+    (0.00012, 0.00032),
+    (0.0001, 0.0003),
+    (0.00008, 0.00027),
+    (0.00006, 0.00025),
+    (0.00004, 0.00022),
+    (0.00002, 0.0002),
+    (0., 0.00017),
+];
+
+/// 使用BNB抵扣手续费的折扣
+const BNB_DISCOUNT: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct FeeConfig {
+    #[serde(default)]
+    pub vip: u8,
+    #[serde(default)]
+    pub bnb_discount: bool,
+}
+
+impl FeeConfig {
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
+        let c = std::fs::read_to_string(path)?;
+        let val: Self = toml::from_str(&c)?;
+        val.validate()?;
+        Ok(val)
+    }
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.vip as usize >= VIP_RATES.len() {
+            return Err(ConfigError::Invalid(format!("vip level {}", self.vip)));
+        }
+        Ok(())
+    }
+}
+
+/// 手续费率表，回测和实盘盈亏估算共用
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub maker: f64,
+    pub taker: f64,
+    /// 费率折扣系数，1为无折扣
+    pub discount: f64,
+}
+
+impl FeeSchedule {
+    /// VIP等级超出范围时取最高等级
+    pub fn vip(level: u8, bnb_discount: bool) -> Self {
+        let (maker, taker) = VIP_RATES[(level as usize).min(VIP_RATES.len() - 1)];
+        Self {
+            maker,
+            taker,
+            discount: if bnb_discount { BNB_DISCOUNT } else { 1. },
+        }
+    }
+    pub fn rate(&self, fill: FillType) -> f64 {
+        let rate = match fill {
+            FillType::Maker => self.maker,
+            FillType::Taker => self.taker,
+        };
+        rate * self.discount
+    }
+    /// 按名义价值计算手续费
+    pub fn fee(&self, notional: f64, fill: FillType) -> f64 {
+        notional.abs() * self.rate(fill)
+    }
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        Self::vip(0, false)
+    }
+}
+
+impl From<FeeConfig> for FeeSchedule {
+    fn from(config: FeeConfig) -> Self {
+        Self::vip(config.vip, config.bnb_discount)
+    }
+}
+
+#[test]
+fn fee_schedule_test() {
+    let fees = FeeSchedule::default();
+    assert_eq!(fees.rate(FillType::Maker), 0.0002);
+    assert_eq!(fees.fee(-1000., FillType::Taker), 0.5);
+    let fees: FeeSchedule = toml::from_str::<FeeConfig>("vip = 3\nbnb_discount = true")
+        .unwrap()
+        .into();
+    assert!((fees.rate(FillType::Taker) - 0.00032 * 0.9).abs() < 1e-12);
+    assert_eq!(FeeSchedule::vip(20, false).maker, 0.);
+}
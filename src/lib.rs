@@ -1,9 +1,24 @@
 pub mod algorithm;
 pub mod backtest;
 pub mod binance_futures;
+pub mod candle;
+pub mod capital;
 pub mod controller;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
+pub mod deleverage;
 pub mod error;
+pub mod events;
+pub mod fee;
+pub mod liquidation;
 pub mod market;
+pub mod notifier;
+pub mod okx_swap;
+pub mod screener;
+pub mod store;
 pub mod strategy;
+pub mod symbol;
+pub mod tax;
+pub mod treasury;
 
-pub mod utils;
\ No newline at end of file
+pub mod utils;
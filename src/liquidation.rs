@@ -0,0 +1,169 @@
+use binance::futures::model::Bracket;
+
+use crate::error::MarketError;
+
+/// 维持保证金阶梯的一档
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarginBracket {
+    pub notional_floor: f64,
+    pub notional_cap: f64,
+    pub maint_margin_ratio: f64,
+    /// 维持保证金速算额（币安接口中的cum）
+    pub maint_amount: f64,
+}
+
+/// 币安U本位合约维持保证金阶梯，按名义价值分档
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarginBrackets(Vec<MarginBracket>);
+
+impl MarginBrackets {
+    /// tiers为按名义价值上限升序排列的（名义价值上限，维持保证金率），速算额由前一档推出
+    pub fn new(tiers: &[(f64, f64)]) -> Self {
+        let mut brackets = Vec::with_capacity(tiers.len());
+        let mut floor = 0.;
+        let mut last: Option<MarginBracket> = None;
+        for &(cap, ratio) in tiers {
+            let maint_amount = last.map_or(0., |l| {
+                l.maint_amount + floor * (ratio - l.maint_margin_ratio)
+            });
+            let bracket = MarginBracket {
+                notional_floor: floor,
+                notional_cap: cap,
+                maint_margin_ratio: ratio,
+                maint_amount,
+            };
+            brackets.push(bracket);
+            last = Some(bracket);
+            floor = cap;
+        }
+        Self(brackets)
+    }
+    pub fn brackets(&self) -> &[MarginBracket] {
+        &self.0
+    }
+    /// 名义价值所在档位，超出最高档时取最高档，阶梯为空时返回错误
+    pub fn bracket(&self, notional: f64) -> Result<&MarginBracket, MarketError> {
+        let notional = notional.abs();
+        self.0
+            .iter()
+            .find(|b| notional < b.notional_cap)
+            .or_else(|| self.0.last())
+            .ok_or_else(|| MarketError::NotFound("margin bracket".to_string()))
+    }
+    pub fn maint_margin(&self, notional: f64) -> Result<f64, MarketError> {
+        let b = self.bracket(notional)?;
+        Ok(notional.abs() * b.maint_margin_ratio - b.maint_amount)
+    }
+    /// 单向持仓的强平价格
+    /// amount          持仓数量，空头为负
+    /// wallet          仓位可用的保证金（逐仓为逐仓钱包余额，全仓为扣除其他仓位维持保证金后的余额）
+    /// order_margin    挂单占用的保证金，从wallet中扣除
+    /// 多头保证金足以覆盖全部仓位时返回0
+    pub fn liq_price(
+        &self,
+        amount: f64,
+        entry_price: f64,
+        wallet: f64,
+        order_margin: f64,
+    ) -> Result<f64, MarketError> {
+        if amount == 0. {
+            return Ok(0.);
+        }
+        let side = amount.signum();
+        let qty = amount.abs();
+        let wallet = wallet - order_margin;
+        // LP = (WB + cum - side * qty * EP) / (qty * MMR - side * qty)，档位按强平时的名义价值确定
+        let price = |b: &MarginBracket| {
+            (wallet + b.maint_amount - side * qty * entry_price)
+                / (qty * b.maint_margin_ratio - side * qty)
+        };
+        let liq_price = match self.0.iter().map(|b| (b, price(b))).find(|(b, p)| {
+            let notional = qty * p;
+            notional >= b.notional_floor && notional < b.notional_cap
+        }) {
+            Some((_, p)) => p,
+            None => price(self.bracket(qty * entry_price)?),
+        };
+        Ok(liq_price.max(0.))
+    }
+    /// 价格向不利方向变动多少比例后触发强平
+    pub fn liquidation_distance(
+        &self,
+        amount: f64,
+        entry_price: f64,
+        wallet: f64,
+        mark: f64,
+    ) -> Result<f64, MarketError> {
+        let notional = amount.abs() * mark;
+        if notional == 0. {
+            return Ok(f64::INFINITY);
+        }
+        let equity = wallet + (mark - entry_price) * amount;
+        Ok((equity - self.maint_margin(notional)?) / notional)
+    }
+}
+
+impl From<&[Bracket]> for MarginBrackets {
+    fn from(brackets: &[Bracket]) -> Self {
+        let mut brackets: Vec<_> = brackets
+            .iter()
+            .map(|b| MarginBracket {
+                notional_floor: b.notional_floor,
+                notional_cap: b.notional_cap,
+                maint_margin_ratio: b.maint_margin_ratio,
+                maint_amount: b.cum,
+            })
+            .collect();
+        brackets.sort_by(|a, b| a.notional_floor.total_cmp(&b.notional_floor));
+        Self(brackets)
+    }
+}
+
+impl Default for MarginBrackets {
+    /// BTCUSDT的阶梯
+    fn default() -> Self {
+        Self::new(&[
+            (50_000., 0.004),
+            (500_000., 0.005),
+            (8_000_000., 0.01),
+            (50_000_000., 0.025),
+            (80_000_000., 0.05),
+            (100_000_000., 0.1),
+            (200_000_000., 0.125),
+            (300_000_000., 0.15),
+            (500_000_000., 0.25),
+            (f64::INFINITY, 0.5),
+        ])
+    }
+}
+
+#[test]
+fn liquidation_test() {
+    let brackets = MarginBrackets::default();
+    assert_eq!(brackets.brackets()[2].maint_amount, 2550.);
+    assert_eq!(brackets.brackets()[9].maint_amount, 167_872_550.);
+    // 10倍多头：强平时权益等于维持保证金
+    let lp = brackets.liq_price(1., 100., 10., 0.).unwrap();
+    assert!((10. + (lp - 100.) - lp * 0.004).abs() < 1e-9);
+    let lp = brackets.liq_price(-1., 100., 10., 0.).unwrap();
+    assert!((10. + (100. - lp) - lp * 0.004).abs() < 1e-9);
+    // 挂单占用保证金使强平价格更近
+    assert!(
+        brackets.liq_price(1., 100., 10., 2.).unwrap()
+            > brackets.liq_price(1., 100., 10., 0.).unwrap()
+    );
+    // 大仓位落在更高档位
+    let lp = brackets.liq_price(100., 10_000., 100_000., 0.).unwrap();
+    let b = brackets.bracket(100. * lp).unwrap();
+    assert_eq!(b.maint_margin_ratio, 0.01);
+    assert!(
+        (100_000. + 100. * (lp - 10_000.) - brackets.maint_margin(100. * lp).unwrap()).abs() < 1e-6
+    );
+    assert_eq!(brackets.liq_price(1., 100., 200., 0.).unwrap(), 0.);
+    assert!((brackets.liquidation_distance(1., 100., 5., 100.).unwrap() - 0.046).abs() < 1e-9);
+    // 交易所未返回阶梯时报错而不是panic
+    let empty = MarginBrackets::from(&[][..]);
+    assert!(empty.bracket(100.).is_err());
+    assert!(empty.liq_price(1., 100., 10., 0.).is_err());
+    assert!(empty.liquidation_distance(1., 100., 5., 100.).is_err());
+}
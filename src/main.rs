@@ -1,5 +1,7 @@
+use std::sync::{atomic::AtomicBool, Arc};
+
 use hurribot::{
-    binance_futures::{BinanceKeys, FuturesWsConnection},
+    binance_futures::{combined::CombinedStreams, BinanceKeys},
     market,
     utils::stdout_logger,
 };
@@ -8,7 +10,10 @@ fn main() {
     // let _guard = file_logger("main");
     stdout_logger();
     info!("start");
-    let (price_rx, prices, conn_h) = FuturesWsConnection::run_price_info();
+    // 行情订阅合并到尽量少的连接上
+    let mut streams = CombinedStreams::default();
+    let (price_rx, prices, price_health, price_stats) = streams.prices(Arc::default());
+    let conn_h = streams.run(Arc::new(AtomicBool::new(true)));
     let binance_keys = BinanceKeys::value_parse("./config/binance_keys.toml").unwrap();
 
     conn_h.join().unwrap();
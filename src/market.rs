@@ -1,16 +1,53 @@
-use anyhow::{anyhow, Ok};
 use crossbeam::channel::{Receiver, Sender};
 use rayon::{
     iter::{ParallelBridge, ParallelIterator},
     result,
 };
 
+use crate::{error::MarketError, fee::FeeSchedule, liquidation::MarginBrackets};
+
+pub mod account_cache;
 pub mod binance_market;
+pub mod config;
+pub mod liquidity;
+pub mod listing;
+pub mod margin_buffer;
+#[cfg(any(test, feature = "paper"))]
+pub mod mock_market;
+pub mod okx_market;
+pub mod shadow;
+pub mod spread;
+pub mod symbol_filters;
+
+use shadow::ShadowReport;
+use spread::{SpreadOrderRequest, SpreadOrderReturn};
 
 pub trait Market: std::fmt::Debug + Send + Sync + 'static {
-    fn clear_orders(&self, symbol: &str) -> anyhow::Result<()>;
-    fn close_position(&self, symbol: &str) -> anyhow::Result<()>;
-    fn order(&self, request: MarketOrderRequest) -> anyhow::Result<MarketOrderReturn>;
+    fn clear_orders(&self, symbol: &str) -> Result<(), MarketError>;
+    fn close_position(&self, symbol: &str) -> Result<(), MarketError>;
+    fn order(&self, request: MarketOrderRequest) -> Result<MarketOrderReturn, MarketError>;
+    /// 只减仓市价单，qty超过持仓时按持仓数量，返回实际减仓数量
+    fn reduce_position(&self, symbol: &str, qty: f64) -> Result<f64, MarketError>;
+    /// 按当前持仓的比例分批减仓，比例之和不能超过1，返回每批的减仓数量
+    fn scale_out(&self, symbol: &str, fractions: &[f64]) -> Result<Vec<f64>, MarketError>;
+    /// 逐仓追加保证金
+    fn add_margin(&self, symbol: &str, amount: f64) -> Result<(), MarketError>;
+    /// 多腿组合单，逐腿下单，某条腿失败时回滚已成交的腿
+    fn order_spread(&self, request: SpreadOrderRequest) -> Result<SpreadOrderReturn, MarketError> {
+        spread::execute(self, request)
+    }
+    /// 影子交易与模拟市场的差异，见shadow::ShadowMarket，其余市场为None
+    fn shadow_report(&self) -> Option<ShadowReport> {
+        None
+    }
+    /// 账户的手续费率，用于估算平仓手续费
+    fn fees(&self) -> FeeSchedule {
+        FeeSchedule::default()
+    }
+    /// 币种的维持保证金阶梯，交易所未提供时为None
+    fn margin_brackets(&self, _symbol: &str) -> Option<MarginBrackets> {
+        None
+    }
 }
 
 pub struct MarketResult {}
@@ -20,12 +57,61 @@ pub enum MarketRequest {
     ClosePosition(String),
     Order(MarketOrderRequest),
 }
+/// 限价单的有效方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeInForce {
+    /// 成交为止一直有效
+    #[default]
+    Gtc,
+    /// 立即成交，未成交部分撤销
+    Ioc,
+    /// 全部立即成交，否则撤销
+    Fok,
+    /// 只做maker，会立即成交时拒绝
+    Gtx,
+}
+
+impl TimeInForce {
+    /// 挂单价会与当前价格交叉时是否立即以taker成交，否则挂单或被撤销
+    pub fn takes_on_cross(self) -> bool {
+        self != TimeInForce::Gtx
+    }
+    /// 未立即成交时是否挂在盘口
+    pub fn rests(self) -> bool {
+        matches!(self, TimeInForce::Gtc | TimeInForce::Gtx)
+    }
+}
+
+impl From<TimeInForce> for binance::futures::account::TimeInForce {
+    fn from(tif: TimeInForce) -> Self {
+        match tif {
+            TimeInForce::Gtc => Self::GTC,
+            TimeInForce::Ioc => Self::IOC,
+            TimeInForce::Fok => Self::FOK,
+            TimeInForce::Gtx => Self::GTX,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct MarketOrderRequest {
     symbol: String,
     is_buy: bool,
     value: f64,
     low_limit: f64,
     high_limit: f64,
+    /// 开仓限价单的有效方式，None为市价开仓
+    time_in_force: Option<TimeInForce>,
+    /// 止损改用限价单，限价相对触发价向亏损方向的偏移比例；None为市价止损
+    stop_limit_offset: Option<f64>,
+    /// （策略序号，请求id），用于生成client order id以关联成交
+    tag: Option<(usize, u64)>,
+    /// 订单序号的起点，组合单的各腿不同
+    leg_base: usize,
+    /// 该币种的逐仓杠杆，None为市场构造时的默认杠杆
+    leverage: Option<u8>,
+    /// 已由组合单在拆分前按成交额限制，下单时不再单独检查
+    liquidity_checked: bool,
 }
 
 impl MarketOrderRequest {
@@ -35,15 +121,19 @@ impl MarketOrderRequest {
         value: f64,
         low_limit: f64,
         high_limit: f64,
-    ) -> anyhow::Result<Self> {
+    ) -> Result<Self, MarketError> {
         if value <= 0. {
-            return Err(anyhow!("value must be positive"));
+            return Err(MarketError::Rejected("value must be positive".to_string()));
         }
         if low_limit <= 0. || low_limit >= 1. {
-            return Err(anyhow!("low_limit must be in (0, 1)"));
+            return Err(MarketError::Rejected(
+                "low_limit must be in (0, 1)".to_string(),
+            ));
         }
         if high_limit <= 1. {
-            return Err(anyhow!("high_limit must be greater than 1"));
+            return Err(MarketError::Rejected(
+                "high_limit must be greater than 1".to_string(),
+            ));
         }
         Ok(Self {
             symbol,
@@ -51,11 +141,112 @@ impl MarketOrderRequest {
             value,
             low_limit,
             high_limit,
+            time_in_force: None,
+            stop_limit_offset: None,
+            tag: None,
+            leg_base: 0,
+            leverage: None,
+            liquidity_checked: false,
         })
     }
+    /// 开仓改为以当前价挂出的限价单；GTC/GTX未成交的部分挂在盘口，
+    /// GTX会与盘口交叉时被交易所拒绝，保证只以maker成交
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = Some(time_in_force);
+        self
+    }
+    /// 止损触发后以限价单成交，避免常规止损支付taker手续费；
+    /// 价格跳空越过限价时不会成交，仓位仍然暴露
+    pub fn with_stop_limit(mut self, offset: f64) -> Self {
+        self.stop_limit_offset = Some(offset.max(0.));
+        self
+    }
+    /// 止损限价单的价格，平多为卖出，限价低于触发价
+    pub fn stop_limit_price(&self, stop_price: f64) -> Option<f64> {
+        let offset = self.stop_limit_offset?;
+        Some(if self.is_buy {
+            stop_price * (1. - offset)
+        } else {
+            stop_price * (1. + offset)
+        })
+    }
+    /// 按该杠杆开仓，与币种当前杠杆不同时由市场调整；超过档位上限时拒单或按市场设置降低
+    pub fn with_leverage(mut self, leverage: u8) -> Self {
+        self.leverage = Some(leverage.max(1));
+        self
+    }
+    pub fn with_tag(mut self, strategy: usize, request_id: u64) -> Self {
+        self.tag = Some((strategy, request_id));
+        self
+    }
+}
+
+/// 结构化的client order id：s{策略序号}_{请求id}_{订单序号}
+/// 订单序号：0为开仓单，之后为止盈止损等附属单；组合单第i条腿从i * LEGS_PER_ORDER开始
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientOrderId {
+    pub strategy: usize,
+    pub request_id: u64,
+    pub leg: usize,
+}
+
+/// 单个开仓请求占用的订单序号数：开仓、止盈、止损
+pub const LEGS_PER_ORDER: usize = 3;
+
+impl ClientOrderId {
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.strip_prefix('s')?.split('_');
+        let id = Self {
+            strategy: parts.next()?.parse().ok()?,
+            request_id: parts.next()?.parse().ok()?,
+            leg: parts.next()?.parse().ok()?,
+        };
+        parts.next().is_none().then_some(id)
+    }
+}
+
+impl std::fmt::Display for ClientOrderId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "s{}_{}_{}", self.strategy, self.request_id, self.leg)
+    }
 }
 pub struct MarketOrderReturn {
+    /// 开仓单
     pub order_id: u64,
     pub qty: f64,
     pub value: f64,
+    /// 计算下单数量所用的价格
+    pub price: f64,
+    /// 开仓单的成交均价，下单响应未返回时（如批量下单的ACK）为None，以成交推送为准
+    pub fill_price: Option<f64>,
+    /// 开仓单的手续费，按taker费率估算，只做maker的开仓按maker费率
+    pub fee: f64,
+    /// 止盈单，交易所没有单独的订单id或下单失败时为None
+    pub take_profit_id: Option<u64>,
+    pub take_profit_price: f64,
+    /// 止损单
+    pub stop_loss_id: Option<u64>,
+    /// 止损触发价
+    pub stop_price: f64,
+    /// 止损限价单的限价，None为市价止损
+    pub stop_limit: Option<f64>,
+    /// 开仓后按MarginBuffer追加的逐仓保证金，未追加为0
+    pub margin_buffer: f64,
+    /// 追加逐仓保证金失败时的错误，仓位已开且附属单照常下达，但保证金未达到缓冲要求
+    pub margin_error: Option<MarketError>,
+    /// 下单失败的附属单序号（1为止盈，2为止损），开仓已成交但仓位缺少保护
+    pub failed_legs: Vec<usize>,
+}
+
+#[test]
+fn client_order_id_test() {
+    let id = ClientOrderId {
+        strategy: 2,
+        request_id: 1700000000123,
+        leg: 1,
+    };
+    assert_eq!(id.to_string(), "s2_1700000000123_1");
+    assert_eq!(ClientOrderId::parse(&id.to_string()), Some(id));
+    assert_eq!(ClientOrderId::parse("web_abc"), None);
+    assert_eq!(ClientOrderId::parse("s1_2_3_4"), None);
 }
@@ -0,0 +1,231 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::Relaxed};
+
+use binance::futures::account::FuturesAccount;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+use crate::{
+    controller::AccountInfo,
+    error::{BinanceResultExt, MarketError},
+    symbol::filter::DEFAULT_QUOTE_ASSET,
+    utils::unix_millis,
+};
+
+/// 距上次REST刷新或对账超过该时长（毫秒）视为过期，防止用户数据流静默断开后一直使用旧数据
+const DEFAULT_MAX_AGE: u64 = 10 * 60 * 1000;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CachedPosition {
+    pub amount: f64,
+    pub entry_price: f64,
+    pub isolated: bool,
+    /// 只由用户数据流事件得到的仓位不含杠杆，为None
+    pub leverage: Option<u8>,
+}
+
+/// 由用户数据流维护的持仓和余额缓存，下单前不必每次通过REST查询；
+/// 初始、检测到事件缺口（重连后对账失败）或超过max_age未与REST同步时失效，下次读取前通过REST刷新
+#[derive(Debug)]
+pub struct AccountCache {
+    positions: DashMap<String, CachedPosition>,
+    /// 结算资产
+    quote_asset: String,
+    /// 结算资产的钱包余额
+    balance: Mutex<f64>,
+    valid: AtomicBool,
+    /// 上次REST刷新或对账的时间（unix毫秒）
+    synced: AtomicU64,
+    max_age: u64,
+}
+
+impl Default for AccountCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUOTE_ASSET)
+    }
+}
+
+impl AccountCache {
+    pub fn new(quote_asset: &str) -> Self {
+        Self {
+            positions: DashMap::new(),
+            quote_asset: quote_asset.to_string(),
+            balance: Mutex::new(0.),
+            valid: AtomicBool::new(false),
+            synced: AtomicU64::new(0),
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+    pub fn with_max_age(mut self, max_age: u64) -> Self {
+        self.max_age = max_age;
+        self
+    }
+    pub fn quote_asset(&self) -> &str {
+        &self.quote_asset
+    }
+    pub fn is_valid(&self) -> bool {
+        self.valid.load(Relaxed)
+            && unix_millis().saturating_sub(self.synced.load(Relaxed)) <= self.max_age
+    }
+    fn mark_synced(&self) {
+        self.synced.store(unix_millis(), Relaxed);
+        self.valid.store(true, Relaxed);
+    }
+    pub fn invalidate(&self) {
+        if self.valid.swap(false, Relaxed) {
+            warn!("Account cache invalidated");
+        }
+    }
+    /// 用户数据流事件和重连后的对账结果
+    pub fn apply(&self, info: &AccountInfo) {
+        match info {
+            AccountInfo::OrderTrade { .. } | AccountInfo::MarginCall { .. } => {}
+            AccountInfo::AccountUpdate { data, .. } => {
+                for b in data.balances.iter().filter(|b| b.asset == self.quote_asset) {
+                    *self.balance.lock() = b.wallet_balance.parse().unwrap_or_default();
+                }
+                for p in data.positions.iter() {
+                    let mut position = self.positions.entry(p.symbol.clone()).or_default();
+                    position.amount = p.position_amount.parse().unwrap_or_default();
+                    position.entry_price = p.entry_price.parse().unwrap_or_default();
+                    position.isolated = p.margin_type == "isolated";
+                }
+            }
+            AccountInfo::Reconcile {
+                balance, positions, ..
+            } => {
+                if let Some(balance) = balance {
+                    *self.balance.lock() = *balance;
+                }
+                // 对账只返回非空仓位
+                for mut position in self.positions.iter_mut() {
+                    if positions.iter().all(|p| &p.symbol != position.key()) {
+                        position.amount = 0.;
+                    }
+                }
+                for p in positions {
+                    let mut position = self.positions.entry(p.symbol.clone()).or_default();
+                    position.amount = p.position_amount;
+                    position.entry_price = p.entry_price;
+                    position.isolated = p.margin_type == "isolated";
+                    position.leverage = p.leverage.parse().ok();
+                }
+                self.mark_synced();
+            }
+        }
+    }
+    /// 通过REST重新加载全部持仓和余额
+    pub fn refresh(&self, account: &FuturesAccount) -> Result<(), MarketError> {
+        let info = account
+            .account_information()
+            .market_context("get account info")?;
+        if let Some(a) = info.assets.iter().find(|a| a.asset == self.quote_asset) {
+            *self.balance.lock() = a.wallet_balance;
+        }
+        self.positions.clear();
+        for p in info.positions {
+            self.positions.insert(
+                p.symbol,
+                CachedPosition {
+                    amount: p.position_amount,
+                    entry_price: p.entry_price,
+                    isolated: p.isolated,
+                    leverage: p.leverage.parse().ok(),
+                },
+            );
+        }
+        self.mark_synced();
+        info!("Account cache refreshed");
+        Ok(())
+    }
+    /// 失效时先刷新
+    pub fn ensure(&self, account: &FuturesAccount) -> Result<(), MarketError> {
+        if self.is_valid() {
+            return Ok(());
+        }
+        self.refresh(account)
+    }
+    pub fn position(&self, symbol: &str) -> Option<CachedPosition> {
+        self.positions.get(symbol).map(|p| p.clone())
+    }
+    pub fn balance(&self) -> f64 {
+        *self.balance.lock()
+    }
+    /// 本地修改杠杆或保证金模式成功后同步到缓存
+    pub fn set_leverage(&self, symbol: &str, leverage: u8) {
+        self.positions
+            .entry(symbol.to_string())
+            .or_default()
+            .leverage = Some(leverage);
+    }
+    pub fn set_isolated(&self, symbol: &str) {
+        self.positions
+            .entry(symbol.to_string())
+            .or_default()
+            .isolated = true;
+    }
+}
+
+#[test]
+fn account_cache_test() {
+    use super::mock_market::{account_update, FakePosition};
+
+    let cache = AccountCache::default();
+    assert!(!cache.is_valid());
+    let position = |amount| FakePosition {
+        symbol: "BTCUSDT".into(),
+        amount,
+        entry_price: 100.,
+        isolated_wallet: 10.,
+    };
+    cache.apply(&account_update(1, 990., &[position(1.)]));
+    cache.set_leverage("BTCUSDT", 10);
+    assert_eq!(cache.balance(), 990.);
+    assert_eq!(
+        cache.position("BTCUSDT"),
+        Some(CachedPosition {
+            amount: 1.,
+            entry_price: 100.,
+            isolated: true,
+            leverage: Some(10),
+        })
+    );
+    // 新出现的仓位杠杆未知
+    cache.apply(&account_update(
+        1,
+        990.,
+        &[FakePosition {
+            symbol: "ETHUSDT".into(),
+            ..position(1.)
+        }],
+    ));
+    assert_eq!(cache.position("ETHUSDT").unwrap().leverage, None);
+    // 事件不会使失效的缓存恢复，只有REST刷新或对账可以
+    assert!(!cache.is_valid());
+    cache.apply(&AccountInfo::Reconcile {
+        time: 2,
+        balance: Some(1000.),
+        positions: vec![],
+        open_orders: vec![],
+    });
+    assert!(cache.is_valid());
+    assert_eq!(cache.balance(), 1000.);
+    assert_eq!(cache.position("BTCUSDT").unwrap().amount, 0.);
+    assert_eq!(cache.position("BTCUSDT").unwrap().leverage, Some(10));
+    cache.invalidate();
+    assert!(!cache.is_valid());
+    // 长时间未同步视为过期
+    let stale = AccountCache::default().with_max_age(0);
+    stale.apply(&AccountInfo::Reconcile {
+        time: 2,
+        balance: None,
+        positions: vec![],
+        open_orders: vec![],
+    });
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    assert!(!stale.is_valid());
+    // 只记录结算资产的余额
+    let usdc = AccountCache::new("USDC");
+    usdc.apply(&account_update(3, 990., &[]));
+    assert_eq!(usdc.balance(), 0.);
+}
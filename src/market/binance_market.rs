@@ -1,122 +1,177 @@
-use anyhow::{anyhow, bail};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
 use binance::futures::{
-    account::{OrderRequest, TimeInForce},
+    account::{OrderRequest, OrderType, TimeInForce},
     model::{Bracket, TransactionOrError},
 };
+use crossbeam::channel::Receiver;
 use dashmap::DashMap;
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 
 use crate::{
-    binance_futures::{BinanceKeys, Clients},
-    utils::truncate_step,
+    binance_futures::{BinanceKeys, Clients, ClockDrift, TimeSync},
+    error::{BinanceErrorCode, BinanceResultExt, MarketError},
+    fee::{FeeSchedule, FillType},
+    liquidation::MarginBrackets,
+    symbol::{filter::SymbolFilter, SymbolId},
 };
 
-use super::{Market, MarketOrderRequest, MarketOrderReturn};
+use super::{
+    account_cache::{AccountCache, CachedPosition},
+    config::MarketConfig,
+    liquidity::{LiquidityGuard, LiquidityLimit},
+    listing::{ListingEvent, ListingStatus, Listings},
+    margin_buffer::{MarginBuffer, MarginInputs},
+    spread::{self, SpreadOrderRequest, SpreadOrderReturn},
+    symbol_filters::{RoundedOrder, SymbolFilters},
+    ClientOrderId, Market, MarketOrderRequest, MarketOrderReturn,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct BinanceSymbolStatus {
-    min_qty: f64,
-    min_qty_step: f64,
-    tick_size: f64,
-    min_notional: f64,
+    /// 保证金资产，下单金额以其计价
+    margin_asset: String,
     brackets: Vec<Bracket>,
-}
-
-impl Default for BinanceSymbolStatus {
-    fn default() -> Self {
-        Self {
-            min_qty: 1.,
-            min_qty_step: 1.,
-            tick_size: 0.1,
-            min_notional: 1.,
-            brackets: Vec::new(),
-        }
-    }
+    /// 该币种当前的杠杆
+    leverage: u8,
 }
 
 impl BinanceSymbolStatus {
-    pub fn update_market_info(&mut self, info: binance::futures::model::Symbol) {
-        for filter in info.filters {
-            match filter {
-                binance::model::Filters::LotSize {
-                    min_qty, step_size, ..
-                } => {
-                    self.min_qty = min_qty.parse().unwrap();
-                    self.min_qty_step = step_size.parse().unwrap();
-                }
-                binance::model::Filters::PriceFilter { tick_size, .. } => {
-                    self.tick_size = tick_size.parse().unwrap();
-                }
-                binance::model::Filters::MinNotional {
-                    notional: Some(n), ..
-                } => {
-                    self.min_notional = n.parse().unwrap();
-                }
-                _ => {}
-            }
-        }
+    /// 更新保证金资产，交易规则缓存到filters
+    pub fn update_market_info(
+        &mut self,
+        info: binance::futures::model::Symbol,
+        filters: &SymbolFilters,
+    ) {
+        filters.update(&info);
+        self.margin_asset = info.margin_asset;
     }
 }
 
 #[derive(Debug)]
 pub struct BinanceMarket {
-    statuses: DashMap<String, BinanceSymbolStatus>,
+    statuses: Arc<DashMap<String, BinanceSymbolStatus>>,
+    /// 默认杠杆，下单请求可单独指定；各币种当前杠杆缓存在statuses中，不同时才调整
     leverage: u8,
+    /// 名义价值超过当前档位允许的杠杆时自动降低杠杆，否则拒单
+    auto_leverage: bool,
+    /// 开仓后追加逐仓保证金的规则
+    margin_buffer: MarginBuffer,
+    /// 用于估算开仓手续费
+    fees: FeeSchedule,
+    /// 按24h成交额限制开仓金额，None为不限制
+    liquidity: Option<LiquidityGuard>,
+    /// 由用户数据流维护的持仓缓存，None时每次通过REST查询
+    account: Option<Arc<AccountCache>>,
     clients: Clients,
+    /// 随BinanceMarket drop停止
+    time_sync: TimeSync,
+    filter: Arc<SymbolFilter>,
+    /// 交易所信息中的下单规则
+    filters: Arc<SymbolFilters>,
+    /// 结算资产相同的全部币种的交易状态，用于发现上下架
+    listings: Arc<Listings>,
 }
 
 impl BinanceMarket {
-    pub fn new(binance_keys: BinanceKeys, leverage: u8) -> anyhow::Result<Self> {
+    pub fn new(binance_keys: BinanceKeys, leverage: u8) -> Result<Self, MarketError> {
+        Self::new_with_filter(binance_keys, leverage, Arc::default())
+    }
+    /// 只加载通过过滤的币种，需要按成交额过滤时先拉取24h行情更新成交额
+    pub fn new_with_filter(
+        binance_keys: BinanceKeys,
+        leverage: u8,
+        filter: Arc<SymbolFilter>,
+    ) -> Result<Self, MarketError> {
         let clients = Clients::new(binance_keys);
-        clients
-            .account
-            .change_position_mode(false)
-            .inspect_err(|e| {
-                warn!("change position mode failed: {:?}", e.0);
-            })
-            .ok();
+        clients.sync_time()?;
+        clients.clock.check()?;
+        let time_sync = clients.run_time_sync(Duration::from_secs(60));
+        ensure_one_way(
+            clients
+                .account
+                .change_position_mode(false)
+                .market_context("change position mode"),
+        )?;
+        if filter.needs_volumes() {
+            let stats = clients
+                .market
+                .get_all_24h_price_stats()
+                .market_context("get 24h price stats")?;
+            filter.set_volumes(stats.into_iter().map(|s| (s.symbol, s.quote_volume)));
+        }
         let statuses = DashMap::new();
+        let filters = Arc::new(SymbolFilters::new());
+        let listings = Arc::new(Listings::default());
 
-        for symbol_info in clients
+        let symbols = clients
             .general
             .exchange_info()
-            .map_err(|e| anyhow!("get ex info failed: {:?}", e.0))?
-            .symbols
-        {
+            .market_context("get ex info")?
+            .symbols;
+        SymbolId::preload(symbols.iter().map(|s| &s.symbol));
+        listings.update(
+            symbols
+                .iter()
+                .filter(|s| s.margin_asset == filter.quote_asset())
+                .map(|s| ListingStatus {
+                    symbol: s.symbol.clone(),
+                    status: s.status.clone(),
+                    delivery_date: s.delivery_date,
+                }),
+        );
+        for symbol_info in symbols {
+            if !filter.allows(&symbol_info.symbol)
+                || symbol_info.margin_asset != filter.quote_asset()
+                || !listings.is_trading(&symbol_info.symbol)
+            {
+                continue;
+            }
             let symbol = symbol_info.symbol.clone();
-            let mut status = BinanceSymbolStatus::default();
-            status.update_market_info(symbol_info);
+            let mut status = BinanceSymbolStatus {
+                leverage,
+                ..Default::default()
+            };
+            status.update_market_info(symbol_info, &filters);
             statuses.insert(symbol, status);
         }
 
         for position in clients
             .account
             .account_information()
-            .map_err(|e| anyhow!("get account info failed: {:?}", e.0))?
+            .market_context("get account info")?
             .positions
         {
+            if !statuses.contains_key(&position.symbol) {
+                continue;
+            }
             let symbol = position.symbol.clone();
             if !position.isolated {
                 clients
                     .account
                     .change_margin_type(&symbol, true)
-                    .map_err(|e| {
-                        anyhow!("Symbol {} change margin type failed: {:?}", symbol, e.0)
-                    })?;
+                    .market_context(&format!("symbol {} change margin type", symbol))?;
             }
-            let l: u8 = position.leverage.parse()?;
+            let l = parse_leverage(&symbol, &position.leverage)?;
             if l != leverage {
                 clients
                     .account
                     .change_initial_leverage(&symbol, leverage)
-                    .map_err(|e| anyhow!("Symbol {} change leverage failed: {:?}", symbol, e.0))?;
+                    .market_context(&format!("symbol {} change leverage", symbol))?;
             }
         }
 
         for brackets in clients
             .account
             .leverage_brackets(None)
-            .map_err(|e| anyhow!("get leverage bracket failed: {:?}", e.0))?
+            .market_context("get leverage bracket")?
         {
             statuses
                 .entry(brackets.symbol.clone())
@@ -130,55 +185,205 @@ impl BinanceMarket {
             }
             !is_empty
         });
+        info!("Loaded {} symbols", statuses.len());
         Ok(Self {
-            statuses,
+            statuses: Arc::new(statuses),
             clients,
+            time_sync,
             leverage,
+            auto_leverage: false,
+            margin_buffer: MarginBuffer::default(),
+            fees: FeeSchedule::default(),
+            liquidity: None,
+            account: None,
+            filter,
+            filters,
+            listings,
         })
     }
-    pub fn update_symbol_status(&self, symbol: &str, is_forced: bool) -> anyhow::Result<()> {
-        if self.statuses.contains_key(symbol) && !is_forced {
-            return Ok(());
+    /// 关闭时停止校时线程并等待其退出，drop时也会停止
+    pub fn stop(&mut self) {
+        self.time_sync.stop();
+    }
+    /// 校时结果，供自行签名的请求（如资金流水、划转）共用
+    pub fn clock(&self) -> Arc<ClockDrift> {
+        self.clients.clock.clone()
+    }
+    /// 大额订单按档位自动降低该币种的杠杆，之后的小额订单恢复到设定杠杆
+    pub fn with_auto_leverage(mut self) -> Self {
+        self.auto_leverage = true;
+        self
+    }
+    pub fn with_fees(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+    pub fn with_margin_buffer(mut self, margin_buffer: MarginBuffer) -> Self {
+        self.margin_buffer = margin_buffer;
+        self
+    }
+    pub fn with_liquidity_limit(mut self, limit: LiquidityLimit) -> Self {
+        self.liquidity = Some(LiquidityGuard::new(limit));
+        self
+    }
+    /// 按配置的杠杆构造并应用其余设置
+    pub fn from_config(
+        binance_keys: BinanceKeys,
+        config: &MarketConfig,
+        filter: Arc<SymbolFilter>,
+    ) -> Result<Self, MarketError> {
+        let mut market = Self::new_with_filter(binance_keys, config.leverage, filter)?
+            .with_fees(config.fee.into());
+        if config.auto_leverage {
+            market = market.with_auto_leverage();
+        }
+        if let Some(limit) = config.liquidity {
+            market = market.with_liquidity_limit(limit);
+        }
+        Ok(market)
+    }
+    /// 按24h成交额返回允许的下单金额，未设置限制时原样返回
+    fn check_liquidity(&self, symbol: &str, value: f64) -> Result<f64, MarketError> {
+        let Some(liquidity) = &self.liquidity else {
+            return Ok(value);
+        };
+        liquidity.check(symbol, value, || {
+            Ok(self
+                .clients
+                .market
+                .get_24h_price_stats(symbol)
+                .market_context("get 24h price stats")?
+                .quote_volume)
+        })
+    }
+    /// 与run_account_info_with_cache共用缓存，缓存的结算资产需与过滤规则一致
+    pub fn with_account_cache(mut self, account: Arc<AccountCache>) -> Self {
+        if account.quote_asset() != self.filter.quote_asset() {
+            warn!(
+                "Account cache quote asset {} differs from {}",
+                account.quote_asset(),
+                self.filter.quote_asset()
+            );
+        }
+        self.account = Some(account);
+        self
+    }
+    /// 已加载币种的下单规则，可与模拟交易所共享
+    pub fn filters(&self) -> Arc<SymbolFilters> {
+        self.filters.clone()
+    }
+    /// 重新拉取交易所信息和杠杆档位，更新已加载币种的下单规则，返回上下架变化；
+    /// 下架的币种从过滤规则中排除，之后的下单会被拒绝
+    pub fn refresh_exchange_info(&self) -> Result<Vec<ListingEvent>, MarketError> {
+        self.refresher().refresh()
+    }
+    /// 定期刷新交易所信息，上下架变化发送到返回的channel，
+    /// 如将Delisting转为ControlCommand::Delist平掉持仓
+    pub fn run_exchange_info_refresh(
+        &self,
+        interval: Duration,
+        running: Arc<AtomicBool>,
+    ) -> (Receiver<ListingEvent>, JoinHandle<()>) {
+        let (tx, rx) = crossbeam::channel::unbounded();
+        let refresher = self.refresher();
+        let h = std::thread::spawn(move || {
+            while running.load(Relaxed) {
+                std::thread::sleep(interval);
+                match refresher.refresh() {
+                    Ok(events) => {
+                        for e in events {
+                            tx.send(e).ok();
+                        }
+                    }
+                    Err(e) => warn!("Refresh exchange info failed: {}", e),
+                }
+            }
+        });
+        (rx, h)
+    }
+    fn refresher(&self) -> ExchangeInfoRefresher {
+        ExchangeInfoRefresher {
+            general: self.clients.general.clone(),
+            account: self.clients.account.clone(),
+            statuses: self.statuses.clone(),
+            filter: self.filter.clone(),
+            filters: self.filters.clone(),
+            listings: self.listings.clone(),
+        }
+    }
+    pub fn update_symbol_status(&self, symbol: &str, is_forced: bool) -> Result<(), MarketError> {
+        if self.statuses.contains_key(symbol) {
+            if !is_forced {
+                return Ok(());
+            }
+        } else if !self.filter.allows(symbol) {
+            return Err(MarketError::Rejected(format!("symbol {} filtered", symbol)));
         }
-        let mut status = BinanceSymbolStatus::default();
+        let mut status = BinanceSymbolStatus {
+            leverage: self.leverage,
+            ..Default::default()
+        };
 
         let symbol_info = self
             .clients
             .general
             .get_symbol_info(symbol)
-            .map_err(|e| anyhow!("get symbol info failed: {:?}", e.0))?;
-        status.update_market_info(symbol_info);
+            .market_context("get symbol info")?;
+        status.update_market_info(symbol_info, &self.filters);
+        // 下单金额按结算资产计算，保证金资产不同的币种无法正确定量
+        if status.margin_asset != self.filter.quote_asset() {
+            return Err(MarketError::Rejected(format!(
+                "symbol {} margin asset {} is not {}",
+                symbol,
+                status.margin_asset,
+                self.filter.quote_asset()
+            )));
+        }
 
-        let position = self
-            .clients
-            .account
-            .account_information()
-            .map_err(|e| anyhow!("get account info failed: {:?}", e.0))?
-            .positions
-            .into_iter()
-            .find(|p| p.symbol == symbol)
-            .ok_or(anyhow!("position not found"))?;
-        if !position.isolated {
+        // 缓存中杠杆未知时从REST读取
+        let cached = self.cached_position(symbol)?;
+        let (isolated, l) = match cached.and_then(|p| Some((p.isolated, p.leverage?))) {
+            Some(known) => known,
+            None => {
+                let position = self
+                    .clients
+                    .account
+                    .account_information()
+                    .market_context("get account info")?
+                    .positions
+                    .into_iter()
+                    .find(|p| p.symbol == symbol)
+                    .ok_or(MarketError::NotFound("position".to_string()))?;
+                let l = parse_leverage(symbol, &position.leverage)?;
+                (position.isolated, l)
+            }
+        };
+        if !isolated {
             self.clients
                 .account
                 .change_margin_type(symbol, true)
-                .map_err(|e| anyhow!("Symbol {} change margin type failed: {:?}", symbol, e.0))?;
+                .market_context(&format!("symbol {} change margin type", symbol))?;
+            if let Some(account) = &self.account {
+                account.set_isolated(symbol);
+            }
         }
-        let l: u8 = position.leverage.parse()?;
         if l != self.leverage {
             self.clients
                 .account
                 .change_initial_leverage(symbol, self.leverage)
-                .map_err(|e| anyhow!("Symbol {} change leverage failed: {:?}", symbol, e.0))?;
+                .market_context(&format!("symbol {} change leverage", symbol))?;
+            if let Some(account) = &self.account {
+                account.set_leverage(symbol, self.leverage);
+            }
         }
 
         status.brackets = self
             .clients
             .account
             .leverage_brackets(Some(symbol.to_string()))
-            .map_err(|e| anyhow!("get leverage bracket failed: {:?}", e.0))?
+            .market_context("get leverage bracket")?
             .pop()
-            .ok_or(anyhow!("brackets not found"))?
+            .ok_or(MarketError::NotFound("brackets".to_string()))?
             .brackets;
 
         self.statuses.insert(symbol.to_string(), status);
@@ -186,127 +391,472 @@ impl BinanceMarket {
     }
 }
 
+impl BinanceMarket {
+    /// 有账户缓存时从缓存读取，缓存失效时先通过REST刷新；没有缓存时返回None
+    fn cached_position(&self, symbol: &str) -> Result<Option<CachedPosition>, MarketError> {
+        let Some(account) = &self.account else {
+            return Ok(None);
+        };
+        account.ensure(&self.clients.account)?;
+        Ok(Some(account.position(symbol).unwrap_or_default()))
+    }
+    fn position_amount(&self, symbol: &str) -> Result<f64, MarketError> {
+        if let Some(p) = self.cached_position(symbol)? {
+            return Ok(p.amount);
+        }
+        Ok(self
+            .clients
+            .account
+            .position_information(symbol.to_string())
+            .market_context("get position")?
+            .pop()
+            .ok_or(MarketError::NotFound("position".to_string()))?
+            .position_amount)
+    }
+    /// 按持仓方向下只减仓市价单
+    fn reduce(&self, symbol: &str, position_amount: f64, qty: f64) -> Result<f64, MarketError> {
+        if position_amount == 0. {
+            return Err(MarketError::NotFound("position".to_string()));
+        }
+        self.update_symbol_status(symbol, false)?;
+        let qty = self
+            .filters
+            .round_qty(symbol, qty.min(position_amount.abs()))?;
+        let mut order = if position_amount > 0. {
+            OrderRequest::market_sell(symbol, qty)
+        } else {
+            OrderRequest::market_buy(symbol, qty)
+        };
+        order.reduce_only = Some(true);
+        self.clients
+            .account
+            .custom_order(order)
+            .market_context("reduce position")?;
+        Ok(qty)
+    }
+}
+
+/// 在刷新线程中更新BinanceMarket共享的状态
+struct ExchangeInfoRefresher {
+    general: binance::futures::general::FuturesGeneral,
+    account: binance::futures::account::FuturesAccount,
+    statuses: Arc<DashMap<String, BinanceSymbolStatus>>,
+    filter: Arc<SymbolFilter>,
+    filters: Arc<SymbolFilters>,
+    listings: Arc<Listings>,
+}
+
+impl ExchangeInfoRefresher {
+    fn refresh(&self) -> Result<Vec<ListingEvent>, MarketError> {
+        let symbols = self
+            .general
+            .exchange_info()
+            .market_context("get ex info")?
+            .symbols;
+        let quote_asset = self.filter.quote_asset();
+        let events = self.listings.update(
+            symbols
+                .iter()
+                .filter(|s| s.margin_asset == quote_asset)
+                .map(|s| ListingStatus {
+                    symbol: s.symbol.clone(),
+                    status: s.status.clone(),
+                    delivery_date: s.delivery_date,
+                }),
+        );
+        for symbol_info in symbols {
+            if let Some(mut status) = self.statuses.get_mut(&symbol_info.symbol) {
+                status.update_market_info(symbol_info, &self.filters);
+            }
+        }
+        for e in events.iter() {
+            match e {
+                ListingEvent::Listed(symbol) => self.filter.set_delisted(symbol, false),
+                ListingEvent::Delisting { symbol, .. } => {
+                    self.filter.set_delisted(symbol, true);
+                    self.statuses.remove(symbol);
+                }
+            }
+        }
+        for brackets in self
+            .account
+            .leverage_brackets(None)
+            .market_context("get leverage bracket")?
+        {
+            if let Some(mut status) = self.statuses.get_mut(&brackets.symbol) {
+                if !brackets.brackets.is_empty() {
+                    status.brackets = brackets.brackets;
+                }
+            }
+        }
+        info!("Exchange info refreshed, {} symbols", self.statuses.len());
+        Ok(events)
+    }
+}
+
 impl Market for BinanceMarket {
-    fn clear_orders(&self, symbol: &str) -> anyhow::Result<()> {
+    fn clear_orders(&self, symbol: &str) -> Result<(), MarketError> {
         self.clients
             .account
             .cancel_all_open_orders(symbol)
-            .map_err(|e| anyhow!("cancel all open orders failed: {:?}", e.0))?;
+            .market_context("cancel all open orders")?;
         Ok(())
     }
 
-    fn close_position(&self, symbol: &str) -> anyhow::Result<()> {
+    fn close_position(&self, symbol: &str) -> Result<(), MarketError> {
         self.clear_orders(symbol)?;
-        let position = self
-            .clients
-            .account
-            .position_information(symbol.to_string())
-            .map_err(|e| anyhow!("get position failed: {:?}", e.0))?
-            .pop()
-            .ok_or(anyhow!("position not found"))?;
-        if position.position_amount == 0. {
+        let position_amount = self.position_amount(symbol)?;
+        if position_amount == 0. {
             return Ok(());
         }
-        if position.position_amount > 0. {
+        if position_amount > 0. {
             self.clients
                 .account
-                .market_sell(symbol, position.position_amount)
-                .map_err(|e| anyhow!("market sell failed: {:?}", e.0))?;
+                .market_sell(symbol, position_amount)
+                .market_context("market sell")?;
         } else {
             self.clients
                 .account
-                .market_buy(symbol, -position.position_amount)
-                .map_err(|e| anyhow!("market buy failed: {:?}", e.0))?;
+                .market_buy(symbol, -position_amount)
+                .market_context("market buy")?;
         }
         Ok(())
     }
 
-    fn order(&self, request: MarketOrderRequest) -> anyhow::Result<MarketOrderReturn> {
+    fn order(&self, mut request: MarketOrderRequest) -> Result<MarketOrderReturn, MarketError> {
+        self.clients.clock.check()?;
         let symbol = request.symbol.clone();
-        let position_risk = self
-            .clients
-            .account
-            .position_information(symbol.clone())
-            .map_err(|e| anyhow!("get position risk failed: {:?}", e.0))?
-            .pop()
-            .ok_or(anyhow!("position risk not found"))?;
-        if position_risk.position_amount != 0. {
-            bail!("position not empty");
+        if !request.liquidity_checked {
+            request.value = self.check_liquidity(&symbol, request.value)?;
+        }
+        if self.position_amount(&symbol)? != 0. {
+            return Err(MarketError::Rejected("position not empty".to_string()));
         }
         self.clear_orders(&symbol)?;
         self.update_symbol_status(&symbol, false)?;
         let status = self
             .statuses
             .get(&symbol)
-            .ok_or(anyhow!("status not found"))?;
+            .ok_or(MarketError::NotFound("status".to_string()))?;
         let price = self
             .clients
             .market
             .get_price(&symbol)
-            .map_err(|e| anyhow!("get price failed: {:?}", e.0))?
+            .market_context("get price")?
             .price;
-        let qty = truncate_step(request.value / price, status.min_qty_step);
-        let executed_value = qty * price;
-        if qty < status.min_qty {
-            bail!("qty too small");
-        }
-        if executed_value < status.min_notional {
-            bail!("min notional not satisfied");
-        }
-        let low_price = truncate_step(price * request.low_limit, status.tick_size);
-        let high_price = truncate_step(price * request.high_limit, status.tick_size);
-        let bracket = status
+        let RoundedOrder {
+            qty,
+            notional: executed_value,
+            low_price,
+            high_price,
+            stop_price,
+            stop_limit,
+            entry_price,
+        } = self.filters.validate_and_round(&request, price)?;
+        let (max_leverage, maint_margin_ratio, cum) = status
             .brackets
             .iter()
             .find(|b| executed_value >= b.notional_floor && executed_value <= b.notional_cap)
-            .ok_or(anyhow!("bracket not found"))?;
-        if self.leverage > bracket.initial_leverage {
-            bail!("leverage/value too high");
+            .map(|b| (b.initial_leverage, b.maint_margin_ratio, b.cum))
+            .ok_or(MarketError::NotFound("bracket".to_string()))?;
+        let current_leverage = status.leverage;
+        drop(status);
+        let leverage = target_leverage(
+            request.leverage.unwrap_or(self.leverage),
+            max_leverage,
+            self.auto_leverage,
+        )?;
+        if leverage != current_leverage {
+            self.clients
+                .account
+                .change_initial_leverage(&symbol, leverage)
+                .market_context(&format!("symbol {} change leverage", symbol))?;
+            info!(
+                "Symbol {} leverage {} -> {} for notional {:.2}",
+                symbol, current_leverage, leverage, executed_value
+            );
+            if let Some(mut status) = self.statuses.get_mut(&symbol) {
+                status.leverage = leverage;
+            }
+            if let Some(account) = &self.account {
+                account.set_leverage(&symbol, leverage);
+            }
         }
         let mut orders = Vec::new();
         if request.is_buy {
-            orders.push(OrderRequest::market_buy(&symbol, qty));
-            let mut order = OrderRequest::limit_sell(&symbol, qty, high_price, TimeInForce::GTC);
-            order.reduce_only = Some(true);
-            orders.push(order);
-            orders.push(OrderRequest::stop_market_close_sell(&symbol, low_price));
+            orders.push(match (entry_price, request.time_in_force) {
+                (Some(limit), Some(tif)) => {
+                    OrderRequest::limit_buy(&symbol, qty, limit, tif.into())
+                }
+                _ => OrderRequest::market_buy(&symbol, qty),
+            });
+            orders.push(take_profit_order(&symbol, true, qty, high_price, request.time_in_force));
+            let mut stop = OrderRequest::stop_market_close_sell(&symbol, low_price);
+            if let Some(limit) = stop_limit {
+                stop_limit_order(&mut stop, qty, limit);
+            }
+            orders.push(stop);
         } else {
-            orders.push(OrderRequest::market_sell(&symbol, qty));
-            let mut order = OrderRequest::limit_buy(&symbol, qty, low_price, TimeInForce::GTC);
-            order.reduce_only = Some(true);
-            orders.push(order);
-            orders.push(OrderRequest::stop_market_close_buy(&symbol, high_price));
+            orders.push(match (entry_price, request.time_in_force) {
+                (Some(limit), Some(tif)) => {
+                    OrderRequest::limit_sell(&symbol, qty, limit, tif.into())
+                }
+                _ => OrderRequest::market_sell(&symbol, qty),
+            });
+            orders.push(take_profit_order(&symbol, false, qty, low_price, request.time_in_force));
+            let mut stop = OrderRequest::stop_market_close_buy(&symbol, high_price);
+            if let Some(limit) = stop_limit {
+                stop_limit_order(&mut stop, qty, limit);
+            }
+            orders.push(stop);
+        }
+        if let Some((strategy, request_id)) = request.tag {
+            for (leg, order) in orders.iter_mut().enumerate() {
+                let id = ClientOrderId {
+                    strategy,
+                    request_id,
+                    leg: request.leg_base + leg,
+                };
+                order.new_client_order_id = Some(id.to_string());
+            }
         }
         let transactions = self
             .clients
             .account
             .custom_batch_orders(orders)
-            .map_err(|e| anyhow!("batch order failed: {:?}", e.0))?;
-        let maintenance_margin = executed_value * bracket.maint_margin_ratio - bracket.cum;
-        let default_margin = executed_value / self.leverage as f64;
-        let target_margin = if request.is_buy {
-            qty * (price - low_price) + maintenance_margin
+            .market_context("batch order")?;
+        let (order_id, avg_price) = match transactions.first().unwrap() {
+            TransactionOrError::Transaction(t) => (t.order_id, t.avg_price),
+            TransactionOrError::Error(e) => {
+                return Err(MarketError::Binance {
+                    context: "order".to_string(),
+                    code: e.code.into(),
+                    msg: e.msg.clone(),
+                })
+            }
+        };
+        // 挂单开仓下单时尚无仓位，不能追加逐仓保证金
+        let resting = request.time_in_force.is_some_and(|tif| tif.rests());
+        let margin_buffer = if resting {
+            0.
         } else {
-            qty * (high_price - price) + maintenance_margin
+            self.margin_buffer.additional(&MarginInputs {
+                is_buy: request.is_buy,
+                qty,
+                price,
+                // 止损限价单按限价成交，保证金需覆盖到限价
+                worst_price: stop_limit.unwrap_or(stop_price),
+                notional: executed_value,
+                leverage,
+                maint_margin_ratio,
+                cum,
+            })
         };
-        let additional_margin = target_margin - default_margin;
-        if additional_margin > 0. {
-            let m = additional_margin + 0.01;
-            self.clients
+        // 开仓已成交，追加保证金失败只记录，止盈止损照常生效
+        let mut margin_error = None;
+        if margin_buffer > 0. {
+            if let Err(e) = self
+                .clients
                 .account
-                .change_position_margin(&symbol, m, true)
-                .map_err(|e| anyhow!("add position margin failed: {:?}", e.0))?;
+                .change_position_margin(&symbol, margin_buffer, true)
+                .market_context("add position margin")
+            {
+                error!("Symbol {} add margin {} failed: {}", symbol, margin_buffer, e);
+                margin_error = Some(e);
+            }
         }
-        let order_id = match transactions.first().unwrap() {
-            TransactionOrError::Transaction(t) => t.order_id,
-            TransactionOrError::Error(e) => bail!("order failed: {:?}", e),
+        // 开仓已成交，附属单失败只记录，由调用方处理
+        let child_id = |leg: usize| match transactions.get(leg) {
+            Some(TransactionOrError::Transaction(t)) => Some(t.order_id),
+            Some(TransactionOrError::Error(e)) => {
+                error!("Symbol {} order leg {} failed: {}", symbol, leg, e.msg);
+                None
+            }
+            None => None,
         };
+        let fill_price = (avg_price > 0.).then_some(avg_price);
+        // 只做maker的开仓不会与盘口交叉，按maker费率估算
+        let fill_type = match request.time_in_force {
+            Some(tif) if !tif.takes_on_cross() => FillType::Maker,
+            _ => FillType::Taker,
+        };
+        let failed_legs = [1, 2]
+            .into_iter()
+            .filter(|leg| {
+                !matches!(
+                    transactions.get(*leg),
+                    Some(TransactionOrError::Transaction(_))
+                )
+            })
+            .collect();
         Ok(MarketOrderReturn {
             order_id,
             qty,
             value: executed_value,
+            price,
+            fill_price,
+            fee: self.fees.fee(qty * fill_price.unwrap_or(price), fill_type),
+            take_profit_id: child_id(1),
+            take_profit_price: if request.is_buy {
+                high_price
+            } else {
+                low_price
+            },
+            stop_loss_id: child_id(2),
+            stop_price,
+            stop_limit,
+            margin_buffer: if margin_error.is_some() { 0. } else { margin_buffer },
+            margin_error,
+            failed_legs,
         })
     }
+
+    fn reduce_position(&self, symbol: &str, qty: f64) -> Result<f64, MarketError> {
+        let position_amount = self.position_amount(symbol)?;
+        self.reduce(symbol, position_amount, qty)
+    }
+
+    fn scale_out(&self, symbol: &str, fractions: &[f64]) -> Result<Vec<f64>, MarketError> {
+        if fractions.iter().any(|f| *f <= 0.) || fractions.iter().sum::<f64>() > 1. {
+            return Err(MarketError::Rejected(
+                "fractions must be positive and sum to at most 1".to_string(),
+            ));
+        }
+        let mut position_amount = self.position_amount(symbol)?;
+        let total = position_amount.abs();
+        let mut reduced = Vec::with_capacity(fractions.len());
+        for fraction in fractions {
+            let qty = self.reduce(symbol, position_amount, total * fraction)?;
+            position_amount -= qty * position_amount.signum();
+            reduced.push(qty);
+        }
+        Ok(reduced)
+    }
+
+    /// 拆分前按成交额限制组合单，缩小时各腿等比例缩小
+    fn order_spread(
+        &self,
+        mut request: SpreadOrderRequest,
+    ) -> Result<SpreadOrderReturn, MarketError> {
+        request.limit_value(|symbol, value| self.check_liquidity(symbol, value))?;
+        spread::execute(self, request)
+    }
+
+    fn add_margin(&self, symbol: &str, amount: f64) -> Result<(), MarketError> {
+        self.clients
+            .account
+            .change_position_margin(symbol, amount, true)
+            .market_context("add position margin")
+    }
+
+    fn fees(&self) -> FeeSchedule {
+        self.fees
+    }
+
+    /// 币种的维持保证金阶梯，用于计算强平价格
+    fn margin_brackets(&self, symbol: &str) -> Option<MarginBrackets> {
+        let status = self.statuses.get(symbol)?;
+        if status.brackets.is_empty() {
+            return None;
+        }
+        Some(status.brackets.as_slice().into())
+    }
+}
+
+/// 把止损市价单改为触发后以limit挂出的只减仓限价单
+/// is_buy为开仓方向的只减仓止盈限价单，开仓只做maker时止盈同样为GTX；
+/// 开仓挂单未成交前没有仓位，只减仓单会被拒绝，此时止盈改为在止盈价触发的限价单
+fn take_profit_order(
+    symbol: &str,
+    is_buy: bool,
+    qty: f64,
+    price: f64,
+    entry: Option<super::TimeInForce>,
+) -> OrderRequest {
+    let tif = match entry {
+        Some(super::TimeInForce::Gtx) => TimeInForce::GTX,
+        _ => TimeInForce::GTC,
+    };
+    let mut order = if is_buy {
+        OrderRequest::limit_sell(symbol, qty, price, tif)
+    } else {
+        OrderRequest::limit_buy(symbol, qty, price, tif)
+    };
+    order.reduce_only = Some(true);
+    if entry.is_some_and(|tif| tif.rests()) {
+        order.order_type = OrderType::TakeProfit;
+        order.stop_price = Some(price);
+    }
+    order
+}
+
+fn stop_limit_order(order: &mut OrderRequest, qty: f64, limit: f64) {
+    order.order_type = OrderType::Stop;
+    order.close_position = None;
+    order.quantity = Some(qty);
+    order.price = Some(limit);
+    order.reduce_only = Some(true);
+    order.time_in_force = Some(TimeInForce::GTC);
+}
+
+/// 订单使用的杠杆：不超过档位允许的最大杠杆，不允许自动降低时拒单
+fn target_leverage(leverage: u8, bracket_max: u8, auto: bool) -> Result<u8, MarketError> {
+    if leverage <= bracket_max {
+        Ok(leverage)
+    } else if auto {
+        Ok(bracket_max)
+    } else {
+        Err(MarketError::Rejected("leverage/value too high".to_string()))
+    }
+}
+
+/// 下单按单向持仓处理，已是单向持仓（-4059）以外的错误都中止启动
+fn ensure_one_way(result: Result<(), MarketError>) -> Result<(), MarketError> {
+    match result {
+        Err(e) if e.code() != Some(BinanceErrorCode::NoNeedToChangePositionSide) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// 账户返回的仓位杠杆，无法解析时不重试
+fn parse_leverage(symbol: &str, leverage: &str) -> Result<u8, MarketError> {
+    leverage.parse().map_err(|e| MarketError::Parse {
+        context: format!("leverage of {}", symbol),
+        msg: format!("{}: {}", leverage, e),
+    })
+}
+
+#[test]
+fn target_leverage_test() {
+    assert_eq!(target_leverage(20, 25, false).unwrap(), 20);
+    assert!(matches!(
+        target_leverage(20, 10, false),
+        Err(MarketError::Rejected(_))
+    ));
+    assert_eq!(target_leverage(20, 10, true).unwrap(), 10);
+    assert_eq!(target_leverage(20, 25, true).unwrap(), 20);
+    assert_eq!(parse_leverage("BTCUSDT", "20").unwrap(), 20);
+    let e = parse_leverage("BTCUSDT", "abc").unwrap_err();
+    assert!(matches!(e, MarketError::Parse { .. }));
+    assert!(!e.is_retryable());
+}
+
+#[test]
+fn ensure_one_way_test() {
+    let binance = |code: i16| MarketError::Binance {
+        context: "change position mode".to_string(),
+        code: code.into(),
+        msg: String::new(),
+    };
+    assert!(ensure_one_way(Ok(())).is_ok());
+    assert!(ensure_one_way(Err(binance(-4059))).is_ok());
+    // 有持仓或挂单时无法切换，不能带着双向持仓启动
+    assert!(ensure_one_way(Err(binance(-4068))).is_err());
+    assert!(ensure_one_way(Err(MarketError::Request {
+        context: "change position mode".to_string(),
+        msg: "timeout".to_string(),
+    }))
+    .is_err());
 }
 
 #[test]
@@ -0,0 +1,86 @@
+use serde::Deserialize;
+
+use super::{liquidity::LiquidityLimit, shadow::ShadowConfig};
+use crate::{error::ConfigError, fee::FeeConfig};
+
+/// 实盘市场的配置，见BinanceMarket::from_config
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MarketConfig {
+    /// 默认的逐仓杠杆
+    pub leverage: u8,
+    /// 名义价值超过当前档位允许的杠杆时自动降低杠杆，否则拒单
+    pub auto_leverage: bool,
+    /// 按24h成交额限制开仓金额，不设置为不限制
+    pub liquidity: Option<LiquidityLimit>,
+    /// 设置后启用影子交易，订单镜像到按实时行情成交的模拟市场，见shadow::ShadowMarket
+    pub shadow: Option<ShadowConfig>,
+    /// 账户的手续费等级，用于估算平仓手续费和影子交易的模拟成交
+    pub fee: FeeConfig,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        Self {
+            leverage: 10,
+            auto_leverage: false,
+            liquidity: None,
+            shadow: None,
+            fee: FeeConfig::default(),
+        }
+    }
+}
+
+impl MarketConfig {
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
+        let c = std::fs::read_to_string(path)?;
+        let val: Self = toml::from_str(&c)?;
+        if val.leverage == 0 {
+            return Err(ConfigError::Invalid(
+                "leverage must be positive".to_string(),
+            ));
+        }
+        val.fee.validate()?;
+        if let Some(limit) = &val.liquidity {
+            if limit.max_fraction <= 0. {
+                return Err(ConfigError::Invalid(format!(
+                    "liquidity max_fraction {}",
+                    limit.max_fraction
+                )));
+            }
+        }
+        Ok(val)
+    }
+}
+
+#[test]
+fn market_config_test() {
+    let config: MarketConfig = toml::from_str(
+        r#"
+        leverage = 5
+        [liquidity]
+        max_fraction = 0.001
+        downsize = true
+        [shadow]
+        capacity = 500
+        [fee]
+        vip = 2
+        bnb_discount = true
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.leverage, 5);
+    assert!(config.liquidity.unwrap().downsize);
+    assert_eq!(config.shadow.unwrap().capacity, 500);
+    assert_eq!(
+        config.fee,
+        FeeConfig {
+            vip: 2,
+            bnb_discount: true
+        }
+    );
+    assert_eq!(
+        toml::from_str::<MarketConfig>("").unwrap(),
+        MarketConfig::default()
+    );
+}
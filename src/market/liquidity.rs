@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::error::MarketError;
+
+/// 开仓名义价值相对24h成交额的上限，避免在流动性差的币种上开出过大的仓位
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct LiquidityLimit {
+    /// 名义价值不超过24h成交额的该比例
+    pub max_fraction: f64,
+    /// 超过时按上限缩小下单金额，否则拒单
+    #[serde(default)]
+    pub downsize: bool,
+    /// 成交额缓存的有效期（秒）
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+}
+
+fn default_max_age() -> u64 {
+    300
+}
+
+impl LiquidityLimit {
+    /// 返回允许的下单金额
+    pub fn apply(&self, symbol: &str, value: f64, quote_volume: f64) -> Result<f64, MarketError> {
+        let cap = quote_volume * self.max_fraction;
+        if value <= cap {
+            return Ok(value);
+        }
+        if self.downsize && cap > 0. {
+            info!(
+                "Order {} downsized from {:.2} to {:.2} by 24h quote volume {:.0}",
+                symbol, value, cap, quote_volume
+            );
+            return Ok(cap);
+        }
+        Err(MarketError::Rejected(format!(
+            "notional {:.2} exceeds {} of 24h quote volume {:.0}",
+            value, self.max_fraction, quote_volume
+        )))
+    }
+}
+
+/// 按币种缓存24h成交额并检查下单金额
+#[derive(Debug)]
+pub struct LiquidityGuard {
+    limit: LiquidityLimit,
+    /// 币种 -> （获取时间，24h成交额）
+    volumes: DashMap<String, (Instant, f64)>,
+}
+
+impl LiquidityGuard {
+    pub fn new(limit: LiquidityLimit) -> Self {
+        Self {
+            limit,
+            volumes: DashMap::new(),
+        }
+    }
+    /// 缓存过期时通过fetch（如REST ticker/24hr）获取成交额，返回允许的下单金额
+    pub fn check(
+        &self,
+        symbol: &str,
+        value: f64,
+        fetch: impl FnOnce() -> Result<f64, MarketError>,
+    ) -> Result<f64, MarketError> {
+        let max_age = Duration::from_secs(self.limit.max_age);
+        let cached = self
+            .volumes
+            .get(symbol)
+            .filter(|v| v.0.elapsed() < max_age)
+            .map(|v| v.1);
+        let volume = match cached {
+            Some(volume) => volume,
+            None => {
+                let volume = fetch()?;
+                self.volumes
+                    .insert(symbol.to_string(), (Instant::now(), volume));
+                volume
+            }
+        };
+        self.limit.apply(symbol, value, volume)
+    }
+}
+
+#[test]
+fn liquidity_guard_test() {
+    let limit: LiquidityLimit = toml::from_str("max_fraction = 0.001").unwrap();
+    assert_eq!(limit.max_age, 300);
+    let guard = LiquidityGuard::new(limit);
+    assert_eq!(guard.check("BTCUSDT", 500., || Ok(1e6)).unwrap(), 500.);
+    // 使用缓存的成交额
+    assert!(matches!(
+        guard.check("BTCUSDT", 2000., || unreachable!()),
+        Err(MarketError::Rejected(_))
+    ));
+    let downsize = LiquidityGuard::new(LiquidityLimit {
+        downsize: true,
+        ..limit
+    });
+    assert_eq!(downsize.check("ALTUSDT", 2000., || Ok(1e6)).unwrap(), 1000.);
+    assert!(downsize
+        .check("NEWUSDT", 100., || Err(MarketError::NotFound(
+            "stats".into()
+        )))
+        .is_err());
+}
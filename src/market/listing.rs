@@ -0,0 +1,173 @@
+use std::{collections::HashMap, time::Duration};
+
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+/// 交易所信息的默认刷新间隔
+pub const EXCHANGE_INFO_REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// 交易所信息中可交易的状态
+const TRADING: &str = "TRADING";
+
+/// 永续合约的交割时间（2100-12-25），设为其他时间表示将要交割下架
+const PERPETUAL_DELIVERY_DATE: u64 = 4133404800000;
+
+/// 币种数少于上次的该比例时视为异常的交易所信息，忽略本次更新
+const MIN_SNAPSHOT_RATIO: f64 = 0.5;
+
+/// 刷新交易所信息时发现的上下架变化
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ListingEvent {
+    /// 新上线或恢复交易
+    Listed(String),
+    /// 进入下架流程（状态不再是TRADING，如SETTLING、CLOSE；永续合约设定了交割时间，状态为DELIVERY）
+    /// 或已从交易所信息中移除，持仓应尽快平掉
+    Delisting { symbol: String, status: String },
+}
+
+/// 交易所信息中的币种
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingStatus {
+    pub symbol: String,
+    pub status: String,
+    /// 交割时间（unix毫秒）
+    pub delivery_date: u64,
+}
+
+/// 各币种最近一次的交易状态
+#[derive(Debug, Default)]
+pub struct Listings {
+    /// 币种 -> （状态，交割时间）
+    statuses: Mutex<HashMap<String, (String, u64)>>,
+}
+
+impl Listings {
+    /// 用最新的交易所信息更新，返回变化；首次更新只记录状态；
+    /// 为空或币种数骤减的交易所信息视为异常，忽略
+    pub fn update(&self, latest: impl IntoIterator<Item = ListingStatus>) -> Vec<ListingEvent> {
+        let latest: HashMap<String, (String, u64)> = latest
+            .into_iter()
+            .map(|s| (s.symbol, (s.status, s.delivery_date)))
+            .collect();
+        let mut statuses = self.statuses.lock();
+        let mut events = vec![];
+        if (latest.len() as f64) < statuses.len() as f64 * MIN_SNAPSHOT_RATIO || latest.is_empty() {
+            warn!(
+                "Exchange info has {} symbols, {} before, ignored",
+                latest.len(),
+                statuses.len()
+            );
+            return events;
+        }
+        if statuses.is_empty() {
+            *statuses = latest;
+            return events;
+        }
+        for (symbol, (status, delivery_date)) in latest.iter() {
+            let previous = statuses.get(symbol);
+            let was_trading = previous.is_some_and(|(s, _)| s == TRADING);
+            match (was_trading, status == TRADING) {
+                (true, false) => events.push(ListingEvent::Delisting {
+                    symbol: symbol.clone(),
+                    status: status.clone(),
+                }),
+                (false, true) => events.push(ListingEvent::Listed(symbol.clone())),
+                (true, true)
+                    if previous.is_some_and(|(_, d)| *d >= PERPETUAL_DELIVERY_DATE)
+                        && *delivery_date < PERPETUAL_DELIVERY_DATE =>
+                {
+                    events.push(ListingEvent::Delisting {
+                        symbol: symbol.clone(),
+                        status: "DELIVERY".to_string(),
+                    })
+                }
+                _ => {}
+            }
+        }
+        for (symbol, (status, _)) in statuses.iter() {
+            if status == TRADING && !latest.contains_key(symbol) {
+                events.push(ListingEvent::Delisting {
+                    symbol: symbol.clone(),
+                    status: "REMOVED".to_string(),
+                });
+            }
+        }
+        *statuses = latest;
+        events.sort();
+        for e in events.iter() {
+            match e {
+                ListingEvent::Listed(symbol) => info!("Symbol {} listed", symbol),
+                ListingEvent::Delisting { symbol, status } => {
+                    warn!("Symbol {} delisting, status {}", symbol, status)
+                }
+            }
+        }
+        events
+    }
+    pub fn is_trading(&self, symbol: &str) -> bool {
+        self.statuses
+            .lock()
+            .get(symbol)
+            .is_some_and(|(s, _)| s == TRADING)
+    }
+}
+
+#[test]
+fn listings_test() {
+    let entry = |symbol: &str, status: &str| ListingStatus {
+        symbol: symbol.to_string(),
+        status: status.to_string(),
+        delivery_date: PERPETUAL_DELIVERY_DATE,
+    };
+    let listings = Listings::default();
+    let events = listings.update([
+        entry("BTCUSDT", "TRADING"),
+        entry("ETHUSDT", "TRADING"),
+        entry("LUNAUSDT", "SETTLING"),
+        entry("XUSDT", "PENDING_TRADING"),
+    ]);
+    assert!(events.is_empty());
+    assert!(listings.is_trading("BTCUSDT"));
+    assert!(!listings.is_trading("XUSDT"));
+    let events = listings.update([
+        entry("BTCUSDT", "SETTLING"),
+        entry("LUNAUSDT", "SETTLING"),
+        entry("XUSDT", "TRADING"),
+        entry("YUSDT", "TRADING"),
+    ]);
+    assert_eq!(
+        events,
+        [
+            ListingEvent::Listed("XUSDT".to_string()),
+            ListingEvent::Listed("YUSDT".to_string()),
+            ListingEvent::Delisting {
+                symbol: "BTCUSDT".to_string(),
+                status: "SETTLING".to_string()
+            },
+            ListingEvent::Delisting {
+                symbol: "ETHUSDT".to_string(),
+                status: "REMOVED".to_string()
+            },
+        ]
+    );
+    assert!(listings
+        .update([entry("XUSDT", "TRADING"), entry("YUSDT", "TRADING")])
+        .is_empty());
+    // 异常的交易所信息不视为全部下架，也不重置为首次更新
+    assert!(listings.update([]).is_empty());
+    assert!(listings.is_trading("XUSDT"));
+    let delivering = ListingStatus {
+        delivery_date: 1767225600000,
+        ..entry("YUSDT", "TRADING")
+    };
+    assert_eq!(
+        listings.update([entry("XUSDT", "TRADING"), delivering.clone()]),
+        [ListingEvent::Delisting {
+            symbol: "YUSDT".to_string(),
+            status: "DELIVERY".to_string()
+        }]
+    );
+    assert!(listings
+        .update([entry("XUSDT", "TRADING"), delivering])
+        .is_empty());
+}
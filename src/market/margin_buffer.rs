@@ -0,0 +1,107 @@
+use serde::Deserialize;
+
+/// 逐仓开仓后按该规则追加保证金，使止损前不被强平
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarginBuffer {
+    /// 保证金覆盖到止损价（止损限价单为限价）的亏损和维持保证金，不足时追加差额再加extra（结算资产）
+    StopDistance { extra: f64 },
+    /// 保证金不低于名义价值的ratio
+    Percent { ratio: f64 },
+    /// 保证金不低于档位的维持保证金加名义价值的ratio，不考虑止损距离
+    Bracket { ratio: f64 },
+}
+
+impl Default for MarginBuffer {
+    fn default() -> Self {
+        Self::StopDistance { extra: 0.01 }
+    }
+}
+
+/// 计算追加保证金所需的开仓信息
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarginInputs {
+    pub is_buy: bool,
+    pub qty: f64,
+    /// 开仓价格
+    pub price: f64,
+    /// 止损的最差成交价
+    pub worst_price: f64,
+    /// 名义价值
+    pub notional: f64,
+    pub leverage: u8,
+    /// 档位的维持保证金率和速算数
+    pub maint_margin_ratio: f64,
+    pub cum: f64,
+}
+
+impl MarginInputs {
+    /// 开仓时按杠杆占用的保证金
+    pub fn initial_margin(&self) -> f64 {
+        self.notional / self.leverage as f64
+    }
+    pub fn maintenance_margin(&self) -> f64 {
+        self.notional * self.maint_margin_ratio - self.cum
+    }
+    /// 价格到达止损时的亏损
+    pub fn stop_loss(&self) -> f64 {
+        if self.is_buy {
+            self.qty * (self.price - self.worst_price)
+        } else {
+            self.qty * (self.worst_price - self.price)
+        }
+    }
+}
+
+impl MarginBuffer {
+    /// 需追加的保证金，不需要时为0
+    pub fn additional(&self, m: &MarginInputs) -> f64 {
+        let (target, extra) = match *self {
+            Self::StopDistance { extra } => (m.stop_loss() + m.maintenance_margin(), extra),
+            Self::Percent { ratio } => (m.notional * ratio, 0.),
+            Self::Bracket { ratio } => (m.maintenance_margin() + m.notional * ratio, 0.),
+        };
+        let additional = target - m.initial_margin();
+        if additional > 0. {
+            additional + extra
+        } else {
+            0.
+        }
+    }
+}
+
+#[test]
+fn margin_buffer_test() {
+    let inputs = MarginInputs {
+        is_buy: true,
+        qty: 1.,
+        price: 1000.,
+        worst_price: 900.,
+        notional: 1000.,
+        leverage: 20,
+        maint_margin_ratio: 0.004,
+        cum: 0.,
+    };
+    assert_eq!(inputs.initial_margin(), 50.);
+    assert_eq!(inputs.maintenance_margin(), 4.);
+    // 止损亏损100加维持保证金4，开仓保证金50
+    let stop = MarginBuffer::default().additional(&inputs);
+    assert!((stop - 54.01).abs() < 1e-9);
+    let short = MarginInputs {
+        is_buy: false,
+        worst_price: 1030.,
+        ..inputs
+    };
+    assert_eq!(MarginBuffer::default().additional(&short), 0.);
+    assert_eq!(
+        MarginBuffer::Percent { ratio: 0.1 }.additional(&inputs),
+        50.
+    );
+    assert_eq!(
+        MarginBuffer::Percent { ratio: 0.02 }.additional(&inputs),
+        0.
+    );
+    assert!((MarginBuffer::Bracket { ratio: 0.05 }.additional(&inputs) - 4.).abs() < 1e-9);
+    let config: MarginBuffer = toml::from_str("percent = { ratio = 0.2 }").unwrap();
+    assert_eq!(config, MarginBuffer::Percent { ratio: 0.2 });
+}
@@ -0,0 +1,550 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use binance::{
+    futures::model::OrderUpdate,
+    model::{AccountUpdateDataEvent, EventBalance, EventPosition},
+};
+use crossbeam::channel::Sender;
+use parking_lot::Mutex;
+
+use super::{
+    symbol_filters::SymbolFilters, ClientOrderId, Market, MarketOrderRequest, MarketOrderReturn,
+};
+use crate::{
+    binance_futures::SymbolPrices,
+    controller::AccountInfo,
+    error::{BinanceErrorCode, MarketError},
+    fee::{FeeSchedule, FillType},
+    symbol::SymbolId,
+    utils::unix_millis,
+};
+
+/// 脚本化的交易所应答，按调用顺序逐个消耗，脚本为空时全部成交
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockResponse {
+    Fill,
+    /// 按比例部分成交
+    PartialFill(f64),
+    /// 开仓全部成交，但止损单被交易所拒绝
+    StopRejected,
+    /// 本地校验不通过
+    Reject(String),
+    /// 交易所返回的错误码
+    Binance(i16, String),
+    /// 网络错误
+    Request(String),
+}
+
+/// 记录的调用，用于断言Controller的行为
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockCall {
+    ClearOrders(String),
+    ClosePosition(String),
+    Order {
+        symbol: String,
+        is_buy: bool,
+        value: f64,
+        client_order_id: Option<String>,
+        leverage: Option<u8>,
+    },
+    ReducePosition(String, f64),
+    ScaleOut(String, Vec<f64>),
+    AddMargin(String, f64),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct MockPosition {
+    amount: f64,
+    entry_price: f64,
+    isolated_wallet: f64,
+}
+
+/// 用于测试的模拟交易所：按脚本应答，维护持仓，并可推送模拟的用户数据事件
+#[derive(Debug)]
+pub struct MockMarket {
+    script: Mutex<VecDeque<MockResponse>>,
+    /// 每次调用的模拟延迟
+    latency: Duration,
+    fees: FeeSchedule,
+    /// 开仓时的逐仓杠杆
+    leverage: f64,
+    prices: Mutex<HashMap<String, f64>>,
+    positions: Mutex<HashMap<String, MockPosition>>,
+    balance: Mutex<f64>,
+    calls: Mutex<Vec<MockCall>>,
+    events: Option<Sender<AccountInfo>>,
+    /// 设置后按交易规则取整下单数量，不满足时拒单
+    filters: Option<Arc<SymbolFilters>>,
+    /// 未用set_price设置的币种按实时行情的标记价格成交，用作影子交易的模拟市场
+    feed: Option<SymbolPrices>,
+    next_order_id: AtomicU64,
+}
+
+impl Default for MockMarket {
+    fn default() -> Self {
+        Self {
+            script: Mutex::new(VecDeque::new()),
+            latency: Duration::ZERO,
+            fees: FeeSchedule::default(),
+            leverage: 10.,
+            prices: Mutex::new(HashMap::new()),
+            positions: Mutex::new(HashMap::new()),
+            balance: Mutex::new(10000.),
+            calls: Mutex::new(vec![]),
+            events: None,
+            filters: None,
+            feed: None,
+            next_order_id: AtomicU64::new(1),
+        }
+    }
+}
+
+impl MockMarket {
+    pub fn new(balance: f64) -> Self {
+        Self {
+            balance: Mutex::new(balance),
+            ..Default::default()
+        }
+    }
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+    /// 成交后推送ORDER_TRADE_UPDATE和ACCOUNT_UPDATE事件
+    pub fn with_events(mut self, events: Sender<AccountInfo>) -> Self {
+        self.events = Some(events);
+        self
+    }
+    /// 与实盘共用交易规则，如BinanceMarket::filters
+    pub fn with_filters(mut self, filters: Arc<SymbolFilters>) -> Self {
+        self.filters = Some(filters);
+        self
+    }
+    /// 模拟成交的手续费率，默认VIP0
+    pub fn with_fees(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+    pub fn with_price_feed(mut self, feed: SymbolPrices) -> Self {
+        self.feed = Some(feed);
+        self
+    }
+    pub fn push(&self, response: MockResponse) {
+        self.script.lock().push_back(response);
+    }
+    pub fn set_price(&self, symbol: &str, price: f64) {
+        self.prices.lock().insert(symbol.to_string(), price);
+    }
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().clone()
+    }
+    /// 带符号的持仓数量
+    pub fn position(&self, symbol: &str) -> f64 {
+        self.positions
+            .lock()
+            .get(symbol)
+            .map(|p| p.amount)
+            .unwrap_or_default()
+    }
+    pub fn balance(&self) -> f64 {
+        *self.balance.lock()
+    }
+    /// 模拟交易所发起的强平或ADL，按当前价格减仓qty（带符号），
+    /// client_order_id如autoclose-1或adl_autoclose
+    pub fn force_close(
+        &self,
+        symbol: &str,
+        qty: f64,
+        client_order_id: &str,
+    ) -> Result<f64, MarketError> {
+        self.fill(symbol, qty, client_order_id).map(|(pnl, _)| pnl)
+    }
+    /// 记录调用并取出下一个应答，返回成交比例
+    fn respond(&self, call: MockCall) -> Result<f64, MarketError> {
+        self.calls.lock().push(call);
+        if !self.latency.is_zero() {
+            std::thread::sleep(self.latency);
+        }
+        let response = self.script.lock().pop_front().unwrap_or(MockResponse::Fill);
+        match response {
+            MockResponse::Fill | MockResponse::StopRejected => Ok(1.),
+            MockResponse::PartialFill(ratio) => Ok(ratio.clamp(0., 1.)),
+            MockResponse::Reject(msg) => Err(MarketError::Rejected(msg)),
+            MockResponse::Binance(code, msg) => Err(MarketError::Binance {
+                context: "mock".into(),
+                code: BinanceErrorCode::from(code),
+                msg,
+            }),
+            MockResponse::Request(msg) => Err(MarketError::Request {
+                context: "mock".into(),
+                msg,
+            }),
+        }
+    }
+    fn price(&self, symbol: &str) -> Result<f64, MarketError> {
+        let price = self.prices.lock().get(symbol).copied().or_else(|| {
+            let feed = self.feed.as_ref()?;
+            feed.get(&SymbolId::get(symbol)?).map(|p| p.mark_price)
+        });
+        price.ok_or_else(|| MarketError::NotFound(format!("mock price of {}", symbol)))
+    }
+    /// 按当前价格成交qty（带符号），更新持仓和余额并推送事件，返回已实现盈亏和订单id
+    fn fill(
+        &self,
+        symbol: &str,
+        qty: f64,
+        client_order_id: &str,
+    ) -> Result<(f64, u64), MarketError> {
+        let price = self.price(symbol)?;
+        let order_id = self.next_order_id.fetch_add(1, Ordering::Relaxed);
+        let commission = self.fees.fee(qty.abs() * price, FillType::Taker);
+        let (pnl, position) = {
+            let mut positions = self.positions.lock();
+            let p = positions.entry(symbol.to_string()).or_default();
+            let mut pnl = 0.;
+            if p.amount * qty < 0. {
+                // 减仓部分按开仓均价结算
+                let closed = qty.abs().min(p.amount.abs());
+                let ratio = closed / p.amount.abs();
+                pnl = closed * (price - p.entry_price) * p.amount.signum();
+                p.isolated_wallet -= p.isolated_wallet * ratio;
+                p.amount += closed * qty.signum();
+                let opened = qty.abs() - closed;
+                if opened > 0. {
+                    p.amount = opened * qty.signum();
+                    p.entry_price = price;
+                    p.isolated_wallet = opened * price / self.leverage;
+                }
+            } else {
+                let amount = p.amount + qty;
+                p.entry_price = (p.amount * p.entry_price + qty * price) / amount;
+                p.amount = amount;
+                p.isolated_wallet += qty.abs() * price / self.leverage;
+            }
+            if p.amount.abs() < 1e-12 {
+                *p = MockPosition::default();
+            }
+            (pnl, *p)
+        };
+        let balance = {
+            let mut balance = self.balance.lock();
+            *balance += pnl - commission;
+            *balance
+        };
+        if let Some(events) = &self.events {
+            let time = unix_millis();
+            let fill = FakeFill {
+                symbol: symbol.to_string(),
+                client_order_id: client_order_id.to_string(),
+                order_id,
+                qty,
+                price,
+                realized_pnl: pnl,
+                commission,
+            };
+            events.send(fill.event(time)).ok();
+            let position = FakePosition {
+                symbol: symbol.to_string(),
+                amount: position.amount,
+                entry_price: position.entry_price,
+                isolated_wallet: position.isolated_wallet,
+            };
+            events.send(account_update(time, balance, &[position])).ok();
+        }
+        Ok((pnl, order_id))
+    }
+}
+
+impl Market for MockMarket {
+    fn clear_orders(&self, symbol: &str) -> Result<(), MarketError> {
+        self.respond(MockCall::ClearOrders(symbol.to_string()))?;
+        Ok(())
+    }
+    fn close_position(&self, symbol: &str) -> Result<(), MarketError> {
+        self.respond(MockCall::ClosePosition(symbol.to_string()))?;
+        let amount = self.position(symbol);
+        if amount != 0. {
+            self.fill(symbol, -amount, "mock_close")?;
+        }
+        Ok(())
+    }
+    fn order(&self, request: MarketOrderRequest) -> Result<MarketOrderReturn, MarketError> {
+        let client_order_id = request.tag.map(|(strategy, request_id)| {
+            ClientOrderId {
+                strategy,
+                request_id,
+                leg: request.leg_base,
+            }
+            .to_string()
+        });
+        let stop_rejected = matches!(self.script.lock().front(), Some(MockResponse::StopRejected));
+        let ratio = self.respond(MockCall::Order {
+            symbol: request.symbol.clone(),
+            is_buy: request.is_buy,
+            value: request.value,
+            client_order_id: client_order_id.clone(),
+            leverage: request.leverage,
+        })?;
+        let price = self.price(&request.symbol)?;
+        let qty = match &self.filters {
+            Some(filters) => filters.validate_and_round(&request, price)?.qty * ratio,
+            None => request.value * ratio / price,
+        };
+        let side = if request.is_buy { 1. } else { -1. };
+        let (_, order_id) = self.fill(
+            &request.symbol,
+            side * qty,
+            client_order_id.as_deref().unwrap_or("mock_order"),
+        )?;
+        let (low_price, high_price) = (price * request.low_limit, price * request.high_limit);
+        let (take_profit_price, stop_price) = if request.is_buy {
+            (high_price, low_price)
+        } else {
+            (low_price, high_price)
+        };
+        Ok(MarketOrderReturn {
+            order_id,
+            qty,
+            value: qty * price,
+            price,
+            fill_price: Some(price),
+            fee: self.fees.fee(qty * price, FillType::Taker),
+            take_profit_id: Some(self.next_order_id.fetch_add(1, Ordering::Relaxed)),
+            take_profit_price,
+            stop_loss_id: (!stop_rejected)
+                .then(|| self.next_order_id.fetch_add(1, Ordering::Relaxed)),
+            stop_price,
+            stop_limit: request.stop_limit_price(stop_price),
+            margin_buffer: 0.,
+            margin_error: None,
+            failed_legs: if stop_rejected { vec![2] } else { vec![] },
+        })
+    }
+    fn reduce_position(&self, symbol: &str, qty: f64) -> Result<f64, MarketError> {
+        let ratio = self.respond(MockCall::ReducePosition(symbol.to_string(), qty))?;
+        let amount = self.position(symbol);
+        let qty = qty.min(amount.abs()) * ratio;
+        if qty > 0. {
+            self.fill(symbol, -qty * amount.signum(), "mock_reduce")?;
+        }
+        Ok(qty)
+    }
+    fn scale_out(&self, symbol: &str, fractions: &[f64]) -> Result<Vec<f64>, MarketError> {
+        if fractions.iter().sum::<f64>() > 1. + 1e-9 {
+            return Err(MarketError::Rejected("fractions sum exceeds 1".into()));
+        }
+        self.respond(MockCall::ScaleOut(symbol.to_string(), fractions.to_vec()))?;
+        let amount = self.position(symbol);
+        let mut reduced = vec![];
+        for f in fractions {
+            let qty = amount.abs() * f;
+            self.fill(symbol, -qty * amount.signum(), "mock_scale_out")?;
+            reduced.push(qty);
+        }
+        Ok(reduced)
+    }
+    fn add_margin(&self, symbol: &str, amount: f64) -> Result<(), MarketError> {
+        self.respond(MockCall::AddMargin(symbol.to_string(), amount))?;
+        match self.positions.lock().get_mut(symbol) {
+            Some(p) if p.amount != 0. => {
+                p.isolated_wallet += amount;
+                Ok(())
+            }
+            _ => Err(MarketError::NotFound(format!("position {}", symbol))),
+        }
+    }
+    fn fees(&self) -> FeeSchedule {
+        self.fees
+    }
+}
+
+/// 模拟的成交，qty带符号（正为买入）
+#[derive(Debug, Clone, Default)]
+pub struct FakeFill {
+    pub symbol: String,
+    pub client_order_id: String,
+    pub order_id: u64,
+    pub qty: f64,
+    pub price: f64,
+    pub realized_pnl: f64,
+    pub commission: f64,
+}
+
+impl FakeFill {
+    /// 完全成交的ORDER_TRADE_UPDATE事件
+    pub fn event(&self, time: u64) -> AccountInfo {
+        let qty = self.qty.abs().to_string();
+        let price = self.price.to_string();
+        AccountInfo::OrderTrade {
+            time,
+            order: Box::new(OrderUpdate {
+                symbol: self.symbol.clone(),
+                new_client_order_id: self.client_order_id.clone(),
+                side: if self.qty >= 0. { "BUY" } else { "SELL" }.into(),
+                order_type: "MARKET".into(),
+                time_in_force: "GTC".into(),
+                qty: qty.clone(),
+                price: "0".into(),
+                average_price: price.clone(),
+                stop_price: "0".into(),
+                execution_type: "TRADE".into(),
+                order_status: "FILLED".into(),
+                order_id: self.order_id,
+                qty_last_filled_trade: qty.clone(),
+                accumulated_qty_filled_trades: qty,
+                price_last_filled_trade: price,
+                asset_commisioned: None,
+                commission: Some(self.commission.to_string()),
+                trade_order_time: time,
+                trade_id: self.order_id,
+                bids_notional: "0".into(),
+                ask_notional: "0".into(),
+                is_buyer_maker: false,
+                is_reduce_only: false,
+                stop_price_working_type: "CONTRACT_PRICE".into(),
+                original_order_type: "MARKET".into(),
+                position_side: "BOTH".into(),
+                close_all: Some(false),
+                activation_price: None,
+                callback_rate: None,
+                pp_ignore: false,
+                si_ignore: 0,
+                ss_ignore: 0,
+                realized_profit: self.realized_pnl.to_string(),
+            }),
+        }
+    }
+}
+
+/// 模拟的逐仓持仓，amount带符号
+#[derive(Debug, Clone, Default)]
+pub struct FakePosition {
+    pub symbol: String,
+    pub amount: f64,
+    pub entry_price: f64,
+    pub isolated_wallet: f64,
+}
+
+/// USDT余额和持仓变化的ACCOUNT_UPDATE事件
+pub fn account_update(time: u64, balance: f64, positions: &[FakePosition]) -> AccountInfo {
+    AccountInfo::AccountUpdate {
+        time,
+        data: AccountUpdateDataEvent {
+            reason: "ORDER".into(),
+            balances: vec![EventBalance {
+                asset: "USDT".into(),
+                wallet_balance: balance.to_string(),
+                cross_wallet_balance: balance.to_string(),
+                balance_change: "0".into(),
+            }],
+            positions: positions
+                .iter()
+                .map(|p| EventPosition {
+                    symbol: p.symbol.clone(),
+                    position_amount: p.amount.to_string(),
+                    entry_price: p.entry_price.to_string(),
+                    accumulated_realized: "0".into(),
+                    unrealized_pnl: "0".into(),
+                    margin_type: "isolated".into(),
+                    isolated_wallet: p.isolated_wallet.to_string(),
+                    position_side: "BOTH".into(),
+                })
+                .collect(),
+        },
+    }
+}
+
+#[test]
+fn mock_market_test() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let market = MockMarket::new(1000.).with_events(tx);
+    market.set_price("BTCUSDT", 100.);
+    let request = MarketOrderRequest::new("BTCUSDT".into(), true, 500., 0.9, 1.1)
+        .unwrap()
+        .with_tag(1, 42);
+    let r = market.order(request).unwrap();
+    assert_eq!(r.qty, 5.);
+    assert_eq!(market.position("BTCUSDT"), 5.);
+    match rx.try_recv().unwrap() {
+        AccountInfo::OrderTrade { order, .. } => {
+            assert_eq!(order.new_client_order_id, "s1_42_0");
+            assert_eq!(order.accumulated_qty_filled_trades, "5");
+        }
+        _ => panic!("expect order trade"),
+    }
+    assert!(matches!(
+        rx.try_recv().unwrap(),
+        AccountInfo::AccountUpdate { .. }
+    ));
+    // 脚本化的拒单和部分成交
+    market.push(MockResponse::Binance(
+        -2019,
+        "Margin is insufficient.".into(),
+    ));
+    market.push(MockResponse::PartialFill(0.5));
+    let request = || MarketOrderRequest::new("BTCUSDT".into(), true, 200., 0.9, 1.1).unwrap();
+    let e = market.order(request()).err().unwrap();
+    assert_eq!(e.code(), Some(BinanceErrorCode::MarginInsufficient));
+    assert_eq!(market.order(request()).unwrap().qty, 1.);
+    // 盈利平仓
+    market.set_price("BTCUSDT", 110.);
+    market.close_position("BTCUSDT").unwrap();
+    assert_eq!(market.position("BTCUSDT"), 0.);
+    assert!(market.balance() > 1000. + 60. - 1.);
+    assert!(matches!(
+        market.calls().last(),
+        Some(MockCall::ClosePosition(s)) if s == "BTCUSDT"
+    ));
+    assert!(market.add_margin("BTCUSDT", 10.).is_err());
+
+    // 按交易规则取整
+    let filters = Arc::new(SymbolFilters::new());
+    filters.insert(
+        "BTCUSDT",
+        super::symbol_filters::SymbolRules {
+            step_size: 0.1,
+            min_notional: 100.,
+            ..Default::default()
+        },
+    );
+    let market = MockMarket::new(1000.).with_filters(filters);
+    market.set_price("BTCUSDT", 30.);
+    assert_eq!(market.order(request()).unwrap().qty, 6.6);
+    let small = MarketOrderRequest::new("BTCUSDT".into(), true, 50., 0.9, 1.1).unwrap();
+    assert!(matches!(market.order(small), Err(MarketError::Rejected(_))));
+}
+
+#[test]
+fn mock_market_order_id_test() {
+    let market = MockMarket::new(1e9);
+    market.set_price("BTCUSDT", 100.);
+    let ids: Vec<u64> = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                s.spawn(|| {
+                    (0..100)
+                        .map(|_| {
+                            let request =
+                                MarketOrderRequest::new("BTCUSDT".into(), true, 100., 0.9, 1.1)
+                                    .unwrap();
+                            market.order(request).unwrap().order_id
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    });
+    let unique: std::collections::HashSet<_> = ids.iter().collect();
+    assert_eq!(unique.len(), ids.len());
+}
@@ -0,0 +1,307 @@
+use dashmap::DashMap;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    error::MarketError,
+    fee::{FeeSchedule, FillType},
+    okx_swap::{inst_id, OkxClient, OkxKeys},
+};
+
+use super::{
+    symbol_filters::SymbolRules, ClientOrderId, Market, MarketOrderRequest, MarketOrderReturn,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Instrument {
+    inst_id: String,
+    /// 每张合约对应的币数量
+    ct_val: String,
+    lot_sz: String,
+    min_sz: String,
+    tick_sz: String,
+}
+
+#[derive(Debug)]
+pub struct OkxSymbolStatus {
+    /// 每张合约对应的币数量
+    contract_value: f64,
+    /// 数量以张为单位
+    rules: SymbolRules,
+}
+
+impl TryFrom<Instrument> for OkxSymbolStatus {
+    type Error = MarketError;
+    fn try_from(i: Instrument) -> Result<Self, Self::Error> {
+        let parse = |field: &str, v: &str| {
+            v.parse().map_err(|e| MarketError::Request {
+                context: format!("parse {} of {}", field, i.inst_id),
+                msg: format!("{}", e),
+            })
+        };
+        Ok(Self {
+            contract_value: parse("ctVal", &i.ct_val)?,
+            rules: SymbolRules {
+                min_qty: parse("minSz", &i.min_sz)?,
+                step_size: parse("lotSz", &i.lot_sz)?,
+                tick_size: parse("tickSz", &i.tick_sz)?,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxPosition {
+    /// 持仓张数，单向持仓模式下空头为负
+    pos: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxOrder {
+    ord_id: String,
+}
+
+/// OKX永续合约市场，逐仓单向持仓，symbol使用币安格式（BTCUSDT）
+#[derive(Debug)]
+pub struct OkxMarket {
+    statuses: DashMap<String, OkxSymbolStatus>,
+    leverage: u8,
+    /// 用于估算开仓手续费
+    fees: FeeSchedule,
+    client: OkxClient,
+}
+
+impl OkxMarket {
+    pub fn new(keys: OkxKeys, leverage: u8) -> Result<Self, MarketError> {
+        let client = OkxClient::new(keys);
+        client
+            .post::<serde_json::Value>(
+                "/api/v5/account/set-position-mode",
+                json!({"posMode": "net_mode"}),
+            )
+            .ok();
+        let statuses = DashMap::new();
+        let instruments: Vec<Instrument> =
+            client.get("/api/v5/public/instruments", "instType=SWAP")?;
+        for i in instruments {
+            statuses.insert(i.inst_id.clone(), i.try_into()?);
+        }
+        Ok(Self {
+            statuses,
+            leverage,
+            fees: FeeSchedule::default(),
+            client,
+        })
+    }
+    fn set_leverage(&self, inst_id: &str, leverage: u8) -> Result<(), MarketError> {
+        self.client.post::<serde_json::Value>(
+            "/api/v5/account/set-leverage",
+            json!({"instId": inst_id, "lever": leverage.to_string(), "mgnMode": "isolated"}),
+        )?;
+        Ok(())
+    }
+    fn price(&self, inst_id: &str) -> Result<f64, MarketError> {
+        #[derive(Deserialize)]
+        struct Ticker {
+            last: String,
+        }
+        let ticker: Vec<Ticker> = self
+            .client
+            .get("/api/v5/market/ticker", &format!("instId={}", inst_id))?;
+        ticker
+            .first()
+            .and_then(|t| t.last.parse().ok())
+            .ok_or(MarketError::NotFound("ticker".to_string()))
+    }
+    /// 持仓张数
+    fn position_size(&self, inst_id: &str) -> Result<f64, MarketError> {
+        let positions: Vec<OkxPosition> = self
+            .client
+            .get("/api/v5/account/positions", &format!("instId={}", inst_id))?;
+        Ok(positions
+            .first()
+            .and_then(|p| p.pos.parse().ok())
+            .unwrap_or_default())
+    }
+    /// 按持仓方向下只减仓市价单，qty为币数量，返回实际减仓的币数量
+    fn reduce(&self, inst_id: &str, position_size: f64, qty: f64) -> Result<f64, MarketError> {
+        if position_size == 0. {
+            return Err(MarketError::NotFound("position".to_string()));
+        }
+        let status = self
+            .statuses
+            .get(inst_id)
+            .ok_or(MarketError::NotFound("status".to_string()))?;
+        let size = status
+            .rules
+            .round_qty((qty / status.contract_value).min(position_size.abs()), true)?;
+        self.client.post::<serde_json::Value>(
+            "/api/v5/trade/order",
+            json!({
+                "instId": inst_id,
+                "tdMode": "isolated",
+                "side": if position_size > 0. { "sell" } else { "buy" },
+                "ordType": "market",
+                "sz": size.to_string(),
+                "reduceOnly": true,
+            }),
+        )?;
+        Ok(size * status.contract_value)
+    }
+}
+
+impl Market for OkxMarket {
+    fn clear_orders(&self, symbol: &str) -> Result<(), MarketError> {
+        let inst_id = inst_id(symbol);
+        let orders: Vec<OkxOrder> = self.client.get(
+            "/api/v5/trade/orders-pending",
+            &format!("instId={}", inst_id),
+        )?;
+        if orders.is_empty() {
+            return Ok(());
+        }
+        let cancels: Vec<_> = orders
+            .iter()
+            .map(|o| json!({"instId": inst_id, "ordId": o.ord_id}))
+            .collect();
+        self.client.post::<serde_json::Value>(
+            "/api/v5/trade/cancel-batch-orders",
+            serde_json::Value::Array(cancels),
+        )?;
+        Ok(())
+    }
+
+    fn close_position(&self, symbol: &str) -> Result<(), MarketError> {
+        self.clear_orders(symbol)?;
+        let inst_id = inst_id(symbol);
+        if self.position_size(&inst_id)? == 0. {
+            return Ok(());
+        }
+        self.client.post::<serde_json::Value>(
+            "/api/v5/trade/close-position",
+            json!({"instId": inst_id, "mgnMode": "isolated", "autoCxl": true}),
+        )?;
+        Ok(())
+    }
+
+    fn order(&self, request: MarketOrderRequest) -> Result<MarketOrderReturn, MarketError> {
+        let inst_id = inst_id(&request.symbol);
+        if self.position_size(&inst_id)? != 0. {
+            return Err(MarketError::Rejected("position not empty".to_string()));
+        }
+        self.clear_orders(&request.symbol)?;
+        self.set_leverage(&inst_id, request.leverage.unwrap_or(self.leverage))?;
+        let status = self
+            .statuses
+            .get(&inst_id)
+            .ok_or(MarketError::NotFound("status".to_string()))?;
+        let price = self.price(&inst_id)?;
+        let size = status
+            .rules
+            .round_qty(request.value / price / status.contract_value, true)?;
+        let qty = size * status.contract_value;
+        let low_price = status.rules.round_price(price * request.low_limit)?;
+        let high_price = status.rules.round_price(price * request.high_limit)?;
+        let (tp, sl) = if request.is_buy {
+            (high_price, low_price)
+        } else {
+            (low_price, high_price)
+        };
+        let mut body = json!({
+            "instId": inst_id,
+            "tdMode": "isolated",
+            "side": if request.is_buy { "buy" } else { "sell" },
+            "ordType": "market",
+            "sz": size.to_string(),
+            "attachAlgoOrds": [{
+                "tpTriggerPx": tp.to_string(),
+                "tpOrdPx": "-1",
+                "slTriggerPx": sl.to_string(),
+                "slOrdPx": "-1",
+            }],
+        });
+        if let Some((strategy, request_id)) = request.tag {
+            // OKX的clOrdId只允许字母和数字
+            let id = ClientOrderId {
+                strategy,
+                request_id,
+                leg: request.leg_base,
+            };
+            body["clOrdId"] = id.to_string().replace('_', "x").into();
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct OrderResult {
+            ord_id: String,
+        }
+        let result: Vec<OrderResult> = self.client.post("/api/v5/trade/order", body)?;
+        let order_id = result
+            .first()
+            .and_then(|r| r.ord_id.parse().ok())
+            .ok_or(MarketError::NotFound("order id".to_string()))?;
+        Ok(MarketOrderReturn {
+            order_id,
+            qty,
+            value: qty * price,
+            price,
+            fill_price: None,
+            fee: self.fees.fee(qty * price, FillType::Taker),
+            // 附带的止盈止损没有单独的订单id
+            take_profit_id: None,
+            take_profit_price: tp,
+            stop_loss_id: None,
+            stop_price: sl,
+            stop_limit: None,
+            // 止盈止损随开仓单附带，不追加保证金
+            margin_buffer: 0.,
+            margin_error: None,
+            failed_legs: vec![],
+        })
+    }
+
+    fn reduce_position(&self, symbol: &str, qty: f64) -> Result<f64, MarketError> {
+        let inst_id = inst_id(symbol);
+        let position_size = self.position_size(&inst_id)?;
+        self.reduce(&inst_id, position_size, qty)
+    }
+
+    fn scale_out(&self, symbol: &str, fractions: &[f64]) -> Result<Vec<f64>, MarketError> {
+        if fractions.iter().any(|f| *f <= 0.) || fractions.iter().sum::<f64>() > 1. {
+            return Err(MarketError::Rejected(
+                "fractions must be positive and sum to at most 1".to_string(),
+            ));
+        }
+        let inst_id = inst_id(symbol);
+        let contract_value = self
+            .statuses
+            .get(&inst_id)
+            .ok_or(MarketError::NotFound("status".to_string()))?
+            .contract_value;
+        let mut position_size = self.position_size(&inst_id)?;
+        let total = position_size.abs() * contract_value;
+        let mut reduced = Vec::with_capacity(fractions.len());
+        for fraction in fractions {
+            let qty = self.reduce(&inst_id, position_size, total * fraction)?;
+            position_size -= qty / contract_value * position_size.signum();
+            reduced.push(qty);
+        }
+        Ok(reduced)
+    }
+
+    fn add_margin(&self, symbol: &str, amount: f64) -> Result<(), MarketError> {
+        self.client.post::<serde_json::Value>(
+            "/api/v5/account/position/margin-balance",
+            json!({
+                "instId": inst_id(symbol),
+                "posSide": "net",
+                "type": "add",
+                "amt": amount.to_string(),
+            }),
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,432 @@
+use std::{collections::VecDeque, fmt::Display, sync::Arc, time::Instant};
+
+use crossbeam::channel::{Sender, TrySendError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{
+    spread::{SpreadOrderRequest, SpreadOrderReturn},
+    Market, MarketOrderRequest, MarketOrderReturn,
+};
+use crate::{
+    error::MarketError, fee::FeeSchedule, liquidation::MarginBrackets, utils::unix_millis,
+};
+
+/// 一笔开仓在实盘和模拟市场的执行结果，未成交的一方价格为None
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShadowRecord {
+    pub symbol: String,
+    pub is_buy: bool,
+    /// 下单时间（毫秒）
+    pub time: u64,
+    pub live_price: Option<f64>,
+    pub paper_price: Option<f64>,
+    pub live_qty: f64,
+    pub paper_qty: f64,
+    /// 下单到返回的耗时（毫秒）
+    pub live_latency: u64,
+    pub paper_latency: u64,
+}
+
+impl ShadowRecord {
+    /// 实盘成交价相对模拟成交价的滑点比例，对下单方向不利为正
+    pub fn slippage(&self) -> Option<f64> {
+        let (live, paper) = (self.live_price?, self.paper_price?);
+        let diff = (live - paper) / paper;
+        Some(if self.is_buy { diff } else { -diff })
+    }
+}
+
+/// 实盘与模拟的差异汇总
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ShadowReport {
+    pub trades: usize,
+    /// 模拟成交而实盘未成交
+    pub missed_live: usize,
+    /// 实盘成交而模拟未成交
+    pub missed_paper: usize,
+    /// 双方都成交时的平均和最大滑点比例
+    pub mean_slippage: f64,
+    pub max_slippage: f64,
+    /// 实盘比模拟多用的平均耗时（毫秒）
+    pub mean_latency: f64,
+}
+
+impl ShadowReport {
+    pub fn new(records: &[ShadowRecord]) -> Self {
+        let mut totals = ShadowTotals::default();
+        for r in records {
+            totals.add(r);
+        }
+        totals.report()
+    }
+}
+
+/// 全部记录的累计值，记录只保留最近的一部分
+#[derive(Debug, Clone, Default)]
+struct ShadowTotals {
+    trades: usize,
+    missed_live: usize,
+    missed_paper: usize,
+    slippages: usize,
+    slippage_sum: f64,
+    max_slippage: f64,
+    latency_sum: f64,
+}
+
+impl ShadowTotals {
+    fn add(&mut self, r: &ShadowRecord) {
+        self.trades += 1;
+        match (r.live_price, r.paper_price) {
+            (None, Some(_)) => self.missed_live += 1,
+            (Some(_), None) => self.missed_paper += 1,
+            _ => {}
+        }
+        if let Some(slippage) = r.slippage() {
+            self.slippages += 1;
+            self.slippage_sum += slippage;
+            self.max_slippage = self.max_slippage.max(slippage);
+        }
+        self.latency_sum += r.live_latency as f64 - r.paper_latency as f64;
+    }
+    fn report(&self) -> ShadowReport {
+        let mean = |sum: f64, n: usize| if n == 0 { 0. } else { sum / n as f64 };
+        ShadowReport {
+            trades: self.trades,
+            missed_live: self.missed_live,
+            missed_paper: self.missed_paper,
+            mean_slippage: mean(self.slippage_sum, self.slippages),
+            max_slippage: self.max_slippage,
+            mean_latency: mean(self.latency_sum, self.trades),
+        }
+    }
+}
+
+impl Display for ShadowReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Shadow: {} trades, missed live {}, missed paper {}, slippage mean {:.4}% max {:.4}%, latency +{:.0}ms",
+            self.trades,
+            self.missed_live,
+            self.missed_paper,
+            self.mean_slippage * 100.,
+            self.max_slippage * 100.,
+            self.mean_latency
+        )
+    }
+}
+
+/// 影子交易的配置，在MarketConfig中设置后启用
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ShadowConfig {
+    /// 保留最近的记录条数，汇总统计不受影响
+    pub capacity: usize,
+    /// 待镜像到模拟市场的操作队列长度，满时丢弃并告警，不阻塞实盘
+    pub queue: usize,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            queue: 1024,
+        }
+    }
+}
+
+/// live的成交（价格，数量）
+type LiveFill = (Option<f64>, f64);
+
+fn live_fill(r: Option<&MarketOrderReturn>) -> LiveFill {
+    match r {
+        Some(r) if r.qty > 0. => (Some(r.fill_price.unwrap_or(r.price)), r.qty),
+        _ => (None, 0.),
+    }
+}
+
+/// 在后台线程按顺序镜像到paper的操作
+enum PaperTask {
+    Order {
+        request: MarketOrderRequest,
+        time: u64,
+        live: LiveFill,
+        live_latency: u64,
+    },
+    Spread {
+        request: SpreadOrderRequest,
+        time: u64,
+        live: Vec<LiveFill>,
+        live_latency: u64,
+    },
+    ClearOrders(String),
+    ClosePosition(String),
+    ReducePosition(String, f64),
+    ScaleOut(String, Vec<f64>),
+    AddMargin(String, f64),
+    /// 之前的操作都已执行
+    Sync(Sender<()>),
+}
+
+#[derive(Debug, Default)]
+struct ShadowState {
+    records: VecDeque<ShadowRecord>,
+    capacity: usize,
+    totals: ShadowTotals,
+}
+
+impl ShadowState {
+    fn push(&mut self, record: ShadowRecord) {
+        self.totals.add(&record);
+        if self.records.len() >= self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+}
+
+/// 影子交易：订单先在live执行，再由后台线程镜像到paper（模拟市场），逐笔记录两者的成交差异，
+/// 用于检验回测和模拟的成交假设；只返回live的结果，paper的错误只记录不影响实盘
+#[derive(Debug)]
+pub struct ShadowMarket<L, P> {
+    pub live: L,
+    pub paper: Arc<P>,
+    tasks: Sender<PaperTask>,
+    state: Arc<Mutex<ShadowState>>,
+}
+
+impl<L: Market, P: Market> ShadowMarket<L, P> {
+    pub fn new(live: L, paper: P) -> Self {
+        Self::from_config(live, paper, ShadowConfig::default())
+    }
+    pub fn from_config(live: L, paper: P, config: ShadowConfig) -> Self {
+        let paper = Arc::new(paper);
+        let state = Arc::new(Mutex::new(ShadowState {
+            capacity: config.capacity.max(1),
+            ..Default::default()
+        }));
+        let (tasks, rx) = crossbeam::channel::bounded(config.queue.max(1));
+        let (worker_paper, worker_state) = (paper.clone(), state.clone());
+        std::thread::spawn(move || {
+            for task in rx {
+                mirror(&*worker_paper, &worker_state, task);
+            }
+        });
+        Self {
+            live,
+            paper,
+            tasks,
+            state,
+        }
+    }
+    /// 最近的记录，按时间排序
+    pub fn records(&self) -> Vec<ShadowRecord> {
+        self.state.lock().records.iter().cloned().collect()
+    }
+    /// 等待已提交的镜像操作执行完
+    pub fn sync(&self) {
+        let (tx, rx) = crossbeam::channel::bounded(1);
+        if self.tasks.send(PaperTask::Sync(tx)).is_ok() {
+            rx.recv().ok();
+        }
+    }
+    fn send(&self, task: PaperTask) {
+        if let Err(TrySendError::Full(_)) = self.tasks.try_send(task) {
+            warn!("Shadow queue full, paper market out of sync");
+        }
+    }
+}
+
+/// 执行并计时（毫秒）
+fn timed<T>(f: impl FnOnce() -> T) -> (T, u64) {
+    let start = Instant::now();
+    let r = f();
+    (r, start.elapsed().as_millis() as u64)
+}
+
+/// 在paper上执行一个操作，开仓时记录与live的差异
+fn mirror<P: Market>(paper: &P, state: &Mutex<ShadowState>, task: PaperTask) {
+    match task {
+        PaperTask::Order {
+            request,
+            time,
+            live: (live_price, live_qty),
+            live_latency,
+        } => {
+            let (symbol, is_buy) = (request.symbol.clone(), request.is_buy);
+            let (paper, paper_latency) = timed(|| paper.order(request));
+            let (paper_price, paper_qty) = live_fill(paper.as_ref().ok());
+            state.lock().push(ShadowRecord {
+                symbol,
+                is_buy,
+                time,
+                live_price,
+                paper_price,
+                live_qty,
+                paper_qty,
+                live_latency,
+                paper_latency,
+            });
+        }
+        PaperTask::Spread {
+            request,
+            time,
+            live,
+            live_latency,
+        } => {
+            let legs: Vec<(String, bool)> = request
+                .legs
+                .iter()
+                .map(|l| (l.symbol.clone(), l.is_buy))
+                .collect();
+            let (paper, paper_latency) = timed(|| paper.order_spread(request));
+            let paper = paper.ok();
+            let n = legs.len() as u64;
+            let mut state = state.lock();
+            for (i, (symbol, is_buy)) in legs.into_iter().enumerate() {
+                let (live_price, live_qty) = live.get(i).copied().unwrap_or((None, 0.));
+                let (paper_price, paper_qty) =
+                    live_fill(paper.as_ref().and_then(|r| r.legs.get(i)));
+                state.push(ShadowRecord {
+                    symbol,
+                    is_buy,
+                    time,
+                    live_price,
+                    paper_price,
+                    live_qty,
+                    paper_qty,
+                    live_latency: live_latency / n,
+                    paper_latency: paper_latency / n,
+                });
+            }
+        }
+        PaperTask::ClearOrders(symbol) => {
+            paper.clear_orders(&symbol).ok();
+        }
+        PaperTask::ClosePosition(symbol) => {
+            paper.close_position(&symbol).ok();
+        }
+        PaperTask::ReducePosition(symbol, qty) => {
+            paper.reduce_position(&symbol, qty).ok();
+        }
+        PaperTask::ScaleOut(symbol, fractions) => {
+            paper.scale_out(&symbol, &fractions).ok();
+        }
+        PaperTask::AddMargin(symbol, amount) => {
+            paper.add_margin(&symbol, amount).ok();
+        }
+        PaperTask::Sync(done) => {
+            done.send(()).ok();
+        }
+    }
+}
+
+impl<L: Market, P: Market> Market for ShadowMarket<L, P> {
+    fn clear_orders(&self, symbol: &str) -> Result<(), MarketError> {
+        let r = self.live.clear_orders(symbol);
+        self.send(PaperTask::ClearOrders(symbol.to_string()));
+        r
+    }
+    fn close_position(&self, symbol: &str) -> Result<(), MarketError> {
+        let r = self.live.close_position(symbol);
+        self.send(PaperTask::ClosePosition(symbol.to_string()));
+        r
+    }
+    fn order(&self, request: MarketOrderRequest) -> Result<MarketOrderReturn, MarketError> {
+        let time = unix_millis();
+        let (live, live_latency) = timed(|| self.live.order(request.clone()));
+        self.send(PaperTask::Order {
+            request,
+            time,
+            live: live_fill(live.as_ref().ok()),
+            live_latency,
+        });
+        live
+    }
+    fn reduce_position(&self, symbol: &str, qty: f64) -> Result<f64, MarketError> {
+        let r = self.live.reduce_position(symbol, qty);
+        self.send(PaperTask::ReducePosition(symbol.to_string(), qty));
+        r
+    }
+    fn scale_out(&self, symbol: &str, fractions: &[f64]) -> Result<Vec<f64>, MarketError> {
+        let r = self.live.scale_out(symbol, fractions);
+        self.send(PaperTask::ScaleOut(symbol.to_string(), fractions.to_vec()));
+        r
+    }
+    fn add_margin(&self, symbol: &str, amount: f64) -> Result<(), MarketError> {
+        let r = self.live.add_margin(symbol, amount);
+        self.send(PaperTask::AddMargin(symbol.to_string(), amount));
+        r
+    }
+    /// 由live按自身规则执行组合单（如拆分前的成交额限制），整组镜像到paper
+    fn order_spread(&self, request: SpreadOrderRequest) -> Result<SpreadOrderReturn, MarketError> {
+        let time = unix_millis();
+        let (live, live_latency) = timed(|| self.live.order_spread(request.clone()));
+        let fills = match &live {
+            Ok(r) => r.legs.iter().map(|l| live_fill(Some(l))).collect(),
+            Err(_) => vec![],
+        };
+        self.send(PaperTask::Spread {
+            request,
+            time,
+            live: fills,
+            live_latency,
+        });
+        live
+    }
+    fn shadow_report(&self) -> Option<ShadowReport> {
+        Some(self.state.lock().totals.report())
+    }
+    fn fees(&self) -> FeeSchedule {
+        self.live.fees()
+    }
+    fn margin_brackets(&self, symbol: &str) -> Option<MarginBrackets> {
+        self.live.margin_brackets(symbol)
+    }
+}
+
+#[test]
+fn shadow_market_test() {
+    use super::mock_market::{MockMarket, MockResponse};
+
+    let live = MockMarket::new(1000.);
+    live.set_price("BTCUSDT", 100.2);
+    // 模拟市场按实时行情的标记价格成交
+    let prices = crate::binance_futures::SymbolPrices::default();
+    prices.insert(
+        "BTCUSDT".into(),
+        crate::algorithm::SymbolPrice {
+            symbol: "BTCUSDT".into(),
+            mark_price: 100.,
+            ..Default::default()
+        },
+    );
+    let paper = MockMarket::new(1000.).with_price_feed(prices);
+    live.push(MockResponse::Fill);
+    live.push(MockResponse::Request("timeout".into()));
+    let market = ShadowMarket::from_config(
+        live,
+        paper,
+        ShadowConfig {
+            capacity: 1,
+            ..Default::default()
+        },
+    );
+    let order = || MarketOrderRequest::new("BTCUSDT".into(), true, 100., 0.9, 1.1).unwrap();
+    assert!(market.order(order()).is_ok());
+    assert!(market.order(order()).is_err());
+    market.sync();
+    // 只保留最近一条记录，汇总包含全部
+    let records = market.records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].live_price, None);
+    let report = market.shadow_report().unwrap();
+    assert_eq!((report.trades, report.missed_live), (2, 1));
+    assert!((report.max_slippage - 0.002).abs() < 1e-9);
+    assert!((report.mean_slippage - 0.002).abs() < 1e-9);
+    market.close_position("BTCUSDT").unwrap();
+    market.sync();
+    assert_eq!(market.paper.position("BTCUSDT"), 0.);
+}
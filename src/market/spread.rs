@@ -0,0 +1,220 @@
+use tracing::{error, info};
+
+use super::{Market, MarketOrderRequest, MarketOrderReturn, LEGS_PER_ORDER};
+use crate::{error::MarketError, utils::AUDIT_TARGET};
+
+/// 多腿组合单，如多ETH空BTC的配对交易
+#[derive(Debug, Clone)]
+pub struct SpreadOrderRequest {
+    /// 按顺序下单，流动性差、更可能失败的腿应放在前面
+    pub(super) legs: Vec<MarketOrderRequest>,
+}
+
+impl SpreadOrderRequest {
+    pub fn new(legs: Vec<MarketOrderRequest>) -> Result<Self, MarketError> {
+        if legs.len() < 2 {
+            return Err(MarketError::Rejected(
+                "spread needs at least 2 legs".to_string(),
+            ));
+        }
+        for (i, leg) in legs.iter().enumerate() {
+            if legs[..i].iter().any(|l| l.symbol == leg.symbol) {
+                return Err(MarketError::Rejected(format!(
+                    "duplicate spread leg {}",
+                    leg.symbol
+                )));
+            }
+        }
+        Ok(Self { legs })
+    }
+    /// 多long空short，long与short的价值比为ratio，总价值为value
+    pub fn pair(
+        long: String,
+        short: String,
+        value: f64,
+        ratio: f64,
+        low_limit: f64,
+        high_limit: f64,
+    ) -> Result<Self, MarketError> {
+        if ratio <= 0. {
+            return Err(MarketError::Rejected("ratio must be positive".to_string()));
+        }
+        let long_value = value * ratio / (1. + ratio);
+        Self::new(vec![
+            MarketOrderRequest::new(long, true, long_value, low_limit, high_limit)?,
+            MarketOrderRequest::new(short, false, value - long_value, low_limit, high_limit)?,
+        ])
+    }
+    /// 各腿共用同一请求id，第i条腿的订单序号从i * LEGS_PER_ORDER开始
+    pub fn with_tag(mut self, strategy: usize, request_id: u64) -> Self {
+        for (i, leg) in self.legs.iter_mut().enumerate() {
+            leg.tag = Some((strategy, request_id));
+            leg.leg_base = i * LEGS_PER_ORDER;
+        }
+        self
+    }
+    /// 拆分下单前按各腿允许的金额等比例缩小全部腿，保持对冲比例；
+    /// check返回该腿允许的下单金额，如LiquidityGuard::check
+    pub fn limit_value(
+        &mut self,
+        mut check: impl FnMut(&str, f64) -> Result<f64, MarketError>,
+    ) -> Result<(), MarketError> {
+        let mut scale: f64 = 1.;
+        for leg in self.legs.iter() {
+            scale = scale.min(check(&leg.symbol, leg.value)? / leg.value);
+        }
+        for leg in self.legs.iter_mut() {
+            leg.value *= scale;
+            leg.liquidity_checked = true;
+        }
+        Ok(())
+    }
+    /// 各腿的（币种，价值）
+    pub fn legs(&self) -> Vec<(String, f64)> {
+        self.legs
+            .iter()
+            .map(|l| (l.symbol.clone(), l.value))
+            .collect()
+    }
+}
+
+/// 按腿的顺序
+pub struct SpreadOrderReturn {
+    pub legs: Vec<MarketOrderReturn>,
+}
+
+impl SpreadOrderReturn {
+    pub fn value(&self) -> f64 {
+        self.legs.iter().map(|l| l.value).sum()
+    }
+}
+
+/// 逐腿下单；某条腿失败时先平掉失败腿可能部分成交的仓位，再按相反顺序撤销已成交腿的附属单并减掉其成交数量，
+/// 回滚失败不中断，尝试所有腿后一并返回
+pub fn execute<M: Market + ?Sized>(
+    market: &M,
+    request: SpreadOrderRequest,
+) -> Result<SpreadOrderReturn, MarketError> {
+    let mut filled: Vec<(String, MarketOrderReturn)> = vec![];
+    for (leg, order) in request.legs.into_iter().enumerate() {
+        let symbol = order.symbol.clone();
+        match market.order(order) {
+            Ok(r) => filled.push((symbol, r)),
+            Err(e) => {
+                let mut unwind_errors = vec![];
+                // 拒单时未成交；其他错误可能发生在开仓成交之后（如附属单、追加保证金失败）
+                if !matches!(e, MarketError::Rejected(_)) {
+                    if let Err(e) = market
+                        .clear_orders(&symbol)
+                        .and_then(|_| market.close_position(&symbol))
+                    {
+                        error!("Close failed spread leg {} failed: {}", symbol, e);
+                        unwind_errors.push((symbol, e));
+                    }
+                }
+                for (symbol, r) in filled.into_iter().rev() {
+                    match market
+                        .clear_orders(&symbol)
+                        .and_then(|_| market.reduce_position(&symbol, r.qty))
+                    {
+                        Ok(qty) => {
+                            info!(target: AUDIT_TARGET, "Spread leg {} rolled back: qty {}", symbol, qty);
+                        }
+                        Err(e) => {
+                            error!("Roll back spread leg {} failed: {}", symbol, e);
+                            unwind_errors.push((symbol, e));
+                        }
+                    }
+                }
+                return Err(MarketError::Spread {
+                    leg,
+                    error: Box::new(e),
+                    unwind_errors,
+                });
+            }
+        }
+    }
+    Ok(SpreadOrderReturn {
+        legs: filled.into_iter().map(|(_, r)| r).collect(),
+    })
+}
+
+#[test]
+fn spread_order_test() {
+    use super::mock_market::{MockCall, MockMarket, MockResponse};
+
+    let market = MockMarket::new(1000.);
+    market.set_price("ETHUSDT", 10.);
+    market.set_price("BTCUSDT", 100.);
+    let request = SpreadOrderRequest::pair("ETHUSDT".into(), "BTCUSDT".into(), 300., 2., 0.9, 1.1)
+        .unwrap()
+        .with_tag(1, 7);
+    assert_eq!(request.legs()[0], ("ETHUSDT".to_string(), 200.));
+    let r = market.order_spread(request).unwrap();
+    assert!((r.value() - 300.).abs() < 1e-9);
+    assert!((market.position("ETHUSDT") - 20.).abs() < 1e-9);
+    assert!((market.position("BTCUSDT") + 1.).abs() < 1e-9);
+    assert!(matches!(
+        &market.calls()[1],
+        MockCall::Order { client_order_id: Some(id), is_buy: false, .. } if id == "s1_7_3"
+    ));
+    // 第二条腿失败，失败腿平仓后第一条腿回滚
+    market.push(MockResponse::Fill);
+    market.push(MockResponse::Request("timeout".into()));
+    let request =
+        SpreadOrderRequest::pair("ETHUSDT".into(), "BTCUSDT".into(), 300., 2., 0.9, 1.1).unwrap();
+    let e = market.order_spread(request).err().unwrap();
+    assert!(matches!(
+        e,
+        MarketError::Spread { leg: 1, ref unwind_errors, .. } if unwind_errors.is_empty()
+    ));
+    assert!((market.position("ETHUSDT") - 20.).abs() < 1e-9);
+    assert!(market
+        .calls()
+        .contains(&MockCall::ClosePosition("BTCUSDT".into())));
+
+    // 某条腿回滚失败时继续回滚其余的腿
+    let market = MockMarket::new(1000.);
+    for symbol in ["AUSDT", "BUSDT", "CUSDT"] {
+        market.set_price(symbol, 10.);
+    }
+    for response in [
+        MockResponse::Fill,
+        MockResponse::Fill,
+        MockResponse::Binance(-2019, "Margin is insufficient.".into()),
+        MockResponse::Fill,
+        MockResponse::Fill,
+        MockResponse::Request("timeout".into()),
+    ] {
+        market.push(response);
+    }
+    let leg =
+        |symbol: &str| MarketOrderRequest::new(symbol.to_string(), true, 100., 0.9, 1.1).unwrap();
+    let request = SpreadOrderRequest::new(vec![leg("AUSDT"), leg("BUSDT"), leg("CUSDT")]).unwrap();
+    match market.order_spread(request) {
+        Err(MarketError::Spread {
+            leg: 2,
+            unwind_errors,
+            ..
+        }) => {
+            assert_eq!(unwind_errors.len(), 1);
+            assert_eq!(unwind_errors[0].0, "BUSDT");
+        }
+        _ => panic!("spread should fail at leg 2"),
+    }
+    assert!((market.position("BUSDT") - 10.).abs() < 1e-9);
+    assert_eq!(market.position("AUSDT"), 0.);
+    assert!(SpreadOrderRequest::pair("A".into(), "A".into(), 1., 1., 0.9, 1.1).is_err());
+
+    // 按成交额缩小时各腿等比例缩小
+    let mut request =
+        SpreadOrderRequest::pair("ETHUSDT".into(), "BTCUSDT".into(), 300., 2., 0.9, 1.1).unwrap();
+    request
+        .limit_value(|symbol, value| Ok(if symbol == "BTCUSDT" { 50. } else { value }))
+        .unwrap();
+    assert_eq!(
+        request.legs(),
+        [("ETHUSDT".to_string(), 100.), ("BTCUSDT".to_string(), 50.)]
+    );
+    assert!(request.legs.iter().all(|l| l.liquidity_checked));
+}
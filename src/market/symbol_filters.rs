@@ -0,0 +1,360 @@
+use binance::{futures::model::Symbol, model::Filters};
+use dashmap::DashMap;
+use thiserror::Error;
+
+use super::MarketOrderRequest;
+use crate::error::MarketError;
+
+/// 下单参数不满足交易规则的具体原因
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum FilterViolation {
+    #[error("qty {qty} below min qty {min}")]
+    QtyTooSmall { qty: f64, min: f64 },
+    #[error("qty {qty} above max qty {max}")]
+    QtyTooLarge { qty: f64, max: f64 },
+    #[error("price {price} below min price {min}")]
+    PriceTooLow { price: f64, min: f64 },
+    #[error("price {price} above max price {max}")]
+    PriceTooHigh { price: f64, max: f64 },
+    #[error("notional {notional} below min notional {min}")]
+    MinNotional { notional: f64, min: f64 },
+    /// 限价偏离当前价格超过PERCENT_PRICE允许的范围
+    #[error("limit price {price} outside [{low}, {high}]")]
+    PercentPrice { price: f64, low: f64, high: f64 },
+}
+
+impl From<FilterViolation> for MarketError {
+    fn from(v: FilterViolation) -> Self {
+        MarketError::Rejected(v.to_string())
+    }
+}
+
+/// 单个币种的下单规则，步长和上限为0表示不限制
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolRules {
+    /// LOT_SIZE，限价单
+    pub min_qty: f64,
+    pub max_qty: f64,
+    pub step_size: f64,
+    /// MARKET_LOT_SIZE，市价单
+    pub market_min_qty: f64,
+    pub market_max_qty: f64,
+    pub market_step_size: f64,
+    /// PRICE_FILTER
+    pub min_price: f64,
+    pub max_price: f64,
+    pub tick_size: f64,
+    /// MIN_NOTIONAL
+    pub min_notional: f64,
+    /// PERCENT_PRICE，限价单价格须在当前价格的[down, up]倍之间
+    pub multiplier_up: f64,
+    pub multiplier_down: f64,
+}
+
+/// 按规则取整后的开仓单及其止盈止损价格
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundedOrder {
+    pub qty: f64,
+    /// qty按下单价格计算的名义价值
+    pub notional: f64,
+    pub low_price: f64,
+    pub high_price: f64,
+    /// 止损触发价，买入为low_price，卖出为high_price
+    pub stop_price: f64,
+    pub stop_limit: Option<f64>,
+    /// 限价开仓的价格，市价开仓为None
+    pub entry_price: Option<f64>,
+}
+
+impl SymbolRules {
+    pub fn from_filters(filters: &[Filters]) -> Self {
+        let parse = |s: &str| s.parse::<f64>().unwrap_or_default();
+        let mut rules = Self::default();
+        for filter in filters {
+            match filter {
+                Filters::LotSize {
+                    min_qty,
+                    max_qty,
+                    step_size,
+                    ..
+                } => {
+                    rules.min_qty = parse(min_qty);
+                    rules.max_qty = parse(max_qty);
+                    rules.step_size = parse(step_size);
+                }
+                Filters::MarketLotSize {
+                    min_qty,
+                    max_qty,
+                    step_size,
+                    ..
+                } => {
+                    rules.market_min_qty = parse(min_qty);
+                    rules.market_max_qty = parse(max_qty);
+                    rules.market_step_size = parse(step_size);
+                }
+                Filters::PriceFilter {
+                    min_price,
+                    max_price,
+                    tick_size,
+                    ..
+                } => {
+                    rules.min_price = parse(min_price);
+                    rules.max_price = parse(max_price);
+                    rules.tick_size = parse(tick_size);
+                }
+                Filters::MinNotional {
+                    notional,
+                    min_notional,
+                    ..
+                } => {
+                    if let Some(n) = notional.as_ref().or(min_notional.as_ref()) {
+                        rules.min_notional = parse(n);
+                    }
+                }
+                Filters::PercentPrice {
+                    multiplier_up,
+                    multiplier_down,
+                    ..
+                } => {
+                    rules.multiplier_up = parse(multiplier_up);
+                    rules.multiplier_down = parse(multiplier_down);
+                }
+                _ => {}
+            }
+        }
+        rules
+    }
+    /// 向下取整到步长并检查数量范围；市价单同时满足LOT_SIZE和MARKET_LOT_SIZE，
+    /// 因为止盈限价单与开仓市价单数量相同
+    pub fn round_qty(&self, qty: f64, is_market: bool) -> Result<f64, FilterViolation> {
+        let (mut step, mut min, mut max) = (self.step_size, self.min_qty, self.max_qty);
+        if is_market {
+            step = step.max(self.market_step_size);
+            min = min.max(self.market_min_qty);
+            max = match (max, self.market_max_qty) {
+                (a, b) if a > 0. && b > 0. => a.min(b),
+                (a, b) => a.max(b),
+            };
+        }
+        let qty = floor_step(qty, step);
+        if qty <= 0. || qty < min {
+            return Err(FilterViolation::QtyTooSmall { qty, min });
+        }
+        if max > 0. && qty > max {
+            return Err(FilterViolation::QtyTooLarge { qty, max });
+        }
+        Ok(qty)
+    }
+    /// 向下取整到tick并检查价格范围
+    pub fn round_price(&self, price: f64) -> Result<f64, FilterViolation> {
+        let price = floor_step(price, self.tick_size);
+        if price <= 0. || price < self.min_price {
+            return Err(FilterViolation::PriceTooLow {
+                price,
+                min: self.min_price,
+            });
+        }
+        if self.max_price > 0. && price > self.max_price {
+            return Err(FilterViolation::PriceTooHigh {
+                price,
+                max: self.max_price,
+            });
+        }
+        Ok(price)
+    }
+    /// 检查限价单价格相对当前价格的偏离
+    pub fn check_percent_price(&self, limit: f64, price: f64) -> Result<(), FilterViolation> {
+        let high = if self.multiplier_up > 0. {
+            price * self.multiplier_up
+        } else {
+            f64::INFINITY
+        };
+        let low = price * self.multiplier_down;
+        if limit > high || limit < low {
+            return Err(FilterViolation::PercentPrice {
+                price: limit,
+                low,
+                high,
+            });
+        }
+        Ok(())
+    }
+    /// 按price计算开仓数量和止盈止损价格，全部取整后校验；止盈单和止损限价单为限价单
+    pub fn validate_and_round(
+        &self,
+        request: &MarketOrderRequest,
+        price: f64,
+    ) -> Result<RoundedOrder, FilterViolation> {
+        let entry_price = match request.time_in_force {
+            Some(_) => Some(self.round_price(price)?),
+            None => None,
+        };
+        let qty = self.round_qty(request.value / price, entry_price.is_none())?;
+        let notional = qty * price;
+        if notional < self.min_notional {
+            return Err(FilterViolation::MinNotional {
+                notional,
+                min: self.min_notional,
+            });
+        }
+        let low_price = self.round_price(price * request.low_limit)?;
+        let high_price = self.round_price(price * request.high_limit)?;
+        let (take_profit, stop_price) = if request.is_buy {
+            (high_price, low_price)
+        } else {
+            (low_price, high_price)
+        };
+        self.check_percent_price(take_profit, price)?;
+        let stop_limit = match request.stop_limit_price(stop_price) {
+            Some(p) => {
+                let p = self.round_price(p)?;
+                self.check_percent_price(p, price)?;
+                Some(p)
+            }
+            None => None,
+        };
+        Ok(RoundedOrder {
+            qty,
+            notional,
+            low_price,
+            high_price,
+            stop_price,
+            stop_limit,
+            entry_price,
+        })
+    }
+}
+
+/// 各币种下单规则的缓存，由交易所信息填充，可在多个Market之间共享
+#[derive(Debug, Default)]
+pub struct SymbolFilters {
+    rules: DashMap<String, SymbolRules>,
+}
+
+impl SymbolFilters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// 用交易所信息中的币种更新缓存
+    pub fn update(&self, info: &Symbol) {
+        self.insert(&info.symbol, SymbolRules::from_filters(&info.filters));
+    }
+    pub fn insert(&self, symbol: &str, rules: SymbolRules) {
+        self.rules.insert(symbol.to_string(), rules);
+    }
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.rules.contains_key(symbol)
+    }
+    pub fn get(&self, symbol: &str) -> Option<SymbolRules> {
+        self.rules.get(symbol).map(|r| r.clone())
+    }
+    fn rules(&self, symbol: &str) -> Result<SymbolRules, MarketError> {
+        self.get(symbol)
+            .ok_or_else(|| MarketError::NotFound(format!("filters of {}", symbol)))
+    }
+    /// 市价单数量
+    pub fn round_qty(&self, symbol: &str, qty: f64) -> Result<f64, MarketError> {
+        Ok(self.rules(symbol)?.round_qty(qty, true)?)
+    }
+    pub fn validate_and_round(
+        &self,
+        request: &MarketOrderRequest,
+        price: f64,
+    ) -> Result<RoundedOrder, MarketError> {
+        Ok(self
+            .rules(&request.symbol)?
+            .validate_and_round(request, price)?)
+    }
+}
+
+/// 向下取整到step的整数倍，并去掉浮点误差的尾数；step不大于0时不取整
+fn floor_step(value: f64, step: f64) -> f64 {
+    if step <= 0. {
+        return value;
+    }
+    let n = (value / step + 1e-9).floor();
+    let scale = 10f64.powi((-step.log10()).ceil().max(0.) as i32);
+    (n * step * scale).round() / scale
+}
+
+#[test]
+fn symbol_filters_test() {
+    let s = |v: &str| v.to_string();
+    let rules = SymbolRules::from_filters(&[
+        Filters::LotSize {
+            min_qty: s("0.001"),
+            max_qty: s("1000"),
+            step_size: s("0.001"),
+        },
+        Filters::MarketLotSize {
+            min_qty: s("0.001"),
+            max_qty: s("120"),
+            step_size: s("0.001"),
+        },
+        Filters::PriceFilter {
+            min_price: s("556.80"),
+            max_price: s("4529764"),
+            tick_size: s("0.10"),
+        },
+        Filters::MinNotional {
+            notional: Some(s("100")),
+            min_notional: None,
+        },
+        Filters::PercentPrice {
+            multiplier_up: s("1.0500"),
+            multiplier_down: s("0.9500"),
+        },
+    ]);
+    assert_eq!(rules.market_max_qty, 120.);
+    assert_eq!(floor_step(0.3, 0.1), 0.3);
+    assert_eq!(floor_step(1.23456, 0.001), 1.234);
+    assert_eq!(rules.round_qty(150., false), Ok(150.));
+    assert_eq!(
+        rules.round_qty(150., true),
+        Err(FilterViolation::QtyTooLarge {
+            qty: 150.,
+            max: 120.
+        })
+    );
+
+    let filters = SymbolFilters::new();
+    filters.insert("BTCUSDT", rules);
+    let request = |value, high_limit| {
+        MarketOrderRequest::new("BTCUSDT".to_string(), true, value, 0.98, high_limit).unwrap()
+    };
+    let order = filters
+        .validate_and_round(&request(1000., 1.02).with_stop_limit(0.01), 30000.)
+        .unwrap();
+    assert_eq!(order.qty, 0.033);
+    assert!((order.notional - 990.).abs() < 1e-9);
+    assert_eq!((order.low_price, order.high_price), (29400., 30600.));
+    assert_eq!(order.stop_price, 29400.);
+    assert_eq!(order.stop_limit, Some(29106.));
+    assert_eq!(order.entry_price, None);
+    // 限价开仓按盘口价格取整
+    let order = filters
+        .validate_and_round(
+            &request(1000., 1.02).with_time_in_force(super::TimeInForce::Ioc),
+            30000.04,
+        )
+        .unwrap();
+    assert_eq!(order.entry_price, Some(30000.));
+    // 取整后名义价值不足
+    assert!(matches!(
+        filters.validate_and_round(&request(100., 1.02), 30000.),
+        Err(MarketError::Rejected(msg)) if msg == "notional 90 below min notional 100"
+    ));
+    // 止盈限价超出PERCENT_PRICE
+    assert!(matches!(
+        filters.validate_and_round(&request(1000., 1.1), 30000.),
+        Err(MarketError::Rejected(msg)) if msg.starts_with("limit price 33000")
+    ));
+    assert!(matches!(
+        filters.validate_and_round(
+            &MarketOrderRequest::new("ETHUSDT".to_string(), true, 100., 0.9, 1.1).unwrap(),
+            2000.
+        ),
+        Err(MarketError::NotFound(_))
+    ));
+    assert_eq!(filters.round_qty("BTCUSDT", 0.0337).unwrap(), 0.033);
+}
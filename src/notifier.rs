@@ -0,0 +1,257 @@
+use std::fmt::Debug;
+
+use crossbeam::channel::Sender;
+use serde::Deserialize;
+use tracing::{error, warn};
+
+use crate::error::ConfigError;
+
+pub mod discord;
+pub mod email;
+
+use discord::{DiscordConfig, DiscordNotifier};
+use email::{EmailConfig, EmailNotifier};
+
+pub trait Notifier: Debug + Send + Sync {
+    fn notify(&self, title: &str, message: &str) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+    fn notify(&self, title: &str, message: &str) -> anyhow::Result<()> {
+        warn!("[{}] {}", title, message);
+        Ok(())
+    }
+}
+
+/// 后台发送队列的容量，渠道持续阻塞时丢弃新的通知
+const QUEUE_CAPACITY: usize = 256;
+
+/// 在后台线程中按顺序发送，避免HTTP、SMTP请求阻塞下单路径；队列满时返回错误并丢弃
+#[derive(Debug)]
+pub struct QueuedNotifier {
+    name: String,
+    tx: Sender<(String, String)>,
+}
+
+impl QueuedNotifier {
+    pub fn new(name: &str, notifier: Box<dyn Notifier>) -> Self {
+        Self::with_capacity(name, notifier, QUEUE_CAPACITY)
+    }
+    fn with_capacity(name: &str, notifier: Box<dyn Notifier>, capacity: usize) -> Self {
+        let (tx, rx) = crossbeam::channel::bounded::<(String, String)>(capacity);
+        let thread_name = name.to_string();
+        std::thread::Builder::new()
+            .name(format!("notify-{}", name))
+            .spawn(move || {
+                // 发送端释放后发完队列中剩余的通知再退出
+                for (title, message) in rx.iter() {
+                    if let Err(e) = notifier.notify(&title, &message) {
+                        error!("Notify {} failed: {:?}", thread_name, e);
+                    }
+                }
+            })
+            .unwrap();
+        Self {
+            name: name.to_string(),
+            tx,
+        }
+    }
+}
+
+impl Notifier for QueuedNotifier {
+    fn notify(&self, title: &str, message: &str) -> anyhow::Result<()> {
+        self.tx
+            .try_send((title.to_string(), message.to_string()))
+            .map_err(|e| anyhow::anyhow!("{} queue: {}, dropped {}", self.name, e, title))
+    }
+}
+
+/// 通知的严重程度，决定发送到哪些渠道
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// 成交等日常事件
+    Info,
+    /// 每日报告
+    Report,
+    /// 需要关注，如追加保证金达到上限、接近强平
+    Warning,
+    /// 熔断等需要立即处理的事件
+    Critical,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 4] = [Self::Info, Self::Report, Self::Warning, Self::Critical];
+}
+
+/// 按严重程度将通知分发到各渠道，单个渠道失败不影响其他渠道
+#[derive(Debug, Default)]
+pub struct NotifierRouter {
+    routes: Vec<(Box<dyn Notifier>, Vec<Severity>)>,
+}
+
+impl NotifierRouter {
+    pub fn add(&mut self, notifier: Box<dyn Notifier>, severities: &[Severity]) {
+        self.routes.push((notifier, severities.to_vec()));
+    }
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+    pub fn notify(&self, severity: Severity, title: &str, message: &str) {
+        for (notifier, severities) in self.routes.iter() {
+            if !severities.contains(&severity) {
+                continue;
+            }
+            if let Err(e) = notifier.notify(title, message) {
+                error!("Notify {:?} failed: {:?}", notifier, e);
+            }
+        }
+    }
+}
+
+/// 配置文件中的[notification]部分，各渠道的severities为空时使用默认路由：
+/// 成交 → Discord，每日报告 → 邮件，警告和熔断 → 所有渠道
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// 写入日志的级别，默认全部
+    pub log: Option<Vec<Severity>>,
+    pub discord: Option<DiscordConfig>,
+    pub email: Option<EmailConfig>,
+}
+
+impl NotificationConfig {
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
+        #[derive(Deserialize)]
+        struct File {
+            #[serde(default)]
+            notification: NotificationConfig,
+        }
+        let c = std::fs::read_to_string(path)?;
+        let val: File = toml::from_str(&c)?;
+        val.notification.validate()?;
+        Ok(val.notification)
+    }
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(email) = &self.email {
+            email.validate()?;
+        }
+        Ok(())
+    }
+    pub fn build(&self) -> Result<NotifierRouter, ConfigError> {
+        let mut router = NotifierRouter::default();
+        let log = self.log.as_deref().unwrap_or(&Severity::ALL);
+        router.add(Box::new(LogNotifier), log);
+        if let Some(c) = &self.discord {
+            let severities = c.severities.as_deref().unwrap_or(&[
+                Severity::Info,
+                Severity::Warning,
+                Severity::Critical,
+            ]);
+            let discord = QueuedNotifier::new("discord", Box::new(DiscordNotifier::new(c.clone())));
+            router.add(Box::new(discord), severities);
+        }
+        if let Some(c) = &self.email {
+            let severities = c.severities.as_deref().unwrap_or(&[
+                Severity::Report,
+                Severity::Warning,
+                Severity::Critical,
+            ]);
+            let email = QueuedNotifier::new("email", Box::new(EmailNotifier::new(c.clone())?));
+            router.add(Box::new(email), severities);
+        }
+        Ok(router)
+    }
+}
+
+#[test]
+fn notifier_router_test() {
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+    impl Notifier for Recorder {
+        fn notify(&self, title: &str, _message: &str) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(title.to_string());
+            Ok(())
+        }
+    }
+    let (fills, reports) = (Recorder::default(), Recorder::default());
+    let (fills_log, reports_log) = (fills.0.clone(), reports.0.clone());
+    let mut router = NotifierRouter::default();
+    router.add(
+        Box::new(fills),
+        &[Severity::Info, Severity::Warning, Severity::Critical],
+    );
+    router.add(
+        Box::new(reports),
+        &[Severity::Report, Severity::Warning, Severity::Critical],
+    );
+    router.notify(Severity::Info, "fill", "");
+    router.notify(Severity::Report, "report", "");
+    router.notify(Severity::Warning, "margin", "");
+    assert_eq!(*fills_log.lock().unwrap(), ["fill", "margin"]);
+    assert_eq!(*reports_log.lock().unwrap(), ["report", "margin"]);
+
+    let config: toml::Value = toml::from_str(
+        r#"
+        [notification]
+        log = ["warning", "critical"]
+        [notification.discord]
+        webhook_url = "https://discord.com/api/webhooks/1/abc"
+        [notification.email]
+        smtp_host = "smtp.example.com"
+        username = "bot"
+        password = "secret"
+        from = "bot@example.com"
+        to = ["me@example.com"]
+        severities = ["report"]
+        "#,
+    )
+    .unwrap();
+    let config: NotificationConfig = config["notification"].clone().try_into().unwrap();
+    config.validate().unwrap();
+    assert_eq!(
+        config.log.as_deref(),
+        Some(&[Severity::Warning, Severity::Critical][..])
+    );
+    assert_eq!(config.email.as_ref().unwrap().port, 587);
+    assert!(config.discord.as_ref().unwrap().severities.is_none());
+    assert_eq!(config.build().unwrap().routes.len(), 3);
+}
+
+#[test]
+fn queued_notifier_test() {
+    use crossbeam::channel::Receiver;
+
+    /// 每条通知等待放行后才算发出
+    #[derive(Debug)]
+    struct Blocking(Receiver<()>, Sender<String>);
+    impl Notifier for Blocking {
+        fn notify(&self, title: &str, _message: &str) -> anyhow::Result<()> {
+            self.0.recv()?;
+            self.1.send(title.to_string())?;
+            Ok(())
+        }
+    }
+    let (release_tx, release_rx) = crossbeam::channel::unbounded();
+    let (sent_tx, sent_rx) = crossbeam::channel::unbounded();
+    let queued = QueuedNotifier::with_capacity("test", Box::new(Blocking(release_rx, sent_tx)), 1);
+    // 渠道阻塞时不阻塞调用方
+    queued.notify("a", "").unwrap();
+    while !queued.tx.is_empty() {
+        std::thread::yield_now();
+    }
+    queued.notify("b", "").unwrap();
+    assert!(queued.notify("c", "").is_err());
+    // 释放后按顺序发出，丢弃的不再补发
+    drop(queued);
+    for _ in 0..3 {
+        release_tx.send(()).unwrap();
+    }
+    let sent: Vec<_> = sent_rx.iter().collect();
+    assert_eq!(sent, ["a", "b"]);
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use super::{Notifier, Severity};
+
+/// Discord单条消息的最大长度
+const MAX_CONTENT: usize = 2000;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DiscordConfig {
+    pub webhook_url: String,
+    /// 发送的通知级别，为空时使用默认路由
+    pub severities: Option<Vec<Severity>>,
+}
+
+/// 通过webhook发送到Discord频道
+#[derive(Debug)]
+pub struct DiscordNotifier {
+    config: DiscordConfig,
+    http: reqwest::blocking::Client,
+}
+
+impl DiscordNotifier {
+    pub fn new(config: DiscordConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+        }
+    }
+}
+
+/// 标题加粗，超长时截断
+fn content(title: &str, message: &str) -> String {
+    let content = format!("**{}**\n{}", title, message);
+    if content.chars().count() <= MAX_CONTENT {
+        return content;
+    }
+    let mut truncated: String = content.chars().take(MAX_CONTENT - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+impl Notifier for DiscordNotifier {
+    fn notify(&self, title: &str, message: &str) -> anyhow::Result<()> {
+        self.http
+            .post(&self.config.webhook_url)
+            .json(&serde_json::json!({ "content": content(title, message) }))
+            .send()
+            .context("discord webhook")?
+            .error_for_status()
+            .context("discord webhook")?;
+        Ok(())
+    }
+}
+
+#[test]
+fn discord_content_test() {
+    assert_eq!(content("Fill", "BTCUSDT"), "**Fill**\nBTCUSDT");
+    let long = content("Report", &"x".repeat(3000));
+    assert_eq!(long.chars().count(), MAX_CONTENT);
+    assert!(long.ends_with('…'));
+}
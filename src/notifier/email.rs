@@ -0,0 +1,123 @@
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, Message, SmtpTransport,
+    Transport,
+};
+use serde::Deserialize;
+
+use super::{Notifier, Severity};
+use crate::error::ConfigError;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// true为STARTTLS（通常587端口），false为隐式TLS（通常465端口）
+    #[serde(default = "default_starttls")]
+    pub starttls: bool,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+    /// 发送的通知级别，为空时使用默认路由
+    pub severities: Option<Vec<Severity>>,
+}
+
+fn default_port() -> u16 {
+    587
+}
+
+fn default_starttls() -> bool {
+    true
+}
+
+impl EmailConfig {
+    pub(super) fn validate(&self) -> Result<(), ConfigError> {
+        self.mailboxes().map(|_| ())
+    }
+    fn mailboxes(&self) -> Result<(Mailbox, Vec<Mailbox>), ConfigError> {
+        let parse = |s: &str| {
+            s.parse::<Mailbox>()
+                .map_err(|e| ConfigError::Invalid(format!("email address {:?}: {}", s, e)))
+        };
+        if self.to.is_empty() {
+            return Err(ConfigError::Invalid("email recipients empty".to_string()));
+        }
+        let to = self.to.iter().map(|s| parse(s)).collect::<Result<_, _>>()?;
+        Ok((parse(&self.from)?, to))
+    }
+}
+
+/// 通过SMTP发送邮件
+#[derive(Debug)]
+pub struct EmailNotifier {
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    transport: SmtpTransport,
+}
+
+impl EmailNotifier {
+    pub fn new(config: EmailConfig) -> Result<Self, ConfigError> {
+        let (from, to) = config.mailboxes()?;
+        let builder = if config.starttls {
+            SmtpTransport::starttls_relay(&config.smtp_host)
+        } else {
+            SmtpTransport::relay(&config.smtp_host)
+        }
+        .map_err(|e| ConfigError::Invalid(format!("smtp host {:?}: {}", config.smtp_host, e)))?;
+        let transport = builder
+            .port(config.port)
+            .credentials(Credentials::new(config.username, config.password))
+            .build();
+        Ok(Self {
+            from,
+            to,
+            transport,
+        })
+    }
+    fn message(&self, title: &str, message: &str) -> anyhow::Result<Message> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(format!("[hurribot] {}", title));
+        for to in self.to.iter() {
+            builder = builder.to(to.clone());
+        }
+        Ok(builder.body(message.to_string())?)
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn notify(&self, title: &str, message: &str) -> anyhow::Result<()> {
+        self.transport.send(&self.message(title, message)?)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn email_message_test() {
+    let config = EmailConfig {
+        smtp_host: "smtp.example.com".to_string(),
+        port: 465,
+        starttls: false,
+        username: "bot".to_string(),
+        password: "secret".to_string(),
+        from: "Hurribot <bot@example.com>".to_string(),
+        to: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+        severities: None,
+    };
+    let notifier = EmailNotifier::new(config.clone()).unwrap();
+    let message = String::from_utf8(
+        notifier
+            .message("Daily report", "balance: 100")
+            .unwrap()
+            .formatted(),
+    )
+    .unwrap();
+    assert!(message.contains("Subject: [hurribot] Daily report"));
+    assert!(message.contains("To: a@example.com, b@example.com"));
+    assert!(EmailNotifier::new(EmailConfig {
+        to: vec!["not an address".to_string()],
+        ..config
+    })
+    .is_err());
+}
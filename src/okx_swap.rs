@@ -0,0 +1,250 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use base64::Engine;
+use crossbeam::channel::Receiver;
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use time::{macros::format_description, OffsetDateTime};
+use tracing::{error, info, warn};
+
+use crate::{
+    algorithm::SymbolPrice,
+    binance_futures::SymbolPrices,
+    error::{ConfigError, MarketError, WsError},
+    symbol::SymbolId,
+};
+
+const REST_URL: &str = "https://www.okx.com";
+const PUBLIC_WS_URL: &str = "wss://ws.okx.com:8443/ws/v5/public";
+
+#[derive(Clone, Deserialize)]
+pub struct OkxKeys {
+    pub api_key: String,
+    pub secret_key: String,
+    pub passphrase: String,
+}
+impl OkxKeys {
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
+        let c = std::fs::read_to_string(path)?;
+        let val: Self = toml::from_str(&c)?;
+        Ok(val)
+    }
+}
+opaque_debug::implement!(OkxKeys);
+
+/// 币安格式的币种（BTCUSDT）转为OKX永续合约（BTC-USDT-SWAP）
+pub fn inst_id(symbol: &str) -> String {
+    let base = symbol.strip_suffix("USDT").unwrap_or(symbol);
+    format!("{}-USDT-SWAP", base)
+}
+
+/// OKX永续合约（BTC-USDT-SWAP）转为币安格式的币种（BTCUSDT）
+pub fn symbol_of(inst_id: &str) -> String {
+    inst_id.trim_end_matches("-SWAP").replace('-', "")
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    code: String,
+    msg: String,
+    data: Option<T>,
+}
+
+/// OKX v5 REST客户端
+#[derive(Clone)]
+pub struct OkxClient {
+    keys: OkxKeys,
+    http: reqwest::blocking::Client,
+}
+opaque_debug::implement!(OkxClient);
+
+impl OkxClient {
+    pub fn new(keys: OkxKeys) -> Self {
+        Self {
+            keys,
+            http: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+        }
+    }
+    pub fn get<T: DeserializeOwned>(&self, path: &str, query: &str) -> Result<T, MarketError> {
+        let path = if query.is_empty() {
+            path.to_string()
+        } else {
+            format!("{}?{}", path, query)
+        };
+        self.request(reqwest::Method::GET, &path, String::new())
+    }
+    pub fn post<T: DeserializeOwned>(&self, path: &str, body: Value) -> Result<T, MarketError> {
+        self.request(reqwest::Method::POST, path, body.to_string())
+    }
+    fn request<T: DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: String,
+    ) -> Result<T, MarketError> {
+        let timestamp = OffsetDateTime::now_utc()
+            .format(format_description!(
+                "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z"
+            ))
+            .unwrap();
+        let sign = self.sign(&format!("{}{}{}{}", timestamp, method, path, body));
+        let response = self
+            .http
+            .request(method, format!("{}{}", REST_URL, path))
+            .header("OK-ACCESS-KEY", &self.keys.api_key)
+            .header("OK-ACCESS-SIGN", sign)
+            .header("OK-ACCESS-TIMESTAMP", timestamp)
+            .header("OK-ACCESS-PASSPHRASE", &self.keys.passphrase)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .and_then(|r| r.json::<Response<T>>())
+            .map_err(|e| MarketError::Request {
+                context: path.to_string(),
+                msg: e.to_string(),
+            })?;
+        if response.code != "0" {
+            return Err(MarketError::Okx {
+                context: path.to_string(),
+                code: response.code,
+                msg: response.msg,
+            });
+        }
+        response.data.ok_or(MarketError::NotFound(path.to_string()))
+    }
+    fn sign(&self, message: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.keys.secret_key.as_bytes()).unwrap();
+        mac.update(message.as_bytes());
+        base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OkxWsConnection {
+    args: Vec<Value>,
+}
+
+impl OkxWsConnection {
+    /// 订阅币种的标记价格，symbols为币安格式
+    pub fn run_price_info(
+        symbols: &[String],
+    ) -> (Receiver<SymbolPrice>, SymbolPrices, JoinHandle<()>) {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct MarkPrice {
+            inst_id: String,
+            mark_px: String,
+            ts: String,
+        }
+        let prices = Arc::new(DashMap::new());
+        let prices_c = prices.clone();
+        let (price_tx, price_rx) = crossbeam::channel::unbounded();
+        let handler = move |msg: Value| {
+            let Some(data) = msg.get("data") else {
+                return;
+            };
+            let Ok(v) = serde_json::from_value::<Vec<MarkPrice>>(data.clone()) else {
+                return;
+            };
+            for p in v {
+                let mark_price: f64 = p.mark_px.parse().unwrap_or_default();
+                let s = SymbolPrice {
+                    symbol: SymbolId::intern_cached(&symbol_of(&p.inst_id)),
+                    mark_price,
+                    price_index: mark_price,
+                    time: p.ts.parse().unwrap_or_default(),
+                    funding_rate: 0.,
+                };
+                prices_c.insert(s.symbol, s.clone());
+                price_tx.send(s).ok();
+            }
+        };
+        let conn = OkxWsConnection {
+            args: symbols
+                .iter()
+                .map(|s| json!({"channel": "mark-price", "instId": inst_id(s)}))
+                .collect(),
+        };
+        let h = conn.run(handler, Arc::new(AtomicBool::new(true)));
+        (price_rx, prices, h)
+    }
+    pub fn run<F>(self, mut handler: F, running: Arc<AtomicBool>) -> JoinHandle<()>
+    where
+        F: FnMut(Value) + Send + 'static,
+    {
+        std::thread::spawn(move || {
+            while running.load(Relaxed) {
+                match self.event_loop(&mut handler, &running) {
+                    Ok(_) => break,
+                    Err(e) if e.is_retryable() => {
+                        warn!("OKX connection lost, reconnecting...: {}", e);
+                        std::thread::sleep(Duration::from_secs(1));
+                    }
+                    Err(e) => {
+                        error!("OKX event loop error, exiting...: {}", e);
+                        break;
+                    }
+                }
+            }
+            running.store(false, Relaxed);
+        })
+    }
+    fn event_loop<F>(&self, handler: &mut F, running: &AtomicBool) -> Result<(), WsError>
+    where
+        F: FnMut(Value),
+    {
+        let (mut socket, _) =
+            tungstenite::connect(PUBLIC_WS_URL).map_err(|e| WsError::Connection(e.to_string()))?;
+        let subscribe = json!({"op": "subscribe", "args": self.args});
+        socket
+            .send(tungstenite::Message::text(subscribe.to_string()))
+            .map_err(|e| WsError::Connection(e.to_string()))?;
+        info!("OKX subscribed: {}", subscribe);
+        while running.load(Relaxed) {
+            let msg = socket
+                .read()
+                .map_err(|e| WsError::Disconnected(e.to_string()))?;
+            match msg {
+                tungstenite::Message::Text(text) => {
+                    match serde_json::from_str::<Value>(text.as_ref()) {
+                        Ok(v) if v.get("event").is_some_and(|e| e == "error") => {
+                            return Err(WsError::Other(text.to_string()));
+                        }
+                        Ok(v) => handler(v),
+                        Err(e) => warn!("OKX message parse failed: {}", e),
+                    }
+                }
+                tungstenite::Message::Ping(p) => {
+                    socket
+                        .send(tungstenite::Message::Pong(p))
+                        .map_err(|e| WsError::Disconnected(e.to_string()))?;
+                }
+                tungstenite::Message::Close(_) => {
+                    return Err(WsError::Disconnected("closed by server".to_string()));
+                }
+                _ => {}
+            }
+        }
+        socket.close(None).ok();
+        Ok(())
+    }
+}
+
+#[test]
+fn okx_symbol_test() {
+    assert_eq!(inst_id("BTCUSDT"), "BTC-USDT-SWAP");
+    assert_eq!(symbol_of("1000PEPE-USDT-SWAP"), "1000PEPEUSDT");
+}
@@ -0,0 +1,312 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use binance::futures::model::{KlineSummaries, KlineSummary};
+use parking_lot::RwLock;
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+use crate::{
+    binance_futures::{subscriptions::Subscriptions, BinanceKeys, Clients, SymbolPrices},
+    candle::CandleData,
+    error::{BinanceResultExt, MarketError},
+    symbol::{filter::SymbolFilter, SymbolId},
+};
+
+#[derive(Debug, Clone)]
+pub struct ScreenerConfig {
+    /// 筛选间隔
+    pub interval: Duration,
+    /// 观察列表长度
+    pub top_n: usize,
+    /// 24h最小成交额（USDT）
+    pub min_quote_volume: f64,
+    /// 24h最小涨幅
+    pub min_change: f64,
+    /// 最大资金费率（做多需支付资金费率）
+    pub max_funding_rate: f64,
+    /// 当前上涨段最小涨幅
+    pub min_increase: f64,
+    /// 用于计算上涨段的k线间隔与数量
+    pub kline_interval: String,
+    pub kline_limit: u16,
+}
+
+impl Default for ScreenerConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15 * 60),
+            top_n: 10,
+            min_quote_volume: 50_000_000.,
+            min_change: 0.05,
+            max_funding_rate: 0.001,
+            min_increase: 0.2,
+            kline_interval: "1h".to_string(),
+            kline_limit: 500,
+        }
+    }
+}
+
+/// 上涨段判定（来自roll_bull_finder）：从最低点开始记录最高点与最大回撤，
+/// 回撤超过 min(涨幅 * 0.5 + 0.1, 0.5) 时该上涨段结束
+#[derive(Debug, Clone, Default)]
+pub struct RollFinder {
+    entry_low: f64,
+    max_high: f64,
+    max_draw: f64,
+    started: bool,
+}
+
+impl RollFinder {
+    /// 返回上涨段结束时的（涨幅，最大回撤）
+    pub fn update(&mut self, candle: &CandleData) -> Option<(f64, f64)> {
+        if !self.started || candle.low < self.entry_low {
+            self.entry_low = candle.low;
+            self.max_high = candle.high;
+            self.max_draw = 0.;
+            self.started = true;
+            return None;
+        }
+        self.max_high = self.max_high.max(candle.high);
+        self.max_draw = self.max_draw.max(1. - candle.low / self.max_high);
+        let increase = self.increase();
+        if self.max_draw > Self::draw_limit(increase) {
+            self.started = false;
+            return Some((increase, self.max_draw));
+        }
+        None
+    }
+    pub fn increase(&self) -> f64 {
+        if self.started {
+            self.max_high / self.entry_low - 1.
+        } else {
+            0.
+        }
+    }
+    pub fn max_draw(&self) -> f64 {
+        self.max_draw
+    }
+    /// 当前是否处于未被回撤破坏的上涨段中
+    pub fn is_alive(&self) -> bool {
+        self.started
+    }
+    pub fn draw_limit(increase: f64) -> f64 {
+        (increase * 0.5 + 0.1).min(0.5)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SymbolStats {
+    pub symbol: String,
+    /// 24h涨跌幅
+    pub change: f64,
+    /// 24h成交额
+    pub quote_volume: f64,
+    pub funding_rate: f64,
+    /// 当前上涨段涨幅，None表示未计算或上涨段已结束
+    pub increase: Option<f64>,
+    pub max_draw: f64,
+}
+
+/// 过滤并按24h涨幅排序，返回前top_n个
+pub fn rank(config: &ScreenerConfig, mut stats: Vec<SymbolStats>) -> Vec<SymbolStats> {
+    stats.retain(|s| {
+        s.quote_volume >= config.min_quote_volume
+            && s.change >= config.min_change
+            && s.funding_rate <= config.max_funding_rate
+            && s.increase.is_some_and(|i| i >= config.min_increase)
+    });
+    stats.sort_by(|a, b| b.change.total_cmp(&a.change));
+    stats.truncate(config.top_n);
+    stats
+}
+
+impl From<&KlineSummary> for CandleData {
+    fn from(k: &KlineSummary) -> Self {
+        let time =
+            |ms: i64| OffsetDateTime::from_unix_timestamp_nanos(ms as i128 * 1_000_000).unwrap();
+        Self {
+            open: k.open,
+            close: k.close,
+            high: k.high,
+            low: k.low,
+            volume: k.volume,
+            open_time: time(k.open_time),
+            close_time: time(k.close_time),
+        }
+    }
+}
+
+/// 供roll策略使用的实时观察列表
+#[derive(Debug, Clone, Default)]
+pub struct Watchlist(Arc<RwLock<Vec<String>>>);
+
+impl Watchlist {
+    pub fn symbols(&self) -> Vec<String> {
+        self.0.read().clone()
+    }
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.0.read().iter().any(|s| s == symbol)
+    }
+    pub(crate) fn set(&self, symbols: Vec<String>) {
+        *self.0.write() = symbols;
+    }
+}
+
+/// 固定的观察列表
+impl From<Vec<String>> for Watchlist {
+    fn from(symbols: Vec<String>) -> Self {
+        Self(Arc::new(RwLock::new(symbols)))
+    }
+}
+
+/// 币种到数据流名的映射，如depth_stream
+pub type StreamName = fn(&str) -> String;
+
+#[derive(Debug)]
+pub struct Screener {
+    config: ScreenerConfig,
+    clients: Clients,
+    prices: SymbolPrices,
+    /// 跟随观察列表的行情订阅及币种到数据流名的映射
+    follow: Vec<(Subscriptions, StreamName)>,
+    filter: Arc<SymbolFilter>,
+}
+
+impl Screener {
+    pub fn new(binance_keys: BinanceKeys, prices: SymbolPrices, config: ScreenerConfig) -> Self {
+        Self {
+            config,
+            clients: Clients::new(binance_keys),
+            prices,
+            follow: vec![],
+            filter: Arc::default(),
+        }
+    }
+    /// 与行情和BinanceMarket共用过滤规则时，每轮筛选顺带更新其24h成交额
+    pub fn with_filter(mut self, filter: Arc<SymbolFilter>) -> Self {
+        self.filter = filter;
+        self
+    }
+    /// 观察列表更新时同步修改订阅，如 follow(subs, depth_stream)
+    pub fn follow(mut self, subscriptions: Subscriptions, stream: StreamName) -> Self {
+        self.follow.push((subscriptions, stream));
+        self
+    }
+    pub fn screen(&self) -> Result<Vec<SymbolStats>, MarketError> {
+        let mut stats = Vec::new();
+        let all = self
+            .clients
+            .market
+            .get_all_24h_price_stats()
+            .market_context("get 24h price stats")?;
+        if self.filter.needs_volumes() {
+            self.filter
+                .set_volumes(all.iter().map(|s| (s.symbol.clone(), s.quote_volume)));
+        }
+        for s in all {
+            if !self.filter.allows_with_volume(&s.symbol, s.quote_volume) {
+                continue;
+            }
+            let funding_rate = SymbolId::get(&s.symbol)
+                .and_then(|id| self.prices.get(&id))
+                .map(|p| p.funding_rate)
+                .unwrap_or_default();
+            stats.push(SymbolStats {
+                change: s.price_change_percent.parse::<f64>().unwrap_or_default() / 100.,
+                quote_volume: s.quote_volume,
+                funding_rate,
+                symbol: s.symbol,
+                increase: None,
+                max_draw: 0.,
+            });
+        }
+        // 只对通过基础过滤的币种拉取k线
+        stats.retain(|s| {
+            s.quote_volume >= self.config.min_quote_volume
+                && s.change >= self.config.min_change
+                && s.funding_rate <= self.config.max_funding_rate
+        });
+        for s in stats.iter_mut() {
+            let KlineSummaries::AllKlineSummaries(klines) = self
+                .clients
+                .market
+                .get_klines(
+                    s.symbol.as_str(),
+                    self.config.kline_interval.as_str(),
+                    self.config.kline_limit,
+                    None,
+                    None,
+                )
+                .market_context(&format!("get klines of {}", s.symbol))?;
+            let mut finder = RollFinder::default();
+            for k in klines.iter() {
+                finder.update(&k.into());
+            }
+            if finder.is_alive() {
+                s.increase = Some(finder.increase());
+                s.max_draw = finder.max_draw();
+            }
+        }
+        Ok(rank(&self.config, stats))
+    }
+    pub fn run(self, running: Arc<AtomicBool>) -> (Watchlist, JoinHandle<()>) {
+        let watchlist = Watchlist::default();
+        let watchlist_c = watchlist.clone();
+        let h = std::thread::spawn(move || {
+            while running.load(Relaxed) {
+                match self.screen() {
+                    Ok(stats) => {
+                        info!("Screener watchlist updated: {:?}", stats);
+                        let symbols: Vec<String> = stats.into_iter().map(|s| s.symbol).collect();
+                        for (subscriptions, stream) in self.follow.iter() {
+                            subscriptions.set(symbols.iter().map(|s| stream(s)).collect());
+                        }
+                        watchlist_c.set(symbols);
+                    }
+                    Err(e) => {
+                        error!("Screen failed: {}", e);
+                    }
+                }
+                std::thread::sleep(self.config.interval);
+            }
+        });
+        (watchlist, h)
+    }
+}
+
+#[test]
+fn screener_rank_test() {
+    let config = ScreenerConfig {
+        top_n: 2,
+        ..Default::default()
+    };
+    let stat = |symbol: &str, change: f64, funding_rate: f64, increase: Option<f64>| SymbolStats {
+        symbol: symbol.to_string(),
+        change,
+        quote_volume: 1e8,
+        funding_rate,
+        increase,
+        max_draw: 0.1,
+    };
+    let ranked = rank(
+        &config,
+        vec![
+            stat("AUSDT", 0.1, 0., Some(0.5)),
+            stat("BUSDT", 0.3, 0., Some(0.5)),
+            stat("CUSDT", 0.5, 0.01, Some(0.5)),
+            stat("DUSDT", 0.4, 0., None),
+            stat("EUSDT", 0.2, 0., Some(0.3)),
+            // 缺少开盘价等原因算出的NaN涨幅不参与排序
+            stat("FUSDT", f64::NAN, 0., Some(0.5)),
+        ],
+    );
+    let symbols: Vec<_> = ranked.iter().map(|s| s.symbol.as_str()).collect();
+    assert_eq!(symbols, vec!["BUSDT", "EUSDT"]);
+}
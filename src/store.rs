@@ -0,0 +1,338 @@
+use parking_lot::Mutex;
+use rusqlite::{params, Connection};
+
+use crate::{
+    binance_futures::income::{Income, IncomeTotals, IncomeType},
+    controller::report::AccountSnapshot,
+    error::DataError,
+    strategy::state::StrategyState,
+    tax::Fill,
+};
+
+/// SQLite本地存储
+#[derive(Debug)]
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn open(path: &str) -> Result<Self, DataError> {
+        Self::init(Connection::open(path)?)
+    }
+    pub fn open_in_memory() -> Result<Self, DataError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+    fn init(conn: Connection) -> Result<Self, DataError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS snapshots (
+                time INTEGER PRIMARY KEY,
+                total_balance REAL NOT NULL,
+                cross_balance REAL NOT NULL,
+                realized_pnl REAL NOT NULL,
+                unrealized_pnl REAL NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS snapshot_positions (
+                time INTEGER NOT NULL,
+                symbol TEXT NOT NULL,
+                amount REAL NOT NULL,
+                entry_price REAL NOT NULL,
+                mark_price REAL NOT NULL,
+                PRIMARY KEY (time, symbol)
+            );
+            CREATE TABLE IF NOT EXISTS strategy_states (
+                name TEXT PRIMARY KEY,
+                version INTEGER NOT NULL,
+                time INTEGER NOT NULL,
+                data TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS incomes (
+                tran_id INTEGER NOT NULL,
+                income_type TEXT NOT NULL,
+                symbol TEXT NOT NULL,
+                asset TEXT NOT NULL,
+                income REAL NOT NULL,
+                time INTEGER NOT NULL,
+                PRIMARY KEY (tran_id, income_type)
+            );
+            CREATE TABLE IF NOT EXISTS fills (
+                symbol TEXT NOT NULL,
+                trade_id INTEGER NOT NULL,
+                time INTEGER NOT NULL,
+                qty REAL NOT NULL,
+                price REAL NOT NULL,
+                fee REAL NOT NULL,
+                PRIMARY KEY (symbol, trade_id)
+            );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+    pub fn insert_snapshot(&self, snapshot: &AccountSnapshot) -> Result<(), DataError> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO snapshots VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                snapshot.time as i64,
+                snapshot.total_balance,
+                snapshot.cross_balance,
+                snapshot.realized_pnl,
+                snapshot.unrealized_pnl()
+            ],
+        )?;
+        for p in snapshot.positions.iter() {
+            tx.execute(
+                "INSERT OR REPLACE INTO snapshot_positions VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    snapshot.time as i64,
+                    p.symbol,
+                    p.amount,
+                    p.entry_price,
+                    p.mark_price
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    /// 每个策略只保留最新的状态快照
+    pub fn save_strategy_state(&self, name: &str, state: &StrategyState) -> Result<(), DataError> {
+        self.conn.lock().execute(
+            "INSERT OR REPLACE INTO strategy_states VALUES (?1, ?2, ?3, ?4)",
+            params![
+                name,
+                state.version,
+                state.time as i64,
+                state.data.to_string()
+            ],
+        )?;
+        Ok(())
+    }
+    pub fn load_strategy_state(&self, name: &str) -> Result<Option<StrategyState>, DataError> {
+        let conn = self.conn.lock();
+        let mut stmt =
+            conn.prepare("SELECT version, time, data FROM strategy_states WHERE name = ?1")?;
+        let mut rows = stmt.query([name])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        let data: String = row.get(2)?;
+        Ok(Some(StrategyState {
+            version: row.get(0)?,
+            time: row.get::<_, i64>(1)? as u64,
+            data: serde_json::from_str(&data).map_err(|e| DataError::parse("strategy state", e))?,
+        }))
+    }
+    /// 导入资金流水，已存在的记录忽略，返回新增条数
+    pub fn insert_incomes(&self, incomes: &[Income]) -> Result<usize, DataError> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        let mut inserted = 0;
+        for i in incomes {
+            inserted += tx.execute(
+                "INSERT OR IGNORE INTO incomes VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    i.tran_id as i64,
+                    i.income_type,
+                    i.symbol,
+                    i.asset,
+                    i.income,
+                    i.time as i64
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+    /// (start, end]内asset的资金流水，按时间升序
+    pub fn incomes(&self, asset: &str, start: u64, end: u64) -> Result<Vec<Income>, DataError> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT tran_id, income_type, symbol, income, time FROM incomes
+            WHERE asset = ?1 AND time > ?2 AND time <= ?3 ORDER BY time",
+        )?;
+        let mut rows = stmt.query(params![asset, start as i64, end as i64])?;
+        let mut incomes = vec![];
+        while let Some(row) = rows.next()? {
+            incomes.push(Income {
+                tran_id: row.get::<_, i64>(0)? as u64,
+                income_type: row.get(1)?,
+                symbol: row.get(2)?,
+                asset: asset.to_string(),
+                income: row.get(3)?,
+                time: row.get::<_, i64>(4)? as u64,
+            });
+        }
+        Ok(incomes)
+    }
+    /// 记录一笔成交，同一成交重复推送时忽略
+    pub fn insert_fill(&self, trade_id: u64, fill: &Fill) -> Result<(), DataError> {
+        self.conn.lock().execute(
+            "INSERT OR IGNORE INTO fills VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                fill.symbol,
+                trade_id as i64,
+                fill.time as i64,
+                fill.qty,
+                fill.price,
+                fill.fee
+            ],
+        )?;
+        Ok(())
+    }
+    /// (start, end]内的成交，按时间升序
+    pub fn fills(&self, start: u64, end: u64) -> Result<Vec<Fill>, DataError> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT symbol, time, qty, price, fee FROM fills
+            WHERE time > ?1 AND time <= ?2 ORDER BY time, trade_id",
+        )?;
+        let mut rows = stmt.query(params![start as i64, end as i64])?;
+        let mut fills = vec![];
+        while let Some(row) = rows.next()? {
+            fills.push(Fill {
+                symbol: row.get(0)?,
+                time: row.get::<_, i64>(1)? as u64,
+                qty: row.get(2)?,
+                price: row.get(3)?,
+                fee: row.get(4)?,
+            });
+        }
+        Ok(fills)
+    }
+    /// 最近一条流水的时间
+    pub fn last_income_time(&self) -> Result<Option<u64>, DataError> {
+        let conn = self.conn.lock();
+        let time: Option<i64> =
+            conn.query_row("SELECT MAX(time) FROM incomes", [], |row| row.get(0))?;
+        Ok(time.map(|t| t as u64))
+    }
+    /// (start, end]内按类型汇总的asset流水
+    pub fn income_totals(
+        &self,
+        asset: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<IncomeTotals, DataError> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT income_type, SUM(income) FROM incomes
+            WHERE asset = ?1 AND time > ?2 AND time <= ?3 GROUP BY income_type",
+        )?;
+        let mut rows = stmt.query(params![asset, start as i64, end as i64])?;
+        let mut totals = IncomeTotals::default();
+        while let Some(row) = rows.next()? {
+            let kind: String = row.get(0)?;
+            totals.add(IncomeType::parse(&kind), row.get(1)?);
+        }
+        Ok(totals)
+    }
+    /// 最近一次快照的时间
+    pub fn last_snapshot_time(&self) -> Result<Option<u64>, DataError> {
+        let conn = self.conn.lock();
+        let time: Option<i64> =
+            conn.query_row("SELECT MAX(time) FROM snapshots", [], |row| row.get(0))?;
+        Ok(time.map(|t| t as u64))
+    }
+    /// 最近一次快照的总资金
+    pub fn last_balance(&self) -> Result<Option<f64>, DataError> {
+        let conn = self.conn.lock();
+        let mut stmt =
+            conn.prepare("SELECT total_balance FROM snapshots ORDER BY time DESC LIMIT 1")?;
+        let mut rows = stmt.query([])?;
+        Ok(match rows.next()? {
+            Some(row) => Some(row.get(0)?),
+            None => None,
+        })
+    }
+}
+
+#[test]
+fn store_test() {
+    use crate::controller::report::PositionSnapshot;
+
+    let store = Store::open_in_memory().unwrap();
+    assert_eq!(store.last_balance().unwrap(), None);
+    let snapshot = AccountSnapshot {
+        time: 1,
+        total_balance: 100.,
+        positions: vec![PositionSnapshot {
+            symbol: "BTCUSDT".to_string(),
+            amount: 1.,
+            entry_price: 10.,
+            mark_price: 12.,
+            close_fee: 0.,
+        }],
+        ..Default::default()
+    };
+    store.insert_snapshot(&snapshot).unwrap();
+    store
+        .insert_snapshot(&AccountSnapshot {
+            time: 2,
+            total_balance: 120.,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(store.last_balance().unwrap(), Some(120.));
+    assert_eq!(store.load_strategy_state("0:roll").unwrap(), None);
+    let state = StrategyState {
+        version: 1,
+        time: 3,
+        data: serde_json::json!({"level": 2}),
+    };
+    store.save_strategy_state("0:roll", &state).unwrap();
+    assert_eq!(store.load_strategy_state("0:roll").unwrap(), Some(state));
+    assert_eq!(store.last_snapshot_time().unwrap(), Some(2));
+}
+
+#[test]
+fn store_income_test() {
+    let store = Store::open_in_memory().unwrap();
+    assert_eq!(store.last_income_time().unwrap(), None);
+    let income = |tran_id, income_type: &str, income, time| Income {
+        symbol: "BTCUSDT".into(),
+        income_type: income_type.into(),
+        income,
+        asset: "USDT".into(),
+        time,
+        tran_id,
+    };
+    let incomes = [
+        income(1, "REALIZED_PNL", 10., 100),
+        income(1, "COMMISSION", -0.5, 100),
+        income(2, "FUNDING_FEE", -0.1, 200),
+        income(3, "REALIZED_PNL", -4., 300),
+    ];
+    assert_eq!(store.insert_incomes(&incomes).unwrap(), 4);
+    // 分页重叠的记录不重复导入
+    assert_eq!(store.insert_incomes(&incomes[2..]).unwrap(), 0);
+    assert_eq!(store.last_income_time().unwrap(), Some(300));
+    let totals = store.income_totals("USDT", 0, 200).unwrap();
+    assert_eq!(totals.realized_pnl, 10.);
+    assert_eq!(totals.commission, -0.5);
+    assert_eq!(totals.funding_fee, -0.1);
+    assert_eq!(
+        store.income_totals("USDT", 200, 300).unwrap().realized_pnl,
+        -4.
+    );
+    let funding = store.incomes("USDT", 100, 300).unwrap();
+    assert_eq!(funding.len(), 2);
+    assert_eq!(funding[0], incomes[2]);
+}
+
+#[test]
+fn store_fill_test() {
+    let store = Store::open_in_memory().unwrap();
+    let fill = Fill {
+        time: 100,
+        symbol: "BTCUSDT".to_string(),
+        qty: -0.5,
+        price: 30000.,
+        fee: 6.,
+    };
+    store.insert_fill(7, &fill).unwrap();
+    store.insert_fill(7, &fill).unwrap();
+    assert_eq!(store.fills(0, 100).unwrap(), [fill]);
+    assert!(store.fills(100, 200).unwrap().is_empty());
+}
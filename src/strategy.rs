@@ -1,26 +1,156 @@
-use std::fmt::Debug;
+use std::{any::Any, fmt::Debug};
 
+use serde::Serialize;
 
-use crate::{algorithm::{ SymbolPrice}, controller::Order};
+use crate::{
+    algorithm::{KlineData, SignalData, SymbolPrice},
+    controller::{report::PositionSnapshot, throttle::ThrottleStats, Order},
+    error::{DataError, MarketError},
+    market::TimeInForce,
+};
 
+pub mod breakout;
+pub mod pairs;
 pub mod roll;
+pub mod roll_judge;
+pub mod rotation;
+pub mod session;
+pub mod state;
+pub mod stop;
 
-pub trait Strategy: Debug + Send + Sync {
+use state::StrategyState;
+
+/// 由所有Strategy自动实现，用于从trait对象还原具体类型
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Strategy + 'static> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub trait Strategy: AsAny + Debug + Send + Sync {
     fn notify(&self, order_return: StrategyOrderReturn);
     fn update(&self, price: &SymbolPrice) -> Option<StrategyOrderRequest>;
+    /// 接收算法产生的信号（如波动率区间变化），默认忽略
+    #[allow(unused_variables)]
+    fn update_signal(&self, signal: &SignalData) {}
+    /// 接收已收盘的k线，默认忽略
+    #[allow(unused_variables)]
+    fn update_kline(&self, kline: &KlineData) -> Option<StrategyOrderRequest> {
+        None
+    }
+    /// 关注的币种，只会收到这些币种的行情；None为全部币种
+    fn symbols(&self) -> Option<Vec<String>> {
+        None
+    }
+    /// 默认为类型名
+    fn name(&self) -> String {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name).to_string()
+    }
+    /// 策略参数（名称，值），用于状态报告
+    fn params(&self) -> Vec<(String, String)> {
+        vec![]
+    }
+    /// 用于崩溃恢复的状态快照，None为无状态
+    #[allow(unused_variables)]
+    fn snapshot(&self, time: u64) -> Option<Result<StrategyState, DataError>> {
+        None
+    }
+    /// 启动时恢复最近的快照，未实现snapshot的策略有快照时报错
+    #[allow(unused_variables)]
+    fn restore(&self, state: StrategyState) -> Result<(), DataError> {
+        Err(DataError::parse(
+            "strategy state",
+            format!("{} does not support snapshots", self.name()),
+        ))
+    }
+}
+
+impl dyn Strategy {
+    pub fn downcast_ref<T: Strategy + 'static>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
+
+/// 运行中策略的状态，由Controller汇总
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StrategyStatus {
+    pub index: usize,
+    pub name: String,
+    /// None为全部币种
+    pub symbols: Option<Vec<String>>,
+    /// 被熔断、手动暂停或处于计划的停用时段
+    pub paused: bool,
+    /// 归属于该策略的持仓
+    pub positions: Vec<PositionSnapshot>,
+    /// 扣除手续费后的已实现盈亏
+    pub realized_pnl: f64,
+    pub params: Vec<(String, String)>,
+    /// 被冷却和频率限制拦截的开仓次数
+    pub throttled: ThrottleStats,
+}
+
+impl std::fmt::Display for StrategyStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Strategy {} {}", self.index, self.name)?;
+        if self.paused {
+            write!(f, " (paused)")?;
+        }
+        if !self.params.is_empty() {
+            let params: Vec<String> = self
+                .params
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            write!(f, " [{}]", params.join(", "))?;
+        }
+        write!(f, ", realized pnl: {:.2}", self.realized_pnl)?;
+        if self.throttled.total() > 0 {
+            write!(f, ", {}", self.throttled)?;
+        }
+        if let Some(symbols) = &self.symbols {
+            write!(f, ", symbols: {}", symbols.join(","))?;
+        }
+        for p in self.positions.iter() {
+            write!(f, "\n  {} {} @ {}", p.symbol, p.amount, p.entry_price)?;
+        }
+        Ok(())
+    }
 }
 
 pub struct StrategyOrderReturn {
     pub request_id: u64,
-    pub result: anyhow::Result<Order>,
+    pub result: Result<Order, MarketError>,
 }
 
+#[derive(Debug)]
 pub struct StrategyOrderRequest {
     pub request_id: u64,
     pub symbol: String,
+    /// 占子账户资金的比例，0为平掉symbol（属于组合单时为全部腿）的持仓
     pub position: f64,
     /// 0 < stop_loss < 1
     pub stop_loss: f64,
     /// take_profit > 1
     pub take_profit: f64,
+    /// 开仓改为以当前价挂出的限价单，None为市价开仓；Gtx时止盈单同样只做maker
+    pub time_in_force: Option<TimeInForce>,
+    /// 止损限价单相对触发价的偏移比例，None为市价止损
+    pub stop_limit_offset: Option<f64>,
+    /// 同时卖出的对冲腿，组成组合单；None为单币种开仓
+    pub hedge: Option<HedgeLeg>,
+    /// 单币种开仓的杠杆，None为市场的默认杠杆
+    pub leverage: Option<u8>,
+}
+
+/// 组合单的对冲腿，方向与symbol相反
+#[derive(Debug, Clone, PartialEq)]
+pub struct HedgeLeg {
+    pub symbol: String,
+    /// symbol与对冲腿的价值比
+    pub ratio: f64,
 }
@@ -0,0 +1,354 @@
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    roll_judge::RollJudge,
+    state::{Stateful, StrategyState},
+    stop::StopPlacer,
+    Strategy, StrategyOrderRequest, StrategyOrderReturn,
+};
+use crate::{
+    algorithm::{KlineData, SymbolPrice},
+    binance_futures::positioning::PositioningFeed,
+    candle::CandleData,
+    error::DataError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BreakoutConfig {
+    /// 收盘突破之前period根k线的最高价（做空为最低价）时入场
+    pub period: usize,
+    /// 突破k线的成交量至少为之前period根平均成交量的倍数，0为不过滤
+    pub volume_factor: f64,
+    pub atr_period: usize,
+    /// 移动止损与最有利价格的距离，为ATR的倍数
+    pub atr_multiplier: f64,
+    /// 占资金的比例
+    pub position: f64,
+    /// 止盈比例，主要依靠移动止损离场，止盈设得较远
+    pub take_profit: f64,
+    /// 突破时持仓量在open_interest_window内的增长比例至少为该值，确认有新资金入场；
+    /// None为不过滤，设置后缺少持仓量数据时不开仓
+    pub min_open_interest_change: Option<f64>,
+    pub open_interest_window: time::Duration,
+    /// 止损距离的计算方式（见StopConfig），None为ATR的atr_multiplier倍
+    pub stop: Option<StopPlacer>,
+}
+
+impl Default for BreakoutConfig {
+    fn default() -> Self {
+        Self {
+            period: 20,
+            volume_factor: 1.5,
+            atr_period: 14,
+            atr_multiplier: 3.,
+            position: 0.2,
+            take_profit: 3.,
+            min_open_interest_change: None,
+            open_interest_window: time::Duration::hours(1),
+            stop: None,
+        }
+    }
+}
+
+impl BreakoutConfig {
+    /// 窗口长度足够计算突破和止损距离
+    pub fn judge(&self) -> RollJudge {
+        let lookback = self.stop.map_or(self.atr_period + 1, |s| s.lookback());
+        RollJudge::new((self.period + 1).max(lookback))
+    }
+    /// judge的最新一根k线是否为入场信号
+    pub fn entry(&self, judge: &RollJudge, is_bull: bool) -> bool {
+        let Some(latest) = judge.latest() else {
+            return false;
+        };
+        judge.breaks_out(self.period, is_bull)
+            && latest.volume >= self.volume_factor * judge.average_volume(self.period)
+    }
+    /// 持仓量变化是否满足过滤条件
+    pub fn confirms(&self, open_interest_change: Option<f64>) -> bool {
+        match self.min_open_interest_change {
+            None => true,
+            Some(min) => open_interest_change.is_some_and(|c| c >= min),
+        }
+    }
+    /// 当前的止损距离（价格），price为最新收盘价
+    pub fn stop_distance(&self, judge: &RollJudge, price: f64, leverage: f64) -> f64 {
+        match self.stop {
+            Some(stop) => stop.distance(judge, price, leverage) * price,
+            None => judge.atr(self.atr_period) * self.atr_multiplier,
+        }
+    }
+}
+
+/// 按ATR距离跟随最有利价格的止损，只向有利方向移动
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrailingStop {
+    pub is_bull: bool,
+    pub price: f64,
+}
+
+impl TrailingStop {
+    pub fn new(is_bull: bool, entry: f64, distance: f64) -> Self {
+        let price = if is_bull {
+            entry - distance
+        } else {
+            entry + distance
+        };
+        Self { is_bull, price }
+    }
+    /// k线触及止损时返回成交价，跳空越过止损价时按开盘价成交
+    pub fn hit(&self, candle: &CandleData) -> Option<f64> {
+        if self.is_bull && candle.low <= self.price {
+            Some(self.price.min(candle.open))
+        } else if !self.is_bull && candle.high >= self.price {
+            Some(self.price.max(candle.open))
+        } else {
+            None
+        }
+    }
+    /// 以k线的最高价（做空为最低价）减去distance移动止损
+    pub fn trail(&mut self, candle: &CandleData, distance: f64) {
+        if self.is_bull {
+            self.price = self.price.max(candle.high - distance);
+        } else {
+            self.price = self.price.min(candle.low + distance);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SymbolState {
+    judge: RollJudge,
+    stop: Option<TrailingStop>,
+}
+
+/// 各币种的k线窗口和移动止损
+impl Stateful for HashMap<String, SymbolState> {
+    const STATE_VERSION: u32 = 1;
+    type State = HashMap<String, SymbolState>;
+    fn save_state(&self) -> Self::State {
+        self.clone()
+    }
+    fn load_state(&mut self, state: Self::State) -> Result<(), DataError> {
+        *self = state;
+        Ok(())
+    }
+}
+
+/// 实盘突破策略，由已收盘的k线驱动；Controller开仓方向固定为买入，因此只做多
+#[derive(Debug)]
+pub struct BreakoutStrategy {
+    symbols: Vec<String>,
+    config: BreakoutConfig,
+    states: Mutex<HashMap<String, SymbolState>>,
+    /// 未返回结果的开仓（request_id，币种）
+    pending: Mutex<HashMap<u64, String>>,
+    /// 持仓量数据，配置了min_open_interest_change时使用
+    positioning: Option<Arc<PositioningFeed>>,
+    /// 开仓杠杆，None为市场的默认杠杆，止损距离按1倍杠杆截断
+    leverage: Option<u8>,
+}
+
+impl BreakoutStrategy {
+    pub fn new(symbols: Vec<String>, config: BreakoutConfig) -> Self {
+        Self {
+            symbols,
+            config,
+            states: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            positioning: None,
+            leverage: None,
+        }
+    }
+    pub fn with_positioning(mut self, feed: Arc<PositioningFeed>) -> Self {
+        self.positioning = Some(feed);
+        self
+    }
+    pub fn with_leverage(mut self, leverage: u8) -> Self {
+        self.leverage = Some(leverage);
+        self
+    }
+    /// 当前的移动止损价
+    pub fn stop(&self, symbol: &str) -> Option<f64> {
+        self.states
+            .lock()
+            .get(symbol)
+            .and_then(|s| s.stop)
+            .map(|s| s.price)
+    }
+}
+
+impl Strategy for BreakoutStrategy {
+    fn notify(&self, order_return: StrategyOrderReturn) {
+        let Some(symbol) = self.pending.lock().remove(&order_return.request_id) else {
+            return;
+        };
+        if order_return.result.is_err() {
+            if let Some(state) = self.states.lock().get_mut(&symbol) {
+                state.stop = None;
+            }
+        }
+    }
+    fn update(&self, _price: &SymbolPrice) -> Option<StrategyOrderRequest> {
+        None
+    }
+    fn update_kline(&self, kline: &KlineData) -> Option<StrategyOrderRequest> {
+        let index = self
+            .symbols
+            .iter()
+            .position(|s| kline.symbol == s.as_str())?;
+        let candle = &kline.candle;
+        let mut states = self.states.lock();
+        let state = states
+            .entry(kline.symbol.to_string())
+            .or_insert_with(|| SymbolState {
+                judge: self.config.judge(),
+                stop: None,
+            });
+        state.judge.update(candle);
+        let leverage = self.leverage.map_or(1., f64::from);
+        let distance = self
+            .config
+            .stop_distance(&state.judge, candle.close, leverage);
+        // 同一时间收盘的k线按币种区分请求id
+        let request_id =
+            (candle.close_time.unix_timestamp_nanos() / 1_000_000) as u64 + index as u64;
+        let request = |position, stop_loss| StrategyOrderRequest {
+            request_id,
+            symbol: kline.symbol.to_string(),
+            position,
+            stop_loss,
+            take_profit: self.config.take_profit,
+            time_in_force: None,
+            stop_limit_offset: None,
+            hedge: None,
+            leverage: self.leverage,
+        };
+        if let Some(stop) = &mut state.stop {
+            if stop.hit(candle).is_some() {
+                state.stop = None;
+                return Some(request(0., 0.5));
+            }
+            stop.trail(candle, distance);
+            return None;
+        }
+        if !self.config.entry(&state.judge, true) || distance <= 0. || distance >= candle.close {
+            return None;
+        }
+        let open_interest_change = self.positioning.as_ref().and_then(|feed| {
+            feed.open_interest_change(
+                &kline.symbol,
+                candle.close_time,
+                self.config.open_interest_window,
+            )
+        });
+        if !self.config.confirms(open_interest_change) {
+            return None;
+        }
+        let stop = TrailingStop::new(true, candle.close, distance);
+        state.stop = Some(stop);
+        self.pending
+            .lock()
+            .insert(request_id, kline.symbol.to_string());
+        Some(request(self.config.position, stop.price / candle.close))
+    }
+    fn symbols(&self) -> Option<Vec<String>> {
+        Some(self.symbols.clone())
+    }
+    fn snapshot(&self, time: u64) -> Option<Result<StrategyState, DataError>> {
+        Some(self.states.lock().snapshot(time))
+    }
+    fn restore(&self, state: StrategyState) -> Result<(), DataError> {
+        self.states.lock().restore(state)
+    }
+    fn params(&self) -> Vec<(String, String)> {
+        let mut params = vec![
+            ("period".into(), self.config.period.to_string()),
+            (
+                "volume_factor".into(),
+                self.config.volume_factor.to_string(),
+            ),
+            (
+                "atr_multiplier".into(),
+                self.config.atr_multiplier.to_string(),
+            ),
+        ];
+        if let Some(stop) = self.config.stop {
+            params.push(("stop".into(), format!("{:?}", stop)));
+        }
+        params
+    }
+}
+
+#[test]
+fn breakout_test() {
+    use time::{Duration, OffsetDateTime};
+
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let kline = |i: i64, close: f64, volume: f64| KlineData {
+        symbol: "ETHUSDT".into(),
+        candle: CandleData {
+            open: close,
+            close,
+            high: close + 1.,
+            low: close - 1.,
+            volume,
+            open_time: start + Duration::minutes(i),
+            close_time: start + Duration::minutes(i + 1),
+        },
+    };
+    let config = BreakoutConfig {
+        period: 5,
+        atr_period: 5,
+        atr_multiplier: 2.,
+        ..Default::default()
+    };
+    let strategy = BreakoutStrategy::new(vec!["ETHUSDT".into()], config);
+    for i in 0..6 {
+        assert!(strategy.update_kline(&kline(i, 100., 10.)).is_none());
+    }
+    // 突破但成交量不足
+    assert!(strategy.update_kline(&kline(6, 102., 12.)).is_none());
+    let request = strategy.update_kline(&kline(7, 104., 30.)).unwrap();
+    assert_eq!(request.position, 0.2);
+    let stop = strategy.stop("ETHUSDT").unwrap();
+    assert!((request.stop_loss - stop / 104.).abs() < 1e-12);
+    // 上涨时止损上移
+    assert!(strategy.update_kline(&kline(8, 110., 10.)).is_none());
+    let raised = strategy.stop("ETHUSDT").unwrap();
+    assert!(raised > stop);
+    // 恢复后沿用快照中的移动止损
+    let restored = BreakoutStrategy::new(vec!["ETHUSDT".into()], config);
+    restored
+        .restore(strategy.snapshot(0).unwrap().unwrap())
+        .unwrap();
+    assert_eq!(restored.stop("ETHUSDT"), Some(raised));
+    let exit = strategy.update_kline(&kline(9, raised - 2., 10.)).unwrap();
+    assert_eq!(exit.position, 0.);
+    assert!(strategy.stop("ETHUSDT").is_none());
+    assert!(strategy
+        .update_kline(&KlineData {
+            symbol: "BTCUSDT".into(),
+            ..kline(10, 200., 100.)
+        })
+        .is_none());
+
+    // 配置的止损放置方式替代ATR距离，杠杆随开仓请求发出
+    let fixed = BreakoutStrategy::new(
+        vec!["ETHUSDT".into()],
+        BreakoutConfig {
+            stop: Some(StopPlacer::Fixed { ratio: 0.05 }),
+            ..config
+        },
+    )
+    .with_leverage(3);
+    for i in 0..6 {
+        assert!(fixed.update_kline(&kline(i, 100., 10.)).is_none());
+    }
+    let request = fixed.update_kline(&kline(7, 104., 30.)).unwrap();
+    assert!((request.stop_loss - 0.95).abs() < 1e-12);
+    assert_eq!(request.leverage, Some(3));
+}
@@ -0,0 +1,412 @@
+use std::collections::{HashMap, VecDeque};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    state::{Stateful, StrategyState},
+    HedgeLeg, Strategy, StrategyOrderRequest, StrategyOrderReturn,
+};
+use crate::{algorithm::SymbolPrice, error::DataError};
+
+/// 两变量Engle-Granger检验5%显著性水平的临界值
+const EG_CRITICAL_5: f64 = -3.34;
+/// 按半衰期设置z-score窗口时的最小窗口
+const MIN_WINDOW: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairsConfig {
+    /// z-score的滚动窗口
+    pub window: usize,
+    /// 价差 = ln(a) - hedge_ratio * ln(b)
+    pub hedge_ratio: f64,
+    /// |z|超过该值时开仓
+    pub entry_z: f64,
+    /// |z|回落到该值以内时平仓
+    pub exit_z: f64,
+    /// |z|继续扩大到该值时止损平仓
+    pub stop_z: f64,
+    /// 每次开仓占资金的比例（两条腿合计）
+    pub position: f64,
+}
+
+impl Default for PairsConfig {
+    fn default() -> Self {
+        Self {
+            window: 60,
+            hedge_ratio: 1.,
+            entry_z: 2.,
+            exit_z: 0.5,
+            stop_z: 4.,
+            position: 0.2,
+        }
+    }
+}
+
+/// Engle-Granger两步法的结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cointegration {
+    /// ln(a)对ln(b)回归的斜率
+    pub hedge_ratio: f64,
+    pub intercept: f64,
+    /// 残差Dickey-Fuller回归的t统计量，越小越平稳
+    pub adf_t: f64,
+    /// 价差偏离回归一半所需的k线数，残差不均值回归时为None
+    pub half_life: Option<f64>,
+}
+
+impl Cointegration {
+    /// a、b为按时间对齐的价格，对数价格做OLS回归，残差做无常数项的Dickey-Fuller回归
+    pub fn estimate(a: &[f64], b: &[f64]) -> Option<Self> {
+        let n = a.len().min(b.len());
+        if n < 3 {
+            return None;
+        }
+        let y: Vec<f64> = a[..n].iter().map(|p| p.ln()).collect();
+        let x: Vec<f64> = b[..n].iter().map(|p| p.ln()).collect();
+        let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+        let (mean_x, mean_y) = (mean(&x), mean(&y));
+        let var_x: f64 = x.iter().map(|x| (x - mean_x).powi(2)).sum();
+        if var_x == 0. {
+            return None;
+        }
+        let cov: f64 = x
+            .iter()
+            .zip(y.iter())
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+        let hedge_ratio = cov / var_x;
+        let intercept = mean_y - hedge_ratio * mean_x;
+        let residuals: Vec<f64> = x
+            .iter()
+            .zip(y.iter())
+            .map(|(x, y)| y - intercept - hedge_ratio * x)
+            .collect();
+        // Δe_t = γ * e_{t-1} + u_t
+        let lagged = &residuals[..n - 1];
+        let diffs: Vec<f64> = residuals.windows(2).map(|w| w[1] - w[0]).collect();
+        let ss: f64 = lagged.iter().map(|e| e * e).sum();
+        if ss == 0. {
+            return None;
+        }
+        let gamma = lagged
+            .iter()
+            .zip(diffs.iter())
+            .map(|(e, d)| e * d)
+            .sum::<f64>()
+            / ss;
+        let sigma2 = lagged
+            .iter()
+            .zip(diffs.iter())
+            .map(|(e, d)| (d - gamma * e).powi(2))
+            .sum::<f64>()
+            / (n - 2) as f64;
+        let adf_t = gamma / (sigma2 / ss).sqrt();
+        let half_life = (gamma < 0. && gamma > -1.).then(|| -(2f64).ln() / (1. + gamma).ln());
+        Some(Self {
+            hedge_ratio,
+            intercept,
+            adf_t,
+            half_life,
+        })
+    }
+    pub fn is_cointegrated(&self) -> bool {
+        self.adf_t < EG_CRITICAL_5
+    }
+}
+
+/// 价差方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PairsSignal {
+    /// 价差偏低：买a卖b
+    Long,
+    /// 价差偏高：卖a买b
+    Short,
+    /// 价差回归或继续扩大到止损
+    Exit,
+}
+
+/// 价差的滚动z-score和持仓状态，回测和实盘共用
+#[derive(Debug, Clone)]
+pub struct PairsModel {
+    config: PairsConfig,
+    spreads: VecDeque<f64>,
+    /// 当前持有的方向，Exit表示空仓
+    position: PairsSignal,
+}
+
+impl PairsModel {
+    pub fn new(config: PairsConfig) -> Self {
+        Self {
+            config,
+            spreads: VecDeque::with_capacity(config.window),
+            position: PairsSignal::Exit,
+        }
+    }
+    /// 用历史价格估计对冲比例，有半衰期时z-score窗口取半衰期；
+    /// 未通过协整检验时仍返回结果，由调用方决定是否交易
+    pub fn calibrate(&mut self, a: &[f64], b: &[f64]) -> Option<Cointegration> {
+        let c = Cointegration::estimate(a, b)?;
+        self.config.hedge_ratio = c.hedge_ratio;
+        if let Some(half_life) = c.half_life {
+            self.config.window = (half_life.round() as usize).max(MIN_WINDOW);
+        }
+        self.spreads.clear();
+        Some(c)
+    }
+    pub fn config(&self) -> &PairsConfig {
+        &self.config
+    }
+    pub fn position(&self) -> PairsSignal {
+        self.position
+    }
+    /// 下单失败时回到空仓
+    pub fn reset(&mut self) {
+        self.position = PairsSignal::Exit;
+    }
+    /// 窗口未满时为None
+    pub fn zscore(&self) -> Option<f64> {
+        let n = self.spreads.len();
+        if n < self.config.window || n < 2 {
+            return None;
+        }
+        let mean = self.spreads.iter().sum::<f64>() / n as f64;
+        let std = (self.spreads.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n as f64).sqrt();
+        let last = *self.spreads.back()?;
+        (std > 0.).then(|| (last - mean) / std)
+    }
+    /// 输入同一时刻的两个价格，持仓方向变化时返回信号
+    pub fn update(&mut self, a: f64, b: f64) -> Option<PairsSignal> {
+        if self.spreads.len() >= self.config.window {
+            self.spreads.pop_front();
+        }
+        self.spreads
+            .push_back(a.ln() - self.config.hedge_ratio * b.ln());
+        let z = self.zscore()?;
+        let signal = match self.position {
+            PairsSignal::Exit if z <= -self.config.entry_z && z > -self.config.stop_z => {
+                PairsSignal::Long
+            }
+            PairsSignal::Exit if z >= self.config.entry_z && z < self.config.stop_z => {
+                PairsSignal::Short
+            }
+            PairsSignal::Long if z >= -self.config.exit_z || z <= -self.config.stop_z => {
+                PairsSignal::Exit
+            }
+            PairsSignal::Short if z <= self.config.exit_z || z >= self.config.stop_z => {
+                PairsSignal::Exit
+            }
+            _ => return None,
+        };
+        self.position = signal;
+        Some(signal)
+    }
+    /// 两条腿的价值比（a : b）
+    pub fn leg_ratio(&self) -> f64 {
+        1. / self.config.hedge_ratio.abs().max(f64::EPSILON)
+    }
+}
+
+/// PairsModel的快照，包括校准得到的对冲比例和窗口，其余参数以配置为准
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PairsModelState {
+    hedge_ratio: f64,
+    window: usize,
+    spreads: VecDeque<f64>,
+    position: PairsSignal,
+}
+
+impl Stateful for PairsModel {
+    const STATE_VERSION: u32 = 1;
+    type State = PairsModelState;
+    fn save_state(&self) -> PairsModelState {
+        PairsModelState {
+            hedge_ratio: self.config.hedge_ratio,
+            window: self.config.window,
+            spreads: self.spreads.clone(),
+            position: self.position,
+        }
+    }
+    fn load_state(&mut self, state: PairsModelState) -> Result<(), DataError> {
+        if state.window == 0 || state.spreads.len() > state.window {
+            return Err(DataError::parse(
+                "pairs state",
+                format!("{} spreads in window {}", state.spreads.len(), state.window),
+            ));
+        }
+        self.config.hedge_ratio = state.hedge_ratio;
+        self.config.window = state.window;
+        self.spreads = state.spreads;
+        self.position = state.position;
+        Ok(())
+    }
+}
+
+/// 配对交易：按a、b两个币种价差的z-score开反向的两条腿，价差回归后平仓
+#[derive(Debug)]
+pub struct PairsStrategy {
+    a: String,
+    b: String,
+    model: Mutex<PairsModel>,
+    prices: Mutex<HashMap<String, f64>>,
+    /// 止损比例，作为各腿的保护性止损，价差止损由模型判断
+    stop_loss: f64,
+}
+
+impl PairsStrategy {
+    pub fn new(a: &str, b: &str, model: PairsModel) -> Self {
+        Self {
+            a: a.to_string(),
+            b: b.to_string(),
+            model: Mutex::new(model),
+            prices: Mutex::new(HashMap::new()),
+            stop_loss: 0.8,
+        }
+    }
+    pub fn with_stop_loss(mut self, stop_loss: f64) -> Self {
+        self.stop_loss = stop_loss;
+        self
+    }
+}
+
+impl Strategy for PairsStrategy {
+    fn notify(&self, order_return: StrategyOrderReturn) {
+        if order_return.result.is_err() {
+            self.model.lock().reset();
+        }
+    }
+    fn update(&self, price: &SymbolPrice) -> Option<StrategyOrderRequest> {
+        let (a, b) = {
+            let mut prices = self.prices.lock();
+            prices.insert(price.symbol.to_string(), price.mark_price);
+            // 以a的行情驱动，b取最近的价格
+            if price.symbol != self.a.as_str() {
+                return None;
+            }
+            (*prices.get(&self.a)?, *prices.get(&self.b)?)
+        };
+        let mut model = self.model.lock();
+        let signal = model.update(a, b)?;
+        let (symbol, hedge, ratio, position) = match signal {
+            PairsSignal::Long => (&self.a, &self.b, model.leg_ratio(), model.config().position),
+            PairsSignal::Short => (
+                &self.b,
+                &self.a,
+                1. / model.leg_ratio(),
+                model.config().position,
+            ),
+            // 平仓：平掉组合单的全部腿
+            PairsSignal::Exit => (&self.a, &self.b, 1., 0.),
+        };
+        Some(StrategyOrderRequest {
+            request_id: price.time,
+            symbol: symbol.clone(),
+            position,
+            stop_loss: self.stop_loss,
+            take_profit: 1. / self.stop_loss,
+            time_in_force: None,
+            stop_limit_offset: None,
+            hedge: Some(HedgeLeg {
+                symbol: hedge.clone(),
+                ratio,
+            }),
+            leverage: None,
+        })
+    }
+    fn symbols(&self) -> Option<Vec<String>> {
+        Some(vec![self.a.clone(), self.b.clone()])
+    }
+    fn snapshot(&self, time: u64) -> Option<Result<StrategyState, DataError>> {
+        Some(self.model.lock().snapshot(time))
+    }
+    fn restore(&self, state: StrategyState) -> Result<(), DataError> {
+        self.model.lock().restore(state)
+    }
+    fn params(&self) -> Vec<(String, String)> {
+        let model = self.model.lock();
+        let config = model.config();
+        vec![
+            ("pair".into(), format!("{}/{}", self.a, self.b)),
+            ("hedge_ratio".into(), format!("{:.4}", config.hedge_ratio)),
+            ("window".into(), config.window.to_string()),
+            ("entry_z".into(), config.entry_z.to_string()),
+            (
+                "z".into(),
+                model
+                    .zscore()
+                    .map(|z| format!("{:.2}", z))
+                    .unwrap_or("-".into()),
+            ),
+        ]
+    }
+}
+
+#[test]
+fn pairs_test() {
+    // b为随机游走，a = b^1.5 * AR(1)噪声，两者协整
+    let mut rng = fastrand::Rng::with_seed(7);
+    let (mut b, mut noise) = (vec![100f64], 0f64);
+    let mut a = vec![];
+    for i in 0..2000 {
+        if i > 0 {
+            b.push(b[i - 1] * (1. + (rng.f64() - 0.5) * 0.02));
+        }
+        noise = noise * 0.9 + (rng.f64() - 0.5) * 0.01;
+        a.push(b[i].powf(1.5) * noise.exp());
+    }
+    let c = Cointegration::estimate(&a, &b).unwrap();
+    assert!((c.hedge_ratio - 1.5).abs() < 0.05);
+    assert!(c.is_cointegrated());
+    // 半衰期约为ln2 / -ln0.9 ≈ 6.6
+    let half_life = c.half_life.unwrap();
+    assert!(half_life > 4. && half_life < 10.);
+    let mut model = PairsModel::new(PairsConfig::default());
+    model.calibrate(&a, &b).unwrap();
+    assert_eq!(model.config().window, MIN_WINDOW);
+    // 价差突然偏高时卖a买b，回归后平仓
+    let mut model = PairsModel::new(PairsConfig {
+        window: 20,
+        stop_z: 10.,
+        ..Default::default()
+    });
+    for i in 0..20 {
+        let wiggle = if i % 2 == 0 { 1.001 } else { 0.999 };
+        assert_eq!(model.update(100. * wiggle, 100.), None);
+    }
+    assert_eq!(model.update(101., 100.), Some(PairsSignal::Short));
+    assert_eq!(model.update(100., 100.), Some(PairsSignal::Exit));
+    assert_eq!(model.position(), PairsSignal::Exit);
+
+    let strategy = PairsStrategy::new(
+        "AUSDT",
+        "BUSDT",
+        PairsModel::new(PairsConfig {
+            window: 20,
+            stop_z: 10.,
+            ..Default::default()
+        }),
+    );
+    let price = |symbol: &str, mark_price: f64, time: u64| SymbolPrice {
+        symbol: symbol.into(),
+        mark_price,
+        time,
+        ..Default::default()
+    };
+    for i in 0..20 {
+        let wiggle = if i % 2 == 0 { 1.001 } else { 0.999 };
+        assert!(strategy.update(&price("BUSDT", 100., i)).is_none());
+        assert!(strategy.update(&price("AUSDT", 100. * wiggle, i)).is_none());
+    }
+    let request = strategy.update(&price("AUSDT", 101., 20)).unwrap();
+    assert_eq!(request.symbol, "BUSDT");
+    assert_eq!(request.hedge.unwrap().symbol, "AUSDT");
+    let request = strategy.update(&price("AUSDT", 100., 21)).unwrap();
+    assert_eq!(request.position, 0.);
+    assert_eq!(strategy.symbols().unwrap().len(), 2);
+    // 从快照恢复后价差窗口和持仓方向不变
+    let state = Strategy::snapshot(&strategy, 21).unwrap().unwrap();
+    let restored = PairsStrategy::new("AUSDT", "BUSDT", PairsModel::new(Default::default()));
+    Strategy::restore(&restored, state).unwrap();
+    assert_eq!(restored.model.lock().spreads, strategy.model.lock().spreads);
+    assert_eq!(restored.model.lock().config().window, 20);
+}
@@ -1,10 +1,171 @@
-// use crate::{algorithm::SymbolPrice};
+use std::collections::{HashMap, HashSet};
 
-// use super::Strategy;
+use parking_lot::Mutex;
 
-pub struct RollStra {}
-// impl Strategy for RollStra{
-//     fn signal_type(&self) -> Vec<SignalType> {
-//         vec![SignalType::Roll, SignalType::Price]
-//     }
-// }
+use super::{Strategy, StrategyOrderRequest, StrategyOrderReturn};
+use crate::{
+    algorithm::{SignalData, SymbolPrice},
+    screener::Watchlist,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RollConfig {
+    /// 占资金的比例
+    pub position: f64,
+    /// 止损价与入场价之比
+    pub stop_loss: f64,
+    /// 止盈价与入场价之比
+    pub take_profit: f64,
+}
+
+impl Default for RollConfig {
+    fn default() -> Self {
+        Self {
+            position: 0.2,
+            stop_loss: 0.95,
+            take_profit: 1.1,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RollState {
+    /// 收到向上突破信号、等待下一个价格开仓的币种
+    signals: HashSet<String>,
+    /// 已开仓的币种
+    held: HashSet<String>,
+    /// 收到向下突破信号、等待下一个价格平仓的币种
+    exits: HashSet<String>,
+    /// 未返回结果的开仓（request_id，币种）
+    pending: HashMap<u64, String>,
+}
+
+/// 跟随screener观察列表的突破策略：路由下发全部币种的价格，是否交易以观察列表的当前内容为准，
+/// 观察列表更新后无需重建路由；Controller开仓方向固定为买入，因此只做多
+#[derive(Debug)]
+pub struct RollStra {
+    /// 由screener维护的候选币种
+    watchlist: Watchlist,
+    config: RollConfig,
+    state: Mutex<RollState>,
+}
+
+impl RollStra {
+    pub fn new(watchlist: Watchlist, config: RollConfig) -> Self {
+        Self {
+            watchlist,
+            config,
+            state: Mutex::default(),
+        }
+    }
+    pub fn is_watched(&self, symbol: &str) -> bool {
+        self.watchlist.contains(symbol)
+    }
+    pub fn held(&self) -> Vec<String> {
+        self.state.lock().held.iter().cloned().collect()
+    }
+}
+
+impl Strategy for RollStra {
+    fn notify(&self, order_return: StrategyOrderReturn) {
+        let mut state = self.state.lock();
+        let Some(symbol) = state.pending.remove(&order_return.request_id) else {
+            return;
+        };
+        if order_return.result.is_err() {
+            state.held.remove(&symbol);
+        }
+    }
+    /// 向下突破或跌出观察列表时平仓，观察列表内向上突破后开仓
+    fn update(&self, price: &SymbolPrice) -> Option<StrategyOrderRequest> {
+        let symbol = price.symbol.as_str();
+        let mut state = self.state.lock();
+        let watched = self.is_watched(symbol);
+        let open = if state.exits.remove(symbol) || state.held.contains(symbol) && !watched {
+            state.held.remove(symbol);
+            false
+        } else if !state.held.contains(symbol) && state.signals.remove(symbol) && watched {
+            state.held.insert(symbol.to_string());
+            state.pending.insert(price.time, symbol.to_string());
+            true
+        } else {
+            return None;
+        };
+        Some(StrategyOrderRequest {
+            request_id: price.time,
+            symbol: symbol.to_string(),
+            position: if open { self.config.position } else { 0. },
+            stop_loss: self.config.stop_loss,
+            take_profit: self.config.take_profit,
+            time_in_force: None,
+            stop_limit_offset: None,
+            hedge: None,
+            leverage: None,
+        })
+    }
+    fn update_signal(&self, signal: &SignalData) {
+        let SignalData::Roll {
+            symbol, is_bull, ..
+        } = signal
+        else {
+            return;
+        };
+        let mut state = self.state.lock();
+        if *is_bull {
+            if self.is_watched(symbol) && !state.held.contains(symbol) {
+                state.signals.insert(symbol.clone());
+            }
+        } else {
+            state.signals.remove(symbol);
+            if state.held.contains(symbol) {
+                state.exits.insert(symbol.clone());
+            }
+        }
+    }
+    fn params(&self) -> Vec<(String, String)> {
+        vec![
+            ("position".into(), self.config.position.to_string()),
+            ("stop_loss".into(), self.config.stop_loss.to_string()),
+            ("take_profit".into(), self.config.take_profit.to_string()),
+        ]
+    }
+}
+
+#[test]
+fn roll_stra_test() {
+    let price = |symbol: &str, time: u64| SymbolPrice {
+        symbol: symbol.into(),
+        mark_price: 100.,
+        time,
+        ..Default::default()
+    };
+    let roll = |symbol: &str, is_bull: bool| SignalData::Roll {
+        symbol: symbol.into(),
+        time: 0,
+        is_bull,
+        price: 100.,
+    };
+    let watchlist = Watchlist::from(vec!["AUSDT".to_string()]);
+    let strategy = RollStra::new(watchlist.clone(), RollConfig::default());
+    assert_eq!(strategy.symbols(), None);
+    // 不在观察列表中的币种不开仓
+    strategy.update_signal(&roll("BUSDT", true));
+    assert!(strategy.update(&price("BUSDT", 1)).is_none());
+    strategy.update_signal(&roll("AUSDT", true));
+    let request = strategy.update(&price("AUSDT", 2)).unwrap();
+    assert_eq!(request.position, 0.2);
+    assert!(strategy.update(&price("AUSDT", 3)).is_none());
+    // 观察列表更新后立即生效
+    watchlist.set(vec!["BUSDT".to_string()]);
+    strategy.update_signal(&roll("BUSDT", true));
+    let request = strategy.update(&price("BUSDT", 4)).unwrap();
+    assert_eq!(request.symbol, "BUSDT");
+    let exit = strategy.update(&price("AUSDT", 5)).unwrap();
+    assert_eq!(exit.position, 0.);
+    assert_eq!(strategy.held(), vec!["BUSDT".to_string()]);
+    // 向下突破平仓
+    strategy.update_signal(&roll("BUSDT", false));
+    let exit = strategy.update(&price("BUSDT", 6)).unwrap();
+    assert_eq!(exit.position, 0.);
+    assert!(strategy.held().is_empty());
+}
@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{algorithm::indicator, candle::CandleData};
+
+/// 按k线维护的滚动窗口，最新的k线在最前
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollJudge {
+    cache: VecDeque<CandleData>,
+    max_length: usize,
+}
+
+impl RollJudge {
+    pub fn new(max_length: usize) -> Self {
+        let cache = VecDeque::with_capacity(max_length);
+        Self { cache, max_length }
+    }
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+    pub fn latest(&self) -> Option<&CandleData> {
+        self.cache.front()
+    }
+    pub fn update(&mut self, candle: &CandleData) {
+        if self.cache.len() >= self.max_length {
+            self.cache.pop_back();
+        }
+        self.cache.push_front(candle.clone());
+    }
+    pub fn max(&self, size: usize) -> CandleData {
+        self.cache
+            .iter()
+            .take(size)
+            .max_by(|x, y| x.high.total_cmp(&y.high))
+            .unwrap()
+            .clone()
+    }
+    pub fn min(&self, size: usize) -> CandleData {
+        self.cache
+            .iter()
+            .take(size)
+            .min_by(|x, y| x.low.total_cmp(&y.low))
+            .unwrap()
+            .clone()
+    }
+    pub fn is_max(&self, size: usize) -> bool {
+        self.cache[0] == self.max(size)
+    }
+    pub fn is_min(&self, size: usize) -> bool {
+        self.cache[0] == self.min(size)
+    }
+    /// 最近size根k线收盘价对数收益率的标准差
+    pub fn volatility(&self, size: usize) -> f64 {
+        let returns: Vec<f64> = self
+            .cache
+            .iter()
+            .take(size)
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|w| (w[0].close / w[1].close).ln())
+            .collect();
+        if returns.is_empty() {
+            return 0.;
+        }
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / returns.len() as f64).sqrt()
+    }
+    /// 最近size根k线内的最大逆向回撤（做多为自高点的回撤，做空为自低点的反弹）
+    pub fn max_draw(&self, size: usize, is_bull: bool) -> f64 {
+        let mut extreme: Option<f64> = None;
+        let mut max_draw: f64 = 0.;
+        for c in self.cache.iter().take(size).rev() {
+            if is_bull {
+                let high = extreme.map_or(c.high, |e| e.max(c.high));
+                max_draw = max_draw.max(1. - c.low / high);
+                extreme = Some(high);
+            } else {
+                let low = extreme.map_or(c.low, |e| e.min(c.low));
+                max_draw = max_draw.max(c.high / low - 1.);
+                extreme = Some(low);
+            }
+        }
+        max_draw
+    }
+    /// 最新一根k线收盘突破之前size根k线的最高价（做空为最低价），k线不足时为false
+    pub fn breaks_out(&self, size: usize, is_bull: bool) -> bool {
+        let Some(latest) = self.cache.front() else {
+            return false;
+        };
+        if size == 0 || self.cache.len() <= size {
+            return false;
+        }
+        let mut previous = self.cache.iter().skip(1).take(size);
+        if is_bull {
+            previous.all(|c| latest.close > c.high)
+        } else {
+            previous.all(|c| latest.close < c.low)
+        }
+    }
+    /// 最新一根k线之前size根k线的平均成交量
+    pub fn average_volume(&self, size: usize) -> f64 {
+        let volumes: Vec<f64> = self
+            .cache
+            .iter()
+            .skip(1)
+            .take(size)
+            .map(|c| c.volume)
+            .collect();
+        if volumes.is_empty() {
+            return 0.;
+        }
+        volumes.iter().sum::<f64>() / volumes.len() as f64
+    }
+    /// 周期为size的ATR，即indicator::atr对窗口内全部k线的最新值，
+    /// k线不足size根时以现有根数为周期，没有k线时为0
+    pub fn atr(&self, size: usize) -> f64 {
+        let column = |f: fn(&CandleData) -> f64| self.cache.iter().rev().map(f).collect::<Vec<_>>();
+        let period = size.min(self.len());
+        indicator::atr(
+            &column(|c| c.high),
+            &column(|c| c.low),
+            &column(|c| c.close),
+            period,
+        )
+        .last()
+        .copied()
+        .unwrap_or(0.)
+    }
+}
+
+#[test]
+fn roll_judge_test() {
+    let mut judge = RollJudge::new(4);
+    for (close, volume) in [(10., 1.), (11., 1.), (10.5, 2.), (12., 6.), (13., 3.)] {
+        judge.update(&CandleData {
+            open: close,
+            close,
+            high: close + 0.5,
+            low: close - 0.5,
+            volume,
+            ..Default::default()
+        });
+    }
+    // 最早的一根已被移出窗口
+    assert_eq!(judge.len(), 4);
+    assert_eq!(judge.latest().unwrap().close, 13.);
+    assert!(judge.breaks_out(3, true));
+    assert!(!judge.breaks_out(3, false));
+    assert!(!judge.breaks_out(4, true));
+    assert!((judge.average_volume(3) - 3.).abs() < 1e-9);
+    // 真实波幅依次为1、max(1, 0, 1)、max(1, 2, 1)、max(1, 1.5, 0.5)，
+    // 以前3根的均值4/3为初值再平滑一次
+    assert!((judge.atr(3) - 25. / 18.).abs() < 1e-9);
+    assert!((judge.atr(10) - 11. / 8.).abs() < 1e-9);
+    assert_eq!(RollJudge::new(3).atr(3), 0.);
+    assert!(judge.is_max(4));
+}
@@ -0,0 +1,320 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::{
+    state::{Stateful, StrategyState},
+    Strategy, StrategyOrderRequest, StrategyOrderReturn,
+};
+use crate::{
+    algorithm::{KlineData, SymbolPrice},
+    error::DataError,
+    screener::Watchlist,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RotationConfig {
+    /// 动量回看的k线数
+    pub lookback: usize,
+    /// 持有动量最强的前top_k个币种，每个占资金的1/top_k
+    pub top_k: usize,
+    /// 调仓间隔
+    pub rebalance: Duration,
+    /// 动量低于该值的币种不持有，名额空出
+    pub min_momentum: f64,
+    /// 实盘每个持仓的止损比例
+    pub stop_loss: f64,
+}
+
+impl Default for RotationConfig {
+    fn default() -> Self {
+        Self {
+            lookback: 7 * 24,
+            top_k: 3,
+            rebalance: Duration::from_secs(24 * 3600),
+            min_momentum: 0.,
+            stop_loss: 0.8,
+        }
+    }
+}
+
+/// 各币种最近lookback + 1个收盘价
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MomentumTracker {
+    lookback: usize,
+    closes: HashMap<String, VecDeque<f64>>,
+}
+
+impl MomentumTracker {
+    pub fn new(lookback: usize) -> Self {
+        Self {
+            lookback: lookback.max(1),
+            closes: HashMap::new(),
+        }
+    }
+    pub fn update(&mut self, symbol: &str, close: f64) {
+        let closes = self.closes.entry(symbol.to_string()).or_default();
+        if closes.len() > self.lookback {
+            closes.pop_front();
+        }
+        closes.push_back(close);
+    }
+    /// 修改回看长度，多出的旧收盘价丢弃
+    pub fn resize(&mut self, lookback: usize) {
+        self.lookback = lookback.max(1);
+        for closes in self.closes.values_mut() {
+            while closes.len() > self.lookback + 1 {
+                closes.pop_front();
+            }
+        }
+    }
+    /// lookback根k线的涨跌幅，数据不足时为None
+    pub fn momentum(&self, symbol: &str) -> Option<f64> {
+        let closes = self.closes.get(symbol)?;
+        if closes.len() <= self.lookback {
+            return None;
+        }
+        Some(closes.back()? / closes.front()? - 1.)
+    }
+    /// universe中动量不低于min_momentum的前top_k个币种，按动量降序
+    pub fn select(&self, universe: &[String], top_k: usize, min_momentum: f64) -> Vec<String> {
+        let mut ranked: Vec<(&String, f64)> = universe
+            .iter()
+            .filter_map(|s| Some((s, self.momentum(s)?)))
+            .filter(|(_, m)| *m >= min_momentum)
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+            .into_iter()
+            .take(top_k)
+            .map(|(s, _)| s.clone())
+            .collect()
+    }
+}
+
+/// 从held调整到target：平掉跌出的币种，买入新进的币种，保留的不动
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rebalance {
+    pub close: Vec<String>,
+    pub open: Vec<String>,
+}
+
+impl Rebalance {
+    pub fn new(held: &[String], target: &[String]) -> Self {
+        Self {
+            close: held
+                .iter()
+                .filter(|s| !target.contains(s))
+                .cloned()
+                .collect(),
+            open: target
+                .iter()
+                .filter(|s| !held.contains(s))
+                .cloned()
+                .collect(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.close.is_empty() && self.open.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RotationState {
+    tracker: MomentumTracker,
+    held: Vec<String>,
+    next: Option<OffsetDateTime>,
+    /// 待发出的（request_id，币种，是否开仓），先平后开
+    queue: VecDeque<(u64, String, bool)>,
+    /// 未返回结果的开仓（request_id，币种），重启后不会再收到结果，不保存
+    #[serde(skip)]
+    pending: HashMap<u64, String>,
+}
+
+impl Stateful for RotationState {
+    const STATE_VERSION: u32 = 1;
+    type State = RotationState;
+    fn save_state(&self) -> RotationState {
+        self.clone()
+    }
+    /// 回看长度以当前配置为准
+    fn load_state(&mut self, mut state: RotationState) -> Result<(), DataError> {
+        state.tracker.resize(self.tracker.lookback);
+        *self = state;
+        Ok(())
+    }
+}
+
+/// 实盘动量轮动：按k线收盘价跟踪screener观察列表的动量，每个调仓间隔持有前top_k个币种；
+/// 一次调仓的多笔订单在之后收到的k线中逐笔发出
+#[derive(Debug)]
+pub struct RotationStrategy {
+    watchlist: Watchlist,
+    config: RotationConfig,
+    state: Mutex<RotationState>,
+}
+
+impl RotationStrategy {
+    pub fn new(watchlist: Watchlist, config: RotationConfig) -> Self {
+        let state = RotationState {
+            tracker: MomentumTracker::new(config.lookback),
+            ..Default::default()
+        };
+        Self {
+            watchlist,
+            config,
+            state: Mutex::new(state),
+        }
+    }
+    pub fn held(&self) -> Vec<String> {
+        self.state.lock().held.clone()
+    }
+}
+
+impl Strategy for RotationStrategy {
+    fn notify(&self, order_return: StrategyOrderReturn) {
+        let mut state = self.state.lock();
+        let Some(symbol) = state.pending.remove(&order_return.request_id) else {
+            return;
+        };
+        if order_return.result.is_err() {
+            state.held.retain(|s| *s != symbol);
+        }
+    }
+    fn update(&self, _price: &SymbolPrice) -> Option<StrategyOrderRequest> {
+        None
+    }
+    fn update_kline(&self, kline: &KlineData) -> Option<StrategyOrderRequest> {
+        let mut state = self.state.lock();
+        state.tracker.update(&kline.symbol, kline.candle.close);
+        let now = kline.candle.close_time;
+        if state.next.is_none_or(|next| now >= next) {
+            state.next = Some(now + self.config.rebalance);
+            let target = state.tracker.select(
+                &self.watchlist.symbols(),
+                self.config.top_k,
+                self.config.min_momentum,
+            );
+            let rebalance = Rebalance::new(&state.held, &target);
+            let time = (now.unix_timestamp_nanos() / 1_000_000) as u64;
+            let orders = rebalance
+                .close
+                .into_iter()
+                .map(|s| (s, false))
+                .chain(rebalance.open.into_iter().map(|s| (s, true)));
+            for (i, (symbol, open)) in orders.enumerate() {
+                state.queue.push_back((time + i as u64, symbol, open));
+            }
+            state.held = target;
+        }
+        let (request_id, symbol, open) = state.queue.pop_front()?;
+        if open {
+            state.pending.insert(request_id, symbol.clone());
+        }
+        Some(StrategyOrderRequest {
+            request_id,
+            symbol,
+            position: if open {
+                1. / self.config.top_k as f64
+            } else {
+                0.
+            },
+            stop_loss: self.config.stop_loss,
+            take_profit: 1. / self.config.stop_loss,
+            time_in_force: None,
+            stop_limit_offset: None,
+            hedge: None,
+            leverage: None,
+        })
+    }
+    fn snapshot(&self, time: u64) -> Option<Result<StrategyState, DataError>> {
+        Some(self.state.lock().snapshot(time))
+    }
+    fn restore(&self, state: StrategyState) -> Result<(), DataError> {
+        self.state.lock().restore(state)
+    }
+    fn params(&self) -> Vec<(String, String)> {
+        vec![
+            ("lookback".into(), self.config.lookback.to_string()),
+            ("top_k".into(), self.config.top_k.to_string()),
+            ("held".into(), self.held().join(",")),
+        ]
+    }
+}
+
+#[test]
+fn rotation_test() {
+    use crate::candle::CandleData;
+    use time::Duration as TimeDuration;
+
+    let mut tracker = MomentumTracker::new(2);
+    for (a, b) in [(10., 10.), (11., 10.), (12., 9.)] {
+        tracker.update("AUSDT", a);
+        tracker.update("BUSDT", b);
+    }
+    tracker.update("CUSDT", 1.);
+    assert!((tracker.momentum("AUSDT").unwrap() - 0.2).abs() < 1e-9);
+    assert!(tracker.momentum("CUSDT").is_none());
+    let universe: Vec<String> = ["AUSDT", "BUSDT", "CUSDT"].map(String::from).to_vec();
+    assert_eq!(tracker.select(&universe, 3, -1.), ["AUSDT", "BUSDT"]);
+    assert_eq!(tracker.select(&universe, 3, 0.), ["AUSDT"]);
+    let rebalance = Rebalance::new(&["AUSDT".into(), "BUSDT".into()], &["BUSDT".into()]);
+    assert_eq!(rebalance.close, ["AUSDT"]);
+    assert!(rebalance.open.is_empty());
+
+    let strategy = RotationStrategy::new(
+        Watchlist::from(vec!["AUSDT".to_string(), "BUSDT".to_string()]),
+        RotationConfig {
+            lookback: 1,
+            top_k: 1,
+            rebalance: Duration::from_secs(60),
+            ..Default::default()
+        },
+    );
+    let start = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let kline = |symbol: &str, minute: i64, close: f64| KlineData {
+        symbol: symbol.into(),
+        candle: CandleData {
+            close,
+            close_time: start + TimeDuration::minutes(minute),
+            ..Default::default()
+        },
+    };
+    // 第一根k线数据不足，不调仓
+    assert!(strategy.update_kline(&kline("AUSDT", 0, 10.)).is_none());
+    assert!(strategy.update_kline(&kline("BUSDT", 0, 10.)).is_none());
+    let request = strategy.update_kline(&kline("BUSDT", 1, 11.)).unwrap();
+    assert_eq!((request.symbol.as_str(), request.position), ("BUSDT", 1.));
+    assert_eq!(strategy.held(), ["BUSDT"]);
+    // 排名不变时不调仓
+    assert!(strategy.update_kline(&kline("AUSDT", 2, 10.)).is_none());
+    // B转弱A转强：先平B再买A
+    strategy.update_kline(&kline("BUSDT", 2, 9.));
+    let close = strategy.update_kline(&kline("AUSDT", 3, 12.)).unwrap();
+    assert_eq!((close.symbol.as_str(), close.position), ("BUSDT", 0.));
+    let open = strategy.update_kline(&kline("BUSDT", 3, 9.)).unwrap();
+    assert_eq!((open.symbol.as_str(), open.position), ("AUSDT", 1.));
+    // 开仓失败后不再视为持有
+    strategy.notify(StrategyOrderReturn {
+        request_id: open.request_id,
+        result: Err(crate::error::MarketError::Rejected("min notional".into())),
+    });
+    assert!(strategy.held().is_empty());
+    // 恢复后继续按快照中的持仓和调仓时间运行
+    let snapshot = strategy.snapshot(0).unwrap().unwrap();
+    let restored = RotationStrategy::new(
+        Watchlist::from(vec!["AUSDT".to_string()]),
+        strategy.config.clone(),
+    );
+    restored.restore(snapshot).unwrap();
+    assert_eq!(restored.state.lock().next, strategy.state.lock().next);
+    assert_eq!(
+        restored.state.lock().tracker.momentum("AUSDT"),
+        strategy.state.lock().tracker.momentum("AUSDT")
+    );
+}
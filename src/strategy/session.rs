@@ -0,0 +1,220 @@
+use std::fmt::Display;
+
+use time::{Date, OffsetDateTime, Time, Weekday};
+use tracing::info;
+
+use super::{Strategy, StrategyOrderRequest, StrategyOrderReturn};
+use crate::{
+    algorithm::{KlineData, SignalData, SymbolPrice},
+    error::{DataError, MarketError},
+    strategy::state::StrategyState,
+};
+
+/// 允许开仓的时段，均按UTC；只限制开仓，不影响平仓和已有仓位的止盈止损
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionFilter {
+    /// （开始，结束），左闭右开，结束早于开始时跨越零点；为空时全天允许
+    windows: Vec<(Time, Time)>,
+    /// 为空时每天允许
+    weekdays: Vec<Weekday>,
+    /// 全天禁止开仓的日期
+    blackout_dates: Vec<Date>,
+}
+
+impl SessionFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_window(mut self, start: Time, end: Time) -> Self {
+        self.windows.push((start, end));
+        self
+    }
+    pub fn with_weekdays(mut self, weekdays: &[Weekday]) -> Self {
+        self.weekdays = weekdays.to_vec();
+        self
+    }
+    pub fn with_blackout_date(mut self, date: Date) -> Self {
+        self.blackout_dates.push(date);
+        self
+    }
+    pub fn allows(&self, time: OffsetDateTime) -> bool {
+        let time = time.to_offset(time::UtcOffset::UTC);
+        if self.blackout_dates.contains(&time.date()) {
+            return false;
+        }
+        if !self.weekdays.is_empty() && !self.weekdays.contains(&time.weekday()) {
+            return false;
+        }
+        let t = time.time();
+        self.windows.is_empty()
+            || self.windows.iter().any(|&(start, end)| {
+                if start <= end {
+                    start <= t && t < end
+                } else {
+                    t >= start || t < end
+                }
+            })
+    }
+    /// time为毫秒时间戳
+    pub fn allows_millis(&self, time: u64) -> bool {
+        OffsetDateTime::from_unix_timestamp_nanos(time as i128 * 1_000_000)
+            .map_or(true, |t| self.allows(t))
+    }
+    /// 包装策略，时段外的开仓请求被丢弃
+    pub fn wrap<S>(self, strategy: S) -> Sessioned<S> {
+        Sessioned {
+            inner: strategy,
+            filter: self,
+        }
+    }
+}
+
+impl Display for SessionFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let windows: Vec<String> = self
+            .windows
+            .iter()
+            .map(|(s, e)| format!("{}-{}", s, e))
+            .collect();
+        write!(f, "windows [{}]", windows.join(","))?;
+        if !self.weekdays.is_empty() {
+            let weekdays: Vec<String> = self.weekdays.iter().map(|w| w.to_string()).collect();
+            write!(f, ", weekdays [{}]", weekdays.join(","))?;
+        }
+        if !self.blackout_dates.is_empty() {
+            write!(f, ", {} blackout dates", self.blackout_dates.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// 带时段过滤的策略，实盘与回测（见backtest::strategy）使用同一个SessionFilter
+#[derive(Debug, Clone)]
+pub struct Sessioned<S> {
+    pub inner: S,
+    pub filter: SessionFilter,
+}
+
+impl<S: Strategy> Sessioned<S> {
+    /// 时段外的开仓请求按被拒绝通知策略，使其恢复到未下单的状态
+    fn filter(&self, request: StrategyOrderRequest, time: u64) -> Option<StrategyOrderRequest> {
+        if request.position == 0. || self.filter.allows_millis(time) {
+            return Some(request);
+        }
+        info!(
+            "Strategy {} order {} suppressed outside trading session",
+            self.inner.name(),
+            request.symbol
+        );
+        self.inner.notify(StrategyOrderReturn {
+            request_id: request.request_id,
+            result: Err(MarketError::Rejected("outside trading session".to_string())),
+        });
+        None
+    }
+}
+
+impl<S: Strategy + 'static> Strategy for Sessioned<S> {
+    fn notify(&self, order_return: StrategyOrderReturn) {
+        self.inner.notify(order_return)
+    }
+    fn update(&self, price: &SymbolPrice) -> Option<StrategyOrderRequest> {
+        let request = self.inner.update(price)?;
+        self.filter(request, price.time)
+    }
+    fn update_signal(&self, signal: &SignalData) {
+        self.inner.update_signal(signal)
+    }
+    fn update_kline(&self, kline: &KlineData) -> Option<StrategyOrderRequest> {
+        let request = self.inner.update_kline(kline)?;
+        let time = (kline.candle.close_time.unix_timestamp_nanos() / 1_000_000) as u64;
+        self.filter(request, time)
+    }
+    fn symbols(&self) -> Option<Vec<String>> {
+        self.inner.symbols()
+    }
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+    fn params(&self) -> Vec<(String, String)> {
+        let mut params = self.inner.params();
+        params.push(("session".into(), self.filter.to_string()));
+        params
+    }
+    fn snapshot(&self, time: u64) -> Option<Result<StrategyState, DataError>> {
+        self.inner.snapshot(time)
+    }
+    fn restore(&self, state: StrategyState) -> Result<(), DataError> {
+        self.inner.restore(state)
+    }
+}
+
+#[test]
+fn session_filter_test() {
+    use parking_lot::Mutex;
+    use time::macros::datetime;
+
+    let filter = SessionFilter::new()
+        .with_window(
+            Time::from_hms(22, 0, 0).unwrap(),
+            Time::from_hms(2, 0, 0).unwrap(),
+        )
+        .with_window(
+            Time::from_hms(13, 30, 0).unwrap(),
+            Time::from_hms(16, 0, 0).unwrap(),
+        )
+        .with_weekdays(&[Weekday::Monday, Weekday::Tuesday])
+        .with_blackout_date(datetime!(2024-01-09 0:00 UTC).date());
+    // 2024-01-08为周一
+    assert!(filter.allows(datetime!(2024-01-08 14:00 UTC)));
+    assert!(filter.allows(datetime!(2024-01-08 23:00 UTC)));
+    assert!(filter.allows(datetime!(2024-01-08 01:00 UTC)));
+    assert!(!filter.allows(datetime!(2024-01-08 16:00 UTC)));
+    // 按UTC判断
+    assert!(filter.allows(datetime!(2024-01-08 22:00 +8)));
+    assert!(!filter.allows(datetime!(2024-01-09 14:00 UTC)));
+    assert!(!filter.allows(datetime!(2024-01-10 14:00 UTC)));
+    assert!(SessionFilter::new().allows(datetime!(2024-01-10 14:00 UTC)));
+
+    #[derive(Debug, Default)]
+    struct Enter {
+        rejected: Mutex<usize>,
+    }
+    impl Strategy for Enter {
+        fn notify(&self, order_return: StrategyOrderReturn) {
+            if order_return.result.is_err() {
+                *self.rejected.lock() += 1;
+            }
+        }
+        fn update(&self, price: &SymbolPrice) -> Option<StrategyOrderRequest> {
+            Some(StrategyOrderRequest {
+                request_id: price.time,
+                symbol: price.symbol.to_string(),
+                position: if price.mark_price > 0. { 0.1 } else { 0. },
+                stop_loss: 0.9,
+                take_profit: 1.1,
+                time_in_force: Default::default(),
+                stop_limit_offset: None,
+                hedge: None,
+                leverage: None,
+            })
+        }
+    }
+    let strategy = filter.wrap(Enter::default());
+    let millis = |t: OffsetDateTime| (t.unix_timestamp_nanos() / 1_000_000) as u64;
+    let price = |time, mark_price| SymbolPrice {
+        symbol: "BTCUSDT".into(),
+        mark_price,
+        time,
+        ..Default::default()
+    };
+    assert!(strategy
+        .update(&price(millis(datetime!(2024-01-08 14:00 UTC)), 1.))
+        .is_some());
+    let outside = millis(datetime!(2024-01-08 18:00 UTC));
+    assert!(strategy.update(&price(outside, 1.)).is_none());
+    assert_eq!(*strategy.inner.rejected.lock(), 1);
+    // 平仓不受限制
+    assert!(strategy.update(&price(outside, 0.)).is_some());
+    assert_eq!(strategy.name(), "Enter");
+}
@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::error::DataError;
+
+/// 带版本号的策略状态快照，data为JSON，新增字段需带#[serde(default)]以兼容旧快照
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrategyState {
+    pub version: u32,
+    /// 快照时间（unix毫秒）
+    pub time: u64,
+    pub data: serde_json::Value,
+}
+
+impl StrategyState {
+    /// 先写临时文件再重命名，崩溃时不会留下写了一半的快照
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), DataError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_vec(self).map_err(|e| DataError::parse("strategy state", e))?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+    /// 文件不存在时返回None
+    pub fn read(path: impl AsRef<Path>) -> Result<Option<Self>, DataError> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| DataError::parse("strategy state", e))
+    }
+}
+
+/// 可保存和恢复内部状态的策略
+pub trait Stateful {
+    /// 状态结构不兼容地变化时递增，并在migrate中处理旧版本
+    const STATE_VERSION: u32;
+    type State: Serialize + DeserializeOwned;
+    fn save_state(&self) -> Self::State;
+    fn load_state(&mut self, state: Self::State) -> Result<(), DataError>;
+    /// 将旧版本的状态转换为当前版本，默认不支持
+    #[allow(unused_variables)]
+    fn migrate(version: u32, data: serde_json::Value) -> Option<Self::State> {
+        None
+    }
+    fn snapshot(&self, time: u64) -> Result<StrategyState, DataError> {
+        Ok(StrategyState {
+            version: Self::STATE_VERSION,
+            time,
+            data: serde_json::to_value(self.save_state())
+                .map_err(|e| DataError::parse("strategy state", e))?,
+        })
+    }
+    /// 新版本的快照无法恢复
+    fn restore(&mut self, state: StrategyState) -> Result<(), DataError> {
+        let data = match state.version {
+            v if v == Self::STATE_VERSION => serde_json::from_value(state.data)
+                .map_err(|e| DataError::parse("strategy state", e))?,
+            v if v < Self::STATE_VERSION => Self::migrate(v, state.data).ok_or_else(|| {
+                DataError::parse("strategy state", format!("cannot migrate version {}", v))
+            })?,
+            v => {
+                return Err(DataError::parse(
+                    "strategy state",
+                    format!("version {} newer than {}", v, Self::STATE_VERSION),
+                ))
+            }
+        };
+        self.load_state(data)
+    }
+}
+
+#[test]
+fn strategy_state_test() {
+    #[derive(Default)]
+    struct Counter(u64);
+    impl Stateful for Counter {
+        const STATE_VERSION: u32 = 2;
+        type State = u64;
+        fn save_state(&self) -> u64 {
+            self.0
+        }
+        fn load_state(&mut self, state: u64) -> Result<(), DataError> {
+            self.0 = state;
+            Ok(())
+        }
+        fn migrate(version: u32, data: serde_json::Value) -> Option<u64> {
+            // 版本1以字符串保存
+            (version == 1).then(|| data.as_str()?.parse().ok())?
+        }
+    }
+    let path = std::env::temp_dir().join("hurribot_strategy_state_test/counter.json");
+    std::fs::remove_file(&path).ok();
+    assert_eq!(StrategyState::read(&path).unwrap(), None);
+    Counter(7).snapshot(1).unwrap().write(&path).unwrap();
+    let mut counter = Counter::default();
+    counter
+        .restore(StrategyState::read(&path).unwrap().unwrap())
+        .unwrap();
+    assert_eq!(counter.0, 7);
+    let old = StrategyState {
+        version: 1,
+        time: 0,
+        data: "5".into(),
+    };
+    counter.restore(old).unwrap();
+    assert_eq!(counter.0, 5);
+    let newer = StrategyState {
+        version: 3,
+        time: 0,
+        data: 1.into(),
+    };
+    assert!(counter.restore(newer).is_err());
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::roll_judge::RollJudge;
+use crate::error::ConfigError;
+
+/// 止损价与强平价之间至少留出的价格比例
+const LIQUIDATION_BUFFER: f64 = 0.004;
+
+/// 止损距离（相对开仓价的比例）的计算方式，回测和实盘共用；
+/// 除Fixed外距离不超过强平前的空间，k线不足以计算波动时按杠杆放置
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopPlacer {
+    /// 固定比例，原样使用不截断（与GeoStrategy原先的stop_loss_ratio一致），
+    /// 超过强平前的空间时由强平先离场
+    Fixed { ratio: f64 },
+    /// 贴近强平价：0.99/杠杆 - buffer
+    Leverage { buffer: f64 },
+    /// 最近period根k线平均真实波幅的multiplier倍
+    Atr { period: usize, multiplier: f64 },
+    /// 最近period根k线对数收益率的标准差按horizon根k线放大（乘以√horizon）后的multiplier倍
+    Volatility {
+        period: usize,
+        horizon: usize,
+        multiplier: f64,
+    },
+}
+
+impl Default for StopPlacer {
+    fn default() -> Self {
+        Self::Leverage {
+            buffer: LIQUIDATION_BUFFER,
+        }
+    }
+}
+
+impl StopPlacer {
+    /// 计算所需的k线数，用作RollJudge的长度
+    pub fn lookback(&self) -> usize {
+        match *self {
+            Self::Fixed { .. } | Self::Leverage { .. } => 1,
+            Self::Atr { period, .. } | Self::Volatility { period, .. } => period + 1,
+        }
+    }
+    pub fn judge(&self) -> RollJudge {
+        RollJudge::new(self.lookback())
+    }
+    /// price为开仓价，judge的最新一根k线为开仓前最后一根
+    pub fn distance(&self, judge: &RollJudge, price: f64, leverage: f64) -> f64 {
+        let max = (0.99 / leverage - LIQUIDATION_BUFFER).max(0.);
+        let ready = judge.len() >= self.lookback() && price > 0.;
+        let distance = match *self {
+            Self::Fixed { ratio } => return ratio,
+            Self::Leverage { buffer } => 0.99 / leverage - buffer,
+            Self::Atr { period, multiplier } if ready => judge.atr(period) * multiplier / price,
+            Self::Volatility {
+                period,
+                horizon,
+                multiplier,
+            } if ready => judge.volatility(period + 1) * (horizon as f64).sqrt() * multiplier,
+            _ => max,
+        };
+        distance.clamp(0., max)
+    }
+    /// 检查参数，错误信息用于ConfigError::Invalid
+    pub fn validate(&self) -> Result<(), String> {
+        let valid = match *self {
+            Self::Fixed { ratio } => ratio > 0. && ratio < 1.,
+            Self::Leverage { buffer } => buffer >= 0.,
+            Self::Atr { period, multiplier } => period > 0 && multiplier > 0.,
+            Self::Volatility {
+                period,
+                horizon,
+                multiplier,
+            } => period > 0 && horizon > 0 && multiplier > 0.,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("stop placer {:?}", self))
+        }
+    }
+    /// 止损价格
+    pub fn stop_price(&self, judge: &RollJudge, is_bull: bool, price: f64, leverage: f64) -> f64 {
+        let distance = self.distance(judge, price, leverage);
+        if is_bull {
+            price * (1. - distance)
+        } else {
+            price * (1. + distance)
+        }
+    }
+}
+
+/// 按策略名选择止损放置方式，未列出的策略使用default，都没有配置时由策略使用自身的止损
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StopConfig {
+    pub default: Option<StopPlacer>,
+    pub strategies: HashMap<String, StopPlacer>,
+}
+
+impl StopConfig {
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
+        let c = std::fs::read_to_string(path)?;
+        let val: Self = toml::from_str(&c)?;
+        for placer in val.default.iter().chain(val.strategies.values()) {
+            placer.validate().map_err(ConfigError::Invalid)?;
+        }
+        Ok(val)
+    }
+    pub fn placer(&self, strategy: &str) -> Option<StopPlacer> {
+        self.strategies.get(strategy).copied().or(self.default)
+    }
+}
+
+#[test]
+fn stop_placer_test() {
+    use crate::candle::CandleData;
+
+    let mut judge = RollJudge::new(10);
+    let leverage = StopPlacer::default();
+    // 与原先的0.99/杠杆 - 0.004一致
+    assert!((leverage.stop_price(&judge, true, 100., 10.) - 90.5).abs() < 1e-9);
+    assert!((leverage.stop_price(&judge, false, 100., 10.) - 109.5).abs() < 1e-9);
+    // 固定比例原样使用，不按强平前的空间截断
+    let fixed = StopPlacer::Fixed { ratio: 0.2 };
+    assert!((fixed.distance(&judge, 100., 2.) - 0.2).abs() < 1e-12);
+    assert!((fixed.distance(&judge, 100., 10.) - 0.2).abs() < 1e-12);
+
+    let atr = StopPlacer::Atr {
+        period: 3,
+        multiplier: 2.,
+    };
+    // k线不足时按杠杆放置
+    assert!((atr.distance(&judge, 100., 5.) - 0.194).abs() < 1e-12);
+    for close in [100., 101., 100., 101.] {
+        judge.update(&CandleData {
+            open: close,
+            high: close + 0.5,
+            low: close - 0.5,
+            close,
+            ..Default::default()
+        });
+    }
+    // 真实波幅依次为1、1.5、1.5、1.5，Wilder平滑后ATR为25/18
+    assert!((atr.distance(&judge, 100., 5.) - 25. / 18. * 2. / 100.).abs() < 1e-12);
+    let volatility = StopPlacer::Volatility {
+        period: 3,
+        horizon: 4,
+        multiplier: 1.,
+    };
+    let d = volatility.distance(&judge, 100., 5.);
+    assert!((d - judge.volatility(4) * 2.).abs() < 1e-12 && d > 0.01);
+
+    let config: StopPlacer =
+        toml::from_str::<toml::Value>("s = { atr = { period = 14, multiplier = 3.0 } }").unwrap()
+            ["s"]
+            .clone()
+            .try_into()
+            .unwrap();
+    assert_eq!(
+        config,
+        StopPlacer::Atr {
+            period: 14,
+            multiplier: 3.
+        }
+    );
+
+    let path = std::env::temp_dir().join("hurribot_stop_config_test.toml");
+    std::fs::write(
+        &path,
+        "default = { fixed = { ratio = 0.05 } }\n\
+         [strategies]\n\
+         breakout = { atr = { period = 14, multiplier = 3.0 } }\n",
+    )
+    .unwrap();
+    let stops = StopConfig::value_parse(path.to_str().unwrap()).unwrap();
+    assert_eq!(stops.placer("breakout"), Some(config));
+    assert_eq!(stops.placer("geo"), Some(StopPlacer::Fixed { ratio: 0.05 }));
+    assert_eq!(StopConfig::default().placer("geo"), None);
+    std::fs::write(
+        &path,
+        "default = { atr = { period = 0, multiplier = 3.0 } }\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        StopConfig::value_parse(path.to_str().unwrap()),
+        Err(ConfigError::Invalid(_))
+    ));
+}
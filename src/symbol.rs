@@ -0,0 +1,256 @@
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::OnceLock,
+};
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub mod filter;
+
+/// 全局币种表，币种名只分配一次且不释放
+#[derive(Default)]
+struct Registry {
+    ids: HashMap<&'static str, u32>,
+    names: Vec<&'static str>,
+}
+
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// 驻留的币种名，比较和哈希只涉及序号，携带驻留的名字，取名字不查全局表，可直接当作&str使用
+#[derive(Clone, Copy)]
+pub struct SymbolId {
+    id: u32,
+    name: &'static str,
+}
+
+impl SymbolId {
+    /// 已驻留时只需一次读锁查找，不分配内存；行情热路径上应使用预先加载的SymbolTable
+    pub fn intern(symbol: &str) -> Self {
+        if let Some(id) = Self::get(symbol) {
+            return id;
+        }
+        let mut registry = registry().write();
+        if let Some(&id) = registry.ids.get(symbol) {
+            return Self {
+                id,
+                name: registry.names[id as usize],
+            };
+        }
+        let name: &'static str = Box::leak(symbol.into());
+        let id = registry.names.len() as u32;
+        registry.names.push(name);
+        registry.ids.insert(name, id);
+        Self { id, name }
+    }
+    /// 先查线程内的SymbolTable，行情处理等热路径使用；
+    /// 每个线程只在首次遇到一个币种时访问全局表
+    pub fn intern_cached(symbol: &str) -> Self {
+        thread_local! {
+            static LOCAL: RefCell<SymbolTable> = RefCell::new(SymbolTable::snapshot());
+        }
+        LOCAL.with(|table| table.borrow_mut().intern(symbol))
+    }
+    /// 加载交易规则等时一次性驻留全部币种，之后创建的SymbolTable直接包含这些币种
+    pub fn preload<S: AsRef<str>>(symbols: impl IntoIterator<Item = S>) {
+        let mut registry = registry().write();
+        for symbol in symbols {
+            let symbol = symbol.as_ref();
+            if registry.ids.contains_key(symbol) {
+                continue;
+            }
+            let name: &'static str = Box::leak(symbol.into());
+            let id = registry.names.len() as u32;
+            registry.names.push(name);
+            registry.ids.insert(name, id);
+        }
+    }
+    /// 未驻留过的币种返回None
+    pub fn get(symbol: &str) -> Option<Self> {
+        let registry = registry().read();
+        let &id = registry.ids.get(symbol)?;
+        Some(Self {
+            id,
+            name: registry.names[id as usize],
+        })
+    }
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+    pub fn as_str(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl PartialEq for SymbolId {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for SymbolId {}
+
+impl Hash for SymbolId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl PartialOrd for SymbolId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SymbolId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+/// 币种名 -> SymbolId的本地缓存，创建时从全局表复制，由单个线程持有，
+/// 查找不经过全局表的锁，只有创建后才驻留的币种（如新上线）需要访问全局表，见SymbolId::intern_cached
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    ids: HashMap<&'static str, SymbolId>,
+}
+
+impl SymbolTable {
+    /// 复制当前已驻留的全部币种
+    pub fn snapshot() -> Self {
+        let registry = registry().read();
+        let ids = registry
+            .names
+            .iter()
+            .enumerate()
+            .map(|(id, &name)| {
+                let id = SymbolId {
+                    id: id as u32,
+                    name,
+                };
+                (name, id)
+            })
+            .collect();
+        Self { ids }
+    }
+    pub fn intern(&mut self, symbol: &str) -> SymbolId {
+        if let Some(&id) = self.ids.get(symbol) {
+            return id;
+        }
+        let id = SymbolId::intern(symbol);
+        self.ids.insert(id.as_str(), id);
+        id
+    }
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+impl Default for SymbolId {
+    fn default() -> Self {
+        Self::intern("")
+    }
+}
+
+impl Deref for SymbolId {
+    type Target = str;
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for SymbolId {
+    fn from(symbol: &str) -> Self {
+        Self::intern(symbol)
+    }
+}
+
+impl From<&String> for SymbolId {
+    fn from(symbol: &String) -> Self {
+        Self::intern(symbol)
+    }
+}
+
+impl PartialEq<str> for SymbolId {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for SymbolId {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Display for SymbolId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for SymbolId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for SymbolId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SymbolId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = SymbolId;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a symbol")
+            }
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<SymbolId, E> {
+                Ok(SymbolId::intern(v))
+            }
+        }
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+#[test]
+fn symbol_id_test() {
+    let btc = SymbolId::intern("BTCUSDT");
+    assert_eq!(btc, SymbolId::from("BTCUSDT"));
+    assert_ne!(btc, SymbolId::intern("ETHUSDT"));
+    assert_eq!(btc, "BTCUSDT");
+    assert!(btc.ends_with("USDT"));
+    assert_eq!(format!("{} {:?}", btc, btc), "BTCUSDT \"BTCUSDT\"");
+    assert_eq!(SymbolId::get("BTCUSDT"), Some(btc));
+    assert_eq!(SymbolId::get("NEVERSEENUSDT"), None);
+    let json = serde_json::to_string(&btc).unwrap();
+    assert_eq!(serde_json::from_str::<SymbolId>(&json).unwrap(), btc);
+
+    SymbolId::preload(["PRELOADUSDT", "BTCUSDT"]);
+    let mut table = SymbolTable::snapshot();
+    let len = table.len();
+    assert_eq!(table.intern("BTCUSDT"), btc);
+    assert_eq!(
+        table.intern("PRELOADUSDT"),
+        SymbolId::get("PRELOADUSDT").unwrap()
+    );
+    assert_eq!(table.len(), len);
+    assert_eq!(table.intern("LATEUSDT"), SymbolId::get("LATEUSDT").unwrap());
+    assert_eq!(table.len(), len + 1);
+    assert_eq!(SymbolId::intern_cached("BTCUSDT"), btc);
+}
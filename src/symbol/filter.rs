@@ -0,0 +1,267 @@
+use std::collections::{HashMap, HashSet};
+
+use parking_lot::RwLock;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use tracing::info;
+
+use crate::error::ConfigError;
+
+/// 结算资产的默认值
+pub const DEFAULT_QUOTE_ASSET: &str = "USDT";
+
+/// 配置文件中的[symbol_filter]部分，默认与原先的硬编码规则一致：
+/// 只保留USDT永续合约，跳过交割合约（带下划线）和USDC合约
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SymbolFilterConfig {
+    /// 非空时只保留其中的币种
+    pub whitelist: Vec<String>,
+    pub blacklist: Vec<String>,
+    /// 币种名需匹配的正则
+    pub include: Option<String>,
+    /// 币种名匹配时排除的正则
+    pub exclude: Option<String>,
+    /// 结算资产，如"USDC"；只保留以其结尾的币种，余额和下单金额均以其计价。
+    /// 也接受旧配置的quote_assets = ["USDT"]，但只能有一个
+    #[serde(alias = "quote_assets", deserialize_with = "one_quote_asset")]
+    pub quote_asset: String,
+    /// 24h最小成交额，0为不限制
+    pub min_quote_volume: f64,
+}
+
+impl Default for SymbolFilterConfig {
+    fn default() -> Self {
+        Self {
+            whitelist: vec![],
+            blacklist: vec![],
+            include: None,
+            exclude: Some("_|USDC".to_string()),
+            quote_asset: DEFAULT_QUOTE_ASSET.to_string(),
+            min_quote_volume: 0.,
+        }
+    }
+}
+
+fn one_quote_asset<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum QuoteAsset {
+        One(String),
+        List(Vec<String>),
+    }
+    match QuoteAsset::deserialize(d)? {
+        QuoteAsset::One(asset) => Ok(asset),
+        QuoteAsset::List(mut assets) if assets.len() == 1 => Ok(assets.remove(0)),
+        QuoteAsset::List(assets) => Err(serde::de::Error::custom(format!(
+            "exactly one quote asset is supported, got {:?}",
+            assets
+        ))),
+    }
+}
+
+impl SymbolFilterConfig {
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
+        #[derive(Deserialize)]
+        struct File {
+            #[serde(default)]
+            symbol_filter: SymbolFilterConfig,
+        }
+        let c = std::fs::read_to_string(path)?;
+        let val: File = toml::from_str(&c)?;
+        val.symbol_filter.build()?;
+        Ok(val.symbol_filter)
+    }
+    pub fn build(&self) -> Result<SymbolFilter, ConfigError> {
+        let regex = |re: &Option<String>| {
+            re.as_deref()
+                .map(Regex::new)
+                .transpose()
+                .map_err(|e| ConfigError::Invalid(format!("symbol filter regex: {}", e)))
+        };
+        if self.quote_asset.is_empty() {
+            return Err(ConfigError::Invalid("empty quote asset".to_string()));
+        }
+        if self.min_quote_volume < 0. {
+            return Err(ConfigError::Invalid(format!(
+                "min quote volume {}",
+                self.min_quote_volume
+            )));
+        }
+        let exclude = regex(&self.exclude)?;
+        // 如结算资产改为USDC但保留了默认的exclude
+        if exclude
+            .as_ref()
+            .is_some_and(|re| re.is_match(&self.quote_asset))
+        {
+            return Err(ConfigError::Invalid(format!(
+                "symbol filter exclude {:?} excludes quote asset {}",
+                self.exclude.as_deref().unwrap_or_default(),
+                self.quote_asset
+            )));
+        }
+        Ok(SymbolFilter {
+            whitelist: self.whitelist.iter().cloned().collect(),
+            blacklist: self.blacklist.iter().cloned().collect(),
+            include: regex(&self.include)?,
+            exclude,
+            quote_asset: self.quote_asset.clone(),
+            min_quote_volume: self.min_quote_volume,
+            volumes: Default::default(),
+            delisted: Default::default(),
+        })
+    }
+}
+
+/// 行情、screener和BinanceMarket共用的币种过滤规则
+#[derive(Debug)]
+pub struct SymbolFilter {
+    whitelist: HashSet<String>,
+    blacklist: HashSet<String>,
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+    quote_asset: String,
+    min_quote_volume: f64,
+    /// 最近一次更新的24h成交额，未更新前不按成交额过滤
+    volumes: RwLock<HashMap<String, f64>>,
+    /// 交易所信息刷新时发现的下架或停止交易的币种
+    delisted: RwLock<HashSet<String>>,
+}
+
+impl Default for SymbolFilter {
+    fn default() -> Self {
+        SymbolFilterConfig::default().build().unwrap()
+    }
+}
+
+impl SymbolFilter {
+    pub fn quote_asset(&self) -> &str {
+        &self.quote_asset
+    }
+    /// 是否需要定期更新24h成交额
+    pub fn needs_volumes(&self) -> bool {
+        self.min_quote_volume > 0.
+    }
+    /// 用24h行情统计更新成交额
+    pub fn set_volumes(&self, volumes: impl IntoIterator<Item = (String, f64)>) {
+        let volumes: HashMap<String, f64> = volumes.into_iter().collect();
+        let passed = volumes
+            .values()
+            .filter(|&&v| v >= self.min_quote_volume)
+            .count();
+        info!(
+            "Symbol filter volumes updated: {}/{} above {}",
+            passed,
+            volumes.len(),
+            self.min_quote_volume
+        );
+        *self.volumes.write() = volumes;
+    }
+    /// 标记币种下架，之后不再通过过滤；delisted为false时恢复
+    pub fn set_delisted(&self, symbol: &str, delisted: bool) {
+        if delisted {
+            self.delisted.write().insert(symbol.to_string());
+        } else {
+            self.delisted.write().remove(symbol);
+        }
+    }
+    /// 只看币种名的规则
+    pub fn allows_name(&self, symbol: &str) -> bool {
+        if self.blacklist.contains(symbol) || self.delisted.read().contains(symbol) {
+            return false;
+        }
+        if !self.whitelist.is_empty() && !self.whitelist.contains(symbol) {
+            return false;
+        }
+        if !symbol.ends_with(&self.quote_asset) {
+            return false;
+        }
+        if self.include.as_ref().is_some_and(|re| !re.is_match(symbol)) {
+            return false;
+        }
+        !self.exclude.as_ref().is_some_and(|re| re.is_match(symbol))
+    }
+    /// 币种名和已知的24h成交额都通过
+    pub fn allows(&self, symbol: &str) -> bool {
+        if !self.allows_name(symbol) {
+            return false;
+        }
+        if !self.needs_volumes() {
+            return true;
+        }
+        let volumes = self.volumes.read();
+        volumes.is_empty()
+            || volumes
+                .get(symbol)
+                .is_some_and(|&v| v >= self.min_quote_volume)
+    }
+    /// 已知24h成交额时直接判断，如screener
+    pub fn allows_with_volume(&self, symbol: &str, quote_volume: f64) -> bool {
+        self.allows_name(symbol) && quote_volume >= self.min_quote_volume
+    }
+}
+
+#[test]
+fn symbol_filter_test() {
+    let filter = SymbolFilter::default();
+    assert!(filter.allows("BTCUSDT"));
+    assert!(!filter.allows("BTCBUSD"));
+    assert!(!filter.allows("BTCUSDT_240329"));
+    assert!(!filter.allows("ETHUSDC"));
+    // 默认的exclude排除USDC合约，改用USDC结算时需同时修改
+    let usdc: SymbolFilterConfig = toml::from_str(r#"quote_asset = "USDC""#).unwrap();
+    assert!(matches!(usdc.build(), Err(ConfigError::Invalid(_))));
+    let usdc: SymbolFilterConfig = toml::from_str(
+        r#"
+        quote_asset = "USDC"
+        exclude = "_"
+        "#,
+    )
+    .unwrap();
+    let usdc = usdc.build().unwrap();
+    assert_eq!(usdc.quote_asset(), "USDC");
+    assert!(usdc.allows("ETHUSDC"));
+    assert!(!usdc.allows("ETHUSDT"));
+    // 旧配置的quote_assets
+    let old: SymbolFilterConfig = toml::from_str(r#"quote_assets = ["USDT"]"#).unwrap();
+    assert_eq!(old.quote_asset, "USDT");
+    assert!(toml::from_str::<SymbolFilterConfig>(r#"quote_assets = ["USDT", "BUSD"]"#).is_err());
+    assert!(toml::from_str::<SymbolFilterConfig>(r#"quote_assets = []"#).is_err());
+    let config: SymbolFilterConfig = toml::from_str(
+        r#"
+        blacklist = ["LUNAUSDT"]
+        exclude = "^1000"
+        min_quote_volume = 1e6
+        "#,
+    )
+    .unwrap();
+    let filter = config.build().unwrap();
+    assert!(!filter.allows("LUNAUSDT"));
+    assert!(!filter.allows("1000PEPEUSDT"));
+    // 交割合约不以USDT结尾，即使exclude未排除下划线
+    assert!(!filter.allows("BTCUSDT_240329"));
+    // 成交额未知时不过滤
+    assert!(filter.allows("DOGEUSDT"));
+    filter.set_volumes([("BTCUSDT".to_string(), 1e9), ("DOGEUSDT".to_string(), 1e5)]);
+    assert!(filter.allows("BTCUSDT"));
+    assert!(!filter.allows("DOGEUSDT"));
+    assert!(!filter.allows("XRPUSDT"));
+    assert!(filter.allows_with_volume("XRPUSDT", 2e6));
+    filter.set_delisted("BTCUSDT", true);
+    assert!(!filter.allows("BTCUSDT"));
+    filter.set_delisted("BTCUSDT", false);
+    assert!(filter.allows("BTCUSDT"));
+    let whitelist = SymbolFilterConfig {
+        whitelist: vec!["BTCUSDT".to_string()],
+        ..Default::default()
+    }
+    .build()
+    .unwrap();
+    assert!(whitelist.allows("BTCUSDT"));
+    assert!(!whitelist.allows("ETHUSDT"));
+    let bad = SymbolFilterConfig {
+        include: Some("(".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(bad.build(), Err(ConfigError::Invalid(_))));
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    str::FromStr,
+};
+
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    backtest::strategy::TradeRecord,
+    binance_futures::income::{Income, IncomeType},
+    error::DataError,
+};
+
+/// 数量小于该值的批次视为已平完
+const QTY_EPSILON: f64 = 1e-12;
+
+/// 平仓时匹配开仓批次的方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CostMethod {
+    /// 先进先出
+    #[default]
+    Fifo,
+    /// 移动加权平均成本：成本按同方向持仓的加权均价，持有期仍按先进先出匹配实际的开仓时间
+    AverageCost,
+}
+
+impl FromStr for CostMethod {
+    type Err = DataError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fifo" => Ok(Self::Fifo),
+            "average" | "average_cost" => Ok(Self::AverageCost),
+            _ => Err(DataError::parse("cost method", s)),
+        }
+    }
+}
+
+/// 一笔成交，qty带符号（买入为正），fee为支付的手续费
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    /// unix毫秒
+    pub time: u64,
+    pub symbol: String,
+    pub qty: f64,
+    pub price: f64,
+    pub fee: f64,
+}
+
+/// 一笔资金费用，收入为正
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingPayment {
+    pub time: u64,
+    pub symbol: String,
+    pub amount: f64,
+}
+
+impl FundingPayment {
+    /// 资金流水中的FUNDING_FEE
+    pub fn from_incomes(incomes: &[Income]) -> Vec<Self> {
+        incomes
+            .iter()
+            .filter(|i| i.kind() == IncomeType::FundingFee)
+            .map(|i| Self {
+                time: i.time,
+                symbol: i.symbol.clone(),
+                amount: i.income,
+            })
+            .collect()
+    }
+}
+
+/// 已平仓的一个批次；资金费用在支付时即为已实现收益，每笔记为qty为0的单独一行
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealizedLot {
+    pub symbol: String,
+    pub is_long: bool,
+    pub open_time: u64,
+    pub close_time: u64,
+    pub qty: f64,
+    pub entry_price: f64,
+    pub exit_price: f64,
+    /// 开仓和平仓手续费中归属该批次的部分
+    pub fees: f64,
+    /// 资金费用行的金额，收入为正；平仓批次为0
+    pub funding: f64,
+}
+
+impl RealizedLot {
+    /// 资金费用在支付时单独记一行
+    fn funding(payment: &FundingPayment) -> Self {
+        Self {
+            symbol: payment.symbol.clone(),
+            is_long: true,
+            open_time: payment.time,
+            close_time: payment.time,
+            qty: 0.,
+            entry_price: 0.,
+            exit_price: 0.,
+            fees: 0.,
+            funding: payment.amount,
+        }
+    }
+    /// 买入成本，空头为平仓时的买回金额
+    pub fn cost_basis(&self) -> f64 {
+        self.qty
+            * if self.is_long {
+                self.entry_price
+            } else {
+                self.exit_price
+            }
+    }
+    /// 卖出所得，空头为开仓时的卖出金额
+    pub fn proceeds(&self) -> f64 {
+        self.qty
+            * if self.is_long {
+                self.exit_price
+            } else {
+                self.entry_price
+            }
+    }
+    /// 扣除手续费、计入资金费用后的已实现收益
+    pub fn gain(&self) -> f64 {
+        self.proceeds() - self.cost_basis() - self.fees + self.funding
+    }
+}
+
+/// 未平仓的批次，qty带符号
+#[derive(Debug, Clone)]
+struct Lot {
+    open_time: u64,
+    qty: f64,
+    price: f64,
+    fee: f64,
+}
+
+/// 按时间顺序处理成交和资金费用，得到已平仓的批次和资金费用行；期末未平仓的部分不计入
+pub fn realize(method: CostMethod, fills: &[Fill], funding: &[FundingPayment]) -> Vec<RealizedLot> {
+    let mut fills: Vec<&Fill> = fills.iter().collect();
+    fills.sort_by_key(|f| f.time);
+    let mut funding: Vec<&FundingPayment> = funding.iter().collect();
+    funding.sort_by_key(|f| f.time);
+    let mut funding = funding.into_iter().peekable();
+    let mut open: HashMap<&str, VecDeque<Lot>> = HashMap::new();
+    let mut realized = vec![];
+    for fill in fills {
+        // 同一时刻的资金费用排在成交之前
+        while let Some(f) = funding.next_if(|f| f.time <= fill.time) {
+            realized.push(RealizedLot::funding(f));
+        }
+        let lots = open.entry(&fill.symbol).or_default();
+        let mut remaining = fill.qty;
+        while remaining.abs() > QTY_EPSILON {
+            let Some(lot) = lots.front_mut().filter(|l| l.qty * remaining < 0.) else {
+                break;
+            };
+            let take = remaining.abs().min(lot.qty.abs());
+            let fee = lot.fee * take / lot.qty.abs();
+            realized.push(RealizedLot {
+                symbol: fill.symbol.clone(),
+                is_long: lot.qty > 0.,
+                open_time: lot.open_time,
+                close_time: fill.time,
+                qty: take,
+                entry_price: lot.price,
+                exit_price: fill.price,
+                fees: fee + fill.fee * take / fill.qty.abs(),
+                funding: 0.,
+            });
+            lot.fee -= fee;
+            lot.qty -= take * lot.qty.signum();
+            if lot.qty.abs() <= QTY_EPSILON {
+                lots.pop_front();
+            }
+            remaining -= take * remaining.signum();
+        }
+        if remaining.abs() <= QTY_EPSILON {
+            continue;
+        }
+        lots.push_back(Lot {
+            open_time: fill.time,
+            qty: remaining,
+            price: fill.price,
+            fee: fill.fee * remaining.abs() / fill.qty.abs(),
+        });
+        // 剩余的批次方向一致，加仓后都按加权均价计成本
+        if method == CostMethod::AverageCost {
+            let qty: f64 = lots.iter().map(|l| l.qty).sum();
+            let price = lots.iter().map(|l| l.qty * l.price).sum::<f64>() / qty;
+            for lot in lots.iter_mut() {
+                lot.price = price;
+            }
+        }
+    }
+    realized.extend(funding.map(RealizedLot::funding));
+    realized
+}
+
+/// 回测交易记录转为开仓和平仓两笔成交，数量为名义价值 / 开仓价
+pub fn fills_from_trades(symbol: &str, trades: &[TradeRecord]) -> Vec<Fill> {
+    let millis = |t: OffsetDateTime| (t.unix_timestamp_nanos() / 1_000_000) as u64;
+    trades
+        .iter()
+        .flat_map(|t| {
+            let qty = t.margin * t.leverage / t.entry_price;
+            let side = if t.is_bull { 1. } else { -1. };
+            [
+                Fill {
+                    time: millis(t.open_time),
+                    symbol: symbol.to_string(),
+                    qty: qty * side,
+                    price: t.entry_price,
+                    fee: t.entry_fee,
+                },
+                Fill {
+                    time: millis(t.close_time),
+                    symbol: symbol.to_string(),
+                    qty: -qty * side,
+                    price: t.exit_price,
+                    fee: t.exit_fee,
+                },
+            ]
+        })
+        .collect()
+}
+
+const HEADERS: [&str; 12] = [
+    "symbol",
+    "side",
+    "open_time",
+    "close_time",
+    "qty",
+    "entry_price",
+    "exit_price",
+    "cost_basis",
+    "proceeds",
+    "fees",
+    "funding",
+    "gain",
+];
+
+/// 写入已实现批次，时间为RFC3339（UTC）
+pub fn write_tax_lots(path: &Path, lots: &[RealizedLot]) -> Result<(), DataError> {
+    let time = |ms: u64| {
+        OffsetDateTime::from_unix_timestamp_nanos(ms as i128 * 1_000_000)
+            .ok()
+            .and_then(|t| t.format(&Rfc3339).ok())
+            .unwrap_or_default()
+    };
+    let mut csv = csv::Writer::from_path(path)?;
+    csv.write_record(HEADERS)?;
+    for l in lots {
+        let side = match (l.qty > 0., l.is_long) {
+            (false, _) => "funding",
+            (true, true) => "long",
+            (true, false) => "short",
+        };
+        csv.write_record([
+            l.symbol.clone(),
+            side.to_string(),
+            time(l.open_time),
+            time(l.close_time),
+            l.qty.to_string(),
+            l.entry_price.to_string(),
+            l.exit_price.to_string(),
+            l.cost_basis().to_string(),
+            l.proceeds().to_string(),
+            l.fees.to_string(),
+            l.funding.to_string(),
+            l.gain().to_string(),
+        ])?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+#[test]
+fn tax_lots_test() {
+    let fill = |time, qty, price, fee| Fill {
+        time,
+        symbol: "BTCUSDT".to_string(),
+        qty,
+        price,
+        fee,
+    };
+    let fills = [
+        fill(1, 1., 100., 0.1),
+        fill(2, 1., 200., 0.2),
+        fill(4, -1.5, 300., 0.3),
+    ];
+    let funding = [
+        FundingPayment {
+            time: 3,
+            symbol: "BTCUSDT".to_string(),
+            amount: -2.,
+        },
+        FundingPayment {
+            time: 5,
+            symbol: "ETHUSDT".to_string(),
+            amount: 1.,
+        },
+    ];
+    let fifo = realize(CostMethod::Fifo, &fills, &funding);
+    assert_eq!(fifo.len(), 4);
+    // 持仓期间的资金费用在支付时即为一行，不推迟到平仓
+    assert_eq!(
+        (fifo[0].close_time, fifo[0].qty, fifo[0].gain()),
+        (3, 0., -2.)
+    );
+    // 第一批全部平掉，第二批平一半
+    assert_eq!((fifo[1].qty, fifo[1].entry_price), (1., 100.));
+    assert_eq!((fifo[2].qty, fifo[2].entry_price), (0.5, 200.));
+    assert!((fifo[1].fees - 0.3).abs() < 1e-12);
+    assert_eq!(fifo[1].funding, 0.);
+    assert!((fifo[1].gain() - (200. - 0.3)).abs() < 1e-9);
+    // 没有持仓时的资金费用同样单独一行
+    assert_eq!((fifo[3].symbol.as_str(), fifo[3].qty), ("ETHUSDT", 0.));
+
+    let average = realize(CostMethod::AverageCost, &fills, &funding);
+    assert_eq!(average.len(), 4);
+    // 成本为加权均价，持有期按实际的开仓时间
+    assert_eq!((average[1].qty, average[1].entry_price), (1., 150.));
+    assert_eq!((average[2].qty, average[2].entry_price), (0.5, 150.));
+    assert_eq!((average[1].open_time, average[2].open_time), (1, 2));
+    assert!((average[1].cost_basis() + average[2].cost_basis() - 225.).abs() < 1e-9);
+
+    // 反手：空头平仓后剩余部分开多
+    let flip = realize(
+        CostMethod::Fifo,
+        &[
+            fill(1, -1., 100., 0.),
+            fill(2, 2., 90., 0.),
+            fill(3, -1., 95., 0.),
+        ],
+        &[],
+    );
+    assert_eq!(flip.len(), 2);
+    assert!(!flip[0].is_long && (flip[0].gain() - 10.).abs() < 1e-9);
+    assert!(flip[1].is_long && (flip[1].gain() - 5.).abs() < 1e-9);
+
+    let path = std::env::temp_dir().join("hurribot_tax_lots_test.csv");
+    write_tax_lots(&path, &fifo).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with("symbol,side,open_time"));
+    assert!(text.contains("BTCUSDT,long,1970-01-01T00:00:00.001Z"));
+    assert!(text.contains("ETHUSDT,funding"));
+    std::fs::remove_file(&path).ok();
+    assert_eq!(
+        "average".parse::<CostMethod>().unwrap(),
+        CostMethod::AverageCost
+    );
+}
@@ -0,0 +1,142 @@
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::{
+    binance_futures::transfer::{TransferAccount, TransferClient, Wallet},
+    error::{ConfigError, MarketError},
+    symbol::filter::DEFAULT_QUOTE_ASSET,
+};
+
+fn default_asset() -> String {
+    DEFAULT_QUOTE_ASSET.to_string()
+}
+
+fn default_from() -> TransferAccount {
+    TransferAccount::main(Wallet::UsdMFutures)
+}
+
+/// 配置文件中的[treasury]部分：每周将合约钱包中超出保留资金的部分划走
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SweepPolicy {
+    #[serde(default = "default_asset")]
+    pub asset: String,
+    /// 划出的钱包，默认为主账户的U本位合约钱包
+    #[serde(default = "default_from")]
+    pub from: TransferAccount,
+    pub to: TransferAccount,
+    /// 合约钱包保留的资金
+    pub keep: f64,
+    /// 超出部分不少于此值才划转，避免频繁小额划转
+    #[serde(default)]
+    pub min_amount: f64,
+    /// 每周划转的日子（东八区），1为周一
+    pub weekday: u8,
+}
+
+impl SweepPolicy {
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
+        #[derive(Deserialize)]
+        struct File {
+            treasury: SweepPolicy,
+        }
+        let c = std::fs::read_to_string(path)?;
+        let val: File = toml::from_str(&c)?;
+        val.treasury.validate()?;
+        Ok(val.treasury)
+    }
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !(1..=7).contains(&self.weekday) {
+            return Err(ConfigError::Invalid(format!("weekday {}", self.weekday)));
+        }
+        if self.keep < 0. || self.min_amount < 0. {
+            return Err(ConfigError::Invalid(format!(
+                "keep {} min amount {}",
+                self.keep, self.min_amount
+            )));
+        }
+        if self.from == self.to {
+            return Err(ConfigError::Invalid("sweep to the same wallet".to_string()));
+        }
+        Ok(())
+    }
+    pub fn is_due(&self, now: OffsetDateTime) -> bool {
+        now.weekday().number_from_monday() == self.weekday
+    }
+    /// 钱包余额为balance时应划出的数量
+    pub fn amount(&self, balance: f64) -> Option<f64> {
+        let excess = balance - self.keep;
+        (excess > 0. && excess >= self.min_amount).then_some(excess)
+    }
+}
+
+/// 按划转策略在钱包和子账户之间调配资金
+#[derive(Debug)]
+pub struct Treasury {
+    client: TransferClient,
+    policy: SweepPolicy,
+}
+
+impl Treasury {
+    pub fn new(client: TransferClient, policy: SweepPolicy) -> Self {
+        Self { client, policy }
+    }
+    pub fn policy(&self) -> &SweepPolicy {
+        &self.policy
+    }
+    /// 划出钱包余额为balance时按策略划转，返回（数量，划转编号）；无需划转时返回None
+    pub fn sweep(&self, balance: f64) -> Result<Option<(f64, u64)>, MarketError> {
+        let Some(amount) = self.policy.amount(balance) else {
+            return Ok(None);
+        };
+        let id = self.transfer(&self.policy.from, &self.policy.to, amount)?;
+        Ok(Some((amount, id)))
+    }
+    /// 手动划转策略资产
+    pub fn transfer(
+        &self,
+        from: &TransferAccount,
+        to: &TransferAccount,
+        amount: f64,
+    ) -> Result<u64, MarketError> {
+        self.client.transfer(from, to, &self.policy.asset, amount)
+    }
+}
+
+#[test]
+fn sweep_policy_test() {
+    #[derive(Deserialize)]
+    struct File {
+        treasury: SweepPolicy,
+    }
+    let policy = toml::from_str::<File>(
+        r#"
+        [treasury]
+        keep = 1000
+        min_amount = 50
+        weekday = 1
+        to = { wallet = "Spot" }
+        "#,
+    )
+    .unwrap()
+    .treasury;
+    policy.validate().unwrap();
+    assert_eq!(policy.asset, "USDT");
+    assert_eq!(policy.from, TransferAccount::main(Wallet::UsdMFutures));
+    assert_eq!(policy.amount(1200.), Some(200.));
+    assert_eq!(policy.amount(1030.), None);
+    assert_eq!(policy.amount(900.), None);
+    // 1970-01-05为周一
+    let monday = OffsetDateTime::from_unix_timestamp(4 * 86400).unwrap();
+    assert!(policy.is_due(monday));
+    assert!(!policy.is_due(monday + time::Duration::days(1)));
+    let bad = SweepPolicy {
+        weekday: 0,
+        ..policy.clone()
+    };
+    assert!(bad.validate().is_err());
+    let bad = SweepPolicy {
+        to: policy.from.clone(),
+        ..policy
+    };
+    assert!(bad.validate().is_err());
+}
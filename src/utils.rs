@@ -1,92 +1,224 @@
+use serde::Deserialize;
 use time::{macros::offset, OffsetDateTime};
+use tracing::{level_filters::LevelFilter, Level, Subscriber};
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    filter::Targets,
+    fmt::{format::FmtSpan, time::FormatTime},
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    Layer,
+};
 
-pub fn init_log(file_name: &str) -> tracing_appender::non_blocking::WorkerGuard {
-    let file_name = file_name.to_owned() + ".log";
-    use tracing::Level;
-    use tracing_subscriber::fmt::format::FmtSpan;
-    use tracing_subscriber::fmt::time::FormatTime;
-    use tracing_subscriber::FmtSubscriber;
-
-    struct Timer;
-    impl FormatTime for Timer {
-        fn format_time(
-            &self,
-            w: &mut tracing_subscriber::fmt::format::Writer<'_>,
-        ) -> std::fmt::Result {
-            let now = local_now();
-            let format =
-                time::format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second]")
-                    .unwrap();
-            write!(w, "{}", now.format(&format).unwrap())
+use crate::error::ConfigError;
+
+pub mod coalesce;
+pub mod log_files;
+
+/// 日志文件目录
+pub const LOG_DIR: &str = "./logs";
+/// 审计日志的target，如 info!(target: AUDIT_TARGET, ...)
+pub const AUDIT_TARGET: &str = "audit";
+
+/// 日志配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// 过滤指令，如"info,hurribot::market=debug"，设置了环境变量RUST_LOG时以其为准
+    pub filter: String,
+    /// 输出到标准输出
+    pub stdout: bool,
+    /// 按天写入./logs/<file>.<日期>.log，为空时不写文件
+    pub file: Option<String>,
+    /// 审计日志单独写入的文件，设置后不再写入file
+    pub audit_file: Option<String>,
+    /// 压缩之前日期的日志
+    pub compress: bool,
+    /// 日志保留天数，0为永久保留
+    pub retention_days: u32,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            filter: "info".to_string(),
+            stdout: true,
+            file: None,
+            audit_file: None,
+            compress: true,
+            retention_days: 30,
         }
     }
-    let file_appender = tracing_appender::rolling::never("./logs", file_name);
+}
 
-    let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+impl LogConfig {
+    pub fn value_parse(path: &str) -> Result<Self, ConfigError> {
+        let c = std::fs::read_to_string(path)?;
+        let val: Self = toml::from_str(&c)?;
+        val.targets()?;
+        Ok(val)
+    }
+    fn targets(&self) -> Result<Targets, ConfigError> {
+        let filter = std::env::var("RUST_LOG").unwrap_or_else(|_| self.filter.clone());
+        filter
+            .parse()
+            .map_err(|e| ConfigError::Invalid(format!("log filter {:?}: {}", filter, e)))
+    }
+}
+
+/// 持有期间保证日志文件写入，drop时刷新缓冲
+#[must_use]
+#[derive(Debug)]
+pub struct LogGuard {
+    _guards: Vec<WorkerGuard>,
+}
 
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .with_writer(non_blocking)
+struct LocalTimer;
+
+impl FormatTime for LocalTimer {
+    fn format_time(&self, w: &mut tracing_subscriber::fmt::format::Writer<'_>) -> std::fmt::Result {
+        let now = local_now();
+        let format =
+            time::format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second]")
+                .unwrap();
+        write!(w, "{}", now.format(&format).unwrap())
+    }
+}
+
+fn daily_writer(name: &str) -> Result<(NonBlocking, WorkerGuard), ConfigError> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(name)
+        .filename_suffix("log")
+        .build(LOG_DIR)
+        .map_err(|e| ConfigError::Invalid(format!("log file {}: {}", name, e)))?;
+    Ok(tracing_appender::non_blocking(appender))
+}
+
+fn file_layer<S>(writer: NonBlocking) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_subscriber::fmt::layer()
+        .with_writer(writer)
         .with_span_events(FmtSpan::CLOSE)
         .with_file(true)
         .with_line_number(true)
         .with_thread_names(true)
-        .with_timer(Timer)
+        .with_timer(LocalTimer)
         .with_ansi(false)
-        .finish();
+}
 
-    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
-    tracing::info!("tttttt");
-    guard
+/// 按配置初始化全局日志，标准输出、日志文件和审计日志文件可同时开启
+/// 已初始化过时保留原有的日志，不会panic；写文件时需持有返回的guard
+pub fn try_init_log(config: &LogConfig) -> Result<LogGuard, ConfigError> {
+    let targets = config.targets()?;
+    let mut guards = vec![];
+    let stdout = config.stdout.then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_file(true)
+            .with_line_number(true)
+            .with_thread_names(true)
+            .with_timer(LocalTimer)
+            .with_filter(targets.clone())
+    });
+    let file = match &config.file {
+        Some(name) => {
+            let (writer, guard) = daily_writer(name)?;
+            guards.push(guard);
+            let filter = match config.audit_file {
+                Some(_) => targets.clone().with_target(AUDIT_TARGET, LevelFilter::OFF),
+                None => targets.clone(),
+            };
+            Some(file_layer(writer).with_filter(filter))
+        }
+        None => None,
+    };
+    let audit = match &config.audit_file {
+        Some(name) => {
+            let (writer, guard) = daily_writer(name)?;
+            guards.push(guard);
+            Some(
+                file_layer(writer)
+                    .with_filter(Targets::new().with_target(AUDIT_TARGET, Level::TRACE)),
+            )
+        }
+        None => None,
+    };
+    match tracing_subscriber::registry()
+        .with(stdout)
+        .with(file)
+        .with(audit)
+        .try_init()
+    {
+        Ok(_) => {
+            for name in config.file.iter().chain(config.audit_file.iter()) {
+                log_files::spawn_maintenance(
+                    LOG_DIR.into(),
+                    name.clone(),
+                    config.compress,
+                    config.retention_days,
+                );
+            }
+        }
+        Err(e) => tracing::warn!("logger already initialized: {}", e),
+    }
+    Ok(LogGuard { _guards: guards })
+}
+
+/// 只按天写入./logs/<file_name>.<日期>.log
+pub fn init_log(file_name: &str) -> LogGuard {
+    let config = LogConfig {
+        stdout: false,
+        file: Some(file_name.to_owned()),
+        ..Default::default()
+    };
+    try_init_log(&config).unwrap_or(LogGuard { _guards: vec![] })
+}
+
+pub fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
 }
 
 pub fn local_now() -> OffsetDateTime {
     OffsetDateTime::now_utc().to_offset(offset!(+8))
 }
-pub fn file_logger(name: &str) -> tracing_appender::non_blocking::WorkerGuard {
-    let name = if name.is_empty() {
-        "".to_string()
+pub fn file_logger(name: &str) -> LogGuard {
+    if name.is_empty() {
+        init_log("hurribot")
     } else {
-        name.to_string() + "_"
-    };
-    let parse_str = format!(
-        "hurribot_{}[year]-[month]-[day]T[hour]:[minute]:[second]",
-        name
-    );
-    let log_name = local_now()
-        .format(&time::format_description::parse(&parse_str).unwrap())
-        .unwrap();
-    init_log(&log_name)
+        init_log(&format!("hurribot_{}", name))
+    }
 }
 pub fn stdout_logger() {
-    use tracing::Level;
-    use tracing_subscriber::fmt::format::FmtSpan;
-    use tracing_subscriber::fmt::time::FormatTime;
-    use tracing_subscriber::FmtSubscriber;
-    struct Timer;
-    impl FormatTime for Timer {
-        fn format_time(
-            &self,
-            w: &mut tracing_subscriber::fmt::format::Writer<'_>,
-        ) -> std::fmt::Result {
-            let now = local_now();
-            let format =
-                time::format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second]")
-                    .unwrap();
-            write!(w, "{}", now.format(&format).unwrap())
-        }
-    }
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .with_span_events(FmtSpan::CLOSE)
-        .with_file(true)
-        .with_line_number(true)
-        .with_thread_names(true)
-        .with_timer(Timer)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber).unwrap();
+    try_init_log(&LogConfig::default()).ok();
 }
 
 pub fn truncate_step(value: f64, step: f64) -> f64 {
     (value / step).trunc() * step
-}
\ No newline at end of file
+}
+
+#[test]
+fn try_init_log_test() {
+    let config: LogConfig = toml::from_str("filter = \"warn,hurribot::market=debug\"").unwrap();
+    assert!(config.stdout);
+    let _guard = try_init_log(&config).unwrap();
+    // 重复初始化不会panic
+    assert!(try_init_log(&config).is_ok());
+    stdout_logger();
+    if std::env::var("RUST_LOG").is_err() {
+        let invalid = LogConfig {
+            filter: "hurribot=loud".to_string(),
+            ..Default::default()
+        };
+        assert!(try_init_log(&invalid).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crossbeam::channel::{Receiver, Sender, TrySendError};
+use parking_lot::Mutex;
+
+/// 通道统计
+#[derive(Debug, Default)]
+pub struct ChannelStats {
+    /// 已送入通道的消息数
+    pub sent: AtomicU64,
+    /// 通道拥堵时被同键新消息覆盖的消息数
+    pub coalesced: AtomicU64,
+    /// 接收端断开后丢弃的消息数
+    pub dropped: AtomicU64,
+}
+
+impl ChannelStats {
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Relaxed)
+    }
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced.load(Relaxed)
+    }
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Relaxed)
+    }
+}
+
+/// 非阻塞发送的有界通道，作为CoalescingSender的底层通道
+pub trait TrySend<T> {
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>>;
+}
+
+impl<T> TrySend<T> for Sender<T> {
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        Sender::try_send(self, value)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> TrySend<T> for tokio::sync::mpsc::Sender<T> {
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        use tokio::sync::mpsc::error::TrySendError as E;
+        tokio::sync::mpsc::Sender::try_send(self, value).map_err(|e| match e {
+            E::Full(v) => TrySendError::Full(v),
+            E::Closed(v) => TrySendError::Disconnected(v),
+        })
+    }
+}
+
+/// 通道空闲时补发暂存消息的默认间隔
+pub const IDLE_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 有界通道的发送端，通道满时不阻塞，按键暂存最新的消息，下次发送时优先补发；
+/// 用with_idle_flush在没有新消息时也定期补发
+#[derive(Debug)]
+pub struct CoalescingSender<T, K, S = Sender<T>> {
+    shared: Arc<Shared<T, K, S>>,
+}
+
+#[derive(Debug)]
+struct Shared<T, K, S> {
+    tx: S,
+    pending: Mutex<HashMap<K, T>>,
+    key: fn(&T) -> K,
+    stats: Arc<ChannelStats>,
+}
+
+impl<T, K, S> Clone for CoalescingSender<T, K, S> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// capacity为通道容量，key为合并消息的键（如币种）
+pub fn coalescing_channel<T, K: Hash + Eq>(
+    capacity: usize,
+    key: fn(&T) -> K,
+) -> (CoalescingSender<T, K>, Receiver<T>) {
+    let (tx, rx) = crossbeam::channel::bounded(capacity.max(1));
+    (CoalescingSender::new(tx, key), rx)
+}
+
+/// coalescing_channel的tokio版本
+#[cfg(feature = "async")]
+pub fn coalescing_channel_async<T, K: Hash + Eq>(
+    capacity: usize,
+    key: fn(&T) -> K,
+) -> (
+    CoalescingSender<T, K, tokio::sync::mpsc::Sender<T>>,
+    tokio::sync::mpsc::Receiver<T>,
+) {
+    let (tx, rx) = tokio::sync::mpsc::channel(capacity.max(1));
+    (CoalescingSender::new(tx, key), rx)
+}
+
+impl<T, K, S> CoalescingSender<T, K, S> {
+    fn new(tx: S, key: fn(&T) -> K) -> Self {
+        let shared = Shared {
+            tx,
+            pending: Mutex::new(HashMap::new()),
+            key,
+            stats: Arc::new(ChannelStats::default()),
+        };
+        Self {
+            shared: Arc::new(shared),
+        }
+    }
+}
+
+impl<T, K: Hash + Eq + Clone, S: TrySend<T>> CoalescingSender<T, K, S> {
+    /// 接收端断开时返回false
+    pub fn send(&self, value: T) -> bool {
+        let shared = &self.shared;
+        let mut pending = shared.pending.lock();
+        if !shared.flush(&mut pending) {
+            shared.stats.dropped.fetch_add(1, Relaxed);
+            return false;
+        }
+        if !pending.is_empty() {
+            shared.stash(&mut pending, value);
+            return true;
+        }
+        match shared.tx.try_send(value) {
+            Ok(_) => {
+                shared.stats.sent.fetch_add(1, Relaxed);
+                true
+            }
+            Err(TrySendError::Full(value)) => {
+                shared.stash(&mut pending, value);
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                shared.stats.dropped.fetch_add(1, Relaxed);
+                false
+            }
+        }
+    }
+    pub fn stats(&self) -> Arc<ChannelStats> {
+        self.shared.stats.clone()
+    }
+    /// 暂存中的消息数
+    pub fn pending(&self) -> usize {
+        self.shared.pending.lock().len()
+    }
+}
+
+impl<T, K, S> CoalescingSender<T, K, S>
+where
+    T: Send + 'static,
+    K: Hash + Eq + Clone + Send + 'static,
+    S: TrySend<T> + Send + Sync + 'static,
+{
+    /// 启动后台线程每隔interval补发暂存的消息，避免行情停止后最新的消息一直留在暂存中；
+    /// 所有发送端释放或接收端断开后线程退出
+    pub fn with_idle_flush(self, interval: Duration) -> Self {
+        let shared = Arc::downgrade(&self.shared);
+        std::thread::Builder::new()
+            .name("coalesce-flush".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(interval);
+                // 不持有发送端，发送端全部释放后接收端才能收到断开
+                let Some(shared) = shared.upgrade() else {
+                    return;
+                };
+                if !shared.flush(&mut shared.pending.lock()) {
+                    return;
+                }
+            })
+            .unwrap();
+        self
+    }
+    /// with_idle_flush的tokio版本，在当前运行时中以任务补发，不占用线程
+    #[cfg(feature = "async")]
+    pub fn with_idle_flush_async(self, interval: Duration) -> Self {
+        let shared = Arc::downgrade(&self.shared);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(shared) = shared.upgrade() else {
+                    return;
+                };
+                if !shared.flush(&mut shared.pending.lock()) {
+                    return;
+                }
+            }
+        });
+        self
+    }
+}
+
+impl<T, K: Hash + Eq + Clone, S: TrySend<T>> Shared<T, K, S> {
+    fn stash(&self, pending: &mut HashMap<K, T>, value: T) {
+        if pending.insert((self.key)(&value), value).is_some() {
+            self.stats.coalesced.fetch_add(1, Relaxed);
+        }
+    }
+    /// 尽量补发暂存的消息，接收端断开时返回false
+    fn flush(&self, pending: &mut HashMap<K, T>) -> bool {
+        if pending.is_empty() {
+            return true;
+        }
+        let keys: Vec<K> = pending.keys().cloned().collect();
+        for key in keys {
+            let value = pending.remove(&key).unwrap();
+            match self.tx.try_send(value) {
+                Ok(_) => {
+                    self.stats.sent.fetch_add(1, Relaxed);
+                }
+                Err(TrySendError::Full(value)) => {
+                    pending.insert(key, value);
+                    break;
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    let dropped = pending.len() as u64 + 1;
+                    self.stats.dropped.fetch_add(dropped, Relaxed);
+                    pending.clear();
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// 取出通道中已有的消息，每个键只保留最新的一条，按首次出现的顺序返回
+pub fn drain_latest<T, K: Hash + Eq>(first: T, rx: &Receiver<T>, key: fn(&T) -> K) -> Vec<T> {
+    latest(std::iter::once(first).chain(rx.try_iter()), key)
+}
+
+/// 每个键只保留最新的一条，按首次出现的顺序返回
+pub fn latest<T, K: Hash + Eq>(values: impl IntoIterator<Item = T>, key: fn(&T) -> K) -> Vec<T> {
+    let mut latest: Vec<T> = vec![];
+    let mut index: HashMap<K, usize> = HashMap::new();
+    for value in values {
+        match index.get(&key(&value)) {
+            Some(&i) => latest[i] = value,
+            None => {
+                index.insert(key(&value), latest.len());
+                latest.push(value);
+            }
+        }
+    }
+    latest
+}
+
+#[test]
+fn coalescing_channel_test() {
+    let (tx, rx) = coalescing_channel(2, |v: &(&str, i32)| v.0);
+    assert!(tx.send(("a", 1)));
+    assert!(tx.send(("b", 1)));
+    // 通道已满，暂存并按键合并
+    assert!(tx.send(("a", 2)));
+    assert!(tx.send(("a", 3)));
+    assert!(tx.send(("c", 1)));
+    assert_eq!(tx.pending(), 2);
+    let stats = tx.stats();
+    assert_eq!((stats.sent(), stats.coalesced()), (2, 1));
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![("a", 1), ("b", 1)]);
+    // 下次发送时先补发暂存的消息
+    assert!(tx.send(("b", 2)));
+    let received: Vec<_> = rx.try_iter().collect();
+    assert_eq!(received.len(), 2);
+    assert!(received.contains(&("a", 3)) && received.contains(&("c", 1)));
+    assert_eq!(tx.pending(), 1);
+    assert!(tx.send(("b", 3)));
+    let latest = drain_latest(("x", 0), &rx, |v| v.0);
+    assert_eq!(latest, vec![("x", 0), ("b", 3)]);
+    drop(rx);
+    assert!(!tx.send(("d", 1)));
+    assert_eq!(stats.dropped(), 1);
+}
+
+#[test]
+fn coalescing_idle_flush_test() {
+    let (tx, rx) = coalescing_channel(1, |v: &(&str, i32)| v.0);
+    let tx = tx.with_idle_flush(Duration::from_millis(10));
+    assert!(tx.send(("a", 1)));
+    assert!(tx.send(("a", 2)));
+    assert_eq!(tx.pending(), 1);
+    assert_eq!(rx.recv().unwrap(), ("a", 1));
+    // 没有新消息时由后台线程补发
+    let received = rx.recv_timeout(Duration::from_secs(1));
+    assert_eq!(received.unwrap(), ("a", 2));
+    assert_eq!(tx.pending(), 0);
+    // 后台线程不持有发送端
+    drop(tx);
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(1)),
+        Err(crossbeam::channel::RecvTimeoutError::Disconnected)
+    );
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn coalescing_channel_async_test() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let (tx, mut rx) = coalescing_channel_async(1, |v: &(&str, i32)| v.0);
+        let tx = tx.with_idle_flush_async(Duration::from_millis(10));
+        assert!(tx.send(("a", 1)));
+        assert!(tx.send(("a", 2)));
+        assert!(tx.send(("a", 3)));
+        assert_eq!(tx.stats().coalesced(), 1);
+        assert_eq!(rx.recv().await, Some(("a", 1)));
+        // 没有新消息时由补发任务送出
+        assert_eq!(rx.recv().await, Some(("a", 3)));
+        // 补发任务不持有发送端
+        drop(tx);
+        assert_eq!(rx.recv().await, None);
+    });
+}
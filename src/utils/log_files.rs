@@ -0,0 +1,96 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use flate2::{write::GzEncoder, Compression};
+use time::{macros::format_description, Date, OffsetDateTime};
+use tracing::{error, info};
+
+/// 按天滚动的日志文件名为 <prefix>.<YYYY-MM-DD>.log（UTC日期），压缩后为 .log.gz
+/// 压缩今天之前的日志，删除超过保留天数的日志
+pub fn maintain(dir: &Path, prefix: &str, compress: bool, retention_days: u32) {
+    let today = OffsetDateTime::now_utc().date();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+        let Some((date, compressed)) = parse_name(&path, prefix) else {
+            continue;
+        };
+        let age = (today - date).whole_days();
+        if retention_days > 0 && age >= retention_days as i64 {
+            match std::fs::remove_file(&path) {
+                Ok(_) => info!("Removed expired log {}", path.display()),
+                Err(e) => error!("Remove log {} failed: {}", path.display(), e),
+            }
+        } else if compress && !compressed && age > 0 {
+            if let Err(e) = gzip(&path) {
+                error!("Compress log {} failed: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// 后台线程每小时整理一次日志目录
+pub fn spawn_maintenance(dir: PathBuf, prefix: String, compress: bool, retention_days: u32) {
+    std::thread::Builder::new()
+        .name(format!("log-maintenance-{}", prefix))
+        .spawn(move || loop {
+            maintain(&dir, &prefix, compress, retention_days);
+            std::thread::sleep(Duration::from_secs(3600));
+        })
+        .ok();
+}
+
+/// 返回（日期，是否已压缩）
+fn parse_name(path: &Path, prefix: &str) -> Option<(Date, bool)> {
+    let name = path.file_name()?.to_str()?;
+    let rest = name.strip_prefix(prefix)?.strip_prefix('.')?;
+    let (date, compressed) = match rest.strip_suffix(".log.gz") {
+        Some(date) => (date, true),
+        None => (rest.strip_suffix(".log")?, false),
+    };
+    let date = Date::parse(date, format_description!("[year]-[month]-[day]")).ok()?;
+    Some((date, compressed))
+}
+
+fn gzip(path: &Path) -> std::io::Result<()> {
+    let mut target = path.as_os_str().to_owned();
+    target.push(".gz");
+    let mut source = File::open(path)?;
+    let mut encoder = GzEncoder::new(
+        BufWriter::new(File::create(&target)?),
+        Compression::default(),
+    );
+    std::io::copy(&mut source, &mut encoder)?;
+    encoder.finish()?;
+    std::fs::remove_file(path)
+}
+
+#[test]
+fn log_maintenance_test() {
+    let dir = std::env::temp_dir().join("hurribot_log_files_test");
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let today = OffsetDateTime::now_utc().date();
+    let name = |days: i64| {
+        let date = (today - time::Duration::days(days))
+            .format(format_description!("[year]-[month]-[day]"))
+            .unwrap();
+        format!("main.{}.log", date)
+    };
+    for days in [0, 1, 40] {
+        std::fs::write(dir.join(name(days)), "log line\n").unwrap();
+    }
+    std::fs::write(dir.join("other.log"), "").unwrap();
+    maintain(&dir, "main", true, 30);
+    assert!(dir.join(name(0)).exists());
+    assert!(!dir.join(name(1)).exists());
+    assert!(dir.join(name(1) + ".gz").exists());
+    assert!(!dir.join(name(40)).exists());
+    assert!(!dir.join(name(40) + ".gz").exists());
+    assert!(dir.join("other.log").exists());
+}
@@ -23,7 +23,7 @@ fn strategy_from_csv() {
         )
         .unwrap();
     let _logger_guard = init_log(&log_name);
-    let chart = CandleChart::read_from_csv("./data/BTCUSDT", Duration::minutes(1));
+    let chart = CandleChart::read_from_csv("./data/BTCUSDT", Duration::minutes(1)).unwrap();
     let total_capital = Arc::new(Mutex::new(1000000.));
     let ratio = 1.;
     let leverage = 10.;
@@ -48,7 +48,7 @@ fn strategy_from_csv() {
                 strategy.value() / strategy.cost
             );
         }
-        strategy.update(candle);
+        strategy.update(&candle);
     }
     strategy.close(chart.candles.last().unwrap().close);
     let ret = strategy.value() / strategy.cost;
@@ -57,3 +57,43 @@ fn strategy_from_csv() {
         strategy.cost, strategy.value(), ret, strategy.open_count
     );
 }
+
+#[test]
+fn strategy_from_header_csv() {
+    // 带表头的币安导出格式，按列名识别
+    let path = std::env::temp_dir().join("hurribot_strategy_header.csv");
+    let mut csv = String::from(
+        "open_time,open,high,low,close,volume,close_time,quote_volume,count,taker_buy_volume,taker_buy_quote_volume,ignore\n",
+    );
+    for i in 0..120_i64 {
+        let open_time = 1704067200000 + i * 60_000;
+        let close = 100. + (i % 10) as f64;
+        csv.push_str(&format!(
+            "{},{},{},{},{},10,{},0,0,0,0,0\n",
+            open_time,
+            close,
+            close + 1.,
+            close - 1.,
+            close,
+            open_time + 59_999
+        ));
+    }
+    std::fs::write(&path, csv).unwrap();
+    let chart = CandleChart::read_from_csv(path.to_str().unwrap(), Duration::minutes(1)).unwrap();
+    assert_eq!(chart.candles.len(), 120);
+    let mut strategy = GeoStrategy::new(
+        true,
+        10.,
+        1.,
+        Duration::minutes(60),
+        10.,
+        0.03,
+        0.002,
+        Arc::new(Mutex::new(1000.)),
+    );
+    for candle in chart.candles.iter() {
+        strategy.update(&candle);
+    }
+    strategy.close(chart.candles.last().unwrap().close);
+    assert!(strategy.value().is_finite());
+}